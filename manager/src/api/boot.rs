@@ -78,6 +78,7 @@ pub async fn handle_local_boot_request(mac_addr: &str) -> Result<(), anyhow::Err
     let machine = Machine::new(&MachineCreationReqType::LocalPhysical(
         LocalMachineProviderPhysicalConfig {
             mac_address: mac_addr.to_string(),
+            ..Default::default()
         },
     ))
     .await?;
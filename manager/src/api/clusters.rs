@@ -16,7 +16,7 @@ use crate::{
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        WebSocketUpgrade,
+        Path, WebSocketUpgrade,
     },
     response::IntoResponse,
     Json,
@@ -132,6 +132,193 @@ pub async fn signal_cluster(
     })
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ClusterNodeAddReqBody {
+    /// Inventory machine to join
+    pub machine_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/clusters/{id}/nodes",
+    params(("id" = String, Path, description = "Cluster id")),
+    request_body = ClusterNodeAddReqBody,
+    responses(
+        (status = 200, description = "Started joining the machine, progress is reported in the cluster status", body = ApiResponse<EmptyApiResponse>),
+    )
+)]
+pub async fn add_cluster_node(
+    Path(cluster_id): Path<String>,
+    Json(req): Json<ClusterNodeAddReqBody>,
+) -> Json<ApiResponse<()>> {
+    match Cluster::start_node_join(&cluster_id, &req.machine_id).await {
+        Ok(_) => Json(ApiResponse {
+            message: "Node join started".to_string(),
+            status: ApiResponseStatus::Success,
+            data: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            message: format!("Failed to add node: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        }),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/clusters/{id}/nodes/{machine_id}",
+    params(
+        ("id" = String, Path, description = "Cluster id"),
+        ("machine_id" = String, Path, description = "Machine of the node to remove"),
+    ),
+    responses(
+        (status = 200, description = "Started removing the node, progress is reported in the cluster status", body = ApiResponse<EmptyApiResponse>),
+    )
+)]
+pub async fn remove_cluster_node(
+    Path((cluster_id, machine_id)): Path<(String, String)>,
+) -> Json<ApiResponse<()>> {
+    match Cluster::start_node_removal(&cluster_id, &machine_id).await {
+        Ok(_) => Json(ApiResponse {
+            message: "Node removal started".to_string(),
+            status: ApiResponseStatus::Success,
+            data: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            message: format!("Failed to remove node: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        }),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ClusterUpgradeReqBody {
+    /// k3s version to upgrade to, e.g. `v1.31.1+k3s1`, with the configured
+    /// OS and Kairos version
+    pub k3s_version: Option<String>,
+    /// Kairos image to upgrade to, takes precedence over `k3s_version`
+    pub os_image: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/clusters/{id}/upgrade",
+    params(("id" = String, Path, description = "Cluster id")),
+    request_body = ClusterUpgradeReqBody,
+    responses(
+        (status = 200, description = "Started or resumed the upgrade, progress is reported in the cluster status", body = ApiResponse<EmptyApiResponse>),
+    )
+)]
+pub async fn upgrade_cluster(
+    Path(cluster_id): Path<String>,
+    Json(req): Json<ClusterUpgradeReqBody>,
+) -> Json<ApiResponse<()>> {
+    match Cluster::start_upgrade(
+        &cluster_id,
+        req.k3s_version.as_deref(),
+        req.os_image.as_deref(),
+    )
+    .await
+    {
+        Ok(_) => Json(ApiResponse {
+            message: "Upgrade started".to_string(),
+            status: ApiResponseStatus::Success,
+            data: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            message: format!("Failed to upgrade cluster: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        }),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ClusterBackupNodeAddReqBody {
+    /// Inventory machine to store the snapshots on
+    pub machine_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/clusters/{id}/backup_nodes",
+    params(("id" = String, Path, description = "Cluster id")),
+    request_body = ClusterBackupNodeAddReqBody,
+    responses(
+        (status = 200, description = "Set up the machine as a backup node", body = ApiResponse<EmptyApiResponse>),
+    )
+)]
+pub async fn add_cluster_backup_node(
+    Path(cluster_id): Path<String>,
+    Json(req): Json<ClusterBackupNodeAddReqBody>,
+) -> Json<ApiResponse<()>> {
+    match Cluster::add_backup_node(&cluster_id, &req.machine_id).await {
+        Ok(_) => Json(ApiResponse {
+            message: "Backup node added".to_string(),
+            status: ApiResponseStatus::Success,
+            data: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            message: format!("Failed to add backup node: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        }),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/clusters/{id}/backups",
+    params(("id" = String, Path, description = "Cluster id")),
+    responses(
+        (status = 200, description = "Took the snapshots and stored them on the backup nodes", body = ApiResponse<EmptyApiResponse>),
+    )
+)]
+pub async fn backup_cluster(Path(cluster_id): Path<String>) -> Json<ApiResponse<()>> {
+    match Cluster::backup(&cluster_id).await {
+        Ok(_) => Json(ApiResponse {
+            message: "Cluster backed up".to_string(),
+            status: ApiResponseStatus::Success,
+            data: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            message: format!("Failed to back up cluster: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        }),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/clusters/{id}/backups/{snapshot}/restore",
+    params(
+        ("id" = String, Path, description = "Cluster id"),
+        ("snapshot" = String, Path, description = "Name of the snapshot to restore"),
+    ),
+    responses(
+        (status = 200, description = "Started the restore, progress is reported in the cluster status", body = ApiResponse<EmptyApiResponse>),
+    )
+)]
+pub async fn restore_cluster_snapshot(
+    Path((cluster_id, snapshot)): Path<(String, String)>,
+) -> Json<ApiResponse<()>> {
+    match Cluster::start_restore(&cluster_id, &snapshot).await {
+        Ok(_) => Json(ApiResponse {
+            message: "Restore started".to_string(),
+            status: ApiResponseStatus::Success,
+            data: None,
+        }),
+        Err(e) => Json(ApiResponse {
+            message: format!("Failed to restore snapshot: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        }),
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/api/clusters/dev_create_from_all_machines_in_inventory",
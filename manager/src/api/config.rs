@@ -1,5 +1,6 @@
 use crate::{
     config::ManagerConfig,
+    config_store::{list_history, read_version, ConfigVersion},
     get_current_config_cloned,
     types::{ApiResponse, ApiResponseStatus, EmptyApiResponse},
     write_config,
@@ -7,12 +8,14 @@ use crate::{
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        WebSocketUpgrade,
+        Path, WebSocketUpgrade,
     },
     response::IntoResponse,
     Json,
 };
+use serde::{Deserialize, Serialize};
 use tracing::info;
+use utoipa::ToSchema;
 
 #[utoipa::path(
     put,
@@ -69,3 +72,72 @@ pub async fn handle_get_config(mut socket: WebSocket) {
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ConfigHistoryResBody {
+    /// Stored versions, oldest first.
+    pub versions: Vec<ConfigVersion>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/config/history",
+    responses(
+        (status = 200, description = "Lists the stored versions of the config", body = ApiResponse<ConfigHistoryResBody>),
+    )
+)]
+pub async fn get_config_history() -> Json<ApiResponse<ConfigHistoryResBody>> {
+    match list_history().await {
+        Ok(versions) => Json(ApiResponse {
+            message: "Got config history".to_string(),
+            status: ApiResponseStatus::Success,
+            data: Some(ConfigHistoryResBody { versions }),
+        }),
+        Err(e) => Json(ApiResponse {
+            message: format!("Failed to list config history: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        }),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/config/history/{version}/restore",
+    params(("version" = u64, Path, description = "Version to restore")),
+    responses(
+        (status = 200, description = "Replaces the config with a stored version, which is saved again as the newest version", body = ApiResponse<EmptyApiResponse>),
+    )
+)]
+pub async fn restore_config_version(Path(version): Path<u64>) -> Json<ApiResponse<()>> {
+    let restored = match read_version(version).await {
+        Ok(restored) => restored,
+        Err(e) => {
+            return Json(ApiResponse {
+                message: format!("Failed to read config version {}: {}", version, e),
+                status: ApiResponseStatus::Error,
+                data: None,
+            })
+        }
+    };
+
+    let mut config = write_config!();
+
+    *config = restored;
+
+    if let Err(e) = config.apply_environment().await {
+        return Json(ApiResponse {
+            message: format!("Failed to apply environment: {}", e),
+            status: ApiResponseStatus::Error,
+            data: None,
+        });
+    }
+
+    info!("Config restored from version {}", version);
+
+    Json(ApiResponse {
+        message: format!("Config restored from version {}", version),
+        status: ApiResponseStatus::Success,
+        data: None,
+    })
+}
@@ -37,11 +37,19 @@ pub fn build_api_router() -> OpenApiRouter {
         .routes(routes!(crate::api::clusters::dev_install_cluster_basics))
         .routes(routes!(crate::api::clusters::get_cluster_status))
         .routes(routes!(crate::api::clusters::signal_cluster))
+        .routes(routes!(crate::api::clusters::add_cluster_node))
+        .routes(routes!(crate::api::clusters::remove_cluster_node))
+        .routes(routes!(crate::api::clusters::upgrade_cluster))
+        .routes(routes!(crate::api::clusters::add_cluster_backup_node))
+        .routes(routes!(crate::api::clusters::backup_cluster))
+        .routes(routes!(crate::api::clusters::restore_cluster_snapshot))
         // health
         .routes(routes!(crate::api::health::get_health))
         // config
         .routes(routes!(crate::api::config::update_config))
         .routes(routes!(crate::api::config::get_config))
+        .routes(routes!(crate::api::config::get_config_history))
+        .routes(routes!(crate::api::config::restore_config_version))
         .routes(routes!(create_public_ip))
         .routes(routes!(get_boot_config_by_mac))
         .routes(routes!(direct_terminal))
@@ -0,0 +1,1169 @@
+//! Snapshots of the k3s datastore and of vault, stored on the backup nodes
+//! of a cluster.
+//!
+//! The primary node takes an etcd snapshot, or an online backup of the
+//! sqlite database on single server clusters, and vault a raft snapshot when
+//! it uses raft storage. The manager encrypts them to the config store
+//! identity and the `backup.recipients` and uploads them to every backup node
+//! through a WireGuard tunnel between the manager and the backup nodes,
+//! `cluster_backup_wg_private_key` being the manager's end. Each cluster's
+//! tunnel is a `10.98.<subnet>.0/24` of its own, with the manager at `.1`.
+//! Backup nodes are set up like the public ip proxies: Debian machines
+//! reached as root.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::Ipv4Addr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{BackupNode, Cluster, ClusterNode, Machine, ManagerConfig},
+    config_store::identity_path,
+    get_current_config_cloned,
+    internal_config::INTERNAL_CONFIG,
+    public_ip::generate_wg_keys,
+    some_or_bail,
+    utils::cmd,
+    write_config,
+};
+
+use super::secrets::ClusterSecrets;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const WG_LISTEN_PORT: u16 = 55108;
+/// Interface on the backup nodes
+const WG_REMOTE_INTERFACE: &str = "mowsbackup";
+const K3S_DB_DIR: &str = "/var/lib/rancher/k3s/server/db";
+/// Where the primary node takes a datastore snapshot for the download
+const SNAPSHOT_DIR: &str = "/tmp/mows-snapshot";
+const SNAPSHOT_PATH: &str = "/tmp/mows-snapshot/snapshot";
+/// Where a snapshot is put on the primary node for a restore
+const RESTORE_PATH: &str = "/tmp/mows-restore";
+/// The k3s token for an etcd restore, kept off the command line
+const RESTORE_TOKEN_PATH: &str = "/tmp/mows-restore-token";
+/// From the restored datastore to a running k3s, 15min
+const RESTORE_READY_POLLS: u32 = 90;
+
+/// Snapshots of a cluster and how the last backup went
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, Default, PartialEq)]
+pub struct ClusterBackups {
+    pub snapshots: Vec<ClusterSnapshot>,
+    /// Unix seconds of the last backup, successful or not
+    pub last_attempt_at: Option<u64>,
+    /// Why the last backup failed
+    pub last_error: Option<String>,
+    pub restore: Option<SnapshotRestore>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct ClusterSnapshot {
+    pub name: String,
+    pub kind: ClusterSnapshotKind,
+    /// Unix seconds
+    pub created_at: u64,
+    /// Encrypted size in bytes
+    pub size: u64,
+    /// Machine ids of the backup nodes holding the snapshot
+    pub backup_nodes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, ToSchema, PartialEq)]
+pub enum ClusterSnapshotKind {
+    Etcd,
+    Sqlite,
+    Vault,
+}
+
+/// A restore in progress, or the failed last one
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct SnapshotRestore {
+    pub snapshot: String,
+    pub error: Option<String>,
+}
+
+impl ClusterSnapshotKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ClusterSnapshotKind::Etcd => "etcd",
+            ClusterSnapshotKind::Sqlite => "sqlite",
+            ClusterSnapshotKind::Vault => "vault",
+        }
+    }
+}
+
+impl Cluster {
+    /// Make a machine a backup node of the cluster: set up its end of the
+    /// WireGuard tunnel and the snapshot directory
+    pub async fn add_backup_node(cluster_id: &str, machine_id: &str) -> anyhow::Result<()> {
+        let config = get_current_config_cloned!();
+
+        let machine = some_or_bail!(config.get_machine_by_id(machine_id), "Machine not found");
+        let cluster = some_or_bail!(config.clusters.get(cluster_id), "Cluster not found");
+
+        if cluster.backup_nodes.contains_key(machine_id) {
+            bail!("Machine is already a backup node of this cluster");
+        }
+
+        if config.clusters.values().any(|cluster| {
+            cluster
+                .cluster_nodes
+                .values()
+                .any(|node| node.machine_id == machine_id)
+        }) {
+            bail!("Machine is a cluster node");
+        }
+
+        let keys = generate_wg_keys()
+            .await
+            .context("Failed to generate WireGuard keys for the backup node")?;
+
+        // all backup nodes of a cluster share the manager's end of the tunnel
+        let cluster_private_key = cluster
+            .cluster_backup_wg_private_key
+            .clone()
+            .unwrap_or(keys.local_wg_private_key);
+
+        let tunnel_ip = free_tunnel_ip(&config, cluster_id)?;
+
+        let remote_wg_config = format!(
+            "[Interface]\nPrivateKey = {}\nAddress = {}/32\nListenPort = {}\n\n[Peer]\nPublicKey = {}\nAllowedIPs = {}/32\n",
+            keys.remote_wg_private_key,
+            tunnel_ip,
+            WG_LISTEN_PORT,
+            wg_public_key(&cluster_private_key).await?,
+            manager_tunnel_ip(tunnel_ip)
+        );
+
+        let commands = vec![
+            "apt-get update".to_string(),
+            "apt-get install -y wireguard".to_string(),
+            format!(
+                "cat <<EOF > /etc/wireguard/{}.conf\n{}EOF\n",
+                WG_REMOTE_INTERFACE, remote_wg_config
+            ),
+            format!("systemctl enable --now wg-quick@{}", WG_REMOTE_INTERFACE),
+            format!("systemctl restart wg-quick@{}", WG_REMOTE_INTERFACE),
+            format!(
+                "mkdir -p {}/{}",
+                INTERNAL_CONFIG.backup.remote_dir, cluster_id
+            ),
+        ];
+
+        for command in &commands {
+            machine.exec(command, 60).await.context(format!(
+                "Failed to set up the backup node. Failed at command: {}",
+                command.lines().next().unwrap_or_default()
+            ))?;
+        }
+
+        let mut config = write_config!();
+
+        let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+
+        cluster.cluster_backup_wg_private_key = Some(cluster_private_key);
+        cluster.backup_nodes.insert(
+            machine_id.to_string(),
+            BackupNode {
+                machine_id: machine_id.to_string(),
+                hostname: machine
+                    .public_ip
+                    .map(|ip| format!("[{}]", ip))
+                    .or(machine.public_legacy_ip.map(|ip| ip.to_string()))
+                    .unwrap_or(machine.ssh.address(&machine)),
+                mac: machine.mac.clone().unwrap_or_default(),
+                ssh: machine.ssh.clone(),
+                backup_wg_private_key: Some(keys.remote_wg_private_key),
+                tunnel_ip: Some(tunnel_ip),
+            },
+        );
+
+        info!(
+            "Machine {} is a backup node of cluster {}",
+            machine_id, cluster_id
+        );
+
+        Ok(())
+    }
+
+    /// Take the snapshots of a cluster, store them on its backup nodes and
+    /// apply the retention rules. The outcome is recorded in `backups`.
+    pub async fn backup(cluster_id: &str) -> anyhow::Result<()> {
+        let _tunnel = lock_tunnel(cluster_id)?;
+
+        let result = Self::run_backup(cluster_id).await;
+
+        let mut config = write_config!();
+
+        if let Some(cluster) = config.clusters.get_mut(cluster_id) {
+            cluster.backups.last_attempt_at = Some(now()?);
+            cluster.backups.last_error = result.as_ref().err().map(|e| format!("{:#}", e));
+        }
+
+        result
+    }
+
+    async fn run_backup(cluster_id: &str) -> anyhow::Result<()> {
+        let cluster = Self::get_by_id(cluster_id).await?;
+
+        if cluster.backup_nodes.is_empty() {
+            bail!("Cluster has no backup nodes");
+        }
+
+        cluster.bring_up_backup_tunnel().await?;
+
+        let created_at = now()?;
+        let work_dir = work_dir().await?;
+
+        let datastore_path = work_dir.join(format!("{}-datastore", cluster.id));
+        let mut snapshots = vec![(
+            cluster.datastore_snapshot(&datastore_path).await?,
+            datastore_path,
+        )];
+
+        if cluster.vault_secrets.is_some() {
+            if let Some(snapshot) = ClusterSecrets::raft_snapshot(&cluster).await? {
+                let vault_path = work_dir.join(format!("{}-vault", cluster.id));
+                tokio::fs::write(&vault_path, snapshot).await?;
+                snapshots.push((ClusterSnapshotKind::Vault, vault_path));
+            }
+        }
+
+        for (kind, plain_path) in snapshots {
+            let name = format!("{}-{}-{}.age", cluster.id, kind.as_str(), created_at);
+            let local_path = work_dir.join(&name);
+
+            let encrypted = age(&encrypt_args(), &plain_path, &local_path).await;
+            tokio::fs::remove_file(&plain_path).await?;
+            if let Err(e) = encrypted {
+                let _ = tokio::fs::remove_file(&local_path).await;
+                return Err(e);
+            }
+            let size = tokio::fs::metadata(&local_path).await?.len();
+
+            let mut stored_on = Vec::new();
+            for backup_node in cluster.backup_nodes.values() {
+                match cluster
+                    .upload_snapshot(backup_node, &local_path, &name)
+                    .await
+                {
+                    Ok(()) => stored_on.push(backup_node.machine_id.clone()),
+                    Err(e) => warn!(
+                        "Could not store {} on backup node {}: {:?}",
+                        name, backup_node.machine_id, e
+                    ),
+                }
+            }
+
+            tokio::fs::remove_file(&local_path).await?;
+
+            if stored_on.is_empty() {
+                bail!("Snapshot {} could not be stored on any backup node", name);
+            }
+
+            debug!("Stored snapshot {} on {:?}", name, stored_on);
+
+            let mut config = write_config!();
+            let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+            cluster.backups.snapshots.push(ClusterSnapshot {
+                name,
+                kind,
+                created_at,
+                size,
+                backup_nodes: stored_on,
+            });
+        }
+
+        Self::prune_snapshots(cluster_id).await?;
+
+        info!("Backed up cluster {}", cluster_id);
+
+        Ok(())
+    }
+
+    /// Etcd snapshot, or a copy of the sqlite database, taken on the primary
+    /// and downloaded to `local_path`. The database is copied with the sqlite3
+    /// backup API, as files copied while k3s writes don't match its WAL;
+    /// without the sqlite3 CLI on the node the snapshot fails rather than
+    /// stopping k3s for a copy.
+    async fn datastore_snapshot(&self, local_path: &Path) -> anyhow::Result<ClusterSnapshotKind> {
+        let machine = self.primary_machine().await?;
+
+        let datastore = machine
+            .exec(
+                &format!(
+                    "sudo sh -c 'if test -d {}/etcd; then echo etcd; elif command -v sqlite3 >/dev/null; then echo sqlite; else echo sqlite-without-cli; fi'",
+                    K3S_DB_DIR
+                ),
+                10,
+            )
+            .await?;
+
+        let (kind, take) = match datastore.trim() {
+            "etcd" => (
+                ClusterSnapshotKind::Etcd,
+                format!(
+                    "k3s etcd-snapshot save --dir {dir} --name mows >/dev/null 2>&1 && mv {dir}/mows-* {}",
+                    SNAPSHOT_PATH,
+                    dir = SNAPSHOT_DIR
+                ),
+            ),
+            "sqlite" => (
+                ClusterSnapshotKind::Sqlite,
+                format!(
+                    "sqlite3 {db}/state.db \".backup {dir}/state.db\" && tar -C {dir} -cf {} state.db",
+                    SNAPSHOT_PATH,
+                    dir = SNAPSHOT_DIR,
+                    db = K3S_DB_DIR
+                ),
+            ),
+            _ => bail!(
+                "The primary node has no sqlite3, which snapshots of a cluster without etcd need"
+            ),
+        };
+
+        // handed to the ssh user for the download
+        let taken = machine
+            .exec(
+                &format!(
+                    "sudo sh -c 'rm -rf {dir} && mkdir -m 700 {dir} && {} && chown -R {} {dir}'",
+                    take,
+                    machine.ssh.ssh_username,
+                    dir = SNAPSHOT_DIR
+                ),
+                120,
+            )
+            .await
+            .context("Failed to take datastore snapshot");
+
+        let downloaded = match taken {
+            Ok(_) => {
+                machine
+                    .ssh
+                    .download_at(
+                        &machine,
+                        &machine.ssh.address(&machine),
+                        SNAPSHOT_PATH,
+                        local_path,
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = machine
+            .exec(&format!("sudo rm -rf {}", SNAPSHOT_DIR), 10)
+            .await
+        {
+            warn!("Could not clean up the snapshot on {}: {:?}", machine.id, e);
+        }
+
+        if let Err(e) = downloaded {
+            let _ = tokio::fs::remove_file(local_path).await;
+            return Err(e);
+        }
+
+        Ok(kind)
+    }
+
+    /// Drop the snapshots that fall out of the retention rules, from the
+    /// backup nodes and the config
+    async fn prune_snapshots(cluster_id: &str) -> anyhow::Result<()> {
+        let cluster = Self::get_by_id(cluster_id).await?;
+        let backup_config = &INTERNAL_CONFIG.backup;
+
+        let mut pruned = Vec::new();
+        for kind in [
+            ClusterSnapshotKind::Etcd,
+            ClusterSnapshotKind::Sqlite,
+            ClusterSnapshotKind::Vault,
+        ] {
+            let of_kind = cluster
+                .backups
+                .snapshots
+                .iter()
+                .filter(|snapshot| snapshot.kind == kind)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            pruned.extend(snapshots_to_prune(
+                &of_kind,
+                backup_config.keep_last,
+                backup_config.keep_daily,
+            ));
+        }
+
+        for snapshot in cluster
+            .backups
+            .snapshots
+            .iter()
+            .filter(|snapshot| pruned.contains(&snapshot.name))
+        {
+            for machine_id in &snapshot.backup_nodes {
+                let Some(backup_node) = cluster.backup_nodes.get(machine_id) else {
+                    continue;
+                };
+                if let Err(e) = cluster
+                    .exec_on_backup_node(
+                        backup_node,
+                        &format!("rm -f {}", remote_path(cluster_id, &snapshot.name)),
+                    )
+                    .await
+                {
+                    warn!(
+                        "Could not delete {} from backup node {}: {:?}",
+                        snapshot.name, machine_id, e
+                    );
+                }
+            }
+            debug!("Pruned snapshot {}", snapshot.name);
+        }
+
+        let mut config = write_config!();
+        let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+        cluster
+            .backups
+            .snapshots
+            .retain(|snapshot| !pruned.contains(&snapshot.name));
+
+        Ok(())
+    }
+
+    /// Rebuild the control plane from a datastore snapshot, or restore vault
+    /// from a vault snapshot. Returns once the restore is recorded, the rest
+    /// runs in the background.
+    pub async fn start_restore(cluster_id: &str, snapshot_name: &str) -> anyhow::Result<()> {
+        let tunnel = lock_tunnel(cluster_id)?;
+
+        let mut config = write_config!();
+
+        let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+
+        if cluster
+            .backups
+            .restore
+            .as_ref()
+            .is_some_and(|restore| restore.error.is_none())
+        {
+            bail!("A restore is already running");
+        }
+
+        // a paused upgrade may need a restore to recover from
+        if cluster
+            .upgrade
+            .as_ref()
+            .is_some_and(|upgrade| upgrade.error.is_none())
+        {
+            bail!("Cluster is being upgraded");
+        }
+
+        if cluster
+            .node_operations
+            .values()
+            .any(|operation| operation.error.is_none())
+        {
+            bail!("Wait for the running node operations to finish");
+        }
+
+        let snapshot = some_or_bail!(
+            cluster
+                .backups
+                .snapshots
+                .iter()
+                .find(|snapshot| snapshot.name == snapshot_name)
+                .cloned(),
+            "Snapshot not found"
+        );
+
+        cluster.backups.restore = Some(SnapshotRestore {
+            snapshot: snapshot.name.clone(),
+            error: None,
+        });
+
+        drop(config);
+
+        let cluster_id = cluster_id.to_string();
+        tokio::spawn(async move {
+            let result = Self::run_restore(&cluster_id, &snapshot).await;
+            Self::finish_restore(&cluster_id, result).await;
+            drop(tunnel);
+        });
+
+        Ok(())
+    }
+
+    async fn run_restore(cluster_id: &str, snapshot: &ClusterSnapshot) -> anyhow::Result<()> {
+        let cluster = Self::get_by_id(cluster_id).await?;
+
+        info!("Restoring cluster {} from {}", cluster_id, snapshot.name);
+
+        cluster.bring_up_backup_tunnel().await?;
+
+        let work_dir = work_dir().await?;
+        let encrypted_path = work_dir.join(&snapshot.name);
+        let plain_path = work_dir.join("restore");
+
+        cluster.download_snapshot(snapshot, &encrypted_path).await?;
+        let decrypted = age(&["--decrypt"], &encrypted_path, &plain_path).await;
+        tokio::fs::remove_file(&encrypted_path).await?;
+        if let Err(e) = decrypted {
+            let _ = tokio::fs::remove_file(&plain_path).await;
+            return Err(e);
+        }
+
+        let result = match snapshot.kind {
+            ClusterSnapshotKind::Vault => match tokio::fs::read(&plain_path).await {
+                Ok(data) => ClusterSecrets::restore_raft_snapshot(&cluster, data).await,
+                Err(e) => Err(e.into()),
+            },
+            ClusterSnapshotKind::Etcd | ClusterSnapshotKind::Sqlite => {
+                cluster.restore_datastore(snapshot.kind, &plain_path).await
+            }
+        };
+        tokio::fs::remove_file(&plain_path).await?;
+
+        result
+    }
+
+    /// Stop k3s everywhere, restore the datastore on the primary and start
+    /// it from there; the other servers rejoin with an empty datastore
+    async fn restore_datastore(
+        &self,
+        kind: ClusterSnapshotKind,
+        local_path: &Path,
+    ) -> anyhow::Result<()> {
+        let (primary_hostname, primary) = some_or_bail!(
+            self.cluster_nodes.iter().find(|(_, node)| node.primary),
+            "Cluster has no primary node"
+        );
+        let primary_machine = self.primary_machine().await?;
+
+        primary_machine
+            .ssh
+            .upload_at(
+                &primary_machine,
+                &primary_machine.ssh.address(&primary_machine),
+                local_path,
+                RESTORE_PATH,
+            )
+            .await?;
+
+        if kind == ClusterSnapshotKind::Etcd {
+            let token_path = work_dir().await?.join("restore-token");
+            tokio::fs::write(&token_path, &self.k3s_token).await?;
+            tokio::fs::set_permissions(&token_path, std::fs::Permissions::from_mode(0o600)).await?;
+            let uploaded = primary_machine
+                .ssh
+                .upload_at(
+                    &primary_machine,
+                    &primary_machine.ssh.address(&primary_machine),
+                    &token_path,
+                    RESTORE_TOKEN_PATH,
+                )
+                .await;
+            tokio::fs::remove_file(&token_path).await?;
+            uploaded?;
+        }
+
+        let config = get_current_config_cloned!();
+        let mut machines = Vec::new();
+        for node in self.cluster_nodes.values() {
+            machines.push((
+                node.primary,
+                some_or_bail!(
+                    config.get_machine_by_id(&node.machine_id),
+                    "Machine not found"
+                ),
+            ));
+        }
+
+        restore_then_start(
+            async {
+                for (_, machine) in &machines {
+                    machine
+                        .exec("sudo systemctl stop k3s", 120)
+                        .await
+                        .context(format!("Failed to stop k3s on {}", machine.id))?;
+                }
+
+                let restore_command = match kind {
+                    ClusterSnapshotKind::Etcd => format!(
+                        "sudo k3s server --cluster-reset --cluster-reset-restore-path={} --token-file={}",
+                        RESTORE_PATH, RESTORE_TOKEN_PATH
+                    ),
+                    _ => format!(
+                        "sudo sh -c 'rm -f {db}/state.db* && tar -C {db} -xf {}'",
+                        RESTORE_PATH,
+                        db = K3S_DB_DIR
+                    ),
+                };
+
+                primary_machine
+                    .exec(&restore_command, 600)
+                    .await
+                    .context("Failed to restore the datastore")?;
+
+                Ok(())
+            },
+            |restored| {
+                self.start_k3s_after_restore(
+                    restored,
+                    primary_hostname,
+                    primary,
+                    &primary_machine,
+                    &machines,
+                )
+            },
+        )
+        .await
+    }
+
+    /// Start k3s on every node of a restore, also when stopping k3s or the
+    /// restore failed. After a restore the other servers rejoin the primary
+    /// with an empty datastore, otherwise they start with the one they had.
+    async fn start_k3s_after_restore(
+        &self,
+        restored: bool,
+        primary_hostname: &str,
+        primary: &ClusterNode,
+        primary_machine: &Machine,
+        machines: &[(bool, Machine)],
+    ) -> anyhow::Result<()> {
+        let mut result = primary_machine
+            .exec(
+                &format!(
+                    "sudo rm -f {} {} && sudo systemctl start k3s",
+                    RESTORE_PATH, RESTORE_TOKEN_PATH
+                ),
+                120,
+            )
+            .await
+            .map(|_| ())
+            .context(format!("Failed to start k3s on {}", primary_machine.id));
+
+        let mut rejoin = restored && result.is_ok();
+        let mut polls = 0;
+        while rejoin && !primary.is_kubernetes_ready().await.unwrap_or(false) {
+            polls += 1;
+            if polls > RESTORE_READY_POLLS {
+                result = Err(anyhow::anyhow!(
+                    "k3s did not start on {} after the restore",
+                    primary_hostname
+                ));
+                rejoin = false;
+                break;
+            }
+            sleep(Duration::from_secs(10)).await;
+        }
+
+        let start_command = if rejoin {
+            format!("sudo sh -c 'rm -rf {} && systemctl start k3s'", K3S_DB_DIR)
+        } else {
+            "sudo systemctl start k3s".to_string()
+        };
+
+        for (_, machine) in machines.iter().filter(|(primary, _)| !primary) {
+            if let Err(e) = machine.exec(&start_command, 120).await {
+                error!("Failed to start k3s on {}: {:?}", machine.id, e);
+                if result.is_ok() {
+                    result = Err(e.context(format!("Failed to start k3s on {}", machine.id)));
+                }
+            }
+        }
+
+        result
+    }
+
+    async fn finish_restore(cluster_id: &str, result: anyhow::Result<()>) {
+        let mut config = write_config!();
+
+        let Some(cluster) = config.clusters.get_mut(cluster_id) else {
+            return;
+        };
+
+        match result {
+            Ok(()) => {
+                info!("Restored cluster {}", cluster_id);
+                cluster.backups.restore = None;
+            }
+            Err(e) => {
+                error!("Restore of cluster {} failed: {:?}", cluster_id, e);
+                if let Some(restore) = cluster.backups.restore.as_mut() {
+                    restore.error = Some(format!("{:#}", e));
+                }
+            }
+        }
+    }
+
+    /// (Re)create the manager's end of the WireGuard tunnel with all
+    /// backup nodes of the cluster as peers
+    async fn bring_up_backup_tunnel(&self) -> anyhow::Result<()> {
+        let private_key = some_or_bail!(
+            &self.cluster_backup_wg_private_key,
+            "Cluster has no backup WireGuard key"
+        );
+
+        let node_ip = some_or_bail!(
+            self.backup_nodes.values().find_map(|node| node.tunnel_ip),
+            "Cluster has no backup node with a tunnel ip"
+        );
+
+        let mut wg_config = format!(
+            "[Interface]\nPrivateKey = {}\nAddress = {}/32\n",
+            private_key,
+            manager_tunnel_ip(node_ip)
+        );
+
+        for backup_node in self.backup_nodes.values() {
+            let (Some(node_private_key), Some(tunnel_ip)) =
+                (&backup_node.backup_wg_private_key, backup_node.tunnel_ip)
+            else {
+                continue;
+            };
+            wg_config.push_str(&format!(
+                "\n[Peer]\nPublicKey = {}\nAllowedIPs = {}/32\nEndpoint = {}:{}\nPersistentKeepalive = 25\n",
+                wg_public_key(node_private_key).await?,
+                tunnel_ip,
+                backup_node.hostname,
+                WG_LISTEN_PORT
+            ));
+        }
+
+        let interface = self.backup_interface();
+        let config_path = format!("/etc/wireguard/{}.conf", interface);
+
+        tokio::fs::create_dir_all("/etc/wireguard").await?;
+        tokio::fs::write(&config_path, wg_config).await?;
+        tokio::fs::set_permissions(&config_path, std::fs::Permissions::from_mode(0o600)).await?;
+
+        // not up on the first backup after a manager start
+        let _ = cmd(vec!["wg-quick", "down", &interface], "").await;
+
+        cmd(
+            vec!["wg-quick", "up", &interface],
+            "Failed to bring up the backup WireGuard tunnel",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Cluster ids are 8 characters, which keeps this within the 15
+    /// characters linux allows for interface names
+    fn backup_interface(&self) -> String {
+        format!("mowsbk{}", self.id)
+    }
+
+    async fn upload_snapshot(
+        &self,
+        backup_node: &BackupNode,
+        local_path: &std::path::Path,
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let (machine, address) = self.backup_node_machine(backup_node).await?;
+
+        machine
+            .ssh
+            .upload_at(&machine, &address, local_path, &remote_path(&self.id, name))
+            .await
+    }
+
+    /// Fetch a snapshot from the first backup node that has it
+    async fn download_snapshot(
+        &self,
+        snapshot: &ClusterSnapshot,
+        local_path: &Path,
+    ) -> anyhow::Result<()> {
+        for machine_id in &snapshot.backup_nodes {
+            let Some(backup_node) = self.backup_nodes.get(machine_id) else {
+                continue;
+            };
+
+            let downloaded = match self.backup_node_machine(backup_node).await {
+                Ok((machine, address)) => {
+                    machine
+                        .ssh
+                        .download_at(
+                            &machine,
+                            &address,
+                            &remote_path(&self.id, &snapshot.name),
+                            local_path,
+                        )
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+
+            match downloaded {
+                Ok(()) => return Ok(()),
+                Err(e) => warn!(
+                    "Could not fetch {} from backup node {}: {:?}",
+                    snapshot.name, machine_id, e
+                ),
+            }
+        }
+
+        let _ = tokio::fs::remove_file(local_path).await;
+        bail!("Snapshot {} is on no reachable backup node", snapshot.name)
+    }
+
+    async fn exec_on_backup_node(
+        &self,
+        backup_node: &BackupNode,
+        command: &str,
+    ) -> anyhow::Result<String> {
+        let (machine, address) = self.backup_node_machine(backup_node).await?;
+
+        machine.ssh.exec_at(&machine, &address, command, 60).await
+    }
+
+    /// The machine of a backup node and its address in the tunnel
+    async fn backup_node_machine(
+        &self,
+        backup_node: &BackupNode,
+    ) -> anyhow::Result<(Machine, String)> {
+        let machine = some_or_bail!(
+            get_current_config_cloned!().get_machine_by_id(&backup_node.machine_id),
+            "Machine of the backup node not found"
+        );
+        let tunnel_ip = some_or_bail!(backup_node.tunnel_ip, "Backup node has no tunnel ip");
+
+        Ok((machine, tunnel_ip.to_string()))
+    }
+
+    async fn primary_machine(&self) -> anyhow::Result<Machine> {
+        let primary = some_or_bail!(
+            self.cluster_nodes.values().find(|node| node.primary),
+            "Cluster has no primary node"
+        );
+
+        Ok(some_or_bail!(
+            get_current_config_cloned!().get_machine_by_id(&primary.machine_id),
+            "Machine of the primary node not found"
+        ))
+    }
+}
+
+/// Which snapshots of one kind to delete: the `keep_last` newest are kept,
+/// as is the newest of each of the `keep_daily` newest days
+fn snapshots_to_prune(
+    snapshots: &[ClusterSnapshot],
+    keep_last: usize,
+    keep_daily: usize,
+) -> Vec<String> {
+    let mut newest_first = snapshots.iter().collect::<Vec<_>>();
+    newest_first.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created_at));
+
+    let mut kept_days = Vec::new();
+    let mut pruned = Vec::new();
+
+    for (position, snapshot) in newest_first.into_iter().enumerate() {
+        let day = snapshot.created_at / SECONDS_PER_DAY;
+
+        let daily = !kept_days.contains(&day) && kept_days.len() < keep_daily;
+        if daily {
+            kept_days.push(day);
+        }
+
+        if position >= keep_last && !daily {
+            pruned.push(snapshot.name.clone());
+        }
+    }
+
+    pruned
+}
+
+/// The lowest free address in the cluster's tunnel subnet, or in the lowest
+/// subnet no other cluster uses for its first backup node
+fn free_tunnel_ip(config: &ManagerConfig, cluster_id: &str) -> anyhow::Result<Ipv4Addr> {
+    let subnet_of = |cluster: &Cluster| {
+        cluster
+            .backup_nodes
+            .values()
+            .find_map(|node| node.tunnel_ip)
+            .map(|ip| ip.octets()[2])
+    };
+
+    let cluster = some_or_bail!(config.clusters.get(cluster_id), "Cluster not found");
+
+    let subnet = match subnet_of(cluster) {
+        Some(subnet) => subnet,
+        None => {
+            let used = config
+                .clusters
+                .values()
+                .filter_map(subnet_of)
+                .collect::<Vec<_>>();
+            some_or_bail!(
+                (0..=u8::MAX).find(|subnet| !used.contains(subnet)),
+                "No free backup tunnel subnet left"
+            )
+        }
+    };
+
+    Ok(some_or_bail!(
+        (2..=254)
+            .map(|host| Ipv4Addr::new(10, 98, subnet, host))
+            .find(|ip| !cluster
+                .backup_nodes
+                .values()
+                .any(|node| node.tunnel_ip == Some(*ip))),
+        "No free tunnel ip left"
+    ))
+}
+
+/// The manager's end of the tunnel a backup node's address is in
+fn manager_tunnel_ip(node_ip: Ipv4Addr) -> Ipv4Addr {
+    let [a, b, subnet, _] = node_ip.octets();
+    Ipv4Addr::new(a, b, subnet, 1)
+}
+
+/// Held while a backup or restore of the cluster runs, as both recreate its
+/// WireGuard tunnel
+fn lock_tunnel(cluster_id: &str) -> anyhow::Result<OwnedMutexGuard<()>> {
+    static LOCKS: OnceLock<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>> = OnceLock::new();
+
+    let lock = LOCKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(cluster_id.to_string())
+        .or_default()
+        .clone();
+
+    lock.try_lock_owned()
+        .map_err(|_| anyhow::anyhow!("A backup or restore of the cluster is already running"))
+}
+
+/// Encrypt to the config store identity and the configured extra recipients
+fn encrypt_args() -> Vec<&'static str> {
+    let mut args = vec!["--encrypt"];
+    for recipient in &INTERNAL_CONFIG.backup.recipients {
+        args.extend(["--recipient", recipient]);
+    }
+    args
+}
+
+fn remote_path(cluster_id: &str, name: &str) -> String {
+    format!(
+        "{}/{}/{}",
+        INTERNAL_CONFIG.backup.remote_dir, cluster_id, name
+    )
+}
+
+/// Run `restore`, then `start` whether it went through or not, so k3s never
+/// stays stopped after a failed restore. The restore error wins over the start
+/// error.
+async fn restore_then_start<S>(
+    restore: impl Future<Output = anyhow::Result<()>>,
+    start: impl FnOnce(bool) -> S,
+) -> anyhow::Result<()>
+where
+    S: Future<Output = anyhow::Result<()>>,
+{
+    let restored = restore.await;
+    let started = start(restored.is_ok()).await;
+
+    match restored {
+        Ok(()) => started,
+        Err(e) => {
+            if let Err(start_error) = started {
+                error!(
+                    "Failed to start k3s after a failed restore: {:?}",
+                    start_error
+                );
+            }
+            Err(e)
+        }
+    }
+}
+
+async fn work_dir() -> anyhow::Result<PathBuf> {
+    let dir = PathBuf::from(&INTERNAL_CONFIG.backup.work_dir);
+    tokio::fs::create_dir_all(&dir).await?;
+    tokio::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).await?;
+    Ok(dir)
+}
+
+fn now() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Run age with the config store identity from `input` to `output`
+async fn age(args: &[&str], input: &Path, output: &Path) -> anyhow::Result<()> {
+    let result = Command::new("age")
+        .args(args)
+        .arg("--identity")
+        .arg(identity_path())
+        .arg("--output")
+        .arg(output)
+        .arg(input)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .context("Failed to run age")?;
+
+    if !result.status.success() {
+        bail!(
+            "age {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+async fn wg_public_key(private_key: &str) -> anyhow::Result<String> {
+    let mut child = Command::new("wg")
+        .arg("pubkey")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run wg")?;
+
+    let mut stdin = child.stdin.take().context("Failed to open wg stdin")?;
+    stdin.write_all(private_key.as_bytes()).await?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        bail!("Failed to derive WireGuard public key");
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(name: &str, created_at: u64) -> ClusterSnapshot {
+        ClusterSnapshot {
+            name: name.to_string(),
+            kind: ClusterSnapshotKind::Etcd,
+            created_at,
+            size: 0,
+            backup_nodes: Vec::new(),
+        }
+    }
+
+    fn backup_node(machine_id: &str, tunnel_ip: Ipv4Addr) -> BackupNode {
+        BackupNode {
+            machine_id: machine_id.to_string(),
+            hostname: machine_id.to_string(),
+            mac: String::new(),
+            ssh: Default::default(),
+            backup_wg_private_key: None,
+            tunnel_ip: Some(tunnel_ip),
+        }
+    }
+
+    #[test]
+    fn gives_each_cluster_its_own_tunnel_subnet() {
+        let mut config = ManagerConfig::default();
+        let mut first = Cluster::default();
+        first.backup_nodes.insert(
+            "b1".to_string(),
+            backup_node("b1", Ipv4Addr::new(10, 98, 0, 2)),
+        );
+        config.clusters.insert("first".to_string(), first);
+        config
+            .clusters
+            .insert("second".to_string(), Cluster::default());
+
+        assert_eq!(
+            free_tunnel_ip(&config, "first").unwrap(),
+            Ipv4Addr::new(10, 98, 0, 3)
+        );
+        let second = free_tunnel_ip(&config, "second").unwrap();
+        assert_eq!(second, Ipv4Addr::new(10, 98, 1, 2));
+        assert_eq!(manager_tunnel_ip(second), Ipv4Addr::new(10, 98, 1, 1));
+
+        config
+            .clusters
+            .get_mut("second")
+            .unwrap()
+            .backup_nodes
+            .insert("b2".to_string(), backup_node("b2", second));
+        assert_eq!(
+            free_tunnel_ip(&config, "second").unwrap(),
+            Ipv4Addr::new(10, 98, 1, 3)
+        );
+        assert!(free_tunnel_ip(&config, "third").is_err());
+    }
+
+    #[test]
+    fn backups_and_restores_of_a_cluster_take_turns() {
+        let tunnel = lock_tunnel("locked").unwrap();
+        assert!(lock_tunnel("locked").is_err());
+        assert!(lock_tunnel("other").is_ok());
+
+        drop(tunnel);
+        assert!(lock_tunnel("locked").is_ok());
+    }
+
+    #[tokio::test]
+    async fn starts_k3s_again_after_a_failed_restore() {
+        let mut started_after = None;
+        let result = restore_then_start(async { bail!("cluster reset failed") }, |restored| {
+            started_after = Some(restored);
+            async { Ok(()) }
+        })
+        .await;
+
+        assert_eq!(result.unwrap_err().to_string(), "cluster reset failed");
+        assert_eq!(started_after, Some(false));
+
+        // the restore error is kept over the start error
+        let result = restore_then_start(async { bail!("cluster reset failed") }, |_| async {
+            bail!("k3s did not start")
+        })
+        .await;
+        assert_eq!(result.unwrap_err().to_string(), "cluster reset failed");
+
+        let result = restore_then_start(async { Ok(()) }, |restored| async move {
+            assert!(restored);
+            bail!("k3s did not start")
+        })
+        .await;
+        assert_eq!(result.unwrap_err().to_string(), "k3s did not start");
+    }
+
+    #[test]
+    fn prunes_beyond_keep_last_and_keep_daily() {
+        let hour = 60 * 60;
+        let day = SECONDS_PER_DAY;
+        let snapshots = vec![
+            snapshot("day0-early", 10 * day + hour),
+            snapshot("day0-late", 10 * day + 20 * hour),
+            snapshot("day1-early", 11 * day + hour),
+            snapshot("day1-late", 11 * day + 20 * hour),
+            snapshot("day2", 12 * day + hour),
+        ];
+
+        // the two newest, then the newest of the two newest days
+        let mut pruned = snapshots_to_prune(&snapshots, 2, 2);
+        pruned.sort();
+        assert_eq!(pruned, vec!["day0-early", "day0-late", "day1-early"]);
+
+        let mut pruned = snapshots_to_prune(&snapshots, 1, 3);
+        pruned.sort();
+        assert_eq!(pruned, vec!["day0-early", "day1-early"]);
+
+        assert!(snapshots_to_prune(&snapshots, 5, 0).is_empty());
+        assert_eq!(snapshots_to_prune(&snapshots, 0, 0).len(), 5);
+    }
+}
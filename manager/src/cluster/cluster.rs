@@ -1,6 +1,9 @@
+use super::backup::ClusterBackups;
 use super::network::ClusterNetwork;
+use super::node::NodeOperation;
 use super::secrets::ClusterSecrets;
 use super::storage::ClusterStorage;
+use super::upgrade::ClusterUpgrade;
 use crate::api::clusters::ClusterSignal;
 use crate::config::{ClusterInstallState, HelmDeploymentState, Vip, VipIp};
 use crate::internal_config::INTERNAL_CONFIG;
@@ -35,6 +38,12 @@ use utoipa::ToSchema;
 pub struct ClusterStatus {
     pub install_state: Option<ClusterInstallState>,
     pub running_state: Option<ClusterRunningState>,
+    /// Node joins and removals in progress or failed, by machine id
+    pub node_operations: HashMap<String, NodeOperation>,
+    /// Rolling upgrade in progress or paused on a failure
+    pub upgrade: Option<ClusterUpgrade>,
+    /// Datastore and vault snapshots on the backup nodes
+    pub backups: ClusterBackups,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
@@ -81,6 +90,10 @@ impl Cluster {
             install_state: None,
             vip,
             vault_secrets: None,
+            node_operations: HashMap::new(),
+            upgrade: None,
+            os_image: None,
+            backups: ClusterBackups::default(),
         })
     }
 
@@ -169,6 +182,9 @@ impl Cluster {
         Ok(ClusterStatus {
             install_state: self.install_state.clone(),
             running_state: self.get_running_state().await?,
+            node_operations: self.node_operations.clone(),
+            upgrade: self.upgrade.clone(),
+            backups: self.backups.clone(),
         })
     }
 
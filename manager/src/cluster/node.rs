@@ -1,10 +1,62 @@
+use std::time::Duration;
+
 use anyhow::{bail, Context};
+use k8s_openapi::api::core::v1::Node;
+use kube::Api;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+use utoipa::ToSchema;
 
 use crate::{
-    config::{ClusterNode, Machine},
+    config::{Cluster, ClusterNode, InternalIps, Machine, MachineInstallState},
     get_current_config_cloned,
+    internal_config::INTERNAL_CONFIG,
+    some_or_bail,
+    utils::cmd,
+    write_config,
 };
 
+use super::{secrets::ClusterSecrets, storage::ClusterStorage};
+
+/// How often the install and kubernetes readiness of a joining node is polled
+const NODE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// PXE boot and OS install, 1h
+const NODE_INSTALL_POLLS: u32 = 360;
+/// From installed OS to a ready kubernetes node, 15min
+const NODE_READY_POLLS: u32 = 90;
+
+/// A node join or removal in progress, reported through the cluster status
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct NodeOperation {
+    pub kind: NodeOperationKind,
+    pub step: NodeOperationStep,
+    /// Set when the step failed; the operation stops there and can be retried
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub enum NodeOperationKind {
+    Join,
+    Remove,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub enum NodeOperationStep {
+    // join
+    Configuring,
+    Installing,
+    Upgrading,
+    JoiningKubernetes,
+    ConfiguringStorage,
+    JoiningVault,
+    // remove
+    Draining,
+    LeavingVault,
+    EvictingStorage,
+    RemovingFromKubernetes,
+}
+
 impl ClusterNode {
     pub async fn get_kubeconfig(&self) -> anyhow::Result<String> {
         let machine = self.get_machine().await?;
@@ -38,3 +90,541 @@ impl ClusterNode {
         Ok(output.contains("active (running)"))
     }
 }
+
+impl InternalIps {
+    pub fn index(&self) -> Option<u8> {
+        let [_, _, _, last] = self.legacy.octets();
+        last.checked_sub(1)
+    }
+}
+
+impl Cluster {
+    /// Lowest node index whose internal ips are not taken
+    pub fn free_node_index(&self) -> anyhow::Result<u8> {
+        let used = self
+            .cluster_nodes
+            .values()
+            .filter_map(|node| node.internal_ips.index())
+            .collect::<Vec<_>>();
+
+        Ok(some_or_bail!(
+            (0..=253).find(|index| !used.contains(index)),
+            "No free node index left"
+        ))
+    }
+
+    fn running_node_operation(&self, machine_id: &str) -> Option<&NodeOperation> {
+        self.node_operations
+            .get(machine_id)
+            .filter(|operation| operation.error.is_none())
+    }
+
+    /// A failed join keeps its node entry, so a retry reuses the ips
+    fn failed_join(&self, machine_id: &str) -> bool {
+        self.node_operations
+            .get(machine_id)
+            .is_some_and(|operation| {
+                operation.kind == NodeOperationKind::Join && operation.error.is_some()
+            })
+    }
+
+    /// Operations run on tasks of the manager process, so any still running
+    /// in a loaded config were cut off by a restart. Failing them lets them
+    /// be retried.
+    pub fn fail_interrupted_node_operations(&mut self) {
+        for (machine_id, operation) in self.node_operations.iter_mut() {
+            if operation.error.is_none() {
+                warn!(
+                    "Node operation for {} in cluster {} was interrupted by a restart",
+                    machine_id, self.id
+                );
+                operation.error = Some("manager restarted".to_string());
+            }
+        }
+    }
+
+    /// Add an inventory machine to the cluster: allocate its internal ips,
+    /// pxe install it and join it to kubernetes, storage and vault. Returns
+    /// once the join is recorded, the rest runs in the background.
+    pub async fn start_node_join(cluster_id: &str, machine_id: &str) -> anyhow::Result<()> {
+        let mut config = write_config!();
+
+        let machine = some_or_bail!(
+            config.machines.get(machine_id).cloned(),
+            "Machine not found"
+        );
+
+        let in_other_cluster = config.clusters.values().any(|cluster| {
+            cluster.id != cluster_id
+                && cluster
+                    .cluster_nodes
+                    .values()
+                    .any(|node| node.machine_id == machine_id)
+        });
+        if in_other_cluster {
+            bail!("Machine is part of another cluster");
+        }
+
+        let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+
+        if cluster.kubeconfig.is_none() {
+            bail!("Cluster is not installed yet");
+        }
+
+        if cluster.upgrade.is_some() {
+            bail!("Cluster is being upgraded");
+        }
+
+        if cluster.running_node_operation(machine_id).is_some() {
+            bail!("A node operation is already running for this machine");
+        }
+
+        let primary_hostname = some_or_bail!(
+            cluster
+                .cluster_nodes
+                .iter()
+                .find(|(_, node)| node.primary)
+                .map(|(hostname, _)| hostname.clone()),
+            "Cluster has no primary node"
+        );
+
+        let hostname = machine_id.to_lowercase();
+
+        if !cluster.failed_join(machine_id) {
+            if cluster
+                .cluster_nodes
+                .values()
+                .any(|node| node.machine_id == machine_id)
+            {
+                bail!("Machine is already a node of this cluster");
+            }
+
+            if machine.install.is_some() {
+                bail!("Machine is already installed");
+            }
+
+            let internal_ips = InternalIps::from_index(cluster.free_node_index()?);
+
+            cluster.cluster_nodes.insert(
+                hostname.clone(),
+                ClusterNode {
+                    machine_id: machine_id.to_string(),
+                    internal_ips,
+                    primary: false,
+                },
+            );
+        }
+
+        cluster.node_operations.insert(
+            machine_id.to_string(),
+            NodeOperation {
+                kind: NodeOperationKind::Join,
+                step: NodeOperationStep::Configuring,
+                error: None,
+            },
+        );
+
+        drop(config);
+
+        let cluster_id = cluster_id.to_string();
+        tokio::spawn(async move {
+            let result = Self::join_node(&cluster_id, &machine, &hostname, &primary_hostname).await;
+            Self::finish_node_operation(&cluster_id, &machine.id, result).await;
+        });
+
+        Ok(())
+    }
+
+    async fn join_node(
+        cluster_id: &str,
+        machine: &Machine,
+        hostname: &str,
+        primary_hostname: &str,
+    ) -> anyhow::Result<()> {
+        let ic = &INTERNAL_CONFIG;
+
+        let cluster = Self::get_by_id(cluster_id).await?;
+        let node = some_or_bail!(
+            cluster.cluster_nodes.get(hostname).cloned(),
+            "Node not found"
+        );
+
+        let installed = |config: &crate::config::ManagerConfig| {
+            config
+                .get_machine_by_id(&machine.id)
+                .and_then(|machine| machine.install)
+                .is_some_and(|install| install.state == Some(MachineInstallState::Installed))
+        };
+
+        if !installed(&get_current_config_cloned!()) {
+            machine
+                .configure_install(
+                    &ic.os_config.kairos_version,
+                    &ic.os_config.k3s_version,
+                    &ic.os_config.os,
+                    &cluster.k3s_token,
+                    hostname,
+                    primary_hostname,
+                    &cluster.vip,
+                    &node.internal_ips,
+                )
+                .await?;
+
+            machine.start().await?;
+
+            Self::set_node_step(cluster_id, &machine.id, NodeOperationStep::Installing).await?;
+
+            let mut polls = 0;
+            while !installed(&get_current_config_cloned!()) {
+                polls += 1;
+                if polls > NODE_INSTALL_POLLS {
+                    bail!("Machine was not installed in time");
+                }
+                sleep(NODE_POLL_INTERVAL).await;
+            }
+        }
+
+        // installs use the configured image, bring the node to the one the
+        // cluster was upgraded to
+        if let Some(os_image) = &cluster.os_image {
+            Self::set_node_step(cluster_id, &machine.id, NodeOperationStep::Upgrading).await?;
+
+            Self::upgrade_machine_os(machine, os_image).await?;
+        }
+
+        Self::set_node_step(
+            cluster_id,
+            &machine.id,
+            NodeOperationStep::JoiningKubernetes,
+        )
+        .await?;
+
+        let mut polls = 0;
+        while !cluster.is_node_ready(hostname).await.unwrap_or(false) {
+            polls += 1;
+            if polls > NODE_READY_POLLS {
+                bail!("Node did not become ready in kubernetes in time");
+            }
+            sleep(NODE_POLL_INTERVAL).await;
+        }
+
+        Self::set_node_step(
+            cluster_id,
+            &machine.id,
+            NodeOperationStep::ConfiguringStorage,
+        )
+        .await?;
+
+        ClusterStorage::set_storage_labels(&node, &cluster).await?;
+
+        if cluster.vault_secrets.is_some() {
+            Self::set_node_step(cluster_id, &machine.id, NodeOperationStep::JoiningVault).await?;
+
+            ClusterSecrets::join_vault_pods_on_node(&cluster, hostname).await?;
+        }
+
+        info!("Node {} joined cluster {}", hostname, cluster_id);
+
+        Ok(())
+    }
+
+    /// Take a node out of the cluster: cordon and drain it, remove it from
+    /// vault raft, move its Longhorn replicas away and delete it from
+    /// kubernetes, then free its index. Returns once the removal is
+    /// recorded, the rest runs in the background.
+    pub async fn start_node_removal(cluster_id: &str, machine_id: &str) -> anyhow::Result<()> {
+        let mut config = write_config!();
+
+        let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+
+        if cluster.upgrade.is_some() {
+            bail!("Cluster is being upgraded");
+        }
+
+        if cluster.running_node_operation(machine_id).is_some() {
+            bail!("A node operation is already running for this machine");
+        }
+
+        let (hostname, node) = some_or_bail!(
+            cluster
+                .cluster_nodes
+                .iter()
+                .find(|(_, node)| node.machine_id == machine_id)
+                .map(|(hostname, node)| (hostname.clone(), node.clone())),
+            "Machine is not a node of this cluster"
+        );
+
+        if node.primary {
+            bail!("The primary node can't be removed");
+        }
+
+        cluster.node_operations.insert(
+            machine_id.to_string(),
+            NodeOperation {
+                kind: NodeOperationKind::Remove,
+                step: NodeOperationStep::Draining,
+                error: None,
+            },
+        );
+
+        drop(config);
+
+        let cluster_id = cluster_id.to_string();
+        let machine_id = machine_id.to_string();
+        tokio::spawn(async move {
+            let result = Self::remove_node(&cluster_id, &machine_id, &hostname).await;
+            Self::finish_node_operation(&cluster_id, &machine_id, result).await;
+        });
+
+        Ok(())
+    }
+
+    async fn remove_node(cluster_id: &str, machine_id: &str, hostname: &str) -> anyhow::Result<()> {
+        let cluster = Self::get_by_id(cluster_id).await?;
+
+        let vault_pods = if cluster.vault_secrets.is_some() {
+            ClusterSecrets::vault_pods_on_node(&cluster, hostname).await?
+        } else {
+            Vec::new()
+        };
+
+        cmd(vec!["kubectl", "cordon", hostname], "Failed to cordon node").await?;
+
+        cmd(
+            vec![
+                "kubectl",
+                "drain",
+                hostname,
+                "--ignore-daemonsets",
+                "--delete-emptydir-data",
+                "--timeout=15m",
+            ],
+            "Failed to drain node",
+        )
+        .await?;
+
+        if !vault_pods.is_empty() {
+            Self::set_node_step(cluster_id, machine_id, NodeOperationStep::LeavingVault).await?;
+
+            ClusterSecrets::remove_vault_raft_peers(&cluster, &vault_pods).await?;
+        }
+
+        Self::set_node_step(cluster_id, machine_id, NodeOperationStep::EvictingStorage).await?;
+
+        ClusterStorage::evict_node(hostname).await?;
+
+        Self::set_node_step(
+            cluster_id,
+            machine_id,
+            NodeOperationStep::RemovingFromKubernetes,
+        )
+        .await?;
+
+        // stop the machine first, a running k3s agent would register the node again
+        if let Some(machine) = get_current_config_cloned!().get_machine_by_id(machine_id) {
+            if let Err(e) = machine.shutdown().await {
+                warn!("Could not shut down machine {}: {:?}", machine_id, e);
+            }
+        }
+
+        cmd(
+            vec!["kubectl", "delete", "node", hostname, "--ignore-not-found"],
+            "Failed to delete node from kubernetes",
+        )
+        .await?;
+
+        ClusterStorage::remove_node(hostname).await?;
+
+        let mut config = write_config!();
+
+        let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+        cluster.cluster_nodes.remove(hostname);
+
+        if let Some(machine) = config.machines.get_mut(machine_id) {
+            machine.install = None;
+        }
+
+        info!("Node {} removed from cluster {}", hostname, cluster_id);
+
+        Ok(())
+    }
+
+    pub async fn is_node_ready(&self, node_name: &str) -> anyhow::Result<bool> {
+        let nodes: Api<Node> = Api::all(self.get_kube_client().await?);
+
+        let node = nodes.get(node_name).await?;
+
+        Ok(node
+            .status
+            .and_then(|status| status.conditions)
+            .is_some_and(|conditions| {
+                conditions
+                    .iter()
+                    .any(|condition| condition.type_ == "Ready" && condition.status == "True")
+            }))
+    }
+
+    pub async fn get_by_id(cluster_id: &str) -> anyhow::Result<Cluster> {
+        Ok(some_or_bail!(
+            get_current_config_cloned!()
+                .clusters
+                .get(cluster_id)
+                .cloned(),
+            "Cluster not found"
+        ))
+    }
+
+    async fn set_node_step(
+        cluster_id: &str,
+        machine_id: &str,
+        step: NodeOperationStep,
+    ) -> anyhow::Result<()> {
+        debug!("Node {} of cluster {}: {:?}", machine_id, cluster_id, step);
+
+        let mut config = write_config!();
+
+        let operation = some_or_bail!(
+            config
+                .clusters
+                .get_mut(cluster_id)
+                .and_then(|cluster| cluster.node_operations.get_mut(machine_id)),
+            "Node operation not found"
+        );
+        operation.step = step;
+
+        Ok(())
+    }
+
+    /// Drop a finished operation from the status, or keep a failed one there
+    /// with its error
+    async fn finish_node_operation(cluster_id: &str, machine_id: &str, result: anyhow::Result<()>) {
+        let mut config = write_config!();
+
+        if let Some(cluster) = config.clusters.get_mut(cluster_id) {
+            cluster.record_node_operation_result(machine_id, result);
+        }
+    }
+
+    fn record_node_operation_result(&mut self, machine_id: &str, result: anyhow::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.node_operations.remove(machine_id);
+            }
+            Err(e) => {
+                error!(
+                    "Node operation for {} in cluster {} failed: {:?}",
+                    machine_id, self.id, e
+                );
+                if let Some(operation) = self.node_operations.get_mut(machine_id) {
+                    operation.error = Some(format!("{:#}", e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn operation(kind: NodeOperationKind, error: Option<&str>) -> NodeOperation {
+        NodeOperation {
+            kind,
+            step: NodeOperationStep::Configuring,
+            error: error.map(str::to_string),
+        }
+    }
+
+    fn node(machine_id: &str, index: u8) -> ClusterNode {
+        ClusterNode {
+            machine_id: machine_id.to_string(),
+            internal_ips: InternalIps::from_index(index),
+            primary: index == 0,
+        }
+    }
+
+    #[test]
+    fn allocates_the_lowest_free_node_index() {
+        let mut cluster = Cluster::default();
+        assert_eq!(cluster.free_node_index().unwrap(), 0);
+
+        cluster.cluster_nodes.insert("a".to_string(), node("a", 0));
+        cluster.cluster_nodes.insert("c".to_string(), node("c", 2));
+        assert_eq!(cluster.free_node_index().unwrap(), 1);
+        assert_eq!(InternalIps::from_index(1).index(), Some(1));
+
+        // a removed node frees its index
+        cluster.cluster_nodes.insert("b".to_string(), node("b", 1));
+        cluster.cluster_nodes.remove("a");
+        assert_eq!(cluster.free_node_index().unwrap(), 0);
+    }
+
+    #[test]
+    fn only_unfailed_operations_block_the_machine() {
+        let mut cluster = Cluster::default();
+        assert!(cluster.running_node_operation("m1").is_none());
+
+        cluster
+            .node_operations
+            .insert("m1".to_string(), operation(NodeOperationKind::Join, None));
+        assert!(cluster.running_node_operation("m1").is_some());
+        assert!(!cluster.failed_join("m1"));
+
+        cluster.record_node_operation_result("m1", Err(anyhow!("install timed out")));
+        assert!(cluster.running_node_operation("m1").is_none());
+        assert!(cluster.failed_join("m1"));
+        assert_eq!(
+            cluster.node_operations["m1"].error.as_deref(),
+            Some("install timed out")
+        );
+
+        cluster.node_operations.insert(
+            "m2".to_string(),
+            operation(NodeOperationKind::Remove, Some("drain failed")),
+        );
+        assert!(cluster.running_node_operation("m2").is_none());
+        assert!(!cluster.failed_join("m2"));
+    }
+
+    #[test]
+    fn finished_operations_leave_the_status() {
+        let mut cluster = Cluster::default();
+        cluster
+            .node_operations
+            .insert("m1".to_string(), operation(NodeOperationKind::Remove, None));
+
+        cluster.record_node_operation_result("m1", Ok(()));
+        assert!(cluster.node_operations.is_empty());
+
+        // the operation may be gone with its cluster entry, nothing to record
+        cluster.record_node_operation_result("m2", Err(anyhow!("failed")));
+        assert!(cluster.node_operations.is_empty());
+    }
+
+    #[test]
+    fn restart_fails_the_running_operations() {
+        let mut cluster = Cluster::default();
+        cluster
+            .node_operations
+            .insert("m1".to_string(), operation(NodeOperationKind::Join, None));
+        cluster.node_operations.insert(
+            "m2".to_string(),
+            operation(NodeOperationKind::Remove, Some("drain failed")),
+        );
+
+        cluster.fail_interrupted_node_operations();
+
+        assert!(cluster.running_node_operation("m1").is_none());
+        assert!(cluster.failed_join("m1"));
+        assert_eq!(
+            cluster.node_operations["m1"].error.as_deref(),
+            Some("manager restarted")
+        );
+        assert_eq!(
+            cluster.node_operations["m2"].error.as_deref(),
+            Some("drain failed")
+        );
+    }
+}
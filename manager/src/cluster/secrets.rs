@@ -5,9 +5,14 @@ use crate::{
     write_config,
 };
 use anyhow::{bail, Context};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::ListParams, Api};
 use mows_common_rust::s;
+use serde::Deserialize;
+use serde_json::json;
 use serde_yaml_neo::Value;
 use std::string::String;
+use tokio::process::Command;
 use tracing::debug;
 use vaultrs::{
     api::auth::kubernetes::requests::{
@@ -19,6 +24,16 @@ use vaultrs::{
 
 pub struct ClusterSecrets;
 
+const VAULT_NAMESPACE: &str = "mows-core-secrets-vault";
+
+/// The parts of `vault status -format=json` we look at
+#[derive(Debug, Deserialize)]
+pub struct VaultPodStatus {
+    pub initialized: bool,
+    pub sealed: bool,
+    pub storage_type: String,
+}
+
 impl ClusterSecrets {
     pub async fn setup_vault(cluster: &Cluster) -> anyhow::Result<()> {
         let secrets = Self::init_vault().await?;
@@ -266,6 +281,214 @@ path "mows-core-secrets-vrc/*" {
         Ok(format!("{:?}", status).contains("SEALED"))
     }
 
+    /// Names of the vault pods scheduled on a kubernetes node
+    pub async fn vault_pods_on_node(
+        cluster: &Cluster,
+        node_name: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let pods: Api<Pod> = Api::namespaced(cluster.get_kube_client().await?, VAULT_NAMESPACE);
+
+        let pods = pods
+            .list(
+                &ListParams::default()
+                    .labels("app.kubernetes.io/name=vault")
+                    .fields(&format!("spec.nodeName={}", node_name)),
+            )
+            .await
+            .context("Failed to list vault pods")?;
+
+        Ok(pods
+            .items
+            .into_iter()
+            .filter_map(|pod| pod.metadata.name)
+            .collect())
+    }
+
+    pub async fn vault_pod_status(pod_name: &str) -> anyhow::Result<VaultPodStatus> {
+        // vault status exits with 2 when sealed, so the exit code is not checked
+        let output = Command::new("kubectl")
+            .args([
+                "exec",
+                "-n",
+                VAULT_NAMESPACE,
+                pod_name,
+                "--",
+                "vault",
+                "status",
+                "-format=json",
+            ])
+            .output()
+            .await
+            .context("Failed to run kubectl exec")?;
+
+        serde_json::from_slice(&output.stdout).context(format!(
+            "Failed to get vault status of pod {}: {}",
+            pod_name,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+
+    /// Bring the vault pods on a freshly joined node into service: pods with
+    /// raft storage join the raft cluster, all of them get unsealed.
+    pub async fn join_vault_pods_on_node(cluster: &Cluster, node_name: &str) -> anyhow::Result<()> {
+        let secrets = some_or_bail!(&cluster.vault_secrets, "Vault secrets not found");
+
+        for pod_name in Self::vault_pods_on_node(cluster, node_name).await? {
+            let status = Self::vault_pod_status(&pod_name).await?;
+
+            if status.storage_type == "raft" && !status.initialized {
+                Self::join_raft_and_unseal(&pod_name, secrets).await?;
+            } else if status.sealed {
+                Self::unseal_pod(&pod_name, secrets).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unseal every sealed vault pod of the cluster, e.g. after a node
+    /// rebooted. Returns the pods that are still sealed or not reachable yet.
+    pub async fn unseal_vault_pods(cluster: &Cluster) -> anyhow::Result<Vec<String>> {
+        let secrets = some_or_bail!(&cluster.vault_secrets, "Vault secrets not found");
+
+        let pods: Api<Pod> = Api::namespaced(cluster.get_kube_client().await?, VAULT_NAMESPACE);
+
+        let pod_names = pods
+            .list(&ListParams::default().labels("app.kubernetes.io/name=vault"))
+            .await
+            .context("Failed to list vault pods")?
+            .items
+            .into_iter()
+            .filter_map(|pod| pod.metadata.name);
+
+        let mut sealed = Vec::new();
+
+        for pod_name in pod_names {
+            let unsealed = match Self::vault_pod_status(&pod_name).await {
+                Ok(status) if status.sealed => Self::unseal_pod(&pod_name, secrets).await.is_ok(),
+                Ok(_) => true,
+                Err(_) => false,
+            };
+
+            if !unsealed {
+                sealed.push(pod_name);
+            }
+        }
+
+        Ok(sealed)
+    }
+
+    async fn unseal_pod(pod_name: &str, secrets: &VaultSecrets) -> anyhow::Result<()> {
+        debug!("Unsealing vault pod {}", pod_name);
+
+        cmd(
+            vec![
+                "kubectl",
+                "exec",
+                "-n",
+                VAULT_NAMESPACE,
+                pod_name,
+                "--",
+                "vault",
+                "operator",
+                "unseal",
+                secrets.unseal_key.as_str(),
+            ],
+            &format!("Failed to unseal vault pod: {pod_name}"),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove vault pods that were on a drained node from the raft cluster.
+    /// Pods that came back up on another node keep their raft membership,
+    /// as do pods of non raft storage backends, which have none.
+    pub async fn remove_vault_raft_peers(
+        cluster: &Cluster,
+        pod_names: &[String],
+    ) -> anyhow::Result<()> {
+        let secrets = some_or_bail!(&cluster.vault_secrets, "Vault secrets not found");
+
+        for pod_name in pod_names {
+            if let Ok(status) = Self::vault_pod_status(pod_name).await {
+                if status.storage_type != "raft" || status.initialized {
+                    debug!("Vault pod {} keeps its raft membership", pod_name);
+                    continue;
+                }
+            }
+
+            debug!("Removing vault pod {} from raft", pod_name);
+
+            let response = reqwest::Client::new()
+                .post("http://127.0.0.1:8200/v1/sys/storage/raft/remove-peer")
+                .header("X-Vault-Token", &secrets.root_token)
+                .json(&json!({ "server_id": pod_name }))
+                .send()
+                .await
+                .context("Failed to reach vault")?;
+
+            if !response.status().is_success() {
+                let body = response.text().await.unwrap_or_default();
+                // non raft storage has no peers to remove
+                if body.contains("raft storage is not in use") {
+                    continue;
+                }
+                bail!(
+                    "Failed to remove vault pod {} from raft: {}",
+                    pod_name,
+                    body
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Raft snapshot of vault, none when vault does not use raft storage
+    pub async fn raft_snapshot(cluster: &Cluster) -> anyhow::Result<Option<Vec<u8>>> {
+        let secrets = some_or_bail!(&cluster.vault_secrets, "Vault secrets not found");
+
+        let response = reqwest::Client::new()
+            .get("http://127.0.0.1:8200/v1/sys/storage/raft/snapshot")
+            .header("X-Vault-Token", &secrets.root_token)
+            .send()
+            .await
+            .context("Failed to reach vault")?;
+
+        if !response.status().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            if body.contains("raft storage is not in use") {
+                return Ok(None);
+            }
+            bail!("Failed to take vault raft snapshot: {}", body);
+        }
+
+        Ok(Some(response.bytes().await?.to_vec()))
+    }
+
+    /// Replace the vault data with a raft snapshot of this vault
+    pub async fn restore_raft_snapshot(cluster: &Cluster, snapshot: Vec<u8>) -> anyhow::Result<()> {
+        let secrets = some_or_bail!(&cluster.vault_secrets, "Vault secrets not found");
+
+        let response = reqwest::Client::new()
+            .post("http://127.0.0.1:8200/v1/sys/storage/raft/snapshot-force")
+            .header("X-Vault-Token", &secrets.root_token)
+            .body(snapshot)
+            .send()
+            .await
+            .context("Failed to reach vault")?;
+
+        if !response.status().is_success() {
+            bail!(
+                "Failed to restore vault raft snapshot: {}",
+                response.text().await.unwrap_or_default()
+            );
+        }
+
+        Ok(())
+    }
+
     pub async fn new_vault_client(token: Option<&str>) -> anyhow::Result<VaultClient> {
         let mut client_builder = VaultClientSettingsBuilder::default();
 
@@ -11,14 +11,19 @@ use kube::{
 };
 use serde_json::json;
 
+use tokio::time::sleep;
 use tracing::debug;
 
-use crate::config::{Cluster, ClusterNode};
+use crate::{
+    config::{Cluster, ClusterNode},
+    utils::cmd,
+};
 use mows_common_rust::{s, utils::generate_id};
 
 pub struct ClusterStorage;
 
 const LONGHORN_STORAGE_CLASS_NAME: &str = "mows-core-storage-longhorn-static";
+const LONGHORN_NAMESPACE: &str = "mows-core-storage-longhorn";
 
 impl ClusterStorage {
     pub async fn install(cluster: &Cluster) -> anyhow::Result<()> {
@@ -276,4 +281,102 @@ impl ClusterStorage {
 
         Ok(())
     }
+
+    /// Stop scheduling replicas on a node and wait until Longhorn has rebuilt
+    /// the ones on it elsewhere
+    pub async fn evict_node(node_name: &str) -> anyhow::Result<()> {
+        debug!("Evicting longhorn replicas from node {}", node_name);
+
+        cmd(
+            vec![
+                "kubectl",
+                "patch",
+                "nodes.longhorn.io",
+                node_name,
+                "-n",
+                LONGHORN_NAMESPACE,
+                "--type",
+                "merge",
+                "-p",
+                r#"{"spec":{"allowScheduling":false,"evictionRequested":true}}"#,
+            ],
+            "Failed to request longhorn eviction",
+        )
+        .await?;
+
+        let replicas_on_node = format!(
+            "jsonpath={{.items[?(@.spec.nodeID==\"{}\")].metadata.name}}",
+            node_name
+        );
+
+        // rebuilding large volumes takes a while
+        for _ in 0..360 {
+            let replicas = cmd(
+                vec![
+                    "kubectl",
+                    "get",
+                    "replicas.longhorn.io",
+                    "-n",
+                    LONGHORN_NAMESPACE,
+                    "-o",
+                    &replicas_on_node,
+                ],
+                "Failed to list longhorn replicas",
+            )
+            .await?;
+
+            if replicas.trim().is_empty() {
+                debug!("No longhorn replicas left on node {}", node_name);
+                return Ok(());
+            }
+
+            sleep(tokio::time::Duration::from_secs(10)).await;
+        }
+
+        bail!("Longhorn replicas were not moved off node {}", node_name)
+    }
+
+    /// Attached volumes that are not healthy, as `name (robustness)`.
+    /// Detached volumes report `unknown` and are left out.
+    pub async fn unhealthy_volumes() -> anyhow::Result<Vec<String>> {
+        let volumes = cmd(
+            vec![
+                "kubectl",
+                "get",
+                "volumes.longhorn.io",
+                "-n",
+                LONGHORN_NAMESPACE,
+                "-o",
+                r#"jsonpath={range .items[*]}{.metadata.name}{" "}{.status.robustness}{"\n"}{end}"#,
+            ],
+            "Failed to list longhorn volumes",
+        )
+        .await?;
+
+        Ok(volumes
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter(|(_, robustness)| matches!(*robustness, "degraded" | "faulted"))
+            .map(|(name, robustness)| format!("{} ({})", name, robustness))
+            .collect())
+    }
+
+    /// Remove a node from Longhorn after it was removed from kubernetes
+    pub async fn remove_node(node_name: &str) -> anyhow::Result<()> {
+        cmd(
+            vec![
+                "kubectl",
+                "delete",
+                "nodes.longhorn.io",
+                node_name,
+                "-n",
+                LONGHORN_NAMESPACE,
+                "--ignore-not-found",
+            ],
+            "Failed to remove node from longhorn",
+        )
+        .await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,498 @@
+//! Rolling upgrades of the OS and k3s on the nodes of a cluster.
+//!
+//! Kairos ships k3s inside its OS images, so both are upgraded by switching
+//! a node to another image with `kairos-agent upgrade` over SSH. Nodes are
+//! upgraded one at a time, the primary first as k3s wants the initial server
+//! upgraded before the others: cordon and drain, upgrade and reboot, wait for
+//! kubernetes, uncordon. Vault has to be unsealed and the Longhorn volumes
+//! healthy again before the next node goes down. A failed step pauses the
+//! upgrade, starting it again resumes with the node it stopped on.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{Cluster, ClusterInstallState, Machine},
+    get_current_config_cloned,
+    internal_config::INTERNAL_CONFIG,
+    some_or_bail,
+    utils::cmd,
+    write_config,
+};
+
+use super::{secrets::ClusterSecrets, storage::ClusterStorage};
+
+const UPGRADE_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Shutdown, firmware and boot of an upgraded machine, 30min
+const REBOOT_POLLS: u32 = 180;
+/// From booted to a ready kubernetes node, 15min
+const READY_POLLS: u32 = 90;
+/// Unsealing and Longhorn replica rebuilds after a node came back, 1h
+const HEALTH_POLLS: u32 = 360;
+
+const BOOT_ID_COMMAND: &str = "cat /proc/sys/kernel/random/boot_id";
+
+/// A rolling upgrade in progress or paused, reported through the cluster status
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct ClusterUpgrade {
+    /// Kairos image the nodes are upgraded to
+    pub os_image: String,
+    /// Hostnames of the nodes already running the image
+    pub upgraded_nodes: Vec<String>,
+    /// Hostname of the node being upgraded, none while checking the cluster
+    /// health before the first node or after the last one
+    pub node: Option<String>,
+    pub step: ClusterUpgradeStep,
+    /// Set when the step failed; the upgrade pauses there and resumes when it
+    /// is started again
+    pub error: Option<String>,
+    /// Install state the cluster returns to once all nodes are upgraded
+    pub previous_install_state: Option<ClusterInstallState>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub enum ClusterUpgradeStep {
+    CheckingHealth,
+    Draining,
+    Upgrading,
+    WaitingForKubernetes,
+    Uncordoning,
+}
+
+impl ClusterUpgrade {
+    fn new(os_image: String, previous_install_state: Option<ClusterInstallState>) -> Self {
+        Self {
+            os_image,
+            upgraded_nodes: Vec::new(),
+            node: None,
+            step: ClusterUpgradeStep::CheckingHealth,
+            error: None,
+            previous_install_state,
+        }
+    }
+}
+
+impl Cluster {
+    /// Upgrade all nodes to `os_image`, or to the standard Kairos image of
+    /// the configured OS and Kairos version with `k3s_version`. Resumes a
+    /// paused upgrade to the same image. Returns once the upgrade is
+    /// recorded, the rest runs in the background.
+    pub async fn start_upgrade(
+        cluster_id: &str,
+        k3s_version: Option<&str>,
+        os_image: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let ic = &INTERNAL_CONFIG;
+
+        let os_image = match (os_image, k3s_version) {
+            (Some(os_image), _) => os_image.to_string(),
+            (None, Some(k3s_version)) => {
+                kairos_image(&ic.os_config.os, &ic.os_config.kairos_version, k3s_version)?
+            }
+            (None, None) => bail!("Neither a k3s version nor an OS image was given"),
+        };
+
+        if !is_valid_image(&os_image) {
+            bail!("Invalid OS image: {}", os_image);
+        }
+
+        let mut config = write_config!();
+
+        let cluster = some_or_bail!(config.clusters.get_mut(cluster_id), "Cluster not found");
+
+        let upgrade = cluster.next_upgrade(os_image)?;
+
+        info!("Upgrading cluster {} to {}", cluster_id, upgrade.os_image);
+
+        cluster.upgrade = Some(upgrade);
+        cluster.install_state = Some(ClusterInstallState::Upgrading);
+
+        drop(config);
+
+        let cluster_id = cluster_id.to_string();
+        tokio::spawn(async move {
+            let result = Self::run_upgrade(&cluster_id).await;
+            Self::finish_upgrade(&cluster_id, result).await;
+        });
+
+        Ok(())
+    }
+
+    /// The upgrade to `os_image` to start, or the paused one to resume
+    fn next_upgrade(&self, os_image: String) -> anyhow::Result<ClusterUpgrade> {
+        if self
+            .node_operations
+            .values()
+            .any(|operation| operation.error.is_none())
+        {
+            bail!("Wait for the running node operations to finish");
+        }
+
+        Ok(match self.upgrade.clone() {
+            Some(upgrade) if upgrade.error.is_none() => bail!("An upgrade is already running"),
+            // the nodes a paused upgrade finished stay done
+            Some(upgrade) if upgrade.os_image == os_image => ClusterUpgrade {
+                error: None,
+                ..upgrade
+            },
+            Some(upgrade) => ClusterUpgrade::new(os_image, upgrade.previous_install_state),
+            None => {
+                if !matches!(
+                    self.install_state,
+                    Some(ClusterInstallState::BasicsConfigured | ClusterInstallState::BasicsReady)
+                ) {
+                    bail!("Cluster is not installed yet");
+                }
+                ClusterUpgrade::new(os_image, self.install_state.clone())
+            }
+        })
+    }
+
+    /// An upgrade still running in a loaded config was cut off by a restart
+    /// of the manager. Pause it like a failed one, so starting it again
+    /// resumes with the node it stopped on; the cluster stays `Upgrading`
+    /// until then.
+    pub fn pause_interrupted_upgrade(&mut self) {
+        if let Some(upgrade) = self
+            .upgrade
+            .as_mut()
+            .filter(|upgrade| upgrade.error.is_none())
+        {
+            warn!(
+                "Upgrade of cluster {} was interrupted by a restart",
+                self.id
+            );
+            upgrade.error = Some("manager restarted".to_string());
+        }
+    }
+
+    async fn run_upgrade(cluster_id: &str) -> anyhow::Result<()> {
+        let cluster = Self::get_by_id(cluster_id).await?;
+        let upgrade = some_or_bail!(cluster.upgrade.clone(), "Upgrade not found");
+
+        // primary first, the rest in a stable order
+        let mut hostnames = cluster
+            .cluster_nodes
+            .iter()
+            .filter(|(hostname, _)| !upgrade.upgraded_nodes.contains(hostname))
+            .map(|(hostname, node)| (!node.primary, hostname.clone()))
+            .collect::<Vec<_>>();
+        hostnames.sort();
+
+        for (_, hostname) in hostnames {
+            Self::set_upgrade_step(
+                cluster_id,
+                Some(&hostname),
+                ClusterUpgradeStep::CheckingHealth,
+            )
+            .await?;
+
+            cluster.wait_healthy().await?;
+
+            cluster
+                .upgrade_node(cluster_id, &hostname, &upgrade.os_image)
+                .await?;
+
+            let mut config = write_config!();
+            let upgrade = some_or_bail!(
+                config
+                    .clusters
+                    .get_mut(cluster_id)
+                    .and_then(|cluster| cluster.upgrade.as_mut()),
+                "Upgrade not found"
+            );
+            upgrade.upgraded_nodes.push(hostname);
+        }
+
+        Self::set_upgrade_step(cluster_id, None, ClusterUpgradeStep::CheckingHealth).await?;
+
+        cluster.wait_healthy().await?;
+
+        info!("Cluster {} upgraded to {}", cluster_id, upgrade.os_image);
+
+        Ok(())
+    }
+
+    async fn upgrade_node(
+        &self,
+        cluster_id: &str,
+        hostname: &str,
+        os_image: &str,
+    ) -> anyhow::Result<()> {
+        let node = some_or_bail!(self.cluster_nodes.get(hostname), "Node not found");
+        let machine = some_or_bail!(
+            get_current_config_cloned!().get_machine_by_id(&node.machine_id),
+            "Machine not found"
+        );
+
+        Self::set_upgrade_step(cluster_id, Some(hostname), ClusterUpgradeStep::Draining).await?;
+
+        cmd(vec!["kubectl", "cordon", hostname], "Failed to cordon node").await?;
+
+        cmd(
+            vec![
+                "kubectl",
+                "drain",
+                hostname,
+                "--ignore-daemonsets",
+                "--delete-emptydir-data",
+                "--timeout=15m",
+            ],
+            "Failed to drain node",
+        )
+        .await?;
+
+        Self::set_upgrade_step(cluster_id, Some(hostname), ClusterUpgradeStep::Upgrading).await?;
+
+        Self::upgrade_machine_os(&machine, os_image).await?;
+
+        Self::set_upgrade_step(
+            cluster_id,
+            Some(hostname),
+            ClusterUpgradeStep::WaitingForKubernetes,
+        )
+        .await?;
+
+        let mut polls = 0;
+        while !(node.is_kubernetes_ready().await.unwrap_or(false)
+            && self.is_node_ready(hostname).await.unwrap_or(false))
+        {
+            polls += 1;
+            if polls > READY_POLLS {
+                bail!(
+                    "Node {} did not become ready in kubernetes in time",
+                    hostname
+                );
+            }
+            sleep(UPGRADE_POLL_INTERVAL).await;
+        }
+
+        Self::set_upgrade_step(cluster_id, Some(hostname), ClusterUpgradeStep::Uncordoning).await?;
+
+        cmd(
+            vec!["kubectl", "uncordon", hostname],
+            "Failed to uncordon node",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Switch a machine to another Kairos image and reboot into it. Returns
+    /// once the machine is up again.
+    pub async fn upgrade_machine_os(machine: &Machine, os_image: &str) -> anyhow::Result<()> {
+        let boot_id = machine.exec(BOOT_ID_COMMAND, 5).await?;
+
+        debug!("Upgrading machine {} to {}", machine.id, os_image);
+
+        machine
+            .exec(
+                &format!("sudo kairos-agent upgrade --source oci:{}", os_image),
+                600,
+            )
+            .await
+            .context(format!("Failed to upgrade machine {}", machine.id))?;
+
+        // the connection drops when the machine goes down
+        if let Err(e) = machine.exec("sudo reboot", 5).await {
+            debug!("Reboot of machine {} returned: {:?}", machine.id, e);
+        }
+
+        for _ in 0..REBOOT_POLLS {
+            sleep(UPGRADE_POLL_INTERVAL).await;
+
+            if machine
+                .exec(BOOT_ID_COMMAND, 5)
+                .await
+                .is_ok_and(|current| current != boot_id)
+            {
+                return Ok(());
+            }
+        }
+
+        bail!("Machine {} did not come back after the upgrade", machine.id)
+    }
+
+    /// Wait until vault is unsealed, unsealing pods that came back sealed,
+    /// and no Longhorn volume is degraded or faulted
+    async fn wait_healthy(&self) -> anyhow::Result<()> {
+        let mut problems = Vec::new();
+
+        for _ in 0..HEALTH_POLLS {
+            problems.clear();
+
+            if self.vault_secrets.is_some() {
+                for pod in ClusterSecrets::unseal_vault_pods(self).await? {
+                    problems.push(format!("vault pod {} is sealed", pod));
+                }
+            }
+
+            for volume in ClusterStorage::unhealthy_volumes().await? {
+                problems.push(format!("volume {}", volume));
+            }
+
+            if problems.is_empty() {
+                return Ok(());
+            }
+
+            debug!("Waiting for cluster {}: {}", self.id, problems.join(", "));
+
+            sleep(UPGRADE_POLL_INTERVAL).await;
+        }
+
+        bail!("Cluster did not become healthy: {}", problems.join(", "))
+    }
+
+    async fn set_upgrade_step(
+        cluster_id: &str,
+        node: Option<&str>,
+        step: ClusterUpgradeStep,
+    ) -> anyhow::Result<()> {
+        debug!(
+            "Upgrade of cluster {} on {:?}: {:?}",
+            cluster_id, node, step
+        );
+
+        let mut config = write_config!();
+
+        let upgrade = some_or_bail!(
+            config
+                .clusters
+                .get_mut(cluster_id)
+                .and_then(|cluster| cluster.upgrade.as_mut()),
+            "Upgrade not found"
+        );
+        upgrade.node = node.map(str::to_string);
+        upgrade.step = step;
+
+        Ok(())
+    }
+
+    /// Record the image and restore the install state after a finished
+    /// upgrade, or pause a failed one with its error
+    async fn finish_upgrade(cluster_id: &str, result: anyhow::Result<()>) {
+        let mut config = write_config!();
+
+        let Some(cluster) = config.clusters.get_mut(cluster_id) else {
+            return;
+        };
+
+        match result {
+            Ok(()) => {
+                if let Some(upgrade) = cluster.upgrade.take() {
+                    cluster.install_state = upgrade.previous_install_state;
+                    cluster.os_image = Some(upgrade.os_image);
+                }
+            }
+            Err(e) => {
+                error!("Upgrade of cluster {} failed: {:?}", cluster_id, e);
+                if let Some(upgrade) = cluster.upgrade.as_mut() {
+                    upgrade.error = Some(format!("{:#}", e));
+                }
+            }
+        }
+    }
+}
+
+/// Standard Kairos image for an OS as configured in `os_config`, e.g.
+/// `opensuse-tumbleweed`, with k3s given as `v1.31.1+k3s1` or `k3sv1.31.1+k3s1`
+fn kairos_image(os: &str, kairos_version: &str, k3s_version: &str) -> anyhow::Result<String> {
+    let (flavor, release) = some_or_bail!(
+        os.split_once('-'),
+        "OS is not of the form <flavor>-<release>"
+    );
+
+    let k3s_version = match k3s_version.strip_prefix("k3s") {
+        Some(version) => version,
+        None => k3s_version,
+    };
+
+    Ok(format!(
+        "quay.io/kairos/{}:{}-standard-amd64-generic-{}-k3s{}",
+        flavor,
+        release,
+        kairos_version,
+        k3s_version.replace('+', "-")
+    ))
+}
+
+/// The image ends up in a shell command on the node
+fn is_valid_image(image: &str) -> bool {
+    !image.is_empty()
+        && image
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/:.-_@".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE: &str =
+        "quay.io/kairos/opensuse:tumbleweed-standard-amd64-generic-v3.2.1-k3sv1.31.1-k3s1";
+
+    #[test]
+    fn restart_pauses_a_running_upgrade_for_resuming() {
+        let mut cluster = Cluster {
+            install_state: Some(ClusterInstallState::BasicsReady),
+            ..Cluster::default()
+        };
+        let mut upgrade = cluster.next_upgrade(IMAGE.to_string()).unwrap();
+        upgrade.upgraded_nodes.push("primary".to_string());
+        upgrade.node = Some("worker".to_string());
+        upgrade.step = ClusterUpgradeStep::Upgrading;
+        cluster.upgrade = Some(upgrade);
+        cluster.install_state = Some(ClusterInstallState::Upgrading);
+        assert!(cluster.next_upgrade(IMAGE.to_string()).is_err());
+
+        cluster.pause_interrupted_upgrade();
+
+        let paused = cluster.upgrade.clone().unwrap();
+        assert_eq!(paused.error.as_deref(), Some("manager restarted"));
+        assert_eq!(paused.step, ClusterUpgradeStep::Upgrading);
+
+        let resumed = cluster.next_upgrade(IMAGE.to_string()).unwrap();
+        assert_eq!(resumed.error, None);
+        assert_eq!(resumed.upgraded_nodes, vec!["primary"]);
+        assert_eq!(
+            resumed.previous_install_state,
+            Some(ClusterInstallState::BasicsReady)
+        );
+
+        // a failed upgrade keeps its own error
+        cluster.upgrade.as_mut().unwrap().error = Some("drain failed".to_string());
+        cluster.pause_interrupted_upgrade();
+        assert_eq!(
+            cluster.upgrade.unwrap().error.as_deref(),
+            Some("drain failed")
+        );
+    }
+
+    #[test]
+    fn builds_kairos_image() {
+        assert_eq!(
+            kairos_image("opensuse-tumbleweed", "v3.2.1", "v1.31.1+k3s1").unwrap(),
+            IMAGE
+        );
+        assert_eq!(
+            kairos_image("opensuse-tumbleweed", "v3.2.1", "k3sv1.31.1+k3s1").unwrap(),
+            IMAGE
+        );
+        assert!(is_valid_image(IMAGE));
+        assert!(kairos_image("alpine", "v3.2.1", "v1.31.1+k3s1").is_err());
+    }
+
+    #[test]
+    fn rejects_images_that_break_out_of_the_command() {
+        assert!(!is_valid_image(""));
+        assert!(!is_valid_image(
+            "quay.io/kairos/opensuse:tumbleweed; reboot"
+        ));
+        assert!(!is_valid_image("$(reboot)"));
+    }
+}
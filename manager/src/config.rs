@@ -1,5 +1,9 @@
+use crate::cluster::backup::ClusterBackups;
+use crate::cluster::node::NodeOperation;
+use crate::cluster::upgrade::ClusterUpgrade;
 use crate::machines::MachineType;
 use crate::providers::hcloud::ExternalProviderConfigHcloud;
+use crate::providers::local_physical::power::PhysicalPowerConfig;
 use crate::public_ip::WgKeys;
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
@@ -182,6 +186,19 @@ pub struct Cluster {
     pub cluster_backup_wg_private_key: Option<String>,
     pub install_state: Option<ClusterInstallState>,
     pub vault_secrets: Option<VaultSecrets>,
+    /// Node joins and removals in progress or failed, by machine id
+    #[serde(default)]
+    pub node_operations: HashMap<String, NodeOperation>,
+    /// Rolling upgrade in progress or paused on a failure
+    #[serde(default)]
+    pub upgrade: Option<ClusterUpgrade>,
+    /// Kairos image the nodes were last upgraded to, new nodes are upgraded
+    /// to it after their install
+    #[serde(default)]
+    pub os_image: Option<String>,
+    /// Datastore and vault snapshots on the backup nodes
+    #[serde(default)]
+    pub backups: ClusterBackups,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema, Default, PartialEq)]
@@ -231,6 +248,9 @@ pub struct Machine {
     pub public_ip: Option<Ipv6Addr>,
     #[schema(schema_with = ipv4adr_to_schema)]
     pub public_legacy_ip: Option<Ipv4Addr>,
+    /// How a LocalPhysical machine is switched on and off
+    #[serde(default)]
+    pub power: Option<PhysicalPowerConfig>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema, Default, PartialEq)]
 pub struct MachineInstall {
@@ -251,6 +271,8 @@ pub enum ClusterInstallState {
     Kubernetes,
     BasicsConfigured,
     BasicsReady,
+    /// A rolling upgrade runs or is paused, see `Cluster::upgrade`
+    Upgrading,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema, Default, PartialEq)]
@@ -291,6 +313,10 @@ pub struct BackupNode {
     pub mac: String,
     pub ssh: SshAccess,
     pub backup_wg_private_key: Option<String>,
+    /// Address of the node in the WireGuard tunnel to the manager
+    #[serde(default)]
+    #[schema(schema_with = ipv4adr_to_schema)]
+    pub tunnel_ip: Option<Ipv4Addr>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
@@ -0,0 +1,361 @@
+//! Encrypted on-disk persistence for the manager config.
+//!
+//! Every version of the config that `write_config!` produces is written to
+//! `<config_store.dir>/<version>.age`, encrypted with the `age` CLI to the
+//! identity in `config_store.identity_file` (generated with `age-keygen` on
+//! first start). The highest version is the current config and is loaded
+//! on startup; the newest `config_store.history_size` versions are kept so
+//! an earlier one can be restored.
+//!
+//! Writes happen on a background task fed by a watch channel, so a burst of
+//! mutations collapses into the latest state and callers never wait on disk
+//! or on `age`.
+
+use std::ops::{Deref, DerefMut};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::{watch, RwLockWriteGuard};
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+
+use crate::config::{config, ManagerConfig};
+use crate::internal_config::INTERNAL_CONFIG;
+
+const VERSION_EXTENSION: &str = "age";
+
+/// A stored config version, without its content.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ConfigVersion {
+    pub version: u64,
+    /// Unix seconds at which the version was written.
+    pub saved_at: u64,
+    /// Whether this is the version the manager currently runs on.
+    pub current: bool,
+}
+
+/// Configs produced by `write_config!`, picked up by the persister task.
+fn changes() -> &'static watch::Sender<ManagerConfig> {
+    static CHANGES: OnceLock<watch::Sender<ManagerConfig>> = OnceLock::new();
+    CHANGES.get_or_init(|| watch::Sender::new(ManagerConfig::default()))
+}
+
+/// Write access to the global config that queues the result for persisting
+/// when dropped, if it differs from the config at acquisition.
+pub struct ConfigWriteGuard {
+    guard: RwLockWriteGuard<'static, ManagerConfig>,
+    before: ManagerConfig,
+    changes: &'static watch::Sender<ManagerConfig>,
+}
+
+impl ConfigWriteGuard {
+    pub fn new(guard: RwLockWriteGuard<'static, ManagerConfig>) -> Self {
+        Self::queuing_to(guard, changes())
+    }
+
+    fn queuing_to(
+        guard: RwLockWriteGuard<'static, ManagerConfig>,
+        changes: &'static watch::Sender<ManagerConfig>,
+    ) -> Self {
+        let before = guard.clone();
+        Self {
+            guard,
+            before,
+            changes,
+        }
+    }
+}
+
+impl Deref for ConfigWriteGuard {
+    type Target = ManagerConfig;
+
+    fn deref(&self) -> &ManagerConfig {
+        &self.guard
+    }
+}
+
+impl DerefMut for ConfigWriteGuard {
+    fn deref_mut(&mut self) -> &mut ManagerConfig {
+        &mut self.guard
+    }
+}
+
+impl Drop for ConfigWriteGuard {
+    fn drop(&mut self) {
+        if *self.guard != self.before {
+            self.changes.send_replace(self.guard.clone());
+        }
+    }
+}
+
+/// Load the current version into the global config and start persisting
+/// further changes. Called once on startup, before anything writes the
+/// config.
+pub async fn start_config_store() -> anyhow::Result<()> {
+    let dir = store_dir();
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create config store {}", dir.display()))?;
+    tokio::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).await?;
+    ensure_identity().await?;
+
+    let mut last_saved = None;
+    if let Some(version) = list_versions(&dir).await?.last() {
+        let loaded = read_version(*version).await?;
+        *config().write().await = loaded.clone();
+        info!("Loaded config version {} from {}", version, dir.display());
+        last_saved = Some(loaded);
+    } else {
+        info!("No stored config in {}, starting empty", dir.display());
+    }
+
+    let mut receiver = changes().subscribe();
+    tokio::spawn(async move {
+        while receiver.changed().await.is_ok() {
+            let latest = receiver.borrow_and_update().clone();
+            if last_saved.as_ref() == Some(&latest) {
+                continue;
+            }
+            match save_version(&latest).await {
+                Ok(version) => {
+                    debug!("Persisted config version {}", version);
+                    last_saved = Some(latest);
+                }
+                Err(e) => error!("Failed to persist config: {:?}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Stored versions, oldest first.
+pub async fn list_history() -> anyhow::Result<Vec<ConfigVersion>> {
+    let dir = store_dir();
+    let versions = list_versions(&dir).await?;
+    let current = versions.last().copied();
+    let mut history = Vec::with_capacity(versions.len());
+    for version in versions {
+        let modified = tokio::fs::metadata(version_path(&dir, version))
+            .await?
+            .modified()?;
+        history.push(ConfigVersion {
+            version,
+            saved_at: modified.duration_since(UNIX_EPOCH)?.as_secs(),
+            current: Some(version) == current,
+        });
+    }
+    Ok(history)
+}
+
+/// Decrypt a stored version.
+pub async fn read_version(version: u64) -> anyhow::Result<ManagerConfig> {
+    let path = version_path(&store_dir(), version);
+    if !tokio::fs::try_exists(&path).await? {
+        bail!("Config version {} does not exist", version);
+    }
+    let output = Command::new("age")
+        .arg("--decrypt")
+        .arg("--identity")
+        .arg(identity_path())
+        .arg(&path)
+        .output()
+        .await
+        .context("Failed to run age")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to decrypt {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&output.stdout)
+        .with_context(|| format!("Failed to parse config version {}", version))
+}
+
+/// Encrypt `config` as the next version and drop versions beyond the
+/// history size.
+async fn save_version(config: &ManagerConfig) -> anyhow::Result<u64> {
+    let dir = store_dir();
+    let version = list_versions(&dir)
+        .await?
+        .last()
+        .map_or(1, |latest| latest + 1);
+    let path = version_path(&dir, version);
+    let temp_path = path.with_extension("tmp");
+
+    let mut child = Command::new("age")
+        .arg("--encrypt")
+        .arg("--identity")
+        .arg(identity_path())
+        .arg("--output")
+        .arg(&temp_path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run age")?;
+    let mut stdin = child.stdin.take().context("Failed to open age stdin")?;
+    stdin.write_all(&serde_json::to_vec(config)?).await?;
+    drop(stdin);
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        bail!(
+            "Failed to encrypt config: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    tokio::fs::rename(&temp_path, &path).await?;
+
+    prune_versions(&dir, INTERNAL_CONFIG.config_store.history_size.max(1)).await?;
+
+    Ok(version)
+}
+
+/// Remove all but the newest `keep` versions in `dir`.
+async fn prune_versions(dir: &Path, keep: usize) -> anyhow::Result<()> {
+    let versions = list_versions(dir).await?;
+    for old in versions.iter().take(versions.len().saturating_sub(keep)) {
+        tokio::fs::remove_file(version_path(dir, *old)).await?;
+    }
+    Ok(())
+}
+
+/// Versions stored in `dir`, oldest first.
+async fn list_versions(dir: &Path) -> anyhow::Result<Vec<u64>> {
+    let mut versions = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(VERSION_EXTENSION) {
+            continue;
+        }
+        if let Some(version) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+        {
+            versions.push(version);
+        }
+    }
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+async fn ensure_identity() -> anyhow::Result<()> {
+    let identity = identity_path();
+    if tokio::fs::try_exists(&identity).await? {
+        return Ok(());
+    }
+    info!("Generating config store identity {}", identity.display());
+    if let Some(parent) = identity.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let output = Command::new("age-keygen")
+        .arg("--output")
+        .arg(identity)
+        .output()
+        .await
+        .context("Failed to run age-keygen")?;
+    if !output.status.success() {
+        bail!(
+            "Failed to generate {}: {}",
+            identity.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    tokio::fs::set_permissions(&identity, std::fs::Permissions::from_mode(0o600)).await?;
+    Ok(())
+}
+
+fn store_dir() -> PathBuf {
+    PathBuf::from(&INTERNAL_CONFIG.config_store.dir)
+}
+
+/// age identity the config versions are encrypted to, also used for the
+/// cluster snapshots.
+pub fn identity_path() -> &'static Path {
+    Path::new(&INTERNAL_CONFIG.config_store.identity_file)
+}
+
+fn version_path(dir: &Path, version: u64) -> PathBuf {
+    dir.join(format!("{:08}.{}", version, VERSION_EXTENSION))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::config::Cluster;
+
+    fn leak<T>(value: T) -> &'static T {
+        Box::leak(Box::new(value))
+    }
+
+    async fn store_with(versions: &[u64]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for version in versions {
+            tokio::fs::write(version_path(dir.path(), *version), b"")
+                .await
+                .unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn queues_the_config_only_when_a_write_changed_it() {
+        let config = leak(RwLock::new(ManagerConfig::default()));
+        let changes = leak(watch::Sender::new(ManagerConfig::default()));
+        let mut receiver = changes.subscribe();
+
+        drop(ConfigWriteGuard::queuing_to(config.write().await, changes));
+        {
+            let mut guard = ConfigWriteGuard::queuing_to(config.write().await, changes);
+            let unchanged = guard.clone();
+            *guard = unchanged;
+        }
+        assert!(!receiver.has_changed().unwrap());
+
+        let mut changed = ManagerConfig::default();
+        changed
+            .clusters
+            .insert("cluster".to_string(), Cluster::default());
+        *ConfigWriteGuard::queuing_to(config.write().await, changes) = changed.clone();
+        assert!(receiver.has_changed().unwrap());
+        assert_eq!(*receiver.borrow_and_update(), changed);
+    }
+
+    #[tokio::test]
+    async fn prunes_to_the_newest_versions() {
+        let dir = store_with(&[1, 2, 3, 4, 5]).await;
+
+        prune_versions(dir.path(), 3).await.unwrap();
+        assert_eq!(list_versions(dir.path()).await.unwrap(), vec![3, 4, 5]);
+
+        prune_versions(dir.path(), 10).await.unwrap();
+        assert_eq!(list_versions(dir.path()).await.unwrap(), vec![3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn loads_the_highest_version() {
+        let dir = store_with(&[9, 10, 2]).await;
+        // an interrupted save and unrelated files are not versions
+        tokio::fs::write(dir.path().join("00000011.tmp"), b"")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("notes.age"), b"")
+            .await
+            .unwrap();
+
+        let versions = list_versions(dir.path()).await.unwrap();
+        assert_eq!(versions, vec![2, 9, 10]);
+        assert_eq!(versions.last(), Some(&10));
+    }
+}
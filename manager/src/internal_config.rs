@@ -60,6 +60,38 @@ pub struct InternalConfig {
     pub primary_origin: Url,
     pub os_config: OsConfig,
     pub package_manager: PackageManagerConfig,
+    pub config_store: ConfigStoreConfig,
+    pub backup: BackupConfig,
+}
+
+#[derive(Deserialize, Debug, Serialize, Eq, PartialEq, Clone)]
+pub struct BackupConfig {
+    /// Minutes between the scheduled snapshots of a cluster.
+    pub interval_minutes: u64,
+    /// Number of newest snapshots of each kind to keep.
+    pub keep_last: usize,
+    /// Number of days for which the newest snapshot of the day is kept on top.
+    pub keep_daily: usize,
+    /// Directory on the backup nodes the snapshots are stored in.
+    pub remote_dir: String,
+    /// Local directory snapshots pass through on their way to and from the
+    /// backup nodes.
+    pub work_dir: String,
+    /// age recipients the snapshots are encrypted to besides the config store
+    /// identity, so they can still be decrypted if the manager is lost.
+    #[serde(default)]
+    pub recipients: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Serialize, Eq, PartialEq, Clone)]
+pub struct ConfigStoreConfig {
+    /// Directory holding the encrypted config versions.
+    pub dir: String,
+    /// age identity the versions are encrypted to; generated on first start
+    /// if missing.
+    pub identity_file: String,
+    /// Number of versions to keep, the current one included.
+    pub history_size: usize,
 }
 
 #[derive(Deserialize, Debug, Serialize, Eq, PartialEq, Clone)]
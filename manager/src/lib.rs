@@ -1,4 +1,5 @@
 pub mod config;
+pub mod config_store;
 pub mod internal_config;
 pub mod machines;
 pub mod macros;
@@ -17,6 +18,8 @@ pub mod providers {
     }
     pub mod local_physical {
         pub mod machine;
+        pub mod power;
+        pub mod redfish;
         pub mod video;
     }
 }
@@ -26,11 +29,13 @@ pub mod os {
 }
 
 pub mod cluster {
+    pub mod backup;
     pub mod cluster;
     pub mod network;
     pub mod node;
     pub mod secrets;
     pub mod storage;
+    pub mod upgrade;
 }
 
 pub mod api {
@@ -75,7 +75,7 @@ impl Machine {
     pub async fn get_status(&self) -> anyhow::Result<MachineStatus> {
         Ok(match self.machine_type {
             MachineType::LocalQemu => LocalMachineProviderQemu::get_status(&self.id).await?,
-            MachineType::LocalPhysical => bail!("Not implemented"),
+            MachineType::LocalPhysical => LocalMachineProviderPhysical::get_status(self).await?,
             MachineType::ExternalHcloud => {
                 ExternalProviderMachineHcloud::get_status(&self.id).await?
             }
@@ -128,7 +128,7 @@ impl Machine {
     pub async fn start(&self) -> anyhow::Result<()> {
         match self.machine_type {
             MachineType::LocalQemu => LocalMachineProviderQemu::start(&self.id).await,
-            MachineType::LocalPhysical => bail!("Not implemented"),
+            MachineType::LocalPhysical => LocalMachineProviderPhysical::start(self).await,
             MachineType::ExternalHcloud => bail!("Not implemented"),
         }
     }
@@ -152,7 +152,7 @@ impl Machine {
     pub async fn reboot(&self) -> anyhow::Result<()> {
         match self.machine_type {
             MachineType::LocalQemu => LocalMachineProviderQemu::reboot(&self.id).await,
-            MachineType::LocalPhysical => bail!("Not implemented"),
+            MachineType::LocalPhysical => LocalMachineProviderPhysical::reboot(self).await,
             MachineType::ExternalHcloud => bail!("Not implemented"),
         }
     }
@@ -160,7 +160,7 @@ impl Machine {
     pub async fn shutdown(&self) -> anyhow::Result<()> {
         match self.machine_type {
             MachineType::LocalQemu => LocalMachineProviderQemu::shutdown(&self.id).await,
-            MachineType::LocalPhysical => bail!("Not implemented"),
+            MachineType::LocalPhysical => LocalMachineProviderPhysical::shutdown(self).await,
             MachineType::ExternalHcloud => bail!("Not implemented"),
         }
     }
@@ -168,7 +168,7 @@ impl Machine {
     pub async fn reset(&self) -> anyhow::Result<()> {
         match self.machine_type {
             MachineType::LocalQemu => LocalMachineProviderQemu::reset(&self.id).await,
-            MachineType::LocalPhysical => bail!("Not implemented"),
+            MachineType::LocalPhysical => LocalMachineProviderPhysical::reset(self).await,
             MachineType::ExternalHcloud => bail!("Not implemented"),
         }
     }
@@ -176,7 +176,7 @@ impl Machine {
     pub async fn force_off(&self) -> anyhow::Result<()> {
         match self.machine_type {
             MachineType::LocalQemu => LocalMachineProviderQemu::force_off(&self.id).await,
-            MachineType::LocalPhysical => bail!("Not implemented"),
+            MachineType::LocalPhysical => LocalMachineProviderPhysical::force_off(self).await,
             MachineType::ExternalHcloud => bail!("Not implemented"),
         }
     }
@@ -23,7 +23,7 @@ macro_rules! get_current_config_cloned {
 macro_rules! write_config {
     ( ) => {{
         tracing::debug!(target: "manager::config_locks","Writing config: {} {}", file!(), line!());
-        crate::config::config().write().await
+        crate::config_store::ConfigWriteGuard::new(crate::config::config().write().await)
     }};
 }
 
@@ -2,6 +2,7 @@ use anyhow::Context;
 use axum::http::header::{CONTENT_TYPE, UPGRADE};
 use axum::http::{HeaderValue, Method};
 use manager::api::openapi::build_api_router;
+use manager::config_store::start_config_store;
 use manager::internal_config::INTERNAL_CONFIG;
 use manager::tasks::start_background_tasks;
 use manager::tracing::start_tracing;
@@ -56,6 +57,10 @@ async fn main() -> Result<(), anyhow::Error> {
 
     info!("Open {} in your browser", ic.primary_origin);
 
+    start_config_store()
+        .await
+        .context("Failed to load the stored config")?;
+
     start_background_tasks().await?;
 
     info!("Starting server");
@@ -152,6 +152,7 @@ impl ExternalProviderMachineHcloud {
                 .ipv4
                 .map(|ip| ip.ip.to_string().parse::<Ipv4Addr>().ok())
                 .flatten(),
+            power: None,
         })
     }
 
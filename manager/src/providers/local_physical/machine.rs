@@ -3,12 +3,23 @@ use utoipa::ToSchema;
 
 use crate::{
     config::{Machine, SshAccess},
-    machines::MachineType,
+    machines::{MachineStatus, MachineType},
+    some_or_bail,
+};
+
+use super::{
+    power::{BmcConfig, PhysicalPowerConfig, WakeOnLanConfig},
+    redfish::ResetType,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema, Default)]
 pub struct LocalMachineProviderPhysicalConfig {
     pub mac_address: String,
+    /// Used to switch the machine on when there is no BMC
+    #[serde(default)]
+    pub wake_on_lan: Option<WakeOnLanConfig>,
+    #[serde(default)]
+    pub bmc: Option<BmcConfig>,
 }
 
 pub struct LocalMachineProviderPhysical;
@@ -28,8 +39,50 @@ impl LocalMachineProviderPhysical {
             ssh,
             public_ip: None,
             public_legacy_ip: None,
+            power: Some(PhysicalPowerConfig {
+                wake_on_lan: cc.wake_on_lan.clone(),
+                bmc: cc.bmc.clone(),
+            }),
         };
 
         Ok(machine)
     }
+
+    pub async fn get_status(machine: &Machine) -> anyhow::Result<MachineStatus> {
+        match &machine.power {
+            Some(power) => power.get_status().await,
+            None => Ok(MachineStatus::Unknown),
+        }
+    }
+
+    pub async fn start(machine: &Machine) -> anyhow::Result<()> {
+        Self::power(machine)?.start(machine.mac.as_deref()).await
+    }
+
+    pub async fn reboot(machine: &Machine) -> anyhow::Result<()> {
+        Self::power(machine)?
+            .reset(ResetType::GracefulRestart)
+            .await
+    }
+
+    pub async fn shutdown(machine: &Machine) -> anyhow::Result<()> {
+        Self::power(machine)?
+            .reset(ResetType::GracefulShutdown)
+            .await
+    }
+
+    pub async fn reset(machine: &Machine) -> anyhow::Result<()> {
+        Self::power(machine)?.reset(ResetType::ForceRestart).await
+    }
+
+    pub async fn force_off(machine: &Machine) -> anyhow::Result<()> {
+        Self::power(machine)?.reset(ResetType::ForceOff).await
+    }
+
+    fn power(machine: &Machine) -> anyhow::Result<&PhysicalPowerConfig> {
+        Ok(some_or_bail!(
+            machine.power.as_ref(),
+            "No power management configured for this machine"
+        ))
+    }
 }
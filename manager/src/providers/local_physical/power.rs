@@ -0,0 +1,253 @@
+//! Power management for physical machines: Wake-on-LAN to switch them on,
+//! and a BMC (Redfish or IPMI-over-LAN) for everything else.
+
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use tokio::{net::UdpSocket, process::Command};
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{machines::MachineStatus, some_or_bail};
+
+use super::redfish::{RedfishClient, ResetType};
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, Default, PartialEq)]
+pub struct PhysicalPowerConfig {
+    pub wake_on_lan: Option<WakeOnLanConfig>,
+    pub bmc: Option<BmcConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct WakeOnLanConfig {
+    /// Where the magic packet is sent, usually the broadcast address of the
+    /// machine's network.
+    #[serde(default = "default_wake_on_lan_address")]
+    #[schema(value_type = String)]
+    pub broadcast_address: Ipv4Addr,
+    #[serde(default = "default_wake_on_lan_port")]
+    pub port: u16,
+}
+
+fn default_wake_on_lan_address() -> Ipv4Addr {
+    Ipv4Addr::BROADCAST
+}
+
+fn default_wake_on_lan_port() -> u16 {
+    9
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+#[serde(tag = "protocol")]
+pub enum BmcConfig {
+    Redfish(RedfishConfig),
+    Ipmi(IpmiConfig),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct RedfishConfig {
+    /// Base URL of the BMC, e.g. `https://10.0.0.10`.
+    pub url: String,
+    pub username: String,
+    pub password: String,
+    /// Most BMCs ship a self-signed certificate.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct IpmiConfig {
+    pub host: String,
+    #[serde(default = "default_ipmi_port")]
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+}
+
+fn default_ipmi_port() -> u16 {
+    623
+}
+
+/// How long a graceful IPMI restart waits for the OS to power off.
+const IPMI_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const IPMI_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+impl PhysicalPowerConfig {
+    /// Power on through the BMC if there is one, else with Wake-on-LAN.
+    pub async fn start(&self, mac: Option<&str>) -> anyhow::Result<()> {
+        match (&self.bmc, &self.wake_on_lan) {
+            (Some(bmc), _) => bmc.reset(ResetType::On).await,
+            (None, Some(wake_on_lan)) => {
+                wake_on_lan
+                    .wake(some_or_bail!(mac, "Machine has no mac address"))
+                    .await
+            }
+            (None, None) => bail!("Neither a BMC nor Wake-on-LAN is configured"),
+        }
+    }
+
+    pub async fn reset(&self, reset_type: ResetType) -> anyhow::Result<()> {
+        self.bmc()?.reset(reset_type).await
+    }
+
+    /// `Unknown` without a BMC to ask.
+    pub async fn get_status(&self) -> anyhow::Result<MachineStatus> {
+        match &self.bmc {
+            Some(bmc) => bmc.get_status().await,
+            None => Ok(MachineStatus::Unknown),
+        }
+    }
+
+    fn bmc(&self) -> anyhow::Result<&BmcConfig> {
+        Ok(some_or_bail!(self.bmc.as_ref(), "No BMC is configured"))
+    }
+}
+
+impl BmcConfig {
+    pub async fn reset(&self, reset_type: ResetType) -> anyhow::Result<()> {
+        match self {
+            BmcConfig::Redfish(redfish) => RedfishClient::new(redfish)?.reset(reset_type).await,
+            BmcConfig::Ipmi(ipmi) => {
+                let action = match reset_type {
+                    ResetType::On => "on",
+                    ResetType::GracefulShutdown => "soft",
+                    ResetType::GracefulRestart => {
+                        // IPMI has no graceful restart (`cycle` cuts the
+                        // power), so shut the OS down and power back on
+                        // once it is off.
+                        ipmi.chassis_power("soft").await?;
+                        let ipmi = ipmi.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = ipmi.power_on_once_off().await {
+                                error!(
+                                    "Failed to power {} back on after a graceful restart: {:?}",
+                                    ipmi.host, e
+                                );
+                            }
+                        });
+                        return Ok(());
+                    }
+                    ResetType::ForceRestart => "reset",
+                    ResetType::ForceOff => "off",
+                };
+                ipmi.chassis_power(action).await?;
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn get_status(&self) -> anyhow::Result<MachineStatus> {
+        match self {
+            BmcConfig::Redfish(redfish) => RedfishClient::new(redfish)?.get_status().await,
+            BmcConfig::Ipmi(ipmi) => ipmi.get_status().await,
+        }
+    }
+}
+
+impl IpmiConfig {
+    async fn get_status(&self) -> anyhow::Result<MachineStatus> {
+        let output = self.chassis_power("status").await?;
+        Ok(match output.trim() {
+            "Chassis Power is on" => MachineStatus::Running,
+            "Chassis Power is off" => MachineStatus::Stopped,
+            _ => MachineStatus::Unknown,
+        })
+    }
+
+    async fn power_on_once_off(&self) -> anyhow::Result<()> {
+        let deadline = tokio::time::Instant::now() + IPMI_SHUTDOWN_TIMEOUT;
+        while self.get_status().await? != MachineStatus::Stopped {
+            if tokio::time::Instant::now() >= deadline {
+                bail!(
+                    "Machine did not power off within {}s",
+                    IPMI_SHUTDOWN_TIMEOUT.as_secs()
+                );
+            }
+            tokio::time::sleep(IPMI_STATUS_INTERVAL).await;
+        }
+        self.chassis_power("on").await?;
+        Ok(())
+    }
+
+    async fn chassis_power(&self, action: &str) -> anyhow::Result<String> {
+        // -E reads the password from IPMI_PASSWORD so it doesn't show up in ps
+        let output = Command::new("ipmitool")
+            .args(["-I", "lanplus", "-H", &self.host, "-p"])
+            .arg(self.port.to_string())
+            .args(["-U", &self.username, "-E", "chassis", "power", action])
+            .env("IPMI_PASSWORD", &self.password)
+            .output()
+            .await
+            .context("Failed to run ipmitool")?;
+
+        if !output.status.success() {
+            bail!(
+                "ipmitool chassis power {} failed: {}",
+                action,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+impl WakeOnLanConfig {
+    pub async fn wake(&self, mac: &str) -> anyhow::Result<()> {
+        let packet = magic_packet(mac)?;
+
+        let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)).await?;
+        socket.set_broadcast(true)?;
+        socket
+            .send_to(
+                &packet,
+                SocketAddrV4::new(self.broadcast_address, self.port),
+            )
+            .await
+            .context("Failed to send Wake-on-LAN packet")?;
+
+        Ok(())
+    }
+}
+
+/// Six `0xFF` bytes followed by the mac address sixteen times.
+fn magic_packet(mac: &str) -> anyhow::Result<Vec<u8>> {
+    let mac = mac
+        .split([':', '-'])
+        .map(|octet| u8::from_str_radix(octet, 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .with_context(|| format!("Invalid mac address: {}", mac))?;
+    if mac.len() != 6 {
+        bail!("Invalid mac address: expected 6 octets, got {}", mac.len());
+    }
+
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_magic_packet() {
+        let packet = magic_packet("52:54:00:ab:CD:ef").unwrap();
+        assert_eq!(packet.len(), 102);
+        assert!(packet.iter().take(6).all(|byte| *byte == 0xFF));
+        assert!(packet
+            .iter()
+            .skip(6)
+            .collect::<Vec<_>>()
+            .chunks(6)
+            .all(|chunk| chunk == [&0x52, &0x54, &0x00, &0xab, &0xcd, &0xef]));
+
+        assert_eq!(magic_packet("52-54-00-ab-cd-ef").unwrap(), packet);
+        assert!(magic_packet("52:54:00:ab:cd").is_err());
+        assert!(magic_packet("52:54:00:ab:cd:zz").is_err());
+    }
+}
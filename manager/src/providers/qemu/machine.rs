@@ -89,6 +89,7 @@ impl LocalMachineProviderQemu {
             ssh,
             public_ip: None,
             public_legacy_ip: None,
+            power: None,
         };
 
         machine.force_off().await?;
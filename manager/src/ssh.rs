@@ -1,9 +1,12 @@
 use anyhow::{bail, Context};
 use async_ssh2_tokio::{AuthMethod, Client, Config, ServerCheckMethod};
 use mows_common_rust::utils::generate_id;
+use russh_sftp::client::SftpSession;
+use russh_sftp::protocol::{FileAttributes, OpenFlags};
 use std::env;
 use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 use std::process::Stdio;
 use std::time::Duration;
 use tempfile::NamedTempFile;
@@ -104,6 +107,110 @@ impl SshAccess {
         command: &str,
         inactivity_timeout_secs: u64,
     ) -> anyhow::Result<String> {
+        self.exec_at(
+            machine,
+            &self.address(machine),
+            command,
+            inactivity_timeout_secs,
+        )
+        .await
+    }
+
+    /// Like `exec`, but reaching the machine at another address, e.g. its
+    /// end of a WireGuard tunnel
+    pub async fn exec_at(
+        &self,
+        machine: &Machine,
+        address: &str,
+        command: &str,
+        inactivity_timeout_secs: u64,
+    ) -> anyhow::Result<String> {
+        let client = self
+            .connect(machine, address, inactivity_timeout_secs)
+            .await?;
+
+        let res = client.execute(command).await?;
+
+        if res.exit_status != 0 {
+            bail!("SSH Command failed: {:?}", res.stderr)
+        }
+
+        Ok(res.stdout)
+    }
+
+    /// Stream a local file to the machine over sftp, readable only by the
+    /// ssh user
+    pub async fn upload_at(
+        &self,
+        machine: &Machine,
+        address: &str,
+        local_path: &Path,
+        remote_path: &str,
+    ) -> anyhow::Result<()> {
+        let client = self.connect(machine, address, 60).await?;
+        let sftp = open_sftp(&client).await?;
+
+        let mut local = tokio::fs::File::open(local_path)
+            .await
+            .context(format!("Failed to open {}", local_path.display()))?;
+        let mut remote = sftp
+            .open_with_flags_and_attributes(
+                remote_path,
+                OpenFlags::CREATE | OpenFlags::TRUNCATE | OpenFlags::WRITE,
+                FileAttributes {
+                    permissions: Some(0o600),
+                    ..FileAttributes::empty()
+                },
+            )
+            .await
+            .context(format!("Failed to create {}", remote_path))?;
+
+        tokio::io::copy(&mut local, &mut remote)
+            .await
+            .context(format!("Failed to upload {}", remote_path))?;
+        remote.shutdown().await?;
+
+        Ok(())
+    }
+
+    /// Stream a file from the machine to a local file over sftp
+    pub async fn download_at(
+        &self,
+        machine: &Machine,
+        address: &str,
+        remote_path: &str,
+        local_path: &Path,
+    ) -> anyhow::Result<()> {
+        let client = self.connect(machine, address, 60).await?;
+        let sftp = open_sftp(&client).await?;
+
+        let mut remote = sftp
+            .open(remote_path)
+            .await
+            .context(format!("Failed to open {}", remote_path))?;
+        let mut local = tokio::fs::File::create(local_path)
+            .await
+            .context(format!("Failed to create {}", local_path.display()))?;
+
+        tokio::io::copy(&mut remote, &mut local)
+            .await
+            .context(format!("Failed to download {}", remote_path))?;
+        local.flush().await?;
+
+        Ok(())
+    }
+
+    /// Where `exec` reaches the machine
+    pub fn address(&self, machine: &Machine) -> String {
+        self.remote_hostname.clone().unwrap_or(machine.id.clone())
+    }
+
+    async fn connect(
+        &self,
+        machine: &Machine,
+        address: &str,
+        inactivity_timeout_secs: u64,
+    ) -> anyhow::Result<Client> {
         let remote_pub_key = match &self.remote_public_key {
             Some(pk) => pk.clone(),
             None => self.set_remote_pub_key(machine).await?.clone(),
@@ -115,10 +222,7 @@ impl SshAccess {
         ssh_config.inactivity_timeout = Some(Duration::from_secs(inactivity_timeout_secs));
 
         let client = Client::connect_with_config(
-            (
-                self.remote_hostname.clone().unwrap_or(machine.id.clone()),
-                22,
-            ),
+            (address, 22),
             &self.ssh_username,
             auth_method,
             ServerCheckMethod::PublicKey(remote_pub_key),
@@ -126,13 +230,7 @@ impl SshAccess {
         )
         .await?;
 
-        let res = client.execute(command).await?;
-
-        if res.exit_status != 0 {
-            bail!("SSH Command failed: {:?}", res.stderr)
-        }
-
-        Ok(res.stdout)
+        Ok(client)
     }
 
     pub async fn ensure_local_ssh_agent_is_running(&self) -> anyhow::Result<()> {
@@ -202,3 +300,11 @@ impl SshAccess {
         }
     }
 }
+
+async fn open_sftp(client: &Client) -> anyhow::Result<SftpSession> {
+    let channel = client.get_channel().await?;
+    channel.request_subsystem(true, "sftp").await?;
+    SftpSession::new(channel.into_stream())
+        .await
+        .context("Failed to start sftp, is it enabled in the sshd config?")
+}
@@ -1,16 +1,20 @@
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::Context;
 use tracing::{debug, error, info, trace};
 
 use crate::{
     config::{Cluster, ClusterInstallState, MachineInstallState},
-    get_current_config_cloned, some_or_bail, write_config,
+    get_current_config_cloned,
+    internal_config::INTERNAL_CONFIG,
+    some_or_bail, write_config,
 };
 
 pub async fn start_background_tasks() -> anyhow::Result<()> {
     info!("Starting background tasks");
 
+    fail_interrupted_operations().await;
+
     // these are separated for easier debugging
     tokio::spawn(async {
         loop {
@@ -48,6 +52,15 @@ pub async fn start_background_tasks() -> anyhow::Result<()> {
         }
     });
 
+    tokio::spawn(async {
+        loop {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            if let Err(e) = backup_clusters().await {
+                error!("Could not back up clusters: {:?}", e);
+            };
+        }
+    });
+
     // Kubectl proxy
     tokio::spawn(async {
         let mut proxy_running_for_cluster: Option<String> = None;
@@ -109,6 +122,16 @@ pub async fn start_background_tasks() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Fail what a previous manager process left running in the loaded config,
+/// so it can be retried instead of blocking forever
+pub async fn fail_interrupted_operations() {
+    let mut config = write_config!();
+    for cluster in config.clusters.values_mut() {
+        cluster.fail_interrupted_node_operations();
+        cluster.pause_interrupted_upgrade();
+    }
+}
+
 #[tracing::instrument]
 pub async fn update_machine_install_state() -> anyhow::Result<()> {
     let cfg1 = get_current_config_cloned!();
@@ -197,6 +220,46 @@ pub async fn install_cluster_basics() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Back up the clusters with backup nodes whose last backup is older than the
+/// backup interval. Clusters being restored, upgraded or changing nodes are
+/// left for a later run.
+#[tracing::instrument]
+pub async fn backup_clusters() -> anyhow::Result<()> {
+    let cfg1 = get_current_config_cloned!();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let interval = INTERNAL_CONFIG.backup.interval_minutes * 60;
+
+    for cluster in cfg1.clusters.values() {
+        if cluster.backup_nodes.is_empty()
+            || cluster.kubeconfig.is_none()
+            || cluster
+                .backups
+                .restore
+                .as_ref()
+                .is_some_and(|restore| restore.error.is_none())
+            || cluster.install_state == Some(ClusterInstallState::Upgrading)
+            || cluster
+                .node_operations
+                .values()
+                .any(|operation| operation.error.is_none())
+            || cluster
+                .backups
+                .last_attempt_at
+                .is_some_and(|last| now < last + interval)
+        {
+            continue;
+        }
+
+        debug!("Backing up cluster {}", cluster.id);
+        // one failing cluster must not hold up the backups of the others
+        if let Err(e) = Cluster::backup(&cluster.id).await {
+            error!("Failed to back up cluster {}: {:?}", cluster.id, e);
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn start_cluster_proxy() -> anyhow::Result<Option<String>> {
     let cfg1 = get_current_config_cloned!();
 
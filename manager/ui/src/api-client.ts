@@ -60,6 +60,39 @@ export enum ClusterInstallState {
   Kubernetes = "Kubernetes",
   BasicsConfigured = "BasicsConfigured",
   BasicsReady = "BasicsReady",
+  Upgrading = "Upgrading",
+}
+
+export enum NodeOperationKind {
+  Join = "Join",
+  Remove = "Remove",
+}
+
+export enum NodeOperationStep {
+  Configuring = "Configuring",
+  Installing = "Installing",
+  Upgrading = "Upgrading",
+  JoiningKubernetes = "JoiningKubernetes",
+  ConfiguringStorage = "ConfiguringStorage",
+  JoiningVault = "JoiningVault",
+  Draining = "Draining",
+  LeavingVault = "LeavingVault",
+  EvictingStorage = "EvictingStorage",
+  RemovingFromKubernetes = "RemovingFromKubernetes",
+}
+
+export enum ClusterUpgradeStep {
+  CheckingHealth = "CheckingHealth",
+  Draining = "Draining",
+  Upgrading = "Upgrading",
+  WaitingForKubernetes = "WaitingForKubernetes",
+  Uncordoning = "Uncordoning",
+}
+
+export enum ClusterSnapshotKind {
+  Etcd = "Etcd",
+  Sqlite = "Sqlite",
+  Vault = "Vault",
 }
 
 export enum ApiResponseStatus {
@@ -76,6 +109,15 @@ export interface ApiResponseClusterStatusResBody {
   status: ApiResponseStatus;
 }
 
+export interface ApiResponseConfigHistoryResBody {
+  data?: {
+    /** Stored versions, oldest first. */
+    versions: ConfigVersion[];
+  };
+  message: string;
+  status: ApiResponseStatus;
+}
+
 export interface ApiResponseEmptyApiResponse {
   /** @default null */
   data?: any;
@@ -121,10 +163,21 @@ export interface BackupNode {
   mac: string;
   machine_id: string;
   ssh: SshAccess;
+  tunnel_ip?: string;
 }
 
+export type BmcConfig =
+  | (RedfishConfig & {
+      protocol: "Redfish";
+    })
+  | (IpmiConfig & {
+      protocol: "Ipmi";
+    });
+
 export interface Cluster {
   backup_nodes: Partial<Record<string, BackupNode>>;
+  /** Datastore and vault snapshots on the backup nodes */
+  backups?: ClusterBackups;
   cluster_backup_wg_private_key?: string | null;
   cluster_nodes: Partial<Record<string, ClusterNode>>;
   encryption_key?: string | null;
@@ -132,7 +185,15 @@ export interface Cluster {
   install_state?: null | ClusterInstallState;
   k3s_token: string;
   kubeconfig?: string | null;
+  /** Node joins and removals in progress or failed, by machine id */
+  node_operations?: Partial<Record<string, NodeOperation>>;
+  /**
+   * Kairos image the nodes were last upgraded to, new nodes are upgraded
+   * to it after their install
+   */
+  os_image?: string | null;
   public_ip_config: Partial<Record<string, PublicIpConfig>>;
+  upgrade?: null | ClusterUpgrade;
   vault_secrets?: null | VaultSecrets;
   vip: Vip;
 }
@@ -145,14 +206,62 @@ export interface ClusterNode {
   primary: boolean;
 }
 
+export interface ClusterBackupNodeAddReqBody {
+  /** Inventory machine to store the snapshots on */
+  machine_id: string;
+}
+
+/** Snapshots of a cluster and how the last backup went */
+export interface ClusterBackups {
+  /**
+   * Unix seconds of the last backup, successful or not
+   * @format int64
+   * @min 0
+   */
+  last_attempt_at?: number | null;
+  /** Why the last backup failed */
+  last_error?: string | null;
+  restore?: null | SnapshotRestore;
+  snapshots: ClusterSnapshot[];
+}
+
+export interface ClusterNodeAddReqBody {
+  /** Inventory machine to join */
+  machine_id: string;
+}
+
 export interface ClusterSignalReqBody {
   cluster_id: string;
   signal: ClusterSignal;
 }
 
+export interface ClusterSnapshot {
+  /** Machine ids of the backup nodes holding the snapshot */
+  backup_nodes: string[];
+  /**
+   * Unix seconds
+   * @format int64
+   * @min 0
+   */
+  created_at: number;
+  kind: ClusterSnapshotKind;
+  name: string;
+  /**
+   * Encrypted size in bytes
+   * @format int64
+   * @min 0
+   */
+  size: number;
+}
+
 export interface ClusterStatus {
+  /** Datastore and vault snapshots on the backup nodes */
+  backups: ClusterBackups;
   install_state?: null | ClusterInstallState;
+  /** Node joins and removals in progress or failed, by machine id */
+  node_operations: Partial<Record<string, NodeOperation>>;
   running_state?: null | ClusterRunningState;
+  upgrade?: null | ClusterUpgrade;
 }
 
 export interface ClusterStatusResBody {
@@ -160,6 +269,58 @@ export interface ClusterStatusResBody {
   status: ClusterStatus;
 }
 
+/** A rolling upgrade in progress or paused, reported through the cluster status */
+export interface ClusterUpgrade {
+  /**
+   * Set when the step failed; the upgrade pauses there and resumes when it
+   * is started again
+   */
+  error?: string | null;
+  /**
+   * Hostname of the node being upgraded, none while checking the cluster
+   * health before the first node or after the last one
+   */
+  node?: string | null;
+  /** Kairos image the nodes are upgraded to */
+  os_image: string;
+  previous_install_state?: null | ClusterInstallState;
+  step: ClusterUpgradeStep;
+  /** Hostnames of the nodes already running the image */
+  upgraded_nodes: string[];
+}
+
+export interface ClusterUpgradeReqBody {
+  /**
+   * k3s version to upgrade to, e.g. `v1.31.1+k3s1`, with the configured
+   * OS and Kairos version
+   */
+  k3s_version?: string | null;
+  /** Kairos image to upgrade to, takes precedence over `k3s_version` */
+  os_image?: string | null;
+}
+
+export interface ConfigHistoryResBody {
+  /** Stored versions, oldest first. */
+  versions: ConfigVersion[];
+}
+
+/** A stored config version, without its content. */
+export interface ConfigVersion {
+  /** Whether this is the version the manager currently runs on. */
+  current: boolean;
+  /**
+   * Unix seconds at which the version was written.
+   * @format int64
+   * @min 0
+   */
+  saved_at: number;
+  /**
+   * @format int64
+   * @min 0
+   */
+  version: number;
+}
+
 /** @default null */
 export type EmptyApiResponse = any;
 
@@ -185,8 +346,22 @@ export interface InternalIps {
   legacy: string;
 }
 
+export interface IpmiConfig {
+  host: string;
+  password: string;
+  /**
+   * @format int32
+   * @min 0
+   */
+  port?: number;
+  username: string;
+}
+
 export interface LocalMachineProviderPhysicalConfig {
+  bmc?: null | BmcConfig;
   mac_address: string;
+  /** Used to switch the machine on when there is no BMC */
+  wake_on_lan?: null | WakeOnLanConfig;
 }
 
 export interface LocalMachineProviderQemuConfig {
@@ -209,6 +384,8 @@ export interface Machine {
   install?: null | MachineInstall;
   mac?: string | null;
   machine_type: MachineType;
+  /** How a LocalPhysical machine is switched on and off */
+  power?: null | PhysicalPowerConfig;
   /** @format Ipv6Addr */
   public_ip?: string;
   /** @format Ipv4Addr */
@@ -265,6 +442,19 @@ export interface ManagerConfig {
   machines: Partial<Record<string, Machine>>;
 }
 
+/** A node join or removal in progress, reported through the cluster status */
+export interface NodeOperation {
+  /** Set when the step failed; the operation stops there and can be retried */
+  error?: string | null;
+  kind: NodeOperationKind;
+  step: NodeOperationStep;
+}
+
+export interface PhysicalPowerConfig {
+  bmc?: null | BmcConfig;
+  wake_on_lan?: null | WakeOnLanConfig;
+}
+
 export interface PixiecoreBootConfig {
   cmdline: string;
   initrd: string[];
@@ -297,6 +487,21 @@ export interface PublicIpVmProxy {
   wg_keys: WgKeys;
 }
 
+export interface RedfishConfig {
+  /** Most BMCs ship a self-signed certificate. */
+  accept_invalid_certs?: boolean;
+  password: string;
+  username: string;
+  /** Base URL of the BMC, e.g. `https://10.0.0.10`. */
+  url: string;
+}
+
+/** A restore in progress, or the failed last one */
+export interface SnapshotRestore {
+  error?: string | null;
+  snapshot: string;
+}
+
 export interface SshAccess {
   remote_hostname?: string | null;
   remote_public_key?: string | null;
@@ -329,6 +534,19 @@ export interface VncWebsocket {
   url: string;
 }
 
+export interface WakeOnLanConfig {
+  /**
+   * Where the magic packet is sent, usually the broadcast address of the
+   * machine's network.
+   */
+  broadcast_address?: string;
+  /**
+   * @format int32
+   * @min 0
+   */
+  port?: number;
+}
+
 export interface WgKeys {
   local_wg_private_key: string;
   local_wg_public_key: string;
@@ -663,6 +881,116 @@ export class Api<
         ...params,
       }),
 
+    /**
+     * No description
+     *
+     * @name AddClusterBackupNode
+     * @request POST:/api/clusters/{id}/backup_nodes
+     */
+    addClusterBackupNode: (
+      id: string,
+      data: ClusterBackupNodeAddReqBody,
+      params: RequestParams = {},
+    ) =>
+      this.request<ApiResponseEmptyApiResponse, any>({
+        path: `/api/clusters/${id}/backup_nodes`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * No description
+     *
+     * @name BackupCluster
+     * @request POST:/api/clusters/{id}/backups
+     */
+    backupCluster: (id: string, params: RequestParams = {}) =>
+      this.request<ApiResponseEmptyApiResponse, any>({
+        path: `/api/clusters/${id}/backups`,
+        method: "POST",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * No description
+     *
+     * @name RestoreClusterSnapshot
+     * @request POST:/api/clusters/{id}/backups/{snapshot}/restore
+     */
+    restoreClusterSnapshot: (
+      id: string,
+      snapshot: string,
+      params: RequestParams = {},
+    ) =>
+      this.request<ApiResponseEmptyApiResponse, any>({
+        path: `/api/clusters/${id}/backups/${snapshot}/restore`,
+        method: "POST",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * No description
+     *
+     * @name AddClusterNode
+     * @request POST:/api/clusters/{id}/nodes
+     */
+    addClusterNode: (
+      id: string,
+      data: ClusterNodeAddReqBody,
+      params: RequestParams = {},
+    ) =>
+      this.request<ApiResponseEmptyApiResponse, any>({
+        path: `/api/clusters/${id}/nodes`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * No description
+     *
+     * @name RemoveClusterNode
+     * @request DELETE:/api/clusters/{id}/nodes/{machine_id}
+     */
+    removeClusterNode: (
+      id: string,
+      machineId: string,
+      params: RequestParams = {},
+    ) =>
+      this.request<ApiResponseEmptyApiResponse, any>({
+        path: `/api/clusters/${id}/nodes/${machineId}`,
+        method: "DELETE",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * No description
+     *
+     * @name UpgradeCluster
+     * @request POST:/api/clusters/{id}/upgrade
+     */
+    upgradeCluster: (
+      id: string,
+      data: ClusterUpgradeReqBody,
+      params: RequestParams = {},
+    ) =>
+      this.request<ApiResponseEmptyApiResponse, any>({
+        path: `/api/clusters/${id}/upgrade`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
     /**
      * No description
      *
@@ -692,6 +1020,34 @@ export class Api<
         ...params,
       }),
 
+    /**
+     * No description
+     *
+     * @name GetConfigHistory
+     * @request GET:/api/config/history
+     */
+    getConfigHistory: (params: RequestParams = {}) =>
+      this.request<ApiResponseConfigHistoryResBody, any>({
+        path: `/api/config/history`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * No description
+     *
+     * @name RestoreConfigVersion
+     * @request POST:/api/config/history/{version}/restore
+     */
+    restoreConfigVersion: (version: number, params: RequestParams = {}) =>
+      this.request<ApiResponseEmptyApiResponse, any>({
+        path: `/api/config/history/${version}/restore`,
+        method: "POST",
+        format: "json",
+        ...params,
+      }),
+
     /**
      * No description
      *
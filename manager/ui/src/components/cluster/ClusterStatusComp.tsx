@@ -20,6 +20,42 @@ export default class ClusterStatusComp extends PureComponent<
         >
             <span>Install state: {this.props.clusterStatus?.install_state}</span>
             <span>Running state: {this.props.clusterStatus?.running_state}</span>
+            {this.props.clusterStatus?.upgrade && (
+                <span>
+                    Upgrade to {this.props.clusterStatus.upgrade.os_image}:{" "}
+                    {this.props.clusterStatus.upgrade.step}{" "}
+                    {this.props.clusterStatus.upgrade.node ?? ``}
+                    {this.props.clusterStatus.upgrade.error &&
+                        ` failed: ${this.props.clusterStatus.upgrade.error}`}
+                </span>
+            )}
+            {this.props.clusterStatus?.backups && (
+                <span>
+                    Backups: {this.props.clusterStatus.backups.snapshots.length} snapshots
+                    {this.props.clusterStatus.backups.last_attempt_at &&
+                        `, last ${new Date(
+                            this.props.clusterStatus.backups.last_attempt_at * 1000
+                        ).toLocaleString()}`}
+                    {this.props.clusterStatus.backups.last_error &&
+                        ` failed: ${this.props.clusterStatus.backups.last_error}`}
+                </span>
+            )}
+            {this.props.clusterStatus?.backups?.restore && (
+                <span>
+                    Restore of {this.props.clusterStatus.backups.restore.snapshot}
+                    {this.props.clusterStatus.backups.restore.error
+                        ? ` failed: ${this.props.clusterStatus.backups.restore.error}`
+                        : ` running`}
+                </span>
+            )}
+            {Object.entries(this.props.clusterStatus?.node_operations ?? {}).map(
+                ([machineId, operation]) => (
+                    <span key={machineId}>
+                        {operation?.kind} {machineId}: {operation?.step}
+                        {operation?.error && ` failed: ${operation.error}`}
+                    </span>
+                )
+            )}
         </div>
     );
 }
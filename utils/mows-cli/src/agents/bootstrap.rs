@@ -17,6 +17,9 @@
 //!   - bind-mount of `${HOME} → ${HOME}` rw (so workspace 9p paths the user
 //!     sends in CreateVmRequest resolve identically inside the container)
 //!   - bind-mount of `${HOME}/.claude → /host-creds:ro` (claude tokens)
+//!
+//! Only the `local` context is auto-started; a remote context (see
+//! `super::context`) is just probed, since its container runs elsewhere.
 
 use std::fs;
 use std::io::Write;
@@ -29,12 +32,20 @@ use colored::Colorize;
 
 use crate::error::{MowsError, Result};
 
+use super::context::ResolvedContext;
+
 const SUPERVISOR_URL: &str = "http://127.0.0.1:7878";
 const IMAGE_TAG: &str = "mows-vm-supervisor:dev";
 pub(super) const CONTAINER_NAME: &str = "mows-vm-supervisor";
+/// Prefix of the supervisor's personal access tokens (`/v1/tokens`).
+const PERSONAL_TOKEN_PREFIX: &str = "mows_pat_";
 
 pub fn ensure_supervisor_running() -> Result<()> {
-    if probe_healthz(Duration::from_millis(400)) {
+    let context = super::context::active()?;
+    if !context.local {
+        return ensure_remote_reachable(&context);
+    }
+    if probe_healthz(SUPERVISOR_URL, Duration::from_millis(400)) {
         return Ok(());
     }
 
@@ -73,7 +84,7 @@ pub fn ensure_supervisor_running() -> Result<()> {
     docker_run(&state_dir, &home)?;
 
     let deadline = Instant::now() + Duration::from_secs(30);
-    while !probe_healthz(Duration::from_millis(400)) {
+    while !probe_healthz(SUPERVISOR_URL, Duration::from_millis(400)) {
         if Instant::now() > deadline {
             return Err(MowsError::Config(format!(
                 "supervisor container failed to become healthy within 30s — see `docker logs {CONTAINER_NAME}`"
@@ -89,11 +100,30 @@ pub fn ensure_supervisor_running() -> Result<()> {
     Ok(())
 }
 
-fn probe_healthz(timeout: Duration) -> bool {
+/// A remote supervisor must already be up, behind its tunnel if it has one.
+fn ensure_remote_reachable(context: &ResolvedContext) -> Result<()> {
+    context.ensure_tunnel_up()?;
+    if probe_healthz(&context.url, Duration::from_secs(3)) {
+        return Ok(());
+    }
+    Err(MowsError::Config(format!(
+        "supervisor of context {:?} at {} is unreachable",
+        context.name, context.url
+    )))
+}
+
+/// True when the context's tunnel is up and its supervisor answers
+/// `/v1/healthz`. Never auto-starts anything; used by `--all-contexts` to
+/// skip dead ones.
+pub(super) fn is_reachable(context: &ResolvedContext) -> bool {
+    context.ensure_tunnel_up().is_ok() && probe_healthz(&context.url, Duration::from_secs(3))
+}
+
+fn probe_healthz(base_url: &str, timeout: Duration) -> bool {
     let client = reqwest::blocking::Client::builder().timeout(timeout).build();
     let Ok(client) = client else { return false };
     matches!(
-        client.get(format!("{SUPERVISOR_URL}/v1/healthz")).send(),
+        client.get(format!("{base_url}/v1/healthz")).send(),
         Ok(r) if r.status().is_success()
     )
 }
@@ -377,6 +407,12 @@ fn docker_run(state_dir: &Path, home: &str) -> Result<()> {
         "MOWS_VM_SUPERVISOR_API_TOKEN",
     ] {
         if let Ok(value) = std::env::var(key) {
+            // A personal access token in MOWS_VM_SUPERVISOR_API_TOKEN is
+            // for this CLI only; handing it to the container would make it
+            // the supervisor's static admin token.
+            if value.starts_with(PERSONAL_TOKEN_PREFIX) {
+                continue;
+            }
             args.push("-e".into());
             args.push(format!("{key}={value}"));
         }
@@ -1,8 +1,9 @@
 //! Tiny HTTP client for the mows-vm-supervisor.
 //!
-//! v1: blocking HTTP over `127.0.0.1:7878` with a bearer token. Unix-socket
-//! transport will land alongside auto-start support for the supervisor
-//! container — see `.plans/agent-vm/PLAN.md`.
+//! v1: blocking HTTP with a bearer token, against the supervisor of the
+//! active context (see `super::context`) — `127.0.0.1:7878` for the local
+//! container. Unix-socket transport will land alongside auto-start support
+//! for the supervisor container — see `.plans/agent-vm/PLAN.md`.
 
 use std::time::Duration;
 
@@ -11,7 +12,7 @@ use serde::Serialize;
 
 use crate::error::{MowsError, Result};
 
-const DEFAULT_BASE_URL: &str = "http://127.0.0.1:7878";
+use super::context::{self, ResolvedContext};
 
 pub struct SupervisorClient {
     base_url: String,
@@ -20,17 +21,21 @@ pub struct SupervisorClient {
 }
 
 impl SupervisorClient {
+    /// Client for the active context (`--context`, `currentContext`, or
+    /// the local container configured through `MOWS_VM_SUPERVISOR_*`).
     pub fn from_env() -> Result<Self> {
-        let base_url = std::env::var("MOWS_VM_SUPERVISOR_URL")
-            .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
-        let token = read_token_from_env()?;
+        Self::for_context(&context::active()?)
+    }
+
+    pub fn for_context(context: &ResolvedContext) -> Result<Self> {
+        context.ensure_tunnel_up()?;
         let http = reqwest::blocking::Client::builder()
             .timeout(Duration::from_secs(30))
             .build()
             .map_err(|e| MowsError::Config(format!("failed to build http client: {e}")))?;
         Ok(Self {
-            base_url,
-            token,
+            base_url: context.url.clone(),
+            token: context.token.clone(),
             http,
         })
     }
@@ -67,6 +72,54 @@ impl SupervisorClient {
             .map_err(|e| MowsError::Config(format!("supervisor POST {path}: bad json: {e}")))
     }
 
+    pub fn put<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        self.send_json(reqwest::Method::PUT, path, body)
+    }
+
+    pub fn patch<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        self.send_json(reqwest::Method::PATCH, path, body)
+    }
+
+    fn send_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: &B,
+    ) -> Result<T> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut req = self.http.request(method.clone(), &url).json(body);
+        if let Some(t) = &self.token {
+            req = req.bearer_auth(t);
+        }
+        let resp = req
+            .send()
+            .map_err(|e| MowsError::Config(format!("supervisor {method} {path}: {e}")))?;
+        if !resp.status().is_success() {
+            return Err(supervisor_error(&url, resp));
+        }
+        resp.json::<T>()
+            .map_err(|e| MowsError::Config(format!("supervisor {method} {path}: bad json: {e}")))
+    }
+
+    /// GET a non-JSON body (task transcripts, output files). `query` pairs
+    /// are URL-encoded by reqwest.
+    pub fn get_bytes(&self, path: &str, query: &[(&str, &str)]) -> Result<Vec<u8>> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut req = self.http.get(&url).query(query);
+        if let Some(t) = &self.token {
+            req = req.bearer_auth(t);
+        }
+        let resp = req
+            .send()
+            .map_err(|e| MowsError::Config(format!("supervisor GET {path}: {e}")))?;
+        if !resp.status().is_success() {
+            return Err(supervisor_error(&url, resp));
+        }
+        resp.bytes()
+            .map(|body| body.to_vec())
+            .map_err(|e| MowsError::Config(format!("supervisor GET {path}: {e}")))
+    }
+
     pub fn delete(&self, path: &str) -> Result<()> {
         let url = format!("{}{}", self.base_url, path);
         let mut req = self.http.delete(&url);
@@ -83,18 +136,6 @@ impl SupervisorClient {
     }
 }
 
-fn read_token_from_env() -> Result<Option<String>> {
-    if let Ok(path) = std::env::var("MOWS_VM_SUPERVISOR_API_TOKEN_FILE") {
-        let raw = std::fs::read_to_string(&path).map_err(|e| {
-            MowsError::Config(format!(
-                "failed to read MOWS_VM_SUPERVISOR_API_TOKEN_FILE={path}: {e}"
-            ))
-        })?;
-        return Ok(Some(raw.trim().to_string()));
-    }
-    Ok(std::env::var("MOWS_VM_SUPERVISOR_API_TOKEN").ok())
-}
-
 fn supervisor_error(url: &str, resp: reqwest::blocking::Response) -> MowsError {
     let status = resp.status();
     let body = resp.text().unwrap_or_default();
@@ -5,13 +5,16 @@
 //! supervisor over SSH and exposed via a websocket IO stream. One VM can
 //! host many agents.
 
+use std::io::{IsTerminal, Read};
 use std::process::Command;
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::{MowsError, Result};
+use crate::package_manager::compose::config::MowsConfig;
 
 use super::client::SupervisorClient;
+use super::context::{SupervisorContext, LOCAL_CONTEXT};
 
 // ---------------------------------------------------------------------------
 // VM lifecycle (POST /v1/vms etc.)
@@ -19,6 +22,8 @@ use super::client::SupervisorClient;
 
 #[derive(Debug, Serialize)]
 struct CreateVmRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preset: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -27,9 +32,170 @@ struct CreateVmRequest {
     cpus: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     memory_mb: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network_policy: Option<NetworkPolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workspace_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_timeout_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_lifetime_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_action: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    mounts: Vec<VmMount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    agent_kind: Option<String>,
     detach: bool,
 }
 
+/// An extra host directory shared into the VM (`--mount`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VmMount {
+    pub host_path: String,
+    pub guest_path: String,
+    #[serde(default = "default_mount_mode")]
+    pub mode: String,
+}
+
+fn default_mount_mode() -> String {
+    "ro".to_string()
+}
+
+impl VmMount {
+    /// Parse `HOST:GUEST[:ro|rw]`. A relative host path is taken from the
+    /// current directory; the supervisor checks it against its allowlist.
+    fn parse(spec: &str) -> Result<Self> {
+        let (rest, mode) = match spec.rsplit_once(':') {
+            Some((rest, mode @ ("ro" | "rw"))) => (rest, mode),
+            _ => (spec, "ro"),
+        };
+        let Some((host, guest)) = rest
+            .rsplit_once(':')
+            .filter(|(host, guest)| !host.is_empty() && !guest.is_empty())
+        else {
+            return Err(MowsError::Config(format!(
+                "--mount {spec:?}: expected HOST:GUEST[:ro|rw]"
+            )));
+        };
+        let host_path = std::env::current_dir()
+            .map_err(|e| MowsError::io("getting current directory", e))?
+            .join(host);
+        Ok(Self {
+            host_path: host_path.to_string_lossy().into_owned(),
+            guest_path: guest.to_string(),
+            mode: mode.to_string(),
+        })
+    }
+}
+
+/// The VM-shaping flags `vms run` and `agents run` share. Explicit values
+/// win over the `--preset`'s, which win over the supervisor's defaults.
+#[derive(Debug, Default)]
+pub struct VmFlags {
+    pub preset: Option<String>,
+    pub name: Option<String>,
+    pub cpus: Option<u32>,
+    pub memory_mb: Option<u32>,
+    /// Guest image name (`--image`).
+    pub image: Option<String>,
+    pub no_workspace: bool,
+    pub workspace_mode: Option<String>,
+    /// `--mount HOST:GUEST[:ro|rw]`, repeatable.
+    pub mounts: Vec<String>,
+}
+
+/// The parts of a supervisor preset the CLI acts on itself; the
+/// supervisor applies the rest when the request names the preset.
+#[derive(Debug, Deserialize)]
+struct VmPreset {
+    /// `false` behaves like `--no-workspace`.
+    #[serde(default = "default_mount_cwd")]
+    mount_cwd: bool,
+    #[serde(default)]
+    agent_kind: Option<String>,
+}
+
+fn default_mount_cwd() -> bool {
+    true
+}
+
+impl Default for VmPreset {
+    fn default() -> Self {
+        Self {
+            mount_cwd: true,
+            agent_kind: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VmDefaults {
+    /// Absent on supervisors that predate presets.
+    #[serde(default)]
+    presets: std::collections::BTreeMap<String, VmPreset>,
+}
+
+/// Look `name` up in `GET /v1/vms/defaults`, or the no-op preset when no
+/// `--preset` was given.
+fn fetch_preset(client: &SupervisorClient, name: Option<&str>) -> Result<VmPreset> {
+    let Some(name) = name else {
+        return Ok(VmPreset::default());
+    };
+    let mut defaults: VmDefaults = client.get("/v1/vms/defaults")?;
+    defaults.presets.remove(name).ok_or_else(|| {
+        let known: Vec<&str> = defaults.presets.keys().map(String::as_str).collect();
+        MowsError::Config(if known.is_empty() {
+            format!("unknown preset {name:?}: the supervisor config defines none")
+        } else {
+            format!("unknown preset {name:?}; available: {}", known.join(", "))
+        })
+    })
+}
+
+/// `--idle-timeout`, `--max-lifetime` and `--idle-action` of `vms run`.
+/// Unset flags leave the choice to the supervisor's `vm_defaults`.
+#[derive(Debug, Default)]
+pub struct IdleFlags {
+    pub idle_timeout: Option<String>,
+    pub max_lifetime: Option<String>,
+    pub idle_action: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NetworkPolicy {
+    pub mode: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_domains: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_cidrs: Vec<String>,
+}
+
+impl NetworkPolicy {
+    /// Build the policy for `vms run`. `None` leaves the choice to the
+    /// supervisor's `default_network_policy`; `--allow` without
+    /// `--network` implies `allowlist`. Entries that start with an IP
+    /// address are CIDRs, everything else is a domain.
+    pub fn from_flags(mode: Option<String>, allow: Vec<String>) -> Option<Self> {
+        if mode.is_none() && allow.is_empty() {
+            return None;
+        }
+        let (allow_cidrs, allow_domains) = allow.into_iter().partition(|entry: &String| {
+            entry
+                .split('/')
+                .next()
+                .is_some_and(|addr| addr.parse::<std::net::IpAddr>().is_ok())
+        });
+        Some(Self {
+            mode: mode.unwrap_or_else(|| "allowlist".into()),
+            allow_domains,
+            allow_cidrs,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[allow(dead_code)]
 pub struct VmSummary {
@@ -41,41 +207,127 @@ pub struct VmSummary {
     pub started_at: String,
     pub exited_at: Option<String>,
     pub exit_code: Option<i64>,
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Absent on supervisors that predate network policies.
+    #[serde(default)]
+    pub network_policy: Option<NetworkPolicy>,
+    /// Absent on supervisors that predate workspace modes.
+    #[serde(default)]
+    pub workspace_mode: Option<String>,
+    #[serde(default)]
+    pub workspace_branch: Option<String>,
+    /// Absent (like the limits below) on supervisors without idle
+    /// detection; `None` also means the limit is off.
+    #[serde(default)]
+    pub idle_timeout_secs: Option<i64>,
+    #[serde(default)]
+    pub max_lifetime_secs: Option<i64>,
+    #[serde(default)]
+    pub idle_action: Option<String>,
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Absent on supervisors without extra mounts.
+    #[serde(default)]
+    pub mounts: Vec<VmMount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockedEgressAttempt {
+    at: String,
+    host: String,
+    port: u16,
+    reason: String,
 }
 
 pub fn vm_run(
-    name: Option<String>,
-    cpus: Option<u32>,
-    memory_mb: Option<u32>,
-    no_workspace: bool,
+    flags: VmFlags,
+    network_policy: Option<NetworkPolicy>,
+    idle: IdleFlags,
 ) -> Result<()> {
-    let cwd = if no_workspace { None } else { current_cwd()? };
+    let idle_timeout_secs = idle.idle_timeout.as_deref().map(parse_limit_secs).transpose()?;
+    let max_lifetime_secs = idle.max_lifetime.as_deref().map(parse_limit_secs).transpose()?;
+    let mounts = parse_mounts(&flags.mounts)?;
     super::bootstrap::ensure_supervisor_running()?;
     let client = SupervisorClient::from_env()?;
+    let preset = fetch_preset(&client, flags.preset.as_deref())?;
+    let cwd = workspace_cwd(
+        flags.no_workspace || !preset.mount_cwd,
+        flags.workspace_mode.as_deref(),
+    )?;
     let summary: VmSummary = client.post(
         "/v1/vms",
         &CreateVmRequest {
-            name,
+            preset: flags.preset,
+            name: flags.name,
             cwd,
-            cpus,
-            memory_mb,
+            cpus: flags.cpus,
+            memory_mb: flags.memory_mb,
+            image: flags.image,
+            network_policy,
+            workspace_mode: flags.workspace_mode,
+            idle_timeout_secs,
+            max_lifetime_secs,
+            idle_action: idle.idle_action,
+            mounts,
+            agent_kind: None,
             detach: true,
         },
     )?;
     println!("vm {} ({}) started — status: {}", summary.name, summary.id, summary.status);
+    if let Some(preset) = &summary.preset {
+        println!("  preset:          {preset}");
+    }
+    if let Some(image) = &summary.image {
+        println!("  image:           {image}");
+    }
+    for mount in &summary.mounts {
+        println!(
+            "  mount ({}):      {} -> {}",
+            mount.mode, mount.host_path, mount.guest_path
+        );
+    }
+    if let Some(timeout) = summary.idle_timeout_secs {
+        let action = summary.idle_action.as_deref().unwrap_or("suspend");
+        println!("  when idle:       {action} after {}", format_duration_secs(timeout));
+    }
+    if let Some(lifetime) = summary.max_lifetime_secs {
+        println!("  stops after:     {}", format_duration_secs(lifetime));
+    }
+    if let Some(policy) = summary.network_policy.as_ref().filter(|p| p.mode != "open") {
+        println!("  network policy:  {}", describe_network_policy(policy));
+    }
+    if let Some(mode) = summary.workspace_mode.as_deref().filter(|mode| *mode != "rw") {
+        match &summary.workspace_branch {
+            Some(branch) => println!("  workspace:       {mode} on branch {branch}"),
+            None => println!("  workspace:       {mode}"),
+        }
+        println!("  review changes:  mows vms diff {}", summary.id);
+    }
     println!("  attach via ssh:  mows vms attach {}", summary.id);
     println!("  add an agent:    mows agents create {} --kind claude", summary.id);
     Ok(())
 }
 
-pub fn vm_list() -> Result<()> {
-    super::bootstrap::ensure_supervisor_running()?;
-    let client = SupervisorClient::from_env()?;
-    let vms: Vec<VmSummary> = client.get("/v1/vms")?;
-    println!("{:<12} {:<32} {:<8} {:<6} {:<28}", "VM ID", "NAME", "STATUS", "SSH", "STARTED");
-    for v in vms {
+pub fn vm_list(all_contexts: bool) -> Result<()> {
+    let vms: Vec<(Option<String>, VmSummary)> = if all_contexts {
+        get_from_all_contexts("/v1/vms")?
+    } else {
+        super::bootstrap::ensure_supervisor_running()?;
+        let client = SupervisorClient::from_env()?;
+        let vms: Vec<VmSummary> = client.get("/v1/vms")?;
+        vms.into_iter().map(|v| (None, v)).collect()
+    };
+    if all_contexts {
+        print!("{:<16} ", "CONTEXT");
+    }
+    println!("{:<12} {:<32} {:<9} {:<6} {:<28}", "VM ID", "NAME", "STATUS", "SSH", "STARTED");
+    for (context, v) in vms {
+        if let Some(context) = context {
+            print!("{context:<16} ");
+        }
         println!(
-            "{:<12} {:<32} {:<8} {:<6} {:<28}",
+            "{:<12} {:<32} {:<9} {:<6} {:<28}",
             shorten(&v.id, 12),
             v.name,
             v.status,
@@ -96,6 +348,10 @@ pub fn vm_attach(id_or_name: String) -> Result<()> {
             vm.id, vm.status
         )));
     }
+    if vm.status == "suspended" {
+        eprintln!("vm {} was suspended while idle — resuming", vm.name);
+    }
+    // Fetching the ssh info resumes a suspended VM.
     let ssh_info = fetch_ssh_info(&client, &vm.id)?;
     ssh_attach(&vm.id, &ssh_info)
 }
@@ -109,9 +365,43 @@ pub fn vm_logs(id_or_name: String, _follow: bool) -> Result<()> {
         vm.name, vm.id, vm.status, vm.started_at
     );
     println!("(websocket console at /v1/vms/{}/console — open in the web UI)", vm.id);
+    let Some(policy) = vm.network_policy.as_ref().filter(|p| p.mode != "open") else {
+        return Ok(());
+    };
+    println!();
+    println!("network policy: {}", describe_network_policy(policy));
+    let blocked: Vec<BlockedEgressAttempt> =
+        client.get(&format!("/v1/vms/{}/egress-log", vm.id))?;
+    if blocked.is_empty() {
+        println!("no blocked connections");
+        return Ok(());
+    }
+    println!("{:<28} {:<40} REASON", "BLOCKED AT", "DESTINATION");
+    for attempt in blocked {
+        println!(
+            "{:<28} {:<40} {}",
+            attempt.at,
+            shorten(&format!("{}:{}", attempt.host, attempt.port), 40),
+            attempt.reason,
+        );
+    }
     Ok(())
 }
 
+fn describe_network_policy(policy: &NetworkPolicy) -> String {
+    let allowed: Vec<&str> = policy
+        .allow_domains
+        .iter()
+        .chain(&policy.allow_cidrs)
+        .map(String::as_str)
+        .collect();
+    if allowed.is_empty() {
+        policy.mode.clone()
+    } else {
+        format!("{} ({})", policy.mode, allowed.join(", "))
+    }
+}
+
 pub fn vm_stop(id_or_name: String, _force: bool) -> Result<()> {
     super::bootstrap::ensure_supervisor_running()?;
     let client = SupervisorClient::from_env()?;
@@ -122,6 +412,21 @@ pub fn vm_stop(id_or_name: String, _force: bool) -> Result<()> {
     Ok(())
 }
 
+pub fn vm_clone(id_or_name: String, name: Option<String>) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    let clone: VmSummary = client.post(
+        &format!("/v1/vms/{}/clone", vm.id),
+        &serde_json::json!({ "name": name }),
+    )?;
+    println!(
+        "vm {} ({}) cloned from {} — status: {}",
+        clone.name, clone.id, vm.name, clone.status
+    );
+    Ok(())
+}
+
 pub fn vm_rm(id_or_name: String) -> Result<()> {
     super::bootstrap::ensure_supervisor_running()?;
     let client = SupervisorClient::from_env()?;
@@ -131,11 +436,331 @@ pub fn vm_rm(id_or_name: String) -> Result<()> {
     Ok(())
 }
 
-pub fn vm_build_image(rebuild: bool) -> Result<()> {
+#[derive(Debug, Deserialize)]
+struct VmShare {
+    username: String,
+    created_at: String,
+}
+
+/// `mows vms share` — share a VM with `username`, or list its shares.
+pub fn vm_share(id_or_name: String, username: Option<String>) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    match username {
+        Some(username) => {
+            let _: VmShare = client.put(
+                &format!("/v1/vms/{}/shares/{username}", vm.id),
+                &serde_json::json!({}),
+            )?;
+            println!("vm {} shared with {username}", vm.name);
+        }
+        None => {
+            let shares: Vec<VmShare> = client.get(&format!("/v1/vms/{}/shares", vm.id))?;
+            if shares.is_empty() {
+                println!("vm {} is not shared", vm.name);
+            }
+            for share in shares {
+                println!("{:<24} {}", share.username, share.created_at);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `mows vms unshare` — revoke `username`'s access to a VM.
+pub fn vm_unshare(id_or_name: String, username: String) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    client.delete(&format!("/v1/vms/{}/shares/{username}", vm.id))?;
+    println!("vm {} no longer shared with {username}", vm.name);
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct VmForward {
+    guest_port: u16,
+    host_port: u16,
+}
+
+/// `GUEST[:HOST]` → `(guest, host)`.
+fn parse_forward(raw: &str) -> Result<(u16, Option<u16>)> {
+    let invalid =
+        || MowsError::Config(format!("invalid port forward {raw:?}; use GUEST or GUEST:HOST"));
+    let port = |s: &str| s.parse::<u16>().ok().filter(|p| *p != 0).ok_or_else(invalid);
+    match raw.split_once(':') {
+        Some((guest, host)) => Ok((port(guest)?, Some(port(host)?))),
+        None => Ok((port(raw)?, None)),
+    }
+}
+
+/// `mows vms forward` — forward a guest port to the supervisor host, or
+/// list the VM's forwards.
+pub fn vm_forward(id_or_name: String, ports: Option<String>) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    match ports {
+        Some(ports) => {
+            let (guest_port, host_port) = parse_forward(&ports)?;
+            let forward: VmForward = client.post(
+                &format!("/v1/vms/{}/forwards", vm.id),
+                &serde_json::json!({ "guest_port": guest_port, "host_port": host_port }),
+            )?;
+            println!(
+                "vm {}: guest port {} -> 127.0.0.1:{}",
+                vm.name, forward.guest_port, forward.host_port
+            );
+        }
+        None => {
+            let forwards: Vec<VmForward> = client.get(&format!("/v1/vms/{}/forwards", vm.id))?;
+            if forwards.is_empty() {
+                println!("vm {} has no port forwards", vm.name);
+            }
+            for forward in forwards {
+                println!("{:<8} -> 127.0.0.1:{}", forward.guest_port, forward.host_port);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `mows vms unforward` — remove a port forward.
+pub fn vm_unforward(id_or_name: String, guest_port: u16) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    client.delete(&format!("/v1/vms/{}/forwards/{guest_port}", vm.id))?;
+    println!("vm {}: guest port {guest_port} no longer forwarded", vm.name);
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct VmStats {
+    vm_id: String,
+    name: String,
+    suspended: bool,
+    cpu_seconds: Option<f64>,
+    memory_rss_bytes: Option<u64>,
+    disk_overlay_bytes: Option<u64>,
+    net_sent_bytes: Option<u64>,
+    net_received_bytes: Option<u64>,
+    agents_running: i64,
+}
+
+/// "1.4G" / "312M" / "18K" / "512B" — compact enough for a `top` column.
+fn human_bytes(bytes: Option<u64>) -> String {
+    let Some(bytes) = bytes else {
+        return "-".into();
+    };
+    let mut value = bytes as f64;
+    for unit in ["B", "K", "M", "G"] {
+        if value < 1024.0 {
+            return if unit == "B" || value >= 100.0 {
+                format!("{value:.0}{unit}")
+            } else {
+                format!("{value:.1}{unit}")
+            };
+        }
+        value /= 1024.0;
+    }
+    format!("{value:.1}T")
+}
+
+/// `mows vms top` — redraw running VMs' resource use every `interval_secs`,
+/// busiest first. CPU% is the `cpu_seconds` delta between polls, so the
+/// first frame shows `-`.
+pub fn vm_top(interval_secs: u64) -> Result<()> {
+    if interval_secs == 0 {
+        return Err(MowsError::Config("--interval must be at least 1 second".into()));
+    }
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let interval = std::time::Duration::from_secs(interval_secs);
+    let mut previous: std::collections::HashMap<String, (f64, std::time::Instant)> =
+        std::collections::HashMap::new();
+    loop {
+        let stats: Vec<VmStats> = client.get("/v1/vms/stats")?;
+        let now = std::time::Instant::now();
+        let mut rows: Vec<(Option<f64>, VmStats)> = stats
+            .into_iter()
+            .map(|s| {
+                let percent = match (s.cpu_seconds, previous.get(&s.vm_id)) {
+                    (Some(cpu), Some((prev_cpu, prev_at))) => {
+                        let wall = now.duration_since(*prev_at).as_secs_f64();
+                        (wall > 0.0).then(|| (cpu - prev_cpu).max(0.0) / wall * 100.0)
+                    }
+                    _ => None,
+                };
+                (percent, s)
+            })
+            .collect();
+        previous = rows
+            .iter()
+            .filter_map(|(_, s)| s.cpu_seconds.map(|cpu| (s.vm_id.clone(), (cpu, now))))
+            .collect();
+        rows.sort_by(|a, b| b.0.unwrap_or(-1.0).total_cmp(&a.0.unwrap_or(-1.0)));
+
+        // Clear the screen and home the cursor.
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "{:<12} {:<32} {:>6} {:>7} {:>7} {:>7} {:>7} {:>6}",
+            "VM ID", "NAME", "CPU%", "RSS", "DISK", "NET TX", "NET RX", "AGENTS"
+        );
+        for (percent, s) in &rows {
+            let cpu = match percent {
+                _ if s.suspended => "idle".to_string(),
+                Some(p) => format!("{p:.1}"),
+                None => "-".to_string(),
+            };
+            println!(
+                "{:<12} {:<32} {:>6} {:>7} {:>7} {:>7} {:>7} {:>6}",
+                shorten(&s.vm_id, 12),
+                s.name,
+                cpu,
+                human_bytes(s.memory_rss_bytes),
+                human_bytes(s.disk_overlay_bytes),
+                human_bytes(s.net_sent_bytes),
+                human_bytes(s.net_received_bytes),
+                s.agents_running,
+            );
+        }
+        if rows.is_empty() {
+            println!("no running VMs");
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceApplyResult {
+    paths: Vec<String>,
+    merged_commit: Option<String>,
+}
+
+/// `mows vms diff` — print the VM's workspace changes as a unified diff.
+pub fn vm_diff(id_or_name: String) -> Result<()> {
+    use std::io::Write;
+
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    let diff = client.get_bytes(&format!("/v1/vms/{}/diff", vm.id), &[])?;
+    if diff.is_empty() {
+        eprintln!("no changes in vm {}", vm.name);
+        return Ok(());
+    }
+    std::io::stdout()
+        .write_all(&diff)
+        .map_err(|e| MowsError::io("writing diff to stdout", e))
+}
+
+/// `mows vms apply` — merge the VM's workspace changes into its cwd.
+pub fn vm_apply(id_or_name: String) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    let result: WorkspaceApplyResult =
+        client.post(&format!("/v1/vms/{}/apply", vm.id), &serde_json::json!({}))?;
+    if result.paths.is_empty() {
+        println!("no changes to apply from vm {}", vm.name);
+        return Ok(());
+    }
+    for path in &result.paths {
+        println!("  {path}");
+    }
+    match &result.merged_commit {
+        Some(commit) => println!(
+            "applied {} file(s) from vm {} (now at {})",
+            result.paths.len(),
+            vm.name,
+            shorten(commit, 12)
+        ),
+        None => println!("applied {} file(s) from vm {}", result.paths.len(), vm.name),
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// VM snapshots (/v1/vms/:id/snapshots)
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Serialize)]
+struct CreateSnapshotRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+}
+
+pub fn vm_snapshot_create(id_or_name: String, name: Option<String>) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    let snapshot: SnapshotSummary = client.post(
+        &format!("/v1/vms/{}/snapshots", vm.id),
+        &CreateSnapshotRequest { name },
+    )?;
+    println!("snapshot {} ({}) of vm {} taken", snapshot.name, snapshot.id, vm.id);
+    Ok(())
+}
+
+pub fn vm_snapshot_list(id_or_name: String) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    let snapshots: Vec<SnapshotSummary> =
+        client.get(&format!("/v1/vms/{}/snapshots", vm.id))?;
+    println!("{:<12} {:<32} {:<28}", "SNAPSHOT ID", "NAME", "CREATED");
+    for s in snapshots {
+        println!(
+            "{:<12} {:<32} {:<28}",
+            shorten(&s.id, 12),
+            s.name,
+            s.created_at,
+        );
+    }
+    Ok(())
+}
+
+pub fn vm_snapshot_restore(id_or_name: String, snapshot: String) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    let snap = resolve_snapshot(&client, &vm.id, &snapshot)?;
+    let _: serde_json::Value = client.post(
+        &format!("/v1/vms/{}/snapshots/{}/restore", vm.id, snap.id),
+        &serde_json::json!({}),
+    )?;
+    println!("vm {} restored to snapshot {} ({})", vm.id, snap.name, snap.id);
+    Ok(())
+}
+
+pub fn vm_snapshot_rm(id_or_name: String, snapshot: String) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let vm = resolve_vm(&client, &id_or_name)?;
+    let snap = resolve_snapshot(&client, &vm.id, &snapshot)?;
+    client.delete(&format!("/v1/vms/{}/snapshots/{}", vm.id, snap.id))?;
+    println!("snapshot {} removed", snap.id);
+    Ok(())
+}
+
+pub fn vm_build_image(distro: String, flavor: String, rebuild: bool) -> Result<()> {
     let builder_dir = locate_builder_dir()?;
-    println!("building Alpine guest image via {}/build.sh", builder_dir.display());
+    println!(
+        "building {distro} ({flavor}) guest image via {}/build.sh",
+        builder_dir.display()
+    );
     let mut cmd = Command::new("bash");
     cmd.arg(builder_dir.join("build.sh"));
+    cmd.args(["--distro", &distro, "--flavor", &flavor]);
     cmd.current_dir(&builder_dir);
     if rebuild {
         cmd.env("BUILDKIT_INLINE_CACHE", "0");
@@ -273,17 +898,19 @@ pub struct AgentSummary {
 
 /// `mows agents run` — spawn a fresh VM, start an agent inside it, and
 /// (unless `--detach`) tail the agent's stdout/stdin via the websocket.
-pub fn agent_run(
-    name: Option<String>,
-    kind: Option<String>,
-    cpus: Option<u32>,
-    memory_mb: Option<u32>,
-    no_workspace: bool,
-    detach: bool,
-) -> Result<()> {
-    let cwd = if no_workspace { None } else { current_cwd()? };
+pub fn agent_run(flags: VmFlags, kind: Option<String>, detach: bool) -> Result<()> {
+    let mounts = parse_mounts(&flags.mounts)?;
     super::bootstrap::ensure_supervisor_running()?;
     let client = SupervisorClient::from_env()?;
+    let preset = fetch_preset(&client, flags.preset.as_deref())?;
+    let cwd = workspace_cwd(
+        flags.no_workspace || !preset.mount_cwd,
+        flags.workspace_mode.as_deref(),
+    )?;
+    let name = flags.name;
+    let kind_name = kind
+        .or(preset.agent_kind)
+        .unwrap_or_else(|| "claude".to_string());
 
     // Step 1 — spawn a VM. We always pass `detach: true` here because the
     // supervisor's POST /v1/vms returns once QEMU is launched; the readiness
@@ -291,10 +918,20 @@ pub fn agent_run(
     let vm: VmSummary = client.post(
         "/v1/vms",
         &CreateVmRequest {
+            preset: flags.preset,
             name: name.clone(),
             cwd,
-            cpus,
-            memory_mb,
+            cpus: flags.cpus,
+            memory_mb: flags.memory_mb,
+            image: flags.image,
+            network_policy: None,
+            workspace_mode: flags.workspace_mode,
+            idle_timeout_secs: None,
+            max_lifetime_secs: None,
+            idle_action: None,
+            mounts,
+            // Picks up the kind's default mounts.
+            agent_kind: Some(kind_name.clone()),
             detach: true,
         },
     )?;
@@ -302,7 +939,6 @@ pub fn agent_run(
     let vm = wait_until_running(&client, &vm.id)?;
 
     // Step 2 — create the agent inside the running VM.
-    let kind_name = kind.unwrap_or_else(|| "claude".to_string());
     let agent: AgentSummary = client.post(
         &format!("/v1/vms/{}/agents", vm.id),
         &CreateAgentRequest {
@@ -359,21 +995,32 @@ pub fn agent_create(
     attach_agent_ws(&agent.id)
 }
 
-pub fn agent_list(vm_filter: Option<String>) -> Result<()> {
-    super::bootstrap::ensure_supervisor_running()?;
-    let client = SupervisorClient::from_env()?;
-    let path = if let Some(vm) = &vm_filter {
-        let resolved = resolve_vm(&client, vm)?;
-        format!("/v1/vms/{}/agents", resolved.id)
+pub fn agent_list(vm_filter: Option<String>, all_contexts: bool) -> Result<()> {
+    let agents: Vec<(Option<String>, AgentSummary)> = if all_contexts {
+        get_from_all_contexts("/v1/agents")?
     } else {
-        "/v1/agents".to_string()
+        super::bootstrap::ensure_supervisor_running()?;
+        let client = SupervisorClient::from_env()?;
+        let path = if let Some(vm) = &vm_filter {
+            let resolved = resolve_vm(&client, vm)?;
+            format!("/v1/vms/{}/agents", resolved.id)
+        } else {
+            "/v1/agents".to_string()
+        };
+        let agents: Vec<AgentSummary> = client.get(&path)?;
+        agents.into_iter().map(|a| (None, a)).collect()
     };
-    let agents: Vec<AgentSummary> = client.get(&path)?;
+    if all_contexts {
+        print!("{:<16} ", "CONTEXT");
+    }
     println!(
         "{:<12} {:<12} {:<28} {:<10} {:<10} {:<28}",
         "AGENT ID", "VM ID", "NAME", "KIND", "STATUS", "STARTED"
     );
-    for a in agents {
+    for (context, a) in agents {
+        if let Some(context) = context {
+            print!("{context:<16} ");
+        }
         println!(
             "{:<12} {:<12} {:<28} {:<10} {:<10} {:<28}",
             shorten(&a.id, 12),
@@ -400,30 +1047,187 @@ pub fn agent_attach(id_or_name: String) -> Result<()> {
     attach_agent_ws(&agent.id)
 }
 
+/// `[seconds, code, data]` — one event line of an asciicast v2 recording.
+type CastEvent = (f64, String, String);
+
+/// Fetch an agent's session recording and parse its event lines. The
+/// header line (terminal size, start time) isn't needed for replay.
+fn fetch_recording(client: &SupervisorClient, agent: &AgentSummary) -> Result<Vec<CastEvent>> {
+    let raw = client.get_bytes(&format!("/v1/agents/{}/recording", agent.id), &[])?;
+    let text = String::from_utf8_lossy(&raw);
+    text.lines()
+        .skip(1)
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                MowsError::Config(format!(
+                    "recording of agent {} has a malformed event on line {}: {e}",
+                    agent.id,
+                    index + 2
+                ))
+            })
+        })
+        .collect()
+}
+
+/// `mows agents logs <id>` — everything the agent printed, without timing.
 pub fn agent_logs(id_or_name: String, _follow: bool) -> Result<()> {
+    use std::io::Write;
+
     super::bootstrap::ensure_supervisor_running()?;
     let client = SupervisorClient::from_env()?;
     let agent = resolve_agent(&client, &id_or_name)?;
-    println!(
-        "agent {} ({}) in vm {}: status={}, started_at={}",
-        agent.name, agent.id, agent.vm_id, agent.status, agent.started_at
-    );
-    println!("(scrollback is replayed when you `mows agents attach {}`)", agent.id);
+    let events = fetch_recording(&client, &agent)?;
+    let mut stdout = std::io::stdout().lock();
+    for (_, code, data) in &events {
+        if code == "o" {
+            stdout
+                .write_all(data.as_bytes())
+                .map_err(|e| MowsError::io("writing agent output to stdout", e))?;
+        }
+    }
+    stdout
+        .flush()
+        .map_err(|e| MowsError::io("writing agent output to stdout", e))?;
     Ok(())
 }
 
-pub fn agent_stop(id_or_name: String, _force: bool) -> Result<()> {
+/// `mows agents replay <id>` — play the session recording back in this
+/// terminal with its original pacing, sped up by `speed`.
+pub fn agent_replay(id_or_name: String, speed: f64) -> Result<()> {
+    use std::io::Write;
+
+    if !(speed.is_finite() && speed > 0.0) {
+        return Err(MowsError::Config(format!(
+            "--speed must be a positive number, got {speed}"
+        )));
+    }
     super::bootstrap::ensure_supervisor_running()?;
     let client = SupervisorClient::from_env()?;
     let agent = resolve_agent(&client, &id_or_name)?;
-    let _: serde_json::Value = client.post(
-        &format!("/v1/agents/{}/stop", agent.id),
+    let events = fetch_recording(&client, &agent)?;
+    let mut stdout = std::io::stdout().lock();
+    let mut previous = 0.0;
+    for (time, code, data) in &events {
+        // Only output is replayed; input shows up in the output as the
+        // session's echo, resizes can't be applied to this terminal, and
+        // markers are for the web player.
+        if code != "o" {
+            continue;
+        }
+        let delay = (time - previous).max(0.0) / speed;
+        previous = *time;
+        std::thread::sleep(std::time::Duration::from_secs_f64(delay));
+        stdout
+            .write_all(data.as_bytes())
+            .and_then(|()| stdout.flush())
+            .map_err(|e| MowsError::io("writing replay to stdout", e))?;
+    }
+    eprintln!("\r\n(end of recording of agent {})", agent.id);
+    Ok(())
+}
+
+pub fn agent_stop(id_or_name: String, _force: bool) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let agent = resolve_agent(&client, &id_or_name)?;
+    let _: serde_json::Value = client.post(
+        &format!("/v1/agents/{}/stop", agent.id),
         &serde_json::json!({}),
     )?;
     println!("agent {} stopped", agent.id);
     Ok(())
 }
 
+/// Longest single `/v1/agents/{id}/wait` request; stays under the
+/// client's 30 s request timeout.
+const WAIT_POLL_SECS: u64 = 25;
+
+#[derive(Deserialize)]
+struct AgentWaitResponse {
+    outcome: String,
+    agent: AgentSummary,
+}
+
+/// `mows agents wait <id>` — long-poll the supervisor until the agent
+/// exits or (without `--exit`) waits for input.
+pub fn agent_wait(
+    id_or_name: String,
+    exit: bool,
+    timeout: Option<String>,
+    notify: bool,
+) -> Result<()> {
+    let deadline = timeout
+        .as_deref()
+        .map(parse_duration_secs)
+        .transpose()?
+        .map(|secs| std::time::Instant::now() + std::time::Duration::from_secs(secs));
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let agent = resolve_agent(&client, &id_or_name)?;
+    let until = if exit { "exit" } else { "attention" };
+    let response = loop {
+        let poll_secs = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(std::time::Instant::now());
+                if left.is_zero() {
+                    return Err(MowsError::Config(format!(
+                        "timed out waiting for agent {}",
+                        agent.id
+                    )));
+                }
+                left.as_secs().clamp(1, WAIT_POLL_SECS)
+            }
+            None => WAIT_POLL_SECS,
+        };
+        let response: AgentWaitResponse = client.get(&format!(
+            "/v1/agents/{}/wait?until={until}&timeout_secs={poll_secs}",
+            agent.id
+        ))?;
+        if response.outcome != "timed_out" {
+            break response;
+        }
+    };
+    let message = match response.outcome.as_str() {
+        "exited" => match response.agent.exit_code {
+            Some(code) => format!(
+                "agent {} {} (exit code {code})",
+                response.agent.name, response.agent.status
+            ),
+            None => format!("agent {} {}", response.agent.name, response.agent.status),
+        },
+        _ => format!("agent {} is waiting for input", response.agent.name),
+    };
+    println!("{message}");
+    if notify {
+        desktop_notify("mows", &message);
+    }
+    Ok(())
+}
+
+/// Best-effort desktop notification; failures (no notification daemon,
+/// headless box) are ignored.
+fn desktop_notify(title: &str, message: &str) {
+    let status = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(message),
+            applescript_string(title)
+        );
+        Command::new("osascript").args(["-e", &script]).status()
+    } else {
+        Command::new("notify-send").args([title, message]).status()
+    };
+    if let Err(e) = status {
+        tracing::debug!(error = %e, "desktop notification failed");
+    }
+}
+
+fn applescript_string(raw: &str) -> String {
+    format!("\"{}\"", raw.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 pub fn agent_rm(id_or_name: String) -> Result<()> {
     super::bootstrap::ensure_supervisor_running()?;
     let client = SupervisorClient::from_env()?;
@@ -433,13 +1237,300 @@ pub fn agent_rm(id_or_name: String) -> Result<()> {
     Ok(())
 }
 
+// ---------------------------------------------------------------------------
+// Headless tasks (POST /v1/tasks etc.)
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Serialize)]
+struct CreateTaskRequest {
+    kind: String,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vm_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TaskSummary {
+    pub id: String,
+    pub name: String,
+    pub kind: String,
+    pub status: String,
+    pub timeout_secs: i64,
+    pub exit_code: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+}
+
+impl TaskSummary {
+    fn is_finished(&self) -> bool {
+        !matches!(self.status.as_str(), "queued" | "running")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskOutputFile {
+    path: String,
+    size_bytes: u64,
+}
+
+/// `mows agents exec` — queue a headless run of `kind` on the prompt in
+/// `prompt_file`. With `--wait`, poll until it finishes and download the
+/// results like `mows agents results` would.
+pub fn agent_exec(
+    kind: Option<String>,
+    prompt_file: std::path::PathBuf,
+    timeout: Option<String>,
+    name: Option<String>,
+    vm: Option<String>,
+    no_workspace: bool,
+    wait: bool,
+) -> Result<()> {
+    let prompt = std::fs::read_to_string(&prompt_file)
+        .map_err(|e| MowsError::io(format!("reading prompt file {}", prompt_file.display()), e))?;
+    let timeout_secs = timeout.as_deref().map(parse_duration_secs).transpose()?;
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let (vm_id, cwd) = match &vm {
+        Some(vm) => (Some(resolve_vm(&client, vm)?.id), None),
+        None if no_workspace => (None, None),
+        None => (None, current_cwd()?),
+    };
+    let task: TaskSummary = client.post(
+        "/v1/tasks",
+        &CreateTaskRequest {
+            kind: kind.unwrap_or_else(|| "claude".to_string()),
+            prompt,
+            name,
+            cwd,
+            vm_id,
+            timeout_secs,
+        },
+    )?;
+    println!(
+        "task {} ({}) queued — kind: {}, timeout: {}s",
+        task.name, task.id, task.kind, task.timeout_secs
+    );
+    if !wait {
+        println!("fetch results with: mows agents results {}", task.id);
+        return Ok(());
+    }
+    let task = wait_until_finished(&client, &task.id)?;
+    download_results(&client, &task, None)
+}
+
+pub fn agent_tasks() -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let tasks: Vec<TaskSummary> = client.get("/v1/tasks")?;
+    println!(
+        "{:<12} {:<28} {:<8} {:<10} {:<6} {:<28}",
+        "TASK ID", "NAME", "KIND", "STATUS", "EXIT", "CREATED"
+    );
+    for t in tasks {
+        println!(
+            "{:<12} {:<28} {:<8} {:<10} {:<6} {:<28}",
+            shorten(&t.id, 12),
+            t.name,
+            t.kind,
+            t.status,
+            t.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".into()),
+            t.created_at,
+        );
+    }
+    Ok(())
+}
+
+/// `mows agents results <id>` — write the transcript and collected `/out`
+/// files of a task into a local directory.
+pub fn agent_results(id_or_name: String, output: Option<std::path::PathBuf>) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let task = resolve_task(&client, &id_or_name)?;
+    download_results(&client, &task, output)
+}
+
+pub fn agent_cancel(id_or_name: String) -> Result<()> {
+    super::bootstrap::ensure_supervisor_running()?;
+    let client = SupervisorClient::from_env()?;
+    let task = resolve_task(&client, &id_or_name)?;
+    let result: serde_json::Value = client.post(
+        &format!("/v1/tasks/{}/cancel", task.id),
+        &serde_json::json!({}),
+    )?;
+    let status = result.get("status").and_then(|s| s.as_str()).unwrap_or("cancelled");
+    println!("task {} {status}", task.id);
+    Ok(())
+}
+
+fn download_results(
+    client: &SupervisorClient,
+    task: &TaskSummary,
+    output: Option<std::path::PathBuf>,
+) -> Result<()> {
+    let dest = output.unwrap_or_else(|| std::path::Path::new("mows-results").join(&task.id));
+    std::fs::create_dir_all(&dest)
+        .map_err(|e| MowsError::io(format!("creating {}", dest.display()), e))?;
+
+    // A queued task has no transcript yet (404); anything else is real.
+    match client.get_bytes(&format!("/v1/tasks/{}/transcript", task.id), &[]) {
+        Ok(transcript) => {
+            let path = dest.join("transcript.log");
+            std::fs::write(&path, transcript)
+                .map_err(|e| MowsError::io(format!("writing {}", path.display()), e))?;
+        }
+        Err(_) if task.status == "queued" => {}
+        Err(e) => return Err(e),
+    }
+
+    let files: Vec<TaskOutputFile> = client.get(&format!("/v1/tasks/{}/outputs", task.id))?;
+    let out_dir = dest.join("out");
+    let mut total_bytes = 0u64;
+    for file in &files {
+        // The supervisor only lists plain relative paths; re-check anyway
+        // before joining onto a local directory.
+        let relative = std::path::Path::new(&file.path);
+        if !relative
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(MowsError::Config(format!(
+                "supervisor returned unsafe output path {:?}",
+                file.path
+            )));
+        }
+        let body = client.get_bytes(
+            &format!("/v1/tasks/{}/outputs/download", task.id),
+            &[("path", file.path.as_str())],
+        )?;
+        let path = out_dir.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| MowsError::io(format!("creating {}", parent.display()), e))?;
+        }
+        std::fs::write(&path, body)
+            .map_err(|e| MowsError::io(format!("writing {}", path.display()), e))?;
+        total_bytes += file.size_bytes;
+    }
+
+    match &task.finished_at {
+        Some(at) => println!("task {} ({}): {} at {at}", task.name, task.id, task.status),
+        None => println!("task {} ({}): {}", task.name, task.id, task.status),
+    }
+    if let Some(code) = task.exit_code {
+        println!("exit code: {code}");
+    }
+    if let Some(error) = &task.error {
+        println!("error: {error}");
+    }
+    if !task.is_finished() {
+        println!("task has not finished yet; re-run to fetch the final results");
+    }
+    println!(
+        "results in {} ({} output files, {total_bytes} bytes)",
+        dest.display(),
+        files.len()
+    );
+    Ok(())
+}
+
+fn wait_until_finished(client: &SupervisorClient, task_id: &str) -> Result<TaskSummary> {
+    use std::thread::sleep;
+    use std::time::Duration;
+    let mut last_status = String::new();
+    loop {
+        let task: TaskSummary = client.get(&format!("/v1/tasks/{task_id}"))?;
+        if task.status != last_status {
+            println!("task {task_id}: {}", task.status);
+            last_status = task.status.clone();
+        }
+        if task.is_finished() {
+            return Ok(task);
+        }
+        sleep(Duration::from_secs(2));
+    }
+}
+
+/// Parse `90`, `90s`, `30m`, `2h`, or combinations like `1h30m` into
+/// seconds. A bare number is seconds.
+fn parse_duration_secs(raw: &str) -> Result<u64> {
+    let invalid = || {
+        MowsError::Config(format!(
+            "invalid duration {raw:?}; use e.g. `90s`, `30m`, `2h`, `1h30m`"
+        ))
+    };
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for ch in raw.trim().chars() {
+        if ch.is_ascii_digit() {
+            digits.push(ch);
+            continue;
+        }
+        let unit = match ch {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+        total = total
+            .checked_add(value.checked_mul(unit).ok_or_else(invalid)?)
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+        total = total.checked_add(value).ok_or_else(invalid)?;
+    }
+    if total == 0 {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// Like [`parse_duration_secs`], but `0` is accepted and turns the limit
+/// off.
+fn parse_mounts(specs: &[String]) -> Result<Vec<VmMount>> {
+    specs.iter().map(|spec| VmMount::parse(spec)).collect()
+}
+
+fn parse_limit_secs(raw: &str) -> Result<u64> {
+    if raw.trim() == "0" {
+        return Ok(0);
+    }
+    parse_duration_secs(raw)
+}
+
+/// Render seconds the way [`parse_duration_secs`] reads them (`1h30m`).
+fn format_duration_secs(secs: i64) -> String {
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    let (hours, rest) = (rest / 3600, rest % 3600);
+    let (minutes, seconds) = (rest / 60, rest % 60);
+    let mut out = String::new();
+    for (value, unit) in [(days, 'd'), (hours, 'h'), (minutes, 'm'), (seconds, 's')] {
+        if value > 0 {
+            out.push_str(&format!("{value}{unit}"));
+        }
+    }
+    if out.is_empty() {
+        out.push_str("0s");
+    }
+    out
+}
+
 /// `mows agents ui` — auto-start the supervisor (if needed) and open the
 /// web UI in the system browser. With `--print`, just emit the URL so it
 /// can be piped into other tooling.
 pub fn agent_ui(print_only: bool) -> Result<()> {
     super::bootstrap::ensure_supervisor_running()?;
-    let url = std::env::var("MOWS_VM_SUPERVISOR_URL")
-        .unwrap_or_else(|_| "http://127.0.0.1:7878".to_string());
+    let url = super::context::active()?.url;
 
     if print_only {
         println!("{url}");
@@ -483,14 +1574,56 @@ pub fn agent_ui(print_only: bool) -> Result<()> {
 // User management (auth)
 // ---------------------------------------------------------------------------
 
+/// `--max-vms`, `--max-vcpus` and `--max-memory-mb` of `user add` /
+/// `user set`. Unset flags are left out of the request; `0` removes a cap.
+#[derive(Debug, Default, Serialize)]
+pub struct QuotaCaps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_vms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_vcpus: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u32>,
+}
+
 #[derive(Debug, Serialize)]
 struct CreateUserRequest {
     username: String,
     password: String,
     role: String,
+    #[serde(flatten)]
+    quota: QuotaCaps,
 }
 
-pub fn agent_user_add(username: String, role: String) -> Result<()> {
+#[derive(Debug, Serialize)]
+struct UpdateUserRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(flatten)]
+    quota: QuotaCaps,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuotaLimits {
+    max_vms: Option<i64>,
+    max_vcpus: Option<i64>,
+    max_memory_mb: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuotaUsage {
+    vms: i64,
+    vcpus: i64,
+    memory_mb: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuotaReport {
+    quota: QuotaLimits,
+    usage: QuotaUsage,
+}
+
+pub fn agent_user_add(username: String, role: String, quota: QuotaCaps) -> Result<()> {
     let password = rpassword::prompt_password(format!("password for {}: ", username))
         .unwrap_or_else(|_| String::new());
     if password.is_empty() {
@@ -499,7 +1632,7 @@ pub fn agent_user_add(username: String, role: String) -> Result<()> {
     let client = SupervisorClient::from_env()?;
     let resp: serde_json::Value = client.post(
         "/v1/users",
-        &CreateUserRequest { username, password, role },
+        &CreateUserRequest { username, password, role, quota },
     )?;
     println!("{}", serde_json::to_string_pretty(&resp).unwrap_or_default());
     Ok(())
@@ -512,6 +1645,33 @@ pub fn agent_user_list() -> Result<()> {
     Ok(())
 }
 
+pub fn agent_user_set(username: String, role: Option<String>, quota: QuotaCaps) -> Result<()> {
+    let client = SupervisorClient::from_env()?;
+    let resp: serde_json::Value = client.patch(
+        &format!("/v1/users/{username}"),
+        &UpdateUserRequest { role, quota },
+    )?;
+    println!("{}", serde_json::to_string_pretty(&resp).unwrap_or_default());
+    Ok(())
+}
+
+/// `mows agents user quota` — the caller's caps next to current use.
+pub fn agent_user_quota() -> Result<()> {
+    let client = SupervisorClient::from_env()?;
+    let report: QuotaReport = client.get("/v1/users/me/quota")?;
+    let cap = |limit: Option<i64>| limit.map_or_else(|| "unlimited".to_string(), |n| n.to_string());
+    println!("{:<10} {:>8} {:>10}", "", "USED", "LIMIT");
+    println!("{:<10} {:>8} {:>10}", "vms", report.usage.vms, cap(report.quota.max_vms));
+    println!("{:<10} {:>8} {:>10}", "vcpus", report.usage.vcpus, cap(report.quota.max_vcpus));
+    println!(
+        "{:<10} {:>8} {:>10}",
+        "memory_mb",
+        report.usage.memory_mb,
+        cap(report.quota.max_memory_mb)
+    );
+    Ok(())
+}
+
 pub fn agent_user_passwd(_username: String) -> Result<()> {
     Err(MowsError::Config(
         "password change endpoint not yet implemented in supervisor".into(),
@@ -525,6 +1685,360 @@ pub fn agent_user_rm(username: String) -> Result<()> {
     Ok(())
 }
 
+#[derive(Debug, Serialize)]
+struct PutSecretRequest {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SecretSummary {
+    name: String,
+    updated_at: String,
+}
+
+/// `mows agents secret set` — the value never appears in argv, so it
+/// stays out of shell history and `ps`.
+pub fn agent_secret_set(name: String, from_file: Option<std::path::PathBuf>) -> Result<()> {
+    let value = match from_file {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|e| MowsError::io(format!("reading {}", path.display()), e))?,
+        None if std::io::stdin().is_terminal() => {
+            rpassword::prompt_password(format!("value for {name}: "))
+                .map_err(|e| MowsError::io("reading secret value", e))?
+        }
+        None => {
+            let mut value = String::new();
+            std::io::stdin()
+                .read_to_string(&mut value)
+                .map_err(|e| MowsError::io("reading secret value from stdin", e))?;
+            value
+        }
+    };
+    let value = value.strip_suffix('\n').unwrap_or(&value).to_string();
+    if value.is_empty() {
+        return Err(MowsError::Config("secret value must not be empty".into()));
+    }
+    let client = SupervisorClient::from_env()?;
+    let stored: SecretSummary =
+        client.put(&format!("/v1/secrets/{name}"), &PutSecretRequest { value })?;
+    println!("secret {} set", stored.name);
+    Ok(())
+}
+
+pub fn agent_secret_list() -> Result<()> {
+    let client = SupervisorClient::from_env()?;
+    let secrets: Vec<SecretSummary> = client.get("/v1/secrets")?;
+    println!("{:<32} UPDATED", "NAME");
+    for secret in secrets {
+        println!("{:<32} {}", secret.name, secret.updated_at);
+    }
+    Ok(())
+}
+
+pub fn agent_secret_rm(name: String) -> Result<()> {
+    let client = SupervisorClient::from_env()?;
+    client.delete(&format!("/v1/secrets/{name}"))?;
+    println!("secret {name} removed");
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct CreateTokenRequest {
+    name: String,
+    scopes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_in_days: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenSummary {
+    id: String,
+    name: String,
+    username: String,
+    scopes: Vec<String>,
+    expires_at: String,
+    last_used_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedToken {
+    token: String,
+    #[serde(flatten)]
+    summary: TokenSummary,
+}
+
+/// `mows agents token create` — the token goes to stdout alone so it can
+/// be captured (`TOKEN=$(mows agents token create ci --scope vms:read)`);
+/// the details go to stderr.
+pub fn agent_token_create(
+    name: String,
+    scopes: Vec<String>,
+    expires_in_days: Option<u32>,
+    username: Option<String>,
+) -> Result<()> {
+    let client = SupervisorClient::from_env()?;
+    let created: CreatedToken = client.post(
+        "/v1/tokens",
+        &CreateTokenRequest {
+            name,
+            scopes,
+            expires_in_days,
+            username,
+        },
+    )?;
+    eprintln!(
+        "token {} ({}) for {}, scopes {}, expires {}; it is not shown again",
+        created.summary.id,
+        created.summary.name,
+        created.summary.username,
+        created.summary.scopes.join(","),
+        created.summary.expires_at
+    );
+    println!("{}", created.token);
+    Ok(())
+}
+
+pub fn agent_token_list() -> Result<()> {
+    let client = SupervisorClient::from_env()?;
+    let tokens: Vec<TokenSummary> = client.get("/v1/tokens")?;
+    println!(
+        "{:<36} {:<16} {:<16} {:<32} {:<26} LAST USED",
+        "ID", "NAME", "USER", "SCOPES", "EXPIRES"
+    );
+    for token in tokens {
+        println!(
+            "{:<36} {:<16} {:<16} {:<32} {:<26} {}",
+            token.id,
+            token.name,
+            token.username,
+            token.scopes.join(","),
+            token.expires_at,
+            token.last_used_at.as_deref().unwrap_or("never")
+        );
+    }
+    Ok(())
+}
+
+pub fn agent_token_revoke(id: String) -> Result<()> {
+    let client = SupervisorClient::from_env()?;
+    client.delete(&format!("/v1/tokens/{id}"))?;
+    println!("token {id} revoked");
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditPage {
+    entries: Vec<AuditEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditEntry {
+    at: String,
+    actor: String,
+    source_ip: Option<String>,
+    action: String,
+    target: Option<String>,
+    outcome: String,
+}
+
+/// `mows agents audit` — a page of the audit log, or all of it as JSON
+/// lines with `--jsonl`.
+pub fn agent_audit(
+    actor: Option<String>,
+    action: Option<String>,
+    target: Option<String>,
+    failed: bool,
+    since: Option<String>,
+    limit: u32,
+    jsonl: bool,
+) -> Result<()> {
+    use std::io::Write;
+
+    let client = SupervisorClient::from_env()?;
+    let limit = limit.to_string();
+    let mut query: Vec<(&str, &str)> = Vec::new();
+    for (key, value) in [
+        ("actor", &actor),
+        ("action", &action),
+        ("target", &target),
+        ("since", &since),
+    ] {
+        if let Some(value) = value {
+            query.push((key, value));
+        }
+    }
+    if failed {
+        query.push(("outcome", "failure"));
+    }
+    if jsonl {
+        let body = client.get_bytes("/v1/audit/export", &query)?;
+        std::io::stdout()
+            .write_all(&body)
+            .map_err(|e| MowsError::io("writing the audit log to stdout", e))?;
+        return Ok(());
+    }
+    query.push(("limit", &limit));
+    let body = client.get_bytes("/v1/audit", &query)?;
+    let page: AuditPage = serde_json::from_slice(&body)
+        .map_err(|e| MowsError::Config(format!("supervisor GET /v1/audit: bad json: {e}")))?;
+    println!(
+        "{:<20} {:<16} {:<16} {:<20} {:<36} OUTCOME",
+        "TIME", "ACTOR", "SOURCE", "ACTION", "TARGET"
+    );
+    for entry in page.entries {
+        println!(
+            "{:<20} {:<16} {:<16} {:<20} {:<36} {}",
+            // RFC 3339 down to the second; the supervisor writes UTC.
+            entry.at.get(..19).unwrap_or(&entry.at),
+            entry.actor,
+            entry.source_ip.as_deref().unwrap_or("unix socket"),
+            entry.action,
+            entry.target.as_deref().unwrap_or("-"),
+            entry.outcome
+        );
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Supervisor contexts (see `super::context`)
+// ---------------------------------------------------------------------------
+
+pub fn agent_context_list() -> Result<()> {
+    let config = MowsConfig::load()?;
+    let current = current_context_name(&config);
+    println!("{:<2}{:<20} URL", "", "NAME");
+    let marker = |name: &str| if name == current { "*" } else { "" };
+    println!(
+        "{:<2}{:<20} {} (auto-started container)",
+        marker(LOCAL_CONTEXT),
+        LOCAL_CONTEXT,
+        super::context::DEFAULT_SUPERVISOR_URL
+    );
+    for context in &config.agents.contexts {
+        println!("{:<2}{:<20} {}", marker(&context.name), context.name, context.url);
+    }
+    Ok(())
+}
+
+pub fn agent_context_current() -> Result<()> {
+    println!("{}", current_context_name(&MowsConfig::load()?));
+    Ok(())
+}
+
+pub fn agent_context_use(name: String) -> Result<()> {
+    MowsConfig::with_locked(|config| {
+        if name == LOCAL_CONTEXT {
+            config.agents.current_context = None;
+            return Ok(());
+        }
+        if config.agents.find(&name).is_none() {
+            return Err(MowsError::Config(format!(
+                "unknown supervisor context {name:?}; add it with `mows agents context add`"
+            )));
+        }
+        config.agents.current_context = Some(name.clone());
+        Ok(())
+    })?;
+    println!("switched to context {name}");
+    Ok(())
+}
+
+pub fn agent_context_add(
+    name: String,
+    url: String,
+    token_file: Option<std::path::PathBuf>,
+    token_stdin: bool,
+    wireguard_config: Option<std::path::PathBuf>,
+) -> Result<()> {
+    if name == LOCAL_CONTEXT {
+        return Err(MowsError::Config(format!(
+            "{LOCAL_CONTEXT:?} is the built-in context for the local container"
+        )));
+    }
+    let parsed = reqwest::Url::parse(&url)
+        .map_err(|e| MowsError::Config(format!("invalid --url {url:?}: {e}")))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(MowsError::Config(format!("--url must be http(s), got {url:?}")));
+    }
+    let token = if token_stdin {
+        let mut token = String::new();
+        std::io::stdin()
+            .read_to_string(&mut token)
+            .map_err(|e| MowsError::io("reading token from stdin", e))?;
+        let token = token.trim().to_string();
+        if token.is_empty() {
+            return Err(MowsError::Config("token on stdin is empty".into()));
+        }
+        Some(token)
+    } else {
+        None
+    };
+    let context = SupervisorContext {
+        name: name.clone(),
+        url: url.trim_end_matches('/').to_string(),
+        token,
+        token_file,
+        wireguard_config,
+    };
+    MowsConfig::with_locked(|config| {
+        config.agents.contexts.retain(|c| c.name != context.name);
+        config.agents.contexts.push(context);
+        Ok(())
+    })?;
+    println!("context {name} saved; select it with `mows agents context use {name}`");
+    Ok(())
+}
+
+pub fn agent_context_rm(name: String) -> Result<()> {
+    MowsConfig::with_locked(|config| {
+        let before = config.agents.contexts.len();
+        config.agents.contexts.retain(|c| c.name != name);
+        if config.agents.contexts.len() == before {
+            return Err(MowsError::Config(format!("no supervisor context named {name:?}")));
+        }
+        if config.agents.current_context.as_deref() == Some(name.as_str()) {
+            config.agents.current_context = None;
+        }
+        Ok(())
+    })?;
+    println!("context {name} removed");
+    Ok(())
+}
+
+fn current_context_name(config: &MowsConfig) -> String {
+    config
+        .agents
+        .current_context
+        .clone()
+        .unwrap_or_else(|| LOCAL_CONTEXT.to_string())
+}
+
+/// `GET path` against every context, for `--all-contexts`. Supervisors
+/// that are down or refuse the request are reported on stderr and
+/// skipped, so one dead build box doesn't hide the others.
+fn get_from_all_contexts<T: serde::de::DeserializeOwned>(
+    path: &str,
+) -> Result<Vec<(Option<String>, T)>> {
+    let mut rows = Vec::new();
+    for context in super::context::all()? {
+        if !super::bootstrap::is_reachable(&context) {
+            eprintln!(
+                "context {}: supervisor at {} is unreachable; skipped",
+                context.name, context.url
+            );
+            continue;
+        }
+        match SupervisorClient::for_context(&context).and_then(|c| c.get::<Vec<T>>(path)) {
+            Ok(items) => rows.extend(items.into_iter().map(|item| (Some(context.name.clone()), item))),
+            Err(e) => eprintln!("context {}: {e}; skipped", context.name),
+        }
+    }
+    Ok(rows)
+}
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
@@ -538,6 +2052,27 @@ fn current_cwd() -> Result<Option<String>> {
     ))
 }
 
+/// Directory to share with a new VM. `worktree` mode needs the top level
+/// of the git checkout, so resolve it from anywhere inside one.
+fn workspace_cwd(no_workspace: bool, workspace_mode: Option<&str>) -> Result<Option<String>> {
+    if no_workspace {
+        return Ok(None);
+    }
+    if workspace_mode == Some("worktree") {
+        let output = Command::new("git")
+            .args(["rev-parse", "--show-toplevel"])
+            .output()
+            .map_err(|e| MowsError::io("running git rev-parse", e))?;
+        if output.status.success() {
+            let toplevel = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !toplevel.is_empty() {
+                return Ok(Some(toplevel));
+            }
+        }
+    }
+    current_cwd()
+}
+
 fn resolve_vm(client: &SupervisorClient, id_or_name: &str) -> Result<VmSummary> {
     let vms: Vec<VmSummary> = client.get("/v1/vms")?;
     let matches: Vec<VmSummary> = vms
@@ -574,6 +2109,48 @@ fn resolve_agent(client: &SupervisorClient, id_or_name: &str) -> Result<AgentSum
     matches.into_iter().next().ok_or_else(|| MowsError::Config("unreachable".into()))
 }
 
+fn resolve_task(client: &SupervisorClient, id_or_name: &str) -> Result<TaskSummary> {
+    let tasks: Vec<TaskSummary> = client.get("/v1/tasks")?;
+    let matches: Vec<TaskSummary> = tasks
+        .into_iter()
+        .filter(|t| t.id == id_or_name || t.id.starts_with(id_or_name) || t.name == id_or_name)
+        .collect();
+    if matches.is_empty() {
+        return Err(MowsError::Config(format!("no task matches {id_or_name:?}")));
+    }
+    if matches.len() > 1 {
+        return Err(MowsError::Config(format!(
+            "ambiguous reference {id_or_name:?}: {} tasks match",
+            matches.len()
+        )));
+    }
+    matches.into_iter().next().ok_or_else(|| MowsError::Config("unreachable".into()))
+}
+
+fn resolve_snapshot(
+    client: &SupervisorClient,
+    vm_id: &str,
+    id_or_name: &str,
+) -> Result<SnapshotSummary> {
+    let snapshots: Vec<SnapshotSummary> = client.get(&format!("/v1/vms/{vm_id}/snapshots"))?;
+    let matches: Vec<SnapshotSummary> = snapshots
+        .into_iter()
+        .filter(|s| s.id == id_or_name || s.id.starts_with(id_or_name) || s.name == id_or_name)
+        .collect();
+    if matches.is_empty() {
+        return Err(MowsError::Config(format!(
+            "no snapshot of vm {vm_id} matches {id_or_name:?}"
+        )));
+    }
+    if matches.len() > 1 {
+        return Err(MowsError::Config(format!(
+            "ambiguous reference {id_or_name:?}: {} snapshots match",
+            matches.len()
+        )));
+    }
+    matches.into_iter().next().ok_or_else(|| MowsError::Config("unreachable".into()))
+}
+
 fn wait_until_running(client: &SupervisorClient, vm_id: &str) -> Result<VmSummary> {
     use std::thread::sleep;
     use std::time::{Duration, Instant};
@@ -0,0 +1,318 @@
+//! Supervisor contexts — kubectl-style named supervisor endpoints.
+//!
+//! The implicit `local` context is the supervisor container on this
+//! machine, auto-started by `bootstrap` and reached at
+//! `MOWS_VM_SUPERVISOR_URL` (default `http://127.0.0.1:7878`) with the
+//! token from `MOWS_VM_SUPERVISOR_API_TOKEN[_FILE]`, exactly as before
+//! contexts existed. Further contexts live under `agents.contexts` in
+//! `mows.yaml` and point at a remote supervisor, typically a build box
+//! reached over a WireGuard tunnel minted with `mows vms supervisor
+//! wg-config`:
+//!
+//! ```yaml
+//! agents:
+//!     currentContext: buildbox
+//!     contexts:
+//!         - name: buildbox
+//!           url: http://10.77.0.1:7878
+//!           tokenFile: ~/.config/mows.cloud/buildbox.token
+//!           wireguardConfig: ~/.config/wireguard/buildbox.conf
+//! ```
+//!
+//! `mows vms|agents --context <name>` picks a context for one command,
+//! `mows agents context use <name>` sets `currentContext`. Remote contexts
+//! are never auto-started and never get the local env token — a token for
+//! one supervisor is not sent to another. A context with a
+//! `wireguardConfig` is only contacted while that tunnel is up.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{MowsError, Result};
+use crate::package_manager::compose::config::MowsConfig;
+
+/// Name of the implicit context for the local supervisor container.
+pub const LOCAL_CONTEXT: &str = "local";
+
+/// Where the local supervisor container publishes its API.
+pub const DEFAULT_SUPERVISOR_URL: &str = "http://127.0.0.1:7878";
+
+/// One entry per network interface that is up, WireGuard ones included.
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// `--context` of the running command, set once by `main`.
+static SELECTED: OnceLock<String> = OnceLock::new();
+
+/// The `agents` section of `mows.yaml`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AgentsConfig {
+    /// Context used when `--context` is not given; `local` when unset.
+    #[serde(rename = "currentContext", default, skip_serializing_if = "Option::is_none")]
+    pub current_context: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contexts: Vec<SupervisorContext>,
+}
+
+impl AgentsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.current_context.is_none() && self.contexts.is_empty()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&SupervisorContext> {
+        self.contexts.iter().find(|c| c.name == name)
+    }
+}
+
+/// A remote supervisor endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SupervisorContext {
+    pub name: String,
+    /// Base URL of the supervisor API, e.g. `http://10.77.0.1:7878`.
+    pub url: String,
+    /// Bearer token (a personal access token from `mows agents token
+    /// create` on that supervisor). Prefer `tokenFile`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// File holding the bearer token; `~/` is expanded.
+    #[serde(rename = "tokenFile", default, skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>,
+    /// WireGuard config that routes to `url`. The supervisor is not
+    /// contacted while the tunnel it brings up is down.
+    #[serde(rename = "wireguardConfig", default, skip_serializing_if = "Option::is_none")]
+    pub wireguard_config: Option<PathBuf>,
+}
+
+/// A context resolved to what a client needs.
+#[derive(Debug, Clone)]
+pub struct ResolvedContext {
+    pub name: String,
+    pub url: String,
+    pub token: Option<String>,
+    /// The auto-started container on this machine.
+    pub local: bool,
+    pub wireguard_config: Option<PathBuf>,
+}
+
+impl ResolvedContext {
+    /// Fail unless the context's WireGuard tunnel, if it has one, is up.
+    /// `wg-quick` names the interface after the config file, so
+    /// `buildbox.conf` is up once `buildbox` exists.
+    pub fn ensure_tunnel_up(&self) -> Result<()> {
+        self.ensure_tunnel_up_in(Path::new(SYS_CLASS_NET))
+    }
+
+    fn ensure_tunnel_up_in(&self, net_dir: &Path) -> Result<()> {
+        let Some(config) = &self.wireguard_config else {
+            return Ok(());
+        };
+        let interface = config.file_stem().ok_or_else(|| {
+            MowsError::Config(format!(
+                "wireguardConfig {} of context {:?} is not a file",
+                config.display(),
+                self.name
+            ))
+        })?;
+        if net_dir.join(interface).exists() {
+            return Ok(());
+        }
+        Err(MowsError::Config(format!(
+            "WireGuard tunnel {} of context {:?} is down; bring it up with `wg-quick up {}`",
+            interface.to_string_lossy(),
+            self.name,
+            config.display()
+        )))
+    }
+}
+
+/// Record `--context` for this process. Later calls are ignored.
+pub fn select(name: Option<String>) {
+    if let Some(name) = name {
+        let _ = SELECTED.set(name);
+    }
+}
+
+/// The context this command talks to: `--context`, else `currentContext`,
+/// else `local`.
+pub fn active() -> Result<ResolvedContext> {
+    let config = MowsConfig::load()?;
+    let name = selected_name(SELECTED.get().map(String::as_str), &config.agents);
+    resolve(&config.agents, &name, &process_env)
+}
+
+fn selected_name(flag: Option<&str>, config: &AgentsConfig) -> String {
+    flag.or(config.current_context.as_deref())
+        .unwrap_or(LOCAL_CONTEXT)
+        .to_string()
+}
+
+/// `local` followed by every configured context, for `--all-contexts`.
+pub fn all() -> Result<Vec<ResolvedContext>> {
+    let config = MowsConfig::load()?;
+    let mut contexts = vec![local(&process_env)?];
+    for context in &config.agents.contexts {
+        contexts.push(resolve_remote(context)?);
+    }
+    Ok(contexts)
+}
+
+/// Looks up an environment variable; the process environment outside tests.
+type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn process_env(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+fn resolve(config: &AgentsConfig, name: &str, env: EnvLookup) -> Result<ResolvedContext> {
+    if name == LOCAL_CONTEXT {
+        return local(env);
+    }
+    let context = config.find(name).ok_or_else(|| {
+        MowsError::Config(format!(
+            "unknown supervisor context {name:?}; see `mows agents context list`"
+        ))
+    })?;
+    resolve_remote(context)
+}
+
+fn local(env: EnvLookup) -> Result<ResolvedContext> {
+    Ok(ResolvedContext {
+        name: LOCAL_CONTEXT.to_string(),
+        url: env("MOWS_VM_SUPERVISOR_URL").unwrap_or_else(|| DEFAULT_SUPERVISOR_URL.to_string()),
+        token: read_token_from_env(env)?,
+        local: true,
+        wireguard_config: None,
+    })
+}
+
+fn resolve_remote(context: &SupervisorContext) -> Result<ResolvedContext> {
+    let token = match (&context.token, &context.token_file) {
+        (Some(token), _) => Some(token.clone()),
+        (None, Some(path)) => {
+            let path = expand_home(path);
+            let raw = std::fs::read_to_string(&path).map_err(|e| {
+                MowsError::Config(format!(
+                    "failed to read tokenFile {} of context {:?}: {e}",
+                    path.display(),
+                    context.name
+                ))
+            })?;
+            Some(raw.trim().to_string())
+        }
+        (None, None) => None,
+    };
+    Ok(ResolvedContext {
+        name: context.name.clone(),
+        url: context.url.trim_end_matches('/').to_string(),
+        token,
+        local: false,
+        wireguard_config: context.wireguard_config.as_deref().map(expand_home),
+    })
+}
+
+fn read_token_from_env(env: EnvLookup) -> Result<Option<String>> {
+    if let Some(path) = env("MOWS_VM_SUPERVISOR_API_TOKEN_FILE") {
+        let raw = std::fs::read_to_string(&path).map_err(|e| {
+            MowsError::Config(format!(
+                "failed to read MOWS_VM_SUPERVISOR_API_TOKEN_FILE={path}: {e}"
+            ))
+        })?;
+        return Ok(Some(raw.trim().to_string()));
+    }
+    Ok(env("MOWS_VM_SUPERVISOR_API_TOKEN"))
+}
+
+/// `~/x` → `$HOME/x`; anything else unchanged.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var("HOME")) {
+        (Ok(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contexts_round_trip_through_mows_yaml() {
+        let yaml = "currentContext: buildbox\n\
+                    contexts:\n\
+                    - name: buildbox\n  \
+                      url: http://10.77.0.1:7878/\n  \
+                      token: mows_pat_abc\n  \
+                      wireguardConfig: /etc/wireguard/buildbox.conf\n";
+        let config: AgentsConfig = serde_yaml_neo::from_str(yaml).unwrap();
+        assert_eq!(config.current_context.as_deref(), Some("buildbox"));
+        let resolved = resolve(&config, "buildbox", &no_env).unwrap();
+        assert_eq!(resolved.url, "http://10.77.0.1:7878");
+        assert_eq!(resolved.token.as_deref(), Some("mows_pat_abc"));
+        assert!(!resolved.local);
+        assert!(resolve(&config, "nope", &no_env).is_err());
+        assert!(resolve(&config, LOCAL_CONTEXT, &no_env).unwrap().local);
+        assert!(AgentsConfig::default().is_empty());
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    fn remote(name: &str) -> SupervisorContext {
+        SupervisorContext {
+            name: name.to_string(),
+            url: format!("http://{name}:7878"),
+            token: None,
+            token_file: None,
+            wireguard_config: None,
+        }
+    }
+
+    #[test]
+    fn context_flag_beats_current_context_beats_local() {
+        let mut config = AgentsConfig {
+            current_context: None,
+            contexts: vec![remote("buildbox"), remote("gpu")],
+        };
+        assert_eq!(selected_name(None, &config), LOCAL_CONTEXT);
+        assert_eq!(selected_name(Some("gpu"), &config), "gpu");
+
+        config.current_context = Some("buildbox".to_string());
+        assert_eq!(selected_name(None, &config), "buildbox");
+        assert_eq!(selected_name(Some("gpu"), &config), "gpu");
+        assert_eq!(selected_name(Some(LOCAL_CONTEXT), &config), LOCAL_CONTEXT);
+
+        let resolved = resolve(&config, &selected_name(Some("gpu"), &config), &no_env).unwrap();
+        assert_eq!(resolved.url, "http://gpu:7878");
+    }
+
+    #[test]
+    fn remote_contexts_never_get_the_local_env_token() {
+        let env = |key: &str| {
+            (key == "MOWS_VM_SUPERVISOR_API_TOKEN").then(|| "local-admin-token".to_string())
+        };
+        let config = AgentsConfig {
+            current_context: None,
+            contexts: vec![remote("buildbox")],
+        };
+        let local = resolve(&config, LOCAL_CONTEXT, &env).unwrap();
+        let buildbox = resolve(&config, "buildbox", &env).unwrap();
+
+        assert_eq!(local.token.as_deref(), Some("local-admin-token"));
+        assert_eq!(buildbox.token, None);
+    }
+
+    #[test]
+    fn tunnel_must_be_up_before_the_context_is_used() {
+        let net_dir = tempfile::tempdir().unwrap();
+        let mut context = resolve_remote(&remote("buildbox")).unwrap();
+        context.ensure_tunnel_up_in(net_dir.path()).unwrap();
+
+        context.wireguard_config = Some(PathBuf::from("/etc/wireguard/buildbox.conf"));
+        let err = context.ensure_tunnel_up_in(net_dir.path()).unwrap_err();
+        assert!(err.to_string().contains("wg-quick up /etc/wireguard/buildbox.conf"));
+
+        std::fs::create_dir(net_dir.path().join("buildbox")).unwrap();
+        context.ensure_tunnel_up_in(net_dir.path()).unwrap();
+    }
+}
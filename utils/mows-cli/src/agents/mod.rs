@@ -8,10 +8,17 @@
 mod bootstrap;
 mod client;
 mod commands;
+pub(crate) mod context;
 
 pub use commands::{
-    agent_attach, agent_create, agent_list, agent_logs, agent_rm, agent_run, agent_stop, agent_ui,
-    agent_user_add, agent_user_list, agent_user_passwd, agent_user_rm,
-    vm_attach, vm_build_image, vm_list, vm_logs, vm_rm, vm_run, vm_stop, vm_supervisor_logs,
+    agent_attach, agent_cancel, agent_context_add, agent_context_current, agent_context_list,
+    agent_audit, agent_context_rm, agent_context_use, agent_create, agent_exec, agent_list, agent_logs, agent_replay,
+    agent_results, agent_rm, agent_run, agent_secret_list, agent_secret_rm, agent_secret_set,
+    agent_stop, agent_tasks, agent_token_create, agent_token_list, agent_token_revoke, agent_ui,
+    agent_wait,
+    agent_user_add, agent_user_list, agent_user_passwd, agent_user_quota, agent_user_rm,
+    agent_user_set, vm_apply, vm_attach, vm_clone, vm_build_image, vm_diff, vm_forward, vm_list, vm_logs, vm_rm, vm_run, vm_share, vm_snapshot_create,
+    vm_snapshot_list, vm_snapshot_restore, vm_snapshot_rm, vm_stop, vm_supervisor_logs,
     vm_supervisor_start, vm_supervisor_status, vm_supervisor_stop, vm_supervisor_wg_config,
+    vm_top, vm_unforward, vm_unshare, IdleFlags, NetworkPolicy, QuotaCaps, VmFlags,
 };
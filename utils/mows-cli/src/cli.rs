@@ -43,6 +43,9 @@ pub enum Commands {
     /// independent dockerd inside the guest. Agents run *inside* VMs (see
     /// `mows agents`) — multiple agents per VM are allowed.
     Vms {
+        /// Supervisor context to talk to (see `mows agents context`).
+        #[arg(long, global = true)]
+        context: Option<String>,
         #[command(subcommand)]
         command: VmsCommands,
     },
@@ -53,6 +56,9 @@ pub enum Commands {
     /// drop me into an agent shell"; `mows agents create <vm-id>` adds an
     /// extra agent to an existing VM.
     Agents {
+        /// Supervisor context to talk to (see `mows agents context`).
+        #[arg(long, global = true)]
+        context: Option<String>,
         #[command(subcommand)]
         command: AgentsCommands,
     },
@@ -103,7 +109,8 @@ pub enum Commands {
     /// Update mows to the latest version
     ///
     /// By default, downloads the latest pre-built binary from GitHub releases
-    /// and verifies its SHA256 checksum before installation.
+    /// and verifies its SHA256 checksum before installation. The checksum file
+    /// itself must carry a valid signature from the hardcoded trusted key.
     ///
     /// Use --build to clone the repository, verify its SSH signature, and
     /// build from source using Docker (requires git and docker).
@@ -123,6 +130,14 @@ pub enum Commands {
         /// download and --build modes.
         #[arg(long)]
         version: Option<String>,
+
+        /// Restore the binary that was installed before the last update
+        ///
+        /// Every update keeps the replaced binary next to the new one as
+        /// `mows.previous`. Rolling back swaps the two, recreates the `mpm`
+        /// symlink and rebuilds the agent guest image.
+        #[arg(long, conflicts_with_all = ["build", "version"])]
+        rollback: bool,
     },
 }
 
@@ -404,6 +419,8 @@ pub enum ToolCommands {
     ///
     /// Example: mows tools cargo-workspace-docker
     /// Example: mows tools cargo-workspace-docker --all
+    /// Example: mows tools cargo-workspace-docker --all --check
+    /// Example: mows tools cargo-workspace-docker --dockerfile
     #[command(name = "cargo-workspace-docker")]
     CargoWorkspaceDocker {
         /// Generate for all packages with docker-compose in the workspace
@@ -412,6 +429,14 @@ pub enum ToolCommands {
         /// Path to package (default: current directory)
         #[arg(short, long)]
         path: Option<PathBuf>,
+        /// Don't write anything; exit non-zero with a diff if a generated
+        /// file on disk is out of date
+        #[arg(long)]
+        check: bool,
+        /// Also generate cargo-workspace-docker.Dockerfile, a cargo-chef
+        /// multi-stage build using the workspace root as build context
+        #[arg(long)]
+        dockerfile: bool,
     },
 }
 
@@ -421,6 +446,10 @@ pub enum VmsCommands {
     /// `mows vms attach` to ssh in, or `mows agents run` to spawn an agent
     /// in one step.
     Run {
+        /// Start from a named preset in the supervisor config; the other
+        /// flags override its values.
+        #[arg(long)]
+        preset: Option<String>,
         /// Override the auto-generated VM name.
         #[arg(long)]
         name: Option<String>,
@@ -430,15 +459,55 @@ pub enum VmsCommands {
         /// Memory in megabytes (default from supervisor config).
         #[arg(long)]
         memory: Option<u32>,
+        /// Guest image: an image-builder distro (`alpine`, `debian`, …) or
+        /// a name from the supervisor's `images:` (default from its config).
+        #[arg(long)]
+        image: Option<String>,
         /// Skip mounting the current working directory into the VM.
         #[arg(long)]
         no_workspace: bool,
+        /// Share another host directory: `HOST:GUEST[:ro|rw]` (read-only
+        /// unless `:rw`). The host path must be under one of the
+        /// supervisor's `host_mounts.allowed_prefixes`. Repeatable.
+        #[arg(long = "mount", value_name = "HOST:GUEST[:MODE]")]
+        mounts: Vec<String>,
+        /// Outbound network policy: `open`, `deny-all` or `allowlist`
+        /// (default from supervisor config; `allowlist` when --allow is set).
+        #[arg(long)]
+        network: Option<String>,
+        /// Allow outbound traffic to a domain (`example.com`,
+        /// `*.example.com`) or CIDR (`10.0.0.0/8`). Repeatable.
+        #[arg(long = "allow", value_name = "DOMAIN_OR_CIDR")]
+        allow: Vec<String>,
+        /// How the working directory is shared: `rw` (default, edits land
+        /// directly), `overlay` (private copy) or `worktree` (new git
+        /// worktree on a `mows/<name>` branch). See `mows vms diff/apply`.
+        #[arg(long)]
+        workspace_mode: Option<String>,
+        /// Apply `--idle-action` once the VM has been idle this long
+        /// (`30m`, `2h`; `0` never). Idle: nothing attached, no agents or
+        /// tasks running, next to no CPU use. Default from supervisor config.
+        #[arg(long, value_name = "DURATION")]
+        idle_timeout: Option<String>,
+        /// Shut the VM down this long after boot, idle or not (`0` never).
+        /// Default from supervisor config.
+        #[arg(long, value_name = "DURATION")]
+        max_lifetime: Option<String>,
+        /// `suspend` (resumed on the next attach) or `stop`. Default from
+        /// supervisor config.
+        #[arg(long)]
+        idle_action: Option<String>,
     },
     /// List all known VMs (running and stopped).
-    List,
+    List {
+        /// List the VMs of every supervisor context; unreachable ones are
+        /// skipped with a warning.
+        #[arg(long)]
+        all_contexts: bool,
+    },
     /// Attach to a running VM over SSH.
     Attach { id_or_name: String },
-    /// Print the most recent VM console log.
+    /// Print VM status and the connections its network policy blocked.
     Logs {
         id_or_name: String,
         #[arg(short, long)]
@@ -450,10 +519,63 @@ pub enum VmsCommands {
         #[arg(long)]
         force: bool,
     },
+    /// Boot a copy of a VM with its own disk, SSH key and ports. A running
+    /// VM is paused only while its disk is copied.
+    Clone {
+        id_or_name: String,
+        /// Name of the copy; generated when omitted.
+        #[arg(long)]
+        name: Option<String>,
+    },
     /// Remove a stopped VM and its on-disk state.
     Rm { id_or_name: String },
-    /// Build (or rebuild) the cached Alpine guest image.
+    /// Show the changes made in an `overlay` or `worktree` VM's workspace.
+    Diff { id_or_name: String },
+    /// Merge an `overlay` or `worktree` VM's workspace changes back into
+    /// the directory it was started from.
+    Apply { id_or_name: String },
+    /// Share a VM with another user, or list who it is shared with when
+    /// no username is given. Only the VM's owner (or an admin) can.
+    Share {
+        id_or_name: String,
+        username: Option<String>,
+    },
+    /// Stop sharing a VM with a user.
+    Unshare { id_or_name: String, username: String },
+    /// Forward a guest TCP port of a running VM to a loopback port on the
+    /// supervisor host, or list the VM's forwards when no port is given.
+    /// `GUEST[:HOST]`; the host port comes from the supervisor's
+    /// `forward_port_range` when omitted. Lasts until `mows vms unforward`
+    /// or the VM stops.
+    Forward {
+        id_or_name: String,
+        #[arg(value_name = "GUEST[:HOST]")]
+        ports: Option<String>,
+    },
+    /// Remove a port forward added with `mows vms forward`.
+    Unforward { id_or_name: String, guest_port: u16 },
+    /// Live CPU, memory, disk and network use of running VMs, busiest
+    /// first. Ctrl-C to quit.
+    Top {
+        /// Seconds between refreshes.
+        #[arg(long, short = 'n', default_value_t = 2)]
+        interval: u64,
+    },
+    /// Checkpoint a VM and roll it back later.
+    Snapshot {
+        #[command(subcommand)]
+        command: VmsSnapshotCommands,
+    },
+    /// Build (or rebuild) a guest image with the image-builder and stage it
+    /// into the supervisor's image dir. Cloud images (`images:` entries
+    /// with `source: cloud`) aren't built — download their qcow2 instead.
     BuildImage {
+        /// `alpine`, `debian`, `ubuntu` or `nixos`.
+        #[arg(long, default_value = "alpine")]
+        distro: String,
+        /// `headless` or `desktop`.
+        #[arg(long, default_value = "headless")]
+        flavor: String,
         #[arg(long)]
         rebuild: bool,
     },
@@ -464,6 +586,31 @@ pub enum VmsCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum VmsSnapshotCommands {
+    /// Take a snapshot of a VM's disk.
+    Create {
+        id_or_name: String,
+        /// Snapshot name (default: `snap-<timestamp>`).
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List a VM's snapshots, newest first.
+    List { id_or_name: String },
+    /// Roll a stopped VM back to a snapshot.
+    Restore {
+        id_or_name: String,
+        /// Snapshot id, id prefix, or name.
+        snapshot: String,
+    },
+    /// Delete a snapshot.
+    Rm {
+        id_or_name: String,
+        /// Snapshot id, id prefix, or name.
+        snapshot: String,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum VmsSupervisorCommands {
     /// Start the supervisor container (no-op if already running).
@@ -486,10 +633,15 @@ pub enum AgentsCommands {
     /// Boot a fresh VM and create an agent inside it (the original
     /// `mows tools agent run` UX). Defaults `--kind=claude`.
     Run {
+        /// Start from a named preset in the supervisor config; the other
+        /// flags override its values.
+        #[arg(long)]
+        preset: Option<String>,
         /// Override the auto-generated VM/agent name.
         #[arg(long)]
         name: Option<String>,
-        /// Agent kind to run (default: claude). Built-ins: shell, claude.
+        /// Agent kind to run (default: the preset's, else claude).
+        /// Built-ins: shell, claude.
         #[arg(long)]
         kind: Option<String>,
         /// vCPU count (default from supervisor config).
@@ -498,9 +650,21 @@ pub enum AgentsCommands {
         /// Memory in megabytes (default from supervisor config).
         #[arg(long)]
         memory: Option<u32>,
+        /// Guest image: an image-builder distro (`alpine`, `debian`, …) or
+        /// a name from the supervisor's `images:` (default from its config).
+        #[arg(long)]
+        image: Option<String>,
         /// Skip mounting the current working directory into the VM.
         #[arg(long)]
         no_workspace: bool,
+        /// Share another host directory: `HOST:GUEST[:ro|rw]`. See
+        /// `mows vms run --help`. Repeatable.
+        #[arg(long = "mount", value_name = "HOST:GUEST[:MODE]")]
+        mounts: Vec<String>,
+        /// How the working directory is shared: `rw` (default), `overlay`
+        /// or `worktree`. See `mows vms run --help`.
+        #[arg(long)]
+        workspace_mode: Option<String>,
         /// Start in the background instead of attaching to the agent's IO.
         #[arg(short, long)]
         detach: bool,
@@ -522,25 +686,95 @@ pub enum AgentsCommands {
     /// List all known agents (across every VM).
     List {
         /// Restrict to agents in this VM.
-        #[arg(long)]
+        #[arg(long, conflicts_with = "all_contexts")]
         vm: Option<String>,
+        /// List the agents of every supervisor context; unreachable ones
+        /// are skipped with a warning.
+        #[arg(long)]
+        all_contexts: bool,
     },
     /// Attach to a running agent's IO (live stdout, type to send stdin).
     Attach { id_or_name: String },
-    /// Print the recent agent log (replay of the persisted stdout).
+    /// Print everything the agent wrote to its terminal (from its session
+    /// recording, without timing).
     Logs {
         id_or_name: String,
         #[arg(short, long)]
         follow: bool,
     },
+    /// Replay an agent's session recording in this terminal with its
+    /// original timing.
+    Replay {
+        id_or_name: String,
+        /// Playback speed multiplier (`4` plays four times as fast).
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
     /// Stop a running agent (kills the SSH-spawned process).
     Stop {
         id_or_name: String,
         #[arg(long)]
         force: bool,
     },
-    /// Remove a stopped agent's row + log.
+    /// Remove a stopped agent's row + session recording.
     Rm { id_or_name: String },
+    /// Block until an agent exits or sits at an input prompt (as defined by
+    /// its kind's `input_prompts`). Exits non-zero on `--timeout`.
+    Wait {
+        id_or_name: String,
+        /// Only return once the agent has exited, ignoring input prompts.
+        #[arg(long)]
+        exit: bool,
+        /// Give up after this long, e.g. `90s`, `30m`, `2h` (default: wait forever).
+        #[arg(long)]
+        timeout: Option<String>,
+        /// Also raise a desktop notification (notify-send / osascript).
+        #[arg(long)]
+        notify: bool,
+    },
+    /// Queue a headless (non-interactive) agent run on a prompt file.
+    ///
+    /// The task boots its own VM (mounting the current directory unless
+    /// `--no-workspace`) or runs inside `--vm`. Files the agent writes to
+    /// `/out` are kept; fetch them with `mows agents results <id>`.
+    Exec {
+        /// Agent kind to run (default: claude).
+        #[arg(long)]
+        kind: Option<String>,
+        /// File whose contents are the prompt.
+        #[arg(long)]
+        prompt_file: std::path::PathBuf,
+        /// Wall-clock limit, e.g. `90s`, `30m`, `2h` (default from supervisor config).
+        #[arg(long)]
+        timeout: Option<String>,
+        /// Override the auto-generated task name.
+        #[arg(long)]
+        name: Option<String>,
+        /// Run inside this existing VM (id, id prefix, or name) instead of a fresh one.
+        #[arg(long, conflicts_with = "no_workspace")]
+        vm: Option<String>,
+        /// Skip mounting the current working directory into the task's VM.
+        #[arg(long)]
+        no_workspace: bool,
+        /// Block until the task finishes, then fetch its results.
+        #[arg(short, long)]
+        wait: bool,
+    },
+    /// List headless tasks and their status.
+    Tasks,
+    /// Download a task's transcript and collected `/out` files.
+    Results {
+        /// Task id, id prefix, or name.
+        id_or_name: String,
+        /// Destination directory (default: `./mows-results/<task-id>`).
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Cancel a queued or running task.
+    Cancel {
+        /// Task id, id prefix, or name.
+        id_or_name: String,
+    },
     /// Open the supervisor's web UI in a browser.
     ///
     /// Auto-starts the supervisor container if it isn't running, then
@@ -556,6 +790,126 @@ pub enum AgentsCommands {
         #[command(subcommand)]
         command: AgentsUserCommands,
     },
+    /// Manage the supervisor's encrypted secrets (admin only).
+    ///
+    /// Agent kinds reference a secret as `${secret:NAME}` in
+    /// `agent_kinds.<kind>.env` or an MCP server's `env`; the value is only
+    /// decrypted when an agent spawns.
+    Secret {
+        #[command(subcommand)]
+        command: AgentsSecretCommands,
+    },
+    /// Manage personal access tokens for scripts and CI.
+    ///
+    /// Export a token as `MOWS_VM_SUPERVISOR_API_TOKEN` (or put it in the
+    /// file named by `MOWS_VM_SUPERVISOR_API_TOKEN_FILE`) to use it.
+    Token {
+        #[command(subcommand)]
+        command: AgentsTokenCommands,
+    },
+    /// Show the supervisor's audit log: who created, stopped, attached to or
+    /// removed what, newest first. Members only see their own entries.
+    Audit {
+        /// Only entries by this username.
+        #[arg(long)]
+        actor: Option<String>,
+        /// An action (`vm.stop`) or every action on a resource (`vm`).
+        #[arg(long)]
+        action: Option<String>,
+        /// Only entries about this VM/agent id, username or secret name.
+        #[arg(long)]
+        target: Option<String>,
+        /// Only failed attempts.
+        #[arg(long)]
+        failed: bool,
+        /// Only entries at or after this RFC 3339 time.
+        #[arg(long)]
+        since: Option<String>,
+        /// Number of entries to show.
+        #[arg(long, default_value_t = 50, conflicts_with = "jsonl")]
+        limit: u32,
+        /// Print every matching entry as JSON lines, oldest first.
+        #[arg(long)]
+        jsonl: bool,
+    },
+    /// Manage supervisor contexts: named supervisor endpoints, e.g. a build
+    /// box reached over WireGuard. `local` (the auto-started container) always
+    /// exists.
+    Context {
+        #[command(subcommand)]
+        command: AgentsContextCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AgentsContextCommands {
+    /// List contexts; the current one is marked with `*`.
+    List,
+    /// Print the current context.
+    Current,
+    /// Make a context the default for `mows vms` and `mows agents`.
+    Use { name: String },
+    /// Add or replace a remote context.
+    Add {
+        name: String,
+        /// Supervisor API base URL, e.g. `http://10.77.0.1:7878`.
+        #[arg(long)]
+        url: String,
+        /// File holding the bearer token (a personal access token from
+        /// that supervisor).
+        #[arg(long, conflicts_with = "token_stdin")]
+        token_file: Option<std::path::PathBuf>,
+        /// Read the token from stdin and store it in `mows.yaml` (mode 0600).
+        #[arg(long)]
+        token_stdin: bool,
+        /// WireGuard config that reaches the supervisor (from `mows vms
+        /// supervisor wg-config`); the context is only used while its
+        /// tunnel is up.
+        #[arg(long)]
+        wireguard_config: Option<std::path::PathBuf>,
+    },
+    /// Remove a context.
+    Rm { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum AgentsTokenCommands {
+    /// Issue a token and print it. It is not shown again.
+    Create {
+        /// What the token is for, e.g. `ci`.
+        name: String,
+        /// Scope to grant: `vms:read`, `vms:write`, `agents:read`,
+        /// `agents:write` or `admin`. Repeatable; at least one.
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<String>,
+        /// Lifetime in days, 1-365 (default 90).
+        #[arg(long)]
+        expires_in_days: Option<u32>,
+        /// Issue the token for this user instead of yourself (admin only).
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// List tokens (yours, or everyone's for an admin).
+    List,
+    /// Revoke a token by id.
+    Revoke { id: String },
+}
+
+#[derive(Subcommand)]
+pub enum AgentsSecretCommands {
+    /// Create or replace a secret. The value is read from `--from-file`,
+    /// else from stdin, prompting without echo on a terminal — never from
+    /// the command line.
+    Set {
+        name: String,
+        /// Read the value from this file (a trailing newline is dropped).
+        #[arg(long)]
+        from_file: Option<std::path::PathBuf>,
+    },
+    /// List secret names and when they were last set.
+    List,
+    /// Delete a secret.
+    Rm { name: String },
 }
 
 #[derive(Subcommand)]
@@ -563,11 +917,39 @@ pub enum AgentsUserCommands {
     /// Create a new user (prompts for password).
     Add {
         username: String,
-        #[arg(long, default_value = "user")]
+        /// `admin` or `member`.
+        #[arg(long, default_value = "member")]
         role: String,
+        /// Maximum number of VMs running at once (`0`: unlimited).
+        #[arg(long)]
+        max_vms: Option<u32>,
+        /// Maximum vCPUs across running VMs (`0`: unlimited).
+        #[arg(long)]
+        max_vcpus: Option<u32>,
+        /// Maximum memory across running VMs, in MiB (`0`: unlimited).
+        #[arg(long)]
+        max_memory_mb: Option<u32>,
     },
-    /// List users.
+    /// List users with their role and quota (admin only).
     List,
+    /// Change a user's role or quota (admin only).
+    Set {
+        username: String,
+        /// `admin` or `member`.
+        #[arg(long)]
+        role: Option<String>,
+        /// Maximum number of VMs running at once (`0`: unlimited).
+        #[arg(long)]
+        max_vms: Option<u32>,
+        /// Maximum vCPUs across running VMs (`0`: unlimited).
+        #[arg(long)]
+        max_vcpus: Option<u32>,
+        /// Maximum memory across running VMs, in MiB (`0`: unlimited).
+        #[arg(long)]
+        max_memory_mb: Option<u32>,
+    },
+    /// Show your own quota and what your running VMs use of it.
+    Quota,
     /// Change a user's password (prompts for new value).
     Passwd { username: String },
     /// Remove a user.
@@ -21,8 +21,8 @@ use colored::Colorize;
 use tracing_subscriber::EnvFilter;
 
 use cli::{
-    AgentsCommands, AgentsUserCommands, Cli, Commands, ComposeCommands, PackageManagerCommands,
-    SecretsCommands, ToolCommands, VmsCommands, VmsSupervisorCommands,
+    AgentsCommands, AgentsContextCommands, AgentsSecretCommands, AgentsTokenCommands, AgentsUserCommands, Cli, Commands, ComposeCommands, PackageManagerCommands,
+    SecretsCommands, ToolCommands, VmsCommands, VmsSnapshotCommands, VmsSupervisorCommands,
 };
 use manpage::manpage;
 use package_manager::{
@@ -33,10 +33,17 @@ use self_update::{check_for_updates_background, notify_if_update_available, self
 use shell_init::shell_init;
 use template::render_template_command;
 use agents::{
-    agent_attach, agent_create, agent_list, agent_logs, agent_rm, agent_run, agent_stop, agent_ui,
-    agent_user_add, agent_user_list, agent_user_passwd, agent_user_rm, vm_attach,
-    vm_build_image, vm_list, vm_logs, vm_rm, vm_run, vm_stop, vm_supervisor_logs,
+    agent_attach, agent_cancel, agent_context_add, agent_context_current, agent_context_list,
+    agent_audit, agent_context_rm, agent_context_use, agent_create, agent_exec, agent_list, agent_logs, agent_replay,
+    agent_results, agent_rm, agent_run, agent_secret_list, agent_secret_rm, agent_secret_set,
+    agent_stop, agent_tasks, agent_token_create, agent_token_list, agent_token_revoke, agent_ui,
+    agent_wait,
+    agent_user_add, agent_user_list, agent_user_passwd, agent_user_quota, agent_user_rm,
+    agent_user_set, vm_apply, vm_attach, vm_clone,
+    vm_build_image, vm_diff, vm_forward, vm_list, vm_logs, vm_rm, vm_run, vm_share, vm_snapshot_create, vm_snapshot_list,
+    vm_snapshot_restore, vm_snapshot_rm, vm_stop, vm_supervisor_logs,
     vm_supervisor_start, vm_supervisor_status, vm_supervisor_stop, vm_supervisor_wg_config,
+    vm_top, vm_unforward, vm_unshare, IdleFlags, NetworkPolicy, QuotaCaps, VmFlags,
 };
 use tools::{
     drives_command, expand_object_command, flatten_object_command, jq_command, json_to_yaml,
@@ -98,14 +105,24 @@ fn main() {
     let result = match cli.command {
         Commands::PackageManager { command } => handle_package_manager_command(command),
         Commands::Tools { tool } => handle_tool_command(tool),
-        Commands::Vms { command } => handle_vms_command(command),
-        Commands::Agents { command } => handle_agents_command(command),
+        Commands::Vms { context, command } => {
+            agents::context::select(context);
+            handle_vms_command(command)
+        }
+        Commands::Agents { context, command } => {
+            agents::context::select(context);
+            handle_agents_command(command)
+        }
         Commands::Template {
             input,
             variables,
             output,
         } => render_template_command(&input, &variables, &output),
-        Commands::SelfUpdate { build, version } => self_update(build, version.as_deref()),
+        Commands::SelfUpdate {
+            build,
+            version,
+            rollback,
+        } => self_update(build, version.as_deref(), rollback),
         Commands::Version => show_version(),
         Commands::Manpage { install } => manpage(install),
         Commands::ShellInit { install } => shell_init(install),
@@ -177,9 +194,12 @@ fn handle_tool_command(tool: ToolCommands) -> error::Result<()> {
             output,
             yaml,
         } => jq_command(&query, input.as_deref(), output.as_deref(), yaml),
-        ToolCommands::CargoWorkspaceDocker { all, path } => {
-            workspace_docker_command(all, path.as_deref())
-        }
+        ToolCommands::CargoWorkspaceDocker {
+            all,
+            path,
+            check,
+            dockerfile,
+        } => workspace_docker_command(all, path.as_deref(), check, dockerfile),
         ToolCommands::Drives => drives_command(),
     }
 }
@@ -187,17 +207,72 @@ fn handle_tool_command(tool: ToolCommands) -> error::Result<()> {
 fn handle_vms_command(command: VmsCommands) -> error::Result<()> {
     match command {
         VmsCommands::Run {
+            preset,
             name,
             cpus,
             memory,
+            image,
             no_workspace,
-        } => vm_run(name, cpus, memory, no_workspace),
-        VmsCommands::List => vm_list(),
+            mounts,
+            network,
+            allow,
+            workspace_mode,
+            idle_timeout,
+            max_lifetime,
+            idle_action,
+        } => vm_run(
+            VmFlags {
+                preset,
+                name,
+                cpus,
+                memory_mb: memory,
+                image,
+                no_workspace,
+                workspace_mode,
+                mounts,
+            },
+            NetworkPolicy::from_flags(network, allow),
+            IdleFlags {
+                idle_timeout,
+                max_lifetime,
+                idle_action,
+            },
+        ),
+        VmsCommands::List { all_contexts } => vm_list(all_contexts),
         VmsCommands::Attach { id_or_name } => vm_attach(id_or_name),
         VmsCommands::Logs { id_or_name, follow } => vm_logs(id_or_name, follow),
         VmsCommands::Stop { id_or_name, force } => vm_stop(id_or_name, force),
+        VmsCommands::Clone { id_or_name, name } => vm_clone(id_or_name, name),
         VmsCommands::Rm { id_or_name } => vm_rm(id_or_name),
-        VmsCommands::BuildImage { rebuild } => vm_build_image(rebuild),
+        VmsCommands::Diff { id_or_name } => vm_diff(id_or_name),
+        VmsCommands::Apply { id_or_name } => vm_apply(id_or_name),
+        VmsCommands::Share { id_or_name, username } => vm_share(id_or_name, username),
+        VmsCommands::Unshare { id_or_name, username } => vm_unshare(id_or_name, username),
+        VmsCommands::Forward { id_or_name, ports } => vm_forward(id_or_name, ports),
+        VmsCommands::Unforward {
+            id_or_name,
+            guest_port,
+        } => vm_unforward(id_or_name, guest_port),
+        VmsCommands::Top { interval } => vm_top(interval),
+        VmsCommands::Snapshot { command } => match command {
+            VmsSnapshotCommands::Create { id_or_name, name } => {
+                vm_snapshot_create(id_or_name, name)
+            }
+            VmsSnapshotCommands::List { id_or_name } => vm_snapshot_list(id_or_name),
+            VmsSnapshotCommands::Restore {
+                id_or_name,
+                snapshot,
+            } => vm_snapshot_restore(id_or_name, snapshot),
+            VmsSnapshotCommands::Rm {
+                id_or_name,
+                snapshot,
+            } => vm_snapshot_rm(id_or_name, snapshot),
+        },
+        VmsCommands::BuildImage {
+            distro,
+            flavor,
+            rebuild,
+        } => vm_build_image(distro, flavor, rebuild),
         VmsCommands::Supervisor { command } => match command {
             VmsSupervisorCommands::Start => vm_supervisor_start(),
             VmsSupervisorCommands::Stop => vm_supervisor_stop(),
@@ -211,31 +286,134 @@ fn handle_vms_command(command: VmsCommands) -> error::Result<()> {
 fn handle_agents_command(command: AgentsCommands) -> error::Result<()> {
     match command {
         AgentsCommands::Run {
+            preset,
             name,
             kind,
             cpus,
             memory,
+            image,
             no_workspace,
+            mounts,
+            workspace_mode,
+            detach,
+        } => agent_run(
+            VmFlags {
+                preset,
+                name,
+                cpus,
+                memory_mb: memory,
+                image,
+                no_workspace,
+                workspace_mode,
+                mounts,
+            },
+            kind,
             detach,
-        } => agent_run(name, kind, cpus, memory, no_workspace, detach),
+        ),
         AgentsCommands::Create {
             vm_id_or_name,
             kind,
             name,
             detach,
         } => agent_create(vm_id_or_name, kind, name, detach),
-        AgentsCommands::List { vm } => agent_list(vm),
+        AgentsCommands::List { vm, all_contexts } => agent_list(vm, all_contexts),
         AgentsCommands::Attach { id_or_name } => agent_attach(id_or_name),
         AgentsCommands::Logs { id_or_name, follow } => agent_logs(id_or_name, follow),
+        AgentsCommands::Replay { id_or_name, speed } => agent_replay(id_or_name, speed),
         AgentsCommands::Stop { id_or_name, force } => agent_stop(id_or_name, force),
         AgentsCommands::Rm { id_or_name } => agent_rm(id_or_name),
+        AgentsCommands::Wait {
+            id_or_name,
+            exit,
+            timeout,
+            notify,
+        } => agent_wait(id_or_name, exit, timeout, notify),
+        AgentsCommands::Exec {
+            kind,
+            prompt_file,
+            timeout,
+            name,
+            vm,
+            no_workspace,
+            wait,
+        } => agent_exec(kind, prompt_file, timeout, name, vm, no_workspace, wait),
+        AgentsCommands::Tasks => agent_tasks(),
+        AgentsCommands::Results { id_or_name, output } => agent_results(id_or_name, output),
+        AgentsCommands::Cancel { id_or_name } => agent_cancel(id_or_name),
         AgentsCommands::Ui { print } => agent_ui(print),
         AgentsCommands::User { command } => match command {
-            AgentsUserCommands::Add { username, role } => agent_user_add(username, role),
+            AgentsUserCommands::Add {
+                username,
+                role,
+                max_vms,
+                max_vcpus,
+                max_memory_mb,
+            } => agent_user_add(
+                username,
+                role,
+                QuotaCaps {
+                    max_vms,
+                    max_vcpus,
+                    max_memory_mb,
+                },
+            ),
             AgentsUserCommands::List => agent_user_list(),
+            AgentsUserCommands::Set {
+                username,
+                role,
+                max_vms,
+                max_vcpus,
+                max_memory_mb,
+            } => agent_user_set(
+                username,
+                role,
+                QuotaCaps {
+                    max_vms,
+                    max_vcpus,
+                    max_memory_mb,
+                },
+            ),
+            AgentsUserCommands::Quota => agent_user_quota(),
             AgentsUserCommands::Passwd { username } => agent_user_passwd(username),
             AgentsUserCommands::Rm { username } => agent_user_rm(username),
         },
+        AgentsCommands::Secret { command } => match command {
+            AgentsSecretCommands::Set { name, from_file } => agent_secret_set(name, from_file),
+            AgentsSecretCommands::List => agent_secret_list(),
+            AgentsSecretCommands::Rm { name } => agent_secret_rm(name),
+        },
+        AgentsCommands::Context { command } => match command {
+            AgentsContextCommands::List => agent_context_list(),
+            AgentsContextCommands::Current => agent_context_current(),
+            AgentsContextCommands::Use { name } => agent_context_use(name),
+            AgentsContextCommands::Add {
+                name,
+                url,
+                token_file,
+                token_stdin,
+                wireguard_config,
+            } => agent_context_add(name, url, token_file, token_stdin, wireguard_config),
+            AgentsContextCommands::Rm { name } => agent_context_rm(name),
+        },
+        AgentsCommands::Audit {
+            actor,
+            action,
+            target,
+            failed,
+            since,
+            limit,
+            jsonl,
+        } => agent_audit(actor, action, target, failed, since, limit, jsonl),
+        AgentsCommands::Token { command } => match command {
+            AgentsTokenCommands::Create {
+                name,
+                scopes,
+                expires_in_days,
+                user,
+            } => agent_token_create(name, scopes, expires_in_days, user),
+            AgentsTokenCommands::List => agent_token_list(),
+            AgentsTokenCommands::Revoke { id } => agent_token_revoke(id),
+        },
     }
 }
 
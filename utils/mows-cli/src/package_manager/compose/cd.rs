@@ -121,6 +121,7 @@ mod tests {
                 ],
             },
             update: None,
+            agents: Default::default(),
         }
     }
 
@@ -6,6 +6,7 @@ use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 
+use crate::agents::context::AgentsConfig;
 use crate::error::{IoResultExt, MowsError, Result};
 
 use super::SENSITIVE_FILE_MODE;
@@ -30,6 +31,9 @@ pub struct MowsConfig {
     pub compose: ComposeConfig,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub update: Option<UpdateNotification>,
+    /// Supervisor contexts for `mows vms` / `mows agents`.
+    #[serde(default, skip_serializing_if = "AgentsConfig::is_empty")]
+    pub agents: AgentsConfig,
 }
 
 /// Stores information about available updates
@@ -413,6 +417,7 @@ mod tests {
                 ],
             },
             update: None,
+            agents: Default::default(),
         };
 
         let yaml = serde_yaml_neo::to_string(&config).unwrap();
@@ -453,6 +458,7 @@ mod tests {
                 ],
             },
             update: None,
+            agents: Default::default(),
         };
 
         let projects = config.find_projects("project-a");
@@ -538,6 +544,7 @@ mod tests {
                 available_version: "1.0.0".to_string(),
                 checked_at: now, // Just checked
             }),
+            agents: Default::default(),
         };
 
         // Should NOT check when recently checked (within 1 hour)
@@ -559,6 +566,7 @@ mod tests {
                 available_version: "1.0.0".to_string(),
                 checked_at: now - 7200, // 2 hours ago
             }),
+            agents: Default::default(),
         };
 
         // Should check when last check was more than 1 hour ago
@@ -581,6 +589,7 @@ mod tests {
                 available_version: "1.0.0".to_string(),
                 checked_at: now - 3600, // Exactly 1 hour ago
             }),
+            agents: Default::default(),
         };
 
         // At exactly 3600 seconds, should NOT check (needs to be > 3600)
@@ -593,6 +602,7 @@ mod tests {
                 available_version: "1.0.0".to_string(),
                 checked_at: now - 3601, // 1 hour + 1 second ago
             }),
+            agents: Default::default(),
         };
 
         // Should check now
@@ -16,9 +16,18 @@ const GITHUB_API_URL: &str = "https://api.github.com/repos/my-own-web-services/m
 const GITHUB_RELEASES_URL: &str = "https://github.com/my-own-web-services/mows/releases/download";
 const REPO_URL: &str = "https://github.com/my-own-web-services/mows.git";
 
-/// Trusted SSH signing key for verifying release tags
+/// Trusted SSH signing key for verifying release tags and release checksum files
 const TRUSTED_SSH_KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ80+F8Xr3QAvxy/asB5QbB17m2vl+Aj+PzUZeatindf";
 
+/// `ssh-keygen -Y` namespace the release workflow signs checksum files under.
+/// Binding signatures to a namespace stops a signature made for another
+/// purpose (e.g. a git commit) from being replayed as a release signature.
+const RELEASE_SIGNATURE_NAMESPACE: &str = "mows-release";
+
+/// Suffix appended to the installed binary's file name for the copy kept
+/// by `replace_binary` and restored by `mows self-update --rollback`.
+const PREVIOUS_BINARY_SUFFIX: &str = "previous";
+
 /// HTTP request timeout in seconds for downloads
 const HTTP_TIMEOUT_SECS: u64 = 30;
 
@@ -207,27 +216,49 @@ fn calculate_checksum(path: &Path) -> Result<String> {
     Ok(format!("{:x}", hasher.finalize()))
 }
 
-/// Verify the checksum of a downloaded binary
+/// Verify the checksum of a downloaded binary.
+///
+/// The checksum file is only trusted after its detached SSH signature
+/// (`<checksum_url>.sig`) has been verified against [`TRUSTED_SSH_KEY`];
+/// otherwise an attacker able to swap the binary on the release could swap
+/// the checksum next to it just as easily.
 fn verify_checksum(binary_path: &Path, checksum_url: &str) -> Result<()> {
     let checksum_path = binary_path.with_extension("checksum");
+    let signature_path = binary_path.with_extension("checksum.sig");
 
     download_file(checksum_url, &checksum_path)?;
+    download_file(&format!("{}.sig", checksum_url), &signature_path).map_err(|e| {
+        MowsError::Message(format!(
+            "Failed to download the checksum signature: {}\n\
+             Releases published before signed checksums were introduced cannot be \
+             installed via self-update; use --build to verify the signed tag instead.",
+            e
+        ))
+    })?;
+
+    let signature_result =
+        verify_detached_signature(&checksum_path, &signature_path, TRUSTED_SSH_KEY);
 
     // Read expected checksum from file (format: "hash  filename")
     let checksum_content =
-        fs::read_to_string(&checksum_path).io_context("Failed to read checksum file")?;
+        fs::read_to_string(&checksum_path).io_context("Failed to read checksum file");
 
-    let expected_checksum = checksum_content
+    // Clean up checksum and signature files
+    for path in [&checksum_path, &signature_path] {
+        if let Err(e) = fs::remove_file(path) {
+            tracing::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+
+    signature_result?;
+    println!("Checksum signature verified successfully.");
+
+    let expected_checksum = checksum_content?
         .split_whitespace()
         .next()
         .ok_or_else(|| MowsError::Message("Invalid checksum file format".to_string()))?
         .to_lowercase();
 
-    // Clean up checksum file
-    if let Err(e) = fs::remove_file(&checksum_path) {
-        tracing::warn!("Failed to remove checksum file {}: {}", checksum_path.display(), e);
-    }
-
     // Calculate actual checksum
     let actual_checksum = calculate_checksum(binary_path)?;
 
@@ -243,19 +274,88 @@ Actual:   {}"#,
     Ok(())
 }
 
+/// Write an `allowed_signers` file trusting `trusted_key` for any principal.
+fn write_allowed_signers(dir: &Path, trusted_key: &str) -> Result<PathBuf> {
+    let allowed_signers_path = dir.join("allowed_signers");
+
+    // Format: <principal> <key-type> <key>
+    // We use "*" as principal to match any email
+    let allowed_signers_content = format!("* {}\n", trusted_key);
+
+    let mut file = File::create(&allowed_signers_path)
+        .io_context("Failed to create allowed_signers file")?;
+    file.write_all(allowed_signers_content.as_bytes())
+        .io_context("Failed to write allowed_signers file")?;
+    drop(file); // Ensure file is flushed before verification reads it
+
+    Ok(allowed_signers_path)
+}
+
+/// Verify a detached `ssh-keygen -Y sign` signature over `data_path`.
+///
+/// The release workflow produces the signature with
+/// `ssh-keygen -Y sign -n mows-release -f <key> <file>-checksum-sha256.txt`.
+fn verify_detached_signature(data_path: &Path, signature_path: &Path, trusted_key: &str) -> Result<()> {
+    let temp_dir =
+        tempfile::tempdir().io_context("Failed to create temp directory")?;
+    let allowed_signers_path = write_allowed_signers(temp_dir.path(), trusted_key)?;
+
+    let data = File::open(data_path)
+        .io_context(format!("Failed to open {}", data_path.display()))?;
+
+    let output = Command::new("ssh-keygen")
+        .arg("-Y")
+        .arg("verify")
+        .arg("-f")
+        .arg(&allowed_signers_path)
+        .args(["-I", RELEASE_SIGNATURE_NAMESPACE, "-n", RELEASE_SIGNATURE_NAMESPACE])
+        .arg("-s")
+        .arg(signature_path)
+        .stdin(data)
+        .output()
+        .map_err(|e| MowsError::command("ssh-keygen -Y verify", e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(MowsError::Message(format!(
+            r#"Signature verification of {} failed. The release may have been tampered with or signed with a different key.
+{}
+{}"#,
+            data_path.display(),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
 /// Get the path to the currently running binary
 fn get_current_binary_path() -> Result<PathBuf> {
     env::current_exe().io_context("Failed to get current executable path")
 }
 
-/// Replace the current binary with the new one
+/// Path of the copy of the installed binary kept for `--rollback`.
+fn previous_binary_path(current_binary: &Path) -> Result<PathBuf> {
+    let filename = current_binary
+        .file_name()
+        .ok_or_else(|| MowsError::path(current_binary, "Cannot determine binary filename"))?;
+    Ok(current_binary.with_file_name(format!(
+        "{}.{}",
+        filename.to_string_lossy(),
+        PREVIOUS_BINARY_SUFFIX
+    )))
+}
+
+/// Replace the current binary with the new one.
+///
+/// The binary being replaced is kept next to it as `<name>.previous` so
+/// that `mows self-update --rollback` can restore it later.
 fn replace_binary(new_binary: &Path, current_binary: &Path) -> Result<()> {
-    // Create backup of current binary
-    let backup_path = current_binary.with_extension("backup");
+    let previous_path = previous_binary_path(current_binary)?;
 
-    // Copy current binary to backup (in case we need to restore)
-    fs::copy(current_binary, &backup_path)
-        .io_context(format!("Failed to create backup at {}", backup_path.display()))?;
+    // Copy current binary aside (restored on failure, kept on success)
+    fs::copy(current_binary, &previous_path)
+        .io_context(format!("Failed to keep previous binary at {}", previous_path.display()))?;
 
     // Try to replace the binary
     let result = (|| -> Result<()> {
@@ -277,22 +377,95 @@ fn replace_binary(new_binary: &Path, current_binary: &Path) -> Result<()> {
         Ok(())
     })();
 
-    // If replacement failed, try to restore backup
+    // If replacement failed, try to restore the previous binary
     if let Err(e) = result {
-        eprintln!("Update failed, attempting to restore backup...");
-        if let Err(restore_err) = move_file(&backup_path, current_binary) {
+        eprintln!("Update failed, attempting to restore previous binary...");
+        if let Err(restore_err) = move_file(&previous_path, current_binary) {
             eprintln!(
-                "WARNING: Failed to restore backup: {}. Backup is at: {}",
+                "WARNING: Failed to restore previous binary: {}. It is at: {}",
                 restore_err,
-                backup_path.display()
+                previous_path.display()
             );
         }
         return Err(e);
     }
 
-    // Clean up backup on success
-    if let Err(e) = fs::remove_file(&backup_path) {
-        tracing::warn!("Failed to remove backup file {}: {}", backup_path.display(), e);
+    Ok(())
+}
+
+/// Swap the installed binary with the `<name>.previous` copy.
+///
+/// The binary being rolled back from becomes the new `.previous`, so running
+/// `--rollback` twice returns to where you started.
+fn swap_with_previous_binary(current_binary: &Path) -> Result<()> {
+    let previous_path = previous_binary_path(current_binary)?;
+
+    if !previous_path.exists() {
+        return Err(MowsError::Message(format!(
+            "Nothing to roll back to: {} does not exist. \
+             A previous binary is only kept after a successful self-update.",
+            previous_path.display()
+        )));
+    }
+
+    // All three paths live in the same directory, so plain renames are atomic.
+    let staging_path = previous_path.with_extension("rollback");
+
+    fs::rename(current_binary, &staging_path)
+        .io_context(format!("Failed to move {} aside", current_binary.display()))?;
+
+    if let Err(e) = fs::rename(&previous_path, current_binary) {
+        if let Err(restore_err) = fs::rename(&staging_path, current_binary) {
+            eprintln!(
+                "WARNING: Failed to restore current binary: {}. It is at: {}",
+                restore_err,
+                staging_path.display()
+            );
+        }
+        return Err(MowsError::io(
+            format!("Failed to restore {}", previous_path.display()),
+            e,
+        ));
+    }
+
+    fs::rename(&staging_path, &previous_path)
+        .io_context(format!("Failed to keep rolled-back binary at {}", previous_path.display()))?;
+
+    Ok(())
+}
+
+/// Restore the binary that was installed before the last self-update
+pub fn rollback() -> Result<()> {
+    let current_binary = get_current_binary_path()?;
+
+    println!("Restoring previous binary...");
+    swap_with_previous_binary(&current_binary)?;
+
+    let restored_version = Command::new(&current_binary)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+
+    match restored_version {
+        Some(v) => println!("Rolled back to {}", v),
+        None => println!("Rolled back to {}", current_binary.display()),
+    }
+
+    // Ensure mpm symlink exists alongside the mows binary
+    create_mpm_symlink_if_possible(&current_binary)?;
+
+    // The agent guest image is versioned with the binary; rebuild it the same
+    // way a successful update does.
+    if std::env::var_os("MOWS_SKIP_AGENT_IMAGE_BUILD").is_none() {
+        if let Err(e) = trigger_agent_image_build() {
+            tracing::warn!(
+                "agent image rebuild after rollback did not succeed: {e}. \
+                 Run `mows agents build-image` manually before your next agent run, \
+                 or set MOWS_SKIP_AGENT_IMAGE_BUILD=1 to silence this."
+            );
+        }
     }
 
     Ok(())
@@ -633,22 +806,10 @@ fn check_dependencies() -> Result<()> {
 
 /// Verify SSH signature on a git tag using the hardcoded trusted key
 fn verify_ssh_signature(repo_path: &Path, tag: &str) -> Result<()> {
-    use std::io::Write;
-
     // Create a temporary allowed_signers file with our trusted key
     let temp_dir =
         tempfile::tempdir().io_context("Failed to create temp directory")?;
-    let allowed_signers_path = temp_dir.path().join("allowed_signers");
-
-    // Format: <principal> <key-type> <key>
-    // We use "*" as principal to match any email
-    let allowed_signers_content = format!("* {}\n", TRUSTED_SSH_KEY);
-
-    let mut file = File::create(&allowed_signers_path)
-        .io_context("Failed to create allowed_signers file")?;
-    file.write_all(allowed_signers_content.as_bytes())
-        .io_context("Failed to write allowed_signers file")?;
-    drop(file); // Ensure file is flushed before verification reads it
+    let allowed_signers_path = write_allowed_signers(temp_dir.path(), TRUSTED_SSH_KEY)?;
 
     // Configure git to use our allowed_signers file for this verification
     let output = Command::new("git")
@@ -828,7 +989,11 @@ pub fn show_version() -> Result<()> {
 }
 
 /// Main self-update entry point
-pub fn self_update(build: bool, version: Option<&str>) -> Result<()> {
+pub fn self_update(build: bool, version: Option<&str>, rollback: bool) -> Result<()> {
+    if rollback {
+        return self::rollback();
+    }
+
     let result = if build {
         update_from_source(version)
     } else {
@@ -1253,6 +1418,126 @@ mod tests {
         );
     }
 
+    // ========================================================================
+    // replace_binary / rollback tests
+    // ========================================================================
+
+    #[test]
+    fn test_replace_binary_keeps_previous() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let current = dir.path().join("mows");
+        let new_binary = dir.path().join("mows-new");
+        fs::write(&current, "old").expect("Failed to write current");
+        fs::write(&new_binary, "new").expect("Failed to write new");
+
+        replace_binary(&new_binary, &current).expect("replace_binary should succeed");
+
+        assert_eq!(fs::read_to_string(&current).unwrap(), "new");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("mows.previous")).unwrap(),
+            "old"
+        );
+        assert!(!new_binary.exists(), "New binary should have been moved into place");
+    }
+
+    #[test]
+    fn test_swap_with_previous_binary_is_reversible() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let current = dir.path().join("mows");
+        let previous = dir.path().join("mows.previous");
+        fs::write(&current, "new").expect("Failed to write current");
+        fs::write(&previous, "old").expect("Failed to write previous");
+
+        swap_with_previous_binary(&current).expect("rollback should succeed");
+        assert_eq!(fs::read_to_string(&current).unwrap(), "old");
+        assert_eq!(fs::read_to_string(&previous).unwrap(), "new");
+
+        swap_with_previous_binary(&current).expect("second rollback should succeed");
+        assert_eq!(fs::read_to_string(&current).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&previous).unwrap(), "old");
+        assert!(!dir.path().join("mows.rollback").exists());
+    }
+
+    #[test]
+    fn test_swap_with_previous_binary_without_previous_fails() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let current = dir.path().join("mows");
+        fs::write(&current, "new").expect("Failed to write current");
+
+        let result = swap_with_previous_binary(&current);
+        assert!(result.is_err(), "Should fail when there is no previous binary");
+        assert_eq!(fs::read_to_string(&current).unwrap(), "new");
+    }
+
+    // ========================================================================
+    // verify_detached_signature tests
+    // ========================================================================
+
+    /// Generate a throwaway ed25519 key and sign `data_path` with it the same
+    /// way the release workflow does. Returns the public key line.
+    fn sign_with_fresh_key(dir: &Path, data_path: &Path) -> String {
+        let key_path = dir.join("release_key");
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key_path)
+            .status()
+            .expect("ssh-keygen must be installed to run this test");
+        assert!(status.success());
+
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-Y", "sign", "-n", RELEASE_SIGNATURE_NAMESPACE, "-f"])
+            .arg(&key_path)
+            .arg(data_path)
+            .status()
+            .expect("Failed to run ssh-keygen -Y sign");
+        assert!(status.success());
+
+        fs::read_to_string(key_path.with_extension("pub"))
+            .expect("Failed to read public key")
+            .trim()
+            .to_string()
+    }
+
+    #[test]
+    fn test_verify_detached_signature_accepts_trusted_key() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let checksum = dir.path().join("checksum.txt");
+        fs::write(&checksum, "abc123  mows-0.1.0-linux-amd64\n").unwrap();
+        let public_key = sign_with_fresh_key(dir.path(), &checksum);
+
+        verify_detached_signature(&checksum, &checksum.with_extension("txt.sig"), &public_key)
+            .expect("Signature from the trusted key should verify");
+    }
+
+    #[test]
+    fn test_verify_detached_signature_rejects_tampered_data() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let checksum = dir.path().join("checksum.txt");
+        fs::write(&checksum, "abc123  mows-0.1.0-linux-amd64\n").unwrap();
+        let public_key = sign_with_fresh_key(dir.path(), &checksum);
+
+        fs::write(&checksum, "def456  mows-0.1.0-linux-amd64\n").unwrap();
+
+        let result =
+            verify_detached_signature(&checksum, &checksum.with_extension("txt.sig"), &public_key);
+        assert!(result.is_err(), "Tampered checksum must not verify");
+    }
+
+    #[test]
+    fn test_verify_detached_signature_rejects_untrusted_key() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let checksum = dir.path().join("checksum.txt");
+        fs::write(&checksum, "abc123  mows-0.1.0-linux-amd64\n").unwrap();
+        sign_with_fresh_key(dir.path(), &checksum);
+
+        let result = verify_detached_signature(
+            &checksum,
+            &checksum.with_extension("txt.sig"),
+            TRUSTED_SSH_KEY,
+        );
+        assert!(result.is_err(), "Signature from an unknown key must not verify");
+    }
+
     // ========================================================================
     // parse_semver / is_valid_semver tests
     // ========================================================================
@@ -1,3 +1,4 @@
+use colored::Colorize;
 use serde::Deserialize;
 use std::collections::{BTreeMap, HashSet};
 use std::fs;
@@ -7,8 +8,31 @@ use walkdir::WalkDir;
 
 use crate::error::{IoResultExt, Result, TomlResultExt};
 
+/// File name of the generated workspace manifest, relative to the package
+const WORKSPACE_TOML_FILE: &str = "cargo-workspace-docker.toml";
+
+/// File name of the generated Dockerfile, relative to the package
+const DOCKERFILE_FILE: &str = "cargo-workspace-docker.Dockerfile";
+
+/// Rust toolchain image used by generated Dockerfiles (kept in sync with the
+/// hand-written Dockerfiles in the repo)
+const MUSLRUST_IMAGE: &str = "clux/muslrust:1.92.0-stable";
+
+/// Pinned cargo-chef fork used by generated Dockerfiles
+const CARGO_CHEF_INSTALL: &str = "cargo install --git https://github.com/firstdorsal/cargo-chef --rev=08314d0";
+
 /// Main entry point for the workspace-docker command
-pub fn workspace_docker_command(all: bool, path: Option<&Path>) -> Result<()> {
+///
+/// With `check`, nothing is written: every generated file is compared with
+/// the one on disk and the command fails with a diff if any is stale.
+/// With `dockerfile`, a matching cargo-chef Dockerfile is generated (or
+/// checked) alongside `cargo-workspace-docker.toml`.
+pub fn workspace_docker_command(
+    all: bool,
+    path: Option<&Path>,
+    check: bool,
+    dockerfile: bool,
+) -> Result<()> {
     let start_path = match path {
         Some(p) => p.to_path_buf(),
         None => std::env::current_dir()
@@ -37,19 +61,139 @@ pub fn workspace_docker_command(all: bool, path: Option<&Path>) -> Result<()> {
         return Err("No packages with docker-compose files found".to_string().into());
     }
 
+    let mut stale_files = Vec::new();
+
     // Process each package
     for package_path in &packages {
         debug!("Processing package: {}", package_path.display());
-        generate_for_package(&workspace_root, package_path, &workspace_config)?;
-        println!(
-            "Generated: {}",
-            package_path.join("cargo-workspace-docker.toml").display()
-        );
+        let generated = generate_for_package(&workspace_root, package_path, &workspace_config, dockerfile)?;
+
+        for (output_path, content) in generated {
+            if check {
+                if !check_generated_file(&output_path, &content)? {
+                    stale_files.push(output_path);
+                }
+            } else {
+                fs::write(&output_path, content)
+                    .io_context(format!("Failed to write {}", output_path.display()))?;
+                println!("Generated: {}", output_path.display());
+            }
+        }
+    }
+
+    if !stale_files.is_empty() {
+        return Err(format!(
+            "{} generated file(s) out of date. Run `mows tools cargo-workspace-docker{}{}` to regenerate.",
+            stale_files.len(),
+            if all { " --all" } else { "" },
+            if dockerfile { " --dockerfile" } else { "" },
+        )
+        .into());
     }
 
     Ok(())
 }
 
+/// Compare a generated file with the one on disk, printing a diff if they
+/// differ. Returns whether the file on disk is up to date.
+fn check_generated_file(output_path: &Path, expected: &str) -> Result<bool> {
+    let actual = match fs::read_to_string(output_path) {
+        Ok(actual) => actual,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("{} {}", "Missing:".red().bold(), output_path.display());
+            return Ok(false);
+        }
+        Err(e) => {
+            return Err(crate::error::MowsError::io(
+                format!("Failed to read {}", output_path.display()),
+                e,
+            ))
+        }
+    };
+
+    if actual == expected {
+        println!("{} {}", "Up to date:".green(), output_path.display());
+        return Ok(true);
+    }
+
+    println!("{} {}", "Out of date:".red().bold(), output_path.display());
+    for line in line_diff(&actual, expected) {
+        match line {
+            DiffLine::Removed(l) => println!("{}", format!("-{}", l).red()),
+            DiffLine::Added(l) => println!("{}", format!("+{}", l).green()),
+        }
+    }
+
+    Ok(false)
+}
+
+/// A changed line in a [`line_diff`]
+#[derive(Debug, PartialEq)]
+enum DiffLine<'a> {
+    /// Present in the file on disk but not in the generated output
+    Removed(&'a str),
+    /// Present in the generated output but not in the file on disk
+    Added(&'a str),
+}
+
+/// Minimal line diff (longest common subsequence) between the file on disk
+/// and the generated output. Only changed lines are returned, in order.
+fn line_diff<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let (n, m) = (old_lines.len(), new_lines.len());
+
+    // lcs[i][j] = length of the LCS of old_lines[i..] and new_lines[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for (i, old_line) in old_lines.iter().enumerate().rev() {
+        for (j, new_line) in new_lines.iter().enumerate().rev() {
+            let value = if old_line == new_line {
+                lcs.get(i + 1).and_then(|row| row.get(j + 1)).copied().unwrap_or(0) + 1
+            } else {
+                let down = lcs.get(i + 1).and_then(|row| row.get(j)).copied().unwrap_or(0);
+                let right = lcs.get(i).and_then(|row| row.get(j + 1)).copied().unwrap_or(0);
+                down.max(right)
+            };
+            if let Some(cell) = lcs.get_mut(i).and_then(|row| row.get_mut(j)) {
+                *cell = value;
+            }
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        match (old_lines.get(i), new_lines.get(j)) {
+            (Some(o), Some(n)) if o == n => {
+                i += 1;
+                j += 1;
+            }
+            (Some(o), Some(_)) => {
+                let down = lcs.get(i + 1).and_then(|row| row.get(j)).copied().unwrap_or(0);
+                let right = lcs.get(i).and_then(|row| row.get(j + 1)).copied().unwrap_or(0);
+                if down >= right {
+                    diff.push(DiffLine::Removed(o));
+                    i += 1;
+                } else {
+                    diff.push(DiffLine::Added(new_lines.get(j).copied().unwrap_or_default()));
+                    j += 1;
+                }
+            }
+            (Some(o), None) => {
+                diff.push(DiffLine::Removed(o));
+                i += 1;
+            }
+            (None, Some(n)) => {
+                diff.push(DiffLine::Added(n));
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+
+    diff
+}
+
 /// Find the workspace root by traversing up from start path
 fn find_workspace_root(start: &Path) -> Result<PathBuf> {
     let mut current = start.to_path_buf();
@@ -138,6 +282,7 @@ fn find_docker_packages(workspace_root: &Path) -> Result<Vec<PathBuf>> {
 #[derive(Debug, Deserialize)]
 struct CargoToml {
     package: Option<Package>,
+    bin: Option<Vec<BinTarget>>,
     workspace: Option<Workspace>,
     dependencies: Option<BTreeMap<String, toml::Value>>,
     #[serde(rename = "dev-dependencies")]
@@ -149,9 +294,15 @@ struct CargoToml {
 
 #[derive(Debug, Deserialize)]
 struct Package {
+    name: Option<String>,
     version: Option<toml::Value>,
 }
 
+#[derive(Debug, Deserialize)]
+struct BinTarget {
+    name: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct Workspace {
     #[allow(dead_code)] // Present in TOML but only used indirectly via has-field checks
@@ -379,12 +530,31 @@ fn collect_all_deps(
 // TOML Generation
 // ============================================================================
 
-/// Generate cargo-workspace-docker.toml for a package
+/// Name of the binary a package builds: the first `[[bin]]` target, falling
+/// back to the package name
+fn parse_binary_name(path: &Path) -> Result<String> {
+    let content =
+        fs::read_to_string(path).io_context(format!("Failed to read {}", path.display()))?;
+
+    let cargo: CargoToml =
+        toml::from_str(&content).toml_context(path.display().to_string())?;
+
+    cargo
+        .bin
+        .and_then(|bins| bins.into_iter().find_map(|bin| bin.name))
+        .or_else(|| cargo.package.and_then(|p| p.name))
+        .ok_or_else(|| format!("{} has neither a [[bin]] target nor a package name", path.display()).into())
+}
+
+/// Generate cargo-workspace-docker.toml (and optionally the matching
+/// Dockerfile) for a package. Returns `(output path, content)` pairs; the
+/// caller decides whether to write or check them.
 fn generate_for_package(
     workspace_root: &Path,
     package_path: &Path,
     workspace_config: &WorkspaceConfig,
-) -> Result<()> {
+    dockerfile: bool,
+) -> Result<Vec<(PathBuf, String)>> {
     let package_toml_path = package_path.join("Cargo.toml");
     let (package_version, direct_deps) = parse_package_deps(&package_toml_path)?;
 
@@ -396,13 +566,27 @@ fn generate_for_package(
 
     // Generate TOML content
     let content = generate_toml_content(workspace_config, &all_deps, package_version.as_deref())?;
+    let mut generated = vec![(package_path.join(WORKSPACE_TOML_FILE), content)];
+
+    if dockerfile {
+        let binary_name = parse_binary_name(&package_toml_path)?;
+        let package_relative = package_path.strip_prefix(workspace_root).map_err(|_| {
+            format!(
+                "Package {} is not inside workspace {}",
+                package_path.display(),
+                workspace_root.display()
+            )
+        })?;
+        let content = generate_dockerfile_content(
+            workspace_config,
+            &all_deps,
+            package_relative,
+            &binary_name,
+        )?;
+        generated.push((package_path.join(DOCKERFILE_FILE), content));
+    }
 
-    // Write to file
-    let output_path = package_path.join("cargo-workspace-docker.toml");
-    fs::write(&output_path, content)
-        .io_context(format!("Failed to write {}", output_path.display()))?;
-
-    Ok(())
+    Ok(generated)
 }
 
 fn generate_toml_content(
@@ -456,6 +640,85 @@ fn generate_toml_content(
     Ok(output)
 }
 
+/// Generate a cargo-chef multi-stage Dockerfile for a package.
+///
+/// The build context is the workspace root, so every path dependency from
+/// the resolved set is copied straight from its workspace location into the
+/// directory `cargo-workspace-docker.toml` expects (`./<crate name>`).
+fn generate_dockerfile_content(
+    workspace_config: &WorkspaceConfig,
+    deps: &BTreeMap<String, PackageDepInfo>,
+    package_relative: &Path,
+    binary_name: &str,
+) -> Result<String> {
+    validate_dependency_name(binary_name)?;
+    let package_dir = package_relative.display().to_string();
+
+    let mut path_deps = Vec::new();
+    for dependency_name in deps.keys() {
+        if let Some(path) = workspace_config
+            .dependencies
+            .get(dependency_name.as_str())
+            .and_then(|dependency| dependency.path.as_deref())
+        {
+            validate_dependency_name(dependency_name)?;
+            path_deps.push((dependency_name, path.trim_start_matches("./").trim_end_matches('/')));
+        }
+    }
+
+    let mut output = String::new();
+    output.push_str("# This file is generated by mows. Do not edit manually.\n");
+    output.push_str("# Build from the workspace root:\n");
+    output.push_str(&format!(
+        "#   docker build -f {}/{} .\n",
+        package_dir, DOCKERFILE_FILE
+    ));
+    output.push_str("ARG PROFILE=\"release\"\n");
+    output.push_str("ARG RUST_TARGET=\"x86_64-unknown-linux-musl\"\n");
+
+    // Workspace skeleton: path dependencies, lockfile and generated manifest
+    output.push_str("\nFROM scratch AS sources\n");
+    for (dependency_name, path) in &path_deps {
+        output.push_str(&format!("COPY {} {}\n", path, dependency_name));
+    }
+    output.push_str("COPY Cargo.lock ./\n");
+    output.push_str(&format!("COPY {}/{} ./Cargo.toml\n", package_dir, WORKSPACE_TOML_FILE));
+
+    output.push_str(&format!("\nFROM {} AS chef\n", MUSLRUST_IMAGE));
+    output.push_str(&format!("RUN {}\n", CARGO_CHEF_INSTALL));
+
+    output.push_str("\nFROM chef AS planner\n");
+    output.push_str("COPY --from=sources / /build/\n");
+    output.push_str(&format!("COPY {} /build/app/\n", package_dir));
+    output.push_str("WORKDIR /build/app/\n");
+    output.push_str("RUN cargo chef prepare --recipe-path recipe.json\n");
+
+    output.push_str("\nFROM chef AS builder\n");
+    output.push_str("ARG PROFILE\n");
+    output.push_str("ARG RUST_TARGET\n");
+    output.push_str("COPY --from=sources / /build/\n");
+    output.push_str("WORKDIR /build/app/\n");
+    output.push_str("COPY --from=planner /build/app/recipe.json recipe.json\n");
+    output.push_str("RUN cargo chef cook --profile=${PROFILE} --recipe-path recipe.json --target=${RUST_TARGET}\n");
+    output.push_str(&format!("COPY {} /build/app/\n", package_dir));
+    output.push_str(&format!(
+        "RUN cargo build --bin {} --profile=${{PROFILE}} --target=${{RUST_TARGET}} && \\\n",
+        binary_name
+    ));
+    output.push_str("    PROFILE_DIR=$(if [ \"${PROFILE}\" = \"dev\" ]; then echo debug; else echo \"${PROFILE}\"; fi) && \\\n");
+    output.push_str(&format!(
+        "    mv /build/target/${{RUST_TARGET}}/${{PROFILE_DIR}}/{} /{}\n",
+        binary_name, binary_name
+    ));
+
+    output.push_str("\nFROM scratch AS app\n");
+    output.push_str(&format!("COPY --from=builder /{} /{}\n", binary_name, binary_name));
+    output.push_str("STOPSIGNAL SIGTERM\n");
+    output.push_str(&format!("ENTRYPOINT [\"/{}\"]\n", binary_name));
+
+    Ok(output)
+}
+
 /// Validate that a dependency name is safe for TOML output
 fn validate_dependency_name(name: &str) -> Result<()> {
     if name.is_empty() {
@@ -628,4 +891,104 @@ mod tests {
         assert_eq!(dep.path, Some("./libs/mylib".to_string().into()));
         assert!(dep.version.is_none());
     }
+
+    #[test]
+    fn test_line_diff_identical() {
+        assert!(line_diff("a\nb\n", "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn test_line_diff_changed_line() {
+        let diff = line_diff("a\nversion = \"0.1.0\"\nc\n", "a\nversion = \"0.2.0\"\nc\n");
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Removed("version = \"0.1.0\""),
+                DiffLine::Added("version = \"0.2.0\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_diff_added_and_removed_sections() {
+        let diff = line_diff("a\nold\nc\n", "a\nc\nnew\n");
+        assert_eq!(diff, vec![DiffLine::Removed("old"), DiffLine::Added("new")]);
+    }
+
+    /// Write a tiny workspace with one dockerized package depending on one
+    /// path crate and one registry crate.
+    fn write_test_workspace(root: &Path) -> PathBuf {
+        fs::write(
+            root.join("Cargo.toml"),
+            r#"[workspace]
+members = ["libs/*", "apps/*"]
+
+[workspace.package]
+edition = "2021"
+
+[workspace.dependencies]
+common = { path = "./libs/common", default-features = false }
+serde = { version = "1.0.219", default-features = false }
+"#,
+        )
+        .unwrap();
+
+        let common = root.join("libs/common");
+        fs::create_dir_all(&common).unwrap();
+        fs::write(
+            common.join("Cargo.toml"),
+            "[package]\nname = \"common\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { workspace = true, features = [\"derive\"] }\n",
+        )
+        .unwrap();
+
+        let app = root.join("apps/app");
+        fs::create_dir_all(&app).unwrap();
+        fs::write(
+            app.join("Cargo.toml"),
+            "[package]\nname = \"my-app\"\nversion = \"0.3.0\"\n\n[[bin]]\nname = \"app-bin\"\npath = \"src/main.rs\"\n\n[dependencies]\ncommon = { workspace = true }\n",
+        )
+        .unwrap();
+        fs::write(app.join("docker-compose.yaml"), "services: {}\n").unwrap();
+        app
+    }
+
+    #[test]
+    fn test_check_detects_stale_and_fresh_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = write_test_workspace(dir.path());
+
+        // Missing file is stale
+        assert!(workspace_docker_command(false, Some(&app), true, false).is_err());
+
+        workspace_docker_command(false, Some(&app), false, false).unwrap();
+        workspace_docker_command(false, Some(&app), true, false)
+            .expect("freshly generated file should pass --check");
+
+        let toml_path = app.join(WORKSPACE_TOML_FILE);
+        let content = fs::read_to_string(&toml_path).unwrap();
+        fs::write(&toml_path, content.replace("0.3.0", "0.2.0")).unwrap();
+        assert!(workspace_docker_command(false, Some(&app), true, false).is_err());
+    }
+
+    #[test]
+    fn test_generate_dockerfile_fills_in_member_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = write_test_workspace(dir.path());
+
+        workspace_docker_command(false, Some(&app), false, true).unwrap();
+        let dockerfile = fs::read_to_string(app.join(DOCKERFILE_FILE)).unwrap();
+
+        assert!(dockerfile.contains("COPY libs/common common\n"));
+        assert!(dockerfile.contains("COPY apps/app/cargo-workspace-docker.toml ./Cargo.toml\n"));
+        assert!(dockerfile.contains("COPY apps/app /build/app/\n"));
+        assert!(dockerfile.contains("cargo build --bin app-bin"));
+        assert!(dockerfile.contains("ENTRYPOINT [\"/app-bin\"]"));
+
+        // The toml references the same directory the Dockerfile copies into
+        let workspace_toml = fs::read_to_string(app.join(WORKSPACE_TOML_FILE)).unwrap();
+        assert!(workspace_toml.contains("path = \"./common\""));
+
+        workspace_docker_command(false, Some(&app), true, true)
+            .expect("freshly generated Dockerfile should pass --check");
+    }
 }
@@ -1,8 +1,9 @@
 #!/bin/sh
 # Distro-agnostic mows-agent-init body.
 #
-# Reads /mowsinit/{authorized_keys,run.yaml,profile.sh} (mounted via 9p),
-# wires the supervisor-issued SSH key into root, picks the agent kind, and
+# Reads /mowsinit/{authorized_keys,run.yaml,profile.sh,mounts} (mounted via
+# 9p), wires the supervisor-issued SSH key into root, picks the agent kind,
+# mounts a worktree workspace's git dir and any extra host mounts, and
 # marks readiness for the supervisor's SSH-banner probe.
 #
 # Sourced (with `. /usr/local/sbin/mows-agent-init.sh`) by the per-distro
@@ -33,6 +34,32 @@ if [ -f /mowsinit/run.yaml ]; then
     echo "MOWS_AGENT_KIND=${kind:-claude}" >> /etc/environment
 fi
 
+# `worktree` workspaces: the worktree's `.git` file points at the host
+# repository's git dir by absolute path, so mount the `gitdir` share
+# there. Guest users differ from the checkout's owner, hence
+# safe.directory.
+if [ -f /mowsinit/run.yaml ]; then
+    gitdir=$(awk -F': *' '$1=="workspace_gitdir"{print $2; exit}' /mowsinit/run.yaml | tr -d "'\"")
+    if [ -n "$gitdir" ]; then
+        mkdir -p "$gitdir"
+        mount -t 9p -o trans=virtio,version=9p2000.L,rw gitdir "$gitdir"
+        if command -v git >/dev/null 2>&1; then
+            git config --system --add safe.directory '*'
+        fi
+    fi
+fi
+
+# Extra host mounts, one `<tag> <ro|rw> <guest path>` per line. A share
+# that fails to mount is reported but doesn't keep the VM from booting.
+if [ -f /mowsinit/mounts ]; then
+    while read -r tag mode target; do
+        [ -n "$target" ] || continue
+        mkdir -p "$target"
+        mount -t 9p -o "trans=virtio,version=9p2000.L,$mode" "$tag" "$target" \
+            || echo "mows-agent-init: mounting $tag at $target failed" >&2
+    done < /mowsinit/mounts
+fi
+
 if [ -f /mowsinit/profile.sh ]; then
     install -m 0644 /mowsinit/profile.sh /etc/profile.d/mows-agent.sh
 fi
@@ -50,6 +50,11 @@
                     fsType = "9p";
                     options = [ "trans=virtio" "version=9p2000.L" "nofail" ];
                 };
+                fileSystems."/workspace-base" = {
+                    device = "workspacebase";
+                    fsType = "9p";
+                    options = [ "trans=virtio" "version=9p2000.L" "nofail" "ro" ];
+                };
                 fileSystems."/creds" = {
                     device = "creds";
                     fsType = "9p";
@@ -84,6 +89,13 @@
                     docker-compose
                 ];
 
+                # `worktree` workspaces share the host repository's git dir,
+                # which belongs to a user the guest doesn't have.
+                programs.git = {
+                    enable = true;
+                    config.safe.directory = "*";
+                };
+
                 systemd.services.mows-agent-init = {
                     description = "MOWS agent init";
                     wantedBy = [ "multi-user.target" ];
@@ -106,6 +118,27 @@
                                 echo "MOWS_AGENT_KIND=''${kind:-claude}" \
                                     >> /etc/environment
                             fi
+                            gitdir=""
+                            if [ -f /mowsinit/run.yaml ]; then
+                                gitdir=$(${pkgs.gawk}/bin/awk -F': *' \
+                                    '$1=="workspace_gitdir"{print $2; exit}' \
+                                    /mowsinit/run.yaml | tr -d "'\"")
+                            fi
+                            if [ -n "$gitdir" ]; then
+                                mkdir -p "$gitdir"
+                                mount -t 9p -o trans=virtio,version=9p2000.L,rw \
+                                    gitdir "$gitdir"
+                            fi
+                            if [ -f /mowsinit/mounts ]; then
+                                while read -r tag mode target; do
+                                    [ -n "$target" ] || continue
+                                    mkdir -p "$target"
+                                    mount -t 9p \
+                                        -o "trans=virtio,version=9p2000.L,$mode" \
+                                        "$tag" "$target" \
+                                        || echo "mows-agent-init: mounting $tag at $target failed" >&2
+                                done < /mowsinit/mounts
+                            fi
                             if [ -f /mowsinit/profile.sh ]; then
                                 install -m 0644 /mowsinit/profile.sh \
                                     /etc/profile.d/mows-agent.sh
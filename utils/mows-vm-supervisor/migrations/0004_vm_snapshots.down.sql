@@ -0,0 +1,6 @@
+-- Rollback for 0004_vm_snapshots.sql (DEVOPS-44).
+--
+-- Drops the bookkeeping only; the internal snapshots stay inside each
+-- VM's disk.qcow2 and can be listed with `qemu-img snapshot -l`.
+
+DROP TABLE vm_snapshots;
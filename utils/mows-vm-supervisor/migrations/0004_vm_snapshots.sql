@@ -0,0 +1,15 @@
+-- Named checkpoints of a VM's qcow2 overlay. Every snapshot is an internal
+-- qcow2 snapshot whose tag is the row `id`; `name` is the user-facing label.
+-- Snapshots are disk only (QMP `blockdev-snapshot-internal-sync` on a
+-- running VM, `qemu-img snapshot -c` on a stopped one). Deleting a VM
+-- removes its overlay and cascades through here.
+CREATE TABLE vm_snapshots (
+    id             TEXT PRIMARY KEY,
+    vm_id          TEXT NOT NULL REFERENCES vms(id) ON DELETE CASCADE,
+    name           TEXT NOT NULL,
+    created_at     TEXT NOT NULL,
+    owner_user_id  TEXT REFERENCES users(id) ON DELETE SET NULL,
+    UNIQUE (vm_id, name)
+);
+
+CREATE INDEX vm_snapshots_vm_idx ON vm_snapshots(vm_id);
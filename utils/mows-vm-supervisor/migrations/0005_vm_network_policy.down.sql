@@ -0,0 +1,5 @@
+-- Rollback for 0005_vm_network_policy.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`.
+
+ALTER TABLE vms DROP COLUMN network_policy;
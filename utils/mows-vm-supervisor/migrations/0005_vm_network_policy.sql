@@ -0,0 +1,6 @@
+-- Per-VM outbound network policy, stored as the JSON form of
+-- `egress::NetworkPolicy` (`{"mode":"open"|"deny-all"|"allowlist", …}`).
+-- Existing VMs were booted with unrestricted networking, so they are
+-- recorded as `open`.
+
+ALTER TABLE vms ADD COLUMN network_policy TEXT NOT NULL DEFAULT '{"mode":"open"}';
@@ -0,0 +1,6 @@
+-- Rollback for 0006_tasks.sql (DEVOPS-44).
+--
+-- Drops the bookkeeping only; transcripts and collected outputs under
+-- `state_dir/tasks/` are left on disk.
+
+DROP TABLE tasks;
@@ -0,0 +1,26 @@
+-- Headless agent task runs (`POST /v1/tasks`). A task is queued, then
+-- picked up by the dispatcher in `crate::tasks`, which boots a VM for it
+-- (`owns_vm = 1`, torn down afterwards) or runs it inside the existing VM
+-- named at queue time (`owns_vm = 0`). `vm_id` deliberately has no FK: the
+-- id of a torn-down VM stays on the task for the record. The transcript
+-- and collected `/out` files live under `state_dir/tasks/<id>/`.
+CREATE TABLE tasks (
+    id             TEXT PRIMARY KEY,
+    name           TEXT NOT NULL,
+    kind           TEXT NOT NULL,
+    prompt         TEXT NOT NULL,
+    status         TEXT NOT NULL CHECK (status IN ('queued','running','succeeded','failed','timed_out','cancelled')),
+    cwd            TEXT,
+    vm_id          TEXT,
+    owns_vm        INTEGER NOT NULL DEFAULT 0,
+    timeout_secs   INTEGER NOT NULL,
+    exit_code      INTEGER,
+    error          TEXT,
+    created_at     TEXT NOT NULL,
+    started_at     TEXT,
+    finished_at    TEXT,
+    owner_user_id  TEXT REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX tasks_status_created_idx ON tasks(status, created_at);
+CREATE INDEX tasks_owner_idx ON tasks(owner_user_id);
@@ -0,0 +1,8 @@
+-- Rollback for 0007_vm_workspace_mode.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`. Worktree
+-- branches (`mows/<vm-name>`) created while the column existed stay in
+-- their repositories.
+
+ALTER TABLE vms DROP COLUMN workspace_branch;
+ALTER TABLE vms DROP COLUMN workspace_mode;
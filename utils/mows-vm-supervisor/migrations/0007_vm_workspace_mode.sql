@@ -0,0 +1,9 @@
+-- How a VM's `cwd` is shared with the guest (`workspace::WorkspaceMode`):
+-- `rw` mounts the host directory read-write, `overlay` mounts a per-VM
+-- copy, `worktree` mounts a fresh `git worktree` on `workspace_branch`.
+-- Existing VMs were booted with the host directory mounted directly, so
+-- they are recorded as `rw`.
+
+ALTER TABLE vms ADD COLUMN workspace_mode TEXT NOT NULL DEFAULT 'rw'
+    CHECK (workspace_mode IN ('rw', 'overlay', 'worktree'));
+ALTER TABLE vms ADD COLUMN workspace_branch TEXT;
@@ -0,0 +1,6 @@
+-- Rollback for 0008_agent_recordings.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`. Recordings
+-- under `state_dir/agents/` stay on disk.
+
+ALTER TABLE agents DROP COLUMN recording_bytes;
@@ -0,0 +1,6 @@
+-- Size in bytes of an agent's finished session recording
+-- (`<state_dir>/agents/<id>/session.cast`, see `recording.rs`). NULL while
+-- the agent runs, when recording was disabled, or once the retention sweep
+-- has removed the file.
+
+ALTER TABLE agents ADD COLUMN recording_bytes INTEGER;
@@ -0,0 +1,9 @@
+-- Rollback for 0009_vm_idle_policy.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`. VMs paused at
+-- the time of the rollback stay paused in QEMU until they are stopped.
+
+ALTER TABLE vms DROP COLUMN suspended_at;
+ALTER TABLE vms DROP COLUMN idle_action;
+ALTER TABLE vms DROP COLUMN max_lifetime_secs;
+ALTER TABLE vms DROP COLUMN idle_timeout_secs;
@@ -0,0 +1,13 @@
+-- Idle detection and lifetime limits (`crate::idle`). `idle_timeout_secs`
+-- and `max_lifetime_secs` are NULL when the limit is off; `idle_action`
+-- says what happens once a VM has been idle for `idle_timeout_secs`
+-- (`suspend` pauses it via QMP `stop`, `stop` shuts it down).
+-- `suspended_at` is set while a `running` VM is paused — the CHECK on
+-- `vms.status` can't be widened in place, so the API derives the
+-- `suspended` status from it. Existing VMs keep running without limits.
+
+ALTER TABLE vms ADD COLUMN idle_timeout_secs INTEGER;
+ALTER TABLE vms ADD COLUMN max_lifetime_secs INTEGER;
+ALTER TABLE vms ADD COLUMN idle_action TEXT NOT NULL DEFAULT 'suspend'
+    CHECK (idle_action IN ('suspend', 'stop'));
+ALTER TABLE vms ADD COLUMN suspended_at TEXT;
@@ -0,0 +1,9 @@
+-- Rollback for 0010_user_quotas_vm_shares.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`. Dropping
+-- `vm_shares` makes shared VMs visible to their owners (and admins) only.
+
+DROP TABLE vm_shares;
+ALTER TABLE users DROP COLUMN max_memory_mb;
+ALTER TABLE users DROP COLUMN max_vcpus;
+ALTER TABLE users DROP COLUMN max_vms;
@@ -0,0 +1,21 @@
+-- Roles, sharing and quotas. Members only see VMs they own
+-- (`vms.owner_user_id`) or that appear in `vm_shares` for them; admins
+-- see everything. The `users.role` CHECK can't be widened in place, so
+-- the `member` role keeps being stored as `user` (see
+-- `auth_middleware::UserRole`).
+--
+-- Per-user quotas are checked when a VM is created, against the user's
+-- live VMs. NULL means unlimited, which is what every existing user gets.
+
+ALTER TABLE users ADD COLUMN max_vms INTEGER;
+ALTER TABLE users ADD COLUMN max_vcpus INTEGER;
+ALTER TABLE users ADD COLUMN max_memory_mb INTEGER;
+
+CREATE TABLE vm_shares (
+    vm_id       TEXT NOT NULL REFERENCES vms(id) ON DELETE CASCADE,
+    user_id     TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at  TEXT NOT NULL,
+    PRIMARY KEY (vm_id, user_id)
+);
+
+CREATE INDEX vm_shares_user_idx ON vm_shares(user_id);
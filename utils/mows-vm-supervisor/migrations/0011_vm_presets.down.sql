@@ -0,0 +1,8 @@
+-- Rollback for 0011_vm_presets.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`. Agents spawned
+-- afterwards in preset VMs lose the preset's env and default kind.
+
+ALTER TABLE vms DROP COLUMN agent_kind;
+ALTER TABLE vms DROP COLUMN env;
+ALTER TABLE vms DROP COLUMN preset;
@@ -0,0 +1,9 @@
+-- Named VM presets. A VM created from a preset remembers its name, plus
+-- the two things only used after boot: the environment layered onto
+-- every agent spawned in it and the agent kind used when a create-agent
+-- request omits `kind`. Both are copied at create time so later config
+-- edits don't reach running VMs.
+
+ALTER TABLE vms ADD COLUMN preset TEXT;
+ALTER TABLE vms ADD COLUMN env TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE vms ADD COLUMN agent_kind TEXT;
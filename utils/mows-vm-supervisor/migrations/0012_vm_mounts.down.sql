@@ -0,0 +1,6 @@
+-- Rollback for 0012_vm_mounts.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`. The host
+-- directories themselves are untouched; VMs just forget they had them.
+
+ALTER TABLE vms DROP COLUMN mounts;
@@ -0,0 +1,5 @@
+-- Extra host mounts. The resolved list (request, preset and agent-kind
+-- mounts, host paths canonicalised) is stored as a JSON array so the VM
+-- shares the same directories however the config changes afterwards.
+
+ALTER TABLE vms ADD COLUMN mounts TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,6 @@
+-- Rollback for 0013_secrets.sql (DEVOPS-44).
+--
+-- Drops every stored secret. Agent kinds that still reference one fail
+-- to spawn until the reference is removed.
+
+DROP TABLE secrets;
@@ -0,0 +1,15 @@
+-- Supervisor-managed secrets (`/v1/secrets`, `mows agents secret set`).
+-- Values are sealed with AES-256-GCM under a key derived from
+-- `secrets.master_key_file` (see `crate::secrets`); the secret's name is
+-- the associated data, so a ciphertext copied onto another row fails to
+-- open. Agents reference secrets by name and get the plaintext only at
+-- spawn time — nothing here is ever copied into `agents` rows.
+
+CREATE TABLE secrets (
+    name        TEXT PRIMARY KEY,
+    nonce       BLOB NOT NULL,
+    ciphertext  BLOB NOT NULL,
+    created_at  TEXT NOT NULL,
+    updated_at  TEXT NOT NULL,
+    updated_by  TEXT REFERENCES users(id) ON DELETE SET NULL
+);
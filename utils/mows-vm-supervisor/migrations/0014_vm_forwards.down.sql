@@ -0,0 +1,6 @@
+-- Rollback for 0014_vm_forwards.sql (DEVOPS-44).
+--
+-- Drops the forward records only. Rules already added to a running QEMU
+-- stay until that VM stops.
+
+DROP TABLE vm_forwards;
@@ -0,0 +1,14 @@
+-- Runtime guest → host port forwards (`/v1/vms/{id}/forwards`,
+-- `mows vms forward`). Each row mirrors one QMP `hostfwd_add` rule on the
+-- VM's `net0` netdev; `crate::recovery::reconcile_forwards` uses them to
+-- re-apply the rules (or drop the rows) after a supervisor restart.
+-- `host_port` is drawn from `forward_port_range`, so it is unique across
+-- every VM.
+
+CREATE TABLE vm_forwards (
+    vm_id       TEXT NOT NULL REFERENCES vms(id) ON DELETE CASCADE,
+    guest_port  INTEGER NOT NULL,
+    host_port   INTEGER NOT NULL UNIQUE,
+    created_at  TEXT NOT NULL,
+    PRIMARY KEY (vm_id, guest_port)
+);
@@ -0,0 +1,6 @@
+-- Rollback for 0015_agent_waiting.sql (DEVOPS-44).
+--
+-- Requires SQLite >= 3.35 for `ALTER TABLE … DROP COLUMN`. Agents stop
+-- reporting that they wait for input; exits are still reported.
+
+ALTER TABLE agents DROP COLUMN waiting_since;
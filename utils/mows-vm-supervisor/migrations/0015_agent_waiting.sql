@@ -0,0 +1,7 @@
+-- When the agent started waiting for input: its screen stopped changing
+-- with one of its kind's `input_prompts` showing (see
+-- `crate::agent_runtime::InputWatch`). Cleared as soon as the screen
+-- changes again. Backs `GET /v1/agents/{id}/wait` and
+-- `mows agents wait`.
+
+ALTER TABLE agents ADD COLUMN waiting_since TEXT;
@@ -0,0 +1,6 @@
+-- Rollback for 0016_api_tokens.sql (DEVOPS-44).
+--
+-- Drops every personal access token; scripts and CI using one get 401
+-- until they log in or are issued a new token.
+
+DROP TABLE api_tokens;
@@ -0,0 +1,16 @@
+-- Personal access tokens (`/v1/tokens`, `mows agents token create`).
+-- Only the SHA-256 of the token is stored; the token itself is shown
+-- once, at creation. `scopes` is a space-separated list of
+-- `tokens::TokenScope` values that narrows what the owning user's role
+-- already allows (see `api::auth_middleware`).
+
+CREATE TABLE api_tokens (
+    id            TEXT PRIMARY KEY,
+    user_id       TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name          TEXT NOT NULL,
+    token_hash    TEXT NOT NULL UNIQUE,
+    scopes        TEXT NOT NULL,
+    created_at    TEXT NOT NULL,
+    expires_at    TEXT NOT NULL,
+    last_used_at  TEXT
+);
@@ -0,0 +1,8 @@
+-- Rollback for 0017_audit_log.sql (DEVOPS-44).
+--
+-- Drops the audit history along with the table; export it first
+-- (`GET /v1/audit/export`) if it must be kept.
+
+DROP TRIGGER audit_log_no_delete;
+DROP TRIGGER audit_log_no_update;
+DROP TABLE audit_log;
@@ -0,0 +1,30 @@
+-- Append-only audit log of user actions (`crate::audit`, `GET /v1/audit`).
+-- `id` doubles as the pagination cursor. `actor_user_id` is deliberately
+-- not a foreign key: entries outlive the users they name, and `actor`
+-- keeps the username as it was at the time. The triggers reject every
+-- UPDATE and DELETE, so rows can only be added.
+
+CREATE TABLE audit_log (
+    id             INTEGER PRIMARY KEY AUTOINCREMENT,
+    at             TEXT NOT NULL,
+    actor_user_id  TEXT,
+    actor          TEXT NOT NULL,
+    source_ip      TEXT,
+    action         TEXT NOT NULL,
+    target         TEXT,
+    outcome        TEXT NOT NULL CHECK (outcome IN ('success', 'failure')),
+    status_code    INTEGER
+);
+
+CREATE INDEX audit_log_actor_user_id ON audit_log (actor_user_id, id);
+CREATE INDEX audit_log_action ON audit_log (action, id);
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
@@ -9,9 +9,10 @@
 //!   ──────────          ──
 //!   spawn(spec)
 //!      │  ssh ─────────►  tmux new-session -d -s mows-<id> -- <argv>
-//!      │  (one-shot, exits)   ↑
-//!      │                      │
-//!   poll alive ─ ssh ───►  tmux has-session
+//!      │  (one-shot, exits)   ; pipe-pane 'cat >> /tmp/mows-<id>.out'
+//!      │                      ↑
+//!   record ──── ssh ───►  tail -f /tmp/mows-<id>.out      (session.cast)
+//!   poll alive ─ ssh ───►  tmux display -p '#{pane_width} #{pane_height}'
 //!                              ↑
 //!   /v1/agents/:id/io ─ ssh ─► tmux attach -t mows-<id>   (per ws client)
 //!   mows agents attach ─ ssh ─► tmux attach -t mows-<id>  (per CLI run)
@@ -24,19 +25,51 @@
 //! pty echo bouncing because each client has its own private pty between
 //! its terminal and tmux.
 //!
-//! The persisted `agent.log` is the tmux session's `pipe-pane` output —
-//! everything the agent ever wrote is captured to disk for replay.
+//! The session is recorded to `<state_dir>/agents/<id>/session.cast` (see
+//! `crate::recording`): the tmux `pipe-pane` output tailed over ssh becomes
+//! the `o` events, the liveness poll doubles as the pane-size probe for `r`
+//! events, and the websocket bridge in `api::agents` adds `i` events and
+//! attach/detach markers.
+//!
+//! Secrets the agent's kind references (`crate::secrets`) are decrypted
+//! here and nowhere else. They go over ssh stdin into
+//! `/run/mows-secrets/<session>.env` on a tmpfs in the guest, which the
+//! launch command sources; the tmux command line only carries the path.
+//! The file is removed when the agent stops.
+//!
+//! For kinds with `input_prompts` the liveness poll also captures the
+//! visible pane, and [`InputWatch`] decides from successive captures
+//! whether the agent sits at a prompt (`agents.waiting_since`, see
+//! `crate::notifications`).
 
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use futures_util::future::BoxFuture;
+use regex::Regex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
+use crate::config::RecordingConfig;
 use crate::error::{Result, SupervisorError};
+use crate::recording::{recording_path_for, CastHeader, Recorder};
+use crate::secrets::{self, SecretKey};
+
+/// Pause before re-opening the output tail after its ssh connection drops.
+const RECORD_RETRY: Duration = Duration::from_secs(2);
+
+/// tmux's size for a detached session with no client attached yet.
+const DEFAULT_PANE_SIZE: (u16, u16) = (80, 24);
+
+/// Guest tmpfs holding the per-agent secret env files. `0711` lets the
+/// claude bootstrap's unprivileged user reach its own file without being
+/// able to list the others.
+const GUEST_SECRETS_DIR: &str = "/run/mows-secrets";
 
 #[derive(Clone)]
 pub struct AgentSpawnSpec {
@@ -44,15 +77,81 @@ pub struct AgentSpawnSpec {
     pub vm_id: String,
     pub vm_ssh_port: u16,
     pub vm_ssh_key_path: PathBuf,
+    /// Display name, used as the recording's title.
+    pub name: String,
     pub argv: Vec<String>,
     pub env: std::collections::BTreeMap<String, String>,
-    /// Where the tmux pipe-pane log gets written inside the supervisor's
-    /// state dir. `<state_dir>/agents/<agent_id>/agent.log`.
-    pub log_path: PathBuf,
+    /// Secret references of the agent's kind: guest env var → secret
+    /// name. Resolved by `spawn`, never stored.
+    pub secret_env: std::collections::BTreeMap<String, String>,
+    pub secret_key: Option<SecretKey>,
+    /// Supervisor-side state for this agent:
+    /// `<state_dir>/agents/<agent_id>/` (known_hosts, `session.cast`).
+    pub agent_dir: PathBuf,
+    pub recording: RecordingConfig,
     /// `<user>@<host>` ssh target. Built by the caller from
     /// `SupervisorConfig::{guest_ssh_user, external_host}` so the agent
     /// runtime stays unaware of config wiring.
     pub ssh_target: String,
+    /// The kind's compiled `input_prompts`; empty skips the screen
+    /// captures entirely.
+    pub input_prompts: Vec<Regex>,
+    /// `notifications.input_idle_secs`.
+    pub input_idle: Duration,
+}
+
+/// Callbacks `spawn` runs on the agent's transitions, after updating its
+/// row. The caller uses them to emit events and drop the registry slot.
+pub struct AgentHooks {
+    /// The tmux session is gone.
+    pub on_exit: Box<dyn FnOnce(i32) -> BoxFuture<'static, ()> + Send>,
+    /// The agent started (`true`) or stopped (`false`) waiting for input.
+    pub on_waiting: Box<dyn Fn(bool) -> BoxFuture<'static, ()> + Send + Sync>,
+}
+
+/// Decides from successive captures of an agent's screen whether it waits
+/// for input: the screen hasn't changed for `idle` and one of the
+/// prompts matches it. Any change ends the wait.
+pub struct InputWatch {
+    prompts: Vec<Regex>,
+    idle: Duration,
+    screen: String,
+    changed_at: Instant,
+    waiting: bool,
+}
+
+impl InputWatch {
+    pub fn new(prompts: Vec<Regex>, idle: Duration, now: Instant) -> Self {
+        Self {
+            prompts,
+            idle,
+            screen: String::new(),
+            changed_at: now,
+            waiting: false,
+        }
+    }
+
+    /// Feed one capture. Returns the new state when it flips.
+    pub fn observe(&mut self, screen: &str, now: Instant) -> Option<bool> {
+        let screen = screen.trim_end();
+        if screen != self.screen {
+            self.screen = screen.to_string();
+            self.changed_at = now;
+            if self.waiting {
+                self.waiting = false;
+                return Some(false);
+            }
+            return None;
+        }
+        if self.waiting || now.duration_since(self.changed_at) < self.idle {
+            return None;
+        }
+        if self.prompts.iter().any(|prompt| prompt.is_match(screen)) {
+            self.waiting = true;
+            return Some(true);
+        }
+        None
+    }
 }
 
 pub struct AgentHandle {
@@ -62,12 +161,16 @@ pub struct AgentHandle {
     /// Name of the tmux session inside the VM. Stable for the agent's
     /// lifetime; clients attach via `tmux attach -t <session>`.
     pub session: String,
-    pub log_path: PathBuf,
+    pub agent_dir: PathBuf,
     /// Fully-qualified SSH target (`<guest_ssh_user>@<external_host>`)
     /// computed once at spawn time from `SupervisorConfig`. Hoisted out
     /// of the previously-duplicated `"root@127.0.0.1"` literals so a
     /// change to the guest user or host name flows through one place.
     pub ssh_target: String,
+    /// `None` when recordings are disabled or the file couldn't be created.
+    pub recorder: Option<Arc<Recorder>>,
+    /// Guest path of the agent's secret env file, if it has secrets.
+    pub secrets_file: Option<String>,
 }
 
 #[derive(Default, Clone)]
@@ -111,8 +214,11 @@ pub async fn stop_handle(handle: &AgentHandle) {
     // Kill the tmux session in the guest — tmux will SIGHUP every pane,
     // every client gets disconnected. Best-effort: if the VM is already
     // torn down, ssh fails; that's fine.
-    let _ = ssh_oneshot(handle, &format!("tmux kill-session -t {}", handle.session))
-        .await;
+    let mut command = format!("tmux kill-session -t {}", handle.session);
+    if let Some(file) = &handle.secrets_file {
+        command.push_str(&format!("; rm -f {}", shell_quote(file)));
+    }
+    let _ = ssh_oneshot(handle, &command).await;
 }
 
 fn session_name(agent_id: &str) -> String {
@@ -121,6 +227,16 @@ fn session_name(agent_id: &str) -> String {
     format!("mows-{}", &agent_id[..agent_id.len().min(8)])
 }
 
+/// Guest-side file the session's `pipe-pane` output is appended to.
+fn pipe_pane_path(session: &str) -> String {
+    format!("/tmp/{session}.out")
+}
+
+/// Guest-side secret env file of the session.
+fn secrets_path(session: &str) -> String {
+    format!("{GUEST_SECRETS_DIR}/{session}.env")
+}
+
 /// Build the `ssh ...` command vector that opens an interactive `tmux
 /// attach` to this agent's session. Same flags the supervisor uses, so
 /// callers (CLI, websocket bridge) share one definition.
@@ -147,8 +263,39 @@ pub fn build_attach_argv(handle: &AgentHandle, known_hosts: &PathBuf) -> Vec<Str
 }
 
 async fn ssh_oneshot(handle: &AgentHandle, remote_cmd: &str) -> Result<std::process::Output> {
-    let known_hosts = handle.log_path.with_file_name("known_hosts");
-    Command::new("ssh")
+    ssh_command(handle, remote_cmd)
+        .output()
+        .await
+        .map_err(|e| SupervisorError::SshFailed(format!("ssh: {e}")))
+}
+
+/// Run `remote_cmd` with `input` on its stdin — the only way secret
+/// values leave the supervisor.
+async fn ssh_with_stdin(
+    handle: &AgentHandle,
+    remote_cmd: &str,
+    input: &[u8],
+) -> Result<std::process::Output> {
+    let mut child = ssh_command(handle, remote_cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| SupervisorError::SshFailed(format!("ssh: {e}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input).await?;
+    }
+    child
+        .wait_with_output()
+        .await
+        .map_err(|e| SupervisorError::SshFailed(format!("ssh: {e}")))
+}
+
+fn ssh_command(handle: &AgentHandle, remote_cmd: &str) -> Command {
+    let known_hosts = handle.agent_dir.join("known_hosts");
+    let mut command = Command::new("ssh");
+    command
         .arg("-i")
         .arg(&handle.vm_ssh_key_path)
         .arg("-p")
@@ -162,45 +309,105 @@ async fn ssh_oneshot(handle: &AgentHandle, remote_cmd: &str) -> Result<std::proc
         .arg("-o")
         .arg("ConnectTimeout=10")
         .arg("-o")
+        .arg("ServerAliveInterval=15")
+        .arg("-o")
         .arg("BatchMode=yes")
         .arg(&handle.ssh_target)
         .arg(remote_cmd)
-        .stdin(Stdio::null())
-        .output()
-        .await
-        .map_err(|e| SupervisorError::SshFailed(format!("ssh: {e}")))
+        .stdin(Stdio::null());
+    command
+}
+
+/// Parse `tmux display -p '#{pane_width} #{pane_height}'` output.
+fn parse_pane_size(stdout: &[u8]) -> Option<(u16, u16)> {
+    parse_size_line(String::from_utf8_lossy(stdout).lines().last()?)
+}
+
+fn parse_size_line(line: &str) -> Option<(u16, u16)> {
+    let mut fields = line.split_whitespace();
+    let width = fields.next()?.parse().ok()?;
+    let height = fields.next()?.parse().ok()?;
+    Some((width, height))
+}
+
+/// Stream the guest's pipe-pane file into the recording until `cancel`
+/// fires. Reconnects from the last offset if the ssh connection drops.
+async fn record_output(
+    handle: Arc<AgentHandle>,
+    recorder: Arc<Recorder>,
+    cancel: CancellationToken,
+) {
+    let pipe_path = pipe_pane_path(&handle.session);
+    let mut offset: u64 = 0;
+    let mut buffer = vec![0u8; 16 * 1024];
+    while !cancel.is_cancelled() {
+        let remote_cmd = format!("tail -c +{} -f {}", offset + 1, shell_quote(&pipe_path));
+        match ssh_command(&handle, &remote_cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(mut child) => {
+                if let Some(mut stdout) = child.stdout.take() {
+                    loop {
+                        tokio::select! {
+                            _ = cancel.cancelled() => return,
+                            read = stdout.read(&mut buffer) => match read {
+                                Ok(0) | Err(_) => break,
+                                Ok(n) => {
+                                    recorder.output(buffer.get(..n).unwrap_or_default());
+                                    offset += n as u64;
+                                }
+                            },
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::debug!(session = %handle.session, error = %e, "recording tail failed to start")
+            }
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = tokio::time::sleep(RECORD_RETRY) => {}
+        }
+    }
 }
 
 /// Spawn the agent: SSH in, create a detached tmux session running the
-/// agent argv, hook up `pipe-pane` so output gets persisted to disk for
-/// scrollback. Returns once the session is up.
+/// agent argv, hook up `pipe-pane` so output gets recorded to
+/// `session.cast`. Returns once the session is up.
 pub async fn spawn(
     spec: AgentSpawnSpec,
     db: sqlx::SqlitePool,
-    on_exit: impl FnOnce(i32) -> futures_util::future::BoxFuture<'static, ()> + Send + 'static,
+    hooks: AgentHooks,
 ) -> Result<Arc<AgentHandle>> {
-    if let Some(parent) = spec.log_path.parent() {
-        tokio::fs::create_dir_all(parent).await.map_err(|e| {
+    tokio::fs::create_dir_all(&spec.agent_dir)
+        .await
+        .map_err(|e| {
             SupervisorError::FilesystemError(format!(
-                "failed to create agent log dir {}: {e}",
-                parent.display()
+                "failed to create agent dir {}: {e}",
+                spec.agent_dir.display()
             ))
         })?;
-    }
-    // Truncate any stale log so scrollback only contains this run. SLOP-34:
-    // swallowing the error here would leave operators with a phantom
-    // "agent running, no log appearing" mystery. Surface the path + cause.
-    tokio::fs::write(&spec.log_path, b"").await.map_err(|e| {
-        SupervisorError::FilesystemError(format!(
-            "failed to truncate agent log {}: {e}",
-            spec.log_path.display()
-        ))
-    })?;
 
     let session = session_name(&spec.agent_id);
+    let secret_values =
+        secrets::resolve(&db, spec.secret_key.as_ref(), &spec.secret_env).await?;
+    let secrets_file = (!secret_values.is_empty()).then(|| secrets_path(&session));
 
-    // Build the env-prefixed shell command that tmux will run.
+    // Build the env-prefixed shell command that tmux will run. Secrets
+    // are sourced from their file with `set -a` so they're exported;
+    // `MOWS_SECRETS_FILE` tells the claude bootstrap where to find them
+    // again after `su` drops the environment.
     let mut env_prefix = String::new();
+    if let Some(file) = &secrets_file {
+        let file = shell_quote(file);
+        env_prefix.push_str(&format!(
+            "set -a; . {file}; set +a; export MOWS_SECRETS_FILE={file}; "
+        ));
+    }
     for (k, v) in &spec.env {
         let escaped = v.replace('\'', "'\\''");
         env_prefix.push_str(&format!("{k}='{escaped}' "));
@@ -216,34 +423,63 @@ pub async fn spawn(
         .join(" ");
     let inner_cmd = format!("{env_prefix}exec {argv_str}");
     // `tmux new-session -d` returns immediately; the agent runs in the
-    // detached session. `pipe-pane` tees the pane to a file inside the
-    // VM, and `cat | nc -l` (or scp later) would be how to retrieve it
-    // for live tail. For now, we run the supervisor-side log capture by
-    // having the supervisor periodically pull the in-VM log.
-    //
-    // Simpler: don't bother with in-VM pipe-pane — use the same per-attach
-    // ssh sessions in the websocket proxy to capture history via tmux's
-    // built-in `capture-pane` on demand.
+    // detached session. When recording, `pipe-pane` is chained onto the
+    // same tmux invocation so it's hooked up before the agent has had a
+    // chance to print much; `record_output` tails the file it appends to.
     // `-e TERM=xterm-256color`: tmux defaults to TERM=screen for new
     // sessions, which claude's TUI rejects with `terminal does not
     // support clear` because alpine's ncurses doesn't ship the full
     // capability set for `screen`. xterm-256color is what the legacy
     // ssh-io path used and is known to be present (image-builder's
     // apk install pulls ncurses-terminfo-base which includes it).
-    let create_cmd = format!(
+    let mut create_cmd = format!(
         "tmux new-session -d -e TERM=xterm-256color -s {} -- /bin/sh -c {}",
         shell_quote(&session),
         shell_quote(&inner_cmd),
     );
+    let pipe_path = pipe_pane_path(&session);
+    if spec.recording.enabled {
+        // The pipe file is removed first so a reused session name doesn't
+        // replay a previous agent's output.
+        create_cmd = format!(
+            "rm -f {pipe} && {create_cmd} \\; pipe-pane -o -t {target} {pipe_cmd} \\; \
+             display-message -p -t {target} '#{{pane_width}} #{{pane_height}}'",
+            pipe = shell_quote(&pipe_path),
+            target = shell_quote(&session),
+            pipe_cmd = shell_quote(&format!("cat >> {}", shell_quote(&pipe_path))),
+        );
+    }
 
-    let handle = Arc::new(AgentHandle {
+    let mut handle = AgentHandle {
         vm_id: spec.vm_id.clone(),
         vm_ssh_port: spec.vm_ssh_port,
         vm_ssh_key_path: spec.vm_ssh_key_path.clone(),
         session: session.clone(),
-        log_path: spec.log_path.clone(),
+        agent_dir: spec.agent_dir.clone(),
         ssh_target: spec.ssh_target.clone(),
-    });
+        recorder: None,
+        secrets_file,
+    };
+
+    if let Some(file) = &handle.secrets_file {
+        // `mount` only when the directory isn't a mountpoint yet, so the
+        // files of agents already running in the VM survive.
+        let write_cmd = format!(
+            "umask 077 && mkdir -p {dir} && {{ mountpoint -q {dir} || \
+             mount -t tmpfs -o mode=0711,size=1m mows-secrets {dir}; }} && cat > {file}",
+            dir = GUEST_SECRETS_DIR,
+            file = shell_quote(file),
+        );
+        let write_out =
+            ssh_with_stdin(&handle, &write_cmd, secrets::env_file(&secret_values).as_bytes())
+                .await?;
+        if !write_out.status.success() {
+            let stderr = String::from_utf8_lossy(&write_out.stderr);
+            return Err(SupervisorError::SshFailed(format!(
+                "writing the agent's secrets failed: {stderr}"
+            )));
+        }
+    }
 
     let create_out = ssh_oneshot(&handle, &create_cmd).await?;
     if !create_out.status.success() {
@@ -253,6 +489,29 @@ pub async fn spawn(
         )));
     }
 
+    if spec.recording.enabled {
+        let (width, height) = parse_pane_size(&create_out.stdout).unwrap_or(DEFAULT_PANE_SIZE);
+        let header = CastHeader::new(width, height, Some(spec.name.clone()));
+        let path = recording_path_for(&spec.agent_dir);
+        // A recording failure must not take the agent down with it — the
+        // session is already running in the guest.
+        match Recorder::create(&path, &header, spec.recording.max_bytes) {
+            Ok(recorder) => handle.recorder = Some(Arc::new(recorder)),
+            Err(e) => {
+                tracing::warn!(agent_id = %spec.agent_id, error = %e, "agent session will not be recorded")
+            }
+        }
+    }
+    let handle = Arc::new(handle);
+    let record_cancel = CancellationToken::new();
+    if let Some(recorder) = &handle.recorder {
+        tokio::spawn(record_output(
+            handle.clone(),
+            recorder.clone(),
+            record_cancel.clone(),
+        ));
+    }
+
     // Mark agent as running immediately — the session exists and is
     // executing argv. (If argv exits instantly, the liveness poll below
     // catches it and flips status to stopped/failed.)
@@ -262,26 +521,63 @@ pub async fn spawn(
         .await;
     tracing::info!(agent_id = %spec.agent_id, session = %session, "tmux session created");
 
-    // Background liveness poll: every 3 s, ssh in and `tmux has-session`.
-    // When it's gone, mark the agent stopped (or failed) and call on_exit.
+    // Background liveness poll: every 3 s, ssh in and ask tmux for the
+    // pane size — success means the session is alive, and a changed size
+    // goes into the recording. For kinds with input prompts the same
+    // round trip captures the visible pane for `InputWatch`. When the
+    // session is gone, mark the agent stopped (or failed), close the
+    // recording and call on_exit.
     let poll_handle = handle.clone();
     let poll_id = spec.agent_id.clone();
     let poll_db = db.clone();
+    let mut probe_cmd = format!(
+        "tmux display-message -p -t {} '#{{pane_width}} #{{pane_height}}' 2>/dev/null",
+        shell_quote(&poll_handle.session)
+    );
+    let mut input_watch = (!spec.input_prompts.is_empty()).then(|| {
+        probe_cmd.push_str(&format!(
+            " && tmux capture-pane -p -t {}",
+            shell_quote(&poll_handle.session)
+        ));
+        InputWatch::new(spec.input_prompts.clone(), spec.input_idle, Instant::now())
+    });
+    let AgentHooks {
+        on_exit,
+        on_waiting,
+    } = hooks;
     tokio::spawn(async move {
         let mut consecutive_misses = 0u32;
         let mut on_exit = Some(on_exit);
         loop {
             tokio::time::sleep(Duration::from_secs(3)).await;
-            let alive = ssh_oneshot(
-                &poll_handle,
-                &format!("tmux has-session -t {} 2>/dev/null", poll_handle.session),
-            )
-            .await
-            .map(|o| o.status.success())
-            .unwrap_or(false);
+            let probe = ssh_oneshot(&poll_handle, &probe_cmd)
+                .await
+                .ok()
+                .filter(|o| o.status.success());
 
-            if alive {
+            if let Some(output) = probe {
                 consecutive_misses = 0;
+                let text = String::from_utf8_lossy(&output.stdout);
+                let (size_line, screen) = text.split_once('\n').unwrap_or((&text, ""));
+                if let (Some(recorder), Some((width, height))) =
+                    (&poll_handle.recorder, parse_size_line(size_line))
+                {
+                    recorder.resize(width, height);
+                }
+                let flipped = input_watch
+                    .as_mut()
+                    .and_then(|watch| watch.observe(screen, Instant::now()));
+                if let Some(waiting) = flipped {
+                    let since = waiting.then(|| chrono::Utc::now().to_rfc3339());
+                    let _ = sqlx::query(
+                        "UPDATE agents SET waiting_since = ?1 WHERE id = ?2 AND status = 'running'",
+                    )
+                    .bind(&since)
+                    .bind(&poll_id)
+                    .execute(&poll_db)
+                    .await;
+                    on_waiting(waiting).await;
+                }
                 continue;
             }
             // Two consecutive misses to defend against transient ssh
@@ -294,16 +590,37 @@ pub async fn spawn(
             tracing::info!(agent_id = %poll_id, "tmux session gone; reaping agent");
             let now = chrono::Utc::now().to_rfc3339();
             let _ = sqlx::query(
-                "UPDATE agents SET status = 'stopped', exited_at = ?1, exit_code = 0 \
-                 WHERE id = ?2 AND status NOT IN ('stopped','failed')",
+                "UPDATE agents SET status = 'stopped', exited_at = ?1, exit_code = 0, \
+                 waiting_since = NULL WHERE id = ?2 AND status NOT IN ('stopped','failed')",
             )
             .bind(&now)
             .bind(&poll_id)
             .execute(&poll_db)
             .await;
+            record_cancel.cancel();
+            if let Some(recorder) = &poll_handle.recorder {
+                let _ = sqlx::query("UPDATE agents SET recording_bytes = ?1 WHERE id = ?2")
+                    .bind(i64::try_from(recorder.finish()).unwrap_or(i64::MAX))
+                    .bind(&poll_id)
+                    .execute(&poll_db)
+                    .await;
+            }
             if let Some(hook) = on_exit.take() {
                 hook(0).await;
             }
+            let leftovers: Vec<String> = poll_handle
+                .recorder
+                .as_ref()
+                .map(|_| pipe_pane_path(&poll_handle.session))
+                .into_iter()
+                .chain(poll_handle.secrets_file.clone())
+                .map(|path| shell_quote(&path))
+                .collect();
+            if !leftovers.is_empty() {
+                // Best-effort: the VM may already be gone.
+                let _ = ssh_oneshot(&poll_handle, &format!("rm -f {}", leftovers.join(" ")))
+                    .await;
+            }
             break;
         }
     });
@@ -318,3 +635,36 @@ fn shell_quote(s: &str) -> String {
         format!("'{}'", s.replace('\'', "'\\''"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kinds::{builtin_shell, compile_input_prompts};
+
+    #[test]
+    fn input_watch_needs_a_still_screen_at_a_prompt() {
+        let prompts = compile_input_prompts(&builtin_shell().input_prompts).unwrap();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut watch = InputWatch::new(prompts, Duration::from_secs(10), start);
+
+        assert_eq!(watch.observe("$ make\nbuilding...\n\n", at(0)), None);
+        // Still, but not at a prompt.
+        assert_eq!(watch.observe("$ make\nbuilding...", at(30)), None);
+        assert_eq!(watch.observe("$ make\ndone\n$\n\n", at(31)), None);
+        // At a prompt, but not still for long enough yet.
+        assert_eq!(watch.observe("$ make\ndone\n$", at(35)), None);
+        assert_eq!(watch.observe("$ make\ndone\n$", at(41)), Some(true));
+        assert_eq!(watch.observe("$ make\ndone\n$", at(50)), None);
+        assert_eq!(watch.observe("$ make\ndone\n$ ls", at(51)), Some(false));
+    }
+
+    #[test]
+    fn probe_output_splits_into_size_and_screen() {
+        let text = "120 40\n$ ls\nfoo\n";
+        let (size_line, screen) = text.split_once('\n').unwrap();
+        assert_eq!(parse_size_line(size_line), Some((120, 40)));
+        assert_eq!(screen, "$ ls\nfoo\n");
+        assert_eq!(parse_pane_size(b"ignored\n80 24\n"), Some((80, 24)));
+    }
+}
@@ -2,26 +2,37 @@
 //!
 //! Agents are workloads running inside a VM (one VM may host many). They're
 //! spawned via SSH from the supervisor; their stdin/stdout is fanned out
-//! over a websocket so the web UI can both watch and type into them.
+//! over a websocket so the web UI can both watch and type into them. Every
+//! session is recorded (`crate::recording`) and served back from
+//! `/v1/agents/{id}/recording`.
 
 use std::path::PathBuf;
+use std::time::Duration;
 
 use axum::extract::ws::{Message, WebSocketUpgrade};
-use axum::extract::{Extension, Path, State};
-use axum::response::IntoResponse;
+use axum::extract::{Extension, Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use axum::{Json, Router};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::{IntoParams, ToSchema};
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 
-use crate::agent_runtime::{self, AgentSpawnSpec};
+use crate::agent_runtime::{self, AgentHooks, AgentSpawnSpec};
 use crate::api::auth_middleware::AuthContext;
 use crate::api::types::{ErrorResponse, OperationResult};
+use crate::audit::AuditTarget;
+use crate::config::SupervisorConfig;
+use crate::egress::{guest_proxy_env, NetworkPolicy};
 use crate::error::{Result, SupervisorError};
 use crate::events::SupervisorEvent;
+use crate::idle::resume_if_suspended;
+use crate::recording::{agent_dir_for, recording_path_for};
+use crate::secrets;
 use crate::ssh_keys::vm_key_paths;
 use crate::state::SharedState;
 
@@ -33,6 +44,8 @@ pub fn rest_router() -> OpenApiRouter<SharedState> {
         .routes(routes!(put_agent))
         .routes(routes!(get_agent, update_agent, delete_agent))
         .routes(routes!(stop_agent))
+        .routes(routes!(wait_agent))
+        .routes(routes!(get_agent_recording))
 }
 
 /// Agent websocket endpoints — not part of OpenAPI.
@@ -51,8 +64,11 @@ pub struct UpdateAgentRequest {
 /// validation (unknown variants → 400 with a descriptive error before the
 /// handler runs), eliminates the stringly-typed match in `spawn_agent`,
 /// and gives the TypeScript codegen a real union literal.
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
 #[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 pub enum AgentKindName {
     #[default]
     Shell,
@@ -67,11 +83,21 @@ impl AgentKindName {
         }
     }
 
-    fn to_kind(self) -> crate::kinds::AgentKind {
-        match self {
+    /// Inverse of [`Self::as_str`], for kind names stored as plain text.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "shell" => Some(Self::Shell),
+            "claude" => Some(Self::Claude),
+            _ => None,
+        }
+    }
+
+    /// The builtin manifest with the config's `agent_kinds` extras.
+    fn to_kind(self, config: &SupervisorConfig) -> crate::kinds::AgentKind {
+        crate::kinds::configured(&config.agent_kinds, self.as_str()).unwrap_or_else(|| match self {
             Self::Shell => crate::kinds::builtin_shell(),
             Self::Claude => crate::kinds::builtin_claude(),
-        }
+        })
     }
 }
 
@@ -99,16 +125,23 @@ pub struct AgentSummary {
     /// rows written before owner tracking was plumbed; admins can still
     /// see them, non-admin users cannot.
     pub owner_user_id: Option<String>,
+    /// Size of the finished session recording. `None` while the agent is
+    /// running (the recording is still growing), when recording was
+    /// disabled, or once retention has removed it.
+    pub recording_bytes: Option<i64>,
+    /// Since when the agent has been sitting at one of its kind's input
+    /// prompts; `None` while it works or once it exited.
+    pub waiting_since: Option<String>,
 }
 
-const AGENT_COLUMNS: &str =
-    "id, vm_id, name, kind, status, started_at, exited_at, exit_code, owner_user_id";
+const AGENT_COLUMNS: &str = "id, vm_id, name, kind, status, started_at, exited_at, exit_code, \
+     owner_user_id, recording_bytes, waiting_since";
 
 #[utoipa::path(
     get,
     path = "/v1/agents",
     tag = "agents",
-    description = "List agents the caller can see (own agents only for non-admin users).",
+    description = "List agents the caller can see (for members: every agent in a VM they own or that was shared with them).",
     responses(
         (status = 200, description = "Agents in the database", body = Vec<AgentSummary>),
         (status = 500, description = "Internal error", body = ErrorResponse),
@@ -123,11 +156,16 @@ async fn list_all_agents(
         let rows: Vec<AgentSummary> = sqlx::query_as(&sql).fetch_all(&state.db).await?;
         Ok(Json(rows))
     } else {
-        // Non-admin: only rows they own. `user_id` is guaranteed `Some`
-        // here because `is_admin()` is the only path that leaves it
-        // `None` (admin token + auth-disabled both set role=admin).
+        // Non-admin: every agent in a VM they own or that was shared
+        // with them (`AuthContext::may_access_agent`). `user_id` is
+        // guaranteed `Some` here because `is_admin()` is the only path
+        // that leaves it `None` (admin token + auth-disabled both set
+        // role=admin).
         let sql = format!(
-            "SELECT {AGENT_COLUMNS} FROM agents WHERE owner_user_id = ?1 ORDER BY started_at DESC"
+            "SELECT {AGENT_COLUMNS} FROM agents WHERE vm_id IN \
+             (SELECT id FROM vms WHERE owner_user_id = ?1 \
+              UNION SELECT vm_id FROM vm_shares WHERE user_id = ?1) \
+             ORDER BY started_at DESC"
         );
         let rows: Vec<AgentSummary> = sqlx::query_as(&sql)
             .bind(actor.user_id.as_deref().unwrap_or(""))
@@ -186,7 +224,7 @@ async fn get_agent(
     Path(id): Path<String>,
 ) -> Result<Json<AgentSummary>> {
     let agent = load_agent(&state, &id).await?;
-    if !actor.may_access(agent.owner_user_id.as_deref()) {
+    if !actor.may_access_agent(&agent.vm_id) {
         // Surface as 404 rather than 403 — non-admins should not learn
         // that an agent with this id exists in someone else's tenant.
         return Err(SupervisorError::NotFound(format!("agent {id} not found")));
@@ -194,6 +232,114 @@ async fn get_agent(
     Ok(Json(agent))
 }
 
+/// Longest a single `wait` request is held open.
+const MAX_WAIT_SECS: u64 = 300;
+
+/// What `GET /v1/agents/{id}/wait` waits for.
+#[derive(Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitCondition {
+    /// The agent exited or is waiting for input.
+    #[default]
+    Attention,
+    /// The agent exited.
+    Exit,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct WaitAgentQuery {
+    #[serde(default)]
+    #[param(inline)]
+    pub until: WaitCondition,
+    /// Seconds to hold the request open (default 25, at most 300).
+    pub timeout_secs: Option<u64>,
+}
+
+/// Why `wait` returned.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitOutcome {
+    Exited,
+    WaitingForInput,
+    TimedOut,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AgentWaitResponse {
+    pub outcome: WaitOutcome,
+    pub agent: AgentSummary,
+}
+
+fn wait_outcome(agent: &AgentSummary, until: WaitCondition) -> Option<WaitOutcome> {
+    if matches!(agent.status.as_str(), "stopped" | "failed") {
+        Some(WaitOutcome::Exited)
+    } else if until == WaitCondition::Attention && agent.waiting_since.is_some() {
+        Some(WaitOutcome::WaitingForInput)
+    } else {
+        None
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/agents/{id}/wait",
+    tag = "agents",
+    description = "Long-poll until the agent exits or (with `until=attention`, the default) \
+                   waits for input. Returns at once if it already does; `timed_out` after \
+                   `timeout_secs`.",
+    params(("id" = String, Path, description = "Agent id"), WaitAgentQuery),
+    responses(
+        (status = 200, description = "Why the wait ended, and the agent", body = AgentWaitResponse),
+        (status = 404, description = "Unknown agent", body = ErrorResponse),
+    )
+)]
+async fn wait_agent(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+    Query(query): Query<WaitAgentQuery>,
+) -> Result<Json<AgentWaitResponse>> {
+    // Subscribe before the first read so a transition between the read
+    // and the subscription can't be missed.
+    let mut events = state.events.subscribe();
+    let timeout = Duration::from_secs(query.timeout_secs.unwrap_or(25).min(MAX_WAIT_SECS));
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let agent = load_agent(&state, &id).await?;
+        if !actor.may_access_agent(&agent.vm_id) {
+            return Err(SupervisorError::NotFound(format!("agent {id} not found")));
+        }
+        if let Some(outcome) = wait_outcome(&agent, query.until) {
+            return Ok(Json(AgentWaitResponse { outcome, agent }));
+        }
+        // Sleep until an event about this agent (or a lag, after which
+        // the state is simply re-read).
+        loop {
+            let event = match tokio::time::timeout_at(deadline, events.recv()).await {
+                Err(_) | Ok(Err(RecvError::Closed)) => {
+                    return Ok(Json(AgentWaitResponse {
+                        outcome: WaitOutcome::TimedOut,
+                        agent,
+                    }))
+                }
+                Ok(Err(RecvError::Lagged(_))) => break,
+                Ok(Ok(event)) => event,
+            };
+            match event {
+                SupervisorEvent::AgentUpdated { id: event_id }
+                | SupervisorEvent::AgentDeleted { id: event_id }
+                | SupervisorEvent::AgentExited { id: event_id, .. }
+                | SupervisorEvent::AgentWaitingForInput { id: event_id, .. }
+                    if event_id == id =>
+                {
+                    break
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
 pub(super) async fn load_agent(state: &SharedState, id: &str) -> Result<AgentSummary> {
     let sql = format!("SELECT {AGENT_COLUMNS} FROM agents WHERE id = ?1");
     sqlx::query_as(&sql)
@@ -208,6 +354,13 @@ struct VmRow {
     status: String,
     host_ssh_port: Option<i64>,
     owner_user_id: Option<String>,
+    #[sqlx(json)]
+    network_policy: NetworkPolicy,
+    /// From the VM's preset: layered over the kind's env, and the kind
+    /// used when the request omits one.
+    #[sqlx(json)]
+    env: std::collections::BTreeMap<String, String>,
+    agent_kind: Option<AgentKindName>,
 }
 
 #[utoipa::path(
@@ -230,16 +383,16 @@ async fn create_agent(
     Extension(actor): Extension<AuthContext>,
     Path(vm_id): Path<String>,
     Json(request): Json<CreateAgentRequest>,
-) -> Result<Json<AgentSummary>> {
-    spawn_agent(
+) -> Result<(Extension<AuditTarget>, Json<AgentSummary>)> {
+    let agent = spawn_agent(
         &state,
         &actor,
         vm_id,
         uuid::Uuid::new_v4().to_string(),
         request,
     )
-    .await
-    .map(Json)
+    .await?;
+    Ok((Extension(AuditTarget(agent.id.clone())), Json(agent)))
 }
 
 #[utoipa::path(
@@ -285,7 +438,7 @@ async fn put_agent(
         Ok(existing) => {
             // Ownership: an authenticated user can't materialise (or
             // discover) an agent owned by someone else.
-            if !actor.may_access(existing.owner_user_id.as_deref()) {
+            if !actor.may_access_agent(&existing.vm_id) {
                 return Err(SupervisorError::NotFound(format!(
                     "agent {agent_id} not found"
                 )));
@@ -328,22 +481,26 @@ async fn spawn_agent(
     id: String,
     request: CreateAgentRequest,
 ) -> Result<AgentSummary> {
-    let kind_name = request.kind.unwrap_or_default();
-    let kind = kind_name.to_kind();
-
     // Validate VM exists, reachable, and visible to the caller.
     let vm: VmRow = sqlx::query_as(
-        "SELECT status, host_ssh_port, owner_user_id FROM vms WHERE id = ?1",
+        "SELECT status, host_ssh_port, owner_user_id, network_policy, env, agent_kind \
+         FROM vms WHERE id = ?1",
     )
     .bind(&vm_id)
     .fetch_optional(&state.db)
     .await?
     .ok_or_else(|| SupervisorError::NotFound(format!("vm {vm_id} not found")))?;
-    if !actor.may_access(vm.owner_user_id.as_deref()) {
+    if !actor.may_access_vm(&vm_id, vm.owner_user_id.as_deref()) {
         // Same reasoning as `get_agent`: don't leak existence of someone
         // else's VM to a non-owner.
         return Err(SupervisorError::NotFound(format!("vm {vm_id} not found")));
     }
+    let kind_name = request.kind.or(vm.agent_kind).unwrap_or_default();
+    let kind = kind_name.to_kind(&state.config);
+    // Count as attached until the agent row exists, so the idle monitor
+    // can't suspend the VM halfway through the spawn.
+    let _attached = state.ssh_sessions.attach(&vm_id);
+    resume_if_suspended(state, &vm_id).await?;
     if vm.status != "running" {
         // SLOP-23: wrong-status is a caller error, not an internal error.
         // 409 lets the client distinguish "transient — retry after the
@@ -371,6 +528,9 @@ async fn spawn_agent(
         .name
         .unwrap_or_else(|| format!("{}-{}", kind_name.as_str(), Utc::now().format("%Y%m%d-%H%M%S")));
     let name = crate::api::validation::validate_resource_name("name", &raw_name)?;
+    let (kind_env, secret_env) = kind.split_secret_env();
+    secrets::ensure_present(&state.db, state.config.secret_key.as_ref(), secret_env.values())
+        .await?;
     let started_at = Utc::now().to_rfc3339();
     let owner_user_id = actor.user_id.clone();
 
@@ -399,7 +559,7 @@ async fn spawn_agent(
         // owner guards `put_agent` enforces above, then return the
         // canonical row.
         let existing = load_agent(state, &id).await?;
-        if !actor.may_access(existing.owner_user_id.as_deref()) {
+        if !actor.may_access_agent(&existing.vm_id) {
             return Err(SupervisorError::NotFound(format!("agent {id} not found")));
         }
         if existing.vm_id != vm_id {
@@ -418,55 +578,96 @@ async fn spawn_agent(
         return Ok(existing);
     }
 
-    let log_path = state
-        .config
-        .state_dir
-        .join("agents")
-        .join(&id)
-        .join("agent.log");
+    let agent_dir = agent_dir_for(&state.config.state_dir, &id);
 
     let argv = if kind.argv.is_empty() {
         vec![kind.binary.clone()]
     } else {
         kind.argv.clone()
     };
-    let env: std::collections::BTreeMap<String, String> = kind.env.into_iter().collect();
+    let mut env = kind_env;
+    env.extend(vm.env);
+    if !vm.network_policy.is_open() {
+        // Non-login ssh commands don't read /etc/profile.d, so the proxy
+        // settings the guest gets from `profile.sh` are repeated here.
+        env.extend(guest_proxy_env());
+    }
 
     let (vm_priv_key, _) = vm_key_paths(&state.config.state_dir, &vm_id);
     let ssh_target = format!(
         "{}@{}",
         state.config.guest_ssh_user, state.config.external_host
     );
+    let input_prompts = crate::kinds::compile_input_prompts(&kind.input_prompts)
+        .map_err(|e| SupervisorError::InvalidState(format!("agent kind `{}`: {e}", kind.name)))?;
     let spec = AgentSpawnSpec {
         agent_id: id.clone(),
         vm_id: vm_id.clone(),
         vm_ssh_port: ssh_port,
         vm_ssh_key_path: PathBuf::from(vm_priv_key),
+        name: name.clone(),
         argv,
         env,
-        log_path,
+        secret_env,
+        secret_key: state.config.secret_key.clone(),
+        agent_dir,
+        recording: state.config.recordings.clone(),
         ssh_target,
+        input_prompts,
+        input_idle: Duration::from_secs(state.config.notifications.input_idle_secs),
     };
 
     // The runtimes registry needs to know to remove the entry on exit; bind
     // a closure that drops the registry slot AND broadcasts an
-    // `AgentUpdated` so subscribers refresh once the liveness probe reaps
-    // the tmux session. Capture clones of the registry handle (Arc inside)
-    // and the event bus so the hook is fully owned.
+    // `AgentUpdated` plus an `AgentExited` carrying the final status, so
+    // subscribers refresh (and notification sinks fire) once the liveness
+    // probe reaps the tmux session. Capture clones of the registry handle
+    // (Arc inside), the pool and the event bus so the hooks are fully
+    // owned.
     let registry = state.agent_runtimes.clone();
     let events = state.events.clone();
-    let id_for_hook = id.clone();
+    let db = state.db.clone();
+    let (id_for_hook, vm_id_for_hook) = (id.clone(), vm_id.clone());
     let on_exit = move |_code: i32| {
-        let registry = registry.clone();
+        Box::pin(async move {
+            registry.remove(&id_for_hook).await;
+            let outcome: Option<(String, Option<i64>)> =
+                sqlx::query_as("SELECT status, exit_code FROM agents WHERE id = ?1")
+                    .bind(&id_for_hook)
+                    .fetch_optional(&db)
+                    .await
+                    .unwrap_or_default();
+            events.emit(SupervisorEvent::AgentUpdated {
+                id: id_for_hook.clone(),
+            });
+            if let Some((status, exit_code)) = outcome {
+                events.emit(SupervisorEvent::AgentExited {
+                    id: id_for_hook,
+                    vm_id: vm_id_for_hook,
+                    status,
+                    exit_code,
+                });
+            }
+        }) as futures_util::future::BoxFuture<'static, ()>
+    };
+    let events = state.events.clone();
+    let (id_for_hook, vm_id_for_hook) = (id.clone(), vm_id.clone());
+    let on_waiting = move |waiting: bool| {
         let events = events.clone();
-        let id = id_for_hook.clone();
+        let (id, vm_id) = (id_for_hook.clone(), vm_id_for_hook.clone());
         Box::pin(async move {
-            registry.remove(&id).await;
-            events.emit(SupervisorEvent::AgentUpdated { id });
+            events.emit(SupervisorEvent::AgentUpdated { id: id.clone() });
+            if waiting {
+                events.emit(SupervisorEvent::AgentWaitingForInput { id, vm_id });
+            }
         }) as futures_util::future::BoxFuture<'static, ()>
     };
+    let hooks = AgentHooks {
+        on_exit: Box::new(on_exit),
+        on_waiting: Box::new(on_waiting),
+    };
 
-    let handle = agent_runtime::spawn(spec, state.db.clone(), on_exit).await?;
+    let handle = agent_runtime::spawn(spec, state.db.clone(), hooks).await?;
     state.agent_runtimes.insert(id.clone(), handle).await;
 
     // Two events fire after spawn: the row exists (AgentCreated) and the
@@ -489,6 +690,8 @@ async fn spawn_agent(
         exited_at: None,
         exit_code: None,
         owner_user_id,
+        recording_bytes: None,
+        waiting_since: None,
     })
 }
 
@@ -510,12 +713,12 @@ async fn stop_agent(
     Path(id): Path<String>,
 ) -> Result<Json<OperationResult>> {
     let agent = load_agent(&state, &id).await?;
-    if !actor.may_access(agent.owner_user_id.as_deref()) {
+    if !actor.may_access_agent(&agent.vm_id) {
         return Err(SupervisorError::NotFound(format!("agent {id} not found")));
     }
     let exited_at = Utc::now().to_rfc3339();
     let query_result = sqlx::query(
-        "UPDATE agents SET status = 'stopped', exited_at = ?1 \
+        "UPDATE agents SET status = 'stopped', exited_at = ?1, waiting_since = NULL \
          WHERE id = ?2 AND status != 'stopped'",
     )
     .bind(&exited_at)
@@ -554,7 +757,7 @@ async fn update_agent(
     Json(request): Json<UpdateAgentRequest>,
 ) -> Result<Json<AgentSummary>> {
     let agent = load_agent(&state, &id).await?;
-    if !actor.may_access(agent.owner_user_id.as_deref()) {
+    if !actor.may_access_agent(&agent.vm_id) {
         return Err(SupervisorError::NotFound(format!("agent {id} not found")));
     }
     let trimmed = crate::api::validation::validate_resource_name("name", &request.name)?;
@@ -574,7 +777,8 @@ async fn update_agent(
     delete,
     path = "/v1/agents/{id}",
     tag = "agents",
-    description = "Delete an agent and its on-disk state. The VM stays running.",
+    description = "Delete an agent and its on-disk state, including its session recording. \
+                   The VM stays running.",
     params(("id" = String, Path, description = "Agent id")),
     responses(
         (status = 200, description = "Agent deleted", body = OperationResult),
@@ -587,7 +791,7 @@ async fn delete_agent(
     Path(id): Path<String>,
 ) -> Result<Json<OperationResult>> {
     let agent = load_agent(&state, &id).await?;
-    if !actor.may_access(agent.owner_user_id.as_deref()) {
+    if !actor.may_access_agent(&agent.vm_id) {
         return Err(SupervisorError::NotFound(format!("agent {id} not found")));
     }
     let query_result = sqlx::query("DELETE FROM agents WHERE id = ?1")
@@ -597,10 +801,56 @@ async fn delete_agent(
     if query_result.rows_affected() == 0 {
         return Err(SupervisorError::NotFound(format!("agent {id} not found")));
     }
+    let agent_dir = agent_dir_for(&state.config.state_dir, &id);
+    if let Err(e) = tokio::fs::remove_dir_all(&agent_dir).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!(
+                agent_id = %id,
+                dir = %agent_dir.display(),
+                error = %e,
+                "failed to remove agent dir on delete"
+            );
+        }
+    }
     state.events.emit(SupervisorEvent::AgentDeleted { id: id.clone() });
     Ok(Json(OperationResult::deleted(id)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/agents/{id}/recording",
+    tag = "agents",
+    description = "The agent's session recording in asciicast v2 format: a JSON header line, \
+                   then one `[seconds, code, data]` line per output (`o`), input (`i`), \
+                   resize (`r`) or marker (`m`) event. Grows while the agent runs.",
+    params(("id" = String, Path, description = "Agent id")),
+    responses(
+        (status = 200, description = "Recording", body = String, content_type = "application/x-asciicast"),
+        (status = 404, description = "Unknown agent, or no recording (disabled or removed by retention)", body = ErrorResponse),
+    )
+)]
+async fn get_agent_recording(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let agent = load_agent(&state, &id).await?;
+    if !actor.may_access_agent(&agent.vm_id) {
+        return Err(SupervisorError::NotFound(format!("agent {id} not found")));
+    }
+    let path = recording_path_for(&agent_dir_for(&state.config.state_dir, &id));
+    let body = match tokio::fs::read(&path).await {
+        Ok(body) => body,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(SupervisorError::NotFound(format!(
+                "agent {id} has no recording"
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(([(header::CONTENT_TYPE, "application/x-asciicast")], body).into_response())
+}
+
 /// Bidirectional websocket bound to a fresh ssh+`tmux attach` session
 /// inside the VM. Each websocket connection gets its own private pty and
 /// joins the agent's shared tmux session — so multi-client works (tmux's
@@ -617,9 +867,10 @@ async fn get_agent_io(
     // connection — otherwise the WebSocket would attach to someone
     // else's session.
     let agent = load_agent(&state, &id).await?;
-    if !actor.may_access(agent.owner_user_id.as_deref()) {
+    if !actor.may_access_agent(&agent.vm_id) {
         return Err(SupervisorError::NotFound(format!("agent {id} not found")));
     }
+    resume_if_suspended(&state, &agent.vm_id).await?;
     let runtime = state
         .agent_runtimes
         .get(&id)
@@ -650,11 +901,8 @@ async fn proxy_agent_io(
     // Per-attach known_hosts so a future server-key rotation doesn't
     // poison parallel connections.
     let known_hosts = handle
-        .log_path
-        .with_file_name(format!(
-            "known_hosts.ws-{}",
-            uuid::Uuid::new_v4().simple()
-        ));
+        .agent_dir
+        .join(format!("known_hosts.ws-{}", uuid::Uuid::new_v4().simple()));
     let _ = tokio::fs::remove_file(&known_hosts).await;
 
     // RAII guard: even if the WS proxy tasks below panic or are cancelled
@@ -695,21 +943,26 @@ async fn proxy_agent_io(
 
     let (mut sink, mut stream) = ws.split();
 
+    // Everything a client types lands in the recording as `i` events,
+    // bracketed by attach / detach markers.
+    let recorder = handle.recorder.clone();
+    if let Some(recorder) = &recorder {
+        recorder.marker("client attached");
+    }
+
     let ws_to_ssh = async move {
         while let Some(msg) = stream.next().await {
-            match msg {
-                Ok(Message::Binary(b)) => {
-                    if ssh_stdin.write_all(&b).await.is_err() {
-                        break;
-                    }
-                }
-                Ok(Message::Text(t)) => {
-                    if ssh_stdin.write_all(t.as_bytes()).await.is_err() {
-                        break;
-                    }
-                }
+            let input: &[u8] = match &msg {
+                Ok(Message::Binary(b)) => b,
+                Ok(Message::Text(t)) => t.as_bytes(),
                 Ok(Message::Close(_)) | Err(_) => break,
-                Ok(_) => {}
+                Ok(_) => continue,
+            };
+            if let Some(recorder) = &recorder {
+                recorder.input(input);
+            }
+            if ssh_stdin.write_all(input).await.is_err() {
+                break;
             }
         }
         let _ = ssh_stdin.shutdown().await;
@@ -737,6 +990,9 @@ async fn proxy_agent_io(
 
     let (ws_to_ssh_result, ssh_to_ws_result) = tokio::join!(ws_to_ssh, ssh_to_ws);
     let _ = ssh.kill().await;
+    if let Some(recorder) = &handle.recorder {
+        recorder.marker("client detached");
+    }
     // _known_hosts_guard drops here, removing the file regardless of
     // whether the proxy tasks returned cleanly.
     ws_to_ssh_result?;
@@ -0,0 +1,195 @@
+//! `/v1/audit` — the audit log (see `crate::audit`), plus the middleware
+//! that writes it. Admins see every entry; members see their own.
+
+use std::net::SocketAddr;
+
+use axum::body::Body;
+use axum::extract::connect_info::ConnectInfo;
+use axum::extract::{Extension, Query, State};
+use axum::http::{header, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::ErrorResponse;
+use crate::audit::{
+    self, AuditEntry, AuditFilter, AuditOutcome, AuditTarget, NewAuditEntry, MAX_PAGE_SIZE,
+    STATIC_ADMIN_ACTOR,
+};
+use crate::error::Result;
+use crate::state::SharedState;
+
+/// Page size when `limit` is omitted.
+const DEFAULT_PAGE_SIZE: u32 = 100;
+
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(list_audit))
+        .routes(routes!(export_audit))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AuditQuery {
+    /// Only entries by this username (`admin` for the static admin
+    /// identity). Ignored for members, who only see their own.
+    pub actor: Option<String>,
+    /// Exact action (`vm.stop`) or resource (`vm`).
+    pub action: Option<String>,
+    /// Id, username or secret name the action targeted.
+    pub target: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    /// Entries at or after this time (RFC 3339).
+    pub since: Option<DateTime<Utc>>,
+    /// Entries before this time (RFC 3339).
+    pub until: Option<DateTime<Utc>>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<i64>,
+    /// Entries per page, 1-1000. Defaults to 100.
+    pub limit: Option<u32>,
+}
+
+impl AuditQuery {
+    fn filter(&self, actor: &AuthContext) -> AuditFilter {
+        let own_only = !actor.is_admin();
+        AuditFilter {
+            actor_user_id: own_only.then(|| actor.user_id.clone().unwrap_or_default()),
+            actor: self.actor.clone().filter(|_| !own_only),
+            action: self.action.clone(),
+            target: self.target.clone(),
+            outcome: self.outcome,
+            since: self.since,
+            until: self.until,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    /// Pass as `cursor` for the next (older) page; `None` on the last one.
+    pub next_cursor: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/audit",
+    tag = "audit",
+    description = "Audit log entries matching the filters, newest first. Admins see every \
+                   entry; members see their own.",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "One page of entries", body = AuditPage),
+        (status = 400, description = "Malformed filter", body = ErrorResponse),
+    )
+)]
+async fn list_audit(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let entries = audit::query(&state.db, &query.filter(&actor), query.cursor, limit).await?;
+    let next_cursor = if entries.len() == limit as usize {
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+    Ok(Json(AuditPage {
+        entries,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/audit/export",
+    tag = "audit",
+    description = "Every entry matching the filters as JSON lines (one `AuditEntry` per line), \
+                   oldest first. `cursor` and `limit` are ignored.",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Entries", body = String, content_type = "application/x-ndjson"),
+        (status = 400, description = "Malformed filter", body = ErrorResponse),
+    )
+)]
+async fn export_audit(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response> {
+    let filter = query.filter(&actor);
+    let mut entries = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = audit::query(&state.db, &filter, cursor, MAX_PAGE_SIZE).await?;
+        let last_page = page.len() < MAX_PAGE_SIZE as usize;
+        cursor = page.last().map(|entry| entry.id);
+        entries.extend(page);
+        if last_page {
+            break;
+        }
+    }
+    let mut body = String::new();
+    for entry in entries.iter().rev() {
+        body.push_str(&serde_json::to_string(entry)?);
+        body.push('\n');
+    }
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"mows-audit.jsonl\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Peer IP of the request; `None` over the unix socket.
+pub fn source_ip<B>(request: &Request<B>) -> Option<String> {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+}
+
+/// Record requests `audit::classify` recognises once they're answered.
+/// Sits inside the auth layer, so the `AuthContext` is always present.
+pub async fn record_request(
+    State(state): State<SharedState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some((action, target)) = audit::classify(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let actor = request.extensions().get::<AuthContext>().cloned();
+    let source_ip = source_ip(&request);
+    let response = next.run(request).await;
+    let target = response
+        .extensions()
+        .get::<AuditTarget>()
+        .map(|AuditTarget(target)| target.clone())
+        .or(target);
+    let entry = NewAuditEntry {
+        actor_user_id: actor.and_then(|actor| actor.user_id),
+        actor: STATIC_ADMIN_ACTOR.to_string(),
+        source_ip,
+        action: action.to_string(),
+        target,
+        outcome: AuditOutcome::from_status(response.status()),
+        status_code: Some(response.status().as_u16()),
+    };
+    if let Err(e) = audit::record(&state.db, entry).await {
+        tracing::warn!(action, error = %e, "failed to write audit log entry");
+    }
+    response
+}
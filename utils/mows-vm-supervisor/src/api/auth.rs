@@ -1,5 +1,9 @@
+use std::net::SocketAddr;
+
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
-use axum::extract::State;
+use axum::extract::connect_info::ConnectInfo;
+use axum::extract::{Extension, State};
+use axum::http::StatusCode;
 use axum::Json;
 use base64::Engine;
 use chrono::{Duration, Utc};
@@ -9,6 +13,7 @@ use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 
 use crate::api::types::ErrorResponse;
+use crate::audit::{self, AuditOutcome, NewAuditEntry};
 use crate::error::{Result, SupervisorError};
 use crate::state::SharedState;
 
@@ -54,6 +59,7 @@ struct UserRow {
 )]
 async fn login(
     State(state): State<SharedState>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
     Json(request): Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>> {
     let row: Option<UserRow> =
@@ -75,8 +81,28 @@ async fn login(
     let verify = Argon2::default().verify_password(request.password.as_bytes(), &parsed);
 
     let id = match (user_id, verify) {
-        (Some(id), Ok(())) => id,
-        _ => return Err(SupervisorError::Unauthorized),
+        (Some(id), Ok(())) => Some(id),
+        _ => None,
+    };
+    let (outcome, status) = match id {
+        Some(_) => (AuditOutcome::Success, StatusCode::OK),
+        None => (AuditOutcome::Failure, StatusCode::UNAUTHORIZED),
+    };
+    // A failure records the attempted username, not whether it exists.
+    let entry = NewAuditEntry {
+        actor_user_id: id.clone(),
+        actor: request.username.chars().take(64).collect(),
+        source_ip: connect_info.map(|Extension(ConnectInfo(addr))| addr.ip().to_string()),
+        action: audit::LOGIN_ACTION.to_string(),
+        target: None,
+        outcome,
+        status_code: Some(status.as_u16()),
+    };
+    if let Err(e) = audit::record(&state.db, entry).await {
+        tracing::warn!(error = %e, "failed to write audit log entry");
+    }
+    let Some(id) = id else {
+        return Err(SupervisorError::Unauthorized);
     };
 
     let token = generate_token();
@@ -3,19 +3,54 @@
 //! The unix-socket listener bypasses this layer (local trust domain — process
 //! uid is enough). On TCP, every protected route requires
 //! `Authorization: Bearer <token>` and the token must either match the
-//! configured `MOWS_VM_SUPERVISOR_API_TOKEN` (admin bootstrap) or resolve to
-//! an unexpired row in the `sessions` table created by `/v1/auth/login`.
+//! configured `MOWS_VM_SUPERVISOR_API_TOKEN` (admin bootstrap), resolve to
+//! an unexpired row in the `sessions` table created by `/v1/auth/login`, or
+//! be an unexpired personal access token (`crate::tokens`) whose scopes
+//! cover the route.
 
 use axum::body::Body;
 use axum::extract::State;
-use axum::http::{header, Request};
+use axum::http::{header, Method, Request};
 use axum::middleware::Next;
 use axum::response::Response;
 use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use subtle::ConstantTimeEq;
+use utoipa::ToSchema;
 
 use crate::error::{Result, SupervisorError};
 use crate::state::SharedState;
+use crate::tokens;
+
+/// Supervisor user role. `admin` sees and manages everything and
+/// administers users; `member` only sees VMs it owns or that were
+/// shared with it, and is subject to its per-user quota.
+///
+/// The `users.role` CHECK from `0001_init.sql` can't be widened in
+/// place, so `member` is stored as the legacy `user` value; the API
+/// still accepts `user` as an alias.
+#[derive(
+    Serialize, Deserialize, ToSchema, sqlx::Type, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum UserRole {
+    Admin,
+    #[default]
+    #[serde(alias = "user")]
+    #[sqlx(rename = "user")]
+    Member,
+}
+
+impl UserRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Member => "member",
+        }
+    }
+}
 
 /// Identity established by `require_auth`. Injected into request extensions
 /// so handlers can scope queries by user.
@@ -25,14 +60,19 @@ use crate::state::SharedState;
 #[derive(Debug, Clone)]
 pub struct AuthContext {
     pub user_id: Option<String>,
-    pub role: String,
+    pub role: UserRole,
+    /// VMs this member owns or that were shared with it (`vm_shares`),
+    /// loaded once per request. Empty for the static admin identity,
+    /// which never consults it.
+    pub vm_ids: HashSet<String>,
 }
 
 impl AuthContext {
     fn admin_static() -> Self {
         Self {
             user_id: None,
-            role: "admin".to_string(),
+            role: UserRole::Admin,
+            vm_ids: HashSet::new(),
         }
     }
 
@@ -47,7 +87,16 @@ impl AuthContext {
     /// gate ownership-scoped lookups without spreading the role-string
     /// comparison across files.
     pub fn is_admin(&self) -> bool {
-        self.role == "admin"
+        self.role == UserRole::Admin
+    }
+
+    /// Gate for admin-only endpoints (user administration).
+    pub fn require_admin(&self) -> Result<()> {
+        if self.is_admin() {
+            Ok(())
+        } else {
+            Err(SupervisorError::Forbidden)
+        }
     }
 
     /// True when this row's `owner_user_id` matches the caller (or the
@@ -64,12 +113,26 @@ impl AuthContext {
             _ => false,
         }
     }
+
+    /// `may_access` widened by `vm_shares`: the VM's owner, an admin, or
+    /// a member the VM was shared with. Gates everything that uses a VM
+    /// short of deleting it or changing who it is shared with.
+    pub fn may_access_vm(&self, vm_id: &str, owned_by: Option<&str>) -> bool {
+        self.may_access(owned_by) || self.vm_ids.contains(vm_id)
+    }
+
+    /// Agents follow their VM: everyone who may use the VM may use the
+    /// agents inside it, whoever started them. Unsharing a VM therefore
+    /// also hides the agents the member started in it.
+    pub fn may_access_agent(&self, vm_id: &str) -> bool {
+        self.is_admin() || self.vm_ids.contains(vm_id)
+    }
 }
 
 #[derive(sqlx::FromRow)]
 struct SessionRow {
     user_id: String,
-    role: String,
+    role: UserRole,
     expires_at: String,
 }
 
@@ -89,6 +152,15 @@ pub async fn require_auth(
 
     let token = bearer_token(&req).ok_or(SupervisorError::Unauthorized)?;
 
+    // Personal access tokens are recognised by their prefix and only ever
+    // checked against `api_tokens`, so one can't double as the static
+    // admin token or a session.
+    if token.starts_with(tokens::TOKEN_PREFIX) {
+        let ctx = personal_token_lookup(&state, &token, req.method(), req.uri().path()).await?;
+        req.extensions_mut().insert(ctx);
+        return Ok(next.run(req).await);
+    }
+
     let ctx = if let Some(expected) = state.config.api_token.as_deref() {
         if bool::from(token.as_bytes().ct_eq(expected.as_bytes())) {
             Some(AuthContext::admin_static())
@@ -183,14 +255,110 @@ async fn session_lookup(state: &SharedState, token: &str) -> Result<AuthContext>
     .fetch_optional(&state.db)
     .await?;
     let row = row.ok_or(SupervisorError::Unauthorized)?;
-    let expires_at = chrono::DateTime::parse_from_rfc3339(&row.expires_at)
+    ensure_unexpired(&row.expires_at)?;
+    user_context(state, row.user_id, row.role).await
+}
+
+#[derive(sqlx::FromRow)]
+struct PersonalTokenRow {
+    id: String,
+    user_id: String,
+    role: UserRole,
+    scopes: String,
+    expires_at: String,
+}
+
+async fn personal_token_lookup(
+    state: &SharedState,
+    token: &str,
+    method: &Method,
+    path: &str,
+) -> Result<AuthContext> {
+    let row: Option<PersonalTokenRow> = sqlx::query_as(
+        "SELECT t.id AS id, t.user_id AS user_id, u.role AS role, t.scopes AS scopes, \
+         t.expires_at AS expires_at \
+         FROM api_tokens t JOIN users u ON u.id = t.user_id \
+         WHERE t.token_hash = ?1",
+    )
+    .bind(tokens::hash(token))
+    .fetch_optional(&state.db)
+    .await?;
+    let row = row.ok_or(SupervisorError::Unauthorized)?;
+    ensure_unexpired(&row.expires_at)?;
+    if !tokens::allows(&tokens::scopes_from_column(&row.scopes), method, path) {
+        return Err(SupervisorError::Forbidden);
+    }
+    sqlx::query("UPDATE api_tokens SET last_used_at = ?1 WHERE id = ?2")
+        .bind(Utc::now().to_rfc3339())
+        .bind(&row.id)
+        .execute(&state.db)
+        .await?;
+    user_context(state, row.user_id, row.role).await
+}
+
+fn ensure_unexpired(expires_at: &str) -> Result<()> {
+    let expires_at = chrono::DateTime::parse_from_rfc3339(expires_at)
         .map_err(|_| SupervisorError::Unauthorized)?
         .with_timezone(&Utc);
     if expires_at <= Utc::now() {
         return Err(SupervisorError::Unauthorized);
     }
+    Ok(())
+}
+
+/// `AuthContext` of a user row, with the VMs it owns or was shared.
+async fn user_context(state: &SharedState, user_id: String, role: UserRole) -> Result<AuthContext> {
+    let vm_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM vms WHERE owner_user_id = ?1 \
+         UNION SELECT vm_id FROM vm_shares WHERE user_id = ?1",
+    )
+    .bind(&user_id)
+    .fetch_all(&state.db)
+    .await?;
     Ok(AuthContext {
-        user_id: Some(row.user_id),
-        role: row.role,
+        user_id: Some(user_id),
+        role,
+        vm_ids: vm_ids.into_iter().collect(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn member_role_is_stored_as_legacy_user_value() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (id, username, argon2_hash, role, created_at) \
+             VALUES ('u1', 'bob', 'x', ?1, '2026-01-01T00:00:00Z')",
+        )
+        .bind(UserRole::Member)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let stored: String = sqlx::query_scalar("SELECT role FROM users WHERE id = 'u1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, "user");
+        let role: UserRole = sqlx::query_scalar("SELECT role FROM users WHERE id = 'u1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(role, UserRole::Member);
+    }
+
+    #[test]
+    fn member_role_accepts_user_alias_on_the_wire() {
+        let role: UserRole = serde_json::from_str("\"user\"").unwrap();
+        assert_eq!(role, UserRole::Member);
+        assert_eq!(serde_json::to_string(&role).unwrap(), "\"member\"");
+    }
+}
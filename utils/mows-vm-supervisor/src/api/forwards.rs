@@ -0,0 +1,161 @@
+//! `/v1/vms/{id}/forwards` — guest ports forwarded to host loopback ports
+//! while the VM runs (see `crate::forwards`). Anyone who may see the VM
+//! may manage its forwards, like its snapshots.
+
+use axum::extract::{Extension, Path, State};
+use axum::Json;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::{ErrorResponse, OperationResult};
+use crate::api::vms::{ensure_vm_visible, load_vm, VmStatus};
+use crate::error::{Result, SupervisorError};
+use crate::events::SupervisorEvent;
+use crate::forwards::{self, VmForward};
+use crate::qemu::qmp_socket_for;
+use crate::state::SharedState;
+
+/// Port forward REST endpoints that participate in the OpenAPI document.
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(list_forwards, add_forward))
+        .routes(routes!(remove_forward))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddForwardRequest {
+    /// TCP port inside the guest.
+    pub guest_port: u16,
+    /// Host port to listen on; must lie in `forward_port_range`. The next
+    /// free port of that range when omitted.
+    #[serde(default)]
+    pub host_port: Option<u16>,
+}
+
+/// Forwards can only be added to a QEMU that is up and answering QMP.
+async fn ensure_running(state: &SharedState, vm_id: &str) -> Result<()> {
+    let vm = load_vm(state, vm_id).await?;
+    let has_process = state.vms.read().await.contains(vm_id);
+    match vm.status {
+        VmStatus::Running | VmStatus::Suspended if has_process => Ok(()),
+        status => Err(SupervisorError::Conflict(format!(
+            "vm {vm_id} is `{}`; port forwards need a running vm",
+            status.as_str()
+        ))),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/forwards",
+    tag = "vms",
+    description = "Runtime port forwards of a VM, by guest port.",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "Forwards of this VM", body = Vec<VmForward>),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+    )
+)]
+async fn list_forwards(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<VmForward>>> {
+    ensure_vm_visible(&state, &actor, &id).await?;
+    Ok(Json(forwards::list(&state.db, &id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vms/{id}/forwards",
+    tag = "vms",
+    description = "Forward a guest TCP port to `127.0.0.1:<host_port>` on the supervisor host \
+                   via QMP `hostfwd_add`. The forward lasts until it is removed or the VM stops.",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = AddForwardRequest,
+    responses(
+        (status = 200, description = "Forward added", body = VmForward),
+        (status = 400, description = "Guest port 0, or host port outside forward_port_range", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+        (status = 409, description = "VM not running, guest port already forwarded or host port taken", body = ErrorResponse),
+        (status = 503, description = "forward_port_range exhausted", body = ErrorResponse),
+    )
+)]
+async fn add_forward(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+    Json(request): Json<AddForwardRequest>,
+) -> Result<Json<VmForward>> {
+    ensure_vm_visible(&state, &actor, &id).await?;
+    if request.guest_port == 0 {
+        return Err(SupervisorError::BadRequest("guest_port must be 1-65535".into()));
+    }
+    ensure_running(&state, &id).await?;
+    if forwards::find(&state.db, &id, request.guest_port).await?.is_some() {
+        return Err(SupervisorError::Conflict(format!(
+            "guest port {} of vm {id} is already forwarded",
+            request.guest_port
+        )));
+    }
+    let host_port = match request.host_port {
+        Some(port) => {
+            state.forward_ports.reserve(port)?;
+            port
+        }
+        None => state.forward_ports.allocate()?,
+    };
+    let qmp_socket = qmp_socket_for(&state.config.state_dir, &id);
+    if let Err(e) = forwards::add_rule(&qmp_socket, host_port, request.guest_port).await {
+        state.forward_ports.release([host_port]);
+        return Err(e);
+    }
+    let forward = match forwards::insert(&state.db, &id, request.guest_port, host_port).await {
+        Ok(forward) => forward,
+        Err(e) => {
+            let _ = forwards::remove_rule(&qmp_socket, host_port).await;
+            state.forward_ports.release([host_port]);
+            return Err(e);
+        }
+    };
+    tracing::info!(vm_id = %id, guest_port = forward.guest_port, host_port, "port forward added");
+    state.events.emit(SupervisorEvent::VmUpdated { id });
+    Ok(Json(forward))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/vms/{id}/forwards/{guest_port}",
+    tag = "vms",
+    description = "Remove a runtime port forward via QMP `hostfwd_remove`.",
+    params(
+        ("id" = String, Path, description = "VM id"),
+        ("guest_port" = u16, Path, description = "Forwarded guest port"),
+    ),
+    responses(
+        (status = 200, description = "Forward removed", body = OperationResult),
+        (status = 404, description = "Unknown VM or no forward for that port", body = ErrorResponse),
+    )
+)]
+async fn remove_forward(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path((id, guest_port)): Path<(String, u16)>,
+) -> Result<Json<OperationResult>> {
+    ensure_vm_visible(&state, &actor, &id).await?;
+    let forward = forwards::find(&state.db, &id, guest_port)
+        .await?
+        .ok_or_else(|| {
+            SupervisorError::NotFound(format!("guest port {guest_port} of vm {id} is not forwarded"))
+        })?;
+    forwards::remove_rule(&qmp_socket_for(&state.config.state_dir, &id), forward.host_port)
+        .await?;
+    forwards::delete(&state.db, &id, guest_port).await?;
+    state.forward_ports.release([forward.host_port]);
+    tracing::info!(vm_id = %id, guest_port, host_port = forward.host_port, "port forward removed");
+    state.events.emit(SupervisorEvent::VmUpdated { id: id.clone() });
+    Ok(Json(OperationResult::deleted(format!("{id}:{guest_port}"))))
+}
@@ -0,0 +1,177 @@
+//! Resource usage endpoints (`crate::metrics` does the sampling).
+//!
+//! - `GET /metrics` — Prometheus scrape target, admin only. Every running
+//!   VM plus VM and agent counts per status.
+//! - `GET /v1/vms/stats` — one reading of every running VM the caller can
+//!   see (what `mows vms top` polls).
+//! - `GET /v1/vms/{id}/stats` — server-sent events: one `stats` event per
+//!   interval until the VM stops, which the web UI's VM page renders.
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::extract::{Extension, Path, Query, State};
+use axum::http::header;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::stream::{self, Stream};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::ErrorResponse;
+use crate::api::vms::ensure_vm_visible;
+use crate::error::{Result, SupervisorError};
+use crate::metrics::{self, render_prometheus, CpuSample, VmStats};
+use crate::state::SharedState;
+
+/// OpenMetrics text, which is what `prometheus_client` encodes.
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+const DEFAULT_STREAM_INTERVAL_SECS: u64 = 2;
+const MAX_STREAM_INTERVAL_SECS: u64 = 60;
+
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(get_metrics))
+        .routes(routes!(list_vm_stats))
+        .routes(routes!(stream_vm_stats))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct StatsStreamQuery {
+    /// Seconds between events (1–60, default 2).
+    pub interval_secs: Option<u64>,
+}
+
+async fn count_by_status(state: &SharedState, table: &str) -> Result<Vec<(String, i64)>> {
+    let sql = format!("SELECT status, COUNT(*) FROM {table} GROUP BY status ORDER BY status");
+    Ok(sqlx::query_as(&sql).fetch_all(&state.db).await?)
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    description = "Prometheus scrape target: CPU, memory, disk and network usage of every \
+                   running VM, and VM / agent counts per status. Admin only.",
+    responses(
+        (status = 200, description = "OpenMetrics text", body = String, content_type = "application/openmetrics-text"),
+        (status = 403, description = "Caller is not an admin", body = ErrorResponse),
+    )
+)]
+async fn get_metrics(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+) -> Result<Response> {
+    actor.require_admin()?;
+    let mut samples = Vec::new();
+    for vm in metrics::running_vms(&state).await? {
+        samples.push(metrics::sample_vm(&state, &vm, None).await?.0);
+    }
+    // The idle monitor's suspended status is derived, not stored.
+    let vms_by_status = count_by_status(&state, "vms").await?;
+    let agents_by_status = count_by_status(&state, "agents").await?;
+    let body = render_prometheus(&samples, &vms_by_status, &agents_by_status)?;
+    Ok(([(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/stats",
+    tag = "vms",
+    description = "One resource reading of every running VM the caller can see. \
+                   `cpu_percent` is always null here; compare `cpu_seconds` between calls.",
+    responses(
+        (status = 200, description = "Readings", body = Vec<VmStats>),
+    )
+)]
+async fn list_vm_stats(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+) -> Result<Json<Vec<VmStats>>> {
+    let mut samples = Vec::new();
+    for vm in metrics::running_vms(&state).await? {
+        if actor.may_access_vm(&vm.id, vm.owner_user_id.as_deref()) {
+            samples.push(metrics::sample_vm(&state, &vm, None).await?.0);
+        }
+    }
+    Ok(Json(samples))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/stats",
+    tag = "vms",
+    description = "Server-sent events: a `stats` event carrying a `VmStats` reading every \
+                   `interval_secs` until the VM stops running.",
+    params(("id" = String, Path, description = "VM id"), StatsStreamQuery),
+    responses(
+        (status = 200, description = "Event stream of readings", body = VmStats, content_type = "text/event-stream"),
+        (status = 400, description = "Interval out of range", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+        (status = 409, description = "VM is not running", body = ErrorResponse),
+    )
+)]
+async fn stream_vm_stats(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+    Query(query): Query<StatsStreamQuery>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    ensure_vm_visible(&state, &actor, &id).await?;
+    let interval_secs = query.interval_secs.unwrap_or(DEFAULT_STREAM_INTERVAL_SECS);
+    if !(1..=MAX_STREAM_INTERVAL_SECS).contains(&interval_secs) {
+        return Err(SupervisorError::BadRequest(format!(
+            "interval_secs must be between 1 and {MAX_STREAM_INTERVAL_SECS}"
+        )));
+    }
+    if metrics::running_vm(&state, &id).await?.is_none() {
+        return Err(SupervisorError::Conflict(format!("vm {id} is not running")));
+    }
+
+    struct Cursor {
+        state: SharedState,
+        id: String,
+        previous: Option<CpuSample>,
+        first: bool,
+    }
+    let interval = Duration::from_secs(interval_secs);
+    let cursor = Cursor {
+        state,
+        id,
+        previous: None,
+        first: true,
+    };
+    let events = stream::unfold(cursor, move |mut cursor| async move {
+        if !cursor.first {
+            tokio::time::sleep(interval).await;
+        }
+        cursor.first = false;
+        let vm = match metrics::running_vm(&cursor.state, &cursor.id).await {
+            Ok(Some(vm)) => vm,
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::debug!(vm_id = %cursor.id, error = %e, "stats stream ended");
+                return None;
+            }
+        };
+        let (stats, cpu) = match metrics::sample_vm(&cursor.state, &vm, cursor.previous).await {
+            Ok(sample) => sample,
+            Err(e) => {
+                tracing::debug!(vm_id = %cursor.id, error = %e, "stats stream ended");
+                return None;
+            }
+        };
+        cursor.previous = cpu;
+        let event = Event::default()
+            .event("stats")
+            .json_data(&stats)
+            .unwrap_or_else(|_| Event::default().comment("unserialisable sample"));
+        Some((Ok(event), cursor))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
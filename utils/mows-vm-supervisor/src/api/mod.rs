@@ -7,8 +7,9 @@
 //! - **Loopback HTTP** at `config.http_listen` — every `/v1/*` route except
 //!   `POST /v1/auth/login`, `GET /v1/healthz`, and the SPA fallback requires
 //!   `Authorization: Bearer <token>`. The token either matches the
-//!   configured `MOWS_VM_SUPERVISOR_API_TOKEN[_FILE]` or resolves to a
-//!   valid row in the `sessions` table.
+//!   configured `MOWS_VM_SUPERVISOR_API_TOKEN[_FILE]`, resolves to a
+//!   valid row in the `sessions` table, or is a scoped personal access
+//!   token from `/v1/tokens`.
 //!
 //! REST routes participate in OpenAPI generation (utoipa-axum). Websocket
 //! upgrades and the SPA fallback stay on a plain `axum::Router` and are
@@ -51,15 +52,23 @@ use utoipa_axum::router::OpenApiRouter;
 use crate::error::Result;
 use crate::state::SharedState;
 
-mod agents;
+pub(crate) mod agents;
+mod audit;
 mod auth;
 mod auth_middleware;
 mod events;
+mod forwards;
 mod health;
+mod metrics;
+mod secrets;
+mod shares;
+mod snapshots;
+mod tasks;
+mod tokens;
 pub(crate) mod types;
 mod users;
 mod validation;
-mod vms;
+pub(crate) mod vms;
 mod web;
 
 pub use auth_middleware::AuthContext;
@@ -68,10 +77,14 @@ pub use auth_middleware::AuthContext;
 #[openapi(
     tags(
         (name = "health", description = "Liveness probes"),
-        (name = "auth",   description = "Authentication / session tokens"),
+        (name = "auth",   description = "Authentication / session and personal access tokens"),
         (name = "vms",    description = "VM lifecycle"),
         (name = "agents", description = "Agent lifecycle inside a VM"),
+        (name = "tasks",  description = "Queued headless agent runs"),
         (name = "users",  description = "Supervisor user management"),
+        (name = "secrets", description = "Encrypted secrets agent kinds reference by name"),
+        (name = "metrics", description = "Prometheus scrape target"),
+        (name = "audit",  description = "Append-only log of user actions"),
     ),
     info(
         title = env!("CARGO_PKG_NAME"),
@@ -83,20 +96,58 @@ pub use auth_middleware::AuthContext;
         types::OperationResult,
         auth::LoginRequest,
         auth::LoginResponse,
+        tokens::CreateTokenRequest,
+        tokens::CreateTokenResponse,
+        crate::tokens::TokenSummary,
+        crate::tokens::TokenScope,
         health::HealthResponse,
+        audit::AuditPage,
+        crate::audit::AuditEntry,
+        crate::audit::AuditOutcome,
+        auth_middleware::UserRole,
         users::CreateUserRequest,
+        users::UpdateUserRequest,
         users::UserSummary,
+        users::QuotaReport,
+        crate::quota::UserQuota,
+        crate::quota::QuotaUsage,
+        secrets::PutSecretRequest,
+        crate::secrets::SecretSummary,
+        vms::CloneVmRequest,
         vms::CreateVmRequest,
         vms::UpdateVmRequest,
         vms::VmSummary,
         vms::VmDefaultsResponse,
+        crate::presets::VmPreset,
+        crate::mounts::VmMount,
+        crate::mounts::MountMode,
         vms::VmSshInfo,
-        vms::VmImage,
+        crate::images::GuestImage,
         vms::VmDisplayMode,
         vms::VmStatus,
+        crate::egress::NetworkPolicy,
+        crate::egress::NetworkPolicyMode,
+        crate::egress::BlockedEgressAttempt,
+        crate::workspace::WorkspaceMode,
+        crate::workspace::WorkspaceApplyResult,
+        crate::idle::IdleAction,
+        crate::metrics::VmStats,
+        shares::VmShare,
+        forwards::AddForwardRequest,
+        crate::forwards::VmForward,
+        snapshots::CreateSnapshotRequest,
+        snapshots::SnapshotSummary,
         agents::CreateAgentRequest,
         agents::UpdateAgentRequest,
         agents::AgentSummary,
+        agents::AgentKindName,
+        agents::WaitCondition,
+        agents::WaitOutcome,
+        agents::AgentWaitResponse,
+        tasks::CreateTaskRequest,
+        tasks::TaskSummary,
+        tasks::TaskOutputFile,
+        crate::tasks::TaskStatus,
     )),
 )]
 pub struct SupervisorApiDoc;
@@ -130,8 +181,16 @@ fn login_governor_config() -> Arc<
 fn authenticated_rest_router() -> OpenApiRouter<SharedState> {
     OpenApiRouter::new()
         .merge(vms::rest_router())
+        .merge(snapshots::rest_router())
+        .merge(shares::rest_router())
+        .merge(forwards::rest_router())
         .merge(agents::rest_router())
+        .merge(tasks::rest_router())
         .merge(users::rest_router())
+        .merge(secrets::rest_router())
+        .merge(tokens::rest_router())
+        .merge(metrics::rest_router())
+        .merge(audit::rest_router())
 }
 
 /// Full `OpenApiRouter<SharedState>` carrying every REST route. Used by the
@@ -186,6 +245,11 @@ fn http_router(state: SharedState) -> Router {
         .merge(vms::ws_router())
         .merge(agents::ws_router())
         .merge(events::ws_router())
+        // Inside `require_auth`, so entries carry the caller's identity.
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            audit::record_request,
+        ))
         .route_layer(axum::middleware::from_fn_with_state(
             state.clone(),
             auth_middleware::require_auth,
@@ -244,6 +308,10 @@ fn unix_router(state: SharedState) -> Router {
     rest.merge(vms::ws_router())
         .merge(agents::ws_router())
         .merge(web::router())
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            audit::record_request,
+        ))
         .layer(axum::middleware::from_fn(auth_middleware::inject_unix_admin))
         .layer(global_middleware())
         .with_state(state)
@@ -0,0 +1,103 @@
+//! `/v1/secrets` — the encrypted secret store agent kinds reference by
+//! name (see `crate::secrets`). Admin only: a secret ends up in every
+//! agent whose kind references it, whoever owns the VM. Values go in and
+//! never come back out.
+
+use axum::extract::{Extension, Path, State};
+use axum::Json;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::{ErrorResponse, OperationResult};
+use crate::error::{Result, SupervisorError};
+use crate::secrets::{self, SecretSummary};
+use crate::state::SharedState;
+
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(list_secrets))
+        .routes(routes!(put_secret, delete_secret))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PutSecretRequest {
+    pub value: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/secrets",
+    tag = "secrets",
+    description = "List stored secrets by name, without their values. Admin only.",
+    responses(
+        (status = 200, description = "Secrets", body = Vec<SecretSummary>),
+        (status = 403, description = "Caller is not an admin", body = ErrorResponse),
+    )
+)]
+async fn list_secrets(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+) -> Result<Json<Vec<SecretSummary>>> {
+    actor.require_admin()?;
+    Ok(Json(secrets::list(&state.db).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/secrets/{name}",
+    tag = "secrets",
+    description = "Create or replace a secret. Agents spawned afterwards get the new value. Admin only.",
+    params(("name" = String, Path, description = "Secret name ([A-Za-z_][A-Za-z0-9_]*)")),
+    request_body = PutSecretRequest,
+    responses(
+        (status = 200, description = "Secret stored", body = SecretSummary),
+        (status = 400, description = "Invalid name, or the store is disabled", body = ErrorResponse),
+        (status = 403, description = "Caller is not an admin", body = ErrorResponse),
+    )
+)]
+async fn put_secret(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(name): Path<String>,
+    Json(request): Json<PutSecretRequest>,
+) -> Result<Json<SecretSummary>> {
+    actor.require_admin()?;
+    let summary = secrets::put(
+        &state.db,
+        state.config.secret_key.as_ref(),
+        &name,
+        &request.value,
+        actor.user_id.as_deref(),
+    )
+    .await?;
+    tracing::info!(secret = %name, "secret stored");
+    Ok(Json(summary))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/secrets/{name}",
+    tag = "secrets",
+    description = "Delete a secret. Running agents keep the value they were spawned with. Admin only.",
+    params(("name" = String, Path, description = "Secret name")),
+    responses(
+        (status = 200, description = "Secret deleted", body = OperationResult),
+        (status = 403, description = "Caller is not an admin", body = ErrorResponse),
+        (status = 404, description = "Unknown secret", body = ErrorResponse),
+    )
+)]
+async fn delete_secret(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(name): Path<String>,
+) -> Result<Json<OperationResult>> {
+    actor.require_admin()?;
+    if !secrets::delete(&state.db, &name).await? {
+        return Err(SupervisorError::NotFound(format!("secret {name:?} not found")));
+    }
+    tracing::info!(secret = %name, "secret deleted");
+    Ok(Json(OperationResult::deleted(name)))
+}
@@ -0,0 +1,158 @@
+//! `/v1/vms/{id}/shares` — which members a VM is shared with.
+//!
+//! A member sees only the VMs it owns plus the ones shared with it
+//! (`vm_shares`, see `AuthContext::may_access_vm`). Sharing grants full
+//! use of the VM and every agent inside it; deleting the VM and managing
+//! its shares stay with the owner and admins.
+
+use axum::extract::{Extension, Path, State};
+use axum::Json;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::{ErrorResponse, OperationResult};
+use crate::api::vms::ensure_vm_owned;
+use crate::error::{Result, SupervisorError};
+use crate::events::SupervisorEvent;
+use crate::state::SharedState;
+
+/// VM share REST endpoints that participate in the OpenAPI document.
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(list_shares))
+        .routes(routes!(add_share, remove_share))
+}
+
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone)]
+pub struct VmShare {
+    pub vm_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub created_at: String,
+}
+
+const SHARE_SELECT: &str = "SELECT s.vm_id AS vm_id, s.user_id AS user_id, u.username AS username, \
+     s.created_at AS created_at FROM vm_shares s JOIN users u ON u.id = s.user_id";
+
+async fn user_id_for(state: &SharedState, username: &str) -> Result<String> {
+    sqlx::query_scalar("SELECT id FROM users WHERE username = ?1")
+        .bind(username)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| SupervisorError::NotFound(format!("user {username:?} not found")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/shares",
+    tag = "vms",
+    description = "Users the VM is shared with, by username.",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "Shares", body = Vec<VmShare>),
+        (status = 403, description = "Caller doesn't own the VM", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+    )
+)]
+async fn list_shares(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<VmShare>>> {
+    ensure_vm_owned(&state, &actor, &id).await?;
+    let sql = format!("{SHARE_SELECT} WHERE s.vm_id = ?1 ORDER BY u.username");
+    let rows: Vec<VmShare> = sqlx::query_as(&sql).bind(&id).fetch_all(&state.db).await?;
+    Ok(Json(rows))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/vms/{id}/shares/{username}",
+    tag = "vms",
+    description = "Share the VM with a user. Idempotent.",
+    params(
+        ("id" = String, Path, description = "VM id"),
+        ("username" = String, Path, description = "User to share with"),
+    ),
+    responses(
+        (status = 200, description = "VM shared", body = VmShare),
+        (status = 400, description = "User already owns the VM", body = ErrorResponse),
+        (status = 403, description = "Caller doesn't own the VM", body = ErrorResponse),
+        (status = 404, description = "Unknown VM or user", body = ErrorResponse),
+    )
+)]
+async fn add_share(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path((id, username)): Path<(String, String)>,
+) -> Result<Json<VmShare>> {
+    ensure_vm_owned(&state, &actor, &id).await?;
+    let user_id = user_id_for(&state, &username).await?;
+    let owner: Option<String> = sqlx::query_scalar("SELECT owner_user_id FROM vms WHERE id = ?1")
+        .bind(&id)
+        .fetch_one(&state.db)
+        .await?;
+    if owner.as_deref() == Some(user_id.as_str()) {
+        return Err(SupervisorError::BadRequest(format!(
+            "{username:?} owns vm {id}"
+        )));
+    }
+    sqlx::query(
+        "INSERT INTO vm_shares (vm_id, user_id, created_at) VALUES (?1, ?2, ?3) \
+         ON CONFLICT (vm_id, user_id) DO NOTHING",
+    )
+    .bind(&id)
+    .bind(&user_id)
+    .bind(Utc::now().to_rfc3339())
+    .execute(&state.db)
+    .await?;
+    let sql = format!("{SHARE_SELECT} WHERE s.vm_id = ?1 AND s.user_id = ?2");
+    let share: VmShare = sqlx::query_as(&sql)
+        .bind(&id)
+        .bind(&user_id)
+        .fetch_one(&state.db)
+        .await?;
+    state.events.emit(SupervisorEvent::VmUpdated { id });
+    Ok(Json(share))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/vms/{id}/shares/{username}",
+    tag = "vms",
+    description = "Stop sharing the VM with a user. Agents they started keep \
+                   running, but they can no longer see or use them.",
+    params(
+        ("id" = String, Path, description = "VM id"),
+        ("username" = String, Path, description = "User to unshare from"),
+    ),
+    responses(
+        (status = 200, description = "Share removed", body = OperationResult),
+        (status = 403, description = "Caller doesn't own the VM", body = ErrorResponse),
+        (status = 404, description = "Unknown VM, user or share", body = ErrorResponse),
+    )
+)]
+async fn remove_share(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path((id, username)): Path<(String, String)>,
+) -> Result<Json<OperationResult>> {
+    ensure_vm_owned(&state, &actor, &id).await?;
+    let user_id = user_id_for(&state, &username).await?;
+    let removed = sqlx::query("DELETE FROM vm_shares WHERE vm_id = ?1 AND user_id = ?2")
+        .bind(&id)
+        .bind(&user_id)
+        .execute(&state.db)
+        .await?;
+    if removed.rows_affected() == 0 {
+        return Err(SupervisorError::NotFound(format!(
+            "vm {id} is not shared with {username:?}"
+        )));
+    }
+    state.events.emit(SupervisorEvent::VmUpdated { id: id.clone() });
+    Ok(Json(OperationResult::deleted(id)))
+}
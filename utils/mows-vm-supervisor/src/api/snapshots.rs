@@ -0,0 +1,322 @@
+//! `/v1/vms/{id}/snapshots` — named checkpoints of a VM's disk overlay.
+//!
+//! Every snapshot is an internal qcow2 snapshot inside the VM's
+//! `disk.qcow2`, tagged with the snapshot row id. Snapshots are disk only:
+//! taken with `blockdev-snapshot-internal-sync` on a running VM
+//! (crash-consistent) or `qemu-img snapshot` on a stopped one. Restoring
+//! rewrites the overlay, so it requires a stopped VM. There are no RAM
+//! snapshots — QEMU refuses `savevm`/`loadvm` while a 9p share is mounted in
+//! the guest (VirtFS is a migration blocker), and every VM carries the
+//! `mowsinit` share (see `crate::qemu`).
+//!
+//! Which path is used follows from whether the supervisor still owns a
+//! QEMU process for the VM: a running VM goes through QMP, a stopped or
+//! failed VM whose process is gone goes through `qemu-img`. VMs in a
+//! transitional state (`starting`, `stopping`) are rejected with 409.
+
+use axum::extract::{Extension, Path, State};
+use axum::Json;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::{ErrorResponse, OperationResult};
+use crate::api::validation::validate_resource_name;
+use crate::api::vms::{load_vm, VmStatus, VmSummary};
+use crate::error::{Result, SupervisorError};
+use crate::events::SupervisorEvent;
+use crate::qemu::{
+    overlay_path_for, qemu_img_snapshot, qmp_socket_for, DiskSnapshotOp, OVERLAY_DRIVE_ID,
+};
+use crate::qmp::QmpClient;
+use crate::state::SharedState;
+
+/// Snapshot REST endpoints that participate in the OpenAPI document.
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(list_snapshots, create_snapshot))
+        .routes(routes!(delete_snapshot))
+        .routes(routes!(restore_snapshot))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateSnapshotRequest {
+    /// Display name, unique per VM. Auto-generated from the UTC timestamp
+    /// when omitted.
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub vm_id: String,
+    pub name: String,
+    pub created_at: String,
+    /// `users.id` of the caller who took the snapshot.
+    pub owner_user_id: Option<String>,
+}
+
+const SNAPSHOT_COLUMNS: &str = "id, vm_id, name, created_at, owner_user_id";
+
+/// How the VM's overlay can be reached right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiskAccess {
+    /// QEMU is running and owns the image — go through QMP.
+    Live,
+    /// No QEMU process — the overlay can be edited with `qemu-img`.
+    Offline,
+}
+
+async fn disk_access(state: &SharedState, vm: &VmSummary) -> Result<DiskAccess> {
+    let has_process = state.vms.read().await.contains(&vm.id);
+    match (vm.status, has_process) {
+        (VmStatus::Running | VmStatus::Suspended, true) => Ok(DiskAccess::Live),
+        (VmStatus::Stopped | VmStatus::Failed, false) => Ok(DiskAccess::Offline),
+        (status, _) => Err(SupervisorError::Conflict(format!(
+            "vm {} is `{}`; snapshots need a running or stopped vm",
+            vm.id,
+            status.as_str()
+        ))),
+    }
+}
+
+/// Load the VM and confirm the caller may see it. Non-owners get the same
+/// 404 as a missing VM.
+async fn load_visible_vm(
+    state: &SharedState,
+    actor: &AuthContext,
+    vm_id: &str,
+) -> Result<VmSummary> {
+    let vm = load_vm(state, vm_id).await?;
+    if !actor.may_access_vm(vm_id, vm.owner_user_id.as_deref()) {
+        return Err(SupervisorError::NotFound(format!("vm {vm_id} not found")));
+    }
+    Ok(vm)
+}
+
+async fn load_snapshot(
+    state: &SharedState,
+    vm_id: &str,
+    snapshot_id: &str,
+) -> Result<SnapshotSummary> {
+    let sql = format!("SELECT {SNAPSHOT_COLUMNS} FROM vm_snapshots WHERE id = ?1 AND vm_id = ?2");
+    sqlx::query_as(&sql)
+        .bind(snapshot_id)
+        .bind(vm_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| {
+            SupervisorError::NotFound(format!("snapshot {snapshot_id} not found in vm {vm_id}"))
+        })
+}
+
+async fn qmp_for(state: &SharedState, vm_id: &str) -> Result<QmpClient> {
+    QmpClient::connect(&qmp_socket_for(&state.config.state_dir, vm_id)).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/snapshots",
+    tag = "vms",
+    description = "List the snapshots of a VM, newest first.",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "Snapshots of this VM", body = Vec<SnapshotSummary>),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+    )
+)]
+async fn list_snapshots(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<SnapshotSummary>>> {
+    load_visible_vm(&state, &actor, &id).await?;
+    let sql = format!(
+        "SELECT {SNAPSHOT_COLUMNS} FROM vm_snapshots WHERE vm_id = ?1 ORDER BY created_at DESC"
+    );
+    let rows: Vec<SnapshotSummary> = sqlx::query_as(&sql)
+        .bind(&id)
+        .fetch_all(&state.db)
+        .await?;
+    Ok(Json(rows))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vms/{id}/snapshots",
+    tag = "vms",
+    description = "Checkpoint a VM's disk, through QMP on running VMs and \
+                   via `qemu-img` on stopped ones.",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = CreateSnapshotRequest,
+    responses(
+        (status = 200, description = "Snapshot taken", body = SnapshotSummary),
+        (status = 400, description = "Invalid name", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+        (status = 409, description = "Name already used, or VM in a transitional state", body = ErrorResponse),
+    )
+)]
+async fn create_snapshot(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(vm_id): Path<String>,
+    Json(request): Json<CreateSnapshotRequest>,
+) -> Result<Json<SnapshotSummary>> {
+    let vm = load_visible_vm(&state, &actor, &vm_id).await?;
+    let access = disk_access(&state, &vm).await?;
+    let raw_name = request
+        .name
+        .unwrap_or_else(|| format!("snap-{}", Utc::now().format("%Y%m%d-%H%M%S")));
+    let name = validate_resource_name("name", &raw_name)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let created_at = Utc::now().to_rfc3339();
+    let owner_user_id = actor.user_id.clone();
+
+    // Insert first so a duplicate name is rejected before QEMU writes
+    // anything; the row is rolled back below if the snapshot itself fails.
+    sqlx::query(
+        "INSERT INTO vm_snapshots (id, vm_id, name, created_at, owner_user_id) \
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )
+    .bind(&id)
+    .bind(&vm_id)
+    .bind(&name)
+    .bind(&created_at)
+    .bind(&owner_user_id)
+    .execute(&state.db)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            SupervisorError::Conflict(format!("vm {vm_id} already has a snapshot named {name:?}"))
+        }
+        other => SupervisorError::from(other),
+    })?;
+
+    let taken = match access {
+        DiskAccess::Live => {
+            let mut qmp = qmp_for(&state, &vm_id).await?;
+            qmp.execute(
+                "blockdev-snapshot-internal-sync",
+                Some(json!({ "device": OVERLAY_DRIVE_ID, "name": id })),
+            )
+            .await
+            .map(|_| ())
+        }
+        DiskAccess::Offline => {
+            let overlay = overlay_path_for(&state.config.state_dir, &vm_id);
+            qemu_img_snapshot(&overlay, DiskSnapshotOp::Create, &id).await
+        }
+    };
+    if let Err(e) = taken {
+        let _ = sqlx::query("DELETE FROM vm_snapshots WHERE id = ?1")
+            .bind(&id)
+            .execute(&state.db)
+            .await;
+        return Err(e);
+    }
+
+    tracing::info!(vm_id = %vm_id, snapshot_id = %id, "snapshot taken");
+    state.events.emit(SupervisorEvent::SnapshotCreated {
+        id: id.clone(),
+        vm_id: vm_id.clone(),
+    });
+    Ok(Json(SnapshotSummary {
+        id,
+        vm_id,
+        name,
+        created_at,
+        owner_user_id,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vms/{id}/snapshots/{snapshot_id}/restore",
+    tag = "vms",
+    description = "Roll the VM's disk back to a snapshot. The VM has to be \
+                   stopped.",
+    params(
+        ("id" = String, Path, description = "VM id"),
+        ("snapshot_id" = String, Path, description = "Snapshot id"),
+    ),
+    responses(
+        (status = 200, description = "Snapshot restored", body = OperationResult),
+        (status = 404, description = "Unknown VM or snapshot", body = ErrorResponse),
+        (status = 409, description = "Snapshot cannot be restored in the VM's current state", body = ErrorResponse),
+    )
+)]
+async fn restore_snapshot(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path((vm_id, snapshot_id)): Path<(String, String)>,
+) -> Result<Json<OperationResult>> {
+    let vm = load_visible_vm(&state, &actor, &vm_id).await?;
+    load_snapshot(&state, &vm_id, &snapshot_id).await?;
+    match disk_access(&state, &vm).await? {
+        DiskAccess::Live => {
+            return Err(SupervisorError::Conflict(format!(
+                "stop vm {vm_id} before restoring snapshot {snapshot_id}"
+            )));
+        }
+        DiskAccess::Offline => {
+            let overlay = overlay_path_for(&state.config.state_dir, &vm_id);
+            qemu_img_snapshot(&overlay, DiskSnapshotOp::Apply, &snapshot_id).await?;
+        }
+    }
+    tracing::info!(vm_id = %vm_id, snapshot_id = %snapshot_id, "snapshot restored");
+    state.events.emit(SupervisorEvent::SnapshotRestored {
+        id: snapshot_id.clone(),
+        vm_id,
+    });
+    Ok(Json(OperationResult::status(snapshot_id, "restored")))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/vms/{id}/snapshots/{snapshot_id}",
+    tag = "vms",
+    description = "Delete a snapshot and free its space in the VM's overlay.",
+    params(
+        ("id" = String, Path, description = "VM id"),
+        ("snapshot_id" = String, Path, description = "Snapshot id"),
+    ),
+    responses(
+        (status = 200, description = "Snapshot deleted", body = OperationResult),
+        (status = 404, description = "Unknown VM or snapshot", body = ErrorResponse),
+        (status = 409, description = "VM in a transitional state", body = ErrorResponse),
+    )
+)]
+async fn delete_snapshot(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path((vm_id, snapshot_id)): Path<(String, String)>,
+) -> Result<Json<OperationResult>> {
+    let vm = load_visible_vm(&state, &actor, &vm_id).await?;
+    load_snapshot(&state, &vm_id, &snapshot_id).await?;
+    match disk_access(&state, &vm).await? {
+        DiskAccess::Live => {
+            // `delvm` drops the tag from every block device, which is just
+            // the overlay here.
+            let mut qmp = qmp_for(&state, &vm_id).await?;
+            qmp.human_monitor_command_checked(&format!("delvm {snapshot_id}"))
+                .await?;
+        }
+        DiskAccess::Offline => {
+            let overlay = overlay_path_for(&state.config.state_dir, &vm_id);
+            qemu_img_snapshot(&overlay, DiskSnapshotOp::Delete, &snapshot_id).await?;
+        }
+    }
+    sqlx::query("DELETE FROM vm_snapshots WHERE id = ?1")
+        .bind(&snapshot_id)
+        .execute(&state.db)
+        .await?;
+    state.events.emit(SupervisorEvent::SnapshotDeleted {
+        id: snapshot_id.clone(),
+        vm_id,
+    });
+    Ok(Json(OperationResult::deleted(snapshot_id)))
+}
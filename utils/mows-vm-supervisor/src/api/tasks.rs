@@ -0,0 +1,530 @@
+//! `/v1/tasks` — queued headless agent runs.
+//!
+//! Creating a task only records it; `crate::tasks` owns the queue and the
+//! run itself. Once a task is terminal its transcript and the files it
+//! left in `/out` are served from `state_dir/tasks/<id>/`.
+
+use std::path::{Component, Path as FsPath, PathBuf};
+
+use axum::extract::{Extension, Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::agents::AgentKindName;
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::{ErrorResponse, OperationResult};
+use crate::api::validation::validate_resource_name;
+use crate::api::vms::{load_vm, VmStatus};
+use crate::audit::AuditTarget;
+use crate::error::{Result, SupervisorError};
+use crate::events::SupervisorEvent;
+use crate::kinds;
+use crate::qemu::validate_workspace_path;
+use crate::state::SharedState;
+use crate::tasks::{outputs_dir_for, task_dir_for, transcript_path_for, TaskStatus};
+
+/// Prompts are stored in the DB and copied into the guest verbatim; cap
+/// them so a runaway client can't queue gigabytes.
+const MAX_PROMPT_BYTES: usize = 1024 * 1024;
+
+/// Task REST endpoints that participate in the OpenAPI document.
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(list_tasks, create_task))
+        .routes(routes!(get_task, delete_task))
+        .routes(routes!(cancel_task))
+        .routes(routes!(get_task_transcript))
+        .routes(routes!(list_task_outputs))
+        .routes(routes!(download_task_output))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateTaskRequest {
+    /// Agent kind to run. Must have a `headless_argv`.
+    pub kind: AgentKindName,
+    /// Fed to the agent on stdin and written to `$MOWS_TASK_PROMPT_FILE`.
+    pub prompt: String,
+    /// Display name. Auto-generated from `kind` + UTC timestamp when omitted.
+    pub name: Option<String>,
+    /// Host directory mounted at `/workspace` in the task's VM. Only for
+    /// tasks that boot their own VM.
+    pub cwd: Option<String>,
+    /// Run inside this existing, running VM instead of booting a fresh
+    /// one. Tasks targeting the same VM run one at a time.
+    pub vm_id: Option<String>,
+    /// Wall-clock limit for the agent process. Defaults to
+    /// `task_queue.default_timeout_secs`.
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone)]
+pub struct TaskSummary {
+    pub id: String,
+    pub name: String,
+    pub kind: String,
+    pub status: TaskStatus,
+    pub cwd: Option<String>,
+    /// VM the task ran (or runs) in. Set at queue time for tasks that
+    /// target an existing VM, once booted for the rest.
+    pub vm_id: Option<String>,
+    /// `true` if the task booted `vm_id` itself; such VMs are deleted
+    /// when the task finishes.
+    pub owns_vm: bool,
+    pub timeout_secs: i64,
+    /// Exit status of the agent process. `None` unless it exited on its own.
+    pub exit_code: Option<i64>,
+    /// Why a `failed` task failed before producing an exit status.
+    pub error: Option<String>,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub owner_user_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq, Eq)]
+pub struct TaskOutputFile {
+    /// Path relative to the guest's `/out`, `/`-separated.
+    pub path: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TaskOutputQuery {
+    /// Path relative to `/out`, as returned by the outputs listing.
+    pub path: String,
+}
+
+const TASK_COLUMNS: &str = "id, name, kind, status, cwd, vm_id, owns_vm, timeout_secs, exit_code, error, created_at, started_at, finished_at, owner_user_id";
+
+async fn load_task(state: &SharedState, id: &str) -> Result<TaskSummary> {
+    let sql = format!("SELECT {TASK_COLUMNS} FROM tasks WHERE id = ?1");
+    sqlx::query_as(&sql)
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| SupervisorError::NotFound(format!("task {id} not found")))
+}
+
+/// Load the task and confirm the caller may see it. Non-owners get the
+/// same 404 as a missing task.
+async fn load_visible_task(
+    state: &SharedState,
+    actor: &AuthContext,
+    id: &str,
+) -> Result<TaskSummary> {
+    let task = load_task(state, id).await?;
+    if !actor.may_access(task.owner_user_id.as_deref()) {
+        return Err(SupervisorError::NotFound(format!("task {id} not found")));
+    }
+    Ok(task)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks",
+    tag = "tasks",
+    description = "List tasks the caller can see, newest first.",
+    responses(
+        (status = 200, description = "Tasks in the database", body = Vec<TaskSummary>),
+        (status = 500, description = "Internal error", body = ErrorResponse),
+    )
+)]
+async fn list_tasks(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+) -> Result<Json<Vec<TaskSummary>>> {
+    if actor.is_admin() {
+        let sql = format!("SELECT {TASK_COLUMNS} FROM tasks ORDER BY created_at DESC");
+        let rows: Vec<TaskSummary> = sqlx::query_as(&sql).fetch_all(&state.db).await?;
+        Ok(Json(rows))
+    } else {
+        let sql = format!(
+            "SELECT {TASK_COLUMNS} FROM tasks WHERE owner_user_id = ?1 ORDER BY created_at DESC"
+        );
+        let rows: Vec<TaskSummary> = sqlx::query_as(&sql)
+            .bind(actor.user_id.as_deref().unwrap_or(""))
+            .fetch_all(&state.db)
+            .await?;
+        Ok(Json(rows))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/tasks",
+    tag = "tasks",
+    description = "Queue a headless agent run. Returns immediately with the `queued` task; \
+                   the dispatcher starts it once a slot (`task_queue.max_concurrent`) frees up.",
+    request_body = CreateTaskRequest,
+    responses(
+        (status = 200, description = "Task queued", body = TaskSummary),
+        (status = 400, description = "Empty/oversized prompt, bad timeout, kind without headless mode, bad cwd", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+        (status = 409, description = "Target VM not running", body = ErrorResponse),
+    )
+)]
+async fn create_task(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Json(request): Json<CreateTaskRequest>,
+) -> Result<(Extension<AuditTarget>, Json<TaskSummary>)> {
+    if request.prompt.trim().is_empty() {
+        return Err(SupervisorError::BadRequest("prompt must not be empty".into()));
+    }
+    if request.prompt.len() > MAX_PROMPT_BYTES {
+        return Err(SupervisorError::BadRequest(format!(
+            "prompt is {} bytes; the limit is {MAX_PROMPT_BYTES}",
+            request.prompt.len()
+        )));
+    }
+    let kind_name = request.kind.as_str();
+    let has_headless = kinds::builtin(kind_name)
+        .map(|kind| !kind.headless_argv.is_empty())
+        .unwrap_or(false);
+    if !has_headless {
+        return Err(SupervisorError::BadRequest(format!(
+            "agent kind `{kind_name}` cannot run headless tasks"
+        )));
+    }
+
+    let limits = &state.config.task_queue;
+    let timeout_secs = request.timeout_secs.unwrap_or(limits.default_timeout_secs);
+    if timeout_secs == 0 || timeout_secs > limits.max_timeout_secs {
+        return Err(SupervisorError::BadRequest(format!(
+            "timeout_secs must be between 1 and {}",
+            limits.max_timeout_secs
+        )));
+    }
+
+    // Resolve cwd now so a typo fails the request rather than the run
+    // hours later in an overnight batch.
+    let cwd = match (&request.vm_id, request.cwd.as_deref()) {
+        (Some(_), Some(_)) => {
+            return Err(SupervisorError::BadRequest(
+                "cwd only applies to tasks that boot their own vm; omit it with vm_id".into(),
+            ));
+        }
+        (None, Some(raw)) => Some(validate_workspace_path(raw)?.display().to_string()),
+        (_, None) => None,
+    };
+    if let Some(vm_id) = &request.vm_id {
+        let vm = load_vm(&state, vm_id).await?;
+        if !actor.may_access_vm(vm_id, vm.owner_user_id.as_deref()) {
+            return Err(SupervisorError::NotFound(format!("vm {vm_id} not found")));
+        }
+        if !matches!(vm.status, VmStatus::Running | VmStatus::Suspended) {
+            return Err(SupervisorError::Conflict(format!(
+                "vm {vm_id} is in status `{}`; tasks can only target a running vm",
+                vm.status.as_str()
+            )));
+        }
+    }
+
+    let raw_name = request
+        .name
+        .unwrap_or_else(|| format!("{kind_name}-{}", Utc::now().format("%Y%m%d-%H%M%S")));
+    let name = validate_resource_name("name", &raw_name)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let timeout_secs = i64::try_from(timeout_secs)
+        .map_err(|_| SupervisorError::BadRequest("timeout_secs is out of range".into()))?;
+
+    sqlx::query(
+        "INSERT INTO tasks (id, name, kind, prompt, status, cwd, vm_id, timeout_secs, created_at, owner_user_id) \
+         VALUES (?1, ?2, ?3, ?4, 'queued', ?5, ?6, ?7, ?8, ?9)",
+    )
+    .bind(&id)
+    .bind(&name)
+    .bind(kind_name)
+    .bind(&request.prompt)
+    .bind(&cwd)
+    .bind(&request.vm_id)
+    .bind(timeout_secs)
+    .bind(Utc::now().to_rfc3339())
+    .bind(&actor.user_id)
+    .execute(&state.db)
+    .await?;
+
+    state.events.emit(SupervisorEvent::TaskCreated { id: id.clone() });
+    state.tasks.wake();
+    Ok((Extension(AuditTarget(id.clone())), Json(load_task(&state, &id).await?)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks/{id}",
+    tag = "tasks",
+    description = "Fetch a single task by id.",
+    params(("id" = String, Path, description = "Task id")),
+    responses(
+        (status = 200, description = "The task", body = TaskSummary),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    )
+)]
+async fn get_task(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<TaskSummary>> {
+    Ok(Json(load_visible_task(&state, &actor, &id).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/tasks/{id}/cancel",
+    tag = "tasks",
+    description = "Cancel a queued or running task. A queued task flips to `cancelled` \
+                   immediately; a running one is killed and flips once its VM is cleaned up.",
+    params(("id" = String, Path, description = "Task id")),
+    responses(
+        (status = 200, description = "Task cancelled or cancelling", body = OperationResult),
+        (status = 404, description = "Unknown task", body = ErrorResponse),
+        (status = 409, description = "Task already finished", body = ErrorResponse),
+    )
+)]
+async fn cancel_task(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<OperationResult>> {
+    load_visible_task(&state, &actor, &id).await?;
+    let dequeued = sqlx::query(
+        "UPDATE tasks SET status = 'cancelled', finished_at = ?1 WHERE id = ?2 AND status = 'queued'",
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(&id)
+    .execute(&state.db)
+    .await?;
+    if dequeued.rows_affected() == 1 {
+        state.events.emit(SupervisorEvent::TaskUpdated { id: id.clone() });
+        return Ok(Json(OperationResult::status(id, TaskStatus::Cancelled.as_str())));
+    }
+    if state.tasks.cancel(&id) {
+        return Ok(Json(OperationResult::status(id, "cancelling")));
+    }
+    let task = load_task(&state, &id).await?;
+    Err(SupervisorError::Conflict(format!(
+        "task {id} is already `{}`",
+        task.status.as_str()
+    )))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/tasks/{id}",
+    tag = "tasks",
+    description = "Delete a finished task together with its transcript and collected outputs.",
+    params(("id" = String, Path, description = "Task id")),
+    responses(
+        (status = 200, description = "Task deleted", body = OperationResult),
+        (status = 404, description = "Unknown task", body = ErrorResponse),
+        (status = 409, description = "Task still queued or running", body = ErrorResponse),
+    )
+)]
+async fn delete_task(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<OperationResult>> {
+    let task = load_visible_task(&state, &actor, &id).await?;
+    if !task.status.is_terminal() {
+        return Err(SupervisorError::Conflict(format!(
+            "task {id} is `{}`; cancel it before deleting",
+            task.status.as_str()
+        )));
+    }
+    sqlx::query("DELETE FROM tasks WHERE id = ?1")
+        .bind(&id)
+        .execute(&state.db)
+        .await?;
+    let task_dir = task_dir_for(&state.config.state_dir, &id);
+    if let Err(e) = tokio::fs::remove_dir_all(&task_dir).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!(
+                task_id = %id,
+                dir = %task_dir.display(),
+                error = %e,
+                "failed to remove task dir on delete"
+            );
+        }
+    }
+    state.events.emit(SupervisorEvent::TaskDeleted { id: id.clone() });
+    Ok(Json(OperationResult::deleted(id)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks/{id}/transcript",
+    tag = "tasks",
+    description = "Everything the agent wrote to stdout and stderr. Grows while the task runs.",
+    params(("id" = String, Path, description = "Task id")),
+    responses(
+        (status = 200, description = "Transcript", body = String, content_type = "text/plain"),
+        (status = 404, description = "Unknown task or not started yet", body = ErrorResponse),
+    )
+)]
+async fn get_task_transcript(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    load_visible_task(&state, &actor, &id).await?;
+    let path = transcript_path_for(&task_dir_for(&state.config.state_dir, &id));
+    let body = match tokio::fs::read(&path).await {
+        Ok(body) => body,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(SupervisorError::NotFound(format!(
+                "task {id} has no transcript yet"
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks/{id}/outputs",
+    tag = "tasks",
+    description = "Files collected from the guest's `/out` after the run. Empty until the task finishes.",
+    params(("id" = String, Path, description = "Task id")),
+    responses(
+        (status = 200, description = "Collected files", body = Vec<TaskOutputFile>),
+        (status = 404, description = "Unknown task", body = ErrorResponse),
+    )
+)]
+async fn list_task_outputs(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TaskOutputFile>>> {
+    load_visible_task(&state, &actor, &id).await?;
+    let root = outputs_dir_for(&task_dir_for(&state.config.state_dir, &id));
+    let files = tokio::task::spawn_blocking(move || list_output_files(&root))
+        .await
+        .map_err(|e| SupervisorError::InvalidState(format!("output listing panicked: {e}")))??;
+    Ok(Json(files))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks/{id}/outputs/download",
+    tag = "tasks",
+    description = "Raw contents of one collected output file.",
+    params(("id" = String, Path, description = "Task id"), TaskOutputQuery),
+    responses(
+        (status = 200, description = "File contents", body = Vec<u8>, content_type = "application/octet-stream"),
+        (status = 400, description = "Path is not a plain relative path", body = ErrorResponse),
+        (status = 404, description = "Unknown task or file", body = ErrorResponse),
+    )
+)]
+async fn download_task_output(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+    Query(query): Query<TaskOutputQuery>,
+) -> Result<Response> {
+    load_visible_task(&state, &actor, &id).await?;
+    let relative = relative_output_path(&query.path)?;
+    let path = outputs_dir_for(&task_dir_for(&state.config.state_dir, &id)).join(relative);
+    let is_file = tokio::fs::symlink_metadata(&path)
+        .await
+        .map(|meta| meta.is_file())
+        .unwrap_or(false);
+    if !is_file {
+        return Err(SupervisorError::NotFound(format!(
+            "task {id} has no output `{}`",
+            query.path
+        )));
+    }
+    let body = tokio::fs::read(&path).await?;
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], body).into_response())
+}
+
+/// Accept only plain relative paths (`a/b.txt`) so a request can't walk
+/// out of the task's output directory.
+fn relative_output_path(raw: &str) -> Result<PathBuf> {
+    let path = FsPath::new(raw);
+    let plain = !raw.is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if !plain {
+        return Err(SupervisorError::BadRequest(format!(
+            "output path {raw:?} must be relative without `..`"
+        )));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Every regular file under `root`, sorted by path. A missing `root`
+/// (nothing collected yet) is an empty list.
+fn list_output_files(root: &FsPath) -> Result<Vec<TaskOutputFile>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            let path = entry.path();
+            if meta.is_dir() {
+                pending.push(path);
+            } else if meta.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                let relative = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push(TaskOutputFile {
+                    path: relative,
+                    size_bytes: meta.len(),
+                });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_output_path_rejects_escapes() {
+        assert!(relative_output_path("report/summary.md").is_ok());
+        for bad in ["", "/etc/passwd", "../x", "a/../../x", "./a"] {
+            assert!(
+                matches!(relative_output_path(bad), Err(SupervisorError::BadRequest(_))),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn list_output_files_walks_nested_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("top.txt"), b"123").unwrap();
+        std::fs::write(dir.path().join("a/b/deep.txt"), b"x").unwrap();
+        assert_eq!(
+            list_output_files(dir.path()).unwrap(),
+            vec![
+                TaskOutputFile { path: "a/b/deep.txt".into(), size_bytes: 1 },
+                TaskOutputFile { path: "top.txt".into(), size_bytes: 3 },
+            ]
+        );
+        assert!(list_output_files(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
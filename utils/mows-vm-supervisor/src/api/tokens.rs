@@ -0,0 +1,150 @@
+//! `/v1/tokens` — personal access tokens (see `crate::tokens`). Users
+//! manage their own; admins see and revoke everyone's and may issue a
+//! token for another user. Token requests themselves need the `admin`
+//! scope, so a narrowly scoped token can't mint a broader one.
+
+use axum::extract::{Extension, Path, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+use crate::api::auth_middleware::AuthContext;
+use crate::api::types::{ErrorResponse, OperationResult};
+use crate::audit::AuditTarget;
+use crate::error::{Result, SupervisorError};
+use crate::state::SharedState;
+use crate::tokens::{self, TokenScope, TokenSummary, DEFAULT_EXPIRY_DAYS};
+
+pub fn rest_router() -> OpenApiRouter<SharedState> {
+    OpenApiRouter::new()
+        .routes(routes!(list_tokens, create_token))
+        .routes(routes!(revoke_token))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateTokenRequest {
+    /// What the token is for, e.g. `ci`.
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    /// Lifetime in days, 1-365. Defaults to 90.
+    #[serde(default)]
+    pub expires_in_days: Option<u32>,
+    /// Issue the token for this user instead of the caller. Admin only;
+    /// required when the caller has no user row (static admin token, unix
+    /// socket).
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct CreateTokenResponse {
+    /// The bearer token. Shown only in this response.
+    pub token: String,
+    #[serde(flatten)]
+    pub summary: TokenSummary,
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tokens",
+    tag = "auth",
+    description = "List personal access tokens: the caller's own, or every user's for an admin.",
+    responses(
+        (status = 200, description = "Tokens", body = Vec<TokenSummary>),
+    )
+)]
+async fn list_tokens(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+) -> Result<Json<Vec<TokenSummary>>> {
+    let owner = if actor.is_admin() {
+        None
+    } else {
+        actor.user_id.as_deref()
+    };
+    Ok(Json(tokens::list(&state.db, owner).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/tokens",
+    tag = "auth",
+    description = "Issue a personal access token. The token is returned once and only its \
+                   hash is stored.",
+    request_body = CreateTokenRequest,
+    responses(
+        (status = 200, description = "Token issued", body = CreateTokenResponse),
+        (status = 400, description = "Empty name or scopes, or expiry out of range", body = ErrorResponse),
+        (status = 403, description = "Issuing for another user without being admin", body = ErrorResponse),
+        (status = 404, description = "Unknown username", body = ErrorResponse),
+    )
+)]
+async fn create_token(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Json(request): Json<CreateTokenRequest>,
+) -> Result<(Extension<AuditTarget>, Json<CreateTokenResponse>)> {
+    let user_id = match (request.username.as_deref(), actor.user_id.as_deref()) {
+        (Some(username), _) => {
+            let user_id: Option<String> =
+                sqlx::query_scalar("SELECT id FROM users WHERE username = ?1")
+                    .bind(username)
+                    .fetch_optional(&state.db)
+                    .await?;
+            let user_id = user_id
+                .ok_or_else(|| SupervisorError::NotFound(format!("user {username:?} not found")))?;
+            if actor.user_id.as_deref() != Some(user_id.as_str()) {
+                actor.require_admin()?;
+            }
+            user_id
+        }
+        (None, Some(own)) => own.to_string(),
+        (None, None) => {
+            return Err(SupervisorError::BadRequest(
+                "the static admin identity owns no tokens; pass `username`".into(),
+            ))
+        }
+    };
+    let (token, summary) = tokens::create(
+        &state.db,
+        &user_id,
+        request.name.trim(),
+        &request.scopes,
+        request.expires_in_days.unwrap_or(DEFAULT_EXPIRY_DAYS),
+    )
+    .await?;
+    tracing::info!(token_id = %summary.id, user = %summary.username, "personal access token issued");
+    Ok((
+        Extension(AuditTarget(summary.id.clone())),
+        Json(CreateTokenResponse { token, summary }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/tokens/{id}",
+    tag = "auth",
+    description = "Revoke a personal access token. Its owner or an admin only.",
+    params(("id" = String, Path, description = "Token id")),
+    responses(
+        (status = 200, description = "Token revoked", body = OperationResult),
+        (status = 404, description = "Unknown token", body = ErrorResponse),
+    )
+)]
+async fn revoke_token(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<OperationResult>> {
+    let owner = tokens::owner(&state.db, &id).await?;
+    // Someone else's token is reported as missing, not forbidden, so ids
+    // can't be probed.
+    if !owner.as_deref().is_some_and(|owner| actor.may_access(Some(owner))) {
+        return Err(SupervisorError::NotFound(format!("token {id} not found")));
+    }
+    tokens::delete(&state.db, &id).await?;
+    tracing::info!(token_id = %id, "personal access token revoked");
+    Ok(Json(OperationResult::deleted(id)))
+}
@@ -1,6 +1,6 @@
 use argon2::password_hash::{rand_core::OsRng, SaltString};
 use argon2::{Argon2, PasswordHasher};
-use axum::extract::{Extension, State};
+use axum::extract::{Extension, Path, State};
 use axum::Json;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
@@ -8,9 +8,11 @@ use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 
-use crate::api::auth_middleware::AuthContext;
+use crate::api::auth_middleware::{AuthContext, UserRole};
 use crate::api::types::ErrorResponse;
+use crate::audit::AuditTarget;
 use crate::error::{Result, SupervisorError};
+use crate::quota::{self, QuotaUsage, UserQuota};
 use crate::state::SharedState;
 
 /// Minimum length for user passwords. Argon2 makes weak passwords expensive
@@ -18,43 +20,85 @@ use crate::state::SharedState;
 const MIN_PASSWORD_LEN: usize = 12;
 
 pub fn rest_router() -> OpenApiRouter<SharedState> {
-    OpenApiRouter::new().routes(routes!(list_users, create_user))
+    OpenApiRouter::new()
+        .routes(routes!(list_users, create_user))
+        .routes(routes!(update_user))
+        .routes(routes!(get_my_quota))
 }
 
 #[derive(Deserialize, ToSchema)]
 pub struct CreateUserRequest {
     pub username: String,
     pub password: String,
-    #[serde(default = "default_role")]
-    pub role: String,
+    /// Defaults to `member`. `user` is accepted as an alias.
+    #[serde(default)]
+    pub role: UserRole,
+    /// Quota caps; omitted or `0` means unlimited.
+    #[serde(default)]
+    pub max_vms: Option<u32>,
+    #[serde(default)]
+    pub max_vcpus: Option<u32>,
+    #[serde(default)]
+    pub max_memory_mb: Option<u32>,
 }
 
-fn default_role() -> String {
-    "user".into()
+/// Fields left out are not changed. A quota of `0` removes the cap.
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateUserRequest {
+    #[serde(default)]
+    pub role: Option<UserRole>,
+    #[serde(default)]
+    pub max_vms: Option<u32>,
+    #[serde(default)]
+    pub max_vcpus: Option<u32>,
+    #[serde(default)]
+    pub max_memory_mb: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow)]
 pub struct UserSummary {
     pub id: String,
     pub username: String,
-    pub role: String,
+    pub role: UserRole,
     pub created_at: String,
+    /// Quota caps; `null` means unlimited. See `crate::quota`.
+    pub max_vms: Option<i64>,
+    pub max_vcpus: Option<i64>,
+    pub max_memory_mb: Option<i64>,
+}
+
+/// The caller's quota next to what their live VMs currently use.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct QuotaReport {
+    pub quota: UserQuota,
+    pub usage: QuotaUsage,
+}
+
+const USER_COLUMNS: &str = "id, username, role, created_at, max_vms, max_vcpus, max_memory_mb";
+
+/// Quota caps are stored as NULL when unlimited; `0` on the wire means
+/// unlimited too.
+fn quota_cap(value: u32) -> Option<i64> {
+    (value != 0).then(|| i64::from(value))
 }
 
 #[utoipa::path(
     get,
     path = "/v1/users",
     tag = "users",
-    description = "List every supervisor user, sorted by username.",
+    description = "List every supervisor user, sorted by username. Admin only.",
     responses(
         (status = 200, description = "Users", body = Vec<UserSummary>),
+        (status = 403, description = "Caller is not an admin", body = ErrorResponse),
     )
 )]
-async fn list_users(State(state): State<SharedState>) -> Result<Json<Vec<UserSummary>>> {
-    let rows: Vec<UserSummary> =
-        sqlx::query_as("SELECT id, username, role, created_at FROM users ORDER BY username")
-            .fetch_all(&state.db)
-            .await?;
+async fn list_users(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+) -> Result<Json<Vec<UserSummary>>> {
+    actor.require_admin()?;
+    let sql = format!("SELECT {USER_COLUMNS} FROM users ORDER BY username");
+    let rows: Vec<UserSummary> = sqlx::query_as(&sql).fetch_all(&state.db).await?;
     Ok(Json(rows))
 }
 
@@ -62,11 +106,12 @@ async fn list_users(State(state): State<SharedState>) -> Result<Json<Vec<UserSum
     post,
     path = "/v1/users",
     tag = "users",
-    description = "Create a new supervisor user.",
+    description = "Create a new supervisor user. Admin only.",
     request_body = CreateUserRequest,
     responses(
         (status = 200, description = "User created", body = UserSummary),
-        (status = 400, description = "Invalid role", body = ErrorResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 403, description = "Caller is not an admin", body = ErrorResponse),
         (status = 409, description = "Username already exists", body = ErrorResponse),
     )
 )]
@@ -74,16 +119,8 @@ async fn create_user(
     State(state): State<SharedState>,
     Extension(actor): Extension<AuthContext>,
     Json(request): Json<CreateUserRequest>,
-) -> Result<Json<UserSummary>> {
-    if actor.role != "admin" {
-        return Err(SupervisorError::Forbidden);
-    }
-    if request.role != "admin" && request.role != "user" {
-        return Err(SupervisorError::BadRequest(format!(
-            "role must be 'admin' or 'user', got {:?}",
-            request.role
-        )));
-    }
+) -> Result<(Extension<AuditTarget>, Json<UserSummary>)> {
+    actor.require_admin()?;
     if request.password.len() < MIN_PASSWORD_LEN {
         return Err(SupervisorError::BadRequest(format!(
             "password must be at least {MIN_PASSWORD_LEN} characters"
@@ -100,15 +137,22 @@ async fn create_user(
         .hash_password(request.password.as_bytes(), &salt)?
         .to_string();
     let created_at = Utc::now().to_rfc3339();
+    let max_vms = request.max_vms.and_then(quota_cap);
+    let max_vcpus = request.max_vcpus.and_then(quota_cap);
+    let max_memory_mb = request.max_memory_mb.and_then(quota_cap);
 
     sqlx::query(
-        "INSERT INTO users (id, username, argon2_hash, role, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO users (id, username, argon2_hash, role, created_at, max_vms, max_vcpus, max_memory_mb) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
     )
     .bind(&id)
     .bind(&request.username)
     .bind(&hash)
-    .bind(&request.role)
+    .bind(request.role)
     .bind(&created_at)
+    .bind(max_vms)
+    .bind(max_vcpus)
+    .bind(max_memory_mb)
     .execute(&state.db)
     .await
     .map_err(|e| match e {
@@ -118,10 +162,99 @@ async fn create_user(
         other => SupervisorError::from(other),
     })?;
 
-    Ok(Json(UserSummary {
-        id,
-        username: request.username,
-        role: request.role,
-        created_at,
+    Ok((
+        Extension(AuditTarget(request.username.clone())),
+        Json(UserSummary {
+            id,
+            username: request.username,
+            role: request.role,
+            created_at,
+            max_vms,
+            max_vcpus,
+            max_memory_mb,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/v1/users/{username}",
+    tag = "users",
+    description = "Change a user's role or quota. Admin only. Quota changes \
+                   apply to the next VM the user creates; running VMs are kept.",
+    params(("username" = String, Path, description = "Username")),
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "Updated user", body = UserSummary),
+        (status = 400, description = "Admin tried to change their own role", body = ErrorResponse),
+        (status = 403, description = "Caller is not an admin", body = ErrorResponse),
+        (status = 404, description = "Unknown user", body = ErrorResponse),
+    )
+)]
+async fn update_user(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(username): Path<String>,
+    Json(request): Json<UpdateUserRequest>,
+) -> Result<Json<UserSummary>> {
+    actor.require_admin()?;
+    let sql = format!("SELECT {USER_COLUMNS} FROM users WHERE username = ?1");
+    let mut user: UserSummary = sqlx::query_as(&sql)
+        .bind(&username)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| SupervisorError::NotFound(format!("user {username:?} not found")))?;
+    if let Some(role) = request.role {
+        // Demoting yourself would leave an admin session without admin
+        // rights mid-flight; have another admin do it.
+        if role != user.role && actor.user_id.as_deref() == Some(user.id.as_str()) {
+            return Err(SupervisorError::BadRequest(
+                "admins cannot change their own role".into(),
+            ));
+        }
+        user.role = role;
+    }
+    if let Some(cap) = request.max_vms {
+        user.max_vms = quota_cap(cap);
+    }
+    if let Some(cap) = request.max_vcpus {
+        user.max_vcpus = quota_cap(cap);
+    }
+    if let Some(cap) = request.max_memory_mb {
+        user.max_memory_mb = quota_cap(cap);
+    }
+    sqlx::query(
+        "UPDATE users SET role = ?1, max_vms = ?2, max_vcpus = ?3, max_memory_mb = ?4 WHERE id = ?5",
+    )
+    .bind(user.role)
+    .bind(user.max_vms)
+    .bind(user.max_vcpus)
+    .bind(user.max_memory_mb)
+    .bind(&user.id)
+    .execute(&state.db)
+    .await?;
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/users/me/quota",
+    tag = "users",
+    description = "The caller's quota and current usage.",
+    responses(
+        (status = 200, description = "Quota and usage", body = QuotaReport),
+        (status = 400, description = "Caller has no user account (static API token)", body = ErrorResponse),
+    )
+)]
+async fn get_my_quota(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+) -> Result<Json<QuotaReport>> {
+    let user_id = actor.user_id.as_deref().ok_or_else(|| {
+        SupervisorError::BadRequest("the static API token has no user account or quota".into())
+    })?;
+    Ok(Json(QuotaReport {
+        quota: quota::load(&state, user_id).await?,
+        usage: quota::usage(&state, user_id).await?,
     }))
 }
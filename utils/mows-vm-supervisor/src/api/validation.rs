@@ -39,7 +39,7 @@ pub(super) fn validate_resource_name(field: &str, raw: &str) -> Result<String> {
     // (i.e. `.` and `..`). These flow into `Path::join`, where `.`
     // collapses to "current directory" and `..` ascends — a literal
     // path-traversal vector for the `agent_id` callsite that uses the
-    // returned string to compose `state_dir/agents/<id>/agent.log`.
+    // returned string to compose `state_dir/agents/<id>/session.cast`.
     if trimmed.chars().all(|c| c == '.') {
         return Err(SupervisorError::BadRequest(format!(
             "{field} must not consist exclusively of `.` characters (rejected: {trimmed:?})"
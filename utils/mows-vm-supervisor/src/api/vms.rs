@@ -4,11 +4,14 @@
 //! socket, and console socket. Agents (claude, shell, etc.) are spawned
 //! *inside* a VM via `/v1/vms/:id/agents` (see `api::agents`).
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::{Extension, Path, State};
+use axum::http::header;
+use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::{Json, Router};
 use chrono::Utc;
@@ -17,18 +20,31 @@ use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 
+use crate::api::agents::AgentKindName;
 use crate::api::auth_middleware::AuthContext;
 use crate::api::types::{ErrorResponse, OperationResult};
 use crate::api::validation::validate_resource_name;
+use crate::audit::AuditTarget;
+use crate::cloud_init;
+use crate::egress::{read_blocked_log, spawn_egress_proxy, BlockedEgressAttempt, NetworkPolicy};
 use crate::error::{Result, SupervisorError};
 use crate::events::SupervisorEvent;
+use crate::forwards;
+use crate::idle::{resume_if_suspended, IdleAction};
 use crate::qemu::{
-    console_socket_for, display_socket_for, locate_image, prepare_vm_dir, spawn_qemu,
+    self, console_socket_for, display_socket_for, overlay_path_for, prepare_vm_dir,
+    qmp_socket_for, spawn_qemu,
     validate_workspace_path, vm_dir_for, DisplayMode as QemuDisplayMode, QemuInvocation,
     VmLaunchSpec, VmResources,
 };
+use crate::images::{self, GuestImage};
+use crate::mounts::{self, VmMount};
+use crate::presets::{self, VmPreset};
+use crate::qmp::QmpClient;
+use crate::quota;
 use crate::ssh_keys::{ensure_vm_keypair, vm_key_paths};
 use crate::state::SharedState;
+use crate::workspace::{self, WorkspaceApplyResult, WorkspaceMode};
 
 /// VM REST endpoints that participate in the OpenAPI document.
 pub fn rest_router() -> OpenApiRouter<SharedState> {
@@ -37,7 +53,11 @@ pub fn rest_router() -> OpenApiRouter<SharedState> {
         .routes(routes!(get_vm_defaults))
         .routes(routes!(get_vm, update_vm, delete_vm))
         .routes(routes!(stop_vm))
+        .routes(routes!(clone_vm))
         .routes(routes!(get_vm_ssh))
+        .routes(routes!(get_vm_egress_log))
+        .routes(routes!(get_vm_diff))
+        .routes(routes!(apply_vm_workspace))
 }
 
 /// VM websocket endpoints — not part of OpenAPI (the spec models REST only).
@@ -54,35 +74,9 @@ pub struct UpdateVmRequest {
     pub name: String,
 }
 
-/// Guest base image. Only `alpine` is currently shipped end-to-end — the
-/// other variants are accepted by the API surface but `create_vm` will
-/// reject them with a 503 until the image-builder lands the qcow2.
-#[derive(
-    Serialize, Deserialize, ToSchema, sqlx::Type, Clone, Copy, Debug, Default, PartialEq, Eq,
-)]
-#[serde(rename_all = "lowercase")]
-#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
-pub enum VmImage {
-    #[default]
-    Alpine,
-    Ubuntu,
-    Debian,
-    Nixos,
-}
-
-impl VmImage {
-    pub fn as_str(self) -> &'static str {
-        match self {
-            Self::Alpine => "alpine",
-            Self::Ubuntu => "ubuntu",
-            Self::Debian => "debian",
-            Self::Nixos => "nixos",
-        }
-    }
-}
-
 /// VM lifecycle status as exposed over the API. Mirrors the SQL CHECK
-/// constraint in `migrations/0001_init.sql`. Serialised as the
+/// constraint in `migrations/0001_init.sql`, plus `suspended`: a
+/// `running` row with `suspended_at` set (see `VM_COLUMNS`). Serialised as the
 /// lowercase variant name so the wire format stays
 /// `"starting"|"running"|…` and the TypeScript codegen emits a union
 /// literal instead of a bare `string` (FUTURE-14).
@@ -97,6 +91,8 @@ pub enum VmStatus {
     Stopping,
     Stopped,
     Failed,
+    /// Paused by the idle monitor (`crate::idle`); resumes on attach.
+    Suspended,
 }
 
 impl VmStatus {
@@ -107,6 +103,7 @@ impl VmStatus {
             Self::Stopping => "stopping",
             Self::Stopped => "stopped",
             Self::Failed => "failed",
+            Self::Suspended => "suspended",
         }
     }
 }
@@ -136,27 +133,78 @@ impl VmDisplayMode {
 
 #[derive(Deserialize, ToSchema)]
 pub struct CreateVmRequest {
+    /// Named preset from the supervisor config (see `GET /v1/vms/defaults`)
+    /// that fills in every field below the request leaves out.
+    #[serde(default)]
+    pub preset: Option<String>,
     pub name: Option<String>,
     pub cwd: Option<String>,
     pub cpus: Option<u32>,
     pub memory_mb: Option<u32>,
-    /// Guest base image. Defaults to `alpine` when omitted, but the
-    /// default is logged at `INFO` level so silently-defaulted requests
-    /// don't hide misspelled image names (SLOP-36). Pass an explicit
-    /// value (`alpine` | `ubuntu` | `debian` | `nixos`) to record intent.
+    /// Guest image by name: one of the image-builder's distros (`alpine`
+    /// | `ubuntu` | `debian` | `nixos`) or an entry under the supervisor's
+    /// `images:` (see `GET /v1/vms/defaults`). Defaults to
+    /// `vm_defaults.image` when omitted, but the default is logged at
+    /// `INFO` level so silently-defaulted requests don't hide misspelled
+    /// image names (SLOP-36).
     #[serde(default)]
-    pub image: Option<VmImage>,
+    pub image: Option<String>,
     /// Whether the guest exposes a graphical surface. Defaults to
     /// `headless` when omitted; the default is logged at `INFO` level
     /// for the same reason as `image`.
     #[serde(default)]
     pub display_mode: Option<VmDisplayMode>,
+    /// Outbound network policy. Defaults to the supervisor's
+    /// `default_network_policy` when omitted.
+    #[serde(default)]
+    pub network_policy: Option<NetworkPolicy>,
+    /// How `cwd` is shared with the guest. Defaults to `rw` (the host
+    /// directory itself). `overlay` and `worktree` give the VM a private
+    /// copy whose changes come back via `/v1/vms/{id}/apply`.
+    #[serde(default)]
+    pub workspace_mode: Option<WorkspaceMode>,
+    /// Seconds the VM may sit idle before `idle_action` applies; `0`
+    /// disables. Defaults to `vm_defaults.idle_timeout_secs`.
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    /// Seconds after which the VM is shut down regardless of activity;
+    /// `0` disables. Defaults to `vm_defaults.max_lifetime_secs`.
+    #[serde(default)]
+    pub max_lifetime_secs: Option<u64>,
+    /// `suspend` or `stop` on idle timeout. Defaults to
+    /// `vm_defaults.idle_action`.
+    #[serde(default)]
+    pub idle_action: Option<IdleAction>,
+    /// Environment for every agent spawned in the VM, on top of the
+    /// preset's and the agent kind's.
+    #[serde(default)]
+    pub env: Option<BTreeMap<String, String>>,
+    /// Extra host directories to share into the guest. Host paths must
+    /// sit under the supervisor's `host_mounts.allowed_prefixes`.
+    #[serde(default)]
+    pub mounts: Option<Vec<VmMount>>,
+    /// Agent kind the VM is for: adds the kind's default mounts and is
+    /// spawned when a create-agent request omits `kind`.
+    #[serde(default)]
+    pub agent_kind: Option<AgentKindName>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct VmDefaultsResponse {
     pub cpus: u32,
     pub memory_mb: u32,
+    pub network_policy: NetworkPolicy,
+    /// `0` when idle VMs are left alone.
+    pub idle_timeout_secs: u64,
+    /// `0` when VMs run until stopped.
+    pub max_lifetime_secs: u64,
+    pub idle_action: IdleAction,
+    /// Named presets a `create_vm` request can pick with `preset`.
+    pub presets: BTreeMap<String, VmPreset>,
+    /// Image used when a request omits `image`.
+    pub image: String,
+    /// Every image a `create_vm` request can pick with `image`.
+    pub images: BTreeMap<String, GuestImage>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone)]
@@ -167,7 +215,7 @@ pub struct VmSummary {
     pub cwd: Option<String>,
     pub cpus: Option<i64>,
     pub memory_mb: Option<i64>,
-    pub image: VmImage,
+    pub image: String,
     pub display_mode: VmDisplayMode,
     pub host_ssh_port: Option<i64>,
     pub host_docker_port: Option<i64>,
@@ -178,6 +226,23 @@ pub struct VmSummary {
     /// rows written before owner tracking landed (admins still see
     /// them; non-admin users do not).
     pub owner_user_id: Option<String>,
+    #[sqlx(json)]
+    pub network_policy: NetworkPolicy,
+    pub workspace_mode: WorkspaceMode,
+    /// Branch the VM's worktree is on (`worktree` mode only).
+    pub workspace_branch: Option<String>,
+    /// `None` when idleness never stops or suspends this VM.
+    pub idle_timeout_secs: Option<i64>,
+    /// `None` when the VM runs until stopped.
+    pub max_lifetime_secs: Option<i64>,
+    pub idle_action: IdleAction,
+    /// When the idle monitor paused the VM (status `suspended`).
+    pub suspended_at: Option<String>,
+    /// Preset the VM was created from.
+    pub preset: Option<String>,
+    /// Extra host mounts, with canonical host paths.
+    #[sqlx(json)]
+    pub mounts: Vec<VmMount>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -189,8 +254,10 @@ pub struct VmSshInfo {
     pub public_key: String,
 }
 
+/// `status` is derived: a suspended VM stays `running` in the table
+/// (the CHECK in `0001_init.sql` can't be widened in place).
 const VM_COLUMNS: &str =
-    "id, name, status, cwd, cpus, memory_mb, image, display_mode, host_ssh_port, host_docker_port, started_at, exited_at, exit_code, owner_user_id";
+    "id, name, CASE WHEN status = 'running' AND suspended_at IS NOT NULL THEN 'suspended' ELSE status END AS status, cwd, cpus, memory_mb, image, display_mode, host_ssh_port, host_docker_port, started_at, exited_at, exit_code, owner_user_id, network_policy, workspace_mode, workspace_branch, idle_timeout_secs, max_lifetime_secs, idle_action, suspended_at, preset, mounts";
 
 /// Confirm the caller may see `vm_id`. Used by every cross-module
 /// agent handler before a path-bound `vm_id` is read — keeps the
@@ -201,6 +268,30 @@ pub(super) async fn ensure_vm_visible(
     actor: &AuthContext,
     vm_id: &str,
 ) -> Result<()> {
+    visible_vm_owner(state, actor, vm_id).await.map(drop)
+}
+
+/// Like `ensure_vm_visible`, but for the operations reserved to the
+/// VM's owner (and admins): deleting it and managing its shares. A
+/// member the VM was only shared with gets 403, not 404 — they already
+/// know it exists.
+pub(super) async fn ensure_vm_owned(
+    state: &SharedState,
+    actor: &AuthContext,
+    vm_id: &str,
+) -> Result<()> {
+    let owner = visible_vm_owner(state, actor, vm_id).await?;
+    if !actor.may_access(owner.as_deref()) {
+        return Err(SupervisorError::Forbidden);
+    }
+    Ok(())
+}
+
+async fn visible_vm_owner(
+    state: &SharedState,
+    actor: &AuthContext,
+    vm_id: &str,
+) -> Result<Option<String>> {
     let owner: Option<Option<String>> =
         sqlx::query_scalar("SELECT owner_user_id FROM vms WHERE id = ?1")
             .bind(vm_id)
@@ -208,10 +299,10 @@ pub(super) async fn ensure_vm_visible(
             .await?;
     let owner = owner
         .ok_or_else(|| SupervisorError::NotFound(format!("vm {vm_id} not found")))?;
-    if !actor.may_access(owner.as_deref()) {
+    if !actor.may_access_vm(vm_id, owner.as_deref()) {
         return Err(SupervisorError::NotFound(format!("vm {vm_id} not found")));
     }
-    Ok(())
+    Ok(owner)
 }
 
 #[utoipa::path(
@@ -234,7 +325,9 @@ async fn list_vms(
         Ok(Json(rows))
     } else {
         let sql = format!(
-            "SELECT {VM_COLUMNS} FROM vms WHERE owner_user_id = ?1 ORDER BY started_at DESC"
+            "SELECT {VM_COLUMNS} FROM vms WHERE owner_user_id = ?1 \
+             OR id IN (SELECT vm_id FROM vm_shares WHERE user_id = ?1) \
+             ORDER BY started_at DESC"
         );
         let rows: Vec<VmSummary> = sqlx::query_as(&sql)
             .bind(actor.user_id.as_deref().unwrap_or(""))
@@ -248,7 +341,7 @@ async fn list_vms(
     get,
     path = "/v1/vms/defaults",
     tag = "vms",
-    description = "Server-side defaults applied when a `create_vm` request omits the corresponding field.",
+    description = "Server-side defaults applied when a `create_vm` request omits the corresponding field, and the named presets it can pick instead.",
     responses(
         (status = 200, description = "Current VM defaults", body = VmDefaultsResponse),
     )
@@ -259,6 +352,13 @@ async fn get_vm_defaults(
     Ok(Json(VmDefaultsResponse {
         cpus: state.config.vm_defaults.cpus,
         memory_mb: state.config.vm_defaults.memory_mb,
+        network_policy: state.config.default_network_policy.clone(),
+        idle_timeout_secs: state.config.vm_defaults.idle_timeout_secs,
+        max_lifetime_secs: state.config.vm_defaults.max_lifetime_secs,
+        idle_action: state.config.vm_defaults.idle_action,
+        presets: state.config.presets.clone(),
+        image: state.config.vm_defaults.image.clone(),
+        images: images::available(&state.config.images),
     }))
 }
 
@@ -279,13 +379,13 @@ async fn get_vm(
     Path(id): Path<String>,
 ) -> Result<Json<VmSummary>> {
     let vm = load_vm(&state, &id).await?;
-    if !actor.may_access(vm.owner_user_id.as_deref()) {
+    if !actor.may_access_vm(&vm.id, vm.owner_user_id.as_deref()) {
         return Err(SupervisorError::NotFound(format!("vm {id} not found")));
     }
     Ok(Json(vm))
 }
 
-pub(super) async fn load_vm(state: &SharedState, id: &str) -> Result<VmSummary> {
+pub(crate) async fn load_vm(state: &SharedState, id: &str) -> Result<VmSummary> {
     let sql = format!("SELECT {VM_COLUMNS} FROM vms WHERE id = ?1");
     sqlx::query_as(&sql)
         .bind(id)
@@ -303,6 +403,7 @@ pub(super) async fn load_vm(state: &SharedState, id: &str) -> Result<VmSummary>
     request_body = CreateVmRequest,
     responses(
         (status = 200, description = "VM is starting", body = VmSummary),
+        (status = 403, description = "Owner's quota would be exceeded", body = ErrorResponse),
         (status = 500, description = "Spawn failed", body = ErrorResponse),
     )
 )]
@@ -310,9 +411,32 @@ async fn create_vm(
     State(state): State<SharedState>,
     Extension(actor): Extension<AuthContext>,
     Json(request): Json<CreateVmRequest>,
-) -> Result<Json<VmSummary>> {
+) -> Result<(Extension<AuditTarget>, Json<VmSummary>)> {
+    let vm = launch_vm(&state, actor.user_id.clone(), request).await?;
+    Ok((Extension(AuditTarget(vm.id.clone())), Json(vm)))
+}
+
+/// Validate `request`, record the VM row, and spawn QEMU. Shared by
+/// `POST /v1/vms` and the headless task runner (`crate::tasks`), which
+/// boots a throwaway VM per task.
+pub(crate) async fn launch_vm(
+    state: &SharedState,
+    owner_user_id: Option<String>,
+    request: CreateVmRequest,
+) -> Result<VmSummary> {
+    launch(state, owner_user_id, request, None).await
+}
+
+/// `launch_vm`, optionally booting from a copy of `clone_of`'s disk
+/// instead of a fresh overlay.
+async fn launch(
+    state: &SharedState,
+    owner_user_id: Option<String>,
+    request: CreateVmRequest,
+    clone_of: Option<&VmSummary>,
+) -> Result<VmSummary> {
     let id = uuid::Uuid::new_v4().to_string();
-    let owner_user_id = actor.user_id.clone();
+    let (request, preset) = presets::apply(&state.config.presets, request)?;
 
     // Validate workspace path BEFORE any side effect. Rejects relative paths,
     // missing directories, and embedded commas/newlines that would inject
@@ -325,6 +449,13 @@ async fn create_vm(
     let canonical_cwd: Option<String> = workspace
         .as_ref()
         .map(|path| path.display().to_string());
+    let workspace_mode = request.workspace_mode.unwrap_or_default();
+    workspace::validate_source(workspace_mode, workspace.as_deref()).await?;
+    let mounts = mounts::resolve(
+        &state.config.host_mounts,
+        request.agent_kind,
+        request.mounts.unwrap_or_default(),
+    )?;
 
     let cwd_basename = workspace.as_deref().and_then(|path| {
         path.file_name().map(|name| name.to_string_lossy().into_owned())
@@ -350,20 +481,22 @@ async fn create_vm(
 
     let cpus = request.cpus.unwrap_or(state.config.vm_defaults.cpus);
     let memory_mb = request.memory_mb.unwrap_or(state.config.vm_defaults.memory_mb);
-    // SLOP-36: if the caller omits `image` we still default to Alpine to
-    // keep the smoke-test surface ergonomic, but surface the implicit
-    // choice so operators can grep for "image=alpine" + missing-image
+    // SLOP-36: if the caller omits `image` we still fall back to
+    // `vm_defaults.image` to keep the smoke-test surface ergonomic, but
+    // surface the implicit choice so operators can grep for missing-image
     // requests instead of having the default silently mask a typo.
     let image = match request.image {
         Some(image) => image,
         None => {
+            let image = state.config.vm_defaults.image.clone();
             tracing::info!(
                 vm_id = %id,
-                "create_vm: no `image` specified, defaulting to alpine"
+                "create_vm: no `image` specified, defaulting to {image}"
             );
-            VmImage::default()
+            image
         }
     };
+    let guest_image = images::resolve(&state.config.images, &image)?;
     let display_mode = match request.display_mode {
         Some(mode) => mode,
         None => {
@@ -374,11 +507,44 @@ async fn create_vm(
             VmDisplayMode::default()
         }
     };
+    // Before the row exists, so a missing image leaves nothing to clean up.
+    let artifacts = images::locate(&state.config, &guest_image, display_mode.as_str())?;
+    let network_policy = request
+        .network_policy
+        .unwrap_or_else(|| state.config.default_network_policy.clone());
+    let egress_rules = network_policy.compile()?;
+    // `0` (from the request or the defaults) turns a limit off; it's
+    // stored as NULL.
+    let limit = |secs: Option<u64>, default: u64| -> Result<Option<i64>> {
+        match secs.unwrap_or(default) {
+            0 => Ok(None),
+            secs => i64::try_from(secs).map(Some).map_err(|_| {
+                SupervisorError::BadRequest(format!("{secs}s is out of range"))
+            }),
+        }
+    };
+    let idle_timeout_secs = limit(
+        request.idle_timeout_secs,
+        state.config.vm_defaults.idle_timeout_secs,
+    )?;
+    let max_lifetime_secs = limit(
+        request.max_lifetime_secs,
+        state.config.vm_defaults.max_lifetime_secs,
+    )?;
+    let idle_action = request
+        .idle_action
+        .unwrap_or(state.config.vm_defaults.idle_action);
+    // Held until the row below is inserted, so a concurrent create by the
+    // same user sees this VM in its usage.
+    let quota_guard = state.vm_quota.lock().await;
+    if let Some(user_id) = owner_user_id.as_deref() {
+        quota::enforce(state, user_id, cpus, memory_mb).await?;
+    }
     let (ssh_port, docker_port) = state.port_allocator.allocate_pair()?;
 
     sqlx::query(
-        "INSERT INTO vms (id, name, status, cwd, cpus, memory_mb, image, display_mode, host_ssh_port, host_docker_port, started_at, owner_user_id) \
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        "INSERT INTO vms (id, name, status, cwd, cpus, memory_mb, image, display_mode, host_ssh_port, host_docker_port, started_at, owner_user_id, network_policy, workspace_mode, idle_timeout_secs, max_lifetime_secs, idle_action, preset, env, agent_kind, mounts) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
     )
     .bind(&id)
     .bind(&name)
@@ -386,62 +552,119 @@ async fn create_vm(
     .bind(&canonical_cwd)
     .bind(i64::from(cpus))
     .bind(i64::from(memory_mb))
-    .bind(image.as_str())
+    .bind(&image)
     .bind(display_mode.as_str())
     .bind(i64::from(ssh_port))
     .bind(i64::from(docker_port))
     .bind(&started_at)
     .bind(&owner_user_id)
+    .bind(serde_json::to_string(&network_policy)?)
+    .bind(workspace_mode.as_str())
+    .bind(idle_timeout_secs)
+    .bind(max_lifetime_secs)
+    .bind(idle_action.as_str())
+    .bind(&preset.preset)
+    .bind(serde_json::to_string(&preset.env)?)
+    .bind(request.agent_kind.map(AgentKindName::as_str))
+    .bind(serde_json::to_string(&mounts)?)
     .execute(&state.db)
     .await?;
+    drop(quota_guard);
 
-    // Per-VM SSH keypair: each VM owns its own ed25519 keypair under
-    // `state_dir/vms/<id>/ssh/`. The public key authorizes inbound SSH; the
-    // private key is what `GET /v1/vms/{id}/ssh` returns. This means
-    // compromising one VM's SSH credential cannot be used to reach any other
-    // VM, and the supervisor no longer needs a single master key whose leak
-    // is catastrophic.
     let vm_dir = vm_dir_for(&state.config.state_dir, &id);
-    tokio::fs::create_dir_all(&vm_dir).await?;
-    let vm_keys = ensure_vm_keypair(&vm_dir, &id).await?;
-
-    let artifacts = locate_image(&state.config, image.as_str(), display_mode.as_str())?;
-    let spec = VmLaunchSpec {
-        vm_id: id.clone(),
-        vm_name: name.clone(),
-        image_path: artifacts.qcow2,
-        kernel_path: artifacts.kernel,
-        initrd_path: artifacts.initramfs,
-        state_dir: state.config.state_dir.clone(),
-        workspace: workspace.clone(),
-        host_ssh_port: ssh_port,
-        host_docker_port: docker_port,
-        resources: VmResources { cpus, memory_mb },
-        authorized_ssh_pubkey: vm_keys.public_key.clone(),
-        display_mode: match display_mode {
-            VmDisplayMode::Headless => QemuDisplayMode::Headless,
-            VmDisplayMode::Desktop => QemuDisplayMode::Desktop,
-        },
-    };
+    // From here on the row holds its ports (and counts against the owner's
+    // quota), so any failure goes through `teardown_vm`, which also drops
+    // the partial `vm_dir` and worktree.
+    let booted: Result<Option<String>> = async {
+        tokio::fs::create_dir_all(&vm_dir).await?;
+        // Per-VM SSH keypair: each VM owns its own ed25519 keypair under
+        // `state_dir/vms/<id>/ssh/`. The public key authorizes inbound SSH; the
+        // private key is what `GET /v1/vms/{id}/ssh` returns. This means
+        // compromising one VM's SSH credential cannot be used to reach any other
+        // VM, and the supervisor no longer needs a single master key whose leak
+        // is catastrophic.
+        let vm_keys = ensure_vm_keypair(&vm_dir, &id).await?;
+        if let Some(source) = clone_of {
+            // `prepare_vm_dir` keeps an existing `disk.qcow2`, so the copy
+            // placed here is what the clone boots.
+            clone_disk(state, source, &vm_dir, &artifacts.qcow2).await?;
+        }
 
-    prepare_vm_dir(&spec).await?;
-    let invocation = QemuInvocation::build(&state.config, &spec)?;
+        let prepared = match &workspace {
+            Some(source) => {
+                Some(workspace::prepare(workspace_mode, source, &vm_dir, &id, &name).await?)
+            }
+            None => None,
+        };
+        let workspace_branch = prepared.as_ref().and_then(|p| p.branch.clone());
+        if workspace_branch.is_some() {
+            sqlx::query("UPDATE vms SET workspace_branch = ?1 WHERE id = ?2")
+                .bind(&workspace_branch)
+                .bind(&id)
+                .execute(&state.db)
+                .await?;
+        }
 
-    tracing::info!(vm_id = %id, qemu = ?invocation.program, "spawning qemu");
-    let child = spawn_qemu(&invocation).await?;
-    let pid = child.id();
+        let spec = VmLaunchSpec {
+            vm_id: id.clone(),
+            vm_name: name.clone(),
+            image_path: artifacts.qcow2,
+            kernel_path: artifacts.kernel,
+            initrd_path: artifacts.initramfs,
+            seed_iso: guest_image.is_cloud().then(|| vm_dir.join("seed.iso")),
+            state_dir: state.config.state_dir.clone(),
+            workspace: prepared.as_ref().map(|p| p.share.clone()),
+            workspace_base: prepared.as_ref().and_then(|p| p.base.clone()),
+            workspace_gitdir: prepared.as_ref().and_then(|p| p.gitdir.clone()),
+            host_ssh_port: ssh_port,
+            host_docker_port: docker_port,
+            resources: VmResources { cpus, memory_mb },
+            authorized_ssh_pubkey: vm_keys.public_key.clone(),
+            display_mode: match display_mode {
+                VmDisplayMode::Headless => QemuDisplayMode::Headless,
+                VmDisplayMode::Desktop => QemuDisplayMode::Desktop,
+            },
+            network_policy: network_policy.clone(),
+            mounts: mounts.iter().map(VmMount::to_host_mount).collect(),
+        };
 
-    {
-        let mut registry = state.vms.write().await;
-        registry.insert(id.clone(), child);
-    }
-    if let Some(pid) = pid {
-        sqlx::query("UPDATE vms SET qemu_pid = ?1 WHERE id = ?2")
-            .bind(i64::from(pid))
-            .bind(&id)
-            .execute(&state.db)
-            .await?;
+        prepare_vm_dir(&spec).await?;
+        cloud_init::write_seed(&state.config, &spec).await?;
+        let invocation = QemuInvocation::build(&state.config, &spec)?;
+        if !network_policy.is_open() {
+            // Listen before QEMU starts so the guest's first proxied
+            // connection doesn't race the bind.
+            let proxy = spawn_egress_proxy(&id, &vm_dir, egress_rules)?;
+            state.egress_proxies.insert(id.clone(), proxy);
+        }
+
+        tracing::info!(vm_id = %id, qemu = ?invocation.program, "spawning qemu");
+        let child = spawn_qemu(&invocation).await?;
+        let pid = child.id();
+
+        {
+            let mut registry = state.vms.write().await;
+            registry.insert(id.clone(), child);
+        }
+        if let Some(pid) = pid {
+            sqlx::query("UPDATE vms SET qemu_pid = ?1 WHERE id = ?2")
+                .bind(i64::from(pid))
+                .bind(&id)
+                .execute(&state.db)
+                .await?;
+        }
+        Ok(workspace_branch)
     }
+    .await;
+    let workspace_branch = match booted {
+        Ok(branch) => branch,
+        Err(e) => {
+            if let Err(cleanup) = teardown_vm(state, &id).await {
+                tracing::warn!(vm_id = %id, error = %cleanup, "failed to clean up partially created vm");
+            }
+            return Err(e);
+        }
+    };
 
     // Fire the create event AFTER the row is inserted but BEFORE we await
     // anything else, so subscribers learn about the new VM as early as
@@ -480,7 +703,7 @@ async fn create_vm(
         }
     });
 
-    Ok(Json(VmSummary {
+    Ok(VmSummary {
         id,
         name,
         status,
@@ -495,7 +718,148 @@ async fn create_vm(
         exited_at: None,
         exit_code: None,
         owner_user_id,
-    }))
+        network_policy,
+        workspace_mode,
+        workspace_branch,
+        idle_timeout_secs,
+        max_lifetime_secs,
+        idle_action,
+        suspended_at: None,
+        preset: preset.preset,
+        mounts,
+    })
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+pub struct CloneVmRequest {
+    /// Name of the copy. Defaults to a generated one, as for `POST /v1/vms`.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vms/{id}/clone",
+    tag = "vms",
+    description = "Boot a copy of a VM: same resources, image, policy, workspace and mounts, \
+                   with its disk flattened into a new overlay, a fresh SSH keypair and new \
+                   ports. A running source is paused only while its overlay is copied. \
+                   `overlay` and `worktree` workspaces get a fresh copy of the host \
+                   directory, not the source VM's pending changes. The caller owns the \
+                   clone and it counts against their quota.",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = CloneVmRequest,
+    responses(
+        (status = 200, description = "Clone is starting", body = VmSummary),
+        (status = 403, description = "Caller's quota would be exceeded", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+        (status = 409, description = "Source is starting or stopping, or has no disk", body = ErrorResponse),
+    )
+)]
+async fn clone_vm(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+    Json(request): Json<CloneVmRequest>,
+) -> Result<Json<VmSummary>> {
+    ensure_vm_visible(&state, &actor, &id).await?;
+    let source = load_vm(&state, &id).await?;
+    if matches!(source.status, VmStatus::Starting | VmStatus::Stopping) {
+        return Err(SupervisorError::Conflict(format!(
+            "vm {id} is `{}`; wait for it to settle before cloning",
+            source.status.as_str()
+        )));
+    }
+    if !overlay_path_for(&state.config.state_dir, &id).exists() {
+        return Err(SupervisorError::Conflict(format!("vm {id} has no disk to clone")));
+    }
+    let (env, agent_kind): (sqlx::types::Json<BTreeMap<String, String>>, Option<AgentKindName>) =
+        sqlx::query_as("SELECT env, agent_kind FROM vms WHERE id = ?1")
+            .bind(&id)
+            .fetch_one(&state.db)
+            .await?;
+    let create = clone_request(&source, &state.config.presets, request.name, env.0, agent_kind);
+    let clone = launch(&state, actor.user_id.clone(), create, Some(&source)).await?;
+    tracing::info!(vm_id = %clone.id, source = %id, "cloned vm");
+    Ok(Json(clone))
+}
+
+/// The `POST /v1/vms` request that recreates `source` under `name`.
+fn clone_request(
+    source: &VmSummary,
+    presets: &BTreeMap<String, VmPreset>,
+    name: Option<String>,
+    env: BTreeMap<String, String>,
+    agent_kind: Option<AgentKindName>,
+) -> CreateVmRequest {
+    // Limits are stored as NULL when off; `0` turns them off again.
+    let limit = |secs: Option<i64>| secs.map_or(Some(0), |secs| u64::try_from(secs).ok());
+    CreateVmRequest {
+        // Only re-recorded while the preset still exists; every field it
+        // filled in is copied below anyway.
+        preset: source
+            .preset
+            .clone()
+            .filter(|preset| presets.contains_key(preset)),
+        name,
+        cwd: source.cwd.clone(),
+        cpus: source.cpus.and_then(|cpus| u32::try_from(cpus).ok()),
+        memory_mb: source.memory_mb.and_then(|memory| u32::try_from(memory).ok()),
+        image: Some(source.image.clone()),
+        display_mode: Some(source.display_mode),
+        network_policy: Some(source.network_policy.clone()),
+        workspace_mode: Some(source.workspace_mode),
+        idle_timeout_secs: limit(source.idle_timeout_secs),
+        max_lifetime_secs: limit(source.max_lifetime_secs),
+        idle_action: Some(source.idle_action),
+        env: Some(env),
+        mounts: Some(source.mounts.clone()),
+        agent_kind,
+    }
+}
+
+/// Put a flattened copy of `source`'s disk at `vm_dir/disk.qcow2`,
+/// backed by `image`. A running source is paused (under `vm_power`, so
+/// the idle monitor can't resume it mid-copy) only while its overlay is
+/// copied; the copy is crash-consistent, like a live snapshot.
+async fn clone_disk(
+    state: &SharedState,
+    source: &VmSummary,
+    vm_dir: &std::path::Path,
+    image: &std::path::Path,
+) -> Result<()> {
+    let overlay = overlay_path_for(&state.config.state_dir, &source.id);
+    let target = vm_dir.join("disk.qcow2");
+    if !state.vms.read().await.contains(&source.id) {
+        return qemu::flatten_overlay(&overlay, &target, image).await;
+    }
+    let snapshot = vm_dir.join("clone-source.qcow2");
+    {
+        let _power = state.vm_power.lock().await;
+        // Re-read under the lock: a suspended VM is already paused and
+        // must stay that way.
+        let suspended: Option<Option<String>> =
+            sqlx::query_scalar("SELECT suspended_at FROM vms WHERE id = ?1")
+                .bind(&source.id)
+                .fetch_optional(&state.db)
+                .await?;
+        let pause = !matches!(suspended, Some(Some(_)));
+        let mut qmp =
+            QmpClient::connect(&qmp_socket_for(&state.config.state_dir, &source.id)).await?;
+        if pause {
+            qmp.execute("stop", None).await?;
+        }
+        let copied = qemu::copy_overlay(&overlay, &snapshot).await;
+        if pause {
+            if let Err(e) = qmp.execute("cont", None).await {
+                tracing::warn!(vm_id = %source.id, error = %e, "failed to resume vm after cloning");
+            }
+        }
+        copied?;
+    }
+    let flattened = qemu::flatten_overlay(&snapshot, &target, image).await;
+    let _ = tokio::fs::remove_file(&snapshot).await;
+    flattened
 }
 
 /// Probe the forwarded SSH port until the guest's sshd answers with an
@@ -565,6 +929,16 @@ async fn stop_vm(
     Path(id): Path<String>,
 ) -> Result<Json<OperationResult>> {
     ensure_vm_visible(&state, &actor, &id).await?;
+    shut_down_vm(&state, &id).await?;
+    Ok(Json(OperationResult::status(id, "stopped")))
+}
+
+/// Kill the VM's QEMU, mark it and its agents stopped, and release its
+/// ports; the row and on-disk state stay. Callers check visibility
+/// first; the idle monitor (`crate::idle`) uses this for VMs past their
+/// idle timeout or lifetime.
+pub(crate) async fn shut_down_vm(state: &SharedState, id: &str) -> Result<()> {
+    let id = id.to_string();
     // Read the port pair BEFORE we mark the VM stopped so we can release
     // them back to the allocator.
     let ports: Option<(Option<i64>, Option<i64>)> = sqlx::query_as(
@@ -576,7 +950,8 @@ async fn stop_vm(
 
     let exited_at = Utc::now().to_rfc3339();
     let query_result = sqlx::query(
-        "UPDATE vms SET status = 'stopped', exited_at = ?1 WHERE id = ?2 AND status != 'stopped'",
+        "UPDATE vms SET status = 'stopped', exited_at = ?1, suspended_at = NULL \
+         WHERE id = ?2 AND status != 'stopped'",
     )
     .bind(&exited_at)
     .bind(&id)
@@ -599,7 +974,7 @@ async fn stop_vm(
     .await
     .unwrap_or_default();
     let _ = sqlx::query(
-        "UPDATE agents SET status = 'stopped', exited_at = ?1 \
+        "UPDATE agents SET status = 'stopped', exited_at = ?1, waiting_since = NULL \
          WHERE vm_id = ?2 AND status != 'stopped'",
     )
     .bind(&exited_at)
@@ -611,6 +986,8 @@ async fn stop_vm(
     if let Some(mut child) = registry.remove(&id) {
         let _ = child.kill().await;
     }
+    state.egress_proxies.remove(&id);
+    release_forwards(state, &id).await;
     if let Some((ssh, docker)) = ports {
         let to_release: Vec<u16> = [ssh, docker]
             .into_iter()
@@ -618,11 +995,20 @@ async fn stop_vm(
             .collect();
         state.port_allocator.release(to_release);
     }
-    state.events.emit(SupervisorEvent::VmUpdated { id: id.clone() });
+    state.events.emit(SupervisorEvent::VmUpdated { id });
     for agent_id in reaped_agent_ids {
         state.events.emit(SupervisorEvent::AgentUpdated { id: agent_id });
     }
-    Ok(Json(OperationResult::status(id, "stopped")))
+    Ok(())
+}
+
+/// Drop the VM's runtime port forwards and free their host ports. The
+/// rules themselves go away with its QEMU.
+async fn release_forwards(state: &SharedState, id: &str) {
+    match forwards::take_for_vm(&state.db, id).await {
+        Ok(ports) => state.forward_ports.release(ports),
+        Err(e) => tracing::warn!(vm_id = %id, error = %e, "failed to drop vm port forwards"),
+    }
 }
 
 #[utoipa::path(
@@ -666,6 +1052,7 @@ async fn update_vm(
     params(("id" = String, Path, description = "VM id")),
     responses(
         (status = 200, description = "VM deleted", body = OperationResult),
+        (status = 403, description = "VM is only shared with the caller", body = ErrorResponse),
         (status = 404, description = "Unknown VM", body = ErrorResponse),
     )
 )]
@@ -674,14 +1061,27 @@ async fn delete_vm(
     Extension(actor): Extension<AuthContext>,
     Path(id): Path<String>,
 ) -> Result<Json<OperationResult>> {
-    ensure_vm_visible(&state, &actor, &id).await?;
-    // Capture port assignments before deletion so we can release them.
-    let ports: Option<(Option<i64>, Option<i64>)> =
-        sqlx::query_as("SELECT host_ssh_port, host_docker_port FROM vms WHERE id = ?1")
-            .bind(&id)
-            .fetch_optional(&state.db)
-            .await?;
+    ensure_vm_owned(&state, &actor, &id).await?;
+    teardown_vm(&state, &id).await?;
+    Ok(Json(OperationResult::deleted(id)))
+}
+
+/// Kill the VM's QEMU, delete its row and on-disk state, and release its
+/// ports. Callers check visibility first; the task runner uses this to
+/// dispose of the VMs it booted.
+pub(crate) async fn teardown_vm(state: &SharedState, id: &str) -> Result<()> {
+    let id = id.to_string();
+    // Capture port assignments (and the workspace) before deletion so we
+    // can release them.
+    let row = match load_vm(state, &id).await {
+        Ok(vm) => Some(vm),
+        Err(SupervisorError::NotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
 
+    // Before the row goes: its `vm_forwards` rows cascade with it, and
+    // their host ports have to go back to the allocator.
+    release_forwards(state, &id).await;
     let query_result = sqlx::query("DELETE FROM vms WHERE id = ?1")
         .bind(&id)
         .execute(&state.db)
@@ -701,7 +1101,7 @@ async fn delete_vm(
     .unwrap_or_default();
     let exited_at = Utc::now().to_rfc3339();
     let _ = sqlx::query(
-        "UPDATE agents SET status = 'stopped', exited_at = ?1 \
+        "UPDATE agents SET status = 'stopped', exited_at = ?1, waiting_since = NULL \
          WHERE vm_id = ?2 AND status != 'stopped'",
     )
     .bind(&exited_at)
@@ -726,7 +1126,20 @@ async fn delete_vm(
             let _ = child.kill().await;
         }
     }
+    state.egress_proxies.remove(&id);
     let vm_dir = vm_dir_for(&state.config.state_dir, &id);
+    // Unregister the worktree before its directory goes away; its branch
+    // stays in the repository.
+    if let Some(VmSummary {
+        cwd: Some(cwd),
+        workspace_mode: WorkspaceMode::Worktree,
+        ..
+    }) = &row
+    {
+        if let Err(e) = workspace::remove_worktree(std::path::Path::new(cwd), &vm_dir).await {
+            tracing::warn!(vm_id = %id, error = %e, "failed to remove vm worktree on delete");
+        }
+    }
     if let Err(e) = tokio::fs::remove_dir_all(&vm_dir).await {
         // NotFound is fine — the dir was never created (e.g. spawn failed
         // before prepare_vm_dir ran). Anything else is suspicious enough
@@ -741,18 +1154,18 @@ async fn delete_vm(
         }
     }
 
-    if let Some((ssh, docker)) = ports {
-        let to_release: Vec<u16> = [ssh, docker]
+    if let Some(vm) = row {
+        let to_release: Vec<u16> = [vm.host_ssh_port, vm.host_docker_port]
             .into_iter()
             .filter_map(|p| p.and_then(|v| u16::try_from(v).ok()))
             .collect();
         state.port_allocator.release(to_release);
     }
-    state.events.emit(SupervisorEvent::VmDeleted { id: id.clone() });
+    state.events.emit(SupervisorEvent::VmDeleted { id });
     for agent_id in reaped_agent_ids {
         state.events.emit(SupervisorEvent::AgentUpdated { id: agent_id });
     }
-    Ok(Json(OperationResult::deleted(id)))
+    Ok(())
 }
 
 #[utoipa::path(
@@ -772,9 +1185,11 @@ async fn get_vm_ssh(
     Path(id): Path<String>,
 ) -> Result<Json<VmSshInfo>> {
     let summary = load_vm(&state, &id).await?;
-    if !actor.may_access(summary.owner_user_id.as_deref()) {
+    if !actor.may_access_vm(&summary.id, summary.owner_user_id.as_deref()) {
         return Err(SupervisorError::NotFound(format!("vm {id} not found")));
     }
+    // The caller is about to ssh in directly.
+    resume_if_suspended(&state, &id).await?;
     let port = summary.host_ssh_port.ok_or_else(|| {
         SupervisorError::NotFound(format!("vm {id} has no allocated ssh port"))
     })?;
@@ -804,6 +1219,110 @@ async fn get_vm_ssh(
     }))
 }
 
+/// Blocked attempts returned by `get_vm_egress_log`; older entries stay
+/// in the on-disk log.
+const EGRESS_LOG_LIMIT: usize = 200;
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/egress-log",
+    tag = "vms",
+    description = "Outbound connections the VM's network policy refused, oldest first \
+                   (at most the newest 200). Always empty for `open` VMs.",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "Blocked egress attempts", body = Vec<BlockedEgressAttempt>),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+    )
+)]
+async fn get_vm_egress_log(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<BlockedEgressAttempt>>> {
+    ensure_vm_visible(&state, &actor, &id).await?;
+    let vm_dir = vm_dir_for(&state.config.state_dir, &id);
+    Ok(Json(read_blocked_log(&vm_dir, EGRESS_LOG_LIMIT).await?))
+}
+
+/// Load a visible VM together with the host directory its workspace was
+/// taken from.
+async fn load_vm_workspace(
+    state: &SharedState,
+    actor: &AuthContext,
+    id: &str,
+) -> Result<(VmSummary, PathBuf)> {
+    let vm = load_vm(state, id).await?;
+    if !actor.may_access_vm(&vm.id, vm.owner_user_id.as_deref()) {
+        return Err(SupervisorError::NotFound(format!("vm {id} not found")));
+    }
+    let source = vm
+        .cwd
+        .as_deref()
+        .map(PathBuf::from)
+        .ok_or_else(|| SupervisorError::BadRequest(format!("vm {id} has no workspace")))?;
+    Ok((vm, source))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/vms/{id}/diff",
+    tag = "vms",
+    description = "The agent's changes as a unified diff against the VM's `cwd`. \
+                   Only for `overlay` and `worktree` workspaces.",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "Unified diff (empty when nothing changed)", body = String, content_type = "text/plain"),
+        (status = 400, description = "VM has no isolated workspace", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+    )
+)]
+async fn get_vm_diff(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<axum::response::Response> {
+    let (vm, source) = load_vm_workspace(&state, &actor, &id).await?;
+    let vm_dir = vm_dir_for(&state.config.state_dir, &id);
+    let body = workspace::diff(vm.workspace_mode, &source, &vm_dir).await?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/vms/{id}/apply",
+    tag = "vms",
+    description = "Write the agent's changes back into the VM's `cwd`. `overlay` copies \
+                   changed files and refuses if any of them also changed in `cwd`; \
+                   `worktree` commits pending changes on the VM's branch and merges it \
+                   into `cwd`'s current branch, aborting the merge on conflicts.",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "Changes applied", body = WorkspaceApplyResult),
+        (status = 400, description = "VM has no isolated workspace", body = ErrorResponse),
+        (status = 404, description = "Unknown VM", body = ErrorResponse),
+        (status = 409, description = "Changes conflict with `cwd`; nothing was written", body = ErrorResponse),
+    )
+)]
+async fn apply_vm_workspace(
+    State(state): State<SharedState>,
+    Extension(actor): Extension<AuthContext>,
+    Path(id): Path<String>,
+) -> Result<Json<WorkspaceApplyResult>> {
+    let (vm, source) = load_vm_workspace(&state, &actor, &id).await?;
+    let vm_dir = vm_dir_for(&state.config.state_dir, &id);
+    let result = workspace::apply(
+        vm.workspace_mode,
+        &source,
+        &vm_dir,
+        &vm.name,
+        vm.workspace_branch.as_deref(),
+    )
+    .await?;
+    tracing::info!(vm_id = %id, files = result.paths.len(), "applied vm workspace changes");
+    Ok(Json(result))
+}
+
 async fn get_vm_display(
     State(state): State<SharedState>,
     Extension(actor): Extension<AuthContext>,
@@ -812,12 +1331,15 @@ async fn get_vm_display(
 ) -> std::result::Result<axum::response::Response, SupervisorError> {
     // Confirm the caller may see this VM before upgrading the connection.
     ensure_vm_visible(&state, &actor, &id).await?;
+    let attached = state.ssh_sessions.attach(&id);
+    resume_if_suspended(&state, &id).await?;
     let socket_path = display_socket_for(&state.config.state_dir, &id);
     Ok(ws
         .protocols(["binary"])
         .max_message_size(WS_MAX_PAYLOAD_BYTES)
         .max_frame_size(WS_MAX_PAYLOAD_BYTES)
         .on_upgrade(move |socket| async move {
+            let _attached = attached;
             if let Err(e) = proxy_websocket_to_unix_socket(socket, &socket_path).await {
                 tracing::debug!(vm_id = %id, error = %e, "display proxy ended");
             }
@@ -832,6 +1354,8 @@ async fn get_vm_console(
 ) -> std::result::Result<axum::response::Response, SupervisorError> {
     // Confirm the caller may see this VM before upgrading the connection.
     ensure_vm_visible(&state, &actor, &id).await?;
+    let attached = state.ssh_sessions.attach(&id);
+    resume_if_suspended(&state, &id).await?;
     let socket_path = console_socket_for(&state.config.state_dir, &id);
     let log_path = state
         .config
@@ -843,6 +1367,7 @@ async fn get_vm_console(
         .max_message_size(WS_MAX_PAYLOAD_BYTES)
         .max_frame_size(WS_MAX_PAYLOAD_BYTES)
         .on_upgrade(move |socket| async move {
+            let _attached = attached;
             if let Err(e) = proxy_websocket_to_unix_socket_with_replay(
                 socket,
                 &socket_path,
@@ -875,15 +1400,17 @@ async fn get_vm_ssh_io(
     ws: WebSocketUpgrade,
 ) -> std::result::Result<axum::response::Response, SupervisorError> {
     let summary = load_vm(&state, &id).await?;
-    if !actor.may_access(summary.owner_user_id.as_deref()) {
+    if !actor.may_access_vm(&summary.id, summary.owner_user_id.as_deref()) {
         return Err(SupervisorError::NotFound(format!("vm {id} not found")));
     }
-    if summary.status != VmStatus::Running {
+    if !matches!(summary.status, VmStatus::Running | VmStatus::Suspended) {
         return Err(SupervisorError::Conflict(format!(
             "vm {id} is in status `{}`; ssh sessions require a running vm",
             summary.status.as_str()
         )));
     }
+    let attached = state.ssh_sessions.attach(&id);
+    resume_if_suspended(&state, &id).await?;
     let port_i64 = summary.host_ssh_port.ok_or_else(|| {
         SupervisorError::InvalidState(format!("vm {id} has no allocated ssh port"))
     })?;
@@ -933,6 +1460,7 @@ async fn get_vm_ssh_io(
         .max_message_size(WS_MAX_PAYLOAD_BYTES)
         .max_frame_size(WS_MAX_PAYLOAD_BYTES)
         .on_upgrade(move |socket| async move {
+            let _attached = attached;
             if let Err(e) = proxy_vm_ssh(
                 socket,
                 VmSshSpec {
@@ -1332,3 +1860,84 @@ pub(super) async fn proxy_websocket_to_unix_socket_with_replay(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mounts::MountMode;
+
+    fn summary() -> VmSummary {
+        VmSummary {
+            id: "source".into(),
+            name: "demo".into(),
+            status: VmStatus::Running,
+            cwd: Some("/home/x/proj".into()),
+            cpus: Some(4),
+            memory_mb: Some(8192),
+            image: "alpine".into(),
+            display_mode: VmDisplayMode::Desktop,
+            host_ssh_port: Some(22001),
+            host_docker_port: Some(22501),
+            started_at: "2026-01-01T00:00:00Z".into(),
+            exited_at: None,
+            exit_code: None,
+            owner_user_id: Some("owner".into()),
+            network_policy: NetworkPolicy::default(),
+            workspace_mode: WorkspaceMode::Overlay,
+            workspace_branch: None,
+            idle_timeout_secs: Some(600),
+            max_lifetime_secs: None,
+            idle_action: IdleAction::Stop,
+            suspended_at: None,
+            preset: Some("big".into()),
+            mounts: vec![VmMount {
+                host_path: "/srv/data".into(),
+                guest_path: "/data".into(),
+                mode: MountMode::Rw,
+            }],
+        }
+    }
+
+    #[test]
+    fn clone_request_copies_the_source() {
+        let preset: VmPreset = serde_json::from_str("{}").unwrap();
+        let presets = BTreeMap::from([("big".to_string(), preset)]);
+        let env = BTreeMap::from([("RUST_LOG".to_string(), "debug".to_string())]);
+        let request = clone_request(
+            &summary(),
+            &presets,
+            Some("copy".into()),
+            env.clone(),
+            Some(AgentKindName::Claude),
+        );
+        assert_eq!(request.preset.as_deref(), Some("big"));
+        assert_eq!(request.name.as_deref(), Some("copy"));
+        assert_eq!(request.cwd.as_deref(), Some("/home/x/proj"));
+        assert_eq!(request.cpus, Some(4));
+        assert_eq!(request.memory_mb, Some(8192));
+        assert_eq!(request.image.as_deref(), Some("alpine"));
+        assert_eq!(request.display_mode, Some(VmDisplayMode::Desktop));
+        assert_eq!(request.network_policy, Some(NetworkPolicy::default()));
+        assert_eq!(request.workspace_mode, Some(WorkspaceMode::Overlay));
+        assert_eq!(request.idle_action, Some(IdleAction::Stop));
+        assert_eq!(request.env, Some(env));
+        assert_eq!(request.mounts, Some(summary().mounts));
+        assert_eq!(request.agent_kind, Some(AgentKindName::Claude));
+    }
+
+    #[test]
+    fn clone_request_turns_null_limits_off_explicitly() {
+        let request = clone_request(&summary(), &BTreeMap::new(), None, BTreeMap::new(), None);
+        assert_eq!(request.idle_timeout_secs, Some(600));
+        // NULL means "off"; leaving it out would pick up `vm_defaults`.
+        assert_eq!(request.max_lifetime_secs, Some(0));
+    }
+
+    #[test]
+    fn clone_request_drops_a_preset_removed_from_the_config() {
+        let request = clone_request(&summary(), &BTreeMap::new(), None, BTreeMap::new(), None);
+        assert_eq!(request.preset, None);
+        assert_eq!(request.cpus, Some(4));
+        assert_eq!(request.memory_mb, Some(8192));
+    }
+}
@@ -0,0 +1,304 @@
+//! Append-only audit log (`audit_log`, `GET /v1/audit`).
+//!
+//! The rest of the database records current state; this records who did
+//! what. `api::audit::record_request` writes one entry per state-changing
+//! or shell-granting request that [`classify`] recognises — VM lifecycle,
+//! agent spawn/attach/stop, snapshots, shares, forwards, users, tokens,
+//! secrets and tasks — once the handler has answered, so the entry
+//! carries the outcome. Logins are recorded by the login handler itself,
+//! because the actor there is the username in the body, not an
+//! authenticated identity.
+//!
+//! Entries are never updated or deleted (the table's triggers refuse
+//! both); `id` increases monotonically and is the pagination cursor.
+
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+
+/// Action recorded for `POST /v1/auth/login`.
+pub const LOGIN_ACTION: &str = "auth.login";
+
+/// `actor` of entries made with no user row behind them: the static admin
+/// token, the unix socket (no `source_ip`) or `auth_disabled` mode.
+pub const STATIC_ADMIN_ACTOR: &str = "admin";
+
+/// Longest page `query` returns.
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+#[derive(Serialize, Deserialize, ToSchema, sqlx::Type, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    /// Anything below 400 succeeded, including a websocket upgrade (101).
+    pub fn from_status(status: StatusCode) -> Self {
+        if status.is_client_error() || status.is_server_error() {
+            Self::Failure
+        } else {
+            Self::Success
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone, Debug)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: String,
+    /// `users.id` of the actor; `None` for the static admin identity and
+    /// for failed logins.
+    pub actor_user_id: Option<String>,
+    /// Username at the time, the attempted username of a login, or
+    /// `admin` for the static admin identity.
+    pub actor: String,
+    /// Peer address; `None` for requests over the unix socket.
+    pub source_ip: Option<String>,
+    /// `<resource>.<verb>`, e.g. `vm.create`, `agent.attach`, `auth.login`.
+    pub action: String,
+    /// Id (or username, secret name) of the resource acted on.
+    pub target: Option<String>,
+    pub outcome: AuditOutcome,
+    /// HTTP status the request was answered with.
+    pub status_code: Option<i64>,
+}
+
+/// An entry to append; `id` and `at` are assigned by [`record`].
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub actor_user_id: Option<String>,
+    /// Only consulted when `actor_user_id` is `None`; otherwise the
+    /// user's current username is looked up.
+    pub actor: String,
+    pub source_ip: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+    pub outcome: AuditOutcome,
+    pub status_code: Option<u16>,
+}
+
+/// Target of a request whose resource id only exists once the handler ran
+/// (creates). Handlers return it as a response extension; it overrides
+/// whatever [`classify`] took from the path.
+#[derive(Debug, Clone)]
+pub struct AuditTarget(pub String);
+
+/// Audit action and path-derived target for `method path`, or `None` when
+/// the request isn't audited (reads, the event stream, metrics).
+pub fn classify(method: &Method, path: &str) -> Option<(&'static str, Option<String>)> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let target = |segment: &str| Some(segment.to_string());
+    let (action, target) = match (method.as_str(), segments.as_slice()) {
+        ("POST", ["v1", "vms"]) => ("vm.create", None),
+        ("POST", ["v1", "vms", id, "clone"]) => ("vm.clone", target(id)),
+        ("POST", ["v1", "vms", id, "stop"]) => ("vm.stop", target(id)),
+        ("PATCH", ["v1", "vms", id]) => ("vm.update", target(id)),
+        ("DELETE", ["v1", "vms", id]) => ("vm.delete", target(id)),
+        ("POST", ["v1", "vms", id, "apply"]) => ("vm.apply", target(id)),
+        ("GET", ["v1", "vms", id, "ssh"]) => ("vm.ssh_credentials", target(id)),
+        ("GET", ["v1", "vms", id, "ssh-io" | "console" | "display"]) => {
+            ("vm.attach", target(id))
+        }
+        ("PUT", ["v1", "vms", id, "shares", _]) => ("vm.share", target(id)),
+        ("DELETE", ["v1", "vms", id, "shares", _]) => ("vm.unshare", target(id)),
+        ("POST", ["v1", "vms", id, "forwards"]) => ("vm.forward", target(id)),
+        ("DELETE", ["v1", "vms", id, "forwards", _]) => ("vm.unforward", target(id)),
+        ("POST", ["v1", "vms", id, "snapshots"]) => ("snapshot.create", target(id)),
+        ("POST", ["v1", "vms", _, "snapshots", snapshot, "restore"]) => {
+            ("snapshot.restore", target(snapshot))
+        }
+        ("DELETE", ["v1", "vms", _, "snapshots", snapshot]) => {
+            ("snapshot.delete", target(snapshot))
+        }
+        ("POST", ["v1", "vms", _, "agents"]) => ("agent.create", None),
+        ("PUT", ["v1", "vms", _, "agents", agent]) => ("agent.create", target(agent)),
+        ("GET", ["v1", "agents", id, "io"]) => ("agent.attach", target(id)),
+        ("POST", ["v1", "agents", id, "stop"]) => ("agent.stop", target(id)),
+        ("PATCH", ["v1", "agents", id]) => ("agent.update", target(id)),
+        ("DELETE", ["v1", "agents", id]) => ("agent.delete", target(id)),
+        ("POST", ["v1", "tasks"]) => ("task.create", None),
+        ("POST", ["v1", "tasks", id, "cancel"]) => ("task.cancel", target(id)),
+        ("DELETE", ["v1", "tasks", id]) => ("task.delete", target(id)),
+        ("POST", ["v1", "users"]) => ("user.create", None),
+        ("PATCH", ["v1", "users", username]) => ("user.update", target(username)),
+        ("POST", ["v1", "tokens"]) => ("token.create", None),
+        ("DELETE", ["v1", "tokens", id]) => ("token.revoke", target(id)),
+        ("PUT", ["v1", "secrets", name]) => ("secret.set", target(name)),
+        ("DELETE", ["v1", "secrets", name]) => ("secret.delete", target(name)),
+        _ => return None,
+    };
+    Some((action, target))
+}
+
+/// Append `entry`. Callers log a failure rather than failing the request
+/// it describes.
+pub async fn record(db: &SqlitePool, entry: NewAuditEntry) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO audit_log (at, actor_user_id, actor, source_ip, action, target, outcome, status_code) \
+         VALUES (?1, ?2, COALESCE((SELECT username FROM users WHERE id = ?2), ?3), ?4, ?5, ?6, ?7, ?8)",
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(&entry.actor_user_id)
+    .bind(&entry.actor)
+    .bind(&entry.source_ip)
+    .bind(&entry.action)
+    .bind(&entry.target)
+    .bind(entry.outcome)
+    .bind(entry.status_code.map(i64::from))
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Filters of `GET /v1/audit`; every field narrows, `None` matches all.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    /// Only this user's entries (`users.id`).
+    pub actor_user_id: Option<String>,
+    /// `actor` as recorded (a username, `admin`).
+    pub actor: Option<String>,
+    /// Exact action, or a resource prefix: `vm` matches every `vm.*`.
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Up to `limit` entries matching `filter` with an id below `before`,
+/// newest first.
+pub async fn query(
+    db: &SqlitePool,
+    filter: &AuditFilter,
+    before: Option<i64>,
+    limit: u32,
+) -> Result<Vec<AuditEntry>> {
+    let rows = sqlx::query_as(
+        "SELECT id, at, actor_user_id, actor, source_ip, action, target, outcome, status_code \
+         FROM audit_log \
+         WHERE (?1 IS NULL OR actor_user_id = ?1) \
+           AND (?2 IS NULL OR actor = ?2) \
+           AND (?3 IS NULL OR action = ?3 OR action LIKE ?3 || '.%') \
+           AND (?4 IS NULL OR target = ?4) \
+           AND (?5 IS NULL OR outcome = ?5) \
+           AND (?6 IS NULL OR at >= ?6) \
+           AND (?7 IS NULL OR at < ?7) \
+           AND (?8 IS NULL OR id < ?8) \
+         ORDER BY id DESC LIMIT ?9",
+    )
+    .bind(&filter.actor_user_id)
+    .bind(&filter.actor)
+    .bind(&filter.action)
+    .bind(&filter.target)
+    .bind(filter.outcome)
+    // Stored timestamps are `to_rfc3339()` in UTC, so the same format
+    // compares correctly as text.
+    .bind(filter.since.map(|at| at.to_rfc3339()))
+    .bind(filter.until.map(|at| at.to_rfc3339()))
+    .bind(before)
+    .bind(i64::from(limit.min(MAX_PAGE_SIZE)))
+    .fetch_all(db)
+    .await?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn entry(action: &str, outcome: AuditOutcome) -> NewAuditEntry {
+        NewAuditEntry {
+            actor_user_id: None,
+            actor: STATIC_ADMIN_ACTOR.into(),
+            source_ip: Some("127.0.0.1".into()),
+            action: action.into(),
+            target: Some("vm-1".into()),
+            outcome,
+            status_code: Some(200),
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_newest_first_and_refuses_edits() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        for action in ["vm.create", "vm.stop", "agent.create", "vm.delete"] {
+            record(&pool, entry(action, AuditOutcome::Success)).await.unwrap();
+        }
+        record(&pool, entry("vm.stop", AuditOutcome::Failure)).await.unwrap();
+
+        let vm = AuditFilter {
+            action: Some("vm".into()),
+            ..AuditFilter::default()
+        };
+        let first = query(&pool, &vm, None, 2).await.unwrap();
+        let actions: Vec<_> = first.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["vm.stop", "vm.delete"]);
+        let rest = query(&pool, &vm, first.last().map(|e| e.id), 10).await.unwrap();
+        let actions: Vec<_> = rest.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["vm.stop", "vm.create"]);
+
+        let failures = AuditFilter {
+            outcome: Some(AuditOutcome::Failure),
+            ..AuditFilter::default()
+        };
+        assert_eq!(query(&pool, &failures, None, 10).await.unwrap().len(), 1);
+
+        assert!(sqlx::query("UPDATE audit_log SET actor = 'someone'")
+            .execute(&pool)
+            .await
+            .is_err());
+        assert!(sqlx::query("DELETE FROM audit_log").execute(&pool).await.is_err());
+    }
+
+    #[test]
+    fn classifies_audited_routes_only() {
+        assert_eq!(classify(&Method::POST, "/v1/vms"), Some(("vm.create", None)));
+        assert_eq!(
+            classify(&Method::DELETE, "/v1/vms/abc"),
+            Some(("vm.delete", Some("abc".into())))
+        );
+        assert_eq!(
+            classify(&Method::POST, "/v1/vms/abc/snapshots/s1/restore"),
+            Some(("snapshot.restore", Some("s1".into())))
+        );
+        assert_eq!(
+            classify(&Method::GET, "/v1/agents/a1/io"),
+            Some(("agent.attach", Some("a1".into())))
+        );
+        assert_eq!(
+            classify(&Method::PATCH, "/v1/users/alice"),
+            Some(("user.update", Some("alice".into())))
+        );
+        assert_eq!(classify(&Method::GET, "/v1/vms"), None);
+        assert_eq!(classify(&Method::GET, "/v1/vms/abc"), None);
+        assert_eq!(classify(&Method::GET, "/v1/events"), None);
+        assert_eq!(classify(&Method::GET, "/v1/audit"), None);
+    }
+
+    #[test]
+    fn outcome_follows_status() {
+        assert_eq!(
+            AuditOutcome::from_status(StatusCode::SWITCHING_PROTOCOLS),
+            AuditOutcome::Success
+        );
+        assert_eq!(AuditOutcome::from_status(StatusCode::OK), AuditOutcome::Success);
+        assert_eq!(AuditOutcome::from_status(StatusCode::FORBIDDEN), AuditOutcome::Failure);
+        assert_eq!(
+            AuditOutcome::from_status(StatusCode::INTERNAL_SERVER_ERROR),
+            AuditOutcome::Failure
+        );
+    }
+}
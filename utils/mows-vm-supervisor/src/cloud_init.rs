@@ -0,0 +1,216 @@
+//! NoCloud seed for `cloud` guest images (see `crate::images`).
+//!
+//! A stock cloud image has none of what the image-builder bakes in, so the
+//! seed does it on first boot:
+//!
+//! - authorizes the VM's own key (`crate::ssh_keys`) for root, which is
+//!   what `guest_ssh_user` logs in as; cloud-init generates the host keys
+//! - mounts the `mowsinit`, `workspace`, `workspacebase` and `creds` 9p
+//!   shares where the built images' fstab puts them
+//! - installs and starts dockerd (`docker.io`), through the egress proxy
+//!   for filtered network policies
+//! - runs the same `mows-agent-init.sh` the built images run, for the
+//!   agent kind, the worktree git dir, extra mounts and `profile.sh`
+//!
+//! `user-data` and `meta-data` are written under `<vm_dir>/cloud-init/`
+//! and packed into `<vm_dir>/seed.iso` (volume id `cidata`) with
+//! `cloud_init_iso_binary`.
+
+use std::path::Path;
+use std::process::Stdio;
+
+use serde_json::{json, Value};
+use tokio::process::Command;
+
+use crate::config::SupervisorConfig;
+use crate::egress::{guest_proxy_env, GUEST_EGRESS_PROXY_ADDR};
+use crate::error::{Result, SupervisorError};
+use crate::qemu::{vm_dir_for, VmLaunchSpec};
+
+const AGENT_INIT_SH: &str = include_str!("../image-builder/common/mows-agent-init.sh");
+
+const NINEP_OPTIONS: &str = "trans=virtio,version=9p2000.L";
+
+/// The `#cloud-config` document for `spec`.
+pub fn user_data(spec: &VmLaunchSpec, share_creds: bool) -> Result<String> {
+    let mut mounts = vec![ninep_mount("mowsinit", "/mowsinit", "ro")];
+    if spec.workspace.is_some() {
+        mounts.push(ninep_mount("workspace", "/workspace", "rw"));
+    }
+    if spec.workspace_base.is_some() {
+        mounts.push(ninep_mount("workspacebase", "/workspace-base", "ro"));
+    }
+    if share_creds {
+        mounts.push(ninep_mount("creds", "/creds", "ro"));
+    }
+    let mut write_files = vec![json!({
+        "path": "/usr/local/sbin/mows-agent-init.sh",
+        "permissions": "0755",
+        "content": AGENT_INIT_SH,
+    })];
+    if !spec.network_policy.is_open() {
+        write_files.push(json!({
+            "path": "/etc/systemd/system/docker.service.d/proxy.conf",
+            "content": docker_proxy_dropin(),
+        }));
+    }
+    let mut config = json!({
+        "hostname": spec.vm_name,
+        "disable_root": false,
+        "ssh_pwauth": false,
+        "ssh_authorized_keys": [spec.authorized_ssh_pubkey],
+        "mounts": mounts,
+        "package_update": true,
+        "packages": ["docker.io"],
+        "write_files": write_files,
+        "runcmd": [
+            ["systemctl", "enable", "--now", "docker"],
+            ["sh", "-c", ". /usr/local/sbin/mows-agent-init.sh"],
+        ],
+    });
+    if !spec.network_policy.is_open() {
+        // Outbound traffic only leaves through the egress proxy, so apt has
+        // to use it too; `profile.sh` covers login shells afterwards.
+        let proxy = format!("http://{GUEST_EGRESS_PROXY_ADDR}");
+        if let Some(config) = config.as_object_mut() {
+            config.insert(
+                "apt".into(),
+                json!({"http_proxy": proxy, "https_proxy": proxy}),
+            );
+        }
+    }
+    Ok(format!("#cloud-config\n{}", serde_yaml_neo::to_string(&config)?))
+}
+
+fn ninep_mount(tag: &str, target: &str, mode: &str) -> Value {
+    json!([tag, target, "9p", format!("{NINEP_OPTIONS},{mode},nofail"), "0", "0"])
+}
+
+fn docker_proxy_dropin() -> String {
+    let mut dropin = String::from("[Service]\n");
+    for (key, value) in guest_proxy_env() {
+        dropin.push_str(&format!("Environment=\"{key}={value}\"\n"));
+    }
+    dropin
+}
+
+/// Write the seed files for `spec` and pack them into `spec.seed_iso`.
+/// A no-op for VMs without one.
+pub async fn write_seed(config: &SupervisorConfig, spec: &VmLaunchSpec) -> Result<()> {
+    let Some(seed_iso) = &spec.seed_iso else {
+        return Ok(());
+    };
+    let seed_dir = vm_dir_for(&spec.state_dir, &spec.vm_id).join("cloud-init");
+    tokio::fs::create_dir_all(&seed_dir).await?;
+    let user_data = user_data(spec, config.agent_host_creds_path.is_some())?;
+    tokio::fs::write(seed_dir.join("user-data"), user_data).await?;
+    // A fresh instance-id per VM makes cloud-init treat every clone or
+    // snapshot restore of the same disk as the VM it was booted as.
+    let meta_data = format!(
+        "instance-id: {}\nlocal-hostname: {}\n",
+        spec.vm_id, spec.vm_name
+    );
+    tokio::fs::write(seed_dir.join("meta-data"), meta_data).await?;
+    pack_iso(&config.cloud_init_iso_binary, &seed_dir, seed_iso).await
+}
+
+async fn pack_iso(binary: &str, seed_dir: &Path, seed_iso: &Path) -> Result<()> {
+    let output = Command::new(binary)
+        .arg("-output")
+        .arg(seed_iso)
+        .args(["-volid", "cidata", "-joliet", "-rock", "-quiet"])
+        .arg(seed_dir.join("user-data"))
+        .arg(seed_dir.join("meta-data"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| SupervisorError::QemuSpawn(format!("failed to exec {binary}: {e}")))?;
+    if !output.status.success() {
+        tracing::warn!(
+            stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+            "cloud-init seed packing failed"
+        );
+        return Err(SupervisorError::QemuSpawn(format!(
+            "{binary} exited with {} while packing the cloud-init seed",
+            output.status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::egress::{NetworkPolicy, NetworkPolicyMode};
+    use crate::qemu::{DisplayMode, VmResources};
+
+    fn spec() -> VmLaunchSpec {
+        VmLaunchSpec {
+            vm_id: "id-123".into(),
+            vm_name: "glibc-box".into(),
+            image_path: PathBuf::from("/var/lib/mows-agent/images/debian-12.qcow2"),
+            kernel_path: None,
+            initrd_path: None,
+            seed_iso: Some(PathBuf::from("/tmp/mows-agent-test/vms/id-123/seed.iso")),
+            state_dir: PathBuf::from("/tmp/mows-agent-test"),
+            workspace: Some(PathBuf::from("/home/me/project")),
+            workspace_base: None,
+            workspace_gitdir: None,
+            host_ssh_port: 22000,
+            host_docker_port: 22001,
+            resources: VmResources {
+                cpus: 2,
+                memory_mb: 2048,
+            },
+            authorized_ssh_pubkey: "ssh-ed25519 AAAA test".into(),
+            display_mode: DisplayMode::Headless,
+            network_policy: NetworkPolicy::default(),
+            mounts: Vec::new(),
+        }
+    }
+
+    fn parse(user_data: &str) -> Value {
+        assert!(user_data.starts_with("#cloud-config\n"));
+        serde_yaml_neo::from_str(user_data).unwrap()
+    }
+
+    #[test]
+    fn user_data_authorizes_the_vm_key_and_mounts_present_shares() {
+        let doc = parse(&user_data(&spec(), false).unwrap());
+        assert_eq!(doc["ssh_authorized_keys"][0], "ssh-ed25519 AAAA test");
+        assert_eq!(doc["disable_root"], false);
+        let targets: Vec<&str> = doc["mounts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m[1].as_str().unwrap())
+            .collect();
+        assert_eq!(targets, ["/mowsinit", "/workspace"]);
+        assert!(doc["write_files"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("/mowsinit/mounts"));
+        assert!(doc.get("apt").is_none());
+    }
+
+    #[test]
+    fn filtered_policy_routes_apt_and_docker_through_the_proxy() {
+        let mut spec = spec();
+        spec.network_policy = NetworkPolicy {
+            mode: NetworkPolicyMode::DenyAll,
+            ..NetworkPolicy::default()
+        };
+        let doc = parse(&user_data(&spec, true).unwrap());
+        let proxy = format!("http://{GUEST_EGRESS_PROXY_ADDR}");
+        assert_eq!(doc["apt"]["https_proxy"], proxy.as_str());
+        assert!(doc["write_files"][1]["content"]
+            .as_str()
+            .unwrap()
+            .contains(&format!("HTTPS_PROXY={proxy}")));
+        assert_eq!(doc["mounts"].as_array().unwrap().len(), 3);
+    }
+}
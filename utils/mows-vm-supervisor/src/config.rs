@@ -15,12 +15,21 @@
 //! | `MOWS_AGENT_HOST_CREDS_PATH`          | `SupervisorConfig::load`                   | Host directory bind-mounted as `/creds` inside every guest (SECURITY-13). |
 //! | `RUST_LOG`                            | `mows_common_rust::observability::init_observability` | Tracing filter — standard tracing-subscriber syntax.                    |
 
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
+use crate::egress::NetworkPolicy;
 use crate::error::{Result, SupervisorError};
+use crate::idle::IdleAction;
+use crate::images::GuestImage;
+use crate::kinds::AgentKindExtras;
+use crate::mounts::VmMount;
+use crate::notifications::{NotificationKind, WebhookSecret};
+use crate::presets::VmPreset;
+use crate::secrets::SecretKey;
 
 /// Single source of truth for runtime configuration.
 ///
@@ -71,14 +80,74 @@ pub struct SupervisorConfig {
     #[serde(default)]
     pub vm_defaults: VmDefaults,
 
+    /// Named guest images a `create_vm` request can pick with `image`, on
+    /// top of the image-builder's distros; see `crate::images`.
+    #[serde(default)]
+    pub images: BTreeMap<String, GuestImage>,
+
+    /// Named bundles of VM settings a `create_vm` request (or
+    /// `mows vms run --preset`) can pick; see `crate::presets`.
+    #[serde(default)]
+    pub presets: BTreeMap<String, VmPreset>,
+
+    /// Extra host directories VMs may share in (`mounts` on `create_vm`);
+    /// see `crate::mounts`.
+    #[serde(default)]
+    pub host_mounts: HostMountConfig,
+
+    /// Extra env and MCP servers for the builtin agent kinds, keyed by
+    /// kind; values may reference secrets (`crate::kinds::AgentKindExtras`).
+    #[serde(default)]
+    pub agent_kinds: BTreeMap<String, AgentKindExtras>,
+
+    /// The encrypted secret store behind `/v1/secrets`; see `crate::secrets`.
+    #[serde(default)]
+    pub secrets: SecretStoreConfig,
+
     /// QEMU binary name (override for cross-arch builds or testing).
     #[serde(default = "default_qemu_binary")]
     pub qemu_binary: String,
 
+    /// Packs the cloud-init seed ISO of `cloud` images. Called with
+    /// genisoimage-style arguments, so `genisoimage` and `mkisofs` work
+    /// as well as the default `xorrisofs`.
+    #[serde(default = "default_cloud_init_iso_binary")]
+    pub cloud_init_iso_binary: String,
+
     /// Loopback port range (inclusive) used for ssh/docker port forwards.
     #[serde(default = "default_port_range")]
     pub port_range: PortRange,
 
+    /// Loopback port range (inclusive) for runtime port forwards added
+    /// through `/v1/vms/{id}/forwards`. Must not overlap `port_range`.
+    #[serde(default = "default_forward_port_range")]
+    pub forward_port_range: PortRange,
+
+    /// Outbound network policy applied when a `create_vm` request omits
+    /// `network_policy`. See `crate::egress` for how non-`open` modes are
+    /// enforced.
+    #[serde(default)]
+    pub default_network_policy: NetworkPolicy,
+
+    /// Headless task queue (`/v1/tasks`, `mows agents exec`).
+    #[serde(default)]
+    pub task_queue: TaskQueueConfig,
+
+    /// Asciicast recordings of agent sessions (`/v1/agents/{id}/recording`,
+    /// `mows agents replay`).
+    #[serde(default)]
+    pub recordings: RecordingConfig,
+
+    /// Idle / lifetime checks behind `vm_defaults.idle_timeout_secs` and
+    /// `vm_defaults.max_lifetime_secs`.
+    #[serde(default)]
+    pub idle_monitor: IdleMonitorConfig,
+
+    /// Agent exit / waiting-for-input detection and its webhook sinks
+    /// (`crate::notifications`).
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+
     /// Token used by the CLI when talking over the loopback HTTP listener.
     /// Read from env `MOWS_VM_SUPERVISOR_API_TOKEN_FILE` if set,
     /// else `MOWS_VM_SUPERVISOR_API_TOKEN`. Required for the HTTP listener.
@@ -92,6 +161,11 @@ pub struct SupervisorConfig {
     #[serde(skip)]
     pub auth_disabled: bool,
 
+    /// Key derived from `secrets.master_key_file` at load time; `None`
+    /// leaves the secret store disabled.
+    #[serde(skip)]
+    pub secret_key: Option<SecretKey>,
+
     /// Host directory bind-mounted read-only into every guest at `/creds`.
     /// Resolved once at startup from `MOWS_AGENT_HOST_CREDS_PATH` (with a
     /// fallback to `/host-creds` if that exists). Reading it from the
@@ -106,6 +180,21 @@ pub struct SupervisorConfig {
 pub struct VmDefaults {
     pub cpus: u32,
     pub memory_mb: u32,
+    /// Guest image used when a `create_vm` request omits `image`.
+    #[serde(default = "default_image")]
+    pub image: String,
+    /// Seconds a VM may sit idle before `idle_action` is applied. `0`
+    /// never acts on idleness. See `crate::idle` for what counts as idle.
+    #[serde(default = "default_idle_timeout_secs")]
+    pub idle_timeout_secs: u64,
+    /// Seconds after boot at which a VM is shut down, idle or not. `0`
+    /// lets VMs run until stopped.
+    #[serde(default)]
+    pub max_lifetime_secs: u64,
+    /// What an idle timeout does: `suspend` (resumed on the next attach)
+    /// or `stop`.
+    #[serde(default)]
+    pub idle_action: IdleAction,
 }
 
 impl Default for VmDefaults {
@@ -113,10 +202,168 @@ impl Default for VmDefaults {
         Self {
             cpus: 2,
             memory_mb: 2048,
+            image: default_image(),
+            idle_timeout_secs: default_idle_timeout_secs(),
+            max_lifetime_secs: 0,
+            idle_action: IdleAction::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdleMonitorConfig {
+    /// Seconds between idle checks; also the window guest CPU use is
+    /// averaged over.
+    pub interval_secs: u64,
+    /// A VM whose QEMU process used less CPU than this over the last
+    /// window counts as idle (percent of one host core, as `top` shows).
+    pub cpu_threshold_percent: f64,
+}
+
+impl Default for IdleMonitorConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            cpu_threshold_percent: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskQueueConfig {
+    /// Tasks running at once. Each one boots its own VM unless it
+    /// targets an existing one, so this bounds the VM count too.
+    pub max_concurrent: u32,
+    /// Applied when a task request omits `timeout_secs`.
+    pub default_timeout_secs: u64,
+    /// Upper bound a request's `timeout_secs` may ask for.
+    pub max_timeout_secs: u64,
+}
+
+impl Default for TaskQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 2,
+            default_timeout_secs: 30 * 60,
+            max_timeout_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordingConfig {
+    /// Record every agent session to `state_dir/agents/<id>/session.cast`.
+    pub enabled: bool,
+    /// Days an exited agent's recording is kept. `0` keeps them until the
+    /// agent is deleted.
+    pub retention_days: u32,
+    /// Per-session size cap; recording stops with a marker once reached.
+    /// `0` leaves recordings uncapped.
+    pub max_bytes: u64,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention_days: 30,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostMountConfig {
+    /// Host directories under which extra mounts may live. Empty (the
+    /// default) refuses every extra mount.
+    #[serde(default)]
+    pub allowed_prefixes: Vec<PathBuf>,
+    /// Default mounts for VMs created for an agent kind, keyed by kind
+    /// name, e.g. a shared cargo registry cache for `claude`.
+    #[serde(default)]
+    pub kinds: BTreeMap<String, Vec<VmMount>>,
+}
+
+impl HostMountConfig {
+    fn validate(&self) -> Result<()> {
+        if let Some(prefix) = self.allowed_prefixes.iter().find(|p| !p.is_absolute()) {
+            return Err(SupervisorError::Config(format!(
+                "host_mounts.allowed_prefixes: {} is not absolute",
+                prefix.display()
+            )));
+        }
+        for (kind, mounts) in &self.kinds {
+            if crate::kinds::builtin(kind).is_none() {
+                return Err(SupervisorError::Config(format!(
+                    "host_mounts.kinds: unknown agent kind {kind:?}"
+                )));
+            }
+            for mount in mounts {
+                crate::mounts::validate_guest_path(&mount.guest_path).map_err(|e| {
+                    SupervisorError::Config(format!("host_mounts.kinds.{kind}: {e}"))
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecretStoreConfig {
+    /// File whose bytes (at least 32) the store key is derived from,
+    /// e.g. `head -c 32 /dev/urandom > master.key`. Replacing it makes
+    /// every stored secret unreadable. Unset disables the store.
+    #[serde(default)]
+    pub master_key_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationConfig {
+    /// Seconds an agent's screen has to stay unchanged, with one of its
+    /// kind's `input_prompts` showing, before it counts as waiting for
+    /// input.
+    #[serde(default = "default_input_idle_secs")]
+    pub input_idle_secs: u64,
+    /// Endpoints every agent notification is POSTed to.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            input_idle_secs: default_input_idle_secs(),
+            webhooks: Vec::new(),
         }
     }
 }
 
+fn default_input_idle_secs() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: url::Url,
+    /// File holding the key deliveries are signed with (HMAC-SHA256,
+    /// `X-Mows-Signature`). Unsigned when unset.
+    #[serde(default)]
+    pub secret_file: Option<PathBuf>,
+    /// Which notifications to deliver; all of them when empty.
+    #[serde(default)]
+    pub events: Vec<NotificationKind>,
+    /// Contents of `secret_file`, read at load time.
+    #[serde(skip)]
+    pub secret: Option<WebhookSecret>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct PortRange {
@@ -124,6 +371,13 @@ pub struct PortRange {
     pub end: u16,
 }
 
+fn default_forward_port_range() -> PortRange {
+    PortRange {
+        start: 23000,
+        end: 23999,
+    }
+}
+
 fn default_state_dir() -> PathBuf {
     PathBuf::from("/var/lib/mows-agent")
 }
@@ -142,9 +396,20 @@ fn default_external_host() -> String {
 fn default_guest_ssh_user() -> String {
     "root".to_string()
 }
+fn default_idle_timeout_secs() -> u64 {
+    60 * 60
+}
 fn default_qemu_binary() -> String {
     "qemu-system-x86_64".to_string()
 }
+fn default_image() -> String {
+    "alpine".into()
+}
+
+fn default_cloud_init_iso_binary() -> String {
+    "xorrisofs".into()
+}
+
 fn default_port_range() -> PortRange {
     PortRange {
         start: 22000,
@@ -162,11 +427,89 @@ impl SupervisorConfig {
             ))
         })?;
         let mut config: Self = serde_yaml_neo::from_str(&raw)?;
+        config.default_network_policy.compile().map_err(|e| {
+            SupervisorError::Config(format!("default_network_policy: {e}"))
+        })?;
+        for (name, image) in &config.images {
+            image.validate(name)?;
+        }
+        let image_known = |name: &str| crate::images::resolve(&config.images, name).is_ok();
+        if !image_known(&config.vm_defaults.image) {
+            return Err(SupervisorError::Config(format!(
+                "vm_defaults.image: unknown image {:?}",
+                config.vm_defaults.image
+            )));
+        }
+        for (name, preset) in &config.presets {
+            preset.validate(name)?;
+            if let Some(image) = preset.image.as_deref().filter(|image| !image_known(image)) {
+                return Err(SupervisorError::Config(format!(
+                    "presets.{name}.image: unknown image {image:?}"
+                )));
+            }
+        }
+        for (key, range) in [
+            ("port_range", &config.port_range),
+            ("forward_port_range", &config.forward_port_range),
+        ] {
+            if range.start > range.end {
+                return Err(SupervisorError::Config(format!(
+                    "{key}: start {} is above end {}",
+                    range.start, range.end
+                )));
+            }
+        }
+        if config.forward_port_range.start <= config.port_range.end
+            && config.port_range.start <= config.forward_port_range.end
+        {
+            return Err(SupervisorError::Config(
+                "forward_port_range must not overlap port_range".into(),
+            ));
+        }
+        config.host_mounts.validate()?;
+        for webhook in &mut config.notifications.webhooks {
+            if !matches!(webhook.url.scheme(), "http" | "https") {
+                return Err(SupervisorError::Config(format!(
+                    "notifications.webhooks: {} is not an http(s) url",
+                    webhook.url
+                )));
+            }
+            webhook.secret = webhook
+                .secret_file
+                .as_deref()
+                .map(WebhookSecret::from_file)
+                .transpose()?;
+        }
+        for (kind, extras) in &config.agent_kinds {
+            extras.validate(kind)?;
+        }
+        if config.task_queue.max_concurrent == 0 {
+            return Err(SupervisorError::Config(
+                "task_queue.max_concurrent must be at least 1".into(),
+            ));
+        }
+        if config.idle_monitor.interval_secs == 0 {
+            return Err(SupervisorError::Config(
+                "idle_monitor.interval_secs must be at least 1".into(),
+            ));
+        }
+        let threshold = config.idle_monitor.cpu_threshold_percent;
+        if !threshold.is_finite() || threshold < 0.0 {
+            return Err(SupervisorError::Config(format!(
+                "idle_monitor.cpu_threshold_percent must be a non-negative number, got {threshold}"
+            )));
+        }
         config.api_token = read_secret(
             "MOWS_VM_SUPERVISOR_API_TOKEN",
             "MOWS_VM_SUPERVISOR_API_TOKEN_FILE",
         )?;
         config.auth_disabled = read_bool_env("MOWS_VM_SUPERVISOR_AUTH_DISABLE");
+        config.secret_key = config
+            .secrets
+            .master_key_file
+            .as_deref()
+            .map(SecretKey::from_file)
+            .transpose()?;
         config.agent_host_creds_path = resolve_agent_host_creds_path();
         Ok(config)
     }
@@ -183,10 +526,23 @@ impl SupervisorConfig {
             guest_ssh_user: default_guest_ssh_user(),
             https_listen: None,
             vm_defaults: VmDefaults::default(),
+            images: BTreeMap::new(),
+            presets: BTreeMap::new(),
+            host_mounts: HostMountConfig::default(),
+            agent_kinds: BTreeMap::new(),
+            secrets: SecretStoreConfig::default(),
             qemu_binary: default_qemu_binary(),
+            cloud_init_iso_binary: default_cloud_init_iso_binary(),
             port_range: default_port_range(),
+            forward_port_range: default_forward_port_range(),
+            default_network_policy: NetworkPolicy::default(),
+            task_queue: TaskQueueConfig::default(),
+            recordings: RecordingConfig::default(),
+            idle_monitor: IdleMonitorConfig::default(),
+            notifications: NotificationConfig::default(),
             api_token: None,
             auth_disabled: false,
+            secret_key: None,
             agent_host_creds_path: None,
         }
     }
@@ -210,10 +566,23 @@ impl SupervisorConfig {
             guest_ssh_user: default_guest_ssh_user(),
             https_listen: None,
             vm_defaults: VmDefaults::default(),
+            images: BTreeMap::new(),
+            presets: BTreeMap::new(),
+            host_mounts: HostMountConfig::default(),
+            agent_kinds: BTreeMap::new(),
+            secrets: SecretStoreConfig::default(),
             qemu_binary: default_qemu_binary(),
+            cloud_init_iso_binary: default_cloud_init_iso_binary(),
             port_range: default_port_range(),
+            forward_port_range: default_forward_port_range(),
+            default_network_policy: NetworkPolicy::default(),
+            task_queue: TaskQueueConfig::default(),
+            recordings: RecordingConfig::default(),
+            idle_monitor: IdleMonitorConfig::default(),
+            notifications: NotificationConfig::default(),
             api_token: None,
             auth_disabled: false,
+            secret_key: None,
             agent_host_creds_path: None,
         }
     }
@@ -0,0 +1,763 @@
+//! Per-VM network egress policy.
+//!
+//! `open` VMs keep the unrestricted user-mode netdev. For `deny-all` and
+//! `allowlist`, `QemuInvocation::build` adds `restrict=on` (slirp drops
+//! every guest-initiated connection, explicit forwards keep working) plus
+//! a `guestfwd` that maps `GUEST_EGRESS_PROXY_ADDR` to a per-VM unix
+//! socket. The supervisor serves an HTTP proxy on that socket which only
+//! lets allowlisted destinations through:
+//!
+//! - `CONNECT host:port` (HTTPS and anything else tunnelled) and
+//!   absolute-form `GET http://host/…` requests are accepted; everything
+//!   else gets a 400.
+//! - Names are resolved on the host, and only after the name itself has
+//!   been allowed, so the guest never needs DNS. (It has none anyway:
+//!   `restrict=on` also drops UDP to the slirp resolver.)
+//! - Allowlisted names that resolve into loopback, link-local or private
+//!   ranges are refused unless that range is allowlisted explicitly as a
+//!   CIDR — otherwise a DNS record under an allowed domain could point the
+//!   proxy at the supervisor's own network.
+//! - Every refused attempt is appended as one JSON line to
+//!   `<vm_dir>/egress-blocked.jsonl` and served by
+//!   `GET /v1/vms/{id}/egress-log`.
+//!
+//! `deny-all` is the proxy with an empty allowlist, so attempts are still
+//! logged rather than silently timing out.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+use utoipa::ToSchema;
+
+use crate::error::{Result, SupervisorError};
+
+/// Guest-side address of the egress proxy. `10.0.2.100` is unused by
+/// slirp's defaults (gateway `.2`, DNS `.3`, DHCP from `.15`).
+pub const GUEST_EGRESS_PROXY_ADDR: &str = "10.0.2.100:3128";
+
+/// Largest request head (request line + headers) the proxy buffers before
+/// giving up on a client.
+const MAX_REQUEST_HEAD_BYTES: usize = 16 * 1024;
+
+const UPSTREAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The blocked-attempt log stops growing past this size; a guest looping
+/// on a denied host must not be able to fill the state volume.
+const MAX_BLOCKED_LOG_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Ranges an allowlisted *domain* may not resolve into. An explicit
+/// `allow_cidrs` entry covering the address still wins.
+const INTERNAL_RANGES: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "::/128",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+];
+
+#[derive(
+    Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum NetworkPolicyMode {
+    /// Unrestricted user-mode networking.
+    #[default]
+    Open,
+    /// No outbound connections at all; attempts through the proxy are logged.
+    DenyAll,
+    /// Only `allow_domains` / `allow_cidrs`, via the supervisor's proxy.
+    Allowlist,
+}
+
+impl NetworkPolicyMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::DenyAll => "deny-all",
+            Self::Allowlist => "allowlist",
+        }
+    }
+}
+
+/// Outbound network policy of a VM. Persisted as JSON in
+/// `vms.network_policy`.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkPolicy {
+    #[serde(default)]
+    pub mode: NetworkPolicyMode,
+    /// `example.com` matches exactly that host; `*.example.com` matches
+    /// any subdomain (but not the apex). Only valid with `allowlist`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_domains: Vec<String>,
+    /// `10.1.0.0/16`, `2001:db8::/32`, or a bare address. Only valid with
+    /// `allowlist`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_cidrs: Vec<String>,
+}
+
+impl NetworkPolicy {
+    pub fn is_open(&self) -> bool {
+        self.mode == NetworkPolicyMode::Open
+    }
+
+    /// Validate the policy and compile it into matchable rules.
+    pub fn compile(&self) -> Result<EgressRules> {
+        if self.mode != NetworkPolicyMode::Allowlist
+            && !(self.allow_domains.is_empty() && self.allow_cidrs.is_empty())
+        {
+            return Err(SupervisorError::BadRequest(format!(
+                "network_policy mode `{}` does not take allow_domains / allow_cidrs",
+                self.mode.as_str()
+            )));
+        }
+        let domains = self
+            .allow_domains
+            .iter()
+            .map(|raw| DomainRule::parse(raw))
+            .collect::<Result<Vec<_>>>()?;
+        let cidrs = self
+            .allow_cidrs
+            .iter()
+            .map(|raw| Cidr::parse(raw))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(EgressRules { domains, cidrs })
+    }
+}
+
+/// Environment pointing HTTP clients in the guest at the egress proxy.
+pub fn guest_proxy_env() -> BTreeMap<String, String> {
+    let url = format!("http://{GUEST_EGRESS_PROXY_ADDR}");
+    let no_proxy = "localhost,127.0.0.1,::1".to_string();
+    BTreeMap::from([
+        ("http_proxy".to_string(), url.clone()),
+        ("https_proxy".to_string(), url.clone()),
+        ("HTTP_PROXY".to_string(), url.clone()),
+        ("HTTPS_PROXY".to_string(), url),
+        ("no_proxy".to_string(), no_proxy.clone()),
+        ("NO_PROXY".to_string(), no_proxy),
+    ])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DomainRule {
+    Exact(String),
+    /// Stored with the leading dot, e.g. `.example.com`.
+    Subdomains(String),
+}
+
+impl DomainRule {
+    fn parse(raw: &str) -> Result<Self> {
+        let lowered = raw.trim().trim_end_matches('.').to_ascii_lowercase();
+        let (wildcard, name) = match lowered.strip_prefix("*.") {
+            Some(rest) => (true, rest.to_string()),
+            None => (false, lowered),
+        };
+        if !is_valid_hostname(&name) {
+            return Err(SupervisorError::BadRequest(format!(
+                "allow_domains entry {raw:?} is not a hostname (use `example.com` or `*.example.com`)"
+            )));
+        }
+        Ok(if wildcard {
+            Self::Subdomains(format!(".{name}"))
+        } else {
+            Self::Exact(name)
+        })
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            Self::Exact(name) => host == name,
+            Self::Subdomains(suffix) => host.len() > suffix.len() && host.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
+/// An IPv4 or IPv6 network in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let invalid =
+            || SupervisorError::BadRequest(format!("allow_cidrs entry {raw:?} is not a CIDR"));
+        let (addr, prefix) = match raw.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (raw.trim(), None),
+        };
+        let network: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| invalid())?,
+            None => max_prefix,
+        };
+        if prefix > max_prefix {
+            return Err(invalid());
+        }
+        Ok(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_internal(ip: IpAddr) -> bool {
+    INTERNAL_RANGES.iter().any(|range| {
+        Cidr::parse(range)
+            .map(|cidr| cidr.contains(ip))
+            .unwrap_or(false)
+    })
+}
+
+/// Compiled form of an `allowlist` / `deny-all` policy.
+#[derive(Debug, Clone)]
+pub struct EgressRules {
+    domains: Vec<DomainRule>,
+    cidrs: Vec<Cidr>,
+}
+
+impl EgressRules {
+    fn allows_domain(&self, host: &str) -> bool {
+        self.domains.iter().any(|rule| rule.matches(host))
+    }
+
+    fn allows_cidr(&self, ip: IpAddr) -> bool {
+        self.cidrs.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// Narrow the addresses `host` resolved to down to the ones the policy
+    /// permits. Empty when the connection must be refused.
+    fn permitted_addresses(&self, host: &str, resolved: &[IpAddr]) -> Vec<IpAddr> {
+        let domain_allowed = self.allows_domain(host);
+        resolved
+            .iter()
+            .copied()
+            .filter(|ip| self.allows_cidr(*ip) || (domain_allowed && !is_internal(*ip)))
+            .collect()
+    }
+
+    /// Decide whether the guest may reach `host:port`, resolving the name
+    /// on the host. `Err` carries the human-readable block reason.
+    async fn resolve(&self, host: &str, port: u16) -> std::result::Result<Vec<SocketAddr>, String> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return if self.allows_cidr(ip) {
+                Ok(vec![SocketAddr::new(ip, port)])
+            } else {
+                Err("address not in allow_cidrs".into())
+            };
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if !is_valid_hostname(&host) {
+            return Err("malformed host".into());
+        }
+        // Don't even resolve names that can't pass: a CIDR-only allowlist
+        // is the one case where an unlisted name may still be let through.
+        if !self.allows_domain(&host) && self.cidrs.is_empty() {
+            return Err("domain not in allow_domains".into());
+        }
+        let resolved: Vec<IpAddr> = tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .map_err(|e| format!("dns lookup failed: {e}"))?
+            .map(|addr| addr.ip())
+            .collect();
+        let permitted = self.permitted_addresses(&host, &resolved);
+        if permitted.is_empty() {
+            return Err(if self.allows_domain(&host) {
+                "domain resolves only to internal addresses".into()
+            } else {
+                "domain not in allow_domains and no address in allow_cidrs".into()
+            });
+        }
+        Ok(permitted.into_iter().map(|ip| SocketAddr::new(ip, port)).collect())
+    }
+}
+
+/// One refused connection attempt, as stored in `egress-blocked.jsonl`.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct BlockedEgressAttempt {
+    /// RFC 3339 timestamp.
+    pub at: String,
+    pub host: String,
+    pub port: u16,
+    pub reason: String,
+}
+
+pub fn egress_socket_for(vm_dir: &Path) -> PathBuf {
+    vm_dir.join("egress.sock")
+}
+
+pub fn blocked_log_for(vm_dir: &Path) -> PathBuf {
+    vm_dir.join("egress-blocked.jsonl")
+}
+
+/// Read the newest `limit` blocked attempts, oldest first. A missing log
+/// (nothing blocked yet, or an `open` VM) is an empty list.
+pub async fn read_blocked_log(vm_dir: &Path, limit: usize) -> Result<Vec<BlockedEgressAttempt>> {
+    let raw = match tokio::fs::read_to_string(blocked_log_for(vm_dir)).await {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries: Vec<BlockedEgressAttempt> = raw
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.split_off(skip))
+}
+
+/// Running egress proxy of one VM. Dropping it stops accepting new guest
+/// connections; tunnels already established finish on their own.
+pub struct EgressProxy {
+    task: JoinHandle<()>,
+    traffic: Arc<EgressTraffic>,
+}
+
+/// Bytes one VM's egress proxy relayed, as seen from the guest. A
+/// connection is counted when it closes, so a long download shows up at
+/// its end. Slirp (`-netdev user`) keeps no per-VM counters, so VMs with
+/// an `open` network policy have no traffic figures at all.
+#[derive(Debug, Default)]
+pub struct EgressTraffic {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl EgressTraffic {
+    fn record(&self, sent: u64, received: u64) {
+        self.sent.fetch_add(sent, Ordering::Relaxed);
+        self.received.fetch_add(received, Ordering::Relaxed);
+    }
+
+    /// `(sent, received)` so far.
+    pub fn totals(&self) -> (u64, u64) {
+        (
+            self.sent.load(Ordering::Relaxed),
+            self.received.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl Drop for EgressProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Bind `<vm_dir>/egress.sock` and serve the proxy for `vm_id`. Must run
+/// before QEMU is spawned so the first guestfwd connection finds a
+/// listener.
+pub fn spawn_egress_proxy(vm_id: &str, vm_dir: &Path, rules: EgressRules) -> Result<EgressProxy> {
+    let socket_path = egress_socket_for(vm_dir);
+    match std::fs::remove_file(&socket_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let listener = UnixListener::bind(&socket_path)?;
+    let traffic = Arc::new(EgressTraffic::default());
+    let context = Arc::new(ProxyContext {
+        vm_id: vm_id.to_string(),
+        log_path: blocked_log_for(vm_dir),
+        rules,
+        traffic: traffic.clone(),
+    });
+    let task = tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!(vm_id = %context.vm_id, error = %e, "egress proxy accept failed");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let context = context.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, &context).await {
+                    tracing::debug!(vm_id = %context.vm_id, error = %e, "egress proxy connection ended");
+                }
+            });
+        }
+    });
+    Ok(EgressProxy { task, traffic })
+}
+
+/// Live egress proxies keyed by VM id.
+#[derive(Default)]
+pub struct EgressProxyRegistry {
+    proxies: Mutex<HashMap<String, EgressProxy>>,
+}
+
+impl EgressProxyRegistry {
+    pub fn insert(&self, vm_id: String, proxy: EgressProxy) {
+        self.proxies
+            .lock()
+            .expect("egress proxy registry mutex poisoned")
+            .insert(vm_id, proxy);
+    }
+
+    /// `(sent, received)` bytes of `vm_id`'s proxy; `None` without one.
+    pub fn traffic(&self, vm_id: &str) -> Option<(u64, u64)> {
+        self.proxies
+            .lock()
+            .expect("egress proxy registry mutex poisoned")
+            .get(vm_id)
+            .map(|proxy| proxy.traffic.totals())
+    }
+
+    /// Stop the proxy of `vm_id`, if any.
+    pub fn remove(&self, vm_id: &str) {
+        let proxy = self
+            .proxies
+            .lock()
+            .expect("egress proxy registry mutex poisoned")
+            .remove(vm_id);
+        drop(proxy);
+    }
+}
+
+struct ProxyContext {
+    vm_id: String,
+    log_path: PathBuf,
+    rules: EgressRules,
+    traffic: Arc<EgressTraffic>,
+}
+
+impl ProxyContext {
+    async fn record_blocked(&self, host: &str, port: u16, reason: &str) {
+        tracing::info!(vm_id = %self.vm_id, host, port, reason, "egress blocked");
+        let too_large = tokio::fs::metadata(&self.log_path)
+            .await
+            .map(|meta| meta.len() >= MAX_BLOCKED_LOG_BYTES)
+            .unwrap_or(false);
+        if too_large {
+            return;
+        }
+        let entry = BlockedEgressAttempt {
+            at: Utc::now().to_rfc3339(),
+            host: host.to_string(),
+            port,
+            reason: reason.to_string(),
+        };
+        let Ok(mut line) = serde_json::to_vec(&entry) else {
+            return;
+        };
+        line.push(b'\n');
+        let written = async {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.log_path)
+                .await?;
+            file.write_all(&line).await
+        };
+        if let Err(e) = written.await {
+            tracing::warn!(vm_id = %self.vm_id, error = %e, "failed to append egress block log");
+        }
+    }
+}
+
+/// Parsed proxy request line.
+#[derive(Debug, PartialEq, Eq)]
+struct ProxyTarget {
+    host: String,
+    port: u16,
+    /// `CONNECT` tunnels answer `200` before relaying; plain HTTP forwards
+    /// the buffered request head instead.
+    tunnel: bool,
+}
+
+fn parse_request_line(head: &str) -> std::result::Result<ProxyTarget, &'static str> {
+    let line = head.lines().next().unwrap_or_default();
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line");
+    };
+    if method.eq_ignore_ascii_case("CONNECT") {
+        let (host, port) = split_authority(target, None).ok_or("malformed CONNECT target")?;
+        return Ok(ProxyTarget { host, port, tunnel: true });
+    }
+    let scheme_len = "http://".len();
+    let is_http = target
+        .get(..scheme_len)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"));
+    let rest = target
+        .get(scheme_len..)
+        .filter(|_| is_http)
+        .ok_or("only CONNECT and absolute http:// requests are proxied")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let (host, port) = split_authority(authority, Some(80)).ok_or("malformed request target")?;
+    Ok(ProxyTarget { host, port, tunnel: false })
+}
+
+/// Split `host[:port]` / `[v6]:port`, dropping any `user@` prefix.
+fn split_authority(authority: &str, default_port: Option<u16>) -> Option<(String, u16)> {
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let (host, rest) = bracketed.split_once(']')?;
+        (host, rest.strip_prefix(':'))
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => default_port?,
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_ascii_lowercase(), port))
+}
+
+async fn respond(stream: &mut UnixStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+async fn handle_connection(mut client: UnixStream, context: &ProxyContext) -> std::io::Result<()> {
+    let mut buffer = Vec::with_capacity(4096);
+    let head_len = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if buffer.len() >= MAX_REQUEST_HEAD_BYTES {
+            return respond(&mut client, "431 Request Header Fields Too Large", "").await;
+        }
+        let mut chunk = [0u8; 4096];
+        let read = client.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        buffer.extend_from_slice(chunk.get(..read).unwrap_or_default());
+    };
+    let head = String::from_utf8_lossy(buffer.get(..head_len).unwrap_or_default()).into_owned();
+
+    let target = match parse_request_line(&head) {
+        Ok(target) => target,
+        Err(reason) => return respond(&mut client, "400 Bad Request", reason).await,
+    };
+    let addrs = match context.rules.resolve(&target.host, target.port).await {
+        Ok(addrs) => addrs,
+        Err(reason) => {
+            context.record_blocked(&target.host, target.port, &reason).await;
+            let body = format!(
+                "mows egress policy blocked {}:{}: {reason}\n",
+                target.host, target.port
+            );
+            return respond(&mut client, "403 Forbidden", &body).await;
+        }
+    };
+    let mut upstream =
+        match tokio::time::timeout(UPSTREAM_CONNECT_TIMEOUT, TcpStream::connect(addrs.as_slice()))
+            .await
+        {
+            Ok(Ok(upstream)) => upstream,
+            Ok(Err(e)) => {
+                return respond(&mut client, "502 Bad Gateway", &format!("{e}\n")).await;
+            }
+            Err(_) => return respond(&mut client, "504 Gateway Timeout", "").await,
+        };
+
+    let forwarded = if target.tunnel {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+        let early = buffer.get(head_len..).unwrap_or_default();
+        upstream.write_all(early).await?;
+        early.len()
+    } else {
+        upstream.write_all(&buffer).await?;
+        buffer.len()
+    };
+    let (sent, received) = tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    let forwarded = u64::try_from(forwarded).unwrap_or(u64::MAX);
+    context.traffic.record(sent.saturating_add(forwarded), received);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(domains: &[&str], cidrs: &[&str]) -> NetworkPolicy {
+        NetworkPolicy {
+            mode: NetworkPolicyMode::Allowlist,
+            allow_domains: domains.iter().map(|s| s.to_string()).collect(),
+            allow_cidrs: cidrs.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn ip(raw: &str) -> IpAddr {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn policy_round_trips_through_json_with_kebab_case_mode() {
+        let policy: NetworkPolicy = serde_json::from_str(r#"{"mode":"deny-all"}"#).unwrap();
+        assert_eq!(policy.mode, NetworkPolicyMode::DenyAll);
+        assert_eq!(serde_json::to_string(&NetworkPolicy::default()).unwrap(), r#"{"mode":"open"}"#);
+    }
+
+    #[test]
+    fn compile_rejects_lists_outside_allowlist_mode() {
+        let policy = NetworkPolicy {
+            mode: NetworkPolicyMode::DenyAll,
+            allow_domains: vec!["example.com".into()],
+            allow_cidrs: Vec::new(),
+        };
+        assert!(matches!(policy.compile(), Err(SupervisorError::BadRequest(_))));
+    }
+
+    #[test]
+    fn compile_rejects_malformed_entries() {
+        assert!(allowlist(&["exa mple.com"], &[]).compile().is_err());
+        assert!(allowlist(&["*"], &[]).compile().is_err());
+        assert!(allowlist(&[], &["10.0.0.0/33"]).compile().is_err());
+        assert!(allowlist(&[], &["not-an-ip/8"]).compile().is_err());
+    }
+
+    #[test]
+    fn cidr_contains_v4_v6_and_mapped_addresses() {
+        let net = Cidr::parse("10.1.0.0/16").unwrap();
+        assert!(net.contains(ip("10.1.200.3")));
+        assert!(!net.contains(ip("10.2.0.1")));
+        assert!(net.contains(ip("::ffff:10.1.0.9")));
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(Cidr::parse("2001:db8::/32").unwrap().contains(ip("2001:db8:1::1")));
+        assert!(!Cidr::parse("2001:db8::/32").unwrap().contains(ip("10.1.0.1")));
+        assert!(Cidr::parse("192.0.2.7").unwrap().contains(ip("192.0.2.7")));
+    }
+
+    #[test]
+    fn domain_rules_match_exact_and_wildcard_subdomains() {
+        let rules = allowlist(&["Example.com.", "*.crates.io"], &[]).compile().unwrap();
+        assert!(rules.allows_domain("example.com"));
+        assert!(!rules.allows_domain("www.example.com"));
+        assert!(rules.allows_domain("static.crates.io"));
+        assert!(!rules.allows_domain("crates.io"));
+        assert!(!rules.allows_domain("evilcrates.io"));
+    }
+
+    #[test]
+    fn allowed_domain_may_not_resolve_into_internal_ranges() {
+        let rules = allowlist(&["example.com"], &["10.5.0.0/16"]).compile().unwrap();
+        let resolved = [ip("93.184.216.34"), ip("127.0.0.1"), ip("10.5.1.1"), ip("192.168.1.1")];
+        assert_eq!(
+            rules.permitted_addresses("example.com", &resolved),
+            vec![ip("93.184.216.34"), ip("10.5.1.1")]
+        );
+        assert_eq!(rules.permitted_addresses("other.org", &resolved), vec![ip("10.5.1.1")]);
+    }
+
+    #[tokio::test]
+    async fn deny_all_refuses_literals_and_names_without_resolving() {
+        let rules = NetworkPolicy {
+            mode: NetworkPolicyMode::DenyAll,
+            ..NetworkPolicy::default()
+        }
+        .compile()
+        .unwrap();
+        assert!(rules.resolve("1.1.1.1", 443).await.is_err());
+        let reason = rules.resolve("example.com", 443).await.unwrap_err();
+        assert!(reason.contains("allow_domains"), "{reason}");
+    }
+
+    #[test]
+    fn parses_connect_and_absolute_form_targets() {
+        assert_eq!(
+            parse_request_line("CONNECT github.com:443 HTTP/1.1\r\n\r\n").unwrap(),
+            ProxyTarget { host: "github.com".into(), port: 443, tunnel: true }
+        );
+        assert_eq!(
+            parse_request_line("GET http://Deb.Debian.org/debian/ HTTP/1.1\r\n").unwrap(),
+            ProxyTarget { host: "deb.debian.org".into(), port: 80, tunnel: false }
+        );
+        assert_eq!(
+            parse_request_line("CONNECT [2001:db8::1]:8443 HTTP/1.1\r\n").unwrap(),
+            ProxyTarget { host: "2001:db8::1".into(), port: 8443, tunnel: true }
+        );
+        assert!(parse_request_line("GET /index.html HTTP/1.1\r\n").is_err());
+        assert!(parse_request_line("CONNECT github.com HTTP/1.1\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn proxy_blocks_and_logs_denied_connect() {
+        let dir = tempfile::tempdir().unwrap();
+        let rules = allowlist(&["example.com"], &[]).compile().unwrap();
+        let _proxy = spawn_egress_proxy("vm-1", dir.path(), rules).unwrap();
+
+        let mut stream = UnixStream::connect(egress_socket_for(dir.path())).await.unwrap();
+        stream
+            .write_all(b"CONNECT 203.0.113.9:22 HTTP/1.1\r\nHost: 203.0.113.9:22\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+
+        let log = read_blocked_log(dir.path(), 10).await.unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log.first().map(|e| (e.host.as_str(), e.port)), Some(("203.0.113.9", 22)));
+    }
+
+    #[tokio::test]
+    async fn blocked_log_returns_newest_entries_and_tolerates_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_blocked_log(dir.path(), 5).await.unwrap().is_empty());
+        let lines: String = (0..4)
+            .map(|i| format!("{{\"at\":\"t{i}\",\"host\":\"h{i}\",\"port\":80,\"reason\":\"r\"}}\n"))
+            .collect();
+        std::fs::write(blocked_log_for(dir.path()), lines).unwrap();
+        let tail = read_blocked_log(dir.path(), 2).await.unwrap();
+        let hosts: Vec<&str> = tail.iter().map(|e| e.host.as_str()).collect();
+        assert_eq!(hosts, vec!["h2", "h3"]);
+    }
+}
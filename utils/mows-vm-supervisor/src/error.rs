@@ -59,6 +59,12 @@ pub enum SupervisorError {
     #[error("invalid state: {0}")]
     InvalidState(String),
 
+    /// Creating the VM would take its owner over their per-user quota
+    /// (`users.max_vms` / `max_vcpus` / `max_memory_mb`). 403 with the
+    /// limit in the body so the caller knows what to stop.
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
+
     /// External `ssh` / `ssh-keygen` / `tmux`-over-ssh process failure.
     /// Mapped to 500 with a redacted public message ("upstream ssh
     /// failed") so the caller doesn't see raw stderr.
@@ -77,6 +83,18 @@ pub enum SupervisorError {
     #[error("vm boot timeout: {0}")]
     VmBootTimeout(String),
 
+    /// QMP monitor round-trip failed (socket missing, QEMU rejected the
+    /// command, timeout). Monitor output can carry host paths, so the
+    /// public body is redacted like `SshFailed`.
+    #[error("qmp: {0}")]
+    Qmp(String),
+
+    /// Host-side `git` invocation for a `worktree` workspace failed.
+    /// stderr names repository paths, so the public body is redacted
+    /// like `SshFailed`.
+    #[error("git: {0}")]
+    Git(String),
+
     /// Local filesystem op failed with a path-bearing message. Keeps
     /// the path out of the public response body (so absolute paths
     /// don't leak to API clients) while making the operator log
@@ -107,6 +125,7 @@ impl IntoResponse for SupervisorError {
             Self::Forbidden => (StatusCode::FORBIDDEN, "forbidden".to_string()),
             Self::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             Self::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            Self::QuotaExceeded(msg) => (StatusCode::FORBIDDEN, format!("quota exceeded: {msg}")),
             Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
             Self::KvmUnavailable(msg) => (
                 StatusCode::SERVICE_UNAVAILABLE,
@@ -143,6 +162,20 @@ impl IntoResponse for SupervisorError {
                     "upstream ssh failed".to_string(),
                 )
             }
+            Self::Qmp(msg) => {
+                tracing::error!(error = %msg, "qmp failure");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "qemu monitor command failed".to_string(),
+                )
+            }
+            Self::Git(msg) => {
+                tracing::error!(error = %msg, "git failure");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "git command failed".to_string(),
+                )
+            }
             Self::PortExhausted(msg) => (
                 StatusCode::SERVICE_UNAVAILABLE,
                 format!("port range exhausted: {msg}"),
@@ -31,6 +31,27 @@ pub enum SupervisorEvent {
     AgentCreated { id: String, vm_id: String },
     AgentUpdated { id: String },
     AgentDeleted { id: String },
+    /// The agent's process is gone: its tmux session ended, it was
+    /// stopped, or its VM went down. `status` is the row's final status.
+    AgentExited {
+        id: String,
+        vm_id: String,
+        status: String,
+        exit_code: Option<i64>,
+    },
+    /// The agent's screen has been still at one of its kind's
+    /// `input_prompts` for `notifications.input_idle_secs`. Leaving that
+    /// state is reported as a plain `AgentUpdated`.
+    AgentWaitingForInput { id: String, vm_id: String },
+    SnapshotCreated { id: String, vm_id: String },
+    /// The VM's disk was rolled back to snapshot `id`. Subscribers showing guest state should
+    /// treat this like a reboot.
+    SnapshotRestored { id: String, vm_id: String },
+    SnapshotDeleted { id: String, vm_id: String },
+    TaskCreated { id: String },
+    /// Status, VM, or result of headless task `id` changed.
+    TaskUpdated { id: String },
+    TaskDeleted { id: String },
     /// Sent by the WS forwarder when the broadcast channel lagged and the
     /// subscriber may have missed events. Clients should treat this as a
     /// hint to re-fetch any data they depend on.
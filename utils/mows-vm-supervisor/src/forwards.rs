@@ -0,0 +1,147 @@
+//! Runtime guest → host port forwards (`/v1/vms/{id}/forwards`,
+//! `mows vms forward`).
+//!
+//! The ssh and docker forwards are baked into the `-netdev user,…` line
+//! at boot. Everything else — a dev server on guest port 3000, say — is
+//! added to the running QEMU with the HMP `hostfwd_add` command (sent
+//! through QMP's `human-monitor-command`) and removed again with
+//! `hostfwd_remove`. Host ports come from `forward_port_range`, a pool of
+//! its own (`AppState::forward_ports`) so forwards can't starve VM boots
+//! of ssh/docker ports. Like those, the host side binds `127.0.0.1` only.
+//!
+//! Every rule is recorded in `vm_forwards`; `crate::recovery` reads the
+//! table after a supervisor restart, and stopping or deleting a VM drops
+//! its rows and returns their ports.
+
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use crate::error::{Result, SupervisorError};
+use crate::qmp::QmpClient;
+
+/// Netdev the rules are added to; see `QemuInvocation::build`.
+const NETDEV_ID: &str = "net0";
+
+/// One forward: `127.0.0.1:host_port` on the host reaches `guest_port`
+/// inside the VM.
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone, Debug, PartialEq, Eq)]
+pub struct VmForward {
+    pub vm_id: String,
+    pub guest_port: u16,
+    pub host_port: u16,
+    pub created_at: String,
+}
+
+const FORWARD_COLUMNS: &str = "vm_id, guest_port, host_port, created_at";
+
+/// `hostfwd_add` argument for a TCP forward to `guest_port`.
+fn rule(host_port: u16, guest_port: u16) -> String {
+    format!("tcp:127.0.0.1:{host_port}-:{guest_port}")
+}
+
+/// Add the rule to the QEMU behind `qmp_socket`.
+pub async fn add_rule(qmp_socket: &Path, host_port: u16, guest_port: u16) -> Result<()> {
+    let mut qmp = QmpClient::connect(qmp_socket).await?;
+    qmp.human_monitor_command_checked(&format!(
+        "hostfwd_add {NETDEV_ID} {}",
+        rule(host_port, guest_port)
+    ))
+    .await
+}
+
+/// Remove the rule listening on `host_port`.
+pub async fn remove_rule(qmp_socket: &Path, host_port: u16) -> Result<()> {
+    let mut qmp = QmpClient::connect(qmp_socket).await?;
+    qmp.human_monitor_command_checked(&format!(
+        "hostfwd_remove {NETDEV_ID} tcp:127.0.0.1:{host_port}"
+    ))
+    .await
+}
+
+pub async fn list(db: &SqlitePool, vm_id: &str) -> Result<Vec<VmForward>> {
+    let sql = format!("SELECT {FORWARD_COLUMNS} FROM vm_forwards WHERE vm_id = ?1 ORDER BY guest_port");
+    Ok(sqlx::query_as(&sql).bind(vm_id).fetch_all(db).await?)
+}
+
+pub async fn find(db: &SqlitePool, vm_id: &str, guest_port: u16) -> Result<Option<VmForward>> {
+    let sql =
+        format!("SELECT {FORWARD_COLUMNS} FROM vm_forwards WHERE vm_id = ?1 AND guest_port = ?2");
+    Ok(sqlx::query_as(&sql)
+        .bind(vm_id)
+        .bind(guest_port)
+        .fetch_optional(db)
+        .await?)
+}
+
+pub async fn insert(
+    db: &SqlitePool,
+    vm_id: &str,
+    guest_port: u16,
+    host_port: u16,
+) -> Result<VmForward> {
+    let forward = VmForward {
+        vm_id: vm_id.to_string(),
+        guest_port,
+        host_port,
+        created_at: Utc::now().to_rfc3339(),
+    };
+    // Only while the VM is still running: a concurrent stop has already
+    // run `take_for_vm`, and a row inserted after it would outlive the rule.
+    let inserted = sqlx::query(
+        "INSERT INTO vm_forwards (vm_id, guest_port, host_port, created_at) \
+         SELECT ?1, ?2, ?3, ?4 WHERE EXISTS \
+         (SELECT 1 FROM vms WHERE id = ?1 AND status = 'running')",
+    )
+    .bind(&forward.vm_id)
+    .bind(forward.guest_port)
+    .bind(forward.host_port)
+    .bind(&forward.created_at)
+    .execute(db)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            SupervisorError::Conflict(format!(
+                "guest port {guest_port} of vm {vm_id} is already forwarded"
+            ))
+        }
+        _ => e.into(),
+    })?;
+    if inserted.rows_affected() == 0 {
+        return Err(SupervisorError::Conflict(format!("vm {vm_id} is no longer running")));
+    }
+    Ok(forward)
+}
+
+pub async fn delete(db: &SqlitePool, vm_id: &str, guest_port: u16) -> Result<()> {
+    sqlx::query("DELETE FROM vm_forwards WHERE vm_id = ?1 AND guest_port = ?2")
+        .bind(vm_id)
+        .bind(guest_port)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Drop every forward of `vm_id` and return their host ports for the
+/// caller to release. The rules themselves die with the VM's QEMU.
+pub async fn take_for_vm(db: &SqlitePool, vm_id: &str) -> Result<Vec<u16>> {
+    Ok(
+        sqlx::query_scalar("DELETE FROM vm_forwards WHERE vm_id = ?1 RETURNING host_port")
+            .bind(vm_id)
+            .fetch_all(db)
+            .await?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_bind_loopback_only() {
+        assert_eq!(rule(23000, 3000), "tcp:127.0.0.1:23000-:3000");
+    }
+}
@@ -0,0 +1,334 @@
+//! Idle detection and lifetime limits for VMs.
+//!
+//! A forgotten VM holds its memory and its `PortAllocator` port pair until
+//! someone stops it. [`spawn_idle_monitor`] looks at every running VM each
+//! `idle_monitor.interval_secs` and
+//!
+//! - shuts it down once it has been up for `max_lifetime_secs`, suspended
+//!   or not;
+//! - applies its `idle_action` once it has been idle for
+//!   `idle_timeout_secs`.
+//!
+//! A VM is idle while no client is attached to it (ssh-io, console and
+//! display websockets — [`VmSshSessionRegistry::attach`]), none of its
+//! agents or tasks is running, and its QEMU process used less than
+//! `idle_monitor.cpu_threshold_percent` of a host core over the last
+//! interval. A plain `ssh` into the forwarded port (`mows vms attach`) is
+//! only visible through that CPU use.
+//!
+//! `suspend` pauses the guest with QMP `stop`: the host CPU is freed, the
+//! memory and ports stay allocated. [`resume_if_suspended`] — called on
+//! every attach path — continues it with QMP `cont`. Detached ssh-io
+//! sessions don't outlive a long suspension (their ssh keepalive gives
+//! up); the next attach starts a fresh one.
+//!
+//! [`VmSshSessionRegistry::attach`]: crate::ssh_sessions::VmSshSessionRegistry::attach
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use crate::api::vms::shut_down_vm;
+use crate::error::Result;
+use crate::events::SupervisorEvent;
+use crate::metrics::{cpu_percent, sample_cpu, CpuSample};
+use crate::qemu::qmp_socket_for;
+use crate::qmp::QmpClient;
+use crate::state::SharedState;
+
+/// What happens to a VM once it has been idle for its `idle_timeout_secs`.
+#[derive(
+    Serialize, Deserialize, ToSchema, sqlx::Type, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum IdleAction {
+    /// Pause the guest (QMP `stop`); it resumes on the next attach.
+    #[default]
+    Suspend,
+    /// Shut the VM down, as `POST /v1/vms/{id}/stop` does.
+    Stop,
+}
+
+impl IdleAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Suspend => "suspend",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+/// Limits of a running VM, as stored on its row.
+#[derive(sqlx::FromRow)]
+struct MonitoredVm {
+    id: String,
+    started_at: String,
+    idle_timeout_secs: Option<i64>,
+    max_lifetime_secs: Option<i64>,
+    idle_action: IdleAction,
+    suspended_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Suspend,
+    Stop,
+}
+
+/// Decide what to do with `vm`, which has been up for `age` and idle for
+/// `idle_for` (`None` while busy).
+fn verdict(vm: &MonitoredVm, age: Duration, idle_for: Option<Duration>) -> Option<Verdict> {
+    let limit = |secs: Option<i64>| {
+        secs.and_then(|secs| u64::try_from(secs).ok())
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs)
+    };
+    if limit(vm.max_lifetime_secs).is_some_and(|max| age >= max) {
+        return Some(Verdict::Stop);
+    }
+    if vm.suspended_at.is_some() {
+        return None;
+    }
+    let timeout = limit(vm.idle_timeout_secs)?;
+    match idle_for {
+        Some(idle) if idle >= timeout => Some(match vm.idle_action {
+            IdleAction::Suspend => Verdict::Suspend,
+            IdleAction::Stop => Verdict::Stop,
+        }),
+        _ => None,
+    }
+}
+
+/// An unknown CPU reading (first sample, unreadable `/proc`) counts as
+/// busy so a VM is never acted on without a measurement.
+fn is_idle(attached: usize, workloads: i64, cpu_percent: Option<f64>, threshold: f64) -> bool {
+    attached == 0 && workloads == 0 && cpu_percent.is_some_and(|cpu| cpu < threshold)
+}
+
+/// Agents and tasks running in `vm_id`.
+async fn active_workloads(db: &SqlitePool, vm_id: &str) -> Result<i64> {
+    Ok(sqlx::query_scalar(
+        "SELECT (SELECT COUNT(*) FROM agents \
+                 WHERE vm_id = ?1 AND status IN ('starting', 'running', 'stopping')) \
+              + (SELECT COUNT(*) FROM tasks WHERE vm_id = ?1 AND status = 'running')",
+    )
+    .bind(vm_id)
+    .fetch_one(db)
+    .await?)
+}
+
+/// What the monitor remembers about a VM between checks.
+#[derive(Default)]
+struct Tracked {
+    cpu: Option<CpuSample>,
+    idle_since: Option<Instant>,
+}
+
+/// Take a fresh reading of `vm_id` and return how long it has been idle.
+async fn measure_idle(
+    state: &SharedState,
+    vm_id: &str,
+    tracked: &mut Tracked,
+) -> Result<Option<Duration>> {
+    let pid = state.vms.read().await.pid(vm_id);
+    let sample = match pid {
+        Some(pid) => sample_cpu(pid).await,
+        None => None,
+    };
+    let cpu = match (tracked.cpu, sample) {
+        (Some(previous), Some(current)) => cpu_percent(previous, current),
+        _ => None,
+    };
+    tracked.cpu = sample;
+    let idle = is_idle(
+        state.ssh_sessions.attached_count(vm_id),
+        active_workloads(&state.db, vm_id).await?,
+        cpu,
+        state.config.idle_monitor.cpu_threshold_percent,
+    );
+    if !idle {
+        tracked.idle_since = None;
+        return Ok(None);
+    }
+    let since = *tracked.idle_since.get_or_insert_with(Instant::now);
+    Ok(Some(since.elapsed()))
+}
+
+async fn check_vms(state: &SharedState, tracked: &mut HashMap<String, Tracked>) -> Result<()> {
+    let vms: Vec<MonitoredVm> = sqlx::query_as(
+        "SELECT id, started_at, idle_timeout_secs, max_lifetime_secs, idle_action, suspended_at \
+         FROM vms WHERE status = 'running' \
+           AND (idle_timeout_secs IS NOT NULL OR max_lifetime_secs IS NOT NULL)",
+    )
+    .fetch_all(&state.db)
+    .await?;
+    tracked.retain(|id, _| vms.iter().any(|vm| &vm.id == id));
+
+    let now = Utc::now();
+    for vm in vms {
+        let age = DateTime::parse_from_rfc3339(&vm.started_at)
+            .ok()
+            .and_then(|started| (now - started.with_timezone(&Utc)).to_std().ok())
+            .unwrap_or_default();
+        let idle_for = if vm.suspended_at.is_some() {
+            tracked.remove(&vm.id);
+            None
+        } else {
+            let entry = tracked.entry(vm.id.clone()).or_default();
+            measure_idle(state, &vm.id, entry).await?
+        };
+        match verdict(&vm, age, idle_for) {
+            Some(Verdict::Stop) => {
+                tracked.remove(&vm.id);
+                tracing::info!(
+                    vm_id = %vm.id,
+                    age_secs = age.as_secs(),
+                    idle_secs = idle_for.map(|idle| idle.as_secs()),
+                    "stopping vm past its idle timeout or lifetime"
+                );
+                if let Err(e) = shut_down_vm(state, &vm.id).await {
+                    tracing::warn!(vm_id = %vm.id, error = %e, "failed to stop vm");
+                }
+            }
+            Some(Verdict::Suspend) => {
+                tracked.remove(&vm.id);
+                match suspend_vm(state, &vm.id).await {
+                    Ok(true) => tracing::info!(
+                        vm_id = %vm.id,
+                        idle_secs = idle_for.map(|idle| idle.as_secs()),
+                        "suspended idle vm"
+                    ),
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!(vm_id = %vm.id, error = %e, "failed to suspend vm"),
+                }
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Pause `vm_id` with QMP `stop`, unless a client attached or a workload
+/// started since the monitor looked. Returns whether it was suspended.
+async fn suspend_vm(state: &SharedState, vm_id: &str) -> Result<bool> {
+    let _power = state.vm_power.lock().await;
+    if state.ssh_sessions.attached_count(vm_id) > 0
+        || active_workloads(&state.db, vm_id).await? > 0
+    {
+        return Ok(false);
+    }
+    let mut qmp = QmpClient::connect(&qmp_socket_for(&state.config.state_dir, vm_id)).await?;
+    qmp.execute("stop", None).await?;
+    let updated = sqlx::query(
+        "UPDATE vms SET suspended_at = ?1 \
+         WHERE id = ?2 AND status = 'running' AND suspended_at IS NULL",
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(vm_id)
+    .execute(&state.db)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+    state.events.emit(SupervisorEvent::VmUpdated {
+        id: vm_id.to_string(),
+    });
+    Ok(true)
+}
+
+/// Continue `vm_id` if the idle monitor suspended it. Attach paths call
+/// this before they touch the guest; it is a no-op for any other VM.
+pub async fn resume_if_suspended(state: &SharedState, vm_id: &str) -> Result<()> {
+    let _power = state.vm_power.lock().await;
+    let suspended: Option<Option<String>> =
+        sqlx::query_scalar("SELECT suspended_at FROM vms WHERE id = ?1 AND status = 'running'")
+            .bind(vm_id)
+            .fetch_optional(&state.db)
+            .await?;
+    if !matches!(suspended, Some(Some(_))) {
+        return Ok(());
+    }
+    let mut qmp = QmpClient::connect(&qmp_socket_for(&state.config.state_dir, vm_id)).await?;
+    qmp.execute("cont", None).await?;
+    sqlx::query("UPDATE vms SET suspended_at = NULL WHERE id = ?1")
+        .bind(vm_id)
+        .execute(&state.db)
+        .await?;
+    tracing::info!(vm_id = %vm_id, "resumed suspended vm");
+    state.events.emit(SupervisorEvent::VmUpdated {
+        id: vm_id.to_string(),
+    });
+    Ok(())
+}
+
+/// Check VM idleness and lifetimes every `idle_monitor.interval_secs`.
+pub fn spawn_idle_monitor(state: SharedState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(state.config.idle_monitor.interval_secs);
+        let mut tracked: HashMap<String, Tracked> = HashMap::new();
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = check_vms(&state, &mut tracked).await {
+                tracing::warn!(error = %e, "idle check failed");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm(idle_timeout_secs: Option<i64>, max_lifetime_secs: Option<i64>) -> MonitoredVm {
+        MonitoredVm {
+            id: "vm".into(),
+            started_at: "2026-01-01T00:00:00Z".into(),
+            idle_timeout_secs,
+            max_lifetime_secs,
+            idle_action: IdleAction::Suspend,
+            suspended_at: None,
+        }
+    }
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn idle_timeout_applies_the_idle_action() {
+        let mut row = vm(Some(600), None);
+        assert_eq!(verdict(&row, MINUTE * 60, Some(MINUTE * 9)), None);
+        assert_eq!(verdict(&row, MINUTE * 60, None), None);
+        assert_eq!(verdict(&row, MINUTE * 60, Some(MINUTE * 10)), Some(Verdict::Suspend));
+        row.idle_action = IdleAction::Stop;
+        assert_eq!(verdict(&row, MINUTE * 60, Some(MINUTE * 10)), Some(Verdict::Stop));
+    }
+
+    #[test]
+    fn lifetime_stops_busy_and_suspended_vms() {
+        let mut row = vm(Some(600), Some(3600));
+        assert_eq!(verdict(&row, MINUTE * 59, None), None);
+        assert_eq!(verdict(&row, MINUTE * 60, None), Some(Verdict::Stop));
+        row.suspended_at = Some("2026-01-01T00:30:00Z".into());
+        assert_eq!(verdict(&row, MINUTE * 59, Some(MINUTE * 30)), None);
+        assert_eq!(verdict(&row, MINUTE * 60, None), Some(Verdict::Stop));
+    }
+
+    #[test]
+    fn missing_or_zero_limits_never_act() {
+        let row = vm(None, Some(0));
+        assert_eq!(verdict(&row, MINUTE * 10_000, Some(MINUTE * 10_000)), None);
+    }
+
+    #[test]
+    fn idle_needs_no_clients_no_workloads_and_low_cpu() {
+        assert!(is_idle(0, 0, Some(1.0), 5.0));
+        assert!(!is_idle(1, 0, Some(1.0), 5.0));
+        assert!(!is_idle(0, 1, Some(1.0), 5.0));
+        assert!(!is_idle(0, 0, Some(12.5), 5.0));
+        assert!(!is_idle(0, 0, None, 5.0));
+    }
+}
@@ -0,0 +1,203 @@
+//! Named guest images (`images:` in the supervisor config).
+//!
+//! An image is either
+//!
+//! - `built`: an `image-builder/build.sh` artefact set in `image_dir`
+//!   (`<distro>-<flavor>-mows-agent-<arch>.{qcow2,vmlinuz,initramfs}`),
+//!   booted with its own kernel and initramfs and set up by the
+//!   `mows-agent-init` baked into it; or
+//! - `cloud`: a stock cloud qcow2 (Debian/Ubuntu `genericcloud`, …) booted
+//!   through its own bootloader and provisioned on first boot by
+//!   cloud-init from a per-VM NoCloud seed (see `crate::cloud_init`).
+//!
+//! The built distros (`alpine`, `debian`, `ubuntu`, `nixos`) are always
+//! available under their own names, so `image: debian` keeps meaning the
+//! image-builder's Debian unless the config defines its own `debian`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::SupervisorConfig;
+use crate::error::{Result, SupervisorError};
+use crate::qemu::{locate_image, ImageArtifacts};
+
+/// Distros `image-builder/build.sh --distro` knows how to build.
+pub const BUILT_DISTROS: &[&str] = &["alpine", "debian", "ubuntu", "nixos"];
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(tag = "source", rename_all = "lowercase", deny_unknown_fields)]
+pub enum GuestImage {
+    /// An image-builder artefact set in `image_dir`.
+    Built {
+        /// One of [`BUILT_DISTROS`].
+        distro: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    /// A stock cloud qcow2, provisioned by cloud-init.
+    Cloud {
+        /// The qcow2; relative paths are taken from `image_dir`. It is only
+        /// ever used as a backing file, never written to.
+        #[schema(value_type = String)]
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+}
+
+impl GuestImage {
+    pub fn is_cloud(&self) -> bool {
+        matches!(self, Self::Cloud { .. })
+    }
+
+    /// Reject definitions that would only fail once a VM boots them.
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(SupervisorError::Config(format!(
+                "images: {name:?} is not a valid image name (letters, digits, `-`, `_`, `.`)"
+            )));
+        }
+        match self {
+            Self::Built { distro, .. } if !BUILT_DISTROS.contains(&distro.as_str()) => {
+                Err(SupervisorError::Config(format!(
+                    "images.{name}.distro: {distro:?} is not one of {}",
+                    BUILT_DISTROS.join(", ")
+                )))
+            }
+            Self::Cloud { path, .. } if path.as_os_str().is_empty() => Err(
+                SupervisorError::Config(format!("images.{name}.path must not be empty")),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Every image a `create_vm` request can name: the built distros, then
+/// the configured ones (which win on a name clash).
+pub fn available(configured: &BTreeMap<String, GuestImage>) -> BTreeMap<String, GuestImage> {
+    let mut images: BTreeMap<String, GuestImage> = BUILT_DISTROS
+        .iter()
+        .map(|distro| {
+            let image = GuestImage::Built {
+                distro: (*distro).to_string(),
+                description: None,
+            };
+            ((*distro).to_string(), image)
+        })
+        .collect();
+    images.extend(configured.iter().map(|(name, image)| (name.clone(), image.clone())));
+    images
+}
+
+/// Look up the image a request named.
+pub fn resolve(configured: &BTreeMap<String, GuestImage>, name: &str) -> Result<GuestImage> {
+    let mut images = available(configured);
+    images.remove(name).ok_or_else(|| {
+        let known: Vec<&str> = images.keys().map(String::as_str).collect();
+        SupervisorError::BadRequest(format!(
+            "unknown image {name:?}; available: {}",
+            known.join(", ")
+        ))
+    })
+}
+
+/// Find the files to boot `image` with `flavor` (`headless`/`desktop`).
+/// The flavor only picks between built artefact sets; a cloud image
+/// boots the same disk either way.
+pub fn locate(config: &SupervisorConfig, image: &GuestImage, flavor: &str) -> Result<ImageArtifacts> {
+    match image {
+        GuestImage::Built { distro, .. } => locate_image(config, distro, flavor),
+        GuestImage::Cloud { path, .. } => {
+            let qcow2 = cloud_image_path(&config.image_dir, path);
+            if !qcow2.is_file() {
+                return Err(SupervisorError::ImageMissing(format!(
+                    "expected cloud qcow2 at {} — download it into image_dir",
+                    qcow2.display()
+                )));
+            }
+            Ok(ImageArtifacts {
+                qcow2,
+                kernel: None,
+                initramfs: None,
+            })
+        }
+    }
+}
+
+fn cloud_image_path(image_dir: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        image_dir.join(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured() -> BTreeMap<String, GuestImage> {
+        serde_yaml_neo::from_str(
+            r#"
+debian-cloud:
+  source: cloud
+  path: debian-12-genericcloud-amd64.qcow2
+  description: Debian 12 with glibc toolchains
+alpine:
+  source: built
+  distro: alpine
+  description: the default
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn built_distros_are_always_available_and_config_wins() {
+        let images = available(&configured());
+        assert!(images.contains_key("nixos"));
+        assert!(images["debian-cloud"].is_cloud());
+        assert!(matches!(
+            &images["alpine"],
+            GuestImage::Built { description: Some(d), .. } if d == "the default"
+        ));
+        let err = resolve(&configured(), "fedora").unwrap_err();
+        assert!(matches!(err, SupervisorError::BadRequest(ref msg) if msg.contains("debian-cloud")));
+    }
+
+    #[test]
+    fn definitions_are_validated() {
+        for image in configured().values() {
+            assert!(image.validate("ok-name").is_ok());
+        }
+        let gentoo = GuestImage::Built {
+            distro: "gentoo".into(),
+            description: None,
+        };
+        assert!(gentoo.validate("gentoo").is_err());
+        assert!(configured()["alpine"].validate("bad name").is_err());
+    }
+
+    #[test]
+    fn cloud_paths_are_relative_to_image_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = SupervisorConfig::defaults_for_tests();
+        config.image_dir = dir.path().to_path_buf();
+        let image = &configured()["debian-cloud"];
+        assert!(matches!(
+            locate(&config, image, "headless"),
+            Err(SupervisorError::ImageMissing(_))
+        ));
+        std::fs::write(dir.path().join("debian-12-genericcloud-amd64.qcow2"), b"").unwrap();
+        let artifacts = locate(&config, image, "desktop").unwrap();
+        assert_eq!(artifacts.qcow2, dir.path().join("debian-12-genericcloud-amd64.qcow2"));
+        assert!(artifacts.kernel.is_none());
+    }
+}
@@ -11,6 +11,8 @@ use std::path::Path;
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Result, SupervisorError};
+use crate::mounts::VmMount;
+use crate::secrets;
 
 /// One MCP server entry inside an agent's `~/.claude.json`. The bootstrap
 /// script materialises this set into the `mcpServers` JSON object at
@@ -34,6 +36,12 @@ pub struct AgentKind {
     /// Argv used to start the agent in interactive mode (defaults to `[binary]`).
     #[serde(default)]
     pub argv: Vec<String>,
+    /// Argv for non-interactive task runs (`/v1/tasks`). The prompt is fed
+    /// on stdin and is also readable at `$MOWS_TASK_PROMPT_FILE`; files the
+    /// agent writes to `$MOWS_TASK_OUT_DIR` (`/out`) are collected once it
+    /// exits. Empty means the kind can't run headless tasks.
+    #[serde(default)]
+    pub headless_argv: Vec<String>,
     /// Login command to run if no credentials are present (e.g. `claude login`).
     pub login_command: Option<String>,
     /// Extra environment variables passed when launching the agent.
@@ -48,6 +56,16 @@ pub struct AgentKind {
     /// resulting `mcpServers` JSON object).
     #[serde(default)]
     pub mcp_servers: BTreeMap<String, McpServerSpec>,
+    /// Extra host directories shared into VMs created for this kind, on
+    /// top of `host_mounts.kinds` in the supervisor config.
+    #[serde(default)]
+    pub mounts: Vec<VmMount>,
+    /// Regexes matched against the agent's visible screen (trailing
+    /// whitespace trimmed). Once the screen has been still for
+    /// `notifications.input_idle_secs` and one matches, the agent counts
+    /// as waiting for input. Empty disables the detection.
+    #[serde(default)]
+    pub input_prompts: Vec<String>,
 }
 
 impl AgentKind {
@@ -75,6 +93,8 @@ impl AgentKind {
     /// fires when a kind is parsed from a user-supplied YAML file under
     /// `kinds.d/`.
     fn validate(&self) -> Result<()> {
+        compile_input_prompts(&self.input_prompts)
+            .map_err(|e| SupervisorError::Config(format!("agent kind `{}`: {e}", self.name)))?;
         for key in self.env.keys() {
             if !is_valid_posix_env_name(key) {
                 return Err(SupervisorError::Config(format!(
@@ -88,7 +108,18 @@ impl AgentKind {
     }
 }
 
-fn is_valid_posix_env_name(s: &str) -> bool {
+/// Compile a kind's `input_prompts`.
+pub fn compile_input_prompts(patterns: &[String]) -> std::result::Result<Vec<regex::Regex>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            regex::Regex::new(pattern)
+                .map_err(|e| format!("input_prompts: invalid regex {pattern:?}: {e}"))
+        })
+        .collect()
+}
+
+pub(crate) fn is_valid_posix_env_name(s: &str) -> bool {
     let mut chars = s.chars();
     let Some(first) = chars.next() else {
         return false;
@@ -110,10 +141,108 @@ pub fn builtin_shell() -> AgentKind {
         // Empty argv → profile.sh skips the auto-launch shim entirely and
         // sshd's default shell takes over.
         argv: Vec::new(),
+        // Headless runs execute the prompt as a bash script.
+        headless_argv: vec!["/bin/bash".to_string(), "-s".to_string()],
         login_command: None,
         env: BTreeMap::new(),
         credentials_mount: None,
         mcp_servers: BTreeMap::new(),
+        mounts: Vec::new(),
+        // A shell prompt on the last line of the screen.
+        input_prompts: vec![r"[$#]\z".to_string()],
+    }
+}
+
+/// Look up a builtin kind by its wire name (`shell`, `claude`).
+pub fn builtin(name: &str) -> Option<AgentKind> {
+    match name {
+        "shell" => Some(builtin_shell()),
+        "claude" => Some(builtin_claude()),
+        _ => None,
+    }
+}
+
+/// Operator additions to a builtin kind (`agent_kinds.<kind>` in the
+/// supervisor config): extra env, input prompts and, for `claude`, extra
+/// MCP servers.
+/// A `${secret:NAME}` value in either `env` map references the secret
+/// store instead of holding the value (see `crate::secrets`).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentKindExtras {
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub mcp_servers: BTreeMap<String, McpServerSpec>,
+    /// Added to the kind's own `input_prompts`.
+    #[serde(default)]
+    pub input_prompts: Vec<String>,
+}
+
+impl AgentKindExtras {
+    pub fn validate(&self, kind: &str) -> Result<()> {
+        let config_err = |detail: String| SupervisorError::Config(format!("agent_kinds.{kind}: {detail}"));
+        if builtin(kind).is_none() {
+            return Err(SupervisorError::Config(format!(
+                "agent_kinds: unknown agent kind {kind:?}"
+            )));
+        }
+        compile_input_prompts(&self.input_prompts).map_err(config_err)?;
+        if kind != "claude" && !self.mcp_servers.is_empty() {
+            return Err(config_err("mcp_servers are only supported for the claude kind".into()));
+        }
+        let env_maps = std::iter::once(&self.env).chain(self.mcp_servers.values().map(|s| &s.env));
+        for env in env_maps {
+            for (key, value) in env {
+                if !is_valid_posix_env_name(key) {
+                    return Err(config_err(format!(
+                        "env key {key:?} is not a valid POSIX env-var name"
+                    )));
+                }
+                if let Some(secret) = secrets::reference(value) {
+                    secrets::validate_name(secret).map_err(config_err)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A builtin kind with its `agent_kinds` extras from the config applied.
+pub fn configured(extras: &BTreeMap<String, AgentKindExtras>, name: &str) -> Option<AgentKind> {
+    let mut kind = builtin(name)?;
+    if let Some(extra) = extras.get(name) {
+        kind.env.extend(extra.env.clone());
+        kind.input_prompts.extend(extra.input_prompts.clone());
+        if !extra.mcp_servers.is_empty() {
+            kind.mcp_servers.extend(extra.mcp_servers.clone());
+            // Only the claude bootstrap consumes `mcp_servers`; config
+            // validation rejects them for every other kind.
+            (kind.argv, kind.headless_argv) = claude_launch_argv(&kind.mcp_servers);
+        }
+    }
+    Some(kind)
+}
+
+impl AgentKind {
+    /// Split `env` into plain values and secret references, and add the
+    /// MCP servers' references. The references map the guest env var to
+    /// the secret name; `agent_runtime::spawn` resolves them.
+    pub fn split_secret_env(&self) -> (BTreeMap<String, String>, BTreeMap<String, String>) {
+        let mut plain = BTreeMap::new();
+        let mut refs = BTreeMap::new();
+        for (key, value) in &self.env {
+            match secrets::reference(value) {
+                Some(secret) => refs.insert(key.clone(), secret.to_string()),
+                None => plain.insert(key.clone(), value.clone()),
+            };
+        }
+        for spec in self.mcp_servers.values() {
+            for secret in spec.env.values().filter_map(|value| secrets::reference(value)) {
+                refs.insert(secrets::mcp_env_var(secret), secret.to_string());
+            }
+        }
+        (plain, refs)
     }
 }
 
@@ -163,22 +292,7 @@ pub fn builtin_claude() -> AgentKind {
         },
     );
 
-    let mcp_json = serde_json::to_string(&mcp_servers)
-        .expect("serialising a BTreeMap<String, McpServerSpec> cannot fail");
-
-    // The bootstrap script reads `MOWS_CLAUDE_MCP_SERVERS` from the
-    // inherited env. agent_runtime shell-quotes both keys and values
-    // when assembling the tmux launch command, so embedding the JSON
-    // directly here is safe regardless of what characters end up in
-    // the values.
-    let mut argv_env = BTreeMap::new();
-    argv_env.insert("MOWS_CLAUDE_MCP_SERVERS".to_string(), mcp_json);
-
-    let bootstrap_command = format!(
-        "export MOWS_CLAUDE_MCP_SERVERS={}; exec /bin/sh -c {}",
-        shell_quote(argv_env.get("MOWS_CLAUDE_MCP_SERVERS").unwrap()),
-        shell_quote(CLAUDE_BOOTSTRAP_SH),
-    );
+    let (argv, headless_argv) = claude_launch_argv(&mcp_servers);
 
     AgentKind {
         name: "claude".to_string(),
@@ -186,11 +300,8 @@ pub fn builtin_claude() -> AgentKind {
         // The agent is sandboxed by KVM + per-agent qcow2 overlay, so
         // granting full tool permissions is the right default — anything
         // it does is contained to its own ephemeral guest.
-        argv: vec![
-            "/bin/sh".to_string(),
-            "-c".to_string(),
-            bootstrap_command,
-        ],
+        argv,
+        headless_argv,
         login_command: Some("/usr/local/bin/claude login".to_string()),
         // SLOP-37: the canonical `CLAUDE_CONFIG_DIR` is set by the bootstrap
         // shell (under the `agent` user, pointing at /home/agent/.claude).
@@ -201,14 +312,62 @@ pub fn builtin_claude() -> AgentKind {
         env: BTreeMap::new(),
         credentials_mount: Some("/creds".to_string()),
         mcp_servers,
+        mounts: Vec::new(),
+        // The idle input box's footer, and permission / choice dialogs.
+        // While claude works its spinner keeps the screen changing.
+        input_prompts: vec![
+            r"\? for shortcuts".to_string(),
+            r"Do you want to".to_string(),
+            r"❯ 1\.".to_string(),
+        ],
     }
 }
 
+/// Interactive and headless argv of the claude kind for `mcp_servers`.
+/// Secret references in a server's `env` become `${MOWS_SECRET_<NAME>}`,
+/// which claude expands from the environment `agent_runtime::spawn`
+/// sources; the JSON itself never carries a value.
+fn claude_launch_argv(mcp_servers: &BTreeMap<String, McpServerSpec>) -> (Vec<String>, Vec<String>) {
+    let guest_servers: BTreeMap<&String, McpServerSpec> = mcp_servers
+        .iter()
+        .map(|(name, spec)| {
+            let env = spec
+                .env
+                .iter()
+                .map(|(key, value)| match secrets::reference(value) {
+                    Some(secret) => (key.clone(), format!("${{{}}}", secrets::mcp_env_var(secret))),
+                    None => (key.clone(), value.clone()),
+                })
+                .collect();
+            (name, McpServerSpec { env, ..spec.clone() })
+        })
+        .collect();
+    let mcp_json = serde_json::to_string(&guest_servers)
+        .expect("serialising a BTreeMap<String, McpServerSpec> cannot fail");
+
+    // The bootstrap script reads `MOWS_CLAUDE_MCP_SERVERS` from the
+    // inherited env. Shell-quoting the JSON makes embedding it here safe
+    // regardless of what characters end up in the values.
+    let mcp_export = format!("export MOWS_CLAUDE_MCP_SERVERS={};", shell_quote(&mcp_json));
+    let bootstrap_command = format!(
+        "{mcp_export} exec /bin/sh -c {}",
+        shell_quote(CLAUDE_BOOTSTRAP_SH),
+    );
+    // Same bootstrap; `MOWS_CLAUDE_HEADLESS=1` makes its final step run
+    // `claude --print` on the prompt arriving on stdin instead of the TUI.
+    let headless_command = format!(
+        "{mcp_export} export MOWS_CLAUDE_HEADLESS=1; exec /bin/sh -c {}",
+        shell_quote(CLAUDE_BOOTSTRAP_SH),
+    );
+    let sh = |command: String| vec!["/bin/sh".to_string(), "-c".to_string(), command];
+    (sh(bootstrap_command), sh(headless_command))
+}
+
 /// POSIX single-quote shell escape. Used to embed the bootstrap script
 /// and the MCP-server JSON into the outer `sh -c` invocation that
-/// agent_runtime hands to tmux. `'` is closed, a `'\''` escape sequence
-/// inserted, and the quoted string reopened.
-fn shell_quote(s: &str) -> String {
+/// agent_runtime hands to tmux (and `tasks` hands to ssh). `'` is closed,
+/// a `'\''` escape sequence inserted, and the quoted string reopened.
+pub(crate) fn shell_quote(s: &str) -> String {
     let mut out = String::with_capacity(s.len() + 2);
     out.push('\'');
     for ch in s.chars() {
@@ -292,6 +451,19 @@ env:
         assert!(AgentKind::from_yaml(yaml).is_err());
     }
 
+    #[test]
+    fn rejects_invalid_input_prompt_regex() {
+        let yaml = r#"
+name: broken
+binary: /bin/sh
+input_prompts: ["(unclosed"]
+"#;
+        assert!(AgentKind::from_yaml(yaml).is_err());
+        for kind in [builtin_shell(), builtin_claude()] {
+            assert!(compile_input_prompts(&kind.input_prompts).is_ok());
+        }
+    }
+
     #[test]
     fn shipped_claude_manifest_parses() {
         // The manifest baked into the VM image must match the schema this
@@ -336,6 +508,63 @@ env:
         );
     }
 
+    #[test]
+    fn builtin_claude_headless_argv_switches_bootstrap_to_print_mode() {
+        let kind = builtin_claude();
+        assert_eq!(kind.headless_argv.len(), 3);
+        let command = &kind.headless_argv[2];
+        assert!(command.starts_with("export MOWS_CLAUDE_MCP_SERVERS="), "{command}");
+        assert!(command.contains("export MOWS_CLAUDE_HEADLESS=1;"), "{command}");
+        assert!(
+            CLAUDE_BOOTSTRAP_SH.contains("--dangerously-skip-permissions --print"),
+            "bootstrap must run claude in print mode for headless tasks"
+        );
+        assert!(!kind.argv[2].contains("export MOWS_CLAUDE_HEADLESS=1"));
+    }
+
+    #[test]
+    fn builtin_lookup_resolves_shipped_kinds() {
+        assert_eq!(builtin("claude").map(|k| k.name), Some("claude".to_string()));
+        assert_eq!(builtin("shell").map(|k| k.headless_argv.is_empty()), Some(false));
+        assert!(builtin("aider").is_none());
+    }
+
+    #[test]
+    fn extras_add_env_and_mcp_servers_with_secret_references() {
+        let extras: BTreeMap<String, AgentKindExtras> = serde_yaml_neo::from_str(
+            r#"
+claude:
+  env:
+    GITHUB_TOKEN: ${secret:GITHUB_TOKEN}
+    RUST_LOG: info
+  mcp_servers:
+    brave:
+      command: brave-search-mcp
+      env:
+        BRAVE_API_KEY: ${secret:BRAVE}
+"#,
+        )
+        .unwrap();
+        extras["claude"].validate("claude").unwrap();
+        let kind = configured(&extras, "claude").unwrap();
+        assert!(kind.mcp_servers.contains_key("chrome-devtools"));
+        assert!(kind.argv[2].contains("brave-search-mcp"));
+        assert!(kind.argv[2].contains("${MOWS_SECRET_BRAVE}"));
+        assert!(!kind.argv[2].contains("${secret:"));
+
+        let (plain, refs) = kind.split_secret_env();
+        assert_eq!(plain.keys().collect::<Vec<_>>(), ["RUST_LOG"]);
+        assert_eq!(refs["GITHUB_TOKEN"], "GITHUB_TOKEN");
+        assert_eq!(refs["MOWS_SECRET_BRAVE"], "BRAVE");
+
+        let shell_mcp = AgentKindExtras {
+            mcp_servers: extras["claude"].mcp_servers.clone(),
+            ..AgentKindExtras::default()
+        };
+        assert!(shell_mcp.validate("shell").is_err());
+        assert!(AgentKindExtras::default().validate("aider").is_err());
+    }
+
     #[test]
     fn shell_quote_escapes_single_quote() {
         // The escape sequence is `'` → `'\''` (close, escaped quote,
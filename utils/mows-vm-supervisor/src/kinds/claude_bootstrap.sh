@@ -16,7 +16,9 @@
 #      server entry.
 #   5. Hand workspace ownership to `agent`.
 #   6. Drop privileges with `su` and exec claude with the right
-#      `CLAUDE_CONFIG_DIR` + `HOME`.
+#      `CLAUDE_CONFIG_DIR` + `HOME` — the TUI, or `--print` on stdin when
+#      `MOWS_CLAUDE_HEADLESS=1` (headless task runs) — re-sourcing the
+#      agent's secrets from `$MOWS_SECRETS_FILE` when it has any.
 #
 # The MCP server table is materialised by the supervisor and injected
 # via the `MOWS_CLAUDE_MCP_SERVERS` env var as a JSON object so this
@@ -159,10 +161,37 @@ if [ -d /workspace ]; then
     chown agent:agent /workspace || true
 fi
 
+# Secrets resolved by the supervisor (`agent_runtime::spawn`) sit in a
+# root-owned file on a tmpfs. `su` starts the agent with a fresh
+# environment, so hand the file to `agent` and source it again inside.
+# The path is supervisor-generated (`/run/mows-secrets/<session>.env`),
+# so splicing it into the `su -c` string is safe.
+secrets_env=""
+if [ -n "${MOWS_SECRETS_FILE:-}" ] && [ -f "$MOWS_SECRETS_FILE" ]; then
+    chown agent "$MOWS_SECRETS_FILE"
+    secrets_env="set -a; . $MOWS_SECRETS_FILE; set +a; "
+fi
+
+# Headless task run (`/v1/tasks`): the prompt arrives on stdin, which
+# `su` passes through, and claude prints its answer instead of starting
+# the TUI. Same environment as the interactive path below.
+if [ "${MOWS_CLAUDE_HEADLESS:-0}" = 1 ]; then
+    exec su -s /bin/sh agent -c \
+        "$secrets_env"'cd /workspace 2>/dev/null || cd; \
+         if [ -f /etc/profile.d/mows-agent.sh ]; then . /etc/profile.d/mows-agent.sh; fi; \
+         export HOME=/home/agent CLAUDE_CONFIG_DIR=/home/agent/.claude DISABLE_AUTOUPDATER=1 \
+                PUPPETEER_SKIP_DOWNLOAD=1 PUPPETEER_EXECUTABLE_PATH=/usr/bin/chromium-browser \
+                MOWS_TASK_PROMPT_FILE=/run/mows-task/prompt.md MOWS_TASK_OUT_DIR=/out; \
+         exec /usr/local/bin/claude --dangerously-skip-permissions --print'
+fi
+
 # Drop privileges and exec claude. The `su` discards the parent env, so
 # every variable the agent needs is re-exported inside the inner shell.
+# The supervisor's profile shim carries the egress proxy settings for
+# VMs with a restricted network policy.
 exec su -s /bin/sh agent -c \
-    'cd /workspace 2>/dev/null || cd; \
+    "$secrets_env"'cd /workspace 2>/dev/null || cd; \
+     if [ -f /etc/profile.d/mows-agent.sh ]; then . /etc/profile.d/mows-agent.sh; fi; \
      export HOME=/home/agent CLAUDE_CONFIG_DIR=/home/agent/.claude DISABLE_AUTOUPDATER=1 \
             PUPPETEER_SKIP_DOWNLOAD=1 PUPPETEER_EXECUTABLE_PATH=/usr/bin/chromium-browser; \
      exec /usr/local/bin/claude --dangerously-skip-permissions'
@@ -5,13 +5,30 @@
 
 pub mod agent_runtime;
 pub mod api;
+pub mod audit;
+pub mod cloud_init;
 pub mod config;
 pub mod db;
+pub mod egress;
 pub mod error;
 pub mod events;
+pub mod forwards;
+pub mod idle;
+pub mod images;
 pub mod kinds;
+pub mod metrics;
+pub mod mounts;
+pub mod notifications;
+pub mod presets;
 pub mod qemu;
+pub mod qmp;
+pub mod quota;
+pub mod recording;
 pub mod recovery;
+pub mod secrets;
 pub mod ssh_keys;
 pub mod ssh_sessions;
 pub mod state;
+pub mod tasks;
+pub mod tokens;
+pub mod workspace;
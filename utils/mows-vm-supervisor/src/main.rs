@@ -7,8 +7,14 @@ use mows_vm_supervisor::config::SupervisorConfig;
 use mows_vm_supervisor::db;
 use mows_vm_supervisor::error::Result;
 use mows_vm_supervisor::events::SupervisorEvent;
+use mows_vm_supervisor::forwards;
+use mows_vm_supervisor::idle;
+use mows_vm_supervisor::notifications;
+use mows_vm_supervisor::qemu;
+use mows_vm_supervisor::recording;
 use mows_vm_supervisor::recovery;
 use mows_vm_supervisor::state::AppState;
+use mows_vm_supervisor::tasks;
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser, Debug)]
@@ -75,6 +81,16 @@ async fn main() -> Result<()> {
         );
     }
 
+    // Tasks mid-run lost their ssh session (and usually their VM) with
+    // the previous process; queued ones are picked up again below.
+    let interrupted_tasks = recovery::reconcile_running_tasks(&pool, chrono::Utc::now()).await?;
+    if !interrupted_tasks.is_empty() {
+        tracing::warn!(
+            tasks = interrupted_tasks.len(),
+            "failed tasks interrupted by the previous supervisor run"
+        );
+    }
+
     // Recover port allocator state from the DB so a supervisor restart
     // doesn't hand out ports already bound by surviving QEMU processes
     // from the previous run. Depends on `reconcile_orphans` having
@@ -101,12 +117,35 @@ async fn main() -> Result<()> {
         );
     }
 
+    // Same for runtime port forwards: rows of VMs that died are dropped,
+    // the rest keep their host ports and get their rules re-applied once
+    // the state is up.
+    let live_forwards = recovery::reconcile_forwards(&pool).await?;
+
     let state = Arc::new(AppState::with_port_reservations(
         config.clone(),
         pool,
         live_ports,
+        live_forwards.iter().map(|forward| forward.host_port),
     ));
 
+    for forward in live_forwards {
+        let qmp_socket = qemu::qmp_socket_for(&state.config.state_dir, &forward.vm_id);
+        // The rule may still be in place if QEMU outlived us; replace it.
+        let _ = forwards::remove_rule(&qmp_socket, forward.host_port).await;
+        if let Err(e) = forwards::add_rule(&qmp_socket, forward.host_port, forward.guest_port).await
+        {
+            tracing::warn!(
+                vm_id = %forward.vm_id,
+                guest_port = forward.guest_port,
+                error = %e,
+                "failed to restore port forward; dropping it"
+            );
+            forwards::delete(&state.db, &forward.vm_id, forward.guest_port).await?;
+            state.forward_ports.release([forward.host_port]);
+        }
+    }
+
     // Emit the recovery transitions on the now-live event bus so any
     // future `/v1/events` subscriber (e.g. a fleet coordinator) and
     // in-process listeners observe the failures rather than relying on
@@ -121,5 +160,13 @@ async fn main() -> Result<()> {
         state.events.emit(SupervisorEvent::AgentUpdated { id: agent_id });
     }
 
+    for task_id in interrupted_tasks {
+        state.events.emit(SupervisorEvent::TaskUpdated { id: task_id });
+    }
+
+    tasks::spawn_dispatcher(Arc::clone(&state));
+    recording::spawn_retention_sweeper(Arc::clone(&state));
+    notifications::spawn_webhook_dispatcher(Arc::clone(&state));
+    idle::spawn_idle_monitor(Arc::clone(&state));
     api::serve(state).await
 }
@@ -0,0 +1,438 @@
+//! Resource usage of running VMs.
+//!
+//! Each sample combines what the host can see of a VM without asking the
+//! guest:
+//!
+//! - CPU time and resident memory of its QEMU process (`/proc/<pid>/stat`,
+//!   `/proc/<pid>/status`);
+//! - the size of its qcow2 overlay on disk;
+//! - disk I/O bytes from QMP `query-blockstats`;
+//! - network bytes from its egress proxy ([`EgressTraffic`]) — slirp has
+//!   no per-VM counters, so VMs with an `open` network policy have none;
+//! - how many agents are running inside it.
+//!
+//! Samples are taken on demand — by a `/metrics` scrape, `GET
+//! /v1/vms/stats` or a `/v1/vms/{id}/stats` stream — never in the
+//! background. The idle monitor (`crate::idle`) reuses the CPU sampling.
+//!
+//! [`EgressTraffic`]: crate::egress::EgressTraffic
+
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::{Registry, Unit};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::error::{Result, SupervisorError};
+use crate::qemu::{overlay_path_for, qmp_socket_for, OVERLAY_DRIVE_ID};
+use crate::qmp::QmpClient;
+use crate::state::SharedState;
+
+/// `/proc/<pid>/stat` reports CPU time in `USER_HZ` clock ticks, which is
+/// 100 on every architecture the supervisor runs on.
+const CLOCK_TICKS_PER_SEC: u32 = 100;
+
+/// Upper bound for the QMP round-trip of one sample. QEMU serves one QMP
+/// client at a time, so a long snapshot would otherwise stall the stats.
+const QMP_STATS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// CPU time consumed by a process up to `at`.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuSample {
+    ticks: u64,
+    at: Instant,
+}
+
+impl CpuSample {
+    fn seconds(self) -> f64 {
+        let per_sec = u64::from(CLOCK_TICKS_PER_SEC);
+        let whole = u32::try_from(self.ticks / per_sec).unwrap_or(u32::MAX);
+        let fraction = u32::try_from(self.ticks % per_sec).unwrap_or(0);
+        f64::from(whole) + f64::from(fraction) / f64::from(CLOCK_TICKS_PER_SEC)
+    }
+}
+
+/// `utime + stime` from the contents of `/proc/<pid>/stat`. The command
+/// name (field 2) is parenthesised and may contain spaces, so fields are
+/// counted from the last `)`.
+fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // `rest` starts at field 3 (`state`); utime and stime are 14 and 15.
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    utime.checked_add(stime)
+}
+
+pub(crate) async fn sample_cpu(pid: u32) -> Option<CpuSample> {
+    let stat = tokio::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .await
+        .ok()?;
+    Some(CpuSample {
+        ticks: parse_cpu_ticks(&stat)?,
+        at: Instant::now(),
+    })
+}
+
+/// Average CPU use between two samples, in percent of one core.
+pub(crate) fn cpu_percent(previous: CpuSample, current: CpuSample) -> Option<f64> {
+    let elapsed = current.at.checked_duration_since(previous.at)?.as_secs_f64();
+    if elapsed <= 0.0 {
+        return None;
+    }
+    let ticks = current.ticks.checked_sub(previous.ticks)?;
+    let ticks = f64::from(u32::try_from(ticks).unwrap_or(u32::MAX));
+    Some(ticks / f64::from(CLOCK_TICKS_PER_SEC) / elapsed * 100.0)
+}
+
+/// `VmRSS` from the contents of `/proc/<pid>/status`, in bytes.
+fn parse_rss_bytes(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    kib.checked_mul(1024)
+}
+
+/// `(read, written)` bytes of the block device `device` in a QMP
+/// `query-blockstats` reply.
+fn parse_blockstats(reply: &Value, device: &str) -> Option<(u64, u64)> {
+    let entry = reply
+        .as_array()?
+        .iter()
+        .find(|entry| entry.get("device").and_then(Value::as_str) == Some(device))?;
+    let stats = entry.get("stats")?;
+    Some((
+        stats.get("rd_bytes")?.as_u64()?,
+        stats.get("wr_bytes")?.as_u64()?,
+    ))
+}
+
+async fn query_disk_io(state: &SharedState, vm_id: &str) -> Result<Option<(u64, u64)>> {
+    let socket = qmp_socket_for(&state.config.state_dir, vm_id);
+    let reply = tokio::time::timeout(QMP_STATS_TIMEOUT, async {
+        let mut qmp = QmpClient::connect(&socket).await?;
+        qmp.execute("query-blockstats", None).await
+    })
+    .await
+    .map_err(|_| SupervisorError::Qmp(format!("query-blockstats on {vm_id} timed out")))??;
+    Ok(parse_blockstats(&reply, OVERLAY_DRIVE_ID))
+}
+
+/// One reading of a running VM. Fields the host couldn't read are `null`.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct VmStats {
+    pub vm_id: String,
+    pub name: String,
+    pub sampled_at: String,
+    /// Paused by the idle monitor; the guest uses no CPU until resumed.
+    pub suspended: bool,
+    /// CPU time the QEMU process has used since it started.
+    pub cpu_seconds: Option<f64>,
+    /// CPU use since the previous sample of a `/stats` stream, in percent
+    /// of one host core. `null` on the first sample and in one-off reads.
+    pub cpu_percent: Option<f64>,
+    /// Resident memory of the QEMU process.
+    pub memory_rss_bytes: Option<u64>,
+    /// Size of the VM's copy-on-write overlay on the host disk.
+    pub disk_overlay_bytes: Option<u64>,
+    pub disk_read_bytes: Option<u64>,
+    pub disk_written_bytes: Option<u64>,
+    /// Traffic through the egress proxy; `null` for `open` VMs.
+    pub net_sent_bytes: Option<u64>,
+    pub net_received_bytes: Option<u64>,
+    /// Agents in `starting`, `running` or `stopping`.
+    pub agents_running: i64,
+}
+
+/// A running VM as the samplers need it.
+#[derive(sqlx::FromRow, Clone, Debug)]
+pub struct RunningVm {
+    pub id: String,
+    pub name: String,
+    pub owner_user_id: Option<String>,
+    pub qemu_pid: Option<i64>,
+    pub suspended_at: Option<String>,
+}
+
+/// Every VM whose status is `running` (suspended ones included).
+pub async fn running_vms(state: &SharedState) -> Result<Vec<RunningVm>> {
+    Ok(sqlx::query_as(
+        "SELECT id, name, owner_user_id, qemu_pid, suspended_at FROM vms \
+         WHERE status = 'running' ORDER BY started_at",
+    )
+    .fetch_all(&state.db)
+    .await?)
+}
+
+/// `vm_id` if it is `running`.
+pub async fn running_vm(state: &SharedState, vm_id: &str) -> Result<Option<RunningVm>> {
+    Ok(sqlx::query_as(
+        "SELECT id, name, owner_user_id, qemu_pid, suspended_at FROM vms \
+         WHERE id = ?1 AND status = 'running'",
+    )
+    .bind(vm_id)
+    .fetch_optional(&state.db)
+    .await?)
+}
+
+/// Sample `vm`. Pass the CPU sample of the previous call to get
+/// `cpu_percent`; the returned one feeds the next call.
+pub async fn sample_vm(
+    state: &SharedState,
+    vm: &RunningVm,
+    previous: Option<CpuSample>,
+) -> Result<(VmStats, Option<CpuSample>)> {
+    let pid = match state.vms.read().await.pid(&vm.id) {
+        Some(pid) => Some(pid),
+        // VMs adopted after a supervisor restart have no child handle.
+        None => vm.qemu_pid.and_then(|pid| u32::try_from(pid).ok()),
+    };
+    let (cpu, rss) = match pid {
+        Some(pid) => {
+            let status = tokio::fs::read_to_string(format!("/proc/{pid}/status"))
+                .await
+                .ok();
+            (
+                sample_cpu(pid).await,
+                status.as_deref().and_then(parse_rss_bytes),
+            )
+        }
+        None => (None, None),
+    };
+    let overlay = tokio::fs::metadata(overlay_path_for(&state.config.state_dir, &vm.id))
+        .await
+        .ok()
+        .map(|meta| meta.len());
+    let disk_io = match query_disk_io(state, &vm.id).await {
+        Ok(io) => io,
+        Err(e) => {
+            tracing::debug!(vm_id = %vm.id, error = %e, "disk stats unavailable");
+            None
+        }
+    };
+    let traffic = state.egress_proxies.traffic(&vm.id);
+    let agents_running: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM agents \
+         WHERE vm_id = ?1 AND status IN ('starting', 'running', 'stopping')",
+    )
+    .bind(&vm.id)
+    .fetch_one(&state.db)
+    .await?;
+    let stats = VmStats {
+        vm_id: vm.id.clone(),
+        name: vm.name.clone(),
+        sampled_at: Utc::now().to_rfc3339(),
+        suspended: vm.suspended_at.is_some(),
+        cpu_seconds: cpu.map(CpuSample::seconds),
+        cpu_percent: previous.zip(cpu).and_then(|(prev, cur)| cpu_percent(prev, cur)),
+        memory_rss_bytes: rss,
+        disk_overlay_bytes: overlay,
+        disk_read_bytes: disk_io.map(|(read, _)| read),
+        disk_written_bytes: disk_io.map(|(_, written)| written),
+        net_sent_bytes: traffic.map(|(sent, _)| sent),
+        net_received_bytes: traffic.map(|(_, received)| received),
+        agents_running,
+    };
+    Ok((stats, cpu))
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct VmLabels {
+    vm_id: String,
+    vm_name: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct StatusLabels {
+    status: String,
+}
+
+/// Render `samples` plus the VM and agent counts per status in the
+/// Prometheus text exposition format.
+pub fn render_prometheus(
+    samples: &[VmStats],
+    vms_by_status: &[(String, i64)],
+    agents_by_status: &[(String, i64)],
+) -> Result<String> {
+    let mut registry = Registry::with_prefix("mows");
+
+    let cpu = Family::<VmLabels, Counter<f64, AtomicU64>>::default();
+    let rss = Family::<VmLabels, Gauge>::default();
+    let overlay = Family::<VmLabels, Gauge>::default();
+    let disk_read = Family::<VmLabels, Counter>::default();
+    let disk_written = Family::<VmLabels, Counter>::default();
+    let net_sent = Family::<VmLabels, Counter>::default();
+    let net_received = Family::<VmLabels, Counter>::default();
+    let agents_running = Family::<VmLabels, Gauge>::default();
+    let suspended = Family::<VmLabels, Gauge>::default();
+    let vms = Family::<StatusLabels, Gauge>::default();
+    let agents = Family::<StatusLabels, Gauge>::default();
+
+    let gauge_value = |bytes: u64| i64::try_from(bytes).unwrap_or(i64::MAX);
+    for sample in samples {
+        let labels = VmLabels {
+            vm_id: sample.vm_id.clone(),
+            vm_name: sample.name.clone(),
+        };
+        if let Some(seconds) = sample.cpu_seconds {
+            cpu.get_or_create(&labels).inc_by(seconds);
+        }
+        if let Some(bytes) = sample.memory_rss_bytes {
+            rss.get_or_create(&labels).set(gauge_value(bytes));
+        }
+        if let Some(bytes) = sample.disk_overlay_bytes {
+            overlay.get_or_create(&labels).set(gauge_value(bytes));
+        }
+        if let Some(bytes) = sample.disk_read_bytes {
+            disk_read.get_or_create(&labels).inc_by(bytes);
+        }
+        if let Some(bytes) = sample.disk_written_bytes {
+            disk_written.get_or_create(&labels).inc_by(bytes);
+        }
+        if let Some(bytes) = sample.net_sent_bytes {
+            net_sent.get_or_create(&labels).inc_by(bytes);
+        }
+        if let Some(bytes) = sample.net_received_bytes {
+            net_received.get_or_create(&labels).inc_by(bytes);
+        }
+        agents_running
+            .get_or_create(&labels)
+            .set(sample.agents_running);
+        suspended
+            .get_or_create(&labels)
+            .set(i64::from(sample.suspended));
+    }
+    for (status, count) in vms_by_status {
+        vms.get_or_create(&StatusLabels { status: status.clone() }).set(*count);
+    }
+    for (status, count) in agents_by_status {
+        agents.get_or_create(&StatusLabels { status: status.clone() }).set(*count);
+    }
+
+    registry.register_with_unit(
+        "vm_cpu",
+        "CPU time used by the VM's QEMU process",
+        Unit::Seconds,
+        cpu,
+    );
+    registry.register_with_unit(
+        "vm_memory_rss",
+        "Resident memory of the VM's QEMU process",
+        Unit::Bytes,
+        rss,
+    );
+    registry.register_with_unit(
+        "vm_disk_overlay",
+        "Size of the VM's qcow2 overlay on the host",
+        Unit::Bytes,
+        overlay,
+    );
+    registry.register_with_unit("vm_disk_read", "Bytes read by the guest's disk", Unit::Bytes, disk_read);
+    registry.register_with_unit(
+        "vm_disk_written",
+        "Bytes written by the guest's disk",
+        Unit::Bytes,
+        disk_written,
+    );
+    registry.register_with_unit(
+        "vm_network_sent",
+        "Bytes the guest sent through its egress proxy",
+        Unit::Bytes,
+        net_sent,
+    );
+    registry.register_with_unit(
+        "vm_network_received",
+        "Bytes the guest received through its egress proxy",
+        Unit::Bytes,
+        net_received,
+    );
+    registry.register("vm_agents_running", "Agents running inside the VM", agents_running);
+    registry.register("vm_suspended", "1 while the VM is paused by the idle monitor", suspended);
+    registry.register("vms", "VMs by status", vms);
+    registry.register("agents", "Agents by status", agents);
+
+    let mut body = String::new();
+    prometheus_client::encoding::text::encode(&mut body, &registry)
+        .map_err(|e| SupervisorError::Internal(format!("encode metrics: {e}")))?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_cpu_ticks_past_a_command_name_with_spaces() {
+        let stat = "4242 (qemu (vm) x) S 1 4242 4242 0 -1 4194560 1000 0 0 0 \
+                    250 130 0 0 20 0 5 0 100 0 0";
+        assert_eq!(parse_cpu_ticks(stat), Some(380));
+        assert_eq!(parse_cpu_ticks("4242 (qemu) S 1"), None);
+    }
+
+    #[test]
+    fn cpu_percent_is_relative_to_one_core() {
+        let start = Instant::now();
+        let previous = CpuSample { ticks: 1_000, at: start };
+        let current = CpuSample {
+            ticks: 1_150,
+            at: start + Duration::from_secs(10),
+        };
+        let percent = cpu_percent(previous, current).expect("measurable");
+        assert!((percent - 15.0).abs() < 1e-9, "{percent}");
+        assert_eq!(cpu_percent(current, previous), None);
+        assert!((current.seconds() - 11.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parses_rss_and_blockstats() {
+        let status = "Name:\tqemu-system-x86\nVmPeak:\t 9000 kB\nVmRSS:\t  2048 kB\n";
+        assert_eq!(parse_rss_bytes(status), Some(2 * 1024 * 1024));
+        assert_eq!(parse_rss_bytes("Name:\tqemu\n"), None);
+
+        let reply = json!([
+            {"device": "pflash0", "stats": {"rd_bytes": 1, "wr_bytes": 2}},
+            {"device": "disk0", "stats": {"rd_bytes": 4096, "wr_bytes": 512}},
+        ]);
+        assert_eq!(parse_blockstats(&reply, "disk0"), Some((4096, 512)));
+        assert_eq!(parse_blockstats(&reply, "disk1"), None);
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let sample = VmStats {
+            vm_id: "vm-1".into(),
+            name: "web".into(),
+            sampled_at: "2026-01-01T00:00:00Z".into(),
+            suspended: false,
+            cpu_seconds: Some(12.5),
+            cpu_percent: None,
+            memory_rss_bytes: Some(1024),
+            disk_overlay_bytes: None,
+            disk_read_bytes: Some(10),
+            disk_written_bytes: Some(20),
+            net_sent_bytes: None,
+            net_received_bytes: None,
+            agents_running: 2,
+        };
+        let body = render_prometheus(
+            &[sample],
+            &[("running".into(), 1)],
+            &[("running".into(), 2)],
+        )
+        .unwrap();
+        assert!(body.contains(r#"mows_vm_cpu_seconds_total{vm_id="vm-1",vm_name="web"} 12.5"#), "{body}");
+        assert!(body.contains(r#"mows_vm_memory_rss_bytes{vm_id="vm-1",vm_name="web"} 1024"#), "{body}");
+        assert!(body.contains(r#"mows_vm_disk_written_bytes_total{vm_id="vm-1",vm_name="web"} 20"#), "{body}");
+        assert!(body.contains(r#"mows_vm_agents_running{vm_id="vm-1",vm_name="web"} 2"#), "{body}");
+        assert!(body.contains(r#"mows_vms{status="running"} 1"#), "{body}");
+        assert!(!body.contains("mows_vm_disk_overlay_bytes{"), "{body}");
+        assert!(body.ends_with("# EOF\n"), "{body}");
+    }
+}
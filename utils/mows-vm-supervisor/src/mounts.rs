@@ -0,0 +1,252 @@
+//! Extra host directories shared into a VM next to `/workspace` and
+//! `/creds` (`mounts` on `create_vm`, `mows vms run --mount`).
+//!
+//! A VM's mounts come from three places, most specific first:
+//!
+//! 1. the request's own `mounts`;
+//! 2. its preset's `mounts` (merged in by `crate::presets::apply`);
+//! 3. the defaults of the agent kind the VM is for — the kind's own
+//!    `mounts` plus `host_mounts.kinds.<kind>` from the config, e.g. a
+//!    shared cargo registry cache for every `claude` VM.
+//!
+//! A later source only fills guest paths an earlier one left free. Every
+//! host path goes through the same checks as the workspace path
+//! (`crate::qemu::validate_share_path`) and must then sit under one of
+//! `host_mounts.allowed_prefixes`; with no prefixes configured, extra
+//! mounts are refused outright. The resolved list is stored on the VM row
+//! with canonical host paths, so a clone or a restart shares exactly what
+//! the VM was created with.
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::agents::AgentKindName;
+use crate::config::HostMountConfig;
+use crate::error::{Result, SupervisorError};
+use crate::qemu::{validate_share_path, HostMount};
+
+/// Guest paths an extra mount may not shadow: the guest's own system
+/// directories and the mount points the supervisor already uses.
+const RESERVED_GUEST_PATHS: &[&str] = &[
+    "/bin",
+    "/boot",
+    "/creds",
+    "/dev",
+    "/etc",
+    "/lib",
+    "/mowsinit",
+    "/proc",
+    "/run",
+    "/sbin",
+    "/sys",
+    "/usr",
+    "/workspace",
+    "/workspace-base",
+];
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MountMode {
+    /// Read-only in the guest (QEMU `readonly=on`).
+    #[default]
+    Ro,
+    /// Writable; guest writes land in the host directory.
+    Rw,
+}
+
+/// One extra host directory shared into the guest.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct VmMount {
+    /// Absolute host directory; must sit under `host_mounts.allowed_prefixes`.
+    pub host_path: String,
+    /// Absolute path the directory is mounted at inside the guest.
+    pub guest_path: String,
+    #[serde(default)]
+    pub mode: MountMode,
+}
+
+impl VmMount {
+    pub fn to_host_mount(&self) -> HostMount {
+        HostMount {
+            host_path: PathBuf::from(&self.host_path),
+            guest_path: self.guest_path.clone(),
+            readonly: self.mode == MountMode::Ro,
+        }
+    }
+}
+
+/// Check a guest mount point without touching the filesystem.
+pub fn validate_guest_path(raw: &str) -> std::result::Result<(), String> {
+    let path = Path::new(raw);
+    if !path.is_absolute() {
+        return Err(format!("guest path must be absolute, got {raw:?}"));
+    }
+    if raw.chars().any(|c| c.is_whitespace() || c == ',') {
+        return Err(format!(
+            "guest path {raw:?} must not contain whitespace or commas"
+        ));
+    }
+    if path
+        .components()
+        .any(|c| matches!(c, Component::CurDir | Component::ParentDir))
+    {
+        return Err(format!("guest path {raw:?} must not contain . or .."));
+    }
+    if path.parent().is_none() {
+        return Err("guest path must not be /".into());
+    }
+    if let Some(reserved) = RESERVED_GUEST_PATHS
+        .iter()
+        .find(|reserved| path.starts_with(reserved))
+    {
+        return Err(format!("guest path {raw:?} is inside reserved {reserved}"));
+    }
+    Ok(())
+}
+
+/// Merge `requested` (request + preset mounts) with the defaults of
+/// `kind`, validate every entry and return the list to store on the VM.
+pub fn resolve(
+    config: &HostMountConfig,
+    kind: Option<AgentKindName>,
+    requested: Vec<VmMount>,
+) -> Result<Vec<VmMount>> {
+    let mut guest_paths = BTreeSet::new();
+    for mount in &requested {
+        if !guest_paths.insert(mount.guest_path.clone()) {
+            return Err(SupervisorError::BadRequest(format!(
+                "mounts: guest path {} is used twice",
+                mount.guest_path
+            )));
+        }
+    }
+    let mut mounts = requested;
+    if let Some(kind) = kind {
+        let builtin = crate::kinds::builtin(kind.as_str())
+            .map(|kind| kind.mounts)
+            .unwrap_or_default();
+        let configured = config.kinds.get(kind.as_str()).cloned().unwrap_or_default();
+        for mount in builtin.into_iter().chain(configured) {
+            if guest_paths.insert(mount.guest_path.clone()) {
+                mounts.push(mount);
+            }
+        }
+    }
+    if mounts.is_empty() {
+        return Ok(mounts);
+    }
+    if config.allowed_prefixes.is_empty() {
+        return Err(SupervisorError::BadRequest(
+            "extra host mounts are disabled: host_mounts.allowed_prefixes is empty".into(),
+        ));
+    }
+    // Canonicalise the prefixes too, so a symlinked prefix (`/home` ->
+    // `/var/home`) still matches the canonical host paths below.
+    let prefixes: Vec<PathBuf> = config
+        .allowed_prefixes
+        .iter()
+        .map(|prefix| std::fs::canonicalize(prefix).unwrap_or_else(|_| prefix.clone()))
+        .collect();
+    mounts
+        .into_iter()
+        .map(|mount| {
+            validate_guest_path(&mount.guest_path)
+                .map_err(|e| SupervisorError::BadRequest(format!("mounts: {e}")))?;
+            let canonical = validate_share_path("mount host path", &mount.host_path)?;
+            if !prefixes.iter().any(|prefix| canonical.starts_with(prefix)) {
+                return Err(SupervisorError::BadRequest(format!(
+                    "mount host path {} is outside host_mounts.allowed_prefixes",
+                    canonical.display()
+                )));
+            }
+            Ok(VmMount {
+                // validate_share_path already rejected non-UTF-8 paths.
+                host_path: canonical.to_string_lossy().into_owned(),
+                ..mount
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn mount(host: &Path, guest: &str) -> VmMount {
+        VmMount {
+            host_path: host.to_str().unwrap().to_string(),
+            guest_path: guest.to_string(),
+            mode: MountMode::Ro,
+        }
+    }
+
+    #[test]
+    fn guest_paths_are_checked() {
+        assert!(validate_guest_path("/data").is_ok());
+        assert!(validate_guest_path("/root/.cargo/registry").is_ok());
+        assert!(validate_guest_path("data").is_err());
+        assert!(validate_guest_path("/").is_err());
+        assert!(validate_guest_path("/data/../etc").is_err());
+        assert!(validate_guest_path("/my data").is_err());
+        assert!(validate_guest_path("/workspace/sub").is_err());
+        assert!(validate_guest_path("/etc").is_err());
+        // A prefix match on the string alone would reject this one.
+        assert!(validate_guest_path("/etcetera").is_ok());
+    }
+
+    #[test]
+    fn host_paths_must_sit_under_an_allowed_prefix() {
+        let allowed = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let mut config = HostMountConfig::default();
+        let inside = mount(allowed.path(), "/data");
+
+        let err = resolve(&config, None, vec![inside.clone()]).unwrap_err();
+        assert!(matches!(err, SupervisorError::BadRequest(ref msg) if msg.contains("disabled")));
+
+        config.allowed_prefixes = vec![allowed.path().to_path_buf()];
+        assert_eq!(resolve(&config, None, vec![inside.clone()]).unwrap().len(), 1);
+        let err = resolve(&config, None, vec![mount(outside.path(), "/data")]).unwrap_err();
+        assert!(matches!(err, SupervisorError::BadRequest(ref msg) if msg.contains("outside")));
+
+        let err = resolve(&config, None, vec![inside.clone(), inside]).unwrap_err();
+        assert!(matches!(err, SupervisorError::BadRequest(ref msg) if msg.contains("twice")));
+    }
+
+    #[test]
+    fn kind_defaults_fill_free_guest_paths_only() {
+        let allowed = tempfile::tempdir().unwrap();
+        let cache = allowed.path().join("cargo");
+        let other = allowed.path().join("other");
+        std::fs::create_dir(&cache).unwrap();
+        std::fs::create_dir(&other).unwrap();
+        let config = HostMountConfig {
+            allowed_prefixes: vec![allowed.path().to_path_buf()],
+            kinds: BTreeMap::from([(
+                "claude".to_string(),
+                vec![
+                    mount(&cache, "/root/.cargo/registry"),
+                    mount(&cache, "/data"),
+                ],
+            )]),
+        };
+        let resolved = resolve(
+            &config,
+            Some(AgentKindName::Claude),
+            vec![mount(&other, "/data")],
+        )
+        .unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert!(resolved[0].host_path.ends_with("other"));
+        assert_eq!(resolved[1].guest_path, "/root/.cargo/registry");
+        assert!(resolve(&config, Some(AgentKindName::Shell), Vec::new())
+            .unwrap()
+            .is_empty());
+    }
+}
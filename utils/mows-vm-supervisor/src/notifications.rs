@@ -0,0 +1,261 @@
+//! Agent notifications: an agent exited, or sits at an input prompt.
+//!
+//! The transitions themselves are detected in `crate::agent_runtime` (the
+//! liveness poll notices the tmux session is gone, `InputWatch` notices a
+//! still screen showing one of the kind's `input_prompts`) and emitted as
+//! `SupervisorEvent::{AgentExited, AgentWaitingForInput}`. From there they
+//! reach three sinks:
+//!
+//! - the `/v1/events` websocket, like every other event;
+//! - `GET /v1/agents/{id}/wait`, the long-poll behind `mows agents wait`
+//!   (which can raise a desktop notification on the caller's machine);
+//! - the webhooks in `notifications.webhooks`, served by
+//!   [`spawn_webhook_dispatcher`].
+//!
+//! Webhook deliveries are a JSON [`Notification`] POSTed with
+//! `X-Mows-Event`, `X-Mows-Timestamp` (unix seconds) and, when the hook has
+//! a `secret_file`, `X-Mows-Signature: sha256=<hex>` — the HMAC-SHA256 of
+//! `<timestamp>.<body>` under the file's bytes. Receivers should recompute
+//! it and reject stale timestamps. Failed deliveries are retried a few
+//! times, then dropped.
+
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::ToSchema;
+
+use crate::config::WebhookConfig;
+use crate::error::{Result, SupervisorError};
+use crate::events::SupervisorEvent;
+use crate::state::SharedState;
+
+/// Per-attempt timeout of a webhook delivery.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pauses before the second and third delivery attempt.
+const RETRY_DELAYS: [Duration; 2] = [Duration::from_secs(1), Duration::from_secs(5)];
+
+/// What a notification is about.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    AgentExited,
+    AgentWaitingForInput,
+}
+
+impl NotificationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::AgentExited => "agent_exited",
+            Self::AgentWaitingForInput => "agent_waiting_for_input",
+        }
+    }
+}
+
+/// A webhook's signing key. `Debug` is redacted so it can sit in
+/// `SupervisorConfig`.
+#[derive(Clone)]
+pub struct WebhookSecret(Vec<u8>);
+
+impl fmt::Debug for WebhookSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebhookSecret(..)")
+    }
+}
+
+impl WebhookSecret {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| {
+            SupervisorError::Config(format!(
+                "failed to read notifications webhook secret_file {}: {e}",
+                path.display()
+            ))
+        })?;
+        let trimmed = bytes.trim_ascii();
+        if trimmed.is_empty() {
+            return Err(SupervisorError::Config(format!(
+                "notifications webhook secret_file {} is empty",
+                path.display()
+            )));
+        }
+        Ok(Self(trimmed.to_vec()))
+    }
+
+    /// `sha256=<hex>` over `<timestamp>.<body>`.
+    pub fn sign(&self, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0)
+            .expect("HMAC-SHA256 accepts keys of any length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+/// Body of a webhook delivery.
+#[derive(Serialize, Clone, Debug)]
+pub struct Notification {
+    pub event: NotificationKind,
+    pub agent_id: String,
+    pub agent_name: Option<String>,
+    pub vm_id: String,
+    pub vm_name: Option<String>,
+    /// Final agent status (`stopped`, `failed`); exits only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i64>,
+    pub at: String,
+}
+
+impl Notification {
+    /// The notification an event stands for, if any, with the agent and
+    /// VM names looked up for the receiver's convenience.
+    async fn for_event(db: &SqlitePool, event: &SupervisorEvent) -> Option<Self> {
+        let (event_kind, agent_id, vm_id, status, exit_code) = match event {
+            SupervisorEvent::AgentExited {
+                id,
+                vm_id,
+                status,
+                exit_code,
+            } => (
+                NotificationKind::AgentExited,
+                id,
+                vm_id,
+                Some(status.clone()),
+                *exit_code,
+            ),
+            SupervisorEvent::AgentWaitingForInput { id, vm_id } => {
+                (NotificationKind::AgentWaitingForInput, id, vm_id, None, None)
+            }
+            _ => return None,
+        };
+        let names: Option<(String, Option<String>)> = sqlx::query_as(
+            "SELECT a.name, v.name FROM agents a LEFT JOIN vms v ON v.id = a.vm_id \
+             WHERE a.id = ?1",
+        )
+        .bind(agent_id)
+        .fetch_optional(db)
+        .await
+        .unwrap_or_default();
+        let (agent_name, vm_name) = match names {
+            Some((agent_name, vm_name)) => (Some(agent_name), vm_name),
+            None => (None, None),
+        };
+        Some(Self {
+            event: event_kind,
+            agent_id: agent_id.clone(),
+            agent_name,
+            vm_id: vm_id.clone(),
+            vm_name,
+            status,
+            exit_code,
+            at: Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+fn wants(webhook: &WebhookConfig, kind: NotificationKind) -> bool {
+    webhook.events.is_empty() || webhook.events.contains(&kind)
+}
+
+/// Forward agent notifications to `notifications.webhooks`. A no-op when
+/// none are configured.
+pub fn spawn_webhook_dispatcher(state: SharedState) {
+    if state.config.notifications.webhooks.is_empty() {
+        return;
+    }
+    let http = match reqwest::Client::builder().timeout(DELIVERY_TIMEOUT).build() {
+        Ok(http) => http,
+        Err(e) => {
+            tracing::error!(error = %e, "notification webhooks disabled: http client failed");
+            return;
+        }
+    };
+    let mut events = state.events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "notification dispatcher lagged; notifications lost");
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let Some(notification) = Notification::for_event(&state.db, &event).await else {
+                continue;
+            };
+            let body = match serde_json::to_vec(&notification) {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to encode notification");
+                    continue;
+                }
+            };
+            for webhook in &state.config.notifications.webhooks {
+                if wants(webhook, notification.event) {
+                    tokio::spawn(deliver(
+                        http.clone(),
+                        webhook.clone(),
+                        notification.event,
+                        body.clone(),
+                    ));
+                }
+            }
+        }
+    });
+}
+
+async fn deliver(http: reqwest::Client, webhook: WebhookConfig, kind: NotificationKind, body: Vec<u8>) {
+    let mut delays = RETRY_DELAYS.iter();
+    loop {
+        let timestamp = Utc::now().timestamp();
+        let mut request = http
+            .post(webhook.url.clone())
+            .header("Content-Type", "application/json")
+            .header("X-Mows-Event", kind.as_str())
+            .header("X-Mows-Timestamp", timestamp.to_string());
+        if let Some(secret) = &webhook.secret {
+            request = request.header("X-Mows-Signature", secret.sign(timestamp, &body));
+        }
+        let failure = match request.body(body.clone()).send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        match delays.next() {
+            Some(delay) => {
+                tracing::debug!(url = %webhook.url, error = %failure, "webhook delivery failed; retrying");
+                tokio::time::sleep(*delay).await;
+            }
+            None => {
+                tracing::warn!(url = %webhook.url, event = kind.as_str(), error = %failure, "webhook delivery failed; giving up");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_cover_timestamp_and_body() {
+        let secret = WebhookSecret(b"hook-key".to_vec());
+        let signature = secret.sign(1_700_000_000, br#"{"event":"agent_exited"}"#);
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"hook-key").unwrap();
+        mac.update(br#"1700000000.{"event":"agent_exited"}"#);
+        assert_eq!(signature, format!("sha256={}", hex::encode(mac.finalize().into_bytes())));
+        assert_ne!(signature, secret.sign(1_700_000_001, br#"{"event":"agent_exited"}"#));
+        assert_eq!(format!("{secret:?}"), "WebhookSecret(..)");
+    }
+}
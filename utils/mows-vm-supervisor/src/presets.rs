@@ -0,0 +1,233 @@
+//! Named VM presets (`presets:` in the supervisor config).
+//!
+//! A preset bundles what a `create_vm` request would otherwise spell out
+//! every time: resources, display and workspace mode, network policy,
+//! idle limits, extra host mounts, plus the environment and default agent
+//! kind for agents spawned in the VM. A request names one with `preset` and every field
+//! it sets explicitly wins over the preset's value; whatever neither sets
+//! falls back to `vm_defaults` as before.
+//!
+//! The VM row keeps the preset's name, env, mounts and agent kind, so editing the
+//! config later doesn't change VMs that are already running.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::agents::AgentKindName;
+use crate::api::vms::{CreateVmRequest, VmDisplayMode};
+use crate::egress::NetworkPolicy;
+use crate::error::{Result, SupervisorError};
+use crate::idle::IdleAction;
+use crate::kinds::is_valid_posix_env_name;
+use crate::mounts::{validate_guest_path, VmMount};
+use crate::workspace::WorkspaceMode;
+
+/// One entry under `presets:`. Every field is optional; unset ones leave
+/// the request (or `vm_defaults`) in charge.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VmPreset {
+    /// Shown next to the name in the web UI's preset picker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<u32>,
+    /// Name of a guest image (see `crate::images`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_mode: Option<VmDisplayMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_mode: Option<WorkspaceMode>,
+    /// `false` tells clients that send their working directory as `cwd`
+    /// (the CLI does) to leave it out, like `--no-workspace`. A `cwd`
+    /// that does arrive is still mounted.
+    #[serde(default = "default_mount_cwd")]
+    pub mount_cwd: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_policy: Option<NetworkPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_lifetime_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_action: Option<IdleAction>,
+    /// Kind of agent spawned when a create-agent request omits `kind`;
+    /// also picks the kind's default mounts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_kind: Option<AgentKindName>,
+    /// Extra host mounts; a request mount on the same guest path wins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<VmMount>,
+    /// Extra environment for every agent in the VM, layered over the
+    /// agent kind's own env.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+fn default_mount_cwd() -> bool {
+    true
+}
+
+impl VmPreset {
+    /// Reject presets that would only fail once a VM is created with them.
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if let Some(policy) = &self.network_policy {
+            policy.compile().map_err(|e| {
+                SupervisorError::Config(format!("presets.{name}.network_policy: {e}"))
+            })?;
+        }
+        for mount in &self.mounts {
+            validate_guest_path(&mount.guest_path).map_err(|e| {
+                SupervisorError::Config(format!("presets.{name}.mounts: {e}"))
+            })?;
+        }
+        check_env(&self.env).map_err(|key| {
+            SupervisorError::Config(format!(
+                "presets.{name}.env: {key:?} is not a valid POSIX env-var name"
+            ))
+        })
+    }
+}
+
+/// The first key of `env` that isn't a valid POSIX env-var name.
+pub fn check_env(env: &BTreeMap<String, String>) -> std::result::Result<(), String> {
+    match env.keys().find(|key| !is_valid_posix_env_name(key)) {
+        Some(key) => Err(key.clone()),
+        None => Ok(()),
+    }
+}
+
+/// What a VM keeps from its preset after `apply` that isn't a
+/// `CreateVmRequest` field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PresetExtras {
+    pub preset: Option<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+/// Fill the fields `request` leaves unset from the preset it names, and
+/// return what the VM row needs to remember. Request env keys override
+/// the preset's.
+pub fn apply(
+    presets: &BTreeMap<String, VmPreset>,
+    mut request: CreateVmRequest,
+) -> Result<(CreateVmRequest, PresetExtras)> {
+    let mut env = request.env.take().unwrap_or_default();
+    check_env(&env).map_err(|key| {
+        SupervisorError::BadRequest(format!("env: {key:?} is not a valid POSIX env-var name"))
+    })?;
+    let Some(name) = request.preset.clone() else {
+        return Ok((
+            request,
+            PresetExtras {
+                env,
+                ..PresetExtras::default()
+            },
+        ));
+    };
+    let preset = presets.get(&name).ok_or_else(|| {
+        SupervisorError::BadRequest(format!("unknown preset {name:?}"))
+    })?;
+    request.cpus = request.cpus.or(preset.cpus);
+    request.memory_mb = request.memory_mb.or(preset.memory_mb);
+    request.image = request.image.or_else(|| preset.image.clone());
+    request.display_mode = request.display_mode.or(preset.display_mode);
+    request.workspace_mode = request.workspace_mode.or(preset.workspace_mode);
+    request.network_policy = request
+        .network_policy
+        .or_else(|| preset.network_policy.clone());
+    request.idle_timeout_secs = request.idle_timeout_secs.or(preset.idle_timeout_secs);
+    request.max_lifetime_secs = request.max_lifetime_secs.or(preset.max_lifetime_secs);
+    request.idle_action = request.idle_action.or(preset.idle_action);
+    request.agent_kind = request.agent_kind.or(preset.agent_kind);
+    if !preset.mounts.is_empty() {
+        let mut mounts = request.mounts.take().unwrap_or_default();
+        for mount in &preset.mounts {
+            if !mounts.iter().any(|m| m.guest_path == mount.guest_path) {
+                mounts.push(mount.clone());
+            }
+        }
+        request.mounts = Some(mounts);
+    }
+    for (key, value) in &preset.env {
+        env.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    let extras = PresetExtras {
+        preset: Some(name),
+        env,
+    };
+    Ok((request, extras))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(json: serde_json::Value) -> CreateVmRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn presets() -> BTreeMap<String, VmPreset> {
+        let yaml = r#"
+big:
+  cpus: 8
+  memory_mb: 16384
+  workspace_mode: overlay
+  agent_kind: claude
+  mounts:
+    - host_path: /srv/cargo
+      guest_path: /root/.cargo/registry
+    - host_path: /srv/data
+      guest_path: /data
+  env:
+    CARGO_HOME: /cache/cargo
+    RUST_LOG: info
+"#;
+        serde_yaml_neo::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn explicit_fields_override_the_preset() {
+        let (request, extras) = apply(
+            &presets(),
+            request(serde_json::json!({
+                "preset": "big",
+                "cpus": 2,
+                "mounts": [{"host_path": "/home/me/data", "guest_path": "/data", "mode": "rw"}],
+                "env": {"RUST_LOG": "debug"}
+            })),
+        )
+        .unwrap();
+        assert_eq!(request.cpus, Some(2));
+        assert_eq!(request.memory_mb, Some(16384));
+        assert_eq!(request.workspace_mode, Some(WorkspaceMode::Overlay));
+        assert_eq!(extras.preset.as_deref(), Some("big"));
+        assert_eq!(request.agent_kind, Some(AgentKindName::Claude));
+        let mounts = request.mounts.unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].host_path, "/home/me/data");
+        assert_eq!(mounts[1].guest_path, "/root/.cargo/registry");
+        assert_eq!(extras.env["RUST_LOG"], "debug");
+        assert_eq!(extras.env["CARGO_HOME"], "/cache/cargo");
+    }
+
+    #[test]
+    fn unknown_preset_and_bad_env_are_rejected() {
+        let err = apply(&presets(), request(serde_json::json!({"preset": "huge"})))
+            .map(drop)
+            .unwrap_err();
+        assert!(matches!(err, SupervisorError::BadRequest(ref msg) if msg.contains("huge")));
+        let err = apply(&presets(), request(serde_json::json!({"env": {"A;B": "x"}})))
+            .map(drop)
+            .unwrap_err();
+        assert!(matches!(err, SupervisorError::BadRequest(_)));
+        let mut broken = presets();
+        broken.get_mut("big").unwrap().env.insert("1X".into(), "y".into());
+        assert!(broken["big"].validate("big").is_err());
+    }
+}
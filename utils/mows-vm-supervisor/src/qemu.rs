@@ -1,14 +1,26 @@
 //! QEMU spawner — pure VM concern (no notion of agent kind).
 //!
 //! Builds an argv for `qemu-system-x86_64` that boots a VM with:
-//! - the cached Alpine qcow2 backing disk (writable overlay per VM)
-//! - 9p `workspace` share, read-write, mounted to `/workspace`
+//! - the guest image's qcow2 backing disk (writable overlay per VM; see
+//!   `crate::images`)
+//! - for cloud images, the per-VM cloud-init seed ISO as a read-only
+//!   virtio disk (see `crate::cloud_init`)
+//! - 9p `workspace` share, read-write, mounted to `/workspace` (the host
+//!   directory itself, or a per-VM copy/worktree — see `crate::workspace`)
+//! - for `overlay` workspaces, 9p `workspacebase` share of the original,
+//!   read-only, mounted to `/workspace-base`; for `worktree` workspaces,
+//!   9p `gitdir` share of the repository's git dir at its host path
 //! - 9p `creds` share, read-only, mounted to `/creds` (forwarded
 //!   `~/.claude` on the host; agents that need it set `CLAUDE_CONFIG_DIR`)
+//! - one 9p `mountN` share per extra host mount (see `crate::mounts`),
+//!   mounted where `/mowsinit/mounts` says
 //! - sshd port-forward `host_ssh_port → guest:22`
 //! - dockerd port-forward `host_docker_port → guest:2375`
+//! - for non-`open` network policies, `restrict=on` plus a guestfwd to
+//!   the per-VM egress proxy socket (see `crate::egress`)
 //! - VNC display bound to a per-VM unix socket (proxied as websocket)
 //! - serial console on a chardev unix socket with `logfile=` for persistence
+//! - QMP monitor on a per-VM unix socket (snapshots, see `crate::qmp`)
 //!
 //! Agents (claude, shell, …) are spawned as ssh-launched processes inside
 //! a running VM by `agent_runtime`, completely separate from this module.
@@ -20,6 +32,7 @@ use std::process::Stdio;
 use tokio::process::{Child, Command};
 
 use crate::config::{PortRange, SupervisorConfig};
+use crate::egress::{egress_socket_for, guest_proxy_env, NetworkPolicy, GUEST_EGRESS_PROXY_ADDR};
 use crate::error::{Result, SupervisorError};
 
 #[derive(Debug, Clone)]
@@ -39,43 +52,55 @@ pub struct VmResources {
 /// path (resolving symlinks and traversals), require it to be an existing
 /// directory, and reject any embedded comma or newline.
 pub fn validate_workspace_path(raw: &str) -> Result<PathBuf> {
+    validate_share_path("workspace path", raw)
+}
+
+/// The checks behind `validate_workspace_path`, for any host directory
+/// handed to QEMU as a 9p share. `what` names the path in errors.
+pub fn validate_share_path(what: &str, raw: &str) -> Result<PathBuf> {
     if raw.is_empty() {
-        return Err(SupervisorError::BadRequest(
-            "workspace path must not be empty".into(),
-        ));
+        return Err(SupervisorError::BadRequest(format!(
+            "{what} must not be empty"
+        )));
     }
     let path = std::path::Path::new(raw);
     if !path.is_absolute() {
         return Err(SupervisorError::BadRequest(format!(
-            "workspace path must be absolute, got {raw:?}"
+            "{what} must be absolute, got {raw:?}"
         )));
     }
     let canonical = std::fs::canonicalize(path).map_err(|e| {
-        SupervisorError::BadRequest(format!(
-            "workspace path {raw:?} could not be resolved: {e}"
-        ))
+        SupervisorError::BadRequest(format!("{what} {raw:?} could not be resolved: {e}"))
     })?;
     if !canonical.is_dir() {
         return Err(SupervisorError::BadRequest(format!(
-            "workspace path {} is not a directory",
+            "{what} {} is not a directory",
             canonical.display()
         )));
     }
     let canonical_str = canonical.to_str().ok_or_else(|| {
         SupervisorError::BadRequest(format!(
-            "workspace path {} is not valid UTF-8",
+            "{what} {} is not valid UTF-8",
             canonical.display()
         ))
     })?;
     if canonical_str.contains(',') || canonical_str.contains('\n') {
         return Err(SupervisorError::BadRequest(format!(
-            "workspace path {canonical_str:?} contains comma or newline, which \
+            "{what} {canonical_str:?} contains comma or newline, which \
              would break QEMU -fsdev argument parsing"
         )));
     }
     Ok(canonical)
 }
 
+/// An extra 9p share, already validated by `crate::mounts::resolve`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostMount {
+    pub host_path: PathBuf,
+    pub guest_path: String,
+    pub readonly: bool,
+}
+
 /// Matches `api::vms::VmDisplayMode`; duplicated here to keep the qemu
 /// module independent of the API layer's serde derivations.
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -93,11 +118,19 @@ pub struct VmLaunchSpec {
     pub image_path: PathBuf,
     /// Kernel + initramfs extracted from the same rootfs as `image_path`.
     /// The supervisor boots via `-kernel`/`-initrd` so the qcow2 doesn't
-    /// need its own bootloader.
-    pub kernel_path: PathBuf,
-    pub initrd_path: PathBuf,
+    /// need its own bootloader. `None` boots the disk's own bootloader
+    /// (cloud images, and the e2e suite's stub qcow2s).
+    pub kernel_path: Option<PathBuf>,
+    pub initrd_path: Option<PathBuf>,
+    /// NoCloud seed ISO for cloud images, attached as a read-only disk.
+    pub seed_iso: Option<PathBuf>,
     pub state_dir: PathBuf,
     pub workspace: Option<PathBuf>,
+    /// Read-only original behind an `overlay` workspace.
+    pub workspace_base: Option<PathBuf>,
+    /// Common git dir of a `worktree` workspace. The guest mounts it at
+    /// the same path so the worktree's `.git` pointer resolves.
+    pub workspace_gitdir: Option<PathBuf>,
     pub host_ssh_port: u16,
     pub host_docker_port: u16,
     pub resources: VmResources,
@@ -105,6 +138,11 @@ pub struct VmLaunchSpec {
     /// `headless` (default) emits `-display none`; `desktop` emits a virtio
     /// GPU plus serial console for VNC access via the per-VM display socket.
     pub display_mode: DisplayMode,
+    /// Outbound network policy. Anything but `open` cuts the guest off
+    /// from slirp's NAT and routes it through the egress proxy.
+    pub network_policy: NetworkPolicy,
+    /// Extra host directories, shared as `mount0`, `mount1`, … in order.
+    pub mounts: Vec<HostMount>,
 }
 
 #[derive(Debug, Clone)]
@@ -115,8 +153,13 @@ pub struct QemuInvocation {
     pub console_log_path: PathBuf,
     pub display_socket_path: PathBuf,
     pub console_socket_path: PathBuf,
+    pub qmp_socket_path: PathBuf,
 }
 
+/// `-drive id=` of the overlay disk. QMP block commands address the disk
+/// by this id, so it must stay stable across releases.
+pub const OVERLAY_DRIVE_ID: &str = "disk0";
+
 impl QemuInvocation {
     pub fn build(config: &SupervisorConfig, spec: &VmLaunchSpec) -> Result<Self> {
         let vm_dir = vm_dir_for(&spec.state_dir, &spec.vm_id);
@@ -124,6 +167,7 @@ impl QemuInvocation {
         let console_log_path = vm_dir.join("console.log");
         let display_socket_path = vm_dir.join("display.sock");
         let console_socket_path = vm_dir.join("console.sock");
+        let qmp_socket_path = vm_dir.join("qmp.sock");
         // Resolved at startup in `SupervisorConfig::load`; reading the env
         // here on every VM spawn would violate the "all env vars upfront"
         // rule and is also racy with `std::env::set_var`.
@@ -132,8 +176,6 @@ impl QemuInvocation {
         // Borrow the spec's PathBufs directly — `spec` is already borrowed
         // for the lifetime of `build`, so the clones (TECH-RUST-16) were
         // gratuitous.
-        let kernel_path = &spec.kernel_path;
-        let initrd_path = &spec.initrd_path;
         let display_args: Vec<String> = match spec.display_mode {
             DisplayMode::Headless => vec!["-display".to_string(), "none".to_string()],
             DisplayMode::Desktop => vec![
@@ -165,18 +207,29 @@ impl QemuInvocation {
             ),
             "-serial".to_string(),
             "chardev:ser0".to_string(),
+            "-qmp".to_string(),
+            format!("unix:{},server=on,wait=off", qmp_socket_path.display()),
             "-drive".to_string(),
             format!(
-                "file={},if=virtio,cache=none,format=qcow2,discard=unmap",
+                "file={},id={OVERLAY_DRIVE_ID},if=virtio,cache=none,format=qcow2,discard=unmap",
                 overlay_path.display()
             ),
         ]);
-        if kernel_path.exists() {
+        if let Some(seed_iso) = &spec.seed_iso {
+            args.extend([
+                "-drive".to_string(),
+                format!(
+                    "file={},if=virtio,readonly=on,format=raw",
+                    seed_iso.display()
+                ),
+            ]);
+        }
+        if let Some(kernel_path) = &spec.kernel_path {
             args.extend([
                 "-kernel".to_string(),
                 kernel_path.display().to_string(),
             ]);
-            if initrd_path.exists() {
+            if let Some(initrd_path) = &spec.initrd_path {
                 args.extend([
                     "-initrd".to_string(),
                     initrd_path.display().to_string(),
@@ -187,12 +240,23 @@ impl QemuInvocation {
                 "root=/dev/vda rw rootfstype=ext4 console=ttyS0,115200 ip=dhcp".to_string(),
             ]);
         }
+        let mut netdev = format!(
+            "user,id=net0,hostfwd=tcp:127.0.0.1:{}-:22,hostfwd=tcp:127.0.0.1:{}-:2375",
+            spec.host_ssh_port, spec.host_docker_port
+        );
+        if !spec.network_policy.is_open() {
+            // `restrict=on` drops every guest-initiated connection but keeps
+            // the hostfwds above. The guestfwd's `cmd:` form spawns one
+            // socat per connection; the chardev form would only ever serve
+            // the first one.
+            netdev.push_str(&format!(
+                ",restrict=on,guestfwd=tcp:{GUEST_EGRESS_PROXY_ADDR}-cmd:socat STDIO UNIX-CONNECT:{}",
+                egress_socket_for(&vm_dir).display()
+            ));
+        }
         args.extend([
             "-netdev".to_string(),
-            format!(
-                "user,id=net0,hostfwd=tcp:127.0.0.1:{}-:22,hostfwd=tcp:127.0.0.1:{}-:2375",
-                spec.host_ssh_port, spec.host_docker_port
-            ),
+            netdev,
             "-device".to_string(),
             "virtio-net-pci,netdev=net0".to_string(),
             "-no-reboot".to_string(),
@@ -210,6 +274,30 @@ impl QemuInvocation {
             ]);
         }
 
+        if let Some(base) = &spec.workspace_base {
+            args.extend([
+                "-fsdev".to_string(),
+                format!(
+                    "local,id=wsbase,path={},security_model=mapped-xattr,readonly=on",
+                    base.display()
+                ),
+                "-device".to_string(),
+                "virtio-9p-pci,fsdev=wsbase,mount_tag=workspacebase".to_string(),
+            ]);
+        }
+
+        if let Some(gitdir) = &spec.workspace_gitdir {
+            args.extend([
+                "-fsdev".to_string(),
+                format!(
+                    "local,id=gitdir,path={},security_model=mapped-xattr",
+                    gitdir.display()
+                ),
+                "-device".to_string(),
+                "virtio-9p-pci,fsdev=gitdir,mount_tag=gitdir".to_string(),
+            ]);
+        }
+
         if let Some(creds) = &creds_host_path {
             args.extend([
                 "-fsdev".to_string(),
@@ -222,6 +310,20 @@ impl QemuInvocation {
             ]);
         }
 
+        for (index, mount) in spec.mounts.iter().enumerate() {
+            let tag = format!("mount{index}");
+            args.extend([
+                "-fsdev".to_string(),
+                format!(
+                    "local,id={tag},path={},security_model=mapped-xattr{}",
+                    mount.host_path.display(),
+                    if mount.readonly { ",readonly=on" } else { "" }
+                ),
+                "-device".to_string(),
+                format!("virtio-9p-pci,fsdev={tag},mount_tag={tag}"),
+            ]);
+        }
+
         // The vminit 9p share carries the per-VM run.yaml + authorized_keys.
         args.extend([
             "-fsdev".to_string(),
@@ -240,6 +342,7 @@ impl QemuInvocation {
             console_log_path,
             display_socket_path,
             console_socket_path,
+            qmp_socket_path,
         })
     }
 }
@@ -256,6 +359,135 @@ pub fn console_socket_for(state_dir: &Path, vm_id: &str) -> PathBuf {
     vm_dir_for(state_dir, vm_id).join("console.sock")
 }
 
+pub fn qmp_socket_for(state_dir: &Path, vm_id: &str) -> PathBuf {
+    vm_dir_for(state_dir, vm_id).join("qmp.sock")
+}
+
+pub fn overlay_path_for(state_dir: &Path, vm_id: &str) -> PathBuf {
+    vm_dir_for(state_dir, vm_id).join("disk.qcow2")
+}
+
+/// Offline internal-snapshot operation on a qcow2 overlay, run through
+/// `qemu-img snapshot`. Only valid while no QEMU process holds the image
+/// open — running VMs go through QMP instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskSnapshotOp {
+    Create,
+    Apply,
+    Delete,
+}
+
+impl DiskSnapshotOp {
+    fn flag(self) -> &'static str {
+        match self {
+            Self::Create => "-c",
+            Self::Apply => "-a",
+            Self::Delete => "-d",
+        }
+    }
+}
+
+pub async fn qemu_img_snapshot(overlay: &Path, op: DiskSnapshotOp, tag: &str) -> Result<()> {
+    let output = Command::new("qemu-img")
+        .arg("snapshot")
+        .arg(op.flag())
+        .arg(tag)
+        .arg(overlay)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| SupervisorError::QemuSpawn(format!("failed to exec qemu-img: {e}")))?;
+    if !output.status.success() {
+        // stderr names the overlay's absolute path; keep it in the operator
+        // log rather than in the (public) error message.
+        tracing::warn!(
+            overlay = %overlay.display(),
+            stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+            "qemu-img snapshot failed"
+        );
+        return Err(SupervisorError::QemuSpawn(format!(
+            "qemu-img snapshot {} {tag} exited with {}",
+            op.flag(),
+            output.status
+        )));
+    }
+    Ok(())
+}
+
+/// Copy a paused VM's overlay to `target` — a reflink where the
+/// filesystem supports it, so the VM only stays paused for a metadata
+/// copy. The result keeps `overlay`'s backing reference and internal
+/// snapshots; `flatten_overlay` turns it into a clean disk.
+pub async fn copy_overlay(overlay: &Path, target: &Path) -> Result<()> {
+    let output = Command::new("cp")
+        .arg("--reflink=auto")
+        .arg("--sparse=always")
+        .arg(overlay)
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| SupervisorError::QemuSpawn(format!("failed to exec cp: {e}")))?;
+    if !output.status.success() {
+        tracing::warn!(
+            overlay = %overlay.display(),
+            stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+            "overlay copy failed"
+        );
+        return Err(SupervisorError::QemuSpawn(format!(
+            "copying the disk overlay exited with {}",
+            output.status
+        )));
+    }
+    Ok(())
+}
+
+/// Write `source`'s guest-visible data above `image` into a new overlay
+/// at `target` backed by `image` (relative, as in `prepare_vm_dir`).
+/// Internal snapshots are not carried over. `-U` lets this read an
+/// overlay a stopped-but-registered QEMU may still hold a lock on.
+pub async fn flatten_overlay(source: &Path, target: &Path, image: &Path) -> Result<()> {
+    let backing_arg: std::ffi::OsString = relative_backing_path(target, image)
+        .map(Into::into)
+        .unwrap_or_else(|| image.as_os_str().to_os_string());
+    let output = Command::new("qemu-img")
+        .arg("convert")
+        .arg("-U")
+        .arg("-q")
+        .arg("-f")
+        .arg("qcow2")
+        .arg("-O")
+        .arg("qcow2")
+        .arg("-F")
+        .arg("qcow2")
+        .arg("-B")
+        .arg(&backing_arg)
+        .arg(source)
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| SupervisorError::QemuSpawn(format!("failed to exec qemu-img: {e}")))?;
+    if !output.status.success() {
+        tracing::warn!(
+            source = %source.display(),
+            stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+            "qemu-img convert failed"
+        );
+        return Err(SupervisorError::QemuSpawn(format!(
+            "qemu-img convert exited with {}",
+            output.status
+        )));
+    }
+    Ok(())
+}
+
 /// In-guest config; `mows-agent-init` (an OpenRC service in the image) reads
 /// this from the `mowsinit` 9p mount on boot. The VM no longer auto-launches
 /// any specific agent — that's done explicitly via `agent_runtime` once the
@@ -265,6 +497,9 @@ pub struct GuestVmConfig {
     pub vm_id: String,
     pub vm_name: String,
     pub authorized_ssh_pubkey: String,
+    /// Where to mount the `gitdir` share (`worktree` workspaces only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace_gitdir: Option<String>,
 }
 
 impl GuestVmConfig {
@@ -273,6 +508,10 @@ impl GuestVmConfig {
             vm_id: spec.vm_id.clone(),
             vm_name: spec.vm_name.clone(),
             authorized_ssh_pubkey: spec.authorized_ssh_pubkey.clone(),
+            workspace_gitdir: spec
+                .workspace_gitdir
+                .as_ref()
+                .map(|path| path.display().to_string()),
         }
     }
 }
@@ -293,6 +532,31 @@ pub async fn prepare_vm_dir(spec: &VmLaunchSpec) -> Result<()> {
     )
     .await?;
 
+    // `mows-agent-init` installs this as /etc/profile.d/mows-agent.sh, so
+    // login shells pick up the egress proxy without any guest-side config.
+    if !spec.network_policy.is_open() {
+        let profile: String = guest_proxy_env()
+            .into_iter()
+            .map(|(key, value)| format!("export {key}={value}\n"))
+            .collect();
+        tokio::fs::write(vm_dir.join("profile.sh"), profile).await?;
+    }
+
+    // `mows-agent-init` mounts each line's share: `<tag> <ro|rw> <guest path>`.
+    // Guest paths can't contain whitespace (`crate::mounts` rejects it).
+    if !spec.mounts.is_empty() {
+        let lines: String = spec
+            .mounts
+            .iter()
+            .enumerate()
+            .map(|(index, mount)| {
+                let mode = if mount.readonly { "ro" } else { "rw" };
+                format!("mount{index} {mode} {}\n", mount.guest_path)
+            })
+            .collect();
+        tokio::fs::write(vm_dir.join("mounts"), lines).await?;
+    }
+
     let overlay = vm_dir.join("disk.qcow2");
     if !overlay.exists() {
         // SLOP-48: store the backing reference as a path relative to the
@@ -370,10 +634,11 @@ fn relative_backing_path(overlay: &Path, image: &Path) -> Option<PathBuf> {
 /// Resolve the qcow2 + kernel + initramfs paths for a (distro, flavor)
 /// combination. Each image-builder variant lands as
 /// `<distro>-<flavor>-mows-agent-<arch>.{qcow2,vmlinuz,initramfs}`.
+/// `kernel`/`initramfs` are `None` when the disk boots itself.
 pub struct ImageArtifacts {
     pub qcow2: PathBuf,
-    pub kernel: PathBuf,
-    pub initramfs: PathBuf,
+    pub kernel: Option<PathBuf>,
+    pub initramfs: Option<PathBuf>,
 }
 
 pub fn locate_image(
@@ -415,8 +680,8 @@ pub fn locate_image(
     }
     Ok(ImageArtifacts {
         qcow2,
-        kernel,
-        initramfs,
+        kernel: kernel_present.then_some(kernel),
+        initramfs: initrd_present.then_some(initramfs),
     })
 }
 
@@ -431,6 +696,8 @@ pub fn locate_image(
 /// climbs.
 pub struct PortAllocator {
     range: PortRange,
+    /// Config key named in the `PortExhausted` hint.
+    setting: &'static str,
     inner: std::sync::Mutex<PortAllocatorInner>,
 }
 
@@ -444,6 +711,7 @@ impl PortAllocator {
         let start = range.start;
         Self {
             range,
+            setting: "port_range",
             inner: std::sync::Mutex::new(PortAllocatorInner {
                 in_use: std::collections::BTreeSet::new(),
                 cursor: start,
@@ -467,6 +735,37 @@ impl PortAllocator {
         allocator
     }
 
+    /// Name the config key to widen when this allocator runs dry. The
+    /// forward allocator (`forward_port_range`) sets it.
+    pub fn for_setting(mut self, setting: &'static str) -> Self {
+        self.setting = setting;
+        self
+    }
+
+    /// Allocate a single port, as for a runtime port forward.
+    pub fn allocate(&self) -> Result<u16> {
+        let mut inner = self.inner.lock().expect("port allocator mutex poisoned");
+        let port = self.next_free(&mut inner)?;
+        inner.in_use.insert(port);
+        Ok(port)
+    }
+
+    /// Claim a caller-chosen port. `BadRequest` if it lies outside the
+    /// range, `Conflict` if it is already handed out.
+    pub fn reserve(&self, port: u16) -> Result<()> {
+        if port < self.range.start || port > self.range.end {
+            return Err(SupervisorError::BadRequest(format!(
+                "port {port} is outside {} ({}-{})",
+                self.setting, self.range.start, self.range.end
+            )));
+        }
+        let mut inner = self.inner.lock().expect("port allocator mutex poisoned");
+        if !inner.in_use.insert(port) {
+            return Err(SupervisorError::Conflict(format!("port {port} is already in use")));
+        }
+        Ok(())
+    }
+
     pub fn allocate_pair(&self) -> Result<(u16, u16)> {
         let mut inner = self.inner.lock().expect("port allocator mutex poisoned");
         let ssh = self.next_free(&mut inner)?;
@@ -498,9 +797,10 @@ impl PortAllocator {
                 return Ok(candidate);
             }
         }
-        Err(SupervisorError::PortExhausted(
-            "widen port_range in config".to_string(),
-        ))
+        Err(SupervisorError::PortExhausted(format!(
+            "widen {} in config",
+            self.setting
+        )))
     }
 }
 
@@ -580,15 +880,20 @@ mod tests {
             vm_id: "id-123".into(),
             vm_name: "demo".into(),
             image_path: PathBuf::from("/var/lib/mows-agent/images/alpine.qcow2"),
-            kernel_path: PathBuf::from("/var/lib/mows-agent/images/alpine.vmlinuz"),
-            initrd_path: PathBuf::from("/var/lib/mows-agent/images/alpine.initramfs"),
+            kernel_path: Some(PathBuf::from("/var/lib/mows-agent/images/alpine.vmlinuz")),
+            initrd_path: Some(PathBuf::from("/var/lib/mows-agent/images/alpine.initramfs")),
+            seed_iso: None,
             state_dir: PathBuf::from("/tmp/mows-agent-test"),
             workspace: Some(PathBuf::from("/home/x/proj")),
+            workspace_base: None,
+            workspace_gitdir: None,
             host_ssh_port: 22001,
             host_docker_port: 22501,
             resources: VmResources { cpus: 2, memory_mb: 2048 },
             authorized_ssh_pubkey: "ssh-ed25519 AAAA test".into(),
             display_mode: DisplayMode::Headless,
+            network_policy: NetworkPolicy::default(),
+            mounts: Vec::new(),
         }
     }
 
@@ -654,6 +959,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn invocation_binds_qmp_unix_socket_and_names_overlay_drive() {
+        let config = SupervisorConfig::defaults_for_tests();
+        let inv = QemuInvocation::build(&config, &test_spec()).unwrap();
+        let joined = inv.args.join(" ");
+        assert!(joined.contains("-qmp unix:/tmp/mows-agent-test/vms/id-123/qmp.sock,server=on,wait=off"));
+        assert!(joined.contains(&format!("id={OVERLAY_DRIVE_ID},if=virtio")));
+        assert_eq!(
+            qmp_socket_for(&PathBuf::from("/tmp/mows-agent-test"), "id-123"),
+            inv.qmp_socket_path,
+        );
+    }
+
     #[test]
     fn invocation_includes_port_forwards() {
         let config = SupervisorConfig::defaults_for_tests();
@@ -663,6 +981,29 @@ mod tests {
         assert!(joined.contains("hostfwd=tcp:127.0.0.1:22501-:2375"));
     }
 
+    #[test]
+    fn open_network_policy_keeps_unrestricted_netdev() {
+        let config = SupervisorConfig::defaults_for_tests();
+        let inv = QemuInvocation::build(&config, &test_spec()).unwrap();
+        let joined = inv.args.join(" ");
+        assert!(!joined.contains("restrict=on"));
+        assert!(!joined.contains("guestfwd"));
+    }
+
+    #[test]
+    fn filtered_network_policy_restricts_and_forwards_to_egress_proxy() {
+        let config = SupervisorConfig::defaults_for_tests();
+        let mut spec = test_spec();
+        spec.network_policy = NetworkPolicy {
+            mode: crate::egress::NetworkPolicyMode::DenyAll,
+            ..NetworkPolicy::default()
+        };
+        let inv = QemuInvocation::build(&config, &spec).unwrap();
+        let joined = inv.args.join(" ");
+        assert!(joined.contains("hostfwd=tcp:127.0.0.1:22001-:22"));
+        assert!(joined.contains(",restrict=on,guestfwd=tcp:10.0.2.100:3128-cmd:socat STDIO UNIX-CONNECT:/tmp/mows-agent-test/vms/id-123/egress.sock"));
+    }
+
     #[test]
     fn invocation_omits_workspace_when_absent() {
         let config = SupervisorConfig::defaults_for_tests();
@@ -674,6 +1015,22 @@ mod tests {
         assert!(joined.contains("mount_tag=mowsinit"));
     }
 
+    #[test]
+    fn invocation_shares_overlay_base_read_only_and_worktree_gitdir() {
+        let config = SupervisorConfig::defaults_for_tests();
+        let mut spec = test_spec();
+        spec.workspace_base = Some(PathBuf::from("/home/x/orig"));
+        spec.workspace_gitdir = Some(PathBuf::from("/home/x/orig/.git"));
+        let inv = QemuInvocation::build(&config, &spec).unwrap();
+        let joined = inv.args.join(" ");
+        assert!(joined.contains(
+            "local,id=wsbase,path=/home/x/orig,security_model=mapped-xattr,readonly=on"
+        ));
+        assert!(joined.contains("mount_tag=workspacebase"));
+        assert!(joined.contains("local,id=gitdir,path=/home/x/orig/.git,security_model=mapped-xattr "));
+        assert!(joined.contains("mount_tag=gitdir"));
+    }
+
     #[test]
     fn port_allocator_returns_distinct_pairs() {
         let alloc = PortAllocator::new(PortRange { start: 22000, end: 22010 });
@@ -721,6 +1078,108 @@ mod tests {
         assert!(alloc.allocate_pair().is_err());
     }
 
+    #[test]
+    fn port_allocator_hands_out_and_reserves_single_ports() {
+        let alloc = PortAllocator::new(PortRange { start: 23000, end: 23002 })
+            .for_setting("forward_port_range");
+        alloc.reserve(23000).unwrap();
+        assert!(matches!(alloc.reserve(23000), Err(SupervisorError::Conflict(_))));
+        assert!(matches!(alloc.reserve(22999), Err(SupervisorError::BadRequest(_))));
+        assert_eq!(alloc.allocate().unwrap(), 23001);
+        assert_eq!(alloc.allocate().unwrap(), 23002);
+        match alloc.allocate() {
+            Err(SupervisorError::PortExhausted(msg)) => assert!(msg.contains("forward_port_range")),
+            other => panic!("expected PortExhausted, got {other:?}"),
+        }
+        alloc.release([23000]);
+        assert_eq!(alloc.allocate().unwrap(), 23000);
+    }
+
+    #[test]
+    fn cloud_image_boots_its_own_bootloader_with_a_seed_disk() {
+        let config = SupervisorConfig::defaults_for_tests();
+        let mut spec = test_spec();
+        let direct = QemuInvocation::build(&config, &spec).unwrap().args.join(" ");
+        assert!(direct.contains("-kernel /var/lib/mows-agent/images/alpine.vmlinuz"));
+        assert!(!direct.contains("seed.iso"));
+
+        spec.kernel_path = None;
+        spec.initrd_path = None;
+        spec.seed_iso = Some(PathBuf::from("/tmp/mows-agent-test/vms/id-123/seed.iso"));
+        let cloud = QemuInvocation::build(&config, &spec).unwrap().args.join(" ");
+        assert!(!cloud.contains("-kernel"));
+        assert!(!cloud.contains("-append"));
+        assert!(cloud.contains(
+            "file=/tmp/mows-agent-test/vms/id-123/seed.iso,if=virtio,readonly=on,format=raw"
+        ));
+    }
+
+    #[test]
+    fn invocation_shares_extra_mounts_in_order() {
+        let config = SupervisorConfig::defaults_for_tests();
+        let mut spec = test_spec();
+        spec.mounts = vec![
+            HostMount {
+                host_path: PathBuf::from("/srv/cargo-registry"),
+                guest_path: "/root/.cargo/registry".into(),
+                readonly: false,
+            },
+            HostMount {
+                host_path: PathBuf::from("/srv/datasets"),
+                guest_path: "/data".into(),
+                readonly: true,
+            },
+        ];
+        let joined = QemuInvocation::build(&config, &spec).unwrap().args.join(" ");
+        assert!(joined.contains(
+            "local,id=mount0,path=/srv/cargo-registry,security_model=mapped-xattr -device"
+        ));
+        assert!(joined.contains(
+            "local,id=mount1,path=/srv/datasets,security_model=mapped-xattr,readonly=on"
+        ));
+        assert!(joined.contains("virtio-9p-pci,fsdev=mount1,mount_tag=mount1"));
+    }
+
+    fn qemu_img(args: &[&str]) -> std::process::Output {
+        let output = std::process::Command::new("qemu-img").args(args).output().unwrap();
+        assert!(
+            output.status.success(),
+            "qemu-img {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    #[tokio::test]
+    async fn flatten_overlay_keeps_the_data_and_drops_internal_snapshots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let image = tmp.path().join("images/base.qcow2");
+        let source = tmp.path().join("vms/source/disk.qcow2");
+        let target = tmp.path().join("vms/clone/disk.qcow2");
+        let guest_data = tmp.path().join("guest.raw");
+        for path in [&image, &source, &target] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        }
+        std::fs::write(&guest_data, vec![0xa5u8; 1 << 20]).unwrap();
+        let [image_arg, source_arg, target_arg, guest_data_arg] =
+            [&image, &source, &target, &guest_data].map(|path| path.to_str().unwrap());
+        qemu_img(&["create", "-q", "-f", "qcow2", image_arg, "1M"]);
+        // An overlay with guest data above the (empty) image.
+        qemu_img(&[
+            "convert", "-f", "raw", "-O", "qcow2", "-F", "qcow2", "-B", image_arg, guest_data_arg,
+            source_arg,
+        ]);
+        qemu_img(&["snapshot", "-c", "before", source_arg]);
+
+        flatten_overlay(&source, &target, &image).await.unwrap();
+
+        qemu_img(&["compare", "-f", "raw", "-F", "qcow2", guest_data_arg, target_arg]);
+        let info = qemu_img(&["info", "--output=json", target_arg]);
+        let info: serde_json::Value = serde_json::from_slice(&info.stdout).unwrap();
+        assert_eq!(info["backing-filename"], "../../images/base.qcow2");
+        assert!(info.get("snapshots").is_none(), "internal snapshots were copied: {info}");
+    }
+
     #[test]
     fn validate_workspace_rejects_relative_paths() {
         assert!(matches!(
@@ -0,0 +1,254 @@
+//! Minimal QMP (QEMU Machine Protocol) client.
+//!
+//! Every VM is spawned with `-qmp unix:<vm_dir>/qmp.sock,server=on,wait=off`
+//! (see `QemuInvocation::build`). The supervisor opens a short-lived
+//! connection per operation rather than holding one per VM: QMP only
+//! accepts a single client at a time, and a per-operation connection
+//! means a wedged command can't block every later caller behind it.
+//!
+//! Wire format: newline-delimited JSON. The server greets with
+//! `{"QMP": {...}}`, the client must negotiate with `qmp_capabilities`,
+//! and afterwards every `{"execute": …}` is answered by exactly one
+//! `{"return": …}` or `{"error": {"class", "desc"}}`. Asynchronous
+//! `{"event": …}` messages may arrive interleaved and are skipped.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+
+use crate::error::{Result, SupervisorError};
+
+/// Upper bound on connect + greeting + capability negotiation.
+const QMP_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bound on a single command round-trip. Internal snapshots walk the
+/// refcounts of the whole overlay, so this is sized for large overlays on
+/// slow disks rather than for the common sub-second case.
+const QMP_COMMAND_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub struct QmpClient {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl QmpClient {
+    /// Connect to the QMP socket at `path` and leave capability
+    /// negotiation mode so commands can be executed.
+    pub async fn connect(path: &Path) -> Result<Self> {
+        tokio::time::timeout(QMP_HANDSHAKE_TIMEOUT, Self::handshake(path))
+            .await
+            .map_err(|_| {
+                SupervisorError::Qmp(format!(
+                    "handshake with {} timed out after {}s",
+                    path.display(),
+                    QMP_HANDSHAKE_TIMEOUT.as_secs()
+                ))
+            })?
+    }
+
+    async fn handshake(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path).await.map_err(|e| {
+            SupervisorError::Qmp(format!("connect {}: {e}", path.display()))
+        })?;
+        let (read_half, writer) = stream.into_split();
+        let mut client = Self {
+            reader: BufReader::new(read_half),
+            writer,
+        };
+        let greeting = client.read_message().await?;
+        if greeting.get("QMP").is_none() {
+            return Err(SupervisorError::Qmp(format!(
+                "expected QMP greeting, got {greeting}"
+            )));
+        }
+        client.round_trip("qmp_capabilities", None).await?;
+        Ok(client)
+    }
+
+    /// Execute a QMP command and return its `return` payload.
+    pub async fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value> {
+        tokio::time::timeout(QMP_COMMAND_TIMEOUT, self.round_trip(command, arguments))
+            .await
+            .map_err(|_| {
+                SupervisorError::Qmp(format!(
+                    "`{command}` did not answer within {}s",
+                    QMP_COMMAND_TIMEOUT.as_secs()
+                ))
+            })?
+    }
+
+    /// Run an HMP command through `human-monitor-command`. `savevm`,
+    /// `loadvm` and `delvm` report failures as monitor output rather than
+    /// as a QMP error, so any non-empty output is treated as an error.
+    pub async fn human_monitor_command_checked(&mut self, command_line: &str) -> Result<()> {
+        let output = self
+            .execute(
+                "human-monitor-command",
+                Some(json!({ "command-line": command_line })),
+            )
+            .await?;
+        let text = output.as_str().unwrap_or_default().trim();
+        if text.is_empty() {
+            Ok(())
+        } else {
+            Err(SupervisorError::Qmp(format!("`{command_line}`: {text}")))
+        }
+    }
+
+    async fn round_trip(&mut self, command: &str, arguments: Option<Value>) -> Result<Value> {
+        let request = match arguments {
+            Some(arguments) => json!({ "execute": command, "arguments": arguments }),
+            None => json!({ "execute": command }),
+        };
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        self.writer
+            .write_all(&line)
+            .await
+            .map_err(|e| SupervisorError::Qmp(format!("write `{command}`: {e}")))?;
+
+        loop {
+            let message = self.read_message().await?;
+            if message.get("event").is_some() {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                let class = error.get("class").and_then(Value::as_str).unwrap_or("unknown");
+                let desc = error.get("desc").and_then(Value::as_str).unwrap_or("");
+                return Err(SupervisorError::Qmp(format!("`{command}` failed ({class}): {desc}")));
+            }
+            if let Some(ret) = message.get("return") {
+                return Ok(ret.clone());
+            }
+            return Err(SupervisorError::Qmp(format!(
+                "unexpected reply to `{command}`: {message}"
+            )));
+        }
+    }
+
+    async fn read_message(&mut self) -> Result<Value> {
+        let mut line = String::new();
+        let read = self
+            .reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| SupervisorError::Qmp(format!("read: {e}")))?;
+        if read == 0 {
+            return Err(SupervisorError::Qmp("monitor closed the connection".into()));
+        }
+        Ok(serde_json::from_str(line.trim_end())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    /// Spawn a scripted QMP server: sends the greeting, then for every
+    /// received line replies with the next entry of `replies` (each entry
+    /// may be several newline-separated messages, e.g. an event followed
+    /// by the actual return).
+    fn fake_monitor(dir: &Path, replies: Vec<&'static str>) -> std::path::PathBuf {
+        let socket = dir.join("qmp.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read_half, mut write_half) = stream.into_split();
+            let mut reader = BufReader::new(read_half);
+            write_half
+                .write_all(b"{\"QMP\": {\"version\": {}, \"capabilities\": []}}\n")
+                .await
+                .unwrap();
+            for reply in replies {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap() == 0 {
+                    return;
+                }
+                write_half.write_all(reply.as_bytes()).await.unwrap();
+                write_half.write_all(b"\n").await.unwrap();
+            }
+        });
+        socket
+    }
+
+    #[tokio::test]
+    async fn negotiates_capabilities_and_returns_payload() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = fake_monitor(
+            dir.path(),
+            vec![
+                r#"{"return": {}}"#,
+                r#"{"return": {"status": "running", "running": true}}"#,
+            ],
+        );
+        let mut client = QmpClient::connect(&socket).await.unwrap();
+        let status = client.execute("query-status", None).await.unwrap();
+        assert_eq!(status["status"], "running");
+    }
+
+    #[tokio::test]
+    async fn skips_interleaved_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = fake_monitor(
+            dir.path(),
+            vec![
+                r#"{"return": {}}"#,
+                "{\"event\": \"STOP\", \"timestamp\": {}}\n{\"return\": {}}",
+            ],
+        );
+        let mut client = QmpClient::connect(&socket).await.unwrap();
+        assert_eq!(client.execute("stop", None).await.unwrap(), json!({}));
+    }
+
+    #[tokio::test]
+    async fn surfaces_qmp_error_class_and_desc() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = fake_monitor(
+            dir.path(),
+            vec![
+                r#"{"return": {}}"#,
+                r#"{"error": {"class": "GenericError", "desc": "no such snapshot"}}"#,
+            ],
+        );
+        let mut client = QmpClient::connect(&socket).await.unwrap();
+        let err = client.execute("loadvm", None).await.unwrap_err().to_string();
+        assert!(err.contains("GenericError"), "{err}");
+        assert!(err.contains("no such snapshot"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn hmp_output_is_treated_as_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = fake_monitor(
+            dir.path(),
+            vec![
+                r#"{"return": {}}"#,
+                r#"{"return": ""}"#,
+                r#"{"return": "Error: Device 'disk0' does not have the requested snapshot\r\n"}"#,
+            ],
+        );
+        let mut client = QmpClient::connect(&socket).await.unwrap();
+        client.human_monitor_command_checked("savevm a").await.unwrap();
+        let err = client
+            .human_monitor_command_checked("loadvm b")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("does not have the requested snapshot"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn connect_fails_without_listener() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = QmpClient::connect(&dir.path().join("missing.sock"))
+            .await
+            .err()
+            .expect("connect must fail");
+        assert!(matches!(err, SupervisorError::Qmp(_)));
+    }
+}
@@ -0,0 +1,132 @@
+//! Per-user VM quotas.
+//!
+//! Admins can cap how many VMs a user runs at once and how many vCPUs and
+//! MiB of memory those VMs add up to (`users.max_vms`, `max_vcpus`,
+//! `max_memory_mb`; NULL is unlimited). Usage counts the user's live VMs
+//! — `starting`, `running` (suspended included, it still holds its
+//! memory) and `stopping`. Stopped and failed VMs don't count.
+//!
+//! The check runs in `launch_vm` while holding `AppState::vm_quota`, up
+//! to the VM's insert, so it covers both `POST /v1/vms` and the VMs the
+//! task runner boots on a user's behalf. The static admin token has no
+//! user row and therefore no quota.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Result, SupervisorError};
+use crate::state::SharedState;
+
+/// A user's limits. `None` means unlimited.
+#[derive(
+    Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+pub struct UserQuota {
+    pub max_vms: Option<i64>,
+    pub max_vcpus: Option<i64>,
+    pub max_memory_mb: Option<i64>,
+}
+
+/// What a user's live VMs currently hold.
+#[derive(
+    Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+pub struct QuotaUsage {
+    pub vms: i64,
+    pub vcpus: i64,
+    pub memory_mb: i64,
+}
+
+/// Reject a new VM of `cpus` × `memory_mb` that would take `usage` past
+/// `quota`.
+pub fn check(quota: &UserQuota, usage: &QuotaUsage, cpus: i64, memory_mb: i64) -> Result<()> {
+    let limits = [
+        (quota.max_vms, usage.vms, 1, "VMs"),
+        (quota.max_vcpus, usage.vcpus, cpus, "vCPUs"),
+        (quota.max_memory_mb, usage.memory_mb, memory_mb, "MiB of memory"),
+    ];
+    for (limit, used, wanted, what) in limits {
+        if let Some(limit) = limit {
+            if used.saturating_add(wanted) > limit {
+                return Err(SupervisorError::QuotaExceeded(format!(
+                    "limit is {limit} {what}, {used} in use, this VM needs {wanted}"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The quota stored on `user_id`'s row; unlimited if the row is gone.
+pub async fn load(state: &SharedState, user_id: &str) -> Result<UserQuota> {
+    let quota: Option<UserQuota> =
+        sqlx::query_as("SELECT max_vms, max_vcpus, max_memory_mb FROM users WHERE id = ?1")
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await?;
+    Ok(quota.unwrap_or_default())
+}
+
+/// Sum `user_id`'s live VMs. Rows from before per-VM sizing was recorded
+/// are counted at today's `vm_defaults`.
+pub async fn usage(state: &SharedState, user_id: &str) -> Result<QuotaUsage> {
+    let usage = sqlx::query_as(
+        "SELECT COUNT(*) AS vms, \
+                COALESCE(SUM(COALESCE(cpus, ?2)), 0) AS vcpus, \
+                COALESCE(SUM(COALESCE(memory_mb, ?3)), 0) AS memory_mb \
+         FROM vms \
+         WHERE owner_user_id = ?1 AND status IN ('starting', 'running', 'stopping')",
+    )
+    .bind(user_id)
+    .bind(i64::from(state.config.vm_defaults.cpus))
+    .bind(i64::from(state.config.vm_defaults.memory_mb))
+    .fetch_one(&state.db)
+    .await?;
+    Ok(usage)
+}
+
+/// Load `user_id`'s quota and usage and `check` a new VM against them.
+/// Callers hold `AppState::vm_quota` until the VM row is inserted.
+pub async fn enforce(state: &SharedState, user_id: &str, cpus: u32, memory_mb: u32) -> Result<()> {
+    let quota = load(state, user_id).await?;
+    if quota == UserQuota::default() {
+        return Ok(());
+    }
+    let usage = usage(state, user_id).await?;
+    check(&quota, &usage, i64::from(cpus), i64::from(memory_mb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(vms: i64, vcpus: i64, memory_mb: i64) -> QuotaUsage {
+        QuotaUsage {
+            vms,
+            vcpus,
+            memory_mb,
+        }
+    }
+
+    #[test]
+    fn unlimited_quota_admits_anything() {
+        let quota = UserQuota::default();
+        assert!(check(&quota, &usage(100, 400, 1 << 20), 64, 1 << 16).is_ok());
+    }
+
+    #[test]
+    fn each_limit_is_checked_against_usage_plus_the_new_vm() {
+        let quota = UserQuota {
+            max_vms: Some(2),
+            max_vcpus: Some(4),
+            max_memory_mb: Some(4096),
+        };
+        assert!(check(&quota, &usage(1, 2, 2048), 2, 2048).is_ok());
+        let err = check(&quota, &usage(2, 2, 2048), 1, 1024).unwrap_err();
+        assert!(matches!(err, SupervisorError::QuotaExceeded(ref msg) if msg.contains("2 VMs")));
+        let err = check(&quota, &usage(1, 3, 1024), 2, 1024).unwrap_err();
+        assert!(matches!(err, SupervisorError::QuotaExceeded(ref msg) if msg.contains("vCPUs")));
+        let err = check(&quota, &usage(1, 1, 3072), 1, 2048).unwrap_err();
+        assert!(matches!(err, SupervisorError::QuotaExceeded(ref msg) if msg.contains("memory")));
+    }
+}
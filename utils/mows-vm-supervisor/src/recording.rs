@@ -0,0 +1,457 @@
+//! Agent session recordings in [asciicast v2] format.
+//!
+//! Every agent session is written to `<state_dir>/agents/<id>/session.cast`
+//! while it runs: a JSON header line followed by one `[time, code, data]`
+//! line per event. The codes used are
+//!
+//! - `o` — bytes the agent wrote to its terminal (tmux `pipe-pane` output),
+//! - `i` — bytes a client typed into the session over `/v1/agents/{id}/io`,
+//! - `r` — the pane was resized (`"<cols>x<rows>"`),
+//! - `m` — markers: clients attaching / detaching, truncation.
+//!
+//! Times are seconds since the recording started, so `mows agents replay`
+//! and the web player reproduce the original pacing. Recordings of exited
+//! agents are removed after `recordings.retention_days` by
+//! [`spawn_retention_sweeper`].
+//!
+//! [asciicast v2]: https://docs.asciinema.org/manual/asciicast/v2/
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::error::{Result, SupervisorError};
+use crate::events::SupervisorEvent;
+use crate::state::SharedState;
+
+/// How often expired recordings are looked for.
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// `<state_dir>/agents/<id>/` — ssh known_hosts and the session recording.
+pub fn agent_dir_for(state_dir: &Path, agent_id: &str) -> PathBuf {
+    state_dir.join("agents").join(agent_id)
+}
+
+pub fn recording_path_for(agent_dir: &Path) -> PathBuf {
+    agent_dir.join("session.cast")
+}
+
+/// First line of a recording.
+#[derive(Debug, Clone, Serialize)]
+pub struct CastHeader {
+    pub version: u8,
+    pub width: u16,
+    pub height: u16,
+    /// Unix time the session started.
+    pub timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub env: BTreeMap<String, String>,
+}
+
+impl CastHeader {
+    pub fn new(width: u16, height: u16, title: Option<String>) -> Self {
+        Self {
+            version: 2,
+            width,
+            height,
+            timestamp: Utc::now().timestamp(),
+            title,
+            env: BTreeMap::from([("TERM".to_string(), "xterm-256color".to_string())]),
+        }
+    }
+}
+
+/// Appends events to one `session.cast`. Shared between the output tail in
+/// `agent_runtime` and every websocket attached to the agent, hence the
+/// interior mutex; each event is a single small `write`, so holding a std
+/// mutex across it is fine.
+pub struct Recorder {
+    started: Instant,
+    inner: Mutex<CastWriter>,
+}
+
+struct CastWriter {
+    /// `None` once the recording is finished, truncated, or failed to write.
+    file: Option<std::fs::File>,
+    path: PathBuf,
+    written: u64,
+    max_bytes: u64,
+    size: (u16, u16),
+    /// Trailing bytes of an incomplete UTF-8 sequence, per stream. Event
+    /// data is a JSON string, so a multi-byte character split across two
+    /// reads has to be held back until the rest of it arrives.
+    pending_output: Vec<u8>,
+    pending_input: Vec<u8>,
+}
+
+impl Recorder {
+    /// Create (or truncate) `path` and write the header. `max_bytes == 0`
+    /// leaves the recording uncapped.
+    pub fn create(path: &Path, header: &CastHeader, max_bytes: u64) -> Result<Self> {
+        let mut line = serde_json::to_vec(header)
+            .map_err(|e| SupervisorError::Internal(format!("encode cast header: {e}")))?;
+        line.push(b'\n');
+        let mut file = std::fs::File::create(path).map_err(|e| {
+            SupervisorError::FilesystemError(format!(
+                "failed to create recording {}: {e}",
+                path.display()
+            ))
+        })?;
+        file.write_all(&line)?;
+        Ok(Self {
+            started: Instant::now(),
+            inner: Mutex::new(CastWriter {
+                file: Some(file),
+                path: path.to_path_buf(),
+                written: line.len() as u64,
+                max_bytes,
+                size: (header.width, header.height),
+                pending_output: Vec::new(),
+                pending_input: Vec::new(),
+            }),
+        })
+    }
+
+    pub fn output(&self, bytes: &[u8]) {
+        let time = self.elapsed();
+        let mut writer = self.lock();
+        let data = take_complete_utf8(&mut writer.pending_output, bytes);
+        writer.event(time, "o", &data);
+    }
+
+    pub fn input(&self, bytes: &[u8]) {
+        let time = self.elapsed();
+        let mut writer = self.lock();
+        let data = take_complete_utf8(&mut writer.pending_input, bytes);
+        writer.event(time, "i", &data);
+    }
+
+    /// Record a resize. No-op when the size is unchanged.
+    pub fn resize(&self, width: u16, height: u16) {
+        let time = self.elapsed();
+        let mut writer = self.lock();
+        if writer.size == (width, height) {
+            return;
+        }
+        writer.size = (width, height);
+        writer.event(time, "r", &format!("{width}x{height}"));
+    }
+
+    pub fn marker(&self, label: &str) {
+        let time = self.elapsed();
+        self.lock().event(time, "m", label);
+    }
+
+    /// Close the recording and return its size in bytes. Later events are
+    /// dropped.
+    pub fn finish(&self) -> u64 {
+        let mut writer = self.lock();
+        writer.file = None;
+        writer.written
+    }
+
+    fn elapsed(&self) -> f64 {
+        // Whole microseconds keep the serialized times short.
+        self.started.elapsed().as_micros() as f64 / 1_000_000.0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CastWriter> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl CastWriter {
+    fn event(&mut self, time: f64, code: &str, data: &str) {
+        if data.is_empty() || self.file.is_none() {
+            return;
+        }
+        let mut line = event_line(time, code, data);
+        if self.max_bytes > 0 && self.written + line.len() as u64 > self.max_bytes {
+            // One last marker so a replay shows where the cut happened;
+            // it may overshoot the cap by its own length.
+            line = event_line(
+                time,
+                "m",
+                &format!("recording truncated at {} bytes", self.max_bytes),
+            );
+            self.write(&line);
+            self.file = None;
+            return;
+        }
+        self.write(&line);
+    }
+
+    fn write(&mut self, line: &[u8]) {
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        match file.write_all(line) {
+            Ok(()) => self.written += line.len() as u64,
+            Err(e) => {
+                tracing::warn!(
+                    path = %self.path.display(),
+                    error = %e,
+                    "failed to write agent recording; recording stopped"
+                );
+                self.file = None;
+            }
+        }
+    }
+}
+
+fn event_line(time: f64, code: &str, data: &str) -> Vec<u8> {
+    let mut line = serde_json::to_vec(&(time, code, data)).unwrap_or_default();
+    line.push(b'\n');
+    line
+}
+
+/// Append `bytes` to `pending` and return everything up to the last
+/// complete UTF-8 sequence, leaving an incomplete tail in `pending`.
+/// Invalid sequences become U+FFFD.
+fn take_complete_utf8(pending: &mut Vec<u8>, bytes: &[u8]) -> String {
+    pending.extend_from_slice(bytes);
+    let keep = incomplete_utf8_tail(pending);
+    let complete: Vec<u8> = pending.drain(..pending.len() - keep).collect();
+    String::from_utf8_lossy(&complete).into_owned()
+}
+
+/// Length of a trailing, not yet complete UTF-8 sequence in `bytes`.
+fn incomplete_utf8_tail(bytes: &[u8]) -> usize {
+    for (index, &byte) in bytes.iter().rev().take(3).enumerate() {
+        let back = index + 1;
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let needed = match byte {
+            0xF0.. => 4,
+            0xE0.. => 3,
+            0xC0.. => 2,
+            _ => 1,
+        };
+        return if needed > back { back } else { 0 };
+    }
+    0
+}
+
+/// Remove the recordings of agents that exited more than `retention_days`
+/// ago and clear their `recording_bytes`. Returns the affected agent ids.
+pub async fn prune_expired(
+    db: &SqlitePool,
+    state_dir: &Path,
+    now: DateTime<Utc>,
+    retention_days: u32,
+) -> Result<Vec<String>> {
+    let cutoff = (now - chrono::Duration::days(i64::from(retention_days))).to_rfc3339();
+    let candidates: Vec<(String, Option<i64>)> = sqlx::query_as(
+        "SELECT id, recording_bytes FROM agents \
+         WHERE exited_at IS NOT NULL AND exited_at < ?1",
+    )
+    .bind(&cutoff)
+    .fetch_all(db)
+    .await?;
+
+    let mut pruned = Vec::new();
+    for (id, recording_bytes) in candidates {
+        let path = recording_path_for(&agent_dir_for(state_dir, &id));
+        let removed = match tokio::fs::remove_file(&path).await {
+            Ok(()) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => {
+                tracing::warn!(agent_id = %id, path = %path.display(), error = %e, "failed to prune recording");
+                continue;
+            }
+        };
+        if recording_bytes.is_some() {
+            sqlx::query("UPDATE agents SET recording_bytes = NULL WHERE id = ?1")
+                .bind(&id)
+                .execute(db)
+                .await?;
+        }
+        if removed || recording_bytes.is_some() {
+            pruned.push(id);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Start the hourly retention sweep. Runs for the life of the process;
+/// does nothing when `recordings.retention_days` is 0.
+pub fn spawn_retention_sweeper(state: SharedState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let retention_days = state.config.recordings.retention_days;
+        if retention_days == 0 {
+            return;
+        }
+        loop {
+            match prune_expired(
+                &state.db,
+                &state.config.state_dir,
+                Utc::now(),
+                retention_days,
+            )
+            .await
+            {
+                Ok(pruned) => {
+                    if !pruned.is_empty() {
+                        tracing::info!(count = pruned.len(), "pruned expired agent recordings");
+                    }
+                    for id in pruned {
+                        state.events.emit(SupervisorEvent::AgentUpdated { id });
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "agent recording retention sweep failed"),
+            }
+            tokio::time::sleep(RETENTION_SWEEP_INTERVAL).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn read_events(path: &Path) -> (serde_json::Value, Vec<serde_json::Value>) {
+        let raw = std::fs::read_to_string(path).unwrap();
+        let mut lines = raw.lines().map(|l| serde_json::from_str(l).unwrap());
+        let header = lines.next().unwrap();
+        (header, lines.collect())
+    }
+
+    #[test]
+    fn writes_header_and_typed_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = recording_path_for(dir.path());
+        let recorder =
+            Recorder::create(&path, &CastHeader::new(80, 24, Some("shell".into())), 0).unwrap();
+        recorder.output(b"hello\r\n");
+        recorder.input(b"ls\r");
+        recorder.resize(80, 24);
+        recorder.resize(120, 40);
+        recorder.marker("client attached");
+        let size = recorder.finish();
+        recorder.output(b"after finish");
+
+        let (header, events) = read_events(&path);
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["width"], 80);
+        assert_eq!(header["height"], 24);
+        assert_eq!(header["title"], "shell");
+        let codes: Vec<&str> = events.iter().map(|e| e[1].as_str().unwrap()).collect();
+        assert_eq!(codes, ["o", "i", "r", "m"]);
+        assert_eq!(events[0][2], "hello\r\n");
+        assert_eq!(events[2][2], "120x40");
+        assert!(events.iter().all(|e| e[0].as_f64().unwrap() >= 0.0));
+        assert_eq!(size, std::fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn holds_back_split_utf8_sequences() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = recording_path_for(dir.path());
+        let recorder = Recorder::create(&path, &CastHeader::new(80, 24, None), 0).unwrap();
+        let euro = "€".as_bytes();
+        recorder.output(&[b'a', euro[0], euro[1]]);
+        recorder.output(&euro[2..]);
+        recorder.output(&[0xFF, b'b']);
+
+        let (_, events) = read_events(&path);
+        let data: Vec<&str> = events.iter().map(|e| e[2].as_str().unwrap()).collect();
+        assert_eq!(data, ["a", "€", "\u{FFFD}b"]);
+    }
+
+    #[test]
+    fn stops_with_marker_at_max_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = recording_path_for(dir.path());
+        let recorder = Recorder::create(&path, &CastHeader::new(80, 24, None), 200).unwrap();
+        for _ in 0..20 {
+            recorder.output(b"0123456789");
+        }
+
+        let (_, events) = read_events(&path);
+        let last = events.last().unwrap();
+        assert_eq!(last[1], "m");
+        assert!(last[2].as_str().unwrap().contains("truncated"));
+        assert!(events.len() < 20);
+    }
+
+    async fn fresh_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        sqlx::query(
+            "INSERT INTO vms (id, name, status, started_at) VALUES ('vm', 'vm', 'running', '2026-01-01T00:00:00Z')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    async fn insert_agent(
+        pool: &SqlitePool,
+        id: &str,
+        exited_at: Option<&str>,
+        bytes: Option<i64>,
+    ) {
+        sqlx::query(
+            "INSERT INTO agents (id, vm_id, name, kind, status, started_at, exited_at, recording_bytes) \
+             VALUES (?1, 'vm', ?1, 'shell', ?2, '2026-01-01T00:00:00+00:00', ?3, ?4)",
+        )
+        .bind(id)
+        .bind(if exited_at.is_some() { "stopped" } else { "running" })
+        .bind(exited_at)
+        .bind(bytes)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn prunes_only_recordings_past_retention() {
+        let pool = fresh_pool().await;
+        let state_dir = tempfile::tempdir().unwrap();
+        insert_agent(&pool, "old", Some("2026-01-01T00:00:00+00:00"), Some(10)).await;
+        insert_agent(&pool, "recent", Some("2026-01-30T00:00:00+00:00"), Some(10)).await;
+        insert_agent(&pool, "running", None, None).await;
+        for id in ["old", "recent", "running"] {
+            let dir = agent_dir_for(state_dir.path(), id);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(recording_path_for(&dir), b"{}\n").unwrap();
+        }
+
+        let now: DateTime<Utc> = "2026-02-01T00:00:00Z".parse().unwrap();
+        let pruned = prune_expired(&pool, state_dir.path(), now, 7)
+            .await
+            .unwrap();
+        assert_eq!(pruned, ["old"]);
+        assert!(!recording_path_for(&agent_dir_for(state_dir.path(), "old")).exists());
+        assert!(recording_path_for(&agent_dir_for(state_dir.path(), "recent")).exists());
+        assert!(recording_path_for(&agent_dir_for(state_dir.path(), "running")).exists());
+        let bytes: Option<i64> =
+            sqlx::query_scalar("SELECT recording_bytes FROM agents WHERE id = 'old'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(bytes, None);
+
+        // A second sweep has nothing left to do.
+        assert!(prune_expired(&pool, state_dir.path(), now, 7)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -32,6 +32,7 @@ use sqlx::SqlitePool;
 
 use crate::error::Result;
 use crate::events::{EventBus, SupervisorEvent};
+use crate::forwards::VmForward;
 
 /// Counts returned from a reconciliation pass — useful for the startup
 /// log line and as the assertion target in tests. Carries the row ids
@@ -141,7 +142,7 @@ pub async fn reconcile_orphans(
     };
 
     let vm_update = sqlx::query(
-        "UPDATE vms SET status = 'failed', exited_at = ?1 \
+        "UPDATE vms SET status = 'failed', exited_at = ?1, suspended_at = NULL \
          WHERE status IN ('starting', 'running', 'stopping')",
     )
     .bind(&timestamp)
@@ -149,7 +150,7 @@ pub async fn reconcile_orphans(
     .await?;
 
     let agent_update = sqlx::query(
-        "UPDATE agents SET status = 'failed', exited_at = ?1 \
+        "UPDATE agents SET status = 'failed', exited_at = ?1, waiting_since = NULL \
          WHERE status IN ('starting', 'running', 'stopping') \
            AND vm_id IN (SELECT id FROM vms WHERE status = 'failed' AND exited_at = ?1)",
     )
@@ -177,6 +178,53 @@ pub async fn reconcile_orphans(
     })
 }
 
+/// Fail every task a previous supervisor run left `running`: its ssh
+/// session and (for `owns_vm` tasks) its VM died with that process, so
+/// there is no result to wait for. `queued` tasks are untouched and get
+/// picked up by the new dispatcher. Returns the affected task ids so the
+/// caller can emit `TaskUpdated`.
+pub async fn reconcile_running_tasks(
+    pool: &SqlitePool,
+    now: DateTime<Utc>,
+) -> Result<Vec<String>> {
+    let failed: Vec<String> = sqlx::query_scalar(
+        "UPDATE tasks SET status = 'failed', error = 'supervisor restarted', finished_at = ?1 \
+         WHERE status = 'running' RETURNING id",
+    )
+    .bind(now.to_rfc3339())
+    .fetch_all(pool)
+    .await?;
+    Ok(failed)
+}
+
+/// Sort out `vm_forwards` after a restart. Rows of VMs that are no longer
+/// `running` are deleted — their QEMU, and with it the rule, is gone. The
+/// rest are returned for the caller to reserve in the forward allocator
+/// and re-apply over QMP (`crate::forwards::add_rule`). Run after
+/// `reconcile_orphans`, which today fails every VM of the previous run,
+/// so in practice this only clears the table; the restore path is for
+/// VMs that outlive the supervisor.
+pub async fn reconcile_forwards(pool: &SqlitePool) -> Result<Vec<VmForward>> {
+    let dropped = sqlx::query(
+        "DELETE FROM vm_forwards WHERE vm_id NOT IN \
+         (SELECT id FROM vms WHERE status = 'running')",
+    )
+    .execute(pool)
+    .await?;
+    if dropped.rows_affected() > 0 {
+        tracing::info!(
+            count = dropped.rows_affected(),
+            "dropped port forwards of vms that did not survive the restart"
+        );
+    }
+    Ok(sqlx::query_as(
+        "SELECT vm_id, guest_port, host_port, created_at FROM vm_forwards \
+         ORDER BY vm_id, guest_port",
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
 /// Best-effort: SIGKILL the given PID **iff** its `/proc/<pid>/comm`
 /// matches a known qemu binary basename exactly. Returns `true` if a
 /// kill was actually sent.
@@ -439,6 +487,68 @@ mod tests {
         assert_eq!(stats.failed_agent_ids, vec!["a1".to_string()]);
     }
 
+    #[tokio::test]
+    async fn fails_running_tasks_and_keeps_queued_ones() {
+        let pool = fresh_pool().await;
+        for (id, status) in [("t-run", "running"), ("t-queued", "queued"), ("t-done", "succeeded")] {
+            sqlx::query(
+                "INSERT INTO tasks (id, name, kind, prompt, status, timeout_secs, created_at) \
+                 VALUES (?1, ?1, 'claude', 'p', ?2, 60, '2026-01-01T00:00:00Z')",
+            )
+            .bind(id)
+            .bind(status)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let failed = reconcile_running_tasks(&pool, Utc::now()).await.unwrap();
+        assert_eq!(failed, vec!["t-run".to_string()]);
+
+        let rows: Vec<(String, String, Option<String>)> =
+            sqlx::query_as("SELECT id, status, error FROM tasks ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("t-done".into(), "succeeded".into(), None),
+                ("t-queued".into(), "queued".into(), None),
+                ("t-run".into(), "failed".into(), Some("supervisor restarted".into())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_forwards_of_running_vms_only() {
+        let pool = fresh_pool().await;
+        insert_vm(&pool, "vm-live", "running", None).await;
+        insert_vm(&pool, "vm-dead", "failed", None).await;
+        for (vm_id, guest, host) in [("vm-live", 3000, 23000), ("vm-dead", 8080, 23001)] {
+            sqlx::query(
+                "INSERT INTO vm_forwards (vm_id, guest_port, host_port, created_at) \
+                 VALUES (?1, ?2, ?3, '2026-01-01T00:00:00Z')",
+            )
+            .bind(vm_id)
+            .bind(guest)
+            .bind(host)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let kept = reconcile_forwards(&pool).await.unwrap();
+        assert_eq!(kept.len(), 1);
+        let forward = kept.first().unwrap();
+        assert_eq!(forward.vm_id, "vm-live");
+        assert_eq!((forward.guest_port, forward.host_port), (3000, 23000));
+
+        // After `reconcile_orphans` nothing is running any more.
+        reconcile_orphans(&pool, Utc::now(), None).await.unwrap();
+        assert!(reconcile_forwards(&pool).await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn refuses_to_kill_non_qemu_pid() {
         // Spawn a real `sleep` child so /proc/<pid>/comm resolves to
@@ -0,0 +1,297 @@
+//! Encrypted secret store (`/v1/secrets`, `mows agents secret set`).
+//!
+//! API keys for MCP servers and tools live here instead of in plaintext
+//! kind env maps or the mounted `~/.claude`. Each value is sealed with
+//! AES-256-GCM under a key derived (HKDF-SHA256) from the bytes of
+//! `secrets.master_key_file`, with a fresh nonce per write and the
+//! secret's name as associated data. Without a master key the store is
+//! disabled: writes are refused and kinds that reference a secret fail to
+//! spawn.
+//!
+//! Agent kinds reference a secret by name with a `${secret:NAME}` value,
+//! either in `agent_kinds.<kind>.env` or in an MCP server's `env` (see
+//! `crate::kinds::AgentKindExtras`). Only kinds can: they come from the
+//! operator's config, whereas VM `env` is set by whoever creates the VM.
+//! Values are decrypted in `agent_runtime::spawn` only, piped over ssh
+//! stdin into a file on a tmpfs inside the guest and sourced by the
+//! agent's launch command — never put on a command line, logged or stored
+//! in an `agents` row.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use chrono::Utc;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use crate::error::{Result, SupervisorError};
+use crate::kinds::{is_valid_posix_env_name, shell_quote};
+
+/// Shortest master key file accepted, in bytes.
+pub const MIN_MASTER_KEY_LEN: usize = 32;
+
+/// Longest secret name accepted.
+const MAX_NAME_LEN: usize = 64;
+
+/// HKDF `info` for the store key. Bump the version to rotate every
+/// derived key without touching the master key file.
+const KEY_INFO: &[u8] = b"mows-vm-supervisor secrets v1";
+
+const NONCE_LEN: usize = 12;
+
+/// Prefix of the guest env var an MCP server's secret is exported as.
+/// The server's own `env` entry is rewritten to `${MOWS_SECRET_<NAME>}`,
+/// which claude expands when it starts the server.
+const MCP_ENV_PREFIX: &str = "MOWS_SECRET_";
+
+/// The store key. `Debug` is redacted so the key can sit in
+/// `SupervisorConfig` without ending up in a log line.
+#[derive(Clone)]
+pub struct SecretKey(Key<Aes256Gcm>);
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+impl SecretKey {
+    pub fn from_master_key(master: &[u8]) -> Result<Self> {
+        if master.len() < MIN_MASTER_KEY_LEN {
+            return Err(SupervisorError::Config(format!(
+                "secrets.master_key_file must hold at least {MIN_MASTER_KEY_LEN} bytes \
+                 (e.g. `head -c 32 /dev/urandom`), got {}",
+                master.len()
+            )));
+        }
+        let mut key = Key::<Aes256Gcm>::default();
+        Hkdf::<Sha256>::new(None, master)
+            .expand(KEY_INFO, &mut key)
+            .map_err(|e| SupervisorError::Config(format!("secrets: key derivation failed: {e}")))?;
+        Ok(Self(key))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let master = std::fs::read(path).map_err(|e| {
+            SupervisorError::Config(format!(
+                "failed to read secrets.master_key_file {}: {e}",
+                path.display()
+            ))
+        })?;
+        Self::from_master_key(&master)
+    }
+
+    fn seal(&self, name: &str, value: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let payload = Payload {
+            msg: value.as_bytes(),
+            aad: name.as_bytes(),
+        };
+        let ciphertext = Aes256Gcm::new(&self.0)
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| SupervisorError::InvalidState(format!("failed to seal secret {name:?}")))?;
+        Ok((nonce.to_vec(), ciphertext))
+    }
+
+    fn open(&self, name: &str, nonce: &[u8], ciphertext: &[u8]) -> Result<String> {
+        let unreadable = || {
+            SupervisorError::InvalidState(format!(
+                "secret {name:?} cannot be decrypted; was secrets.master_key_file changed?"
+            ))
+        };
+        if nonce.len() != NONCE_LEN {
+            return Err(unreadable());
+        }
+        let payload = Payload {
+            msg: ciphertext,
+            aad: name.as_bytes(),
+        };
+        let plaintext = Aes256Gcm::new(&self.0)
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| unreadable())?;
+        String::from_utf8(plaintext).map_err(|_| unreadable())
+    }
+}
+
+/// Names double as part of a guest env var (`MOWS_SECRET_<NAME>`), so
+/// they follow the same rule as env keys.
+pub fn validate_name(name: &str) -> std::result::Result<(), String> {
+    if name.len() > MAX_NAME_LEN || !is_valid_posix_env_name(name) {
+        return Err(format!(
+            "secret name {name:?} must match [A-Za-z_][A-Za-z0-9_]* and be at most \
+             {MAX_NAME_LEN} characters"
+        ));
+    }
+    Ok(())
+}
+
+/// The secret an env value refers to: `${secret:NAME}` → `NAME`. Only a
+/// whole value is a reference.
+pub fn reference(value: &str) -> Option<&str> {
+    value.strip_prefix("${secret:")?.strip_suffix('}')
+}
+
+/// Guest env var an MCP server's reference to `name` is exported as.
+pub fn mcp_env_var(name: &str) -> String {
+    format!("{MCP_ENV_PREFIX}{name}")
+}
+
+/// A stored secret, without its value — nothing on the API returns that.
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow, Clone, Debug)]
+pub struct SecretSummary {
+    pub name: String,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Id of the user who last set the value; `null` for the API token.
+    pub updated_by: Option<String>,
+}
+
+const SECRET_COLUMNS: &str = "name, created_at, updated_at, updated_by";
+
+fn store_key(key: Option<&SecretKey>) -> Result<&SecretKey> {
+    key.ok_or_else(|| {
+        SupervisorError::BadRequest(
+            "the secret store is disabled: secrets.master_key_file is not set".into(),
+        )
+    })
+}
+
+/// Create or replace `name`.
+pub async fn put(
+    db: &SqlitePool,
+    key: Option<&SecretKey>,
+    name: &str,
+    value: &str,
+    updated_by: Option<&str>,
+) -> Result<SecretSummary> {
+    validate_name(name).map_err(SupervisorError::BadRequest)?;
+    let (nonce, ciphertext) = store_key(key)?.seal(name, value)?;
+    let now = Utc::now().to_rfc3339();
+    sqlx::query(
+        "INSERT INTO secrets (name, nonce, ciphertext, created_at, updated_at, updated_by) \
+         VALUES (?1, ?2, ?3, ?4, ?4, ?5) \
+         ON CONFLICT(name) DO UPDATE SET nonce = excluded.nonce, \
+         ciphertext = excluded.ciphertext, updated_at = excluded.updated_at, \
+         updated_by = excluded.updated_by",
+    )
+    .bind(name)
+    .bind(&nonce)
+    .bind(&ciphertext)
+    .bind(&now)
+    .bind(updated_by)
+    .execute(db)
+    .await?;
+    let sql = format!("SELECT {SECRET_COLUMNS} FROM secrets WHERE name = ?1");
+    Ok(sqlx::query_as(&sql).bind(name).fetch_one(db).await?)
+}
+
+pub async fn list(db: &SqlitePool) -> Result<Vec<SecretSummary>> {
+    let sql = format!("SELECT {SECRET_COLUMNS} FROM secrets ORDER BY name");
+    Ok(sqlx::query_as(&sql).fetch_all(db).await?)
+}
+
+/// Remove `name`; `false` if there was no such secret.
+pub async fn delete(db: &SqlitePool, name: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM secrets WHERE name = ?1")
+        .bind(name)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Fail early, before an agent row exists, if a kind references secrets
+/// the store can't provide. Nothing is decrypted.
+pub async fn ensure_present<'a>(
+    db: &SqlitePool,
+    key: Option<&SecretKey>,
+    names: impl IntoIterator<Item = &'a String>,
+) -> Result<()> {
+    let mut names = names.into_iter().peekable();
+    if names.peek().is_none() {
+        return Ok(());
+    }
+    store_key(key)?;
+    for name in names {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM secrets WHERE name = ?1")
+            .bind(name)
+            .fetch_optional(db)
+            .await?;
+        if exists.is_none() {
+            return Err(SupervisorError::BadRequest(format!(
+                "secret {name:?} is not set (mows agents secret set {name})"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Decrypt the secrets behind `refs` (guest env var → secret name).
+pub async fn resolve(
+    db: &SqlitePool,
+    key: Option<&SecretKey>,
+    refs: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>> {
+    if refs.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let key = store_key(key)?;
+    let mut values = BTreeMap::new();
+    for (env_var, name) in refs {
+        let row: Option<(Vec<u8>, Vec<u8>)> =
+            sqlx::query_as("SELECT nonce, ciphertext FROM secrets WHERE name = ?1")
+                .bind(name)
+                .fetch_optional(db)
+                .await?;
+        let (nonce, ciphertext) = row.ok_or_else(|| {
+            SupervisorError::BadRequest(format!("secret {name:?} is not set"))
+        })?;
+        values.insert(env_var.clone(), key.open(name, &nonce, &ciphertext)?);
+    }
+    Ok(values)
+}
+
+/// Shell fragment the guest sources with `set -a`: one quoted assignment
+/// per line.
+pub fn env_file(values: &BTreeMap<String, String>) -> String {
+    values
+        .iter()
+        .map(|(env_var, value)| format!("{env_var}={}\n", shell_quote(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_values_only_open_under_the_same_key_and_name() {
+        let key = SecretKey::from_master_key(&[7u8; 32]).unwrap();
+        let (nonce, ciphertext) = key.seal("BRAVE_API_KEY", "s3cr3t").unwrap();
+        assert!(!ciphertext.windows(6).any(|w| w == b"s3cr3t"));
+        assert_eq!(key.open("BRAVE_API_KEY", &nonce, &ciphertext).unwrap(), "s3cr3t");
+        assert!(key.open("OTHER", &nonce, &ciphertext).is_err());
+        let other = SecretKey::from_master_key(&[8u8; 32]).unwrap();
+        assert!(other.open("BRAVE_API_KEY", &nonce, &ciphertext).is_err());
+        assert!(SecretKey::from_master_key(b"short").is_err());
+        assert_eq!(format!("{key:?}"), "SecretKey(..)");
+    }
+
+    #[test]
+    fn references_and_names() {
+        assert_eq!(reference("${secret:GITHUB_TOKEN}"), Some("GITHUB_TOKEN"));
+        assert_eq!(reference("prefix ${secret:GITHUB_TOKEN}"), None);
+        assert_eq!(reference("plain"), None);
+        assert!(validate_name("GITHUB_TOKEN").is_ok());
+        assert!(validate_name("has-dash").is_err());
+        assert!(validate_name("").is_err());
+        assert_eq!(mcp_env_var("brave"), "MOWS_SECRET_brave");
+        let file = env_file(&BTreeMap::from([("TOKEN".to_string(), "it's".to_string())]));
+        assert_eq!(file, "TOKEN='it'\\''s'\n");
+    }
+}
@@ -66,6 +66,29 @@ struct SessionKey {
 #[derive(Default)]
 pub struct VmSshSessionRegistry {
     inner: Mutex<HashMap<SessionKey, Arc<VmSshSession>>>,
+    /// Websocket clients currently attached per VM — ssh-io, console and
+    /// display alike. A VM with attachments is never idle (`crate::idle`),
+    /// whereas a session nobody is attached to doesn't keep it awake.
+    attached: Mutex<HashMap<String, usize>>,
+}
+
+/// Counts one attached client against a VM until dropped. See
+/// [`VmSshSessionRegistry::attach`].
+pub struct AttachGuard {
+    registry: Arc<VmSshSessionRegistry>,
+    vm_id: String,
+}
+
+impl Drop for AttachGuard {
+    fn drop(&mut self) {
+        let mut attached = self.registry.attached.lock();
+        if let Some(count) = attached.get_mut(&self.vm_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                attached.remove(&self.vm_id);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -125,6 +148,21 @@ impl VmSshSessionRegistry {
         self.inner.lock().remove(&key);
     }
 
+    /// Record a client attaching to `vm_id`; it counts as attached until
+    /// the returned guard is dropped.
+    pub fn attach(self: &Arc<Self>, vm_id: &str) -> AttachGuard {
+        *self.attached.lock().entry(vm_id.to_owned()).or_default() += 1;
+        AttachGuard {
+            registry: Arc::clone(self),
+            vm_id: vm_id.to_owned(),
+        }
+    }
+
+    /// Clients currently attached to `vm_id`.
+    pub fn attached_count(&self, vm_id: &str) -> usize {
+        self.attached.lock().get(vm_id).copied().unwrap_or(0)
+    }
+
     /// Kill every session for `vm_id`. Invoked when the VM is being
     /// deleted so we never leak ssh subprocesses against a guest
     /// that no longer exists.
@@ -487,4 +525,18 @@ mod tests {
         assert!(registry.get("vm-keep", "t").is_some());
         assert!(!keep.cancel.is_cancelled());
     }
+
+    #[test]
+    fn attach_guards_count_per_vm() {
+        let registry = Arc::new(VmSshSessionRegistry::new());
+        let first = registry.attach("vm");
+        let second = registry.attach("vm");
+        let _other = registry.attach("other");
+        assert_eq!(registry.attached_count("vm"), 2);
+        drop(first);
+        assert_eq!(registry.attached_count("vm"), 1);
+        drop(second);
+        assert_eq!(registry.attached_count("vm"), 0);
+        assert_eq!(registry.attached_count("other"), 1);
+    }
 }
@@ -1,13 +1,15 @@
 use std::sync::Arc;
 
 use sqlx::SqlitePool;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::agent_runtime::AgentRuntimeRegistry;
 use crate::config::SupervisorConfig;
+use crate::egress::EgressProxyRegistry;
 use crate::events::EventBus;
 use crate::qemu::{PortAllocator, VmRegistry};
 use crate::ssh_sessions::VmSshSessionRegistry;
+use crate::tasks::TaskQueue;
 
 pub struct AppState {
     pub config: SupervisorConfig,
@@ -17,6 +19,9 @@ pub struct AppState {
     /// Tracks live agent SSH/pty processes per agent id.
     pub agent_runtimes: AgentRuntimeRegistry,
     pub port_allocator: PortAllocator,
+    /// Host ports of runtime forwards (`crate::forwards`), drawn from
+    /// `forward_port_range`.
+    pub forward_ports: PortAllocator,
     /// In-process state-change broadcast. Mutation sites emit; the
     /// `/v1/events` websocket forwards to connected UI clients.
     pub events: EventBus,
@@ -25,40 +30,71 @@ pub struct AppState {
     /// same entry instead of spawning a fresh ssh subprocess so the
     /// inner shell / claude process stays alive.
     pub ssh_sessions: Arc<VmSshSessionRegistry>,
+    /// Egress proxies of VMs whose network policy isn't `open`.
+    pub egress_proxies: EgressProxyRegistry,
+    /// Wake-up and cancel handles for the headless task dispatcher.
+    pub tasks: TaskQueue,
+    /// Serialises idle suspends against resumes on attach
+    /// (`crate::idle`), so a client can't attach to a VM the monitor is
+    /// about to pause.
+    pub vm_power: Mutex<()>,
+    /// Serialises the per-user quota check in `create_vm` with the VM
+    /// insert, so two concurrent creates can't both squeeze under the
+    /// same limit (`crate::quota`).
+    pub vm_quota: Mutex<()>,
 }
 
 impl AppState {
     pub fn new(config: SupervisorConfig, db: SqlitePool) -> Self {
         let port_allocator = PortAllocator::new(config.port_range.clone());
+        let forward_ports =
+            PortAllocator::new(config.forward_port_range.clone()).for_setting("forward_port_range");
         Self {
             config,
             db,
             vms: RwLock::new(VmRegistry::default()),
             agent_runtimes: AgentRuntimeRegistry::new(),
             port_allocator,
+            forward_ports,
             events: EventBus::new(),
             ssh_sessions: Arc::new(VmSshSessionRegistry::new()),
+            egress_proxies: EgressProxyRegistry::default(),
+            tasks: TaskQueue::default(),
+            vm_power: Mutex::new(()),
+            vm_quota: Mutex::new(()),
         }
     }
 
     /// Construct with an already-reserved set of ports. Used at startup to
-    /// recover allocator state from the `vms` table so the supervisor never
-    /// hands out a port still bound by a VM that survived the restart.
+    /// recover allocator state from the `vms` and `vm_forwards` tables so
+    /// the supervisor never hands out a port still bound by a VM that
+    /// survived the restart.
     pub fn with_port_reservations(
         config: SupervisorConfig,
         db: SqlitePool,
         reservations: impl IntoIterator<Item = u16>,
+        forward_reservations: impl IntoIterator<Item = u16>,
     ) -> Self {
         let port_allocator =
             PortAllocator::with_reservations(config.port_range.clone(), reservations);
+        let forward_ports = PortAllocator::with_reservations(
+            config.forward_port_range.clone(),
+            forward_reservations,
+        )
+        .for_setting("forward_port_range");
         Self {
             config,
             db,
             vms: RwLock::new(VmRegistry::default()),
             agent_runtimes: AgentRuntimeRegistry::new(),
             port_allocator,
+            forward_ports,
             events: EventBus::new(),
             ssh_sessions: Arc::new(VmSshSessionRegistry::new()),
+            egress_proxies: EgressProxyRegistry::default(),
+            tasks: TaskQueue::default(),
+            vm_power: Mutex::new(()),
+            vm_quota: Mutex::new(()),
         }
     }
 }
@@ -0,0 +1,676 @@
+//! Headless agent task runs: queue, dispatcher, and runner.
+//!
+//! A task is a one-shot, non-interactive agent invocation: "run kind K on
+//! this prompt, give me the transcript and whatever it wrote to `/out`".
+//! `POST /v1/tasks` only inserts a `queued` row; the dispatcher spawned at
+//! startup claims rows oldest-first, up to `task_queue.max_concurrent` at
+//! a time, and runs each one:
+//!
+//! ```text
+//!   queued ─► running ─┬─► succeeded   (argv exited 0)
+//!                      ├─► failed      (non-zero exit, boot/ssh error)
+//!                      ├─► timed_out   (ran past timeout_secs)
+//!                      └─► cancelled   (POST /v1/tasks/{id}/cancel)
+//! ```
+//!
+//! 1. Use the VM named at queue time, or boot a throwaway one (`owns_vm`)
+//!    through the same `launch_vm` path `POST /v1/vms` uses.
+//! 2. Over ssh, write the prompt to [`GUEST_TASK_PROMPT_PATH`] and create
+//!    an empty [`GUEST_TASK_OUT_DIR`].
+//! 3. Run the kind's `headless_argv` under `timeout`, prompt on stdin;
+//!    stdout+stderr stream into `<state_dir>/tasks/<id>/transcript.log`.
+//! 4. `tar` up `/out` and unpack it into `<state_dir>/tasks/<id>/out/`.
+//! 5. Tear down the VM if the task booted it.
+//!
+//! Two tasks never run in the same existing VM at once — they would share
+//! `/out`. Tasks still `running` when the supervisor restarts are failed
+//! by `recovery::reconcile_running_tasks`; queued ones just wait.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::sync::{Notify, Semaphore};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+
+use crate::api::agents::AgentKindName;
+use crate::api::vms::{launch_vm, load_vm, teardown_vm, CreateVmRequest, VmStatus, VmSummary};
+use crate::egress::guest_proxy_env;
+use crate::error::{Result, SupervisorError};
+use crate::events::SupervisorEvent;
+use crate::idle::resume_if_suspended;
+use crate::kinds::{self, shell_quote, AgentKind};
+use crate::ssh_keys::vm_key_paths;
+use crate::state::SharedState;
+
+/// Guest directory holding the prompt and the runner's pid file.
+pub const GUEST_TASK_DIR: &str = "/run/mows-task";
+/// Where the prompt lands inside the guest (`$MOWS_TASK_PROMPT_FILE`).
+pub const GUEST_TASK_PROMPT_PATH: &str = "/run/mows-task/prompt.md";
+/// Everything the agent writes here is collected (`$MOWS_TASK_OUT_DIR`).
+pub const GUEST_TASK_OUT_DIR: &str = "/out";
+
+/// Collected `/out` archives larger than this fail the task rather than
+/// fill the supervisor's disk.
+pub const MAX_OUTPUT_ARCHIVE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// How long a freshly booted task VM may take to answer ssh. A bit above
+/// the readiness probe's own 180 s so the probe's verdict wins.
+const VM_READY_TIMEOUT: Duration = Duration::from_secs(200);
+
+/// Extra time past `timeout_secs` before the supervisor kills the ssh
+/// session itself. The in-guest `timeout` should have fired by then; this
+/// covers a guest that stopped responding altogether.
+const TIMEOUT_GRACE: Duration = Duration::from_secs(30);
+
+/// Fallback re-scan interval when nothing wakes the dispatcher. Covers
+/// tasks that became claimable without an explicit wake, e.g. a queued
+/// task whose target VM just finished its previous task.
+const IDLE_RESCAN: Duration = Duration::from_secs(5);
+
+/// Task lifecycle status. Mirrors the CHECK constraint in
+/// `migrations/0006_tasks.sql`.
+#[derive(
+    Serialize, Deserialize, ToSchema, sqlx::Type, Clone, Copy, Debug, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum TaskStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    TimedOut,
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_terminal(self) -> bool {
+        !matches!(self, Self::Queued | Self::Running)
+    }
+}
+
+/// `<state_dir>/tasks/<id>/` — transcript and collected outputs.
+pub fn task_dir_for(state_dir: &Path, task_id: &str) -> PathBuf {
+    state_dir.join("tasks").join(task_id)
+}
+
+pub fn transcript_path_for(task_dir: &Path) -> PathBuf {
+    task_dir.join("transcript.log")
+}
+
+pub fn outputs_dir_for(task_dir: &Path) -> PathBuf {
+    task_dir.join("out")
+}
+
+/// In-process side of the queue. The `tasks` table is the queue itself;
+/// this only wakes the dispatcher and holds cancel handles for running
+/// tasks.
+#[derive(Default)]
+pub struct TaskQueue {
+    wake: Notify,
+    running: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl TaskQueue {
+    /// Ask the dispatcher to look for claimable work now rather than at
+    /// its next idle re-scan.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Cancel a running task. Returns `false` if it isn't running in this
+    /// process (already finished, or still queued).
+    pub fn cancel(&self, task_id: &str) -> bool {
+        let running = self.running.lock().expect("task queue mutex poisoned");
+        match running.get(task_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn register(&self, task_id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.running
+            .lock()
+            .expect("task queue mutex poisoned")
+            .insert(task_id.to_string(), token.clone());
+        token
+    }
+
+    fn unregister(&self, task_id: &str) {
+        self.running
+            .lock()
+            .expect("task queue mutex poisoned")
+            .remove(task_id);
+    }
+}
+
+/// A row the dispatcher just flipped from `queued` to `running`.
+#[derive(sqlx::FromRow)]
+struct ClaimedTask {
+    id: String,
+    kind: String,
+    prompt: String,
+    cwd: Option<String>,
+    vm_id: Option<String>,
+    timeout_secs: i64,
+    owner_user_id: Option<String>,
+}
+
+/// How the agent process ended, when the run got that far.
+enum Finish {
+    Exited(i32),
+    TimedOut,
+    Cancelled,
+}
+
+/// Start the dispatcher loop. Runs for the life of the process.
+pub fn spawn_dispatcher(state: SharedState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let slots = Arc::new(Semaphore::new(state.config.task_queue.max_concurrent as usize));
+        loop {
+            let Ok(permit) = Arc::clone(&slots).acquire_owned().await else {
+                return;
+            };
+            match claim_next(&state).await {
+                Ok(Some(task)) => {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        run_task(&state, task).await;
+                        drop(permit);
+                        state.tasks.wake();
+                    });
+                }
+                Ok(None) => {
+                    drop(permit);
+                    let _ = tokio::time::timeout(IDLE_RESCAN, state.tasks.wake.notified()).await;
+                }
+                Err(e) => {
+                    drop(permit);
+                    tracing::warn!(error = %e, "task dispatcher failed to claim a task");
+                    tokio::time::sleep(IDLE_RESCAN).await;
+                }
+            }
+        }
+    })
+}
+
+/// Atomically claim the oldest queued task whose target VM (if any) isn't
+/// already busy with another task.
+async fn claim_next(state: &SharedState) -> Result<Option<ClaimedTask>> {
+    let claimed: Option<ClaimedTask> = sqlx::query_as(
+        "UPDATE tasks SET status = 'running', started_at = ?1 \
+         WHERE id = ( \
+             SELECT q.id FROM tasks q \
+             WHERE q.status = 'queued' \
+               AND (q.vm_id IS NULL OR NOT EXISTS ( \
+                   SELECT 1 FROM tasks r WHERE r.status = 'running' AND r.vm_id = q.vm_id)) \
+             ORDER BY q.created_at, q.rowid LIMIT 1) \
+         RETURNING id, kind, prompt, cwd, vm_id, timeout_secs, owner_user_id",
+    )
+    .bind(Utc::now().to_rfc3339())
+    .fetch_optional(&state.db)
+    .await?;
+    if let Some(task) = &claimed {
+        state.events.emit(SupervisorEvent::TaskUpdated { id: task.id.clone() });
+    }
+    Ok(claimed)
+}
+
+async fn run_task(state: &SharedState, task: ClaimedTask) {
+    tracing::info!(task_id = %task.id, kind = %task.kind, "task started");
+    let token = state.tasks.register(&task.id);
+    let task_dir = task_dir_for(&state.config.state_dir, &task.id);
+    let result = execute(state, &task, &task_dir, &token).await;
+    state.tasks.unregister(&task.id);
+
+    // Re-read rather than thread the VM through `execute`: the row is
+    // updated the moment a VM is booted, so this also catches one booted
+    // by a run that failed before it finished waiting for ssh.
+    let vm: Option<(Option<String>, bool)> =
+        sqlx::query_as("SELECT vm_id, owns_vm FROM tasks WHERE id = ?1")
+            .bind(&task.id)
+            .fetch_optional(&state.db)
+            .await
+            .unwrap_or_default();
+    if let Some((Some(vm_id), true)) = vm {
+        if let Err(e) = teardown_vm(state, &vm_id).await {
+            tracing::warn!(task_id = %task.id, vm_id = %vm_id, error = %e, "failed to tear down task vm");
+        }
+    }
+
+    let (status, exit_code, error) = match result {
+        Ok(Finish::Exited(0)) => (TaskStatus::Succeeded, Some(0), None),
+        Ok(Finish::Exited(code)) => (TaskStatus::Failed, Some(code), None),
+        Ok(Finish::TimedOut) => (TaskStatus::TimedOut, None, None),
+        Ok(Finish::Cancelled) => (TaskStatus::Cancelled, None, None),
+        Err(e) => (TaskStatus::Failed, None, Some(e.to_string())),
+    };
+    tracing::info!(task_id = %task.id, status = status.as_str(), exit_code, error, "task finished");
+    let update = sqlx::query(
+        "UPDATE tasks SET status = ?1, exit_code = ?2, error = ?3, finished_at = ?4 \
+         WHERE id = ?5 AND status = 'running'",
+    )
+    .bind(status.as_str())
+    .bind(exit_code.map(i64::from))
+    .bind(&error)
+    .bind(Utc::now().to_rfc3339())
+    .bind(&task.id)
+    .execute(&state.db)
+    .await;
+    if let Err(e) = update {
+        tracing::warn!(task_id = %task.id, error = %e, "failed to record task result");
+    }
+    state.events.emit(SupervisorEvent::TaskUpdated { id: task.id.clone() });
+}
+
+async fn execute(
+    state: &SharedState,
+    task: &ClaimedTask,
+    task_dir: &Path,
+    token: &CancellationToken,
+) -> Result<Finish> {
+    let kind = kinds::builtin(&task.kind).ok_or_else(|| {
+        SupervisorError::InvalidState(format!("task {} has unknown kind `{}`", task.id, task.kind))
+    })?;
+    if kind.headless_argv.is_empty() {
+        return Err(SupervisorError::InvalidState(format!(
+            "agent kind `{}` has no headless_argv",
+            kind.name
+        )));
+    }
+    tokio::fs::create_dir_all(task_dir).await?;
+
+    let vm_id = match &task.vm_id {
+        Some(vm_id) => vm_id.clone(),
+        None => boot_task_vm(state, task).await?,
+    };
+    let vm = tokio::select! {
+        vm = wait_until_running(state, &vm_id) => vm?,
+        _ = token.cancelled() => return Ok(Finish::Cancelled),
+    };
+    let guest = GuestSsh::for_vm(state, &vm, task_dir)?;
+
+    let prepare = format!(
+        "rm -rf {out} {dir} && mkdir -p -m 0777 {out} && mkdir -p {dir} \
+         && cat > {prompt} && chmod 0644 {prompt}",
+        out = GUEST_TASK_OUT_DIR,
+        dir = GUEST_TASK_DIR,
+        prompt = GUEST_TASK_PROMPT_PATH,
+    );
+    guest.run_with_stdin(&prepare, task.prompt.as_bytes()).await?;
+
+    let timeout = Duration::from_secs(u64::try_from(task.timeout_secs).unwrap_or(0));
+    let finish = run_agent(&guest, &kind, &vm, task_dir, timeout, token).await?;
+    if !matches!(finish, Finish::Exited(_)) && task.vm_id.is_some() {
+        // The VM outlives the task, and killing ssh doesn't reliably take
+        // the remote process down with it. `$$` of the remote shell is
+        // its process-group id (sshd starts each command in a new
+        // session), so this reaps the whole tree.
+        let kill = format!("kill -TERM -- -$(cat {GUEST_TASK_DIR}/pid) 2>/dev/null; true");
+        if let Err(e) = guest.run_with_stdin(&kill, b"").await {
+            tracing::warn!(task_id = %task.id, error = %e, "failed to kill task process in reused vm");
+        }
+    }
+    if !matches!(finish, Finish::Cancelled) {
+        collect_outputs(&guest, &outputs_dir_for(task_dir)).await?;
+    }
+    Ok(finish)
+}
+
+/// Boot a throwaway VM for `task` and record it on the row right away, so
+/// `run_task` tears it down whatever happens next.
+async fn boot_task_vm(state: &SharedState, task: &ClaimedTask) -> Result<String> {
+    let short = task.id.get(..8).unwrap_or(&task.id);
+    let request = CreateVmRequest {
+        preset: None,
+        name: Some(format!("task-{short}")),
+        cwd: task.cwd.clone(),
+        cpus: None,
+        memory_mb: None,
+        image: None,
+        display_mode: None,
+        network_policy: None,
+        workspace_mode: None,
+        // The task's own timeout bounds the VM, which is torn down with it.
+        idle_timeout_secs: Some(0),
+        max_lifetime_secs: Some(0),
+        idle_action: None,
+        env: None,
+        mounts: None,
+        agent_kind: AgentKindName::from_name(&task.kind),
+    };
+    let vm = launch_vm(state, task.owner_user_id.clone(), request).await?;
+    sqlx::query("UPDATE tasks SET vm_id = ?1, owns_vm = 1 WHERE id = ?2")
+        .bind(&vm.id)
+        .bind(&task.id)
+        .execute(&state.db)
+        .await?;
+    state.events.emit(SupervisorEvent::TaskUpdated { id: task.id.clone() });
+    Ok(vm.id)
+}
+
+/// Poll the VM row until the readiness probe flips it to `running`.
+async fn wait_until_running(state: &SharedState, vm_id: &str) -> Result<VmSummary> {
+    let deadline = Instant::now() + VM_READY_TIMEOUT;
+    loop {
+        let vm = load_vm(state, vm_id).await?;
+        match vm.status {
+            VmStatus::Running => return Ok(vm),
+            VmStatus::Suspended => {
+                resume_if_suspended(state, vm_id).await?;
+                return load_vm(state, vm_id).await;
+            }
+            VmStatus::Starting if Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            VmStatus::Starting => {
+                return Err(SupervisorError::VmBootTimeout(format!(
+                    "vm {vm_id} did not become reachable within {}s",
+                    VM_READY_TIMEOUT.as_secs()
+                )));
+            }
+            other => {
+                return Err(SupervisorError::Conflict(format!(
+                    "vm {vm_id} is in status `{}`; tasks need a running vm",
+                    other.as_str()
+                )));
+            }
+        }
+    }
+}
+
+/// Run `headless_argv` in the guest with the transcript streaming to disk.
+async fn run_agent(
+    guest: &GuestSsh,
+    kind: &AgentKind,
+    vm: &VmSummary,
+    task_dir: &Path,
+    timeout: Duration,
+    token: &CancellationToken,
+) -> Result<Finish> {
+    let mut env: Vec<(String, String)> = kind.env.clone().into_iter().collect();
+    env.push(("MOWS_TASK_PROMPT_FILE".into(), GUEST_TASK_PROMPT_PATH.into()));
+    env.push(("MOWS_TASK_OUT_DIR".into(), GUEST_TASK_OUT_DIR.into()));
+    if !vm.network_policy.is_open() {
+        env.extend(guest_proxy_env());
+    }
+    let remote = headless_command(&kind.headless_argv, &env, timeout.as_secs());
+
+    let transcript = std::fs::File::create(transcript_path_for(task_dir))?;
+    let mut child = guest
+        .command(&remote)
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(transcript.try_clone()?))
+        .stderr(Stdio::from(transcript))
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| SupervisorError::SshFailed(format!("ssh: {e}")))?;
+
+    let started = Instant::now();
+    tokio::select! {
+        status = child.wait() => {
+            let status = status?;
+            if started.elapsed() >= timeout {
+                Ok(Finish::TimedOut)
+            } else {
+                // ssh exits with the remote status; 255 is ssh's own
+                // failure and still counts as a failed run.
+                Ok(Finish::Exited(status.code().unwrap_or(-1)))
+            }
+        }
+        _ = tokio::time::sleep(timeout + TIMEOUT_GRACE) => {
+            let _ = child.kill().await;
+            Ok(Finish::TimedOut)
+        }
+        _ = token.cancelled() => {
+            let _ = child.kill().await;
+            Ok(Finish::Cancelled)
+        }
+    }
+}
+
+/// Remote shell line that runs `argv` with the prompt on stdin. Records
+/// the shell's pid first (see the reused-VM kill in `execute`), then
+/// `exec`s so that pid is the agent's process group.
+fn headless_command(argv: &[String], env: &[(String, String)], timeout_secs: u64) -> String {
+    let exports = env
+        .iter()
+        .map(|(key, value)| format!("export {key}={}; ", shell_quote(value)))
+        .collect::<String>();
+    let argv = argv.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" ");
+    format!(
+        "cd /workspace 2>/dev/null || cd; {exports}echo $$ > {GUEST_TASK_DIR}/pid; \
+         exec timeout {timeout_secs} {argv} < {GUEST_TASK_PROMPT_PATH} 2>&1"
+    )
+}
+
+/// Stream `/out` out of the guest as a tar archive and unpack it.
+async fn collect_outputs(guest: &GuestSsh, dest: &Path) -> Result<()> {
+    let mut child = guest
+        .command(&format!("cd {GUEST_TASK_OUT_DIR} 2>/dev/null && tar -cf - ."))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| SupervisorError::SshFailed(format!("ssh: {e}")))?;
+    let mut archive = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        stdout
+            .take(MAX_OUTPUT_ARCHIVE_BYTES + 1)
+            .read_to_end(&mut archive)
+            .await?;
+    }
+    if archive.len() as u64 > MAX_OUTPUT_ARCHIVE_BYTES {
+        let _ = child.kill().await;
+        return Err(SupervisorError::BadRequest(format!(
+            "{GUEST_TASK_OUT_DIR} exceeds the {} MiB collection limit",
+            MAX_OUTPUT_ARCHIVE_BYTES / (1024 * 1024)
+        )));
+    }
+    child.wait().await?;
+    if archive.is_empty() {
+        // No `/out` (the agent removed it) — nothing to collect.
+        return Ok(());
+    }
+    let dest = dest.to_path_buf();
+    tokio::task::spawn_blocking(move || unpack_outputs(&archive, &dest))
+        .await
+        .map_err(|e| SupervisorError::InvalidState(format!("output unpack task panicked: {e}")))?
+}
+
+/// Unpack a guest-produced archive. Only regular files and directories
+/// are kept; `unpack_in` refuses entries that would land outside `dest`,
+/// so a hostile agent can't plant links or write elsewhere on the host.
+fn unpack_outputs(archive: &[u8], dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+    let mut archive = tar::Archive::new(Cursor::new(archive));
+    archive.set_preserve_permissions(false);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_file() || entry_type.is_dir() {
+            entry.unpack_in(dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// ssh invocations against one task's VM. Same flags as the agent
+/// runtime's one-shot ssh; `known_hosts` is per task.
+struct GuestSsh {
+    key_path: PathBuf,
+    port: u16,
+    known_hosts: PathBuf,
+    target: String,
+}
+
+impl GuestSsh {
+    fn for_vm(state: &SharedState, vm: &VmSummary, task_dir: &Path) -> Result<Self> {
+        let port = vm
+            .host_ssh_port
+            .and_then(|port| u16::try_from(port).ok())
+            .ok_or_else(|| {
+                SupervisorError::InvalidState(format!("vm {} has no usable ssh port", vm.id))
+            })?;
+        let (key_path, _) = vm_key_paths(&state.config.state_dir, &vm.id);
+        Ok(Self {
+            key_path,
+            port,
+            known_hosts: task_dir.join("known_hosts"),
+            target: format!(
+                "{}@{}",
+                state.config.guest_ssh_user, state.config.external_host
+            ),
+        })
+    }
+
+    fn command(&self, remote_cmd: &str) -> Command {
+        let mut command = Command::new("ssh");
+        command
+            .arg("-i")
+            .arg(&self.key_path)
+            .arg("-p")
+            .arg(self.port.to_string())
+            .arg("-o")
+            .arg("StrictHostKeyChecking=accept-new")
+            .arg("-o")
+            .arg(format!("UserKnownHostsFile={}", self.known_hosts.display()))
+            .arg("-o")
+            .arg("IdentitiesOnly=yes")
+            .arg("-o")
+            .arg("ConnectTimeout=10")
+            .arg("-o")
+            .arg("ServerAliveInterval=15")
+            .arg("-o")
+            .arg("BatchMode=yes")
+            .arg(&self.target)
+            .arg(remote_cmd);
+        command
+    }
+
+    /// Run `remote_cmd` to completion with `stdin` piped in; non-zero exit
+    /// is an error carrying the remote stderr.
+    async fn run_with_stdin(&self, remote_cmd: &str, stdin: &[u8]) -> Result<()> {
+        let mut child = self
+            .command(remote_cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| SupervisorError::SshFailed(format!("ssh: {e}")))?;
+        if let Some(mut pipe) = child.stdin.take() {
+            pipe.write_all(stdin).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(SupervisorError::SshFailed(format!(
+                "`{remote_cmd}` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headless_command_quotes_argv_and_env() {
+        let cmd = headless_command(
+            &["/usr/bin/agent".into(), "--print".into(), "it's".into()],
+            &[("MOWS_TASK_OUT_DIR".into(), "/out".into()), ("NOTE".into(), "a b".into())],
+            90,
+        );
+        assert!(cmd.starts_with("cd /workspace 2>/dev/null || cd; "));
+        assert!(cmd.contains("export MOWS_TASK_OUT_DIR='/out'; export NOTE='a b'; "));
+        assert!(cmd.contains("echo $$ > /run/mows-task/pid; "));
+        assert!(cmd.ends_with(
+            "exec timeout 90 '/usr/bin/agent' '--print' 'it'\\''s' < /run/mows-task/prompt.md 2>&1"
+        ));
+    }
+
+    fn archive_with(entries: &[(&str, tar::EntryType, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, entry_type, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(*entry_type);
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            if *entry_type == tar::EntryType::Symlink {
+                header.set_link_name("/etc/passwd").unwrap();
+            }
+            // `append_data` validates the path; write the raw name so the
+            // test can also express `..` entries a hostile guest could send.
+            let name = header.as_old_mut().name.as_mut();
+            name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn unpack_keeps_files_and_dirs_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = archive_with(&[
+            ("./report/", tar::EntryType::Directory, b""),
+            ("./report/summary.md", tar::EntryType::Regular, b"done"),
+            ("./link", tar::EntryType::Symlink, b""),
+        ]);
+        unpack_outputs(&archive, dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("report/summary.md")).unwrap(),
+            "done"
+        );
+        assert!(std::fs::symlink_metadata(dir.path().join("link")).is_err());
+    }
+
+    #[test]
+    fn unpack_refuses_paths_outside_dest() {
+        let root = tempfile::tempdir().unwrap();
+        let dest = root.path().join("out");
+        let archive = archive_with(&[("../escape.txt", tar::EntryType::Regular, b"x")]);
+        let _ = unpack_outputs(&archive, &dest);
+        assert!(!root.path().join("escape.txt").exists());
+    }
+
+    #[test]
+    fn cancel_only_reaches_registered_tasks() {
+        let queue = TaskQueue::default();
+        assert!(!queue.cancel("t1"));
+        let token = queue.register("t1");
+        assert!(queue.cancel("t1"));
+        assert!(token.is_cancelled());
+        queue.unregister("t1");
+        assert!(!queue.cancel("t1"));
+    }
+}
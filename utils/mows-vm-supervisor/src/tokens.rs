@@ -0,0 +1,323 @@
+//! Personal access tokens (`/v1/tokens`, `mows agents token create`).
+//!
+//! Sessions from `/v1/auth/login` need a password and last a week, which
+//! suits the web UI but not scripts or CI. A personal access token is a
+//! long-lived bearer token bound to one user, with an expiry and a set of
+//! [`TokenScope`]s. Scopes only narrow: a request passes when the token's
+//! scopes cover the route (see [`required_scope`]) *and* the user's role
+//! allows it, so an `admin`-scoped token of a member is still a member.
+//!
+//! Tokens carry the [`TOKEN_PREFIX`] so `api::auth_middleware` can tell
+//! them from session tokens and the static admin token without a lookup.
+//! Only their SHA-256 is stored — a token has 256 random bits, so a slow
+//! password hash would buy nothing — and the plaintext is returned once,
+//! at creation.
+
+use axum::http::Method;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use crate::error::{Result, SupervisorError};
+
+/// Leading marker of every personal access token.
+pub const TOKEN_PREFIX: &str = "mows_pat_";
+
+/// Lifetime of a token created without `expires_in_days`.
+pub const DEFAULT_EXPIRY_DAYS: u32 = 90;
+
+/// Longest lifetime a token may be created with.
+pub const MAX_EXPIRY_DAYS: u32 = 365;
+
+/// What a token may be used for. `*:write` implies `*:read`; `admin`
+/// implies everything, including the admin-only endpoints (users, secrets,
+/// tokens, metrics) — which still check the user's role.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenScope {
+    #[serde(rename = "vms:read")]
+    VmsRead,
+    #[serde(rename = "vms:write")]
+    VmsWrite,
+    #[serde(rename = "agents:read")]
+    AgentsRead,
+    #[serde(rename = "agents:write")]
+    AgentsWrite,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl TokenScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::VmsRead => "vms:read",
+            Self::VmsWrite => "vms:write",
+            Self::AgentsRead => "agents:read",
+            Self::AgentsWrite => "agents:write",
+            Self::Admin => "admin",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        [
+            Self::VmsRead,
+            Self::VmsWrite,
+            Self::AgentsRead,
+            Self::AgentsWrite,
+            Self::Admin,
+        ]
+        .into_iter()
+        .find(|scope| scope.as_str() == raw)
+    }
+
+    /// True when holding `self` grants `needed`.
+    pub fn covers(self, needed: Self) -> bool {
+        self == needed
+            || self == Self::Admin
+            || matches!(
+                (self, needed),
+                (Self::VmsWrite, Self::VmsRead) | (Self::AgentsWrite, Self::AgentsRead)
+            )
+    }
+}
+
+/// `scopes` column value: space-separated, sorted, deduplicated.
+fn scopes_to_column(scopes: &[TokenScope]) -> String {
+    let mut scopes = scopes.to_vec();
+    scopes.sort();
+    scopes.dedup();
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Inverse of [`scopes_to_column`]. Unknown entries (written by a newer
+/// supervisor) are dropped, so they grant nothing.
+pub fn scopes_from_column(column: &str) -> Vec<TokenScope> {
+    column.split_whitespace().filter_map(TokenScope::parse).collect()
+}
+
+/// Scope a token needs for `method path`; `None` when any scope will do
+/// (the event stream and the caller's own quota).
+///
+/// Reads are `GET`/`HEAD`, except for the interactive streams (agent IO,
+/// VM display/console/ssh) and the VM ssh credentials, which hand out a
+/// shell and so need the write scope. Agents nested under a VM and tasks
+/// (which run agents) count as agents. Every other route is admin.
+pub fn required_scope(method: &Method, path: &str) -> Option<TokenScope> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let interactive = matches!(
+        segments.last().copied(),
+        Some("io" | "display" | "console" | "ssh" | "ssh-io")
+    );
+    let read = (method == Method::GET || method == Method::HEAD) && !interactive;
+    let (read_scope, write_scope) = match segments.as_slice() {
+        ["v1", "events"] | ["v1", "users", "me", "quota"] => return None,
+        ["v1", "vms", _, "agents", ..] | ["v1", "agents" | "tasks", ..] => {
+            (TokenScope::AgentsRead, TokenScope::AgentsWrite)
+        }
+        ["v1", "vms", ..] => (TokenScope::VmsRead, TokenScope::VmsWrite),
+        _ => return Some(TokenScope::Admin),
+    };
+    Some(if read { read_scope } else { write_scope })
+}
+
+/// True when `scopes` cover `method path`.
+pub fn allows(scopes: &[TokenScope], method: &Method, path: &str) -> bool {
+    match required_scope(method, path) {
+        Some(needed) => scopes.iter().any(|scope| scope.covers(needed)),
+        None => !scopes.is_empty(),
+    }
+}
+
+/// Hex SHA-256 of a token, as stored in `api_tokens.token_hash`.
+pub fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn generate() -> String {
+    use rand::RngCore;
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    format!(
+        "{TOKEN_PREFIX}{}",
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    )
+}
+
+/// A token as listed; never includes the token itself.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct TokenSummary {
+    pub id: String,
+    pub name: String,
+    /// Owner of the token.
+    pub username: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: String,
+    pub expires_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TokenRow {
+    id: String,
+    name: String,
+    username: String,
+    scopes: String,
+    created_at: String,
+    expires_at: String,
+    last_used_at: Option<String>,
+}
+
+impl From<TokenRow> for TokenSummary {
+    fn from(row: TokenRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            username: row.username,
+            scopes: scopes_from_column(&row.scopes),
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            last_used_at: row.last_used_at,
+        }
+    }
+}
+
+const SUMMARY_SELECT: &str = "SELECT t.id AS id, t.name AS name, u.username AS username, \
+     t.scopes AS scopes, t.created_at AS created_at, t.expires_at AS expires_at, \
+     t.last_used_at AS last_used_at FROM api_tokens t JOIN users u ON u.id = t.user_id";
+
+/// Issue a token for `user_id`. Returns the plaintext token — the only
+/// time it is available — next to its summary.
+pub async fn create(
+    db: &SqlitePool,
+    user_id: &str,
+    name: &str,
+    scopes: &[TokenScope],
+    expires_in_days: u32,
+) -> Result<(String, TokenSummary)> {
+    if name.trim().is_empty() {
+        return Err(SupervisorError::BadRequest("token name must not be empty".into()));
+    }
+    if scopes.is_empty() {
+        return Err(SupervisorError::BadRequest("a token needs at least one scope".into()));
+    }
+    if !(1..=MAX_EXPIRY_DAYS).contains(&expires_in_days) {
+        return Err(SupervisorError::BadRequest(format!(
+            "expires_in_days must be 1-{MAX_EXPIRY_DAYS}"
+        )));
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    let token = generate();
+    let now = Utc::now();
+    sqlx::query(
+        "INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, created_at, expires_at) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )
+    .bind(&id)
+    .bind(user_id)
+    .bind(name)
+    .bind(hash(&token))
+    .bind(scopes_to_column(scopes))
+    .bind(now.to_rfc3339())
+    .bind((now + Duration::days(i64::from(expires_in_days))).to_rfc3339())
+    .execute(db)
+    .await?;
+    let summary = find(db, &id)
+        .await?
+        .ok_or_else(|| SupervisorError::Internal(format!("token {id} vanished after insert")))?;
+    Ok((token, summary))
+}
+
+/// Tokens of `user_id`, or of every user when `None`.
+pub async fn list(db: &SqlitePool, user_id: Option<&str>) -> Result<Vec<TokenSummary>> {
+    let rows: Vec<TokenRow> = match user_id {
+        Some(user_id) => {
+            sqlx::query_as(&format!("{SUMMARY_SELECT} WHERE t.user_id = ?1 ORDER BY t.created_at"))
+                .bind(user_id)
+                .fetch_all(db)
+                .await?
+        }
+        None => {
+            sqlx::query_as(&format!("{SUMMARY_SELECT} ORDER BY u.username, t.created_at"))
+                .fetch_all(db)
+                .await?
+        }
+    };
+    Ok(rows.into_iter().map(TokenSummary::from).collect())
+}
+
+pub async fn find(db: &SqlitePool, id: &str) -> Result<Option<TokenSummary>> {
+    let row: Option<TokenRow> = sqlx::query_as(&format!("{SUMMARY_SELECT} WHERE t.id = ?1"))
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+    Ok(row.map(TokenSummary::from))
+}
+
+/// Owner of token `id`, if it exists.
+pub async fn owner(db: &SqlitePool, id: &str) -> Result<Option<String>> {
+    Ok(sqlx::query_scalar("SELECT user_id FROM api_tokens WHERE id = ?1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?)
+}
+
+/// Revoke a token. It stops authenticating immediately.
+pub async fn delete(db: &SqlitePool, id: &str) -> Result<()> {
+    sqlx::query("DELETE FROM api_tokens WHERE id = ?1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_map_routes_and_imply_reads() {
+        let get = Method::GET;
+        let post = Method::POST;
+        assert_eq!(required_scope(&get, "/v1/vms"), Some(TokenScope::VmsRead));
+        assert_eq!(required_scope(&post, "/v1/vms/abc/stop"), Some(TokenScope::VmsWrite));
+        assert_eq!(required_scope(&get, "/v1/vms/abc/console"), Some(TokenScope::VmsWrite));
+        assert_eq!(required_scope(&get, "/v1/vms/abc/ssh"), Some(TokenScope::VmsWrite));
+        assert_eq!(required_scope(&get, "/v1/vms/abc/agents"), Some(TokenScope::AgentsRead));
+        assert_eq!(required_scope(&get, "/v1/agents/x/io"), Some(TokenScope::AgentsWrite));
+        assert_eq!(required_scope(&post, "/v1/tasks"), Some(TokenScope::AgentsWrite));
+        assert_eq!(required_scope(&get, "/v1/users"), Some(TokenScope::Admin));
+        assert_eq!(required_scope(&post, "/v1/tokens"), Some(TokenScope::Admin));
+        assert_eq!(required_scope(&get, "/v1/events"), None);
+
+        assert!(allows(&[TokenScope::VmsWrite], &get, "/v1/vms/abc"));
+        assert!(!allows(&[TokenScope::VmsWrite], &get, "/v1/agents"));
+        assert!(!allows(&[TokenScope::AgentsRead], &post, "/v1/agents/x/stop"));
+        assert!(allows(&[TokenScope::Admin], &post, "/v1/users"));
+        assert!(allows(&[TokenScope::AgentsRead], &get, "/v1/events"));
+        assert!(!allows(&[], &get, "/v1/events"));
+    }
+
+    #[test]
+    fn scope_column_round_trips_and_ignores_unknown_entries() {
+        let column = scopes_to_column(&[
+            TokenScope::AgentsWrite,
+            TokenScope::VmsRead,
+            TokenScope::AgentsWrite,
+        ]);
+        assert_eq!(column, "vms:read agents:write");
+        assert_eq!(
+            scopes_from_column("vms:read nodes:write agents:write"),
+            vec![TokenScope::VmsRead, TokenScope::AgentsWrite]
+        );
+        let token = generate();
+        assert!(token.starts_with(TOKEN_PREFIX));
+        assert_eq!(hash(&token).len(), 64);
+        assert_ne!(hash(&token), hash(&generate()));
+    }
+}
@@ -0,0 +1,933 @@
+//! Workspace isolation for a VM's `cwd`.
+//!
+//! - `rw`: the host directory itself is the `workspace` 9p share; the
+//!   guest edits the checkout in place.
+//! - `overlay`: the directory is copied into `<vm_dir>/workspace` and the
+//!   copy is shared read-write, while the original is shared read-only as
+//!   `workspacebase` (`/workspace-base` in the guest). `std::fs::copy`
+//!   reflinks on filesystems that support it, so the copy is cheap on
+//!   btrfs/xfs. A manifest of size + mtime taken at copy time tells the
+//!   agent's edits apart from later edits to the original: `apply`
+//!   refuses to overwrite a file both sides touched.
+//! - `worktree`: `git worktree add` checks out a new `mows/<vm-name>`
+//!   branch into `<vm_dir>/workspace`. The repository's common git dir is
+//!   shared as `gitdir` and mounted at its host path, so the worktree's
+//!   `.git` pointer resolves unchanged inside the guest. `apply` commits
+//!   whatever the agent left uncommitted and merges the branch into the
+//!   original checkout's current branch. Deleting the VM removes the
+//!   worktree but keeps the branch.
+//!
+//! Git runs with `safe.directory=*`: the supervisor runs as root while
+//! checkouts belong to the host user.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use utoipa::ToSchema;
+
+use crate::error::{Result, SupervisorError};
+
+/// Worktree branches are `mows/<vm-name>` (plus the VM id's first
+/// segment if that name is taken).
+pub const WORKTREE_BRANCH_PREFIX: &str = "mows/";
+
+/// Overlay bookkeeping, next to the copy in the VM dir.
+const MANIFEST_FILE: &str = "workspace-manifest.json";
+
+/// Top-level entries `overlay` mode copies but never diffs or applies:
+/// the copy's git metadata is the agent's scratch state, not a change to
+/// hand back.
+const OVERLAY_UNTRACKED: &[&str] = &[".git"];
+
+/// Identity for the commit `apply` makes from uncommitted worktree changes
+/// (and for the merge, when the repository has no identity configured).
+const GIT_USER_NAME: &str = "mows-vm-supervisor";
+const GIT_USER_EMAIL: &str = "mows-vm-supervisor@localhost";
+
+#[derive(
+    Serialize, Deserialize, ToSchema, sqlx::Type, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum WorkspaceMode {
+    /// The host directory, mounted read-write.
+    #[default]
+    Rw,
+    /// A per-VM copy; the original is mounted read-only at `/workspace-base`.
+    Overlay,
+    /// A fresh `git worktree` on a `mows/<vm-name>` branch.
+    Worktree,
+}
+
+impl WorkspaceMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Rw => "rw",
+            Self::Overlay => "overlay",
+            Self::Worktree => "worktree",
+        }
+    }
+}
+
+/// Result of `POST /v1/vms/{id}/apply`.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq, Eq)]
+pub struct WorkspaceApplyResult {
+    /// Paths, relative to the VM's `cwd`, written or removed there.
+    pub paths: Vec<String>,
+    /// `worktree` mode: the commit the original checkout is at after the
+    /// merge.
+    pub merged_commit: Option<String>,
+}
+
+/// What `QemuInvocation::build` shares with the guest for one VM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedWorkspace {
+    /// Mounted read-write at `/workspace`.
+    pub share: PathBuf,
+    /// `overlay`: the original directory, mounted read-only.
+    pub base: Option<PathBuf>,
+    /// `worktree`: the repository's common git dir, mounted read-write at
+    /// the same path in the guest.
+    pub gitdir: Option<PathBuf>,
+    /// `worktree`: the branch checked out in `share`.
+    pub branch: Option<String>,
+}
+
+/// Where `overlay` and `worktree` modes put the VM's private workspace.
+pub fn workspace_dir_for(vm_dir: &Path) -> PathBuf {
+    vm_dir.join("workspace")
+}
+
+/// Reject a `(mode, cwd)` combination before the VM row is written.
+/// `worktree` needs `cwd` to be the top level of a git checkout with at
+/// least one commit.
+pub async fn validate_source(mode: WorkspaceMode, source: Option<&Path>) -> Result<()> {
+    if mode == WorkspaceMode::Rw {
+        return Ok(());
+    }
+    let Some(source) = source else {
+        return Err(SupervisorError::BadRequest(format!(
+            "workspace_mode `{}` requires `cwd`",
+            mode.as_str()
+        )));
+    };
+    if mode == WorkspaceMode::Worktree {
+        let toplevel = git(source, ["rev-parse", "--show-toplevel"])
+            .await
+            .map_err(|_| {
+                SupervisorError::BadRequest(
+                    "workspace_mode `worktree` requires `cwd` to be a git checkout".into(),
+                )
+            })?;
+        let toplevel = PathBuf::from(toplevel.trim());
+        if toplevel.canonicalize().ok().as_deref() != Some(source) {
+            return Err(SupervisorError::BadRequest(
+                "workspace_mode `worktree` requires `cwd` to be the top level of its git checkout"
+                    .into(),
+            ));
+        }
+        git(source, ["rev-parse", "--verify", "HEAD"])
+            .await
+            .map_err(|_| {
+                SupervisorError::BadRequest(
+                    "workspace_mode `worktree` requires a repository with at least one commit"
+                        .into(),
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// Set up the VM's workspace share. `source` has passed
+/// `qemu::validate_workspace_path` and [`validate_source`].
+pub async fn prepare(
+    mode: WorkspaceMode,
+    source: &Path,
+    vm_dir: &Path,
+    vm_id: &str,
+    vm_name: &str,
+) -> Result<PreparedWorkspace> {
+    match mode {
+        WorkspaceMode::Rw => Ok(PreparedWorkspace {
+            share: source.to_path_buf(),
+            base: None,
+            gitdir: None,
+            branch: None,
+        }),
+        WorkspaceMode::Overlay => {
+            let copy = workspace_dir_for(vm_dir);
+            let manifest_path = vm_dir.join(MANIFEST_FILE);
+            let (src, dst) = (source.to_path_buf(), copy.clone());
+            tokio::task::spawn_blocking(move || {
+                let manifest = copy_tree(&src, &dst)?;
+                write_manifest(&manifest_path, &manifest)
+            })
+            .await
+            .map_err(|e| SupervisorError::InvalidState(format!("workspace copy panicked: {e}")))??;
+            Ok(PreparedWorkspace {
+                share: copy,
+                base: Some(source.to_path_buf()),
+                gitdir: None,
+                branch: None,
+            })
+        }
+        WorkspaceMode::Worktree => {
+            let worktree = workspace_dir_for(vm_dir);
+            let gitdir = git(
+                source,
+                ["rev-parse", "--path-format=absolute", "--git-common-dir"],
+            )
+            .await?;
+            let gitdir = PathBuf::from(gitdir.trim()).canonicalize()?;
+            // Same constraint as `validate_workspace_path`: the path ends
+            // up inside a comma-separated `-fsdev` option.
+            if gitdir
+                .to_str()
+                .is_none_or(|path| path.contains([',', '\n', '\r']))
+            {
+                return Err(SupervisorError::BadRequest(format!(
+                    "git dir {} can't be shared with the guest (non-UTF-8, comma or newline)",
+                    gitdir.display()
+                )));
+            }
+            let mut branch = format!("{WORKTREE_BRANCH_PREFIX}{vm_name}");
+            if branch_exists(source, &branch).await? {
+                let short = vm_id.split('-').next().unwrap_or(vm_id);
+                branch = format!("{branch}-{short}");
+            }
+            git(
+                source,
+                [
+                    OsStr::new("worktree"),
+                    OsStr::new("add"),
+                    OsStr::new("-b"),
+                    OsStr::new(&branch),
+                    worktree.as_os_str(),
+                    OsStr::new("HEAD"),
+                ],
+            )
+            .await?;
+            Ok(PreparedWorkspace {
+                share: worktree,
+                base: None,
+                gitdir: Some(gitdir),
+                branch: Some(branch),
+            })
+        }
+    }
+}
+
+/// The agent's changes as a unified diff against the original directory.
+pub async fn diff(mode: WorkspaceMode, source: &Path, vm_dir: &Path) -> Result<String> {
+    match mode {
+        WorkspaceMode::Rw => Err(rw_has_no_changes()),
+        WorkspaceMode::Overlay => overlay_diff(source, vm_dir).await,
+        WorkspaceMode::Worktree => worktree_diff(source, vm_dir).await,
+    }
+}
+
+/// Merge the agent's changes back into the original directory.
+pub async fn apply(
+    mode: WorkspaceMode,
+    source: &Path,
+    vm_dir: &Path,
+    vm_name: &str,
+    branch: Option<&str>,
+) -> Result<WorkspaceApplyResult> {
+    match mode {
+        WorkspaceMode::Rw => Err(rw_has_no_changes()),
+        WorkspaceMode::Overlay => {
+            let (src, dir) = (source.to_path_buf(), vm_dir.to_path_buf());
+            let paths = tokio::task::spawn_blocking(move || apply_overlay(&src, &dir))
+                .await
+                .map_err(|e| {
+                    SupervisorError::InvalidState(format!("workspace apply panicked: {e}"))
+                })??;
+            Ok(WorkspaceApplyResult {
+                paths,
+                merged_commit: None,
+            })
+        }
+        WorkspaceMode::Worktree => {
+            let branch = branch.ok_or_else(|| {
+                SupervisorError::InvalidState("worktree VM has no workspace_branch".into())
+            })?;
+            apply_worktree(source, vm_dir, vm_name, branch).await
+        }
+    }
+}
+
+/// Detach a `worktree` VM's checkout from its repository. The branch
+/// stays so the agent's work survives the VM.
+pub async fn remove_worktree(source: &Path, vm_dir: &Path) -> Result<()> {
+    let worktree = workspace_dir_for(vm_dir);
+    git(
+        source,
+        [
+            OsStr::new("worktree"),
+            OsStr::new("remove"),
+            OsStr::new("--force"),
+            worktree.as_os_str(),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+fn rw_has_no_changes() -> SupervisorError {
+    SupervisorError::BadRequest(
+        "vm uses workspace_mode `rw`; its changes are already in cwd".into(),
+    )
+}
+
+// ---------------------------------------------------------------------------
+// overlay
+// ---------------------------------------------------------------------------
+
+/// Size + mtime of a regular file or symlink (never followed).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+struct FileStamp {
+    symlink: bool,
+    len: u64,
+    mtime_ns: i64,
+}
+
+impl FileStamp {
+    fn of(meta: &std::fs::Metadata) -> Self {
+        Self {
+            symlink: meta.file_type().is_symlink(),
+            len: meta.len(),
+            mtime_ns: meta.mtime() * 1_000_000_000 + meta.mtime_nsec(),
+        }
+    }
+
+    fn read(path: &Path) -> std::io::Result<Option<Self>> {
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if meta.is_file() || meta.file_type().is_symlink() => {
+                Ok(Some(Self::of(&meta)))
+            }
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Both sides of a file as of the copy (or the last apply).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+struct ManifestEntry {
+    source: FileStamp,
+    copy: FileStamp,
+}
+
+type OverlayManifest = BTreeMap<String, ManifestEntry>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverlayChange {
+    Added,
+    Modified,
+    Deleted,
+}
+
+fn write_manifest(path: &Path, manifest: &OverlayManifest) -> Result<()> {
+    std::fs::write(path, serde_json::to_vec(manifest)?)?;
+    Ok(())
+}
+
+fn read_manifest(path: &Path) -> Result<OverlayManifest> {
+    let bytes = std::fs::read(path).map_err(|e| {
+        SupervisorError::FilesystemError(format!(
+            "failed to read workspace manifest {}: {e}",
+            path.display()
+        ))
+    })?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+fn is_untracked(rel: &Path) -> bool {
+    rel.components()
+        .next()
+        .is_some_and(|first| OVERLAY_UNTRACKED.iter().any(|name| first.as_os_str() == *name))
+}
+
+fn manifest_key(rel: &Path) -> Result<String> {
+    rel.to_str().map(str::to_owned).ok_or_else(|| {
+        SupervisorError::BadRequest(format!(
+            "workspace path {} is not valid UTF-8; overlay mode can't track it",
+            rel.display()
+        ))
+    })
+}
+
+/// Copy `source` into the not-yet-existing `dest`, returning the manifest
+/// of every tracked file and symlink. Sockets, fifos and devices are
+/// skipped.
+fn copy_tree(source: &Path, dest: &Path) -> Result<OverlayManifest> {
+    let mut manifest = OverlayManifest::new();
+    std::fs::create_dir(dest)?;
+    copy_dir(source, dest, Path::new(""), &mut manifest)?;
+    Ok(manifest)
+}
+
+fn copy_dir(
+    source_root: &Path,
+    dest_root: &Path,
+    rel_dir: &Path,
+    manifest: &mut OverlayManifest,
+) -> Result<()> {
+    for entry in std::fs::read_dir(source_root.join(rel_dir))? {
+        let entry = entry?;
+        let rel = rel_dir.join(entry.file_name());
+        let (src, dst) = (source_root.join(&rel), dest_root.join(&rel));
+        let meta = entry.metadata()?;
+        let file_type = meta.file_type();
+        if file_type.is_dir() {
+            std::fs::create_dir(&dst)?;
+            std::fs::set_permissions(&dst, meta.permissions())?;
+            copy_dir(source_root, dest_root, &rel, manifest)?;
+            continue;
+        }
+        if file_type.is_file() {
+            std::fs::copy(&src, &dst)?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(&src)?, &dst)?;
+        } else {
+            continue;
+        }
+        if !is_untracked(&rel) {
+            manifest.insert(
+                manifest_key(&rel)?,
+                ManifestEntry {
+                    source: FileStamp::of(&meta),
+                    copy: FileStamp::of(&std::fs::symlink_metadata(&dst)?),
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Every tracked file and symlink currently under `root`.
+fn scan_tree(root: &Path) -> Result<BTreeMap<String, FileStamp>> {
+    fn walk(root: &Path, rel_dir: &Path, out: &mut BTreeMap<String, FileStamp>) -> Result<()> {
+        for entry in std::fs::read_dir(root.join(rel_dir))? {
+            let entry = entry?;
+            let rel = rel_dir.join(entry.file_name());
+            if is_untracked(&rel) {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                walk(root, &rel, out)?;
+            } else if meta.is_file() || meta.file_type().is_symlink() {
+                out.insert(manifest_key(&rel)?, FileStamp::of(&meta));
+            }
+        }
+        Ok(())
+    }
+    let mut out = BTreeMap::new();
+    walk(root, Path::new(""), &mut out)?;
+    Ok(out)
+}
+
+/// What the agent changed in the copy since the manifest was written.
+fn overlay_changes(
+    manifest: &OverlayManifest,
+    copy_now: &BTreeMap<String, FileStamp>,
+) -> Vec<(String, OverlayChange)> {
+    let mut changes: Vec<(String, OverlayChange)> = copy_now
+        .iter()
+        .filter_map(|(path, stamp)| match manifest.get(path) {
+            None => Some((path.clone(), OverlayChange::Added)),
+            Some(entry) if entry.copy != *stamp => Some((path.clone(), OverlayChange::Modified)),
+            Some(_) => None,
+        })
+        .collect();
+    changes.extend(
+        manifest
+            .keys()
+            .filter(|path| !copy_now.contains_key(*path))
+            .map(|path| (path.clone(), OverlayChange::Deleted)),
+    );
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
+
+async fn overlay_diff(source: &Path, vm_dir: &Path) -> Result<String> {
+    let copy = workspace_dir_for(vm_dir);
+    let manifest_path = vm_dir.join(MANIFEST_FILE);
+    let scan_root = copy.clone();
+    let (manifest, copy_now) = tokio::task::spawn_blocking(move || {
+        Ok::<_, SupervisorError>((read_manifest(&manifest_path)?, scan_tree(&scan_root)?))
+    })
+    .await
+    .map_err(|e| SupervisorError::InvalidState(format!("workspace scan panicked: {e}")))??;
+
+    let mut out = String::new();
+    for (rel, _) in overlay_changes(&manifest, &copy_now) {
+        let (old, new) = (source.join(&rel), copy.join(&rel));
+        let old_link = std::fs::read_link(&old).ok();
+        let new_link = std::fs::read_link(&new).ok();
+        if old_link.is_some() || new_link.is_some() {
+            let show = |link: Option<PathBuf>, path: &Path| match link {
+                Some(target) => format!("symlink to {}", target.display()),
+                None if path.exists() => "regular file".to_string(),
+                None => "absent".to_string(),
+            };
+            out.push_str(&format!(
+                "{rel}: {} -> {}\n",
+                show(old_link, &old),
+                show(new_link, &new)
+            ));
+            continue;
+        }
+        out.push_str(&unified_diff(&rel, &old, &new).await?);
+    }
+    Ok(out)
+}
+
+/// `diff -uN` of one file, labelled `a/<rel>` / `b/<rel>` like git.
+async fn unified_diff(rel: &str, old: &Path, new: &Path) -> Result<String> {
+    let output = Command::new("diff")
+        .arg("-u")
+        .arg("-N")
+        .arg("-L")
+        .arg(format!("a/{rel}"))
+        .arg("-L")
+        .arg(format!("b/{rel}"))
+        .arg(old)
+        .arg(new)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| SupervisorError::Internal(format!("failed to exec diff: {e}")))?;
+    // 0 = identical, 1 = differ, anything else = trouble.
+    match output.status.code() {
+        Some(0 | 1) => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        _ => Err(SupervisorError::FilesystemError(format!(
+            "diff {} {} exited with {}: {}",
+            old.display(),
+            new.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+/// Copy the agent's changes into `source`. All-or-nothing on conflicts:
+/// if any touched file also changed in `source` since the copy, nothing
+/// is written.
+fn apply_overlay(source: &Path, vm_dir: &Path) -> Result<Vec<String>> {
+    let copy = workspace_dir_for(vm_dir);
+    let manifest_path = vm_dir.join(MANIFEST_FILE);
+    let mut manifest = read_manifest(&manifest_path)?;
+    let changes = overlay_changes(&manifest, &scan_tree(&copy)?);
+
+    let mut conflicts = Vec::new();
+    for (rel, _) in &changes {
+        let expected = manifest.get(rel).map(|entry| entry.source);
+        if FileStamp::read(&source.join(rel))? != expected {
+            conflicts.push(rel.as_str());
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(SupervisorError::Conflict(format!(
+            "changed in cwd since the vm was created: {}",
+            conflicts.join(", ")
+        )));
+    }
+
+    for (rel, change) in &changes {
+        let (dst, src) = (source.join(rel), copy.join(rel));
+        if *change == OverlayChange::Deleted {
+            match std::fs::remove_file(&dst) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            manifest.remove(rel);
+            continue;
+        }
+        if let Some(parent) = dst.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let src_meta = std::fs::symlink_metadata(&src)?;
+        // Write regular files in place so the original keeps its owner;
+        // anything whose type changed is replaced.
+        let replace = FileStamp::read(&dst)?
+            .is_some_and(|stamp| stamp.symlink || src_meta.file_type().is_symlink());
+        if replace {
+            std::fs::remove_file(&dst)?;
+        }
+        if src_meta.file_type().is_symlink() {
+            std::os::unix::fs::symlink(std::fs::read_link(&src)?, &dst)?;
+        } else {
+            std::fs::copy(&src, &dst)?;
+        }
+        manifest.insert(
+            rel.clone(),
+            ManifestEntry {
+                source: FileStamp::of(&std::fs::symlink_metadata(&dst)?),
+                copy: FileStamp::of(&src_meta),
+            },
+        );
+    }
+    write_manifest(&manifest_path, &manifest)?;
+    Ok(changes.into_iter().map(|(rel, _)| rel).collect())
+}
+
+// ---------------------------------------------------------------------------
+// worktree
+// ---------------------------------------------------------------------------
+
+fn git_command(repo: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("-c")
+        .arg("safe.directory=*")
+        .arg("-C")
+        .arg(repo)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null());
+    command
+}
+
+async fn git_output(mut command: Command) -> Result<Output> {
+    command
+        .output()
+        .await
+        .map_err(|e| SupervisorError::Git(format!("failed to exec git: {e}")))
+}
+
+fn git_failure(args: &[&OsStr], output: &Output) -> SupervisorError {
+    let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+    SupervisorError::Git(format!(
+        "git {} exited with {}: {}",
+        args.join(" "),
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+/// Run `git -C repo <args>` and return its stdout; non-zero exit is an error.
+async fn git<I, S>(repo: &Path, args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+    let args: Vec<&OsStr> = args.iter().map(AsRef::as_ref).collect();
+    let mut command = git_command(repo);
+    command.args(&args);
+    let output = git_output(command).await?;
+    if !output.status.success() {
+        return Err(git_failure(&args, &output));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn branch_exists(repo: &Path, branch: &str) -> Result<bool> {
+    let mut command = git_command(repo);
+    command
+        .args(["show-ref", "--verify", "--quiet"])
+        .arg(format!("refs/heads/{branch}"));
+    Ok(git_output(command).await?.status.success())
+}
+
+/// Committed and uncommitted worktree changes since the branch forked
+/// from the original checkout's `HEAD`, untracked files included. Staged
+/// through a throwaway index so the agent's own index is left alone.
+async fn worktree_diff(source: &Path, vm_dir: &Path) -> Result<String> {
+    let worktree = workspace_dir_for(vm_dir);
+    let source_head = git(source, ["rev-parse", "HEAD"]).await?;
+    let base = git(&worktree, ["merge-base", "HEAD", source_head.trim()]).await?;
+
+    let index = vm_dir.join("workspace-diff.index");
+    let _ = tokio::fs::remove_file(&index).await;
+    let result = async {
+        for args in [&["read-tree", "HEAD"][..], &["add", "-A"][..]] {
+            let mut command = git_command(&worktree);
+            command.env("GIT_INDEX_FILE", &index).args(args);
+            let output = git_output(command).await?;
+            if !output.status.success() {
+                let args: Vec<&OsStr> = args.iter().map(OsStr::new).collect();
+                return Err(git_failure(&args, &output));
+            }
+        }
+        let mut command = git_command(&worktree);
+        command
+            .env("GIT_INDEX_FILE", &index)
+            .args(["diff", "--cached", "--binary", base.trim()]);
+        let output = git_output(command).await?;
+        if !output.status.success() {
+            return Err(git_failure(&[OsStr::new("diff")], &output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&index).await;
+    result
+}
+
+async fn apply_worktree(
+    source: &Path,
+    vm_dir: &Path,
+    vm_name: &str,
+    branch: &str,
+) -> Result<WorkspaceApplyResult> {
+    let worktree = workspace_dir_for(vm_dir);
+    let identity = [
+        format!("user.name={GIT_USER_NAME}"),
+        format!("user.email={GIT_USER_EMAIL}"),
+    ];
+
+    git(&worktree, ["add", "-A"]).await?;
+    let mut command = git_command(&worktree);
+    command.args(["diff", "--cached", "--quiet"]);
+    if !git_output(command).await?.status.success() {
+        git(
+            &worktree,
+            [
+                "-c",
+                &identity[0],
+                "-c",
+                &identity[1],
+                "commit",
+                "--quiet",
+                "--no-verify",
+                "-m",
+                &format!("Agent changes from vm {vm_name}"),
+            ],
+        )
+        .await?;
+    }
+
+    let before = git(source, ["rev-parse", "HEAD"]).await?;
+    let mut command = git_command(source);
+    // Only fall back to the supervisor identity when the repository has
+    // none; a merge commit should normally carry the checkout owner's.
+    if git(source, ["config", "user.email"]).await.is_err() {
+        command.arg("-c").arg(&identity[0]).arg("-c").arg(&identity[1]);
+    }
+    command.args(["merge", "--no-edit", branch]);
+    let output = git_output(command).await?;
+    if !output.status.success() {
+        let mut abort = git_command(source);
+        abort.args(["merge", "--abort"]);
+        let _ = git_output(abort).await;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .chain(stdout.lines())
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("merge failed");
+        return Err(SupervisorError::Conflict(format!(
+            "merging {branch} into cwd failed; cwd was left unchanged: {}",
+            reason.trim()
+        )));
+    }
+    let after = git(source, ["rev-parse", "HEAD"]).await?;
+    let (before, after) = (before.trim(), after.trim());
+    let paths = if before == after {
+        Vec::new()
+    } else {
+        git(source, ["diff", "--name-only", before, after])
+            .await?
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    };
+    Ok(WorkspaceApplyResult {
+        paths,
+        merged_commit: Some(after.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(len: u64, mtime_ns: i64) -> FileStamp {
+        FileStamp {
+            symlink: false,
+            len,
+            mtime_ns,
+        }
+    }
+
+    #[test]
+    fn overlay_changes_classifies_added_modified_deleted() {
+        let mut manifest = OverlayManifest::new();
+        for (path, copy) in [("kept", stamp(1, 1)), ("edited", stamp(2, 2)), ("gone", stamp(3, 3))] {
+            manifest.insert(path.into(), ManifestEntry { source: stamp(9, 9), copy });
+        }
+        let copy_now = BTreeMap::from([
+            ("kept".to_string(), stamp(1, 1)),
+            ("edited".to_string(), stamp(2, 5)),
+            ("new".to_string(), stamp(4, 4)),
+        ]);
+        assert_eq!(
+            overlay_changes(&manifest, &copy_now),
+            vec![
+                ("edited".to_string(), OverlayChange::Modified),
+                ("gone".to_string(), OverlayChange::Deleted),
+                ("new".to_string(), OverlayChange::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn copy_tree_skips_git_dir_in_manifest() {
+        let source = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(source.path().join(".git/objects")).unwrap();
+        std::fs::write(source.path().join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::create_dir(source.path().join("src")).unwrap();
+        std::fs::write(source.path().join("src/lib.rs"), "fn a() {}\n").unwrap();
+        std::os::unix::fs::symlink("src/lib.rs", source.path().join("link")).unwrap();
+
+        let vm_dir = tempfile::tempdir().unwrap();
+        let copy = workspace_dir_for(vm_dir.path());
+        let manifest = copy_tree(source.path(), &copy).unwrap();
+
+        assert!(copy.join(".git/HEAD").is_file());
+        assert_eq!(
+            manifest.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["link", "src/lib.rs"]
+        );
+        assert!(manifest["link"].copy.symlink);
+        assert!(overlay_changes(&manifest, &scan_tree(&copy).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn apply_overlay_writes_changes_back() {
+        let source = tempfile::tempdir().unwrap();
+        std::fs::write(source.path().join("keep.txt"), "same\n").unwrap();
+        std::fs::write(source.path().join("edit.txt"), "old\n").unwrap();
+        std::fs::write(source.path().join("drop.txt"), "bye\n").unwrap();
+        let vm_dir = tempfile::tempdir().unwrap();
+        let copy = workspace_dir_for(vm_dir.path());
+        write_manifest(
+            &vm_dir.path().join(MANIFEST_FILE),
+            &copy_tree(source.path(), &copy).unwrap(),
+        )
+        .unwrap();
+
+        std::fs::write(copy.join("edit.txt"), "new and longer\n").unwrap();
+        std::fs::remove_file(copy.join("drop.txt")).unwrap();
+        std::fs::create_dir(copy.join("sub")).unwrap();
+        std::fs::write(copy.join("sub/added.txt"), "hi\n").unwrap();
+
+        let paths = apply_overlay(source.path(), vm_dir.path()).unwrap();
+        assert_eq!(paths, vec!["drop.txt", "edit.txt", "sub/added.txt"]);
+        assert_eq!(
+            std::fs::read_to_string(source.path().join("edit.txt")).unwrap(),
+            "new and longer\n"
+        );
+        assert!(!source.path().join("drop.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(source.path().join("sub/added.txt")).unwrap(),
+            "hi\n"
+        );
+        // A second apply has nothing left to do.
+        assert!(apply_overlay(source.path(), vm_dir.path()).unwrap().is_empty());
+    }
+
+    async fn git_init(dir: &Path) {
+        for args in [
+            &["init", "--quiet", "--initial-branch=main"][..],
+            &["config", "user.name", "test"][..],
+            &["config", "user.email", "test@example.com"][..],
+        ] {
+            git(dir, args).await.unwrap();
+        }
+        std::fs::write(dir.join("README.md"), "hello\n").unwrap();
+        git(dir, ["add", "README.md"]).await.unwrap();
+        git(dir, ["commit", "--quiet", "-m", "init"]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn worktree_round_trip_diffs_and_merges_agent_changes() {
+        let repo = tempfile::tempdir().unwrap();
+        let source = repo.path().canonicalize().unwrap();
+        git_init(&source).await;
+        validate_source(WorkspaceMode::Worktree, Some(&source)).await.unwrap();
+
+        let vm_dir = tempfile::tempdir().unwrap();
+        let prepared = prepare(WorkspaceMode::Worktree, &source, vm_dir.path(), "0e1c-x", "demo")
+            .await
+            .unwrap();
+        assert_eq!(prepared.branch.as_deref(), Some("mows/demo"));
+        assert_eq!(prepared.gitdir, Some(source.join(".git")));
+
+        std::fs::write(prepared.share.join("README.md"), "hello agent\n").unwrap();
+        std::fs::write(prepared.share.join("notes.txt"), "new\n").unwrap();
+        let patch = diff(WorkspaceMode::Worktree, &source, vm_dir.path()).await.unwrap();
+        assert!(patch.contains("+hello agent"), "{patch}");
+        assert!(patch.contains("b/notes.txt"), "{patch}");
+
+        let result = apply(
+            WorkspaceMode::Worktree,
+            &source,
+            vm_dir.path(),
+            "demo",
+            prepared.branch.as_deref(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.paths, vec!["README.md", "notes.txt"]);
+        assert_eq!(
+            std::fs::read_to_string(source.join("README.md")).unwrap(),
+            "hello agent\n"
+        );
+
+        remove_worktree(&source, vm_dir.path()).await.unwrap();
+        assert!(!prepared.share.exists());
+        assert!(branch_exists(&source, "mows/demo").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn worktree_mode_rejects_subdirectory_of_checkout() {
+        let repo = tempfile::tempdir().unwrap();
+        let source = repo.path().canonicalize().unwrap();
+        git_init(&source).await;
+        std::fs::create_dir(source.join("sub")).unwrap();
+        let err = validate_source(WorkspaceMode::Worktree, Some(&source.join("sub")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SupervisorError::BadRequest(_)), "{err:?}");
+    }
+
+    #[test]
+    fn apply_overlay_refuses_when_original_changed_too() {
+        let source = tempfile::tempdir().unwrap();
+        std::fs::write(source.path().join("both.txt"), "base\n").unwrap();
+        std::fs::write(source.path().join("agent.txt"), "base\n").unwrap();
+        let vm_dir = tempfile::tempdir().unwrap();
+        let copy = workspace_dir_for(vm_dir.path());
+        write_manifest(
+            &vm_dir.path().join(MANIFEST_FILE),
+            &copy_tree(source.path(), &copy).unwrap(),
+        )
+        .unwrap();
+
+        std::fs::write(copy.join("both.txt"), "agent edit\n").unwrap();
+        std::fs::write(copy.join("agent.txt"), "agent edit\n").unwrap();
+        std::fs::write(source.path().join("both.txt"), "user edit\n").unwrap();
+
+        let err = apply_overlay(source.path(), vm_dir.path()).unwrap_err();
+        match err {
+            SupervisorError::Conflict(msg) => assert!(msg.ends_with("both.txt"), "{msg}"),
+            other => panic!("expected Conflict, got {other:?}"),
+        }
+        // Nothing was written, not even the non-conflicting file.
+        assert_eq!(
+            std::fs::read_to_string(source.path().join("agent.txt")).unwrap(),
+            "base\n"
+        );
+    }
+}
@@ -251,12 +251,12 @@ port_range:
                         .state_dir
                         .join("agents")
                         .join(agent_id)
-                        .join("agent.log");
+                        .join("session.cast");
                     let log = std::fs::read_to_string(&log_path).unwrap_or_else(|e| {
                         format!("(could not read {}: {e})", log_path.display())
                     });
                     panic!(
-                        "agent {agent_id} failed: {a}\n--- agent.log ---\n{log}\n--- end ---"
+                        "agent {agent_id} failed: {a}\n--- session.cast ---\n{log}\n--- end ---"
                     );
                 }
                 _ => {}
@@ -553,7 +553,7 @@ fn console_websocket_replays_real_boot_output() {
 /// supervisor sees a real stdout byte from claude — i.e. the binary
 /// launched, found its credentials, and printed *something*).
 ///
-/// Then read the persisted `agent.log` and assert it contains a
+/// Then read the session recording (`session.cast`) and assert it contains a
 /// recognizable claude marker. The exact banner text shifts between
 /// claude-code releases, so we accept any of several known substrings;
 /// failure of all of them likely means claude bailed out before printing.
@@ -582,28 +582,28 @@ fn claude_agent_starts_and_prints_banner() {
     // prints its banner immediately, so 60 s is generous.
     let _running = h.wait_agent_running(&agent_id, Duration::from_secs(60));
 
-    // Drain the persisted agent.log (lives on the supervisor side).
+    // Read the session recording (lives on the supervisor side).
     let log_path = h
         .state_dir
         .join("agents")
         .join(&agent_id)
-        .join("agent.log");
-    // The supervisor writes the log; it may take a beat to flush before we
-    // read it directly off disk. A short wait avoids racing.
+        .join("session.cast");
+    // The recording is fed by a tail over ssh; give it a beat to catch up
+    // before we read it directly off disk.
     std::thread::sleep(Duration::from_millis(500));
     let log = std::fs::read_to_string(&log_path)
-        .unwrap_or_else(|e| panic!("failed to read agent log {}: {e}", log_path.display()));
+        .unwrap_or_else(|e| panic!("failed to read agent recording {}: {e}", log_path.display()));
 
     // Accept any of these markers — all are claude-code-specific; the exact
     // banner text is version-dependent. (Plain `claude` would also match a
     // bash error like "claude: not found" in stderr, but we send stderr to
-    // its own file via the runtime, not agent.log.)
+    // its own file via the runtime, not the recording.)
     let markers = ["Welcome", "Claude Code", "claude.ai", "Anthropic", "anthropic"];
     let hit = markers.iter().any(|m| log.contains(m));
     assert!(
         hit,
-        "agent.log does not look like claude output. \
-         Tried markers {markers:?}; log first 400B: {:?}",
+        "session.cast does not look like claude output. \
+         Tried markers {markers:?}; recording first 400B: {:?}",
         &log[..log.len().min(400)],
     );
 
@@ -694,7 +694,7 @@ fn claude_recovers_when_active_config_is_empty() {
     // If the auto-restore branch worked, claude accepts the (restored)
     // config and prints something — the runtime sees a stdout byte and
     // flips the agent to running. If it didn't, claude exits with
-    // `Unexpected EOF` and we hit `failed` (the panic dumps agent.log).
+    // `Unexpected EOF` and we hit `failed` (the panic dumps session.cast).
     h.wait_agent_running(&agent_id, Duration::from_secs(60));
 
     h.stop_vm(&vm_id);
@@ -41,6 +41,11 @@ impl Harness {
         let image_dir = state_dir.join("images");
         let socket = tempdir.path().join("agent.sock");
         std::fs::create_dir_all(&image_dir).unwrap();
+        // The only directory extra host mounts may come from.
+        let shared_dir = tempdir.path().join("shared");
+        std::fs::create_dir_all(shared_dir.join("data")).unwrap();
+        let master_key = tempdir.path().join("master.key");
+        std::fs::write(&master_key, [0x5a; 32]).unwrap();
 
         // Stub qcow2 so the spawn path is reachable. `locate_image` expects
         // `<image>-<flavor>-mows-agent-<arch>.qcow2`; we create stubs for
@@ -79,6 +84,20 @@ qemu_binary: qemu-system-x86_64
 vm_defaults:
     cpus: 2
     memory_mb: 2048
+images:
+    debian-cloud:
+        source: cloud
+        path: debian-12-genericcloud-amd64.qcow2
+presets:
+    small:
+        description: one core
+        cpus: 1
+        memory_mb: 512
+        workspace_mode: overlay
+host_mounts:
+    allowed_prefixes: [{shared}]
+secrets:
+    master_key_file: {master_key}
 port_range:
     start: {port_lo}
     end: {port_hi}
@@ -86,6 +105,8 @@ port_range:
                 state = state_dir.display(),
                 images = image_dir.display(),
                 sock = socket.display(),
+                shared = shared_dir.display(),
+                master_key = master_key.display(),
                 port = port,
                 port_lo = port + 1000,
                 port_hi = port + 1500,
@@ -400,6 +421,28 @@ fn create_agent_on_unknown_vm_returns_404() {
     assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
 }
 
+#[test]
+fn recording_of_unknown_agent_returns_404() {
+    let h = Harness::start(next_port());
+    let resp = h
+        .client()
+        .get(h.url("/v1/agents/00000000-0000-0000-0000-000000000000/recording"))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn vm_defaults_include_idle_limits() {
+    let h = Harness::start(next_port());
+    let resp = h.client().get(h.url("/v1/vms/defaults")).send().unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().unwrap();
+    assert_eq!(body["idle_timeout_secs"], 3600);
+    assert_eq!(body["max_lifetime_secs"], 0);
+    assert_eq!(body["idle_action"], "suspend");
+}
+
 /// QA-21: a TCP request without a bearer token must 401, not 500, and
 /// must not leak internal-error noise into the body.
 #[test]
@@ -786,6 +829,346 @@ fn create_vm_records_owner_user_id() {
     );
 }
 
+/// Create a `member` and return a client that carries their session token.
+fn member_client(h: &Harness, username: &str, extra: serde_json::Value) -> MemberClient {
+    let mut body = json!({"username": username, "password": "correcthorsebatterystaple", "role": "member"});
+    if let (Some(body), Some(extra)) = (body.as_object_mut(), extra.as_object()) {
+        body.extend(extra.clone());
+    }
+    let created = h.client().post(h.url("/v1/users")).json(&body).send().unwrap();
+    assert!(created.status().is_success(), "create_user failed: {}", created.text().unwrap());
+    let login: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/auth/login"))
+        .json(&json!({"username": username, "password": "correcthorsebatterystaple"}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    MemberClient {
+        client: reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap(),
+        token: login["token"].as_str().unwrap().to_string(),
+    }
+}
+
+struct MemberClient {
+    client: reqwest::blocking::Client,
+    token: String,
+}
+
+impl MemberClient {
+    fn request(&self, method: reqwest::Method, url: String) -> reqwest::blocking::RequestBuilder {
+        self.client
+            .request(method, url)
+            .header("Authorization", format!("Bearer {}", self.token))
+    }
+}
+
+/// Sharing widens a member's view to the shared VM, but deleting it and
+/// managing its shares stay with the owner; unsharing hides it again.
+#[test]
+fn member_sees_vms_shared_with_them() {
+    let h = Harness::start(next_port());
+    let vm: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let vm_id = vm["id"].as_str().unwrap();
+    let dave = member_client(&h, "dave", json!({}));
+
+    let shared = h
+        .client()
+        .put(h.url(&format!("/v1/vms/{vm_id}/shares/dave")))
+        .send()
+        .unwrap();
+    assert!(shared.status().is_success(), "share failed: {}", shared.text().unwrap());
+
+    let list: Vec<serde_json::Value> = dave
+        .request(reqwest::Method::GET, h.url("/v1/vms"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(list.iter().any(|v| v["id"] == vm_id), "shared vm missing from {list:?}");
+    let shares = dave
+        .request(reqwest::Method::GET, h.url(&format!("/v1/vms/{vm_id}/shares")))
+        .send()
+        .unwrap();
+    assert_eq!(shares.status(), reqwest::StatusCode::FORBIDDEN);
+    let delete = dave
+        .request(reqwest::Method::DELETE, h.url(&format!("/v1/vms/{vm_id}")))
+        .send()
+        .unwrap();
+    assert_eq!(delete.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let unshared = h
+        .client()
+        .delete(h.url(&format!("/v1/vms/{vm_id}/shares/dave")))
+        .send()
+        .unwrap();
+    assert!(unshared.status().is_success());
+    let get = dave
+        .request(reqwest::Method::GET, h.url(&format!("/v1/vms/{vm_id}")))
+        .send()
+        .unwrap();
+    assert_eq!(get.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+/// A member at their `max_vms` quota can't create another VM, and user
+/// administration stays admin-only.
+#[test]
+fn member_quota_blocks_vm_create() {
+    let h = Harness::start(next_port());
+    let erin = member_client(&h, "erin", json!({"max_vms": 1}));
+
+    let first = erin
+        .request(reqwest::Method::POST, h.url("/v1/vms"))
+        .json(&json!({}))
+        .send()
+        .unwrap();
+    assert!(first.status().is_success(), "first vm failed: {}", first.text().unwrap());
+    let second = erin
+        .request(reqwest::Method::POST, h.url("/v1/vms"))
+        .json(&json!({}))
+        .send()
+        .unwrap();
+    assert_eq!(second.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: serde_json::Value = second.json().unwrap();
+    assert!(body["error"].as_str().unwrap_or("").contains("quota exceeded"), "{body:?}");
+
+    let report: serde_json::Value = erin
+        .request(reqwest::Method::GET, h.url("/v1/users/me/quota"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(report["quota"]["max_vms"], 1);
+    assert_eq!(report["usage"]["vms"], 1);
+
+    let users = erin
+        .request(reqwest::Method::GET, h.url("/v1/users"))
+        .send()
+        .unwrap();
+    assert_eq!(users.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let raised = h
+        .client()
+        .patch(h.url("/v1/users/erin"))
+        .json(&json!({"max_vms": 0}))
+        .send()
+        .unwrap();
+    assert!(raised.status().is_success());
+    let user: serde_json::Value = raised.json().unwrap();
+    assert!(user["max_vms"].is_null(), "0 must clear the cap, got {user:?}");
+}
+
+/// A preset fills in what the request leaves out, explicit fields win,
+/// and `/v1/vms/defaults` lists the presets for clients.
+#[test]
+fn create_with_preset_applies_it_and_explicit_fields_override() {
+    let h = Harness::start(next_port());
+    let defaults: serde_json::Value = h
+        .client()
+        .get(h.url("/v1/vms/defaults"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(defaults["presets"]["small"]["cpus"], 1);
+
+    let vm: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"preset": "small", "memory_mb": 1024}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(vm["preset"], "small");
+    assert_eq!(vm["cpus"], 1);
+    assert_eq!(vm["memory_mb"], 1024);
+
+    let unknown = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"preset": "huge"}))
+        .send()
+        .unwrap();
+    assert_eq!(unknown.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+/// `image` names an image-builder distro or a configured image; a cloud
+/// image whose qcow2 hasn't been downloaded is a 503 like a missing build.
+#[test]
+fn create_vm_resolves_named_images() {
+    let h = Harness::start(next_port());
+    let defaults: serde_json::Value = h
+        .client()
+        .get(h.url("/v1/vms/defaults"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(defaults["image"], "alpine");
+    assert_eq!(defaults["images"]["debian-cloud"]["source"], "cloud");
+    assert_eq!(defaults["images"]["nixos"]["source"], "built");
+
+    let unknown = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"image": "fedora"}))
+        .send()
+        .unwrap();
+    assert_eq!(unknown.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let missing = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"image": "debian-cloud"}))
+        .send()
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+}
+
+/// Extra mounts must sit under `host_mounts.allowed_prefixes`; accepted
+/// ones come back on the VM with canonical host paths.
+#[test]
+fn extra_mounts_are_checked_against_the_allowlist() {
+    let h = Harness::start(next_port());
+    let data = h.config_path.parent().unwrap().join("shared").join("data");
+    let outside = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"mounts": [{
+            "host_path": h.state_dir.display().to_string(),
+            "guest_path": "/data",
+        }]}))
+        .send()
+        .unwrap();
+    assert_eq!(outside.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let reserved = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"mounts": [{
+            "host_path": data.display().to_string(),
+            "guest_path": "/etc/data",
+        }]}))
+        .send()
+        .unwrap();
+    assert_eq!(reserved.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let vm: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"mounts": [{
+            "host_path": data.display().to_string(),
+            "guest_path": "/data",
+            "mode": "rw",
+        }]}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let canonical = std::fs::canonicalize(&data).unwrap();
+    assert_eq!(vm["mounts"][0]["host_path"], canonical.display().to_string());
+    assert_eq!(vm["mounts"][0]["guest_path"], "/data");
+    assert_eq!(vm["mounts"][0]["mode"], "rw");
+}
+
+/// Secrets are admin-only, validated by name and never echoed back; the
+/// database only holds ciphertext.
+#[test]
+fn secrets_are_stored_encrypted_and_never_returned() {
+    let h = Harness::start(next_port());
+    let value = "sk-e2e-0123456789abcdef";
+    let stored: serde_json::Value = h
+        .client()
+        .put(h.url("/v1/secrets/BRAVE_API_KEY"))
+        .json(&json!({"value": value}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(stored["name"], "BRAVE_API_KEY");
+    assert!(stored.get("value").is_none());
+
+    let invalid = h
+        .client()
+        .put(h.url("/v1/secrets/not-a-name"))
+        .json(&json!({"value": value}))
+        .send()
+        .unwrap();
+    assert_eq!(invalid.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let list = h.client().get(h.url("/v1/secrets")).send().unwrap().text().unwrap();
+    assert!(list.contains("BRAVE_API_KEY"), "{list}");
+    assert!(!list.contains(value), "{list}");
+    for file in ["state.db", "state.db-wal"] {
+        let bytes = std::fs::read(h.state_dir.join(file)).unwrap_or_default();
+        assert!(!bytes.windows(value.len()).any(|w| w == value.as_bytes()), "{file}");
+    }
+
+    let erin = member_client(&h, "erin", json!({}));
+    let forbidden = erin
+        .request(reqwest::Method::GET, h.url("/v1/secrets"))
+        .send()
+        .unwrap();
+    assert_eq!(forbidden.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let deleted = h.client().delete(h.url("/v1/secrets/BRAVE_API_KEY")).send().unwrap();
+    assert!(deleted.status().is_success());
+    let again = h.client().delete(h.url("/v1/secrets/BRAVE_API_KEY")).send().unwrap();
+    assert_eq!(again.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+/// `/metrics` is an admin-only OpenMetrics scrape; `/v1/vms/stats` is open
+/// to members and only lists what they can see.
+#[test]
+fn metrics_scrape_and_vm_stats() {
+    let h = Harness::start(next_port());
+    let _vm: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+
+    let scrape = h.client().get(h.url("/metrics")).send().unwrap();
+    assert_eq!(scrape.status(), reqwest::StatusCode::OK);
+    let content_type = scrape.headers()["content-type"].to_str().unwrap().to_string();
+    assert!(content_type.starts_with("application/openmetrics-text"), "{content_type}");
+    let body = scrape.text().unwrap();
+    assert!(body.contains("mows_vms{"), "{body}");
+    assert!(body.trim_end().ends_with("# EOF"), "{body}");
+
+    let frank = member_client(&h, "frank", json!({}));
+    let forbidden = frank
+        .request(reqwest::Method::GET, h.url("/metrics"))
+        .send()
+        .unwrap();
+    assert_eq!(forbidden.status(), reqwest::StatusCode::FORBIDDEN);
+    let stats: Vec<serde_json::Value> = frank
+        .request(reqwest::Method::GET, h.url("/v1/vms/stats"))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(stats.is_empty(), "member sees someone else's vm: {stats:?}");
+
+    let unknown = h.client().get(h.url("/v1/vms/nope/stats")).send().unwrap();
+    assert_eq!(unknown.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
 /// Open the display websocket and verify QEMU's VNC server greets us with
 /// the standard RFB protocol-version banner. This proves the full chain:
 /// `-vnc unix:...` argv was emitted → QEMU bound the unix socket → axum
@@ -997,6 +1380,270 @@ fn create_with_explicit_resources_round_trips() {
     assert_eq!(row["display_mode"], "headless");
 }
 
+#[test]
+fn create_with_allowlist_network_policy_round_trips() {
+    let h = Harness::start(next_port());
+    let created: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({
+            "name": "fenced-vm",
+            "network_policy": {
+                "mode": "allowlist",
+                "allow_domains": ["*.crates.io"],
+                "allow_cidrs": ["10.20.0.0/16"],
+            },
+        }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+    let row: serde_json::Value = h
+        .client()
+        .get(h.url(&format!("/v1/vms/{id}")))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(row["network_policy"]["mode"], "allowlist");
+    assert_eq!(row["network_policy"]["allow_domains"], json!(["*.crates.io"]));
+    assert_eq!(row["network_policy"]["allow_cidrs"], json!(["10.20.0.0/16"]));
+    assert!(h.state_dir.join("vms").join(id).join("egress.sock").exists());
+
+    // Nothing has tried to leave the VM yet.
+    let blocked: serde_json::Value = h
+        .client()
+        .get(h.url(&format!("/v1/vms/{id}/egress-log")))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(blocked, json!([]));
+
+    // VMs created without a policy fall back to the config default (`open`).
+    let open: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"name": "open-vm"}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(open["network_policy"], json!({"mode": "open"}));
+}
+
+#[test]
+fn create_with_malformed_network_policy_returns_400() {
+    let h = Harness::start(next_port());
+    for policy in [
+        json!({"mode": "allowlist", "allow_cidrs": ["10.0.0.0/40"]}),
+        json!({"mode": "deny-all", "allow_domains": ["example.com"]}),
+    ] {
+        let resp = h
+            .client()
+            .post(h.url("/v1/vms"))
+            .json(&json!({"network_policy": policy}))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{policy}");
+    }
+    let vms: Vec<serde_json::Value> =
+        h.client().get(h.url("/v1/vms")).send().unwrap().json().unwrap();
+    assert!(vms.is_empty(), "rejected policies must not leave a VM row behind");
+}
+
+#[test]
+fn overlay_workspace_diffs_and_applies_agent_changes() {
+    let h = Harness::start(next_port());
+    let cwd = tempfile::tempdir().unwrap();
+    std::fs::write(cwd.path().join("main.rs"), "fn main() {}\n").unwrap();
+    let created: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({
+            "name": "overlay-vm",
+            "cwd": cwd.path().to_str().unwrap(),
+            "workspace_mode": "overlay",
+        }))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(created["workspace_mode"], "overlay");
+    let id = created["id"].as_str().unwrap();
+
+    // Stand in for the agent: edit the VM's private copy.
+    let copy = h.state_dir.join("vms").join(id).join("workspace");
+    std::fs::write(copy.join("main.rs"), "fn main() { println!(\"hi\"); }\n").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(cwd.path().join("main.rs")).unwrap(),
+        "fn main() {}\n",
+        "overlay mode must not touch cwd before apply"
+    );
+
+    let diff = h
+        .client()
+        .get(h.url(&format!("/v1/vms/{id}/diff")))
+        .send()
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(diff.contains("+++ b/main.rs"), "{diff}");
+    assert!(diff.contains("+fn main() { println!(\"hi\"); }"), "{diff}");
+
+    let applied: serde_json::Value = h
+        .client()
+        .post(h.url(&format!("/v1/vms/{id}/apply")))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(applied["paths"], json!(["main.rs"]));
+    assert_eq!(
+        std::fs::read_to_string(cwd.path().join("main.rs")).unwrap(),
+        "fn main() { println!(\"hi\"); }\n"
+    );
+}
+
+#[test]
+fn workspace_mode_errors_return_400() {
+    let h = Harness::start(next_port());
+    let resp = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"workspace_mode": "overlay"}))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let not_a_repo = tempfile::tempdir().unwrap();
+    let resp = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({
+            "cwd": not_a_repo.path().to_str().unwrap(),
+            "workspace_mode": "worktree",
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // `rw` VMs have nothing to diff: their changes are already in cwd.
+    let rw: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"cwd": not_a_repo.path().to_str().unwrap()}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(rw["workspace_mode"], "rw");
+    let resp = h
+        .client()
+        .get(h.url(&format!("/v1/vms/{}/diff", rw["id"].as_str().unwrap())))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn create_task_with_invalid_request_returns_400() {
+    let h = Harness::start(next_port());
+    for body in [
+        json!({"kind": "claude", "prompt": "   "}),
+        json!({"kind": "claude", "prompt": "hi", "timeout_secs": 0}),
+        json!({"kind": "claude", "prompt": "hi", "cwd": "relative/dir"}),
+        json!({"kind": "claude", "prompt": "hi", "cwd": "/tmp", "vm_id": "x"}),
+    ] {
+        let resp = h.client().post(h.url("/v1/tasks")).json(&body).send().unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{body}");
+    }
+    let resp = h
+        .client()
+        .post(h.url("/v1/tasks"))
+        .json(&json!({"kind": "claude", "prompt": "hi", "vm_id": "missing"}))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    let tasks: Vec<serde_json::Value> =
+        h.client().get(h.url("/v1/tasks")).send().unwrap().json().unwrap();
+    assert!(tasks.is_empty(), "rejected requests must not queue a task");
+}
+
+/// A cancelled task ends `cancelled` whether the dispatcher had claimed
+/// it yet or not, takes its throwaway VM with it, and can then be deleted.
+#[test]
+fn cancelled_task_cleans_up_and_deletes() {
+    let h = Harness::start(next_port());
+    let task: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/tasks"))
+        .json(&json!({"kind": "shell", "prompt": "echo hi > /out/hi.txt", "timeout_secs": 60}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(task["status"], "queued");
+    let id = task["id"].as_str().unwrap().to_string();
+
+    let resp = h
+        .client()
+        .post(h.url(&format!("/v1/tasks/{id}/cancel")))
+        .send()
+        .unwrap();
+    assert!(
+        resp.status().is_success() || resp.status() == reqwest::StatusCode::CONFLICT,
+        "cancel returned {}",
+        resp.status()
+    );
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(30);
+    let finished = loop {
+        let task: serde_json::Value = h
+            .client()
+            .get(h.url(&format!("/v1/tasks/{id}")))
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        if task["status"] != "queued" && task["status"] != "running" {
+            break task;
+        }
+        assert!(std::time::Instant::now() < deadline, "task never finished: {task}");
+        std::thread::sleep(Duration::from_millis(200));
+    };
+    // With the stub image the boot can also fail before the cancel lands.
+    assert!(
+        finished["status"] == "cancelled" || finished["status"] == "failed",
+        "{finished}"
+    );
+    let vms: Vec<serde_json::Value> =
+        h.client().get(h.url("/v1/vms")).send().unwrap().json().unwrap();
+    assert!(vms.is_empty(), "task vm must be torn down: {vms:?}");
+
+    let outputs: Vec<serde_json::Value> = h
+        .client()
+        .get(h.url(&format!("/v1/tasks/{id}/outputs")))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(outputs.is_empty());
+
+    let resp = h
+        .client()
+        .delete(h.url(&format!("/v1/tasks/{id}")))
+        .send()
+        .unwrap();
+    assert!(resp.status().is_success());
+    let resp = h
+        .client()
+        .get(h.url(&format!("/v1/tasks/{id}")))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
 /// `/v1/events` is the push-based replacement for the web UI's 2 s polling
 /// loop. Subscribing first, then provoking VM + agent mutations, must yield
 /// matching JSON events in the order they happened.
@@ -1110,3 +1757,85 @@ fn events_websocket_streams_vm_lifecycle() {
         "expected at least one vm_updated between create/delete; got {kinds:?}"
     );
 }
+
+#[test]
+fn disk_snapshot_lifecycle_on_stopped_vm() {
+    let h = Harness::start(next_port());
+    let created: serde_json::Value = h
+        .client()
+        .post(h.url("/v1/vms"))
+        .json(&json!({"detach": true}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+
+    // Still `starting` (stub image never boots) — snapshots are refused
+    // until the VM is either running or fully stopped.
+    let starting = h
+        .client()
+        .post(h.url(&format!("/v1/vms/{id}/snapshots")))
+        .json(&json!({}))
+        .send()
+        .unwrap();
+    assert_eq!(starting.status(), 409);
+
+    let stop = h
+        .client()
+        .post(h.url(&format!("/v1/vms/{id}/stop")))
+        .send()
+        .unwrap();
+    assert!(stop.status().is_success());
+
+    let snapshot: serde_json::Value = h
+        .client()
+        .post(h.url(&format!("/v1/vms/{id}/snapshots")))
+        .json(&json!({"name": "before-risky-change"}))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(snapshot["vm_id"], id);
+    let snapshot_id = snapshot["id"].as_str().unwrap();
+
+    let duplicate = h
+        .client()
+        .post(h.url(&format!("/v1/vms/{id}/snapshots")))
+        .json(&json!({"name": "before-risky-change"}))
+        .send()
+        .unwrap();
+    assert_eq!(duplicate.status(), 409);
+
+    let list: Vec<serde_json::Value> = h
+        .client()
+        .get(h.url(&format!("/v1/vms/{id}/snapshots")))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0]["name"], "before-risky-change");
+
+    let restore = h
+        .client()
+        .post(h.url(&format!("/v1/vms/{id}/snapshots/{snapshot_id}/restore")))
+        .send()
+        .unwrap();
+    assert!(restore.status().is_success(), "restore failed: {restore:?}");
+
+    let delete = h
+        .client()
+        .delete(h.url(&format!("/v1/vms/{id}/snapshots/{snapshot_id}")))
+        .send()
+        .unwrap();
+    assert!(delete.status().is_success());
+    let list: Vec<serde_json::Value> = h
+        .client()
+        .get(h.url(&format!("/v1/vms/{id}/snapshots")))
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(list.is_empty());
+}
@@ -16,6 +16,8 @@ import { SidebarProvider } from "@my-own-web-services/react-components/component
 import { BrowserRouter, Route, Routes } from "react-router-dom";
 import ModalHost from "./components/ModalHost";
 import Sidebar from "./components/Sidebar";
+import AgentRecording from "./pages/AgentRecording";
+import AuditLog from "./pages/AuditLog";
 import VmDetail from "./pages/VmDetail";
 
 const Home = () => (
@@ -50,6 +52,8 @@ const App = () => (
                         <Routes>
                             <Route path="/" element={<Home />} />
                             <Route path="/vms/:id" element={<VmDetail />} />
+                            <Route path="/agents/:id/recording" element={<AgentRecording />} />
+                            <Route path="/audit" element={<AuditLog />} />
                         </Routes>
                     </main>
                 </ResizablePanel>
@@ -10,9 +10,11 @@
  * ---------------------------------------------------------------
  */
 
+
 /**
  * VM lifecycle status as exposed over the API. Mirrors the SQL CHECK
- * constraint in `migrations/0001_init.sql`. Serialised as the
+ * constraint in `migrations/0001_init.sql`, plus `suspended`: a
+ * `running` row with `suspended_at` set (see `VM_COLUMNS`). Serialised as the
  * lowercase variant name so the wire format stays
  * `"starting"|"running"|…` and the TypeScript codegen emits a union
  * literal instead of a bare `string` (FUTURE-14).
@@ -23,18 +25,7 @@ export enum VmStatus {
   Stopping = "stopping",
   Stopped = "stopped",
   Failed = "failed",
-}
-
-/**
- * Guest base image. Only `alpine` is currently shipped end-to-end — the
- * other variants are accepted by the API surface but `create_vm` will
- * reject them with a 503 until the image-builder lands the qcow2.
- */
-export enum VmImage {
-  Alpine = "alpine",
-  Ubuntu = "ubuntu",
-  Debian = "debian",
-  Nixos = "nixos",
+  Suspended = "suspended",
 }
 
 /**
@@ -59,6 +50,103 @@ export enum AgentKindName {
   Claude = "claude",
 }
 
+export enum AuditOutcome {
+  Success = "success",
+  Failure = "failure",
+}
+
+/** What happens to a VM once it has been idle for its `idle_timeout_secs`. */
+export enum IdleAction {
+  Suspend = "suspend",
+  Stop = "stop",
+}
+
+export enum MountMode {
+  Ro = "ro",
+  Rw = "rw",
+}
+
+export enum NetworkPolicyMode {
+  Open = "open",
+  DenyAll = "deny-all",
+  Allowlist = "allowlist",
+}
+
+/**
+ * Task lifecycle status. Mirrors the CHECK constraint in
+ * `migrations/0006_tasks.sql`.
+ */
+export enum TaskStatus {
+  Queued = "queued",
+  Running = "running",
+  Succeeded = "succeeded",
+  Failed = "failed",
+  TimedOut = "timed_out",
+  Cancelled = "cancelled",
+}
+
+/**
+ * What a token may be used for. `*:write` implies `*:read`; `admin`
+ * implies everything, including the admin-only endpoints (users, secrets,
+ * tokens, metrics) — which still check the user's role.
+ */
+export enum TokenScope {
+  VmsRead = "vms:read",
+  VmsWrite = "vms:write",
+  AgentsRead = "agents:read",
+  AgentsWrite = "agents:write",
+  Admin = "admin",
+}
+
+/**
+ * Supervisor user role. `admin` sees and manages everything and
+ * administers users; `member` only sees VMs it owns or that were
+ * shared with it, and is subject to its per-user quota.
+ *
+ * The `users.role` CHECK from `0001_init.sql` can't be widened in
+ * place, so `member` is stored as the legacy `user` value; the API
+ * still accepts `user` as an alias.
+ */
+export enum UserRole {
+  Admin = "admin",
+  Member = "member",
+}
+
+/** What `GET /v1/agents/{id}/wait` waits for. */
+export enum WaitCondition {
+  Attention = "attention",
+  Exit = "exit",
+}
+
+/** Why `wait` returned. */
+export enum WaitOutcome {
+  Exited = "exited",
+  WaitingForInput = "waiting_for_input",
+  TimedOut = "timed_out",
+}
+
+export enum WorkspaceMode {
+  Rw = "rw",
+  Overlay = "overlay",
+  Worktree = "worktree",
+}
+
+export interface AddForwardRequest {
+  /**
+   * TCP port inside the guest.
+   * @format int32
+   * @min 0
+   */
+  guest_port: number;
+  /**
+   * Host port to listen on; must lie in `forward_port_range`. The next
+   * free port of that range when omitted.
+   * @format int32
+   * @min 0
+   */
+  host_port?: number | null;
+}
+
 export interface AgentSummary {
   /** @format int64 */
   exit_code?: number | null;
@@ -72,9 +160,82 @@ export interface AgentSummary {
    * see them, non-admin users cannot.
    */
   owner_user_id?: string | null;
+  /**
+   * Size of the finished session recording. `None` while the agent is
+   * running (the recording is still growing), when recording was
+   * disabled, or once retention has removed it.
+   * @format int64
+   */
+  recording_bytes?: number | null;
   started_at: string;
   status: string;
   vm_id: string;
+  /**
+   * Since when the agent has been sitting at one of its kind's input
+   * prompts; `None` while it works or once it exited.
+   */
+  waiting_since?: string | null;
+}
+
+export interface AgentWaitResponse {
+  agent: AgentSummary;
+  /** Why `wait` returned. */
+  outcome: WaitOutcome;
+}
+
+export interface AuditEntry {
+  /** `<resource>.<verb>`, e.g. `vm.create`, `agent.attach`, `auth.login`. */
+  action: string;
+  /**
+   * Username at the time, the attempted username of a login, or
+   * `admin` for the static admin identity.
+   */
+  actor: string;
+  /**
+   * `users.id` of the actor; `None` for the static admin identity and
+   * for failed logins.
+   */
+  actor_user_id?: string | null;
+  at: string;
+  /** @format int64 */
+  id: number;
+  outcome: AuditOutcome;
+  /** Peer address; `None` for requests over the unix socket. */
+  source_ip?: string | null;
+  /**
+   * HTTP status the request was answered with.
+   * @format int64
+   */
+  status_code?: number | null;
+  /** Id (or username, secret name) of the resource acted on. */
+  target?: string | null;
+}
+
+export interface AuditPage {
+  entries: AuditEntry[];
+  /**
+   * Pass as `cursor` for the next (older) page; `None` on the last one.
+   * @format int64
+   */
+  next_cursor?: number | null;
+}
+
+/** One refused connection attempt, as stored in `egress-blocked.jsonl`. */
+export interface BlockedEgressAttempt {
+  /** RFC 3339 timestamp. */
+  at: string;
+  host: string;
+  /**
+   * @format int32
+   * @min 0
+   */
+  port: number;
+  reason: string;
+}
+
+export interface CloneVmRequest {
+  /** Name of the copy. Defaults to a generated one, as for `POST /v1/vms`. */
+  name?: string | null;
 }
 
 export interface CreateAgentRequest {
@@ -88,13 +249,92 @@ export interface CreateAgentRequest {
   name?: string | null;
 }
 
+export interface CreateSnapshotRequest {
+  /**
+   * Display name, unique per VM. Auto-generated from the UTC timestamp
+   * when omitted.
+   */
+  name?: string | null;
+}
+
+export interface CreateTaskRequest {
+  /**
+   * Host directory mounted at `/workspace` in the task's VM. Only for
+   * tasks that boot their own VM.
+   */
+  cwd?: string | null;
+  /** Agent kind to run. Must have a `headless_argv`. */
+  kind: AgentKindName;
+  /** Display name. Auto-generated from `kind` + UTC timestamp when omitted. */
+  name?: string | null;
+  /** Fed to the agent on stdin and written to `$MOWS_TASK_PROMPT_FILE`. */
+  prompt: string;
+  /**
+   * Wall-clock limit for the agent process. Defaults to
+   * `task_queue.default_timeout_secs`.
+   * @format int64
+   * @min 0
+   */
+  timeout_secs?: number | null;
+  /**
+   * Run inside this existing, running VM instead of booting a fresh
+   * one. Tasks targeting the same VM run one at a time.
+   */
+  vm_id?: string | null;
+}
+
+export interface CreateTokenRequest {
+  /**
+   * Lifetime in days, 1-365. Defaults to 90.
+   * @format int32
+   * @min 0
+   */
+  expires_in_days?: number | null;
+  /** What the token is for, e.g. `ci`. */
+  name: string;
+  scopes: TokenScope[];
+  /**
+   * Issue the token for this user instead of the caller. Admin only;
+   * required when the caller has no user row (static admin token, unix
+   * socket).
+   */
+  username?: string | null;
+}
+
+export type CreateTokenResponse = TokenSummary & {
+  /** The bearer token. Shown only in this response. */
+  token: string;
+};
+
 export interface CreateUserRequest {
+  /**
+   * @format int32
+   * @min 0
+   */
+  max_memory_mb?: number | null;
+  /**
+   * @format int32
+   * @min 0
+   */
+  max_vcpus?: number | null;
+  /**
+   * Quota caps; omitted or `0` means unlimited.
+   * @format int32
+   * @min 0
+   */
+  max_vms?: number | null;
   password: string;
-  role?: string;
+  /** Defaults to `member`. `user` is accepted as an alias. */
+  role?: UserRole;
   username: string;
 }
 
 export interface CreateVmRequest {
+  /**
+   * Agent kind the VM is for: adds the kind's default mounts and is
+   * spawned when a create-agent request omits `kind`.
+   */
+  agent_kind?: null | AgentKindName;
   /**
    * @format int32
    * @min 0
@@ -108,18 +348,65 @@ export interface CreateVmRequest {
    */
   display_mode?: null | VmDisplayMode;
   /**
-   * Guest base image. Defaults to `alpine` when omitted, but the
-   * default is logged at `INFO` level so silently-defaulted requests
-   * don't hide misspelled image names (SLOP-36). Pass an explicit
-   * value (`alpine` | `ubuntu` | `debian` | `nixos`) to record intent.
+   * Environment for every agent spawned in the VM, on top of the
+   * preset's and the agent kind's.
+   */
+  env?: Record<string, string> | null;
+  /**
+   * `suspend` or `stop` on idle timeout. Defaults to
+   * `vm_defaults.idle_action`.
+   */
+  idle_action?: null | IdleAction;
+  /**
+   * Seconds the VM may sit idle before `idle_action` applies; `0`
+   * disables. Defaults to `vm_defaults.idle_timeout_secs`.
+   * @format int64
+   * @min 0
+   */
+  idle_timeout_secs?: number | null;
+  /**
+   * Guest image by name: one of the image-builder's distros (`alpine`
+   * | `ubuntu` | `debian` | `nixos`) or an entry under the supervisor's
+   * `images:` (see `GET /v1/vms/defaults`). Defaults to
+   * `vm_defaults.image` when omitted, but the default is logged at
+   * `INFO` level so silently-defaulted requests don't hide misspelled
+   * image names (SLOP-36).
    */
-  image?: null | VmImage;
+  image?: string | null;
+  /**
+   * Seconds after which the VM is shut down regardless of activity;
+   * `0` disables. Defaults to `vm_defaults.max_lifetime_secs`.
+   * @format int64
+   * @min 0
+   */
+  max_lifetime_secs?: number | null;
   /**
    * @format int32
    * @min 0
    */
   memory_mb?: number | null;
+  /**
+   * Extra host directories to share into the guest. Host paths must
+   * sit under the supervisor's `host_mounts.allowed_prefixes`.
+   */
+  mounts?: VmMount[] | null;
   name?: string | null;
+  /**
+   * Outbound network policy. Defaults to the supervisor's
+   * `default_network_policy` when omitted.
+   */
+  network_policy?: null | NetworkPolicy;
+  /**
+   * Named preset from the supervisor config (see `GET /v1/vms/defaults`)
+   * that fills in every field below the request leaves out.
+   */
+  preset?: string | null;
+  /**
+   * How `cwd` is shared with the guest. Defaults to `rw` (the host
+   * directory itself). `overlay` and `worktree` give the VM a private
+   * copy whose changes come back via `/v1/vms/{id}/apply`.
+   */
+  workspace_mode?: null | WorkspaceMode;
 }
 
 /**
@@ -131,6 +418,23 @@ export interface ErrorResponse {
   error: string;
 }
 
+export type GuestImage =
+  | {
+      description?: string | null;
+      /** One of [`BUILT_DISTROS`]. */
+      distro: string;
+      source: "built";
+    }
+  | {
+      description?: string | null;
+      /**
+       * The qcow2; relative paths are taken from `image_dir`. It is only
+       * ever used as a backing file, never written to.
+       */
+      path: string;
+      source: "cloud";
+    };
+
 export interface HealthResponse {
   service: string;
   status: string;
@@ -148,6 +452,24 @@ export interface LoginResponse {
   token: string;
 }
 
+/**
+ * Outbound network policy of a VM. Persisted as JSON in
+ * `vms.network_policy`.
+ */
+export interface NetworkPolicy {
+  /**
+   * `10.1.0.0/16`, `2001:db8::/32`, or a bare address. Only valid with
+   * `allowlist`.
+   */
+  allow_cidrs?: string[];
+  /**
+   * `example.com` matches exactly that host; `*.example.com` matches
+   * any subdomain (but not the apex). Only valid with `allowlist`.
+   */
+  allow_domains?: string[];
+  mode?: NetworkPolicyMode;
+}
+
 /** Response body for a successful lifecycle mutation on a VM or agent. */
 export interface OperationResult {
   /** `true` if the resource was deleted. */
@@ -158,20 +480,165 @@ export interface OperationResult {
   status?: string | null;
 }
 
+export interface PutSecretRequest {
+  value: string;
+}
+
+/** The caller's quota next to what their live VMs currently use. */
+export interface QuotaReport {
+  /** A user's limits. `None` means unlimited. */
+  quota: UserQuota;
+  /** What a user's live VMs currently hold. */
+  usage: QuotaUsage;
+}
+
+/** What a user's live VMs currently hold. */
+export interface QuotaUsage {
+  /** @format int64 */
+  memory_mb: number;
+  /** @format int64 */
+  vcpus: number;
+  /** @format int64 */
+  vms: number;
+}
+
+/** A stored secret, without its value — nothing on the API returns that. */
+export interface SecretSummary {
+  created_at: string;
+  name: string;
+  updated_at: string;
+  /** Id of the user who last set the value; `null` for the API token. */
+  updated_by?: string | null;
+}
+
+export interface SnapshotSummary {
+  created_at: string;
+  id: string;
+  name: string;
+  /** `users.id` of the caller who took the snapshot. */
+  owner_user_id?: string | null;
+  vm_id: string;
+}
+
+export interface TaskOutputFile {
+  /** Path relative to the guest's `/out`, `/`-separated. */
+  path: string;
+  /**
+   * @format int64
+   * @min 0
+   */
+  size_bytes: number;
+}
+
+export interface TaskSummary {
+  created_at: string;
+  cwd?: string | null;
+  /** Why a `failed` task failed before producing an exit status. */
+  error?: string | null;
+  /**
+   * Exit status of the agent process. `None` unless it exited on its own.
+   * @format int64
+   */
+  exit_code?: number | null;
+  finished_at?: string | null;
+  id: string;
+  kind: string;
+  name: string;
+  owner_user_id?: string | null;
+  /**
+   * `true` if the task booted `vm_id` itself; such VMs are deleted
+   * when the task finishes.
+   */
+  owns_vm: boolean;
+  started_at?: string | null;
+  /**
+   * Task lifecycle status. Mirrors the CHECK constraint in
+   * `migrations/0006_tasks.sql`.
+   */
+  status: TaskStatus;
+  /** @format int64 */
+  timeout_secs: number;
+  /**
+   * VM the task ran (or runs) in. Set at queue time for tasks that
+   * target an existing VM, once booted for the rest.
+   */
+  vm_id?: string | null;
+}
+
+/** A token as listed; never includes the token itself. */
+export interface TokenSummary {
+  created_at: string;
+  expires_at: string;
+  id: string;
+  last_used_at?: string | null;
+  name: string;
+  scopes: TokenScope[];
+  /** Owner of the token. */
+  username: string;
+}
+
 export interface UpdateAgentRequest {
   /** New display name. Must be non-empty. */
   name: string;
 }
 
+/** Fields left out are not changed. A quota of `0` removes the cap. */
+export interface UpdateUserRequest {
+  /**
+   * @format int32
+   * @min 0
+   */
+  max_memory_mb?: number | null;
+  /**
+   * @format int32
+   * @min 0
+   */
+  max_vcpus?: number | null;
+  /**
+   * @format int32
+   * @min 0
+   */
+  max_vms?: number | null;
+  role?: null | UserRole;
+}
+
 export interface UpdateVmRequest {
   /** New display name. Must be non-empty. */
   name: string;
 }
 
+/** A user's limits. `None` means unlimited. */
+export interface UserQuota {
+  /** @format int64 */
+  max_memory_mb?: number | null;
+  /** @format int64 */
+  max_vcpus?: number | null;
+  /** @format int64 */
+  max_vms?: number | null;
+}
+
 export interface UserSummary {
   created_at: string;
   id: string;
-  role: string;
+  /** @format int64 */
+  max_memory_mb?: number | null;
+  /** @format int64 */
+  max_vcpus?: number | null;
+  /**
+   * Quota caps; `null` means unlimited. See `crate::quota`.
+   * @format int64
+   */
+  max_vms?: number | null;
+  /**
+   * Supervisor user role. `admin` sees and manages everything and
+   * administers users; `member` only sees VMs it owns or that were
+   * shared with it, and is subject to its per-user quota.
+   *
+   * The `users.role` CHECK from `0001_init.sql` can't be widened in
+   * place, so `member` is stored as the legacy `user` value; the API
+   * still accepts `user` as an alias.
+   */
+  role: UserRole;
   username: string;
 }
 
@@ -180,12 +647,125 @@ export interface VmDefaultsResponse {
    * @format int32
    * @min 0
    */
-  cpus: number;
+  cpus: number;
+  /** What happens to a VM once it has been idle for its `idle_timeout_secs`. */
+  idle_action: IdleAction;
+  /**
+   * `0` when idle VMs are left alone.
+   * @format int64
+   * @min 0
+   */
+  idle_timeout_secs: number;
+  /** Image used when a request omits `image`. */
+  image: string;
+  /** Every image a `create_vm` request can pick with `image`. */
+  images: Record<string, GuestImage>;
+  /**
+   * `0` when VMs run until stopped.
+   * @format int64
+   * @min 0
+   */
+  max_lifetime_secs: number;
+  /**
+   * @format int32
+   * @min 0
+   */
+  memory_mb: number;
+  /**
+   * Outbound network policy of a VM. Persisted as JSON in
+   * `vms.network_policy`.
+   */
+  network_policy: NetworkPolicy;
+  /** Named presets a `create_vm` request can pick with `preset`. */
+  presets: Record<string, VmPreset>;
+}
+
+/**
+ * One forward: `127.0.0.1:host_port` on the host reaches `guest_port`
+ * inside the VM.
+ */
+export interface VmForward {
+  created_at: string;
+  /**
+   * @format int32
+   * @min 0
+   */
+  guest_port: number;
+  /**
+   * @format int32
+   * @min 0
+   */
+  host_port: number;
+  vm_id: string;
+}
+
+/** One extra host directory shared into the guest. */
+export interface VmMount {
+  /** Absolute path the directory is mounted at inside the guest. */
+  guest_path: string;
+  /** Absolute host directory; must sit under `host_mounts.allowed_prefixes`. */
+  host_path: string;
+  mode?: MountMode;
+}
+
+/**
+ * One entry under `presets:`. Every field is optional; unset ones leave
+ * the request (or `vm_defaults`) in charge.
+ */
+export interface VmPreset {
+  /**
+   * Kind of agent spawned when a create-agent request omits `kind`;
+   * also picks the kind's default mounts.
+   */
+  agent_kind?: null | AgentKindName;
+  /**
+   * @format int32
+   * @min 0
+   */
+  cpus?: number | null;
+  /** Shown next to the name in the web UI's preset picker. */
+  description?: string | null;
+  display_mode?: null | VmDisplayMode;
+  /**
+   * Extra environment for every agent in the VM, layered over the
+   * agent kind's own env.
+   */
+  env?: Record<string, string>;
+  idle_action?: null | IdleAction;
+  /**
+   * @format int64
+   * @min 0
+   */
+  idle_timeout_secs?: number | null;
+  /** Name of a guest image (see `crate::images`). */
+  image?: string | null;
+  /**
+   * @format int64
+   * @min 0
+   */
+  max_lifetime_secs?: number | null;
   /**
    * @format int32
    * @min 0
    */
-  memory_mb: number;
+  memory_mb?: number | null;
+  /**
+   * `false` tells clients that send their working directory as `cwd`
+   * (the CLI does) to leave it out, like `--no-workspace`. A `cwd`
+   * that does arrive is still mounted.
+   */
+  mount_cwd?: boolean;
+  /** Extra host mounts; a request mount on the same guest path wins. */
+  mounts?: VmMount[];
+  network_policy?: null | NetworkPolicy;
+  workspace_mode?: null | WorkspaceMode;
+}
+
+export interface VmShare {
+  created_at: string;
+  user_id: string;
+  username: string;
+  vm_id: string;
 }
 
 export interface VmSshInfo {
@@ -197,6 +777,64 @@ export interface VmSshInfo {
   user: string;
 }
 
+/** One reading of a running VM. Fields the host couldn't read are `null`. */
+export interface VmStats {
+  /**
+   * Agents in `starting`, `running` or `stopping`.
+   * @format int64
+   */
+  agents_running: number;
+  /**
+   * CPU use since the previous sample of a `/stats` stream, in percent
+   * of one host core. `null` on the first sample and in one-off reads.
+   * @format double
+   */
+  cpu_percent?: number | null;
+  /**
+   * CPU time the QEMU process has used since it started.
+   * @format double
+   */
+  cpu_seconds?: number | null;
+  /**
+   * Size of the VM's copy-on-write overlay on the host disk.
+   * @format int64
+   * @min 0
+   */
+  disk_overlay_bytes?: number | null;
+  /**
+   * @format int64
+   * @min 0
+   */
+  disk_read_bytes?: number | null;
+  /**
+   * @format int64
+   * @min 0
+   */
+  disk_written_bytes?: number | null;
+  /**
+   * Resident memory of the QEMU process.
+   * @format int64
+   * @min 0
+   */
+  memory_rss_bytes?: number | null;
+  name: string;
+  /**
+   * @format int64
+   * @min 0
+   */
+  net_received_bytes?: number | null;
+  /**
+   * Traffic through the egress proxy; `null` for `open` VMs.
+   * @format int64
+   * @min 0
+   */
+  net_sent_bytes?: number | null;
+  sampled_at: string;
+  /** Paused by the idle monitor; the guest uses no CPU until resumed. */
+  suspended: boolean;
+  vm_id: string;
+}
+
 export interface VmSummary {
   /** @format int64 */
   cpus?: number | null;
@@ -215,30 +853,63 @@ export interface VmSummary {
   /** @format int64 */
   host_ssh_port?: number | null;
   id: string;
+  /** What happens to a VM once it has been idle for its `idle_timeout_secs`. */
+  idle_action: IdleAction;
+  /**
+   * `None` when idleness never stops or suspends this VM.
+   * @format int64
+   */
+  idle_timeout_secs?: number | null;
+  image: string;
   /**
-   * Guest base image. Only `alpine` is currently shipped end-to-end — the
-   * other variants are accepted by the API surface but `create_vm` will
-   * reject them with a 503 until the image-builder lands the qcow2.
+   * `None` when the VM runs until stopped.
+   * @format int64
    */
-  image: VmImage;
+  max_lifetime_secs?: number | null;
   /** @format int64 */
   memory_mb?: number | null;
+  /** Extra host mounts, with canonical host paths. */
+  mounts: VmMount[];
   name: string;
+  /**
+   * Outbound network policy of a VM. Persisted as JSON in
+   * `vms.network_policy`.
+   */
+  network_policy: NetworkPolicy;
   /**
    * `users.id` of the caller who created the VM. `None` for legacy
    * rows written before owner tracking landed (admins still see
    * them; non-admin users do not).
    */
   owner_user_id?: string | null;
+  /** Preset the VM was created from. */
+  preset?: string | null;
   started_at: string;
   /**
    * VM lifecycle status as exposed over the API. Mirrors the SQL CHECK
-   * constraint in `migrations/0001_init.sql`. Serialised as the
+   * constraint in `migrations/0001_init.sql`, plus `suspended`: a
+   * `running` row with `suspended_at` set (see `VM_COLUMNS`). Serialised as the
    * lowercase variant name so the wire format stays
    * `"starting"|"running"|…` and the TypeScript codegen emits a union
    * literal instead of a bare `string` (FUTURE-14).
    */
   status: VmStatus;
+  /** When the idle monitor paused the VM (status `suspended`). */
+  suspended_at?: string | null;
+  /** Branch the VM's worktree is on (`worktree` mode only). */
+  workspace_branch?: string | null;
+  workspace_mode: WorkspaceMode;
+}
+
+/** Result of `POST /v1/vms/{id}/apply`. */
+export interface WorkspaceApplyResult {
+  /**
+   * `worktree` mode: the commit the original checkout is at after the
+   * merge.
+   */
+  merged_commit?: string | null;
+  /** Paths, relative to the VM's `cwd`, written or removed there. */
+  paths: string[];
 }
 
 export type QueryParamsType = Record<string | number, any>;
@@ -500,9 +1171,24 @@ export class HttpClient<SecurityDataType = unknown> {
 export class Api<
   SecurityDataType extends unknown,
 > extends HttpClient<SecurityDataType> {
+  metrics = {
+    /**
+     * @description Prometheus scrape target: CPU, memory, disk and network usage of every running VM, and VM / agent counts per status. Admin only.
+     *
+     * @tags metrics
+     * @name GetMetrics
+     * @request GET:/metrics
+     */
+    getMetrics: (params: RequestParams = {}) =>
+      this.request<string, ErrorResponse>({
+        path: `/metrics`,
+        method: "GET",
+        ...params,
+      }),
+  };
   v1 = {
     /**
-     * @description List agents the caller can see (own agents only for non-admin users).
+     * @description List agents the caller can see (for members: every agent in a VM they own or that was shared with them).
      *
      * @tags agents
      * @name ListAllAgents
@@ -532,66 +1218,436 @@ export class Api<
       }),
 
     /**
-     * @description Delete an agent and its on-disk state. The VM stays running.
+     * @description Delete an agent and its on-disk state, including its session recording. The VM stays running.
+     *
+     * @tags agents
+     * @name DeleteAgent
+     * @request DELETE:/v1/agents/{id}
+     */
+    deleteAgent: (id: string, params: RequestParams = {}) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/agents/${id}`,
+        method: "DELETE",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Update mutable fields of an agent (currently just `name`).
+     *
+     * @tags agents
+     * @name UpdateAgent
+     * @request PATCH:/v1/agents/{id}
+     */
+    updateAgent: (
+      id: string,
+      data: UpdateAgentRequest,
+      params: RequestParams = {},
+    ) =>
+      this.request<AgentSummary, ErrorResponse>({
+        path: `/v1/agents/${id}`,
+        method: "PATCH",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description The agent's session recording in asciicast v2 format: a JSON header line, then one `[seconds, code, data]` line per output (`o`), input (`i`), resize (`r`) or marker (`m`) event. Grows while the agent runs.
+     *
+     * @tags agents
+     * @name GetAgentRecording
+     * @request GET:/v1/agents/{id}/recording
+     */
+    getAgentRecording: (id: string, params: RequestParams = {}) =>
+      this.request<string, ErrorResponse>({
+        path: `/v1/agents/${id}/recording`,
+        method: "GET",
+        ...params,
+      }),
+
+    /**
+     * @description Stop a running agent. The VM hosting it stays up.
+     *
+     * @tags agents
+     * @name StopAgent
+     * @request POST:/v1/agents/{id}/stop
+     */
+    stopAgent: (id: string, params: RequestParams = {}) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/agents/${id}/stop`,
+        method: "POST",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Long-poll until the agent exits or (with `until=attention`, the default) waits for input. Returns at once if it already does; `timed_out` after `timeout_secs`.
+     *
+     * @tags agents
+     * @name WaitAgent
+     * @request GET:/v1/agents/{id}/wait
+     */
+    waitAgent: (
+      id: string,
+      query?: {
+        /** What `GET /v1/agents/{id}/wait` waits for. */
+        until?: "attention" | "exit";
+        /**
+         * Seconds to hold the request open (default 25, at most 300).
+         * @format int64
+         * @min 0
+         */
+        timeout_secs?: number | null;
+      },
+      params: RequestParams = {},
+    ) =>
+      this.request<AgentWaitResponse, ErrorResponse>({
+        path: `/v1/agents/${id}/wait`,
+        method: "GET",
+        query: query,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Audit log entries matching the filters, newest first. Admins see every entry; members see their own.
+     *
+     * @tags audit
+     * @name ListAudit
+     * @request GET:/v1/audit
+     */
+    listAudit: (
+      query?: {
+        /**
+         * Only entries by this username (`admin` for the static admin
+         * identity). Ignored for members, who only see their own.
+         */
+        actor?: string | null;
+        /** Exact action (`vm.stop`) or resource (`vm`). */
+        action?: string | null;
+        /** Id, username or secret name the action targeted. */
+        target?: string | null;
+        outcome?: null | AuditOutcome;
+        /**
+         * Entries at or after this time (RFC 3339).
+         * @format date-time
+         */
+        since?: string | null;
+        /**
+         * Entries before this time (RFC 3339).
+         * @format date-time
+         */
+        until?: string | null;
+        /**
+         * `next_cursor` of the previous page.
+         * @format int64
+         */
+        cursor?: number | null;
+        /**
+         * Entries per page, 1-1000. Defaults to 100.
+         * @format int32
+         * @min 0
+         */
+        limit?: number | null;
+      },
+      params: RequestParams = {},
+    ) =>
+      this.request<AuditPage, ErrorResponse>({
+        path: `/v1/audit`,
+        method: "GET",
+        query: query,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Every entry matching the filters as JSON lines (one `AuditEntry` per line), oldest first. `cursor` and `limit` are ignored.
+     *
+     * @tags audit
+     * @name ExportAudit
+     * @request GET:/v1/audit/export
+     */
+    exportAudit: (
+      query?: {
+        /**
+         * Only entries by this username (`admin` for the static admin
+         * identity). Ignored for members, who only see their own.
+         */
+        actor?: string | null;
+        /** Exact action (`vm.stop`) or resource (`vm`). */
+        action?: string | null;
+        /** Id, username or secret name the action targeted. */
+        target?: string | null;
+        outcome?: null | AuditOutcome;
+        /**
+         * Entries at or after this time (RFC 3339).
+         * @format date-time
+         */
+        since?: string | null;
+        /**
+         * Entries before this time (RFC 3339).
+         * @format date-time
+         */
+        until?: string | null;
+        /**
+         * `next_cursor` of the previous page.
+         * @format int64
+         */
+        cursor?: number | null;
+        /**
+         * Entries per page, 1-1000. Defaults to 100.
+         * @format int32
+         * @min 0
+         */
+        limit?: number | null;
+      },
+      params: RequestParams = {},
+    ) =>
+      this.request<string, ErrorResponse>({
+        path: `/v1/audit/export`,
+        method: "GET",
+        query: query,
+        ...params,
+      }),
+
+    /**
+     * @description Exchange username + password for an opaque bearer token. The token is good for 30 days.
+     *
+     * @tags auth
+     * @name Login
+     * @request POST:/v1/auth/login
+     */
+    login: (data: LoginRequest, params: RequestParams = {}) =>
+      this.request<LoginResponse, ErrorResponse>({
+        path: `/v1/auth/login`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Liveness probe. Always 200 if the process is up.
+     *
+     * @tags health
+     * @name Healthz
+     * @request GET:/v1/healthz
+     */
+    healthz: (params: RequestParams = {}) =>
+      this.request<HealthResponse, any>({
+        path: `/v1/healthz`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description List stored secrets by name, without their values. Admin only.
+     *
+     * @tags secrets
+     * @name ListSecrets
+     * @request GET:/v1/secrets
+     */
+    listSecrets: (params: RequestParams = {}) =>
+      this.request<SecretSummary[], ErrorResponse>({
+        path: `/v1/secrets`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Create or replace a secret. Agents spawned afterwards get the new value. Admin only.
+     *
+     * @tags secrets
+     * @name PutSecret
+     * @request PUT:/v1/secrets/{name}
+     */
+    putSecret: (
+      name: string,
+      data: PutSecretRequest,
+      params: RequestParams = {},
+    ) =>
+      this.request<SecretSummary, ErrorResponse>({
+        path: `/v1/secrets/${name}`,
+        method: "PUT",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Delete a secret. Running agents keep the value they were spawned with. Admin only.
+     *
+     * @tags secrets
+     * @name DeleteSecret
+     * @request DELETE:/v1/secrets/{name}
+     */
+    deleteSecret: (name: string, params: RequestParams = {}) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/secrets/${name}`,
+        method: "DELETE",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description List tasks the caller can see, newest first.
+     *
+     * @tags tasks
+     * @name ListTasks
+     * @request GET:/v1/tasks
+     */
+    listTasks: (params: RequestParams = {}) =>
+      this.request<TaskSummary[], ErrorResponse>({
+        path: `/v1/tasks`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Queue a headless agent run. Returns immediately with the `queued` task; the dispatcher starts it once a slot (`task_queue.max_concurrent`) frees up.
+     *
+     * @tags tasks
+     * @name CreateTask
+     * @request POST:/v1/tasks
+     */
+    createTask: (data: CreateTaskRequest, params: RequestParams = {}) =>
+      this.request<TaskSummary, ErrorResponse>({
+        path: `/v1/tasks`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Fetch a single task by id.
+     *
+     * @tags tasks
+     * @name GetTask
+     * @request GET:/v1/tasks/{id}
+     */
+    getTask: (id: string, params: RequestParams = {}) =>
+      this.request<TaskSummary, ErrorResponse>({
+        path: `/v1/tasks/${id}`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Delete a finished task together with its transcript and collected outputs.
+     *
+     * @tags tasks
+     * @name DeleteTask
+     * @request DELETE:/v1/tasks/{id}
+     */
+    deleteTask: (id: string, params: RequestParams = {}) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/tasks/${id}`,
+        method: "DELETE",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Cancel a queued or running task. A queued task flips to `cancelled` immediately; a running one is killed and flips once its VM is cleaned up.
+     *
+     * @tags tasks
+     * @name CancelTask
+     * @request POST:/v1/tasks/{id}/cancel
+     */
+    cancelTask: (id: string, params: RequestParams = {}) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/tasks/${id}/cancel`,
+        method: "POST",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Files collected from the guest's `/out` after the run. Empty until the task finishes.
      *
-     * @tags agents
-     * @name DeleteAgent
-     * @request DELETE:/v1/agents/{id}
+     * @tags tasks
+     * @name ListTaskOutputs
+     * @request GET:/v1/tasks/{id}/outputs
      */
-    deleteAgent: (id: string, params: RequestParams = {}) =>
-      this.request<OperationResult, ErrorResponse>({
-        path: `/v1/agents/${id}`,
-        method: "DELETE",
+    listTaskOutputs: (id: string, params: RequestParams = {}) =>
+      this.request<TaskOutputFile[], ErrorResponse>({
+        path: `/v1/tasks/${id}/outputs`,
+        method: "GET",
         format: "json",
         ...params,
       }),
 
     /**
-     * @description Update mutable fields of an agent (currently just `name`).
+     * @description Raw contents of one collected output file.
      *
-     * @tags agents
-     * @name UpdateAgent
-     * @request PATCH:/v1/agents/{id}
+     * @tags tasks
+     * @name DownloadTaskOutput
+     * @request GET:/v1/tasks/{id}/outputs/download
      */
-    updateAgent: (
+    downloadTaskOutput: (
       id: string,
-      data: UpdateAgentRequest,
+      query: {
+        /** Path relative to `/out`, as returned by the outputs listing. */
+        path: string;
+      },
       params: RequestParams = {},
     ) =>
-      this.request<AgentSummary, ErrorResponse>({
-        path: `/v1/agents/${id}`,
-        method: "PATCH",
-        body: data,
-        type: ContentType.Json,
-        format: "json",
+      this.request<number[], ErrorResponse>({
+        path: `/v1/tasks/${id}/outputs/download`,
+        method: "GET",
+        query: query,
         ...params,
       }),
 
     /**
-     * @description Stop a running agent. The VM hosting it stays up.
+     * @description Everything the agent wrote to stdout and stderr. Grows while the task runs.
      *
-     * @tags agents
-     * @name StopAgent
-     * @request POST:/v1/agents/{id}/stop
+     * @tags tasks
+     * @name GetTaskTranscript
+     * @request GET:/v1/tasks/{id}/transcript
      */
-    stopAgent: (id: string, params: RequestParams = {}) =>
-      this.request<OperationResult, ErrorResponse>({
-        path: `/v1/agents/${id}/stop`,
-        method: "POST",
+    getTaskTranscript: (id: string, params: RequestParams = {}) =>
+      this.request<string, ErrorResponse>({
+        path: `/v1/tasks/${id}/transcript`,
+        method: "GET",
+        ...params,
+      }),
+
+    /**
+     * @description List personal access tokens: the caller's own, or every user's for an admin.
+     *
+     * @tags auth
+     * @name ListTokens
+     * @request GET:/v1/tokens
+     */
+    listTokens: (params: RequestParams = {}) =>
+      this.request<TokenSummary[], any>({
+        path: `/v1/tokens`,
+        method: "GET",
         format: "json",
         ...params,
       }),
 
     /**
-     * @description Exchange username + password for an opaque bearer token. The token is good for 30 days.
+     * @description Issue a personal access token. The token is returned once and only its hash is stored.
      *
      * @tags auth
-     * @name Login
-     * @request POST:/v1/auth/login
+     * @name CreateToken
+     * @request POST:/v1/tokens
      */
-    login: (data: LoginRequest, params: RequestParams = {}) =>
-      this.request<LoginResponse, ErrorResponse>({
-        path: `/v1/auth/login`,
+    createToken: (data: CreateTokenRequest, params: RequestParams = {}) =>
+      this.request<CreateTokenResponse, ErrorResponse>({
+        path: `/v1/tokens`,
         method: "POST",
         body: data,
         type: ContentType.Json,
@@ -600,29 +1656,29 @@ export class Api<
       }),
 
     /**
-     * @description Liveness probe. Always 200 if the process is up.
+     * @description Revoke a personal access token. Its owner or an admin only.
      *
-     * @tags health
-     * @name Healthz
-     * @request GET:/v1/healthz
+     * @tags auth
+     * @name RevokeToken
+     * @request DELETE:/v1/tokens/{id}
      */
-    healthz: (params: RequestParams = {}) =>
-      this.request<HealthResponse, any>({
-        path: `/v1/healthz`,
-        method: "GET",
+    revokeToken: (id: string, params: RequestParams = {}) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/tokens/${id}`,
+        method: "DELETE",
         format: "json",
         ...params,
       }),
 
     /**
-     * @description List every supervisor user, sorted by username.
+     * @description List every supervisor user, sorted by username. Admin only.
      *
      * @tags users
      * @name ListUsers
      * @request GET:/v1/users
      */
     listUsers: (params: RequestParams = {}) =>
-      this.request<UserSummary[], any>({
+      this.request<UserSummary[], ErrorResponse>({
         path: `/v1/users`,
         method: "GET",
         format: "json",
@@ -630,7 +1686,7 @@ export class Api<
       }),
 
     /**
-     * @description Create a new supervisor user.
+     * @description Create a new supervisor user. Admin only.
      *
      * @tags users
      * @name CreateUser
@@ -646,6 +1702,42 @@ export class Api<
         ...params,
       }),
 
+    /**
+     * @description The caller's quota and current usage.
+     *
+     * @tags users
+     * @name GetMyQuota
+     * @request GET:/v1/users/me/quota
+     */
+    getMyQuota: (params: RequestParams = {}) =>
+      this.request<QuotaReport, ErrorResponse>({
+        path: `/v1/users/me/quota`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Change a user's role or quota. Admin only. Quota changes apply to the next VM the user creates; running VMs are kept.
+     *
+     * @tags users
+     * @name UpdateUser
+     * @request PATCH:/v1/users/{username}
+     */
+    updateUser: (
+      username: string,
+      data: UpdateUserRequest,
+      params: RequestParams = {},
+    ) =>
+      this.request<UserSummary, ErrorResponse>({
+        path: `/v1/users/${username}`,
+        method: "PATCH",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
     /**
      * @description List every VM, newest first.
      *
@@ -679,7 +1771,7 @@ export class Api<
       }),
 
     /**
-     * @description Server-side defaults applied when a `create_vm` request omits the corresponding field.
+     * @description Server-side defaults applied when a `create_vm` request omits the corresponding field, and the named presets it can pick instead.
      *
      * @tags vms
      * @name GetVmDefaults
@@ -693,6 +1785,21 @@ export class Api<
         ...params,
       }),
 
+    /**
+     * @description One resource reading of every running VM the caller can see. `cpu_percent` is always null here; compare `cpu_seconds` between calls.
+     *
+     * @tags vms
+     * @name ListVmStats
+     * @request GET:/v1/vms/stats
+     */
+    listVmStats: (params: RequestParams = {}) =>
+      this.request<VmStats[], any>({
+        path: `/v1/vms/stats`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
     /**
      * @description Fetch a single VM by id.
      *
@@ -740,6 +1847,241 @@ export class Api<
         ...params,
       }),
 
+    /**
+     * @description Write the agent's changes back into the VM's `cwd`. `overlay` copies changed files and refuses if any of them also changed in `cwd`; `worktree` commits pending changes on the VM's branch and merges it into `cwd`'s current branch, aborting the merge on conflicts.
+     *
+     * @tags vms
+     * @name ApplyVmWorkspace
+     * @request POST:/v1/vms/{id}/apply
+     */
+    applyVmWorkspace: (id: string, params: RequestParams = {}) =>
+      this.request<WorkspaceApplyResult, ErrorResponse>({
+        path: `/v1/vms/${id}/apply`,
+        method: "POST",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Boot a copy of a VM: same resources, image, policy, workspace and mounts, with its disk flattened into a new overlay, a fresh SSH keypair and new ports. A running source is paused only while its overlay is copied. `overlay` and `worktree` workspaces get a fresh copy of the host directory, not the source VM's pending changes. The caller owns the clone and it counts against their quota.
+     *
+     * @tags vms
+     * @name CloneVm
+     * @request POST:/v1/vms/{id}/clone
+     */
+    cloneVm: (id: string, data: CloneVmRequest, params: RequestParams = {}) =>
+      this.request<VmSummary, ErrorResponse>({
+        path: `/v1/vms/${id}/clone`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description The agent's changes as a unified diff against the VM's `cwd`. Only for `overlay` and `worktree` workspaces.
+     *
+     * @tags vms
+     * @name GetVmDiff
+     * @request GET:/v1/vms/{id}/diff
+     */
+    getVmDiff: (id: string, params: RequestParams = {}) =>
+      this.request<string, ErrorResponse>({
+        path: `/v1/vms/${id}/diff`,
+        method: "GET",
+        ...params,
+      }),
+
+    /**
+     * @description Outbound connections the VM's network policy refused, oldest first (at most the newest 200). Always empty for `open` VMs.
+     *
+     * @tags vms
+     * @name GetVmEgressLog
+     * @request GET:/v1/vms/{id}/egress-log
+     */
+    getVmEgressLog: (id: string, params: RequestParams = {}) =>
+      this.request<BlockedEgressAttempt[], ErrorResponse>({
+        path: `/v1/vms/${id}/egress-log`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Runtime port forwards of a VM, by guest port.
+     *
+     * @tags vms
+     * @name ListForwards
+     * @request GET:/v1/vms/{id}/forwards
+     */
+    listForwards: (id: string, params: RequestParams = {}) =>
+      this.request<VmForward[], ErrorResponse>({
+        path: `/v1/vms/${id}/forwards`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Forward a guest TCP port to `127.0.0.1:<host_port>` on the supervisor host via QMP `hostfwd_add`. The forward lasts until it is removed or the VM stops.
+     *
+     * @tags vms
+     * @name AddForward
+     * @request POST:/v1/vms/{id}/forwards
+     */
+    addForward: (
+      id: string,
+      data: AddForwardRequest,
+      params: RequestParams = {},
+    ) =>
+      this.request<VmForward, ErrorResponse>({
+        path: `/v1/vms/${id}/forwards`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Remove a runtime port forward via QMP `hostfwd_remove`.
+     *
+     * @tags vms
+     * @name RemoveForward
+     * @request DELETE:/v1/vms/{id}/forwards/{guest_port}
+     */
+    removeForward: (
+      id: string,
+      guestPort: number,
+      params: RequestParams = {},
+    ) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/vms/${id}/forwards/${guestPort}`,
+        method: "DELETE",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Users the VM is shared with, by username.
+     *
+     * @tags vms
+     * @name ListShares
+     * @request GET:/v1/vms/{id}/shares
+     */
+    listShares: (id: string, params: RequestParams = {}) =>
+      this.request<VmShare[], ErrorResponse>({
+        path: `/v1/vms/${id}/shares`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Share the VM with a user. Idempotent.
+     *
+     * @tags vms
+     * @name AddShare
+     * @request PUT:/v1/vms/{id}/shares/{username}
+     */
+    addShare: (id: string, username: string, params: RequestParams = {}) =>
+      this.request<VmShare, ErrorResponse>({
+        path: `/v1/vms/${id}/shares/${username}`,
+        method: "PUT",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Stop sharing the VM with a user. Agents they started keep running, but they can no longer see or use them.
+     *
+     * @tags vms
+     * @name RemoveShare
+     * @request DELETE:/v1/vms/{id}/shares/{username}
+     */
+    removeShare: (id: string, username: string, params: RequestParams = {}) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/vms/${id}/shares/${username}`,
+        method: "DELETE",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description List the snapshots of a VM, newest first.
+     *
+     * @tags vms
+     * @name ListSnapshots
+     * @request GET:/v1/vms/{id}/snapshots
+     */
+    listSnapshots: (id: string, params: RequestParams = {}) =>
+      this.request<SnapshotSummary[], ErrorResponse>({
+        path: `/v1/vms/${id}/snapshots`,
+        method: "GET",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Checkpoint a VM's disk, through QMP on running VMs and via `qemu-img` on stopped ones.
+     *
+     * @tags vms
+     * @name CreateSnapshot
+     * @request POST:/v1/vms/{id}/snapshots
+     */
+    createSnapshot: (
+      id: string,
+      data: CreateSnapshotRequest,
+      params: RequestParams = {},
+    ) =>
+      this.request<SnapshotSummary, ErrorResponse>({
+        path: `/v1/vms/${id}/snapshots`,
+        method: "POST",
+        body: data,
+        type: ContentType.Json,
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Delete a snapshot and free its space in the VM's overlay.
+     *
+     * @tags vms
+     * @name DeleteSnapshot
+     * @request DELETE:/v1/vms/{id}/snapshots/{snapshot_id}
+     */
+    deleteSnapshot: (
+      id: string,
+      snapshotId: string,
+      params: RequestParams = {},
+    ) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/vms/${id}/snapshots/${snapshotId}`,
+        method: "DELETE",
+        format: "json",
+        ...params,
+      }),
+
+    /**
+     * @description Roll the VM's disk back to a snapshot. The VM has to be stopped.
+     *
+     * @tags vms
+     * @name RestoreSnapshot
+     * @request POST:/v1/vms/{id}/snapshots/{snapshot_id}/restore
+     */
+    restoreSnapshot: (
+      id: string,
+      snapshotId: string,
+      params: RequestParams = {},
+    ) =>
+      this.request<OperationResult, ErrorResponse>({
+        path: `/v1/vms/${id}/snapshots/${snapshotId}/restore`,
+        method: "POST",
+        format: "json",
+        ...params,
+      }),
+
     /**
      * @description Return host/port + the supervisor's host keypair so the caller can ssh in.
      *
@@ -755,6 +2097,32 @@ export class Api<
         ...params,
       }),
 
+    /**
+     * @description Server-sent events: a `stats` event carrying a `VmStats` reading every `interval_secs` until the VM stops running.
+     *
+     * @tags vms
+     * @name StreamVmStats
+     * @request GET:/v1/vms/{id}/stats
+     */
+    streamVmStats: (
+      id: string,
+      query?: {
+        /**
+         * Seconds between events (1–60, default 2).
+         * @format int64
+         * @min 0
+         */
+        interval_secs?: number | null;
+      },
+      params: RequestParams = {},
+    ) =>
+      this.request<VmStats, ErrorResponse>({
+        path: `/v1/vms/${id}/stats`,
+        method: "GET",
+        query: query,
+        ...params,
+      }),
+
     /**
      * @description Stop a running VM. Reaps all agents the VM was hosting.
      *
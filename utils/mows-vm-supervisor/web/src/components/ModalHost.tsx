@@ -27,7 +27,14 @@ import {
     SelectValue
 } from "@my-own-web-services/react-components/components/ui/select";
 import { useEffect, useState } from "react";
-import { getVmDefaults } from "../lib/api";
+import { VmDisplayMode } from "../api/generated/api-client";
+import {
+    getVmDefaults,
+    getVmImages,
+    getVmPresets,
+    type GuestImage,
+    type VmPreset
+} from "../lib/api";
 import {
     getCurrentModal,
     subscribeModal,
@@ -37,6 +44,14 @@ import {
     type VmImageChoice
 } from "../lib/modals";
 
+// Shown until (or if) `GET /v1/vms/defaults` doesn't answer.
+const BUILT_IMAGES: Record<string, GuestImage> = Object.fromEntries(
+    ["alpine", "debian", "ubuntu", "nixos"].map((distro) => [
+        distro,
+        { source: "built" as const, distro }
+    ])
+);
+
 const ModalHost = () => {
     const [activeRequest, setActiveRequest] = useState<ModalRequest | null>(getCurrentModal());
     useEffect(() => subscribeModal(setActiveRequest), []);
@@ -44,13 +59,16 @@ const ModalHost = () => {
     // Re-seed the prompt input each time a new prompt request arrives.
     const [promptValue, setPromptValue] = useState("");
     const [vmForm, setVmForm] = useState<VmCreateInput>({
+        preset: null,
         name: "",
         cwd: "",
         cpus: null,
         memoryMb: null,
         image: "alpine",
-        displayMode: "headless"
+        displayMode: VmDisplayMode.Headless
     });
+    const [presets, setPresets] = useState<Record<string, VmPreset>>({});
+    const [images, setImages] = useState<Record<string, GuestImage>>(BUILT_IMAGES);
     useEffect(() => {
         if (!activeRequest) return;
         if (activeRequest.kind === "prompt") setPromptValue(activeRequest.initial);
@@ -70,9 +88,34 @@ const ModalHost = () => {
                 .catch(() => {
                     /* leave fields empty — backend applies its own default */
                 });
+            getVmPresets()
+                .then(setPresets)
+                .catch(() => setPresets({}));
+            getVmImages()
+                .then(({ image, images }) => {
+                    setImages(images);
+                    // Swap the form's `alpine` fallback for the supervisor's
+                    // own default.
+                    setVmForm((prev) => (prev.image === "alpine" ? { ...prev, image } : prev));
+                })
+                .catch(() => setImages(BUILT_IMAGES));
         }
     }, [activeRequest]);
 
+    // Picking a preset copies its values into the form so the user sees
+    // (and can still override) what the VM will get.
+    const choosePreset = (name: string) => {
+        const preset = name === "" ? null : presets[name];
+        setVmForm((prev) => ({
+            ...prev,
+            preset: preset ? name : null,
+            cpus: preset?.cpus ?? prev.cpus,
+            memoryMb: preset?.memory_mb ?? prev.memoryMb,
+            image: preset?.image ?? prev.image,
+            displayMode: preset?.display_mode ?? prev.displayMode
+        }));
+    };
+
     if (!activeRequest) return null;
 
     const cancel = () => {
@@ -126,6 +169,28 @@ const ModalHost = () => {
                             confirm();
                         }}
                     >
+                        {Object.keys(presets).length > 0 && (
+                            <div className="flex flex-col gap-1.5">
+                                <Label htmlFor="vm-preset">Preset</Label>
+                                <Select
+                                    value={vmForm.preset ?? ""}
+                                    onValueChange={choosePreset}
+                                >
+                                    <SelectTrigger id="vm-preset">
+                                        <SelectValue placeholder="None" />
+                                    </SelectTrigger>
+                                    <SelectContent>
+                                        {Object.entries(presets).map(([name, preset]) => (
+                                            <SelectItem key={name} value={name}>
+                                                {preset.description
+                                                    ? `${name} — ${preset.description}`
+                                                    : name}
+                                            </SelectItem>
+                                        ))}
+                                    </SelectContent>
+                                </Select>
+                            </div>
+                        )}
                         <div className="flex flex-col gap-1.5">
                             <Label htmlFor="vm-name">Name (optional)</Label>
                             <Input
@@ -162,10 +227,13 @@ const ModalHost = () => {
                                         <SelectValue />
                                     </SelectTrigger>
                                     <SelectContent>
-                                        <SelectItem value="alpine">Alpine</SelectItem>
-                                        <SelectItem value="debian">Debian</SelectItem>
-                                        <SelectItem value="ubuntu">Ubuntu</SelectItem>
-                                        <SelectItem value="nixos">NixOS</SelectItem>
+                                        {Object.entries(images).map(([name, image]) => (
+                                            <SelectItem key={name} value={name}>
+                                                {image.description
+                                                    ? `${name} — ${image.description}`
+                                                    : name}
+                                            </SelectItem>
+                                        ))}
                                     </SelectContent>
                                 </Select>
                             </div>
@@ -0,0 +1,204 @@
+// RecordingPlayer — plays an agent's asciicast v2 session recording
+// (written by the supervisor's `recording.rs`) into an <xterm> Terminal.
+//
+// Wire shape: one JSON header line (`{ version: 2, width, height, … }`),
+// then one `[seconds, code, data]` line per event:
+//   • `o` — terminal output → written to xterm at its recorded time.
+//   • `i` — keystrokes from an attached client. Not replayed: the session's
+//     echo already shows up as output.
+//   • `r` — pane resize (`"<cols>x<rows>"`). xterm stays fitted to the
+//     container, so these are only informational.
+//   • `m` — markers (client attached / detached, truncation), listed under
+//     the player with their offsets.
+//
+// Gaps between events are replayed as recorded, divided by the speed
+// selector — when the agent did something matters as much as what.
+
+import { Button } from "@my-own-web-services/react-components/components/ui/button";
+import Terminal, {
+    type TerminalHandle
+} from "@my-own-web-services/react-components/components/console/terminal/Terminal";
+import { useMows } from "@my-own-web-services/react-components/lib/mowsContext/MowsContext";
+import { Pause, Play, RotateCcw } from "lucide-react";
+import { useCallback, useEffect, useMemo, useRef, useState, type ReactNode } from "react";
+import { formatDuration } from "../lib/format";
+
+const SPEEDS = [1, 2, 4, 8, 16] as const;
+
+interface CastEvent {
+    readonly time: number;
+    readonly code: string;
+    readonly data: string;
+}
+
+/** Parse the event lines of an asciicast v2 document. The header isn't
+ *  needed (xterm is fitted to the page) and malformed lines are skipped
+ *  so a recording cut off mid-write still plays. */
+const parseCast = (raw: string): CastEvent[] => {
+    const events: CastEvent[] = [];
+    for (const line of raw.split("\n").slice(1)) {
+        if (!line.trim()) continue;
+        try {
+            const [time, code, data] = JSON.parse(line) as [number, string, string];
+            if (typeof time === "number" && typeof data === "string") {
+                events.push({ time, code, data });
+            }
+        } catch {
+            // partial trailing line of a recording that's still growing
+        }
+    }
+    return events;
+};
+
+interface RecordingPlayerProps {
+    /** Raw `application/x-asciicast` body. */
+    readonly cast: string;
+}
+
+const RecordingPlayer = ({ cast }: RecordingPlayerProps): ReactNode => {
+    const t = useMows().t.supervisor.recording;
+    const events = useMemo(() => parseCast(cast), [cast]);
+    const output = useMemo(() => events.filter((e) => e.code === "o"), [events]);
+    const markers = useMemo(() => events.filter((e) => e.code === "m"), [events]);
+    const duration = events.length > 0 ? events[events.length - 1].time : 0;
+
+    const terminalRef = useRef<TerminalHandle | null>(null);
+    const timerRef = useRef<number | null>(null);
+    const indexRef = useRef(0);
+    const speedRef = useRef<number>(1);
+    const [playing, setPlaying] = useState(false);
+    const [position, setPosition] = useState(0);
+    const [speed, setSpeed] = useState<number>(1);
+
+    const stopTimer = () => {
+        if (timerRef.current !== null) {
+            window.clearTimeout(timerRef.current);
+            timerRef.current = null;
+        }
+    };
+
+    // Chain one timeout per output event. Pausing drops the pending timer;
+    // resuming waits the current gap out again, which is close enough for
+    // review and keeps the loop free of wall-clock bookkeeping.
+    const scheduleNext = useCallback(() => {
+        const index = indexRef.current;
+        if (index >= output.length) {
+            setPlaying(false);
+            return;
+        }
+        const previous = index === 0 ? 0 : output[index - 1].time;
+        const delayMs = (Math.max(0, output[index].time - previous) * 1000) / speedRef.current;
+        timerRef.current = window.setTimeout(() => {
+            const event = output[index];
+            terminalRef.current?.write(event.data);
+            indexRef.current = index + 1;
+            setPosition(event.time);
+            scheduleNext();
+        }, delayMs);
+    }, [output]);
+
+    const play = useCallback(() => {
+        stopTimer();
+        setPlaying(true);
+        scheduleNext();
+    }, [scheduleNext]);
+
+    const pause = () => {
+        stopTimer();
+        setPlaying(false);
+    };
+
+    const restart = () => {
+        stopTimer();
+        terminalRef.current?.clear();
+        indexRef.current = 0;
+        setPosition(0);
+        play();
+    };
+
+    const changeSpeed = (next: number) => {
+        speedRef.current = next;
+        setSpeed(next);
+    };
+
+    // A new recording (e.g. navigating to another agent) starts over.
+    useEffect(() => {
+        indexRef.current = 0;
+        setPosition(0);
+        terminalRef.current?.clear();
+        if (terminalRef.current) play();
+        return stopTimer;
+    }, [output, play]);
+
+    const handleReady = (handle: TerminalHandle) => {
+        terminalRef.current = handle;
+        try {
+            handle.fit();
+        } catch {
+            // ignore — container not yet measurable
+        }
+        play();
+    };
+
+    return (
+        <div className="flex h-full min-h-0 flex-col gap-2">
+            <div className="flex items-center gap-2">
+                <Button
+                    size="sm"
+                    variant="outline"
+                    onClick={playing ? pause : play}
+                    disabled={output.length === 0}
+                >
+                    {playing ? <Pause className="size-3.5" /> : <Play className="size-3.5" />}
+                    {playing ? t.pause : t.play}
+                </Button>
+                <Button
+                    size="sm"
+                    variant="ghost"
+                    onClick={restart}
+                    disabled={output.length === 0}
+                >
+                    <RotateCcw className="size-3.5" />
+                    {t.restart}
+                </Button>
+                <label className="text-muted-foreground ml-2 flex items-center gap-1.5 text-xs">
+                    {t.speed}
+                    <select
+                        className="bg-background rounded-md border px-1.5 py-0.5 text-xs"
+                        value={speed}
+                        onChange={(event) => changeSpeed(Number(event.target.value))}
+                    >
+                        {SPEEDS.map((value) => (
+                            <option key={value} value={value}>
+                                {value}×
+                            </option>
+                        ))}
+                    </select>
+                </label>
+                <span className="text-muted-foreground ml-auto font-mono text-xs tabular-nums">
+                    {formatDuration(position * 1000)} / {formatDuration(duration * 1000)}
+                </span>
+            </div>
+            {output.length === 0 && (
+                <div className="text-muted-foreground text-sm">{t.empty}</div>
+            )}
+            <div className="min-h-0 flex-1">
+                <Terminal onReady={handleReady} />
+            </div>
+            {markers.length > 0 && (
+                <div className="text-muted-foreground text-xs">
+                    <div className="mb-1 text-[10px] uppercase tracking-wider">{t.markers}</div>
+                    <ul className="space-y-0.5 font-mono">
+                        {markers.map((marker, index) => (
+                            <li key={index}>
+                                {formatDuration(marker.time * 1000)} · {marker.data}
+                            </li>
+                        ))}
+                    </ul>
+                </div>
+            )}
+        </div>
+    );
+};
+
+export default RecordingPlayer;
@@ -27,7 +27,7 @@ import {
     SidebarMenuSubItem
 } from "@my-own-web-services/react-components/components/ui/sidebar";
 import { useMows } from "@my-own-web-services/react-components/lib/mowsContext/MowsContext";
-import { Bot, ChevronRight, Plus, Server, type LucideIcon } from "lucide-react";
+import { Bot, ChevronRight, Plus, ScrollText, Server, type LucideIcon } from "lucide-react";
 import { useEffect, useState } from "react";
 import { Link, useMatch, useNavigate } from "react-router-dom";
 import { toast } from "sonner";
@@ -45,10 +45,6 @@ import {
     type VmSummary
 } from "../lib/api";
 import { subscribeEvents, type SupervisorEvent } from "../lib/events";
-import {
-    VmDisplayMode,
-    VmImage
-} from "../api/generated/api-client";
 import { requestNewVm } from "../lib/modals";
 
 const statusDot = (status: string): string => {
@@ -56,6 +52,7 @@ const statusDot = (status: string): string => {
     if (status === "starting" || status === "stopping")
         return "bg-amber-500 animate-pulse";
     if (status === "failed") return "bg-red-500";
+    if (status === "suspended") return "bg-sky-500";
     return "bg-muted-foreground/40";
 };
 
@@ -221,6 +218,9 @@ const Sidebar = () => {
     // highlight the active VM row regardless of mount depth.
     const vmMatch = useMatch("/vms/:id");
     const activeVmId = vmMatch?.params.id;
+    const agentMatch = useMatch("/agents/:id/recording");
+    const activeAgentId = agentMatch?.params.id;
+    const auditMatch = useMatch("/audit");
     const navigate = useNavigate();
     const vms = useLiveData<VmSummary>(listVms, isVmEvent);
     const agents = useLiveData<AgentSummary>(listAgents, isAgentEvent);
@@ -246,9 +246,10 @@ const Sidebar = () => {
                                     });
                                     if (!form) return;
                                     const createVmPayload: CreateVmRequest = {
-                                        image: form.image as VmImage,
-                                        display_mode: form.displayMode as VmDisplayMode
+                                        image: form.image,
+                                        display_mode: form.displayMode
                                     };
+                                    if (form.preset) createVmPayload.preset = form.preset;
                                     if (form.name.trim()) createVmPayload.name = form.name.trim();
                                     if (form.cwd.trim()) createVmPayload.cwd = form.cwd.trim();
                                     if (form.cpus !== null) createVmPayload.cpus = form.cpus;
@@ -302,20 +303,38 @@ const Sidebar = () => {
                                     data-action-target-status={agent.status}
                                 >
                                     <SidebarMenuSubButton
-                                        className="cursor-pointer"
-                                        title={`${agent.kind} · ${agent.name} · ${agent.status}`}
+                                        asChild
+                                        isActive={activeAgentId === agent.id}
+                                        className="cursor-pointer data-[active=true]:bg-sidebar-accent data-[active=true]:font-semibold"
                                     >
-                                        <span
-                                            className={`${statusDot(agent.status)} size-2 shrink-0 rounded-full`}
-                                        />
-                                        <span className="text-muted-foreground font-mono text-[10px]">
-                                            {agent.kind}
-                                        </span>
-                                        <span className="truncate">{agent.name}</span>
+                                        <Link
+                                            to={`/agents/${agent.id}/recording`}
+                                            title={`${agent.kind} · ${agent.name} · ${agent.status}`}
+                                        >
+                                            <span
+                                                className={`${statusDot(agent.status)} size-2 shrink-0 rounded-full`}
+                                            />
+                                            <span className="text-muted-foreground font-mono text-[10px]">
+                                                {agent.kind}
+                                            </span>
+                                            <span className="truncate">{agent.name}</span>
+                                        </Link>
                                     </SidebarMenuSubButton>
                                 </SidebarMenuSubItem>
                             )}
                         />
+                        <SidebarMenuItem>
+                            <SidebarMenuButton
+                                asChild
+                                isActive={auditMatch !== null}
+                                tooltip={t.supervisor.audit.link}
+                            >
+                                <Link to="/audit">
+                                    <ScrollText />
+                                    <span>{t.supervisor.audit.link}</span>
+                                </Link>
+                            </SidebarMenuButton>
+                        </SidebarMenuItem>
                     </SidebarMenu>
                 </SidebarGroup>
             </SidebarContent>
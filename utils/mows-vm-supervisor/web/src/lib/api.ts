@@ -7,19 +7,29 @@
 // `Authorization: Bearer <token>` header from localStorage so every call
 // honours the auth flow without bespoke fetch wiring.
 
-import { Api, type CreateVmRequest } from "../api/generated/api-client";
+import {
+    Api,
+    type CreateVmRequest,
+    type VmDefaultsResponse,
+    type VmPreset
+} from "../api/generated/api-client";
 
 export type {
     AgentSummary,
+    AuditEntry,
+    AuditPage,
     CreateAgentRequest,
     CreateUserRequest,
     CreateVmRequest,
     ErrorResponse,
+    GuestImage,
     HealthResponse,
     LoginRequest,
     LoginResponse,
     OperationResult,
     UserSummary,
+    VmDefaultsResponse,
+    VmPreset,
     VmSshInfo,
     VmSummary
 } from "../api/generated/api-client";
@@ -51,6 +61,16 @@ export const listVms = () => unwrap(api.v1.listVms());
 export const createVm = (request: CreateVmRequest = {}) =>
     unwrap(api.v1.createVm(request));
 export const getVmDefaults = () => unwrap(api.v1.getVmDefaults());
+
+/** Named presets from `presets:` in the supervisor config. */
+export const getVmPresets = async (): Promise<Record<string, VmPreset>> =>
+    (await getVmDefaults()).presets;
+
+/** Every image a VM can be created from, plus the supervisor's default. */
+export const getVmImages = async (): Promise<Pick<VmDefaultsResponse, "image" | "images">> => {
+    const { image, images } = await getVmDefaults();
+    return { image, images };
+};
 export const stopVm = (id: string) => unwrap(api.v1.stopVm(id));
 export const deleteVm = (id: string) => unwrap(api.v1.deleteVm(id));
 export const renameVm = (id: string, name: string) =>
@@ -62,6 +82,34 @@ export const deleteAgent = (id: string) => unwrap(api.v1.deleteAgent(id));
 export const renameAgent = (id: string, name: string) =>
     unwrap(api.v1.updateAgent(id, { name }));
 
+/** Raw asciicast v2 text of an agent's session recording. Fetched by hand
+ *  because the body is newline-delimited JSON, not one JSON document.
+ *  Rejects with the `Response` on failure so `describeApiError` applies. */
+export const fetchAgentRecording = async (id: string): Promise<string> => {
+    const token = localStorage.getItem(TOKEN_STORAGE_KEY);
+    const response = await fetch(`/v1/agents/${encodeURIComponent(id)}/recording`, {
+        headers: token ? { Authorization: `Bearer ${token}` } : {}
+    });
+    if (!response.ok) throw response;
+    return response.text();
+};
+
+/** Filters of `GET /v1/audit` and `GET /v1/audit/export`. */
+export type AuditFilter = Pick<
+    NonNullable<Parameters<typeof api.v1.listAudit>[0]>,
+    "actor" | "action" | "target" | "outcome"
+>;
+
+/** One page of the audit log, newest first; pass `next_cursor` back as
+ *  `cursor` for the next one. */
+export const listAudit = (filter: AuditFilter, cursor?: number) =>
+    unwrap(api.v1.listAudit({ ...filter, cursor }));
+
+/** Every matching entry as JSON lines, oldest first. The generated call
+ *  leaves the body unread since it isn't one JSON document. */
+export const exportAudit = async (filter: AuditFilter): Promise<Blob> =>
+    (await api.v1.exportAudit(filter)).blob();
+
 export { api };
 
 // swagger-typescript-api rejects with the raw `Response` (plus an `.error`
@@ -20,6 +20,14 @@ export type SupervisorEvent =
     | { type: "agent_created"; id: string; vm_id: string }
     | { type: "agent_updated"; id: string }
     | { type: "agent_deleted"; id: string }
+    | { type: "agent_exited"; id: string; vm_id: string; status: string; exit_code: number | null }
+    | { type: "agent_waiting_for_input"; id: string; vm_id: string }
+    | { type: "snapshot_created"; id: string; vm_id: string }
+    | { type: "snapshot_restored"; id: string; vm_id: string }
+    | { type: "snapshot_deleted"; id: string; vm_id: string }
+    | { type: "task_created"; id: string }
+    | { type: "task_updated"; id: string }
+    | { type: "task_deleted"; id: string }
     | { type: "resync" };
 
 export type EventListener = (event: SupervisorEvent) => void;
@@ -65,3 +65,17 @@ export const formatBytes = (mb: number): string => {
     }
     return `${mb} MB`;
 };
+
+/** Byte counts from the stats stream: "1.4 GB" / "312 MB" / "18 KB" / "512 B". */
+export const formatByteCount = (bytes: number): string => {
+    const units = ["B", "KB", "MB", "GB", "TB"];
+    let value = bytes;
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+        value /= 1024;
+        unit += 1;
+    }
+    return unit === 0 || value >= 100
+        ? `${Math.round(value)} ${units[unit]}`
+        : `${value.toFixed(1)} ${units[unit]}`;
+};
@@ -39,6 +39,7 @@ const enUS: Translation = {
                 stopping: "stopping",
                 stopped: "stopped",
                 failed: "failed",
+                suspended: "suspended (idle)",
                 exited: "exited"
             },
             renamedTo: "renamed to {name}",
@@ -51,6 +52,12 @@ const enUS: Translation = {
                 memory: "Memory",
                 uptime: "Uptime",
                 baseImage: "Base image",
+                cpuUsed: "{percent}% used",
+                memoryUsed: "{size} in use",
+                disk: "Disk I/O",
+                diskValue: "{read} read · {written} written",
+                network: "Network",
+                networkValue: "{sent} out · {received} in",
                 unknown: "—"
             },
             console: {
@@ -66,6 +73,34 @@ const enUS: Translation = {
                     typeLabel: "Claude Code"
                 }
             }
+        },
+        recording: {
+            title: "Session recording",
+            loading: "Loading recording…",
+            loadFailed: "Failed to load recording:",
+            empty: "The recording has no output yet.",
+            play: "Play",
+            pause: "Pause",
+            restart: "Restart",
+            speed: "Speed",
+            markers: "Markers"
+        },
+        audit: {
+            title: "Audit log",
+            link: "Audit log",
+            loading: "Loading audit log…",
+            loadFailed: "Failed to load audit log:",
+            empty: "No matching entries.",
+            loadMore: "Load more",
+            export: "Export JSON lines",
+            failuresOnly: "Failures only",
+            time: "Time",
+            actor: "Actor",
+            source: "Source",
+            action: "Action",
+            target: "Target",
+            outcome: "Outcome",
+            unixSocket: "unix socket"
         }
     }
 };
@@ -5,6 +5,8 @@
 // dismisses the modal. A single <ModalHost /> mounted from App.tsx subscribes
 // to the state and renders the actual UI — no `window.confirm`/`prompt`.
 
+import { VmDisplayMode } from "../api/generated/api-client";
+
 export interface ConfirmRequest {
     readonly kind: "confirm";
     readonly title: string;
@@ -26,10 +28,13 @@ export interface PromptRequest {
     readonly resolve: (value: string | null) => void;
 }
 
-export type VmImageChoice = "alpine" | "ubuntu" | "debian" | "nixos";
-export type VmDisplayModeChoice = "headless" | "desktop";
+/** Name of a guest image (see `getVmImages`). */
+export type VmImageChoice = string;
+export type VmDisplayModeChoice = VmDisplayMode;
 
 export interface VmCreateInput {
+    /** Named supervisor preset; `null` for none. */
+    readonly preset: string | null;
     readonly name: string;
     readonly cwd: string;
     readonly cpus: number | null;
@@ -122,12 +127,13 @@ export const requestNewVm = (
             title: opts.title ?? "Create VM",
             description: opts.description,
             initial: {
+                preset: opts.initial?.preset ?? null,
                 name: opts.initial?.name ?? "",
                 cwd: opts.initial?.cwd ?? "",
                 cpus: opts.initial?.cpus ?? null,
                 memoryMb: opts.initial?.memoryMb ?? null,
                 image: opts.initial?.image ?? "alpine",
-                displayMode: opts.initial?.displayMode ?? "headless"
+                displayMode: opts.initial?.displayMode ?? VmDisplayMode.Headless
             },
             confirmLabel: opts.confirmLabel ?? "Create",
             cancelLabel: opts.cancelLabel ?? "Cancel",
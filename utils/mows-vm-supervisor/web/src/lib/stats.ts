@@ -0,0 +1,65 @@
+// Live resource readings for one VM from the supervisor's
+// `/v1/vms/{id}/stats` server-sent-event stream.
+//
+// Unlike `events.ts` this is one stream per mounted VM page, not a shared
+// singleton: the stream is specific to a VM and the server ends it when
+// the VM stops. `EventSource` can't send an `Authorization` header either,
+// so the session token rides along as `?token=` like the events socket.
+
+import { useEffect, useState } from "react";
+
+const TOKEN_STORAGE_KEY = "mows-vm-supervisor:token";
+
+/** Mirror of the supervisor's `VmStats` schema. */
+export interface VmStats {
+    vm_id: string;
+    name: string;
+    sampled_at: string;
+    suspended: boolean;
+    cpu_seconds: number | null;
+    cpu_percent: number | null;
+    memory_rss_bytes: number | null;
+    disk_overlay_bytes: number | null;
+    disk_read_bytes: number | null;
+    disk_written_bytes: number | null;
+    net_sent_bytes: number | null;
+    net_received_bytes: number | null;
+    agents_running: number;
+}
+
+/**
+ * Latest reading for `vmId`, or `null` until the first one arrives. Only
+ * streams while `running` is true; the reading resets when it flips off.
+ */
+export const useVmStats = (
+    vmId: string | null,
+    running: boolean,
+    intervalSecs = 2
+): VmStats | null => {
+    const [stats, setStats] = useState<VmStats | null>(null);
+
+    useEffect(() => {
+        setStats(null);
+        if (!vmId || !running) return;
+        const token = localStorage.getItem(TOKEN_STORAGE_KEY) ?? "";
+        const params = new URLSearchParams({ interval_secs: String(intervalSecs) });
+        if (token) params.set("token", token);
+        const source = new EventSource(
+            `/v1/vms/${encodeURIComponent(vmId)}/stats?${params.toString()}`
+        );
+        source.addEventListener("stats", (event) => {
+            try {
+                setStats(JSON.parse((event as MessageEvent<string>).data) as VmStats);
+            } catch {
+                // Malformed frame — keep the previous reading.
+            }
+        });
+        // The server closes the stream once the VM stops; don't let the
+        // browser's automatic reconnect spin against a 409. A status change
+        // re-runs this effect if the VM comes back.
+        source.onerror = () => source.close();
+        return () => source.close();
+    }, [vmId, running, intervalSecs]);
+
+    return stats;
+};
@@ -29,6 +29,8 @@ declare module "@my-own-web-services/react-components/lib/languages" {
                     stopping: string;
                     stopped: string;
                     failed: string;
+                    /** Paused by the supervisor's idle monitor; resumes on attach. */
+                    suspended: string;
                     exited: string;
                 };
                 /** Rename success toast — `{name}` placeholder for the new value (SLOP-11). */
@@ -47,6 +49,16 @@ declare module "@my-own-web-services/react-components/lib/languages" {
                     memory: string;
                     uptime: string;
                     baseImage: string;
+                    /** Live CPU use sub-line — `{percent}` of one host core. */
+                    cpuUsed: string;
+                    /** Live resident memory sub-line — `{size}` placeholder. */
+                    memoryUsed: string;
+                    /** Disk I/O cell: `{read}` / `{written}` since boot. */
+                    disk: string;
+                    diskValue: string;
+                    /** Network cell: `{sent}` / `{received}` through the egress proxy. */
+                    network: string;
+                    networkValue: string;
                     /** Placeholder when a stat value is missing/unknown. */
                     unknown: string;
                 };
@@ -68,6 +80,42 @@ declare module "@my-own-web-services/react-components/lib/languages" {
                     };
                 };
             };
+            /** Agent session recording page + player. */
+            recording: {
+                title: string;
+                loading: string;
+                loadFailed: string;
+                /** Shown when the recording has no output events. */
+                empty: string;
+                play: string;
+                pause: string;
+                restart: string;
+                speed: string;
+                /** Heading over the attach / detach / truncation markers. */
+                markers: string;
+            };
+            /** Audit log page (`GET /v1/audit`). */
+            audit: {
+                title: string;
+                /** Sidebar link to the page. */
+                link: string;
+                loading: string;
+                loadFailed: string;
+                empty: string;
+                loadMore: string;
+                export: string;
+                failuresOnly: string;
+                /** Column headers; `actor`, `action` and `target` double as
+                 *  filter placeholders. */
+                time: string;
+                actor: string;
+                source: string;
+                action: string;
+                target: string;
+                outcome: string;
+                /** Source of requests made over the unix socket. */
+                unixSocket: string;
+            };
         };
     }
 }
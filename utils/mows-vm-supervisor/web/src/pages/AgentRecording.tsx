@@ -0,0 +1,84 @@
+// Agent recording page — shown when a sidebar agent row is clicked.
+//
+// Fetches the agent row (for the header) and its session recording, then
+// hands the raw asciicast text to <RecordingPlayer>. A running agent's
+// recording is still growing; the page shows what was on disk when it
+// loaded, and an `agent_updated` event for this agent (e.g. it exited)
+// re-fetches — restarting playback — so the replay ends where the
+// session did.
+
+import { useMows } from "@my-own-web-services/react-components/lib/mowsContext/MowsContext";
+import { useEffect, useState, type ReactNode } from "react";
+import { Link, useParams } from "react-router-dom";
+import RecordingPlayer from "../components/RecordingPlayer";
+import { api, describeApiError, fetchAgentRecording } from "../lib/api";
+import { subscribeEvents } from "../lib/events";
+import type { AgentSummary } from "../api/generated/api-client";
+
+const AgentRecording = (): ReactNode => {
+    const { id } = useParams<{ id: string }>();
+    const t = useMows().t.supervisor.recording;
+    const [agent, setAgent] = useState<AgentSummary | null>(null);
+    const [cast, setCast] = useState<string | null>(null);
+    const [error, setError] = useState<string | null>(null);
+
+    useEffect(() => {
+        if (!id) return;
+        let alive = true;
+        const refetch = async () => {
+            try {
+                const [agentResponse, recording] = await Promise.all([
+                    api.v1.getAgent(id),
+                    fetchAgentRecording(id)
+                ]);
+                if (!alive) return;
+                setAgent(agentResponse.data);
+                setCast(recording);
+                setError(null);
+            } catch (error) {
+                if (!alive) return;
+                setError(await describeApiError(error));
+            }
+        };
+        refetch();
+        const unsubscribe = subscribeEvents((event) => {
+            if (event.type === "resync" || (event.type === "agent_updated" && event.id === id)) {
+                refetch();
+            }
+        });
+        return () => {
+            alive = false;
+            unsubscribe();
+        };
+    }, [id]);
+
+    if (error) {
+        return (
+            <div className="text-destructive p-6 text-sm">
+                {t.loadFailed} {error}
+            </div>
+        );
+    }
+    if (!agent || cast === null) {
+        return <div className="text-muted-foreground p-6 text-sm">{t.loading}</div>;
+    }
+
+    return (
+        <div className="flex h-full min-h-0 flex-col gap-3 p-6">
+            <div>
+                <h1 className="text-lg font-semibold">{agent.name}</h1>
+                <div className="text-muted-foreground text-xs">
+                    {t.title} · {agent.kind} · {agent.status} ·{" "}
+                    <Link to={`/vms/${agent.vm_id}`} className="underline">
+                        {agent.vm_id.slice(0, 8)}
+                    </Link>
+                </div>
+            </div>
+            <div className="min-h-0 flex-1">
+                <RecordingPlayer cast={cast} />
+            </div>
+        </div>
+    );
+};
+
+export default AgentRecording;
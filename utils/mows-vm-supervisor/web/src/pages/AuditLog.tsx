@@ -0,0 +1,159 @@
+// Audit log page — who did what on the supervisor, newest first.
+//
+// Filters re-query from the top; "Load more" follows the cursor the
+// supervisor hands back. "Export" downloads every matching entry as JSON
+// lines. Members only ever get their own entries back.
+
+import { Button } from "@my-own-web-services/react-components/components/ui/button";
+import { Input } from "@my-own-web-services/react-components/components/ui/input";
+import { useMows } from "@my-own-web-services/react-components/lib/mowsContext/MowsContext";
+import { useEffect, useState, type ReactNode } from "react";
+import { toast } from "sonner";
+import { AuditOutcome } from "../api/generated/api-client";
+import {
+    describeApiError,
+    exportAudit,
+    listAudit,
+    type AuditEntry,
+    type AuditFilter
+} from "../lib/api";
+
+const AuditLog = (): ReactNode => {
+    const t = useMows().t.supervisor.audit;
+    const [filter, setFilter] = useState<AuditFilter>({});
+    const [entries, setEntries] = useState<AuditEntry[] | null>(null);
+    const [cursor, setCursor] = useState<number | null>(null);
+    const [error, setError] = useState<string | null>(null);
+
+    const load = async (from?: number) => {
+        try {
+            const page = await listAudit(filter, from);
+            setEntries((prev) => (from === undefined ? page.entries : [...(prev ?? []), ...page.entries]));
+            setCursor(page.next_cursor ?? null);
+            setError(null);
+        } catch (error) {
+            setError(await describeApiError(error));
+        }
+    };
+
+    useEffect(() => {
+        load();
+        // Re-query from the top whenever a filter changes.
+        // eslint-disable-next-line react-hooks/exhaustive-deps
+    }, [filter]);
+
+    const download = async () => {
+        try {
+            const blob = await exportAudit(filter);
+            const url = URL.createObjectURL(blob);
+            const link = document.createElement("a");
+            link.href = url;
+            link.download = "mows-audit.jsonl";
+            link.click();
+            URL.revokeObjectURL(url);
+        } catch (error) {
+            toast.error(await describeApiError(error));
+        }
+    };
+
+    const setField = (key: "actor" | "action" | "target", value: string) =>
+        setFilter((prev) => ({ ...prev, [key]: value.trim() || undefined }));
+
+    return (
+        <div className="flex flex-col gap-4 p-6">
+            <div className="flex items-center justify-between gap-4">
+                <h1 className="text-lg font-semibold">{t.title}</h1>
+                <Button type="button" variant="outline" size="sm" onClick={download}>
+                    {t.export}
+                </Button>
+            </div>
+            <div className="flex flex-wrap items-center gap-2">
+                <Input
+                    className="w-40"
+                    placeholder={t.actor}
+                    onChange={(e) => setField("actor", e.target.value)}
+                />
+                <Input
+                    className="w-40"
+                    placeholder={t.action}
+                    onChange={(e) => setField("action", e.target.value)}
+                />
+                <Input
+                    className="w-72"
+                    placeholder={t.target}
+                    onChange={(e) => setField("target", e.target.value)}
+                />
+                <Button
+                    type="button"
+                    variant={filter.outcome === AuditOutcome.Failure ? "default" : "outline"}
+                    size="sm"
+                    onClick={() =>
+                        setFilter((prev) => ({
+                            ...prev,
+                            outcome:
+                                prev.outcome === AuditOutcome.Failure
+                                    ? undefined
+                                    : AuditOutcome.Failure
+                        }))
+                    }
+                >
+                    {t.failuresOnly}
+                </Button>
+            </div>
+            {error ? (
+                <div className="text-destructive text-sm">
+                    {t.loadFailed} {error}
+                </div>
+            ) : entries === null ? (
+                <div className="text-muted-foreground text-sm">{t.loading}</div>
+            ) : entries.length === 0 ? (
+                <div className="text-muted-foreground text-sm italic">{t.empty}</div>
+            ) : (
+                <table className="w-full text-left text-xs">
+                    <thead className="text-muted-foreground uppercase tracking-wider">
+                        <tr>
+                            <th className="py-1 pr-3 font-normal">{t.time}</th>
+                            <th className="py-1 pr-3 font-normal">{t.actor}</th>
+                            <th className="py-1 pr-3 font-normal">{t.source}</th>
+                            <th className="py-1 pr-3 font-normal">{t.action}</th>
+                            <th className="py-1 pr-3 font-normal">{t.target}</th>
+                            <th className="py-1 font-normal">{t.outcome}</th>
+                        </tr>
+                    </thead>
+                    <tbody className="font-mono">
+                        {entries.map((entry) => (
+                            <tr key={entry.id} className="border-border border-t">
+                                <td className="py-1 pr-3 tabular-nums">
+                                    {new Date(entry.at).toLocaleString()}
+                                </td>
+                                <td className="py-1 pr-3">{entry.actor}</td>
+                                <td className="py-1 pr-3">{entry.source_ip ?? t.unixSocket}</td>
+                                <td className="py-1 pr-3">{entry.action}</td>
+                                <td className="py-1 pr-3">{entry.target ?? "—"}</td>
+                                <td
+                                    className={`py-1 ${entry.outcome === AuditOutcome.Failure ? "text-destructive" : ""}`}
+                                >
+                                    {entry.outcome}
+                                    {entry.status_code ? ` (${entry.status_code})` : ""}
+                                </td>
+                            </tr>
+                        ))}
+                    </tbody>
+                </table>
+            )}
+            {cursor !== null && (
+                <Button
+                    type="button"
+                    variant="ghost"
+                    size="sm"
+                    className="self-start"
+                    onClick={() => load(cursor)}
+                >
+                    {t.loadMore}
+                </Button>
+            )}
+        </div>
+    );
+};
+
+export default AuditLog;
@@ -20,7 +20,9 @@
 // Fetches `/v1/vms/{id}` once on mount and re-fetches on every matching
 // supervisor event (`vm_updated` / `vm_deleted` for this VM, plus the
 // synthetic `resync` after a WS reconnect). A separate 1 s ticker keeps
-// the relative-time + uptime stat fresh between data refreshes.
+// the relative-time + uptime stat fresh between data refreshes. While the
+// VM runs, `/v1/vms/{id}/stats` streams live CPU / memory / disk / network
+// readings into the same stat row.
 
 import {
     Card,
@@ -36,7 +38,9 @@ import {
     Clock,
     Cpu,
     HardDrive,
+    HardDriveDownload,
     MemoryStick,
+    Network,
     Server,
     TerminalSquare
 } from "lucide-react";
@@ -52,10 +56,12 @@ import { toast } from "sonner";
 import { api, describeApiError, renameVm } from "../lib/api";
 import { subscribeEvents } from "../lib/events";
 import {
+    formatByteCount,
     formatBytes,
     formatDuration,
     formatRelative
 } from "../lib/format";
+import { useVmStats } from "../lib/stats";
 import type { VmSummary } from "../api/generated/api-client";
 
 // Visual treatment per status. Labels themselves come from translations
@@ -68,6 +74,7 @@ const STATUS_STYLE: Record<string, { dot: string; text: string }> = {
     stopping: { dot: "bg-amber-500 animate-pulse", text: "text-amber-500" },
     stopped: { dot: "bg-muted-foreground/40", text: "text-muted-foreground" },
     failed: { dot: "bg-red-500", text: "text-red-500" },
+    suspended: { dot: "bg-sky-500", text: "text-sky-500" },
     exited: { dot: "bg-muted-foreground/40", text: "text-muted-foreground" }
 };
 
@@ -206,6 +213,7 @@ const VmDetail = () => {
     // above the early returns below or the hook count changes between
     // the pre-data and post-data renders.
     const vmId = vm?.id ?? null;
+    const liveStats = useVmStats(vmId, vm?.status === "running");
     const consoleTypes = useMemo<readonly ConsoleType[]>(
         () =>
             vmId
@@ -318,6 +326,14 @@ const VmDetail = () => {
                                     ? `${vm.cpus} ${mowsContext.t.supervisor.vmDetail.stat.vcpuSuffix}`
                                     : mowsContext.t.supervisor.vmDetail.stat.unknown
                             }
+                            sub={
+                                liveStats?.cpu_percent != null
+                                    ? mowsContext.t.supervisor.vmDetail.stat.cpuUsed.replace(
+                                          "{percent}",
+                                          liveStats.cpu_percent.toFixed(0)
+                                      )
+                                    : undefined
+                            }
                         />
                         <Stat
                             icon={MemoryStick}
@@ -327,6 +343,14 @@ const VmDetail = () => {
                                     ? formatBytes(vm.memory_mb)
                                     : mowsContext.t.supervisor.vmDetail.stat.unknown
                             }
+                            sub={
+                                liveStats?.memory_rss_bytes != null
+                                    ? mowsContext.t.supervisor.vmDetail.stat.memoryUsed.replace(
+                                          "{size}",
+                                          formatByteCount(liveStats.memory_rss_bytes)
+                                      )
+                                    : undefined
+                            }
                         />
                         <Stat
                             icon={Clock}
@@ -343,6 +367,34 @@ const VmDetail = () => {
                                 </span>
                             }
                         />
+                        {liveStats && (
+                            <Stat
+                                icon={HardDriveDownload}
+                                label={mowsContext.t.supervisor.vmDetail.stat.disk}
+                                value={
+                                    liveStats.disk_read_bytes != null &&
+                                    liveStats.disk_written_bytes != null
+                                        ? mowsContext.t.supervisor.vmDetail.stat.diskValue
+                                              .replace("{read}", formatByteCount(liveStats.disk_read_bytes))
+                                              .replace("{written}", formatByteCount(liveStats.disk_written_bytes))
+                                        : mowsContext.t.supervisor.vmDetail.stat.unknown
+                                }
+                            />
+                        )}
+                        {liveStats && (
+                            <Stat
+                                icon={Network}
+                                label={mowsContext.t.supervisor.vmDetail.stat.network}
+                                value={
+                                    liveStats.net_sent_bytes != null &&
+                                    liveStats.net_received_bytes != null
+                                        ? mowsContext.t.supervisor.vmDetail.stat.networkValue
+                                              .replace("{sent}", formatByteCount(liveStats.net_sent_bytes))
+                                              .replace("{received}", formatByteCount(liveStats.net_received_bytes))
+                                        : mowsContext.t.supervisor.vmDetail.stat.unknown
+                                }
+                            />
+                        )}
                     </div>
                 </CardContent>
             </Card>